@@ -0,0 +1,77 @@
+//! Vault load and save at scale. Run with `cargo bench`; criterion keeps the
+//! previous results in target/criterion and reports regressions against them.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use remote_desktop_manager::client::{Client, Contact};
+use remote_desktop_manager::encryption::{decrypt, encrypt, generate_salt, KEY_SIZE};
+use remote_desktop_manager::repository::{ClientRepository, EncryptedFileRepository};
+use remote_desktop_manager::vault::{parse_vault, serialize_vault, VaultPolicy};
+
+const CLIENTS: usize = 10_000;
+const KEY: [u8; KEY_SIZE] = [42; KEY_SIZE];
+
+/// Clients with every text field filled, plus a few kilobytes of free text
+/// in `reference` to stand in for long notes.
+fn sample_clients() -> Vec<Client> {
+    let long_text = "Lorem ipsum dolor sit amet, consectetur adipiscing elit. ".repeat(64);
+    (0..CLIENTS)
+        .map(|i| Client {
+            name: format!("server-{:05}", i),
+            ip: format!("10.{}.{}.{}", i / 65536, (i / 256) % 256, i % 256),
+            password: format!("correct-horse-{}", i),
+            reference: if i % 10 == 0 { long_text.clone() } else { format!("ASSET-{}", i) },
+            owner: Contact {
+                name: "Operations".to_string(),
+                email: "ops@example.com".to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .collect()
+}
+
+fn vault_benchmarks(c: &mut Criterion) {
+    let clients = sample_clients();
+    let policy = VaultPolicy::default();
+    let serialized = serialize_vault(&policy, &clients, &[], &[], &[], &Default::default());
+    let encrypted = encrypt(&serialized, &KEY).unwrap();
+
+    let mut group = c.benchmark_group("vault_10k");
+    group.sample_size(20);
+    group.bench_function("serialize", |b| b.iter(|| serialize_vault(black_box(&policy), black_box(&clients), &[], &[], &[], &Default::default())));
+    group.bench_function("parse", |b| b.iter(|| parse_vault(black_box(&serialized)).unwrap()));
+    group.bench_function("encrypt", |b| b.iter(|| encrypt(black_box(&serialized), &KEY).unwrap()));
+    group.bench_function("decrypt", |b| b.iter(|| decrypt(black_box(&encrypted), &KEY).unwrap()));
+
+    let path = std::env::temp_dir().join("remote_desktop_manager-bench.vault");
+    let mut repository = EncryptedFileRepository::new(&path);
+    repository.create(KEY, generate_salt());
+    repository.replace_all(clients.clone());
+    group.bench_function("save", |b| b.iter(|| repository.save().unwrap()));
+    group.bench_function("open", |b| {
+        b.iter_batched(|| EncryptedFileRepository::new(&path), |mut repository| repository.open(KEY).unwrap(), BatchSize::SmallInput)
+    });
+
+    let database_path = std::env::temp_dir().join("remote_desktop_manager-bench.sqlite");
+    let _ = std::fs::remove_file(&database_path);
+    let mut database = EncryptedFileRepository::new(&database_path);
+    database.create(KEY, generate_salt());
+    database.replace_all(clients.clone());
+    database.use_database(true).unwrap();
+    database.save().unwrap();
+    group.bench_function("save one change (SQLite)", |b| {
+        b.iter(|| {
+            database.update(clients[0].clone());
+            database.save().unwrap()
+        })
+    });
+    group.bench_function("open (SQLite)", |b| {
+        b.iter_batched(|| EncryptedFileRepository::new(&database_path), |mut repository| repository.open(KEY).unwrap(), BatchSize::SmallInput)
+    });
+    group.finish();
+    let _ = std::fs::remove_file(path);
+    let _ = std::fs::remove_file(database_path);
+}
+
+criterion_group!(benches, vault_benchmarks);
+criterion_main!(benches);
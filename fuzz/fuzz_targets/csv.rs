@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use remote_desktop_manager::transfer::{decode_text, merge_rows, parse_csv};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(rows) = parse_csv(&decode_text(data)) {
+        let mut clients = Vec::new();
+        merge_rows(&mut clients, rows);
+    }
+});
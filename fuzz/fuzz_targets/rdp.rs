@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use remote_desktop_manager::transfer::{decode_text, parse_rdp};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = parse_rdp(&decode_text(data), "fuzz");
+});
@@ -0,0 +1,18 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use remote_desktop_manager::encryption::{decrypt, encrypt, KEY_SIZE};
+use remote_desktop_manager::vault::parse_vault;
+
+const KEY: [u8; KEY_SIZE] = [7; KEY_SIZE];
+
+fuzz_target!(|data: &[u8]| {
+    // Tampered ciphertext must be rejected, never panic.
+    let _ = decrypt(data, &KEY);
+    // Whatever decrypts correctly goes to the parser, as on unlock.
+    let _ = parse_vault(data);
+    if let Ok(encrypted) = encrypt(data, &KEY) {
+        let decrypted = decrypt(&encrypted, &KEY).expect("round trip");
+        assert_eq!(decrypted, data);
+    }
+});
@@ -1,272 +1,2218 @@
 use eframe::egui;
-use crate::client::{Client, AppMode};
-use crate::encryption::{decrypt, encrypt, KEY_SIZE};
+use crate::bundle::{export_bundle, export_encrypted_bundle, import_bundle};
+use crate::clipboard_guard::{self, detect_clipboard_history};
+use crate::cmdkey;
+use crate::diagnosis::{self, Failure, LaunchError};
+use crate::duplicates::{resolve, split_conflicts, ImportConflict};
+use crate::attachment::Attachment;
+use crate::client::{Client, AppMode, Contact, KeyStatus, Protocol};
+use crate::expiry::{today, trash_expired};
+use crate::launcher::{Launcher, Process, RdpViewer};
+use crate::link::{matches_address, ConnectionLink};
+use crate::mstsc_history::{read_history, HistoryEntry};
+use crate::monitoring::{self, status_index, HostStatus, MonitoringSystem};
+use crate::netbox::{merge_objects, pushed_tags, NetboxClient, NetboxObject};
+use crate::events::{AppEvent, EventBus};
+use crate::encryption::{derive_key, generate_salt, open_setting, seal_setting, KEY_SIZE};
+use crate::handover::{self, DownHost, ReportFormat};
+use crate::hello::{self, QuickUnlock};
+use crate::hosts_file::{self, HostsEntry};
+use crate::history::{self, ConnectionRecord};
+use crate::inventory::{duplicate_names, export_inventory, InventoryFormat};
+use crate::ipc::RemoteCommand;
+use crate::os_events::OsEvent;
+use crate::presence::{self, LanPresence, PeerSession};
+use crate::rdg::parse_rdg;
+use crate::fingerprint::{fingerprint_all, has_os_tag, Candidate, OsGuess};
+use crate::reachability::{probe_all, Target};
+use crate::rdp;
+use crate::search::SearchIndex;
+use crate::secure_prompt;
+use crate::session_window;
+use crate::sessions::{ActiveSession, SessionTracker};
+use crate::ssh;
+use crate::storage;
+use crate::taskbar;
+use crate::vnc;
+use crate::credential::{self, Credential};
+use crate::keycache;
+use crate::layout::{self, Layout, View};
+use crate::views::client_list::{filter_id, ClientList};
+use crate::views::connect_failure::ConnectFailure;
+use crate::views::dialogs::{ExportFormat, TransferDialog};
+use crate::views::editor_panel::ClientForm;
+use crate::views::history_view::HistoryFilter;
+use crate::views::composition_view::CompositionReport;
+use crate::views::group_connect::GroupConnect;
+use crate::views::help_view::HelpWindow;
+use crate::views::pinned_note::NotePrompt;
+use crate::views::rotation_view::RotationDialog;
+use crate::views::menu_bar::MenuAction;
+use crate::views::settings_view::SettingsTab;
+use crate::views::unlock_view::{Derived, PendingUnlock, UnlockForm};
+use crate::repository::{ClientRepository, DerivedKeys, EncryptedFileRepository, RepositoryEvent, INCORRECT_KEY};
+use crate::settings::{DefaultPorts, KeyCaching, RdpOptions, Settings, Theme, UrlTemplate};
+use crate::shortcuts::{self, Shortcut};
+use crate::strength::estimate_bits;
+use crate::transfer::{decode_text, export_csv, merge_clients, parse_csv, read_import_file, rows_to_clients, ImportSummary, ImportUndo};
+use crate::watch::{archive, FolderWatch, WATCH_INTERVAL};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
-use std::process::Command;
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
 use arboard::Clipboard;
+use chrono::{Local, NaiveDateTime, TimeZone};
+use zeroize::{Zeroize, Zeroizing};
+
+const APP_TITLE: &str = "Remote Desktop Manager";
+pub(crate) const HANDOVER_TIME: &str = "%Y-%m-%d %H:%M";
 
 pub struct AppState {
-    pub clients: Vec<Client>,
-    pub selected_client: Option<usize>,
-    pub new_client_name: String,
-    pub new_client_ip: String,
-    pub new_client_password: String,
+    pub repository: EncryptedFileRepository,
+    repository_events: Receiver<RepositoryEvent>,
+    pub client_list: ClientList,
+    pub form: ClientForm,
     pub mode: AppMode,
-    pub show_password: bool,
     pub error_message: Option<String>,
-    pub encryption_key: [u8; KEY_SIZE],
+    pub info_message: Option<String>,
+    pub transfer: TransferDialog,
+    pub settings: Settings,
+    pub watch_folder_input: String,
+    pub vault_path_input: String,
+    pub ssh_client_input: String,
+    pub vnc_viewer_input: String,
+    pub default_ports_input: DefaultPorts,
+    pub vault_backups_input: u32,
+    /// Keep the open vault in a SQLite database.
+    pub vault_database_input: bool,
+    /// Tags that ask before connecting, as typed, separated by commas.
+    pub confirm_tags_input: String,
+    pub dry_run_input: bool,
+    pub ask_for_pinned_note_input: bool,
+    pub rdp_options_input: RdpOptions,
+    pub check_rdp_sign_in_input: bool,
+    pub theme_input: Theme,
+    pub accent_input: Option<[u8; 3]>,
+    pub shortcut_inputs: Vec<(Shortcut, String)>,
+    /// Reported by the window; `None` where the platform does not tell.
+    pub system_theme: Option<eframe::Theme>,
+    pub settings_tab: SettingsTab,
+    pub url_templates_input: Vec<UrlTemplate>,
+    /// The group whose contact is being edited, and the contact as typed.
+    pub group_contact_input: (String, Contact),
+    pub netbox_url_input: String,
+    /// A newly entered NetBox token; empty keeps the stored one.
+    pub netbox_token_input: String,
+    /// A NetBox pull or push is running.
+    pub netbox_busy: bool,
+    pub monitoring_system_input: MonitoringSystem,
+    pub monitoring_url_input: String,
+    /// A newly entered monitoring token; empty keeps the stored one.
+    pub monitoring_token_input: String,
+    pub monitoring_refresh_input: u32,
+    /// Host status from the monitoring system by lowercase address.
+    pub monitoring_status: HashMap<String, HostStatus>,
+    pub last_monitoring_poll: Option<Instant>,
+    pub monitoring_busy: bool,
+    /// Why the last poll failed; cleared by the next one that succeeds.
+    pub monitoring_error: Option<String>,
+    /// Handshake time of each probed client by id; `None` when it did not
+    /// answer.
+    pub reachability: HashMap<String, Option<Duration>>,
+    /// When each client last answered a probe, since the vault was unlocked.
+    pub last_seen: HashMap<String, chrono::DateTime<chrono::Utc>>,
+    pub last_reachability_check: Option<Instant>,
+    pub reachability_busy: bool,
+    /// Clients whose host was fingerprinted since the vault was unlocked,
+    /// whether the guess told anything or not.
+    pub fingerprinted: HashSet<String>,
+    pub fingerprint_busy: bool,
+    /// File the list of down hosts is exported to.
+    pub down_hosts_path_input: String,
+    /// Running while LAN presence is turned on.
+    pub presence: Option<LanPresence>,
+    pub history_filter: HistoryFilter,
+    /// Handover report range as local `YYYY-MM-DD HH:MM` times.
+    pub report_from_input: String,
+    pub report_to_input: String,
+    pub report_format_input: ReportFormat,
+    pub inventory_format_input: InventoryFormat,
+    /// Every inventory field with the name it is exported as.
+    pub inventory_field_names_input: Vec<(&'static str, String)>,
+    pub last_watch_scan: Instant,
+    pub folder_watch: FolderWatch,
+    pub notification: Option<String>,
+    pub import_undo: Option<ImportUndo>,
+    /// Imported clients that look like stored ones, waiting for a choice.
+    pub import_conflicts: Vec<ImportConflict>,
+    /// Ids of the stored clients Tools → Find Duplicates groups together.
+    pub duplicate_groups: Vec<Vec<String>>,
+    /// Where the layout of each vault is kept.
+    pub layouts_path: PathBuf,
+    /// Where vault keys wrapped for unlocking without the master key are kept.
+    pub wrapped_keys_path: PathBuf,
+    /// Where the certificates of the RDP servers the sign-in check met are
+    /// pinned.
+    pub server_certificates_path: PathBuf,
+    /// Layout of the vault just unlocked, applied on the next frame.
+    pub pending_layout: Option<Layout>,
+    /// Inner size and outer position of the window in the last frame.
+    pub window_size: Option<egui::Vec2>,
+    pub window_position: Option<egui::Pos2>,
+    /// The hosts file Tools → Hosts File edits, and the entries it owns there.
+    pub hosts_path: PathBuf,
+    pub hosts_entries: Vec<HostsEntry>,
+    /// The credential being added or edited in Tools → Credentials.
+    pub credential_form: Option<Credential>,
+    pub rotation: RotationDialog,
+    pub group_connect: GroupConnect,
+    pub composition: CompositionReport,
+    pub help: HelpWindow,
+    /// Clients whose session ended, waiting to be asked for a handoff note.
+    pub note_prompt: NotePrompt,
+    pub hosts_name_input: String,
+    pub hosts_address_input: String,
+    /// Hosts offered by the mstsc history import and whether each is ticked.
+    pub history_import: Vec<(HistoryEntry, bool)>,
+    pub pending_link: Option<ConnectionLink>,
+    /// `--connect`/`--search` from the command line or a later launch, run
+    /// once the vault is unlocked.
+    pub pending_command: Option<RemoteCommand>,
+    pub remote_commands: Option<Receiver<RemoteCommand>>,
+    pub thumbnails: HashMap<String, egui::TextureHandle>,
+    pub viewing_attachment: Option<Attachment>,
+    pub search_index: SearchIndex,
+    pub search_query: String,
+    pub last_expiry_check: Option<Instant>,
+    pub trash_after_days_input: u32,
+    pub key_status: KeyStatus,
+    pub unlock_form: UnlockForm,
+    /// Key derivation running for the Unlock or Create button, or the
+    /// Windows unlock.
+    pub pending_unlock: Option<PendingUnlock>,
+    /// The Windows Hello prompt confirming the user before the key is
+    /// stored for it.
+    pub pending_hello_enrollment: Option<Receiver<Result<bool, String>>>,
+    pub os_events: Option<Receiver<OsEvent>>,
+    pub events: EventBus,
+    event_receiver: Receiver<AppEvent>,
+    pub sessions: SessionTracker,
+    /// Starts viewers, or only pretends to with `--dry-run`.
+    pub launcher: Launcher,
+    pub last_saved: Option<chrono::DateTime<chrono::Local>>,
+    /// Sessions that ended while the vault was locked, as client id, start
+    /// and end; written to the history on the next unlock.
+    pub pending_session_ends: Vec<(String, chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>)>,
+    /// Key kept across a lock when the caching policy allows it.
+    pub retained_key: Option<[u8; KEY_SIZE]>,
+    pub weak_master_key: bool,
+    pub last_activity: Instant,
+    /// Clipboard history tools found at the last check, refreshed every minute.
+    pub clipboard_history: Option<(Instant, Vec<String>)>,
+    /// The app itself runs inside a Remote Desktop session, checked at
+    /// startup. Passwords are then kept off the clipboard where possible.
+    pub remote_session: bool,
+    /// Client waiting for the user to answer the clipboard history warning.
+    pub pending_connect: Option<Client>,
+    /// Client whose tag asks for confirmation before connecting, as it is
+    /// to be connected to.
+    pub confirm_connect: Option<Client>,
+    /// The last connect that failed, shown with hints until dismissed.
+    pub connect_failure: Option<ConnectFailure>,
 }
 
 impl AppState {
     pub fn new() -> Self {
-        let encryption_key = [0; KEY_SIZE]; // Use a static key or generate it
-        let clients = if let Ok(data) = fs::read("clients.json") {
-            if let Ok(decrypted_data) = decrypt(&data, &encryption_key) {
-                serde_json::from_slice(&decrypted_data).unwrap_or_default()
-            } else {
-                Vec::new()
+        let migrated = storage::migrate_local_files();
+        let settings = Settings::load();
+        let vault = storage::vault_path(&settings.vault_path);
+        let mut state = Self::with_repository(EncryptedFileRepository::new(vault), settings);
+        match migrated {
+            Ok(moved) if !moved.is_empty() => {
+                let names: Vec<String> = moved.iter().filter_map(|path| path.file_name()).map(|name| name.to_string_lossy().into_owned()).collect();
+                state.notification = Some(format!("Moved {} to {}", names.join(", "), storage::config_dir().display()));
             }
-        } else {
-            Vec::new()
-        };
+            Ok(_) => {}
+            Err(e) => state.error_message = Some(e),
+        }
+        state.unlock_from_key_cache();
+        state
+    }
+
+    /// Opens a vault other than the default one, e.g. a file double-clicked
+    /// in Explorer.
+    pub fn with_vault_file(path: PathBuf) -> Self {
+        let mut state = Self::with_repository(EncryptedFileRepository::new(path), Settings::load());
+        state.unlock_from_key_cache();
+        state
+    }
+
+    /// Opens the locked vault with the key cached for it this OS session,
+    /// when the caching policy keeps one.
+    fn unlock_from_key_cache(&mut self) {
+        if self.settings.key_caching != KeyCaching::RememberForSession || !matches!(self.key_status, KeyStatus::Locked) {
+            return;
+        }
+        if let Some(key) = keycache::load(self.repository.path()) {
+            if self.unlock_with_key(key).is_err() {
+                keycache::clear(self.repository.path());
+            }
+        }
+    }
+
+    pub fn with_repository(mut repository: EncryptedFileRepository, settings: Settings) -> Self {
+        let repository_events = repository.subscribe();
+        repository.backups = settings.vault_backups;
+        let events = EventBus::default();
         Self {
-            clients,
-            selected_client: None,
-            new_client_name: String::new(),
-            new_client_ip: String::new(),
-            new_client_password: String::new(),
+            key_status: if repository.exists() { KeyStatus::Locked } else { KeyStatus::NotSet },
+            repository,
+            repository_events,
+            client_list: ClientList::default(),
+            form: ClientForm::default(),
             mode: AppMode::Normal,
-            show_password: false,
             error_message: None,
-            encryption_key,
+            info_message: None,
+            transfer: TransferDialog::default(),
+            watch_folder_input: settings.watch_folder.clone(),
+            vault_path_input: String::new(),
+            ssh_client_input: String::new(),
+            vnc_viewer_input: String::new(),
+            default_ports_input: DefaultPorts::default(),
+            vault_backups_input: 0,
+            vault_database_input: false,
+            confirm_tags_input: String::new(),
+            dry_run_input: false,
+            ask_for_pinned_note_input: true,
+            rdp_options_input: RdpOptions::default(),
+            check_rdp_sign_in_input: false,
+            theme_input: Theme::System,
+            accent_input: None,
+            shortcut_inputs: Vec::new(),
+            system_theme: None,
+            settings_tab: SettingsTab::default(),
+            launcher: if settings.dry_run { Launcher::DryRun } else { Launcher::System },
+            url_templates_input: Vec::new(),
+            group_contact_input: (String::new(), Contact::default()),
+            netbox_url_input: String::new(),
+            netbox_token_input: String::new(),
+            netbox_busy: false,
+            monitoring_system_input: MonitoringSystem::default(),
+            monitoring_url_input: String::new(),
+            monitoring_token_input: String::new(),
+            monitoring_refresh_input: 2,
+            monitoring_status: HashMap::new(),
+            last_monitoring_poll: None,
+            monitoring_busy: false,
+            monitoring_error: None,
+            reachability: HashMap::new(),
+            last_seen: HashMap::new(),
+            last_reachability_check: None,
+            reachability_busy: false,
+            fingerprinted: HashSet::new(),
+            fingerprint_busy: false,
+            down_hosts_path_input: "down-hosts.csv".to_string(),
+            presence: None,
+            history_filter: HistoryFilter::default(),
+            report_from_input: String::new(),
+            report_to_input: String::new(),
+            report_format_input: ReportFormat::default(),
+            inventory_format_input: InventoryFormat::default(),
+            inventory_field_names_input: Vec::new(),
+            settings,
+            last_watch_scan: Instant::now(),
+            folder_watch: FolderWatch::default(),
+            notification: None,
+            import_undo: None,
+            import_conflicts: Vec::new(),
+            duplicate_groups: Vec::new(),
+            layouts_path: storage::layouts_path(),
+            wrapped_keys_path: storage::wrapped_keys_path(),
+            server_certificates_path: storage::server_certificates_path(),
+            pending_layout: None,
+            window_size: None,
+            window_position: None,
+            hosts_path: hosts_file::system_path(),
+            hosts_entries: Vec::new(),
+            credential_form: None,
+            rotation: RotationDialog::default(),
+            group_connect: GroupConnect::default(),
+            composition: CompositionReport::default(),
+            help: HelpWindow::default(),
+            note_prompt: NotePrompt::default(),
+            hosts_name_input: String::new(),
+            hosts_address_input: String::new(),
+            history_import: Vec::new(),
+            pending_link: None,
+            pending_command: None,
+            remote_commands: None,
+            thumbnails: HashMap::new(),
+            viewing_attachment: None,
+            search_index: SearchIndex::default(),
+            search_query: String::new(),
+            last_expiry_check: None,
+            trash_after_days_input: 7,
+            unlock_form: UnlockForm::default(),
+            pending_unlock: None,
+            pending_hello_enrollment: None,
+            os_events: None,
+            event_receiver: events.subscribe(),
+            sessions: SessionTracker::new(events.clone()),
+            events,
+            last_saved: None,
+            pending_session_ends: Vec::new(),
+            retained_key: None,
+            weak_master_key: false,
+            last_activity: Instant::now(),
+            clipboard_history: None,
+            remote_session: clipboard_guard::in_remote_session(),
+            pending_connect: None,
+            confirm_connect: None,
+            connect_failure: None,
         }
     }
 
-    pub fn save_clients(&self) {
-        let data = serde_json::to_vec(&self.clients).unwrap();
-        if let Ok(encrypted_data) = encrypt(&data, &self.encryption_key) {
-            fs::write("clients.json", encrypted_data).unwrap();
+    /// A state whose vault does not exist yet, at a fresh temp path.
+    #[cfg(test)]
+    pub fn for_test() -> Self {
+        let path = std::env::temp_dir().join(format!("rdm-test-{}.json", crate::client::new_client_id()));
+        Self::for_test_with_repository(EncryptedFileRepository::new(path))
+    }
+
+    /// A state for `repository` that keeps no backups and writes its other
+    /// files next to the vault rather than to the config directory.
+    #[cfg(test)]
+    pub fn for_test_with_repository(repository: EncryptedFileRepository) -> Self {
+        let path = repository.path().to_path_buf();
+        let settings = Settings { vault_backups: 0, ..Default::default() };
+        let mut state = Self::with_repository(repository, settings);
+        state.layouts_path = path.with_extension("layouts.json");
+        state.wrapped_keys_path = path.with_extension("wrapped_keys.json");
+        state.server_certificates_path = path.with_extension("server_certificates.json");
+        state
+    }
+
+    /// `unlock`, waiting for the key to be derived.
+    #[cfg(test)]
+    pub fn unlock_and_wait(&mut self) {
+        self.unlock();
+        self.wait_for_unlock();
+    }
+
+    /// Polls the running unlock until it finished.
+    #[cfg(test)]
+    pub fn wait_for_unlock(&mut self) {
+        while self.pending_unlock.is_some() {
+            thread::sleep(Duration::from_millis(5));
+            self.poll_unlock();
         }
     }
 
-    pub fn connect_to_client(&self, client: &Client) {
-        let mut clipboard = Clipboard::new().unwrap();
-        clipboard.set_text(&client.password).unwrap();
+    /// Like `for_test`, with an empty vault already unlocked.
+    #[cfg(test)]
+    pub fn unlocked_for_test() -> Self {
+        let mut state = Self::for_test();
+        state.repository.create([7; KEY_SIZE], [3; crate::encryption::SALT_SIZE]);
+        state.key_status = KeyStatus::Unlocked;
+        state
+    }
+
+    /// File name of the open vault when it is not the default one.
+    pub fn vault_name(&self) -> Option<String> {
+        let path = self.repository.path();
+        (path != storage::vault_path(&self.settings.vault_path)).then(|| path.file_name().unwrap_or(path.as_os_str()).to_string_lossy().into_owned())
+    }
 
-        Command::new("mstsc")
-            .arg("/v")
-            .arg(&client.ip)
-            .arg("/prompt")
-            .spawn()
-            .expect("Failed to launch Remote Desktop");
+    pub fn is_unlocked(&self) -> bool {
+        matches!(self.key_status, KeyStatus::Unlocked)
     }
 
-    pub fn clear_new_client_fields(&mut self) {
-        self.new_client_name.clear();
-        self.new_client_ip.clear();
-        self.new_client_password.clear();
+    /// Weakest new master key accepted: the user's setting, raised by the
+    /// open vault's policy.
+    pub fn min_master_key_bits(&self) -> u32 {
+        self.settings.min_master_key_bits.max(self.repository.policy.min_master_key_bits)
     }
 
-    pub fn load_selected_client(&mut self) {
-        if let Some(index) = self.selected_client {
-            if index < self.clients.len() {
-                let client = &self.clients[index];
-                self.new_client_name = client.name.clone();
-                self.new_client_ip = client.ip.clone();
-                self.new_client_password = client.password.clone();
+    /// Whether secrets are entered in the Windows credential prompt on the
+    /// secure desktop rather than in the window.
+    pub fn secure_prompt(&self) -> bool {
+        self.settings.secure_desktop_prompt && secure_prompt::AVAILABLE
+    }
+
+    /// Saves the vault location typed into the Settings dialog. The open
+    /// default vault moves along when nothing is at the new place yet;
+    /// otherwise the file there is opened from the next start.
+    pub fn change_vault_location(&mut self) -> Result<String, String> {
+        let current = storage::vault_path(&self.settings.vault_path);
+        let target = storage::vault_path(&self.vault_path_input);
+        let moved = self.repository.path() == current && target != current && !target.exists();
+        if moved {
+            self.repository.relocate(target.clone())?;
+        }
+        let previous = std::mem::replace(&mut self.settings.vault_path, self.vault_path_input.trim().to_string());
+        if let Err(e) = self.settings.save() {
+            self.settings.vault_path = previous;
+            if moved {
+                self.repository.relocate(current)?;
             }
+            return Err(format!("Could not save settings: {}", e));
         }
+        Ok(if moved {
+            format!("Moved the vault to {}.", target.display())
+        } else {
+            format!("{} is opened from the next start.", target.display())
+        })
     }
-}
 
-impl eframe::App for AppState {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
-            egui::menu::bar(ui, |ui| {
-                ui.menu_button("File", |ui| {
-                    if ui.button("New").clicked() {
-                        self.mode = AppMode::Adding;
-                        self.clear_new_client_fields();
-                        ui.close_menu();
-                    }
-                    if ui.button("Edit").clicked() {
-                        if self.selected_client.is_some() {
-                            self.mode = AppMode::Editing;
-                            self.load_selected_client();
-                            ui.close_menu();
-                        } else {
-                            self.error_message = Some("Please select a target to edit.".to_string());
-                        }
-                    }
-                    if ui.button("Remove").clicked() {
-                        if self.selected_client.is_some() {
-                            self.mode = AppMode::Removing;
-                            ui.close_menu();
-                        } else {
-                            self.error_message = Some("Please select a target to remove.".to_string());
-                        }
-                    }
-                    if ui.button("Exit").clicked() {
-                        std::process::exit(0);
-                    }
-                });
-                ui.menu_button("Help", |ui| {
-                    if ui.button("About").clicked() {
-                        self.mode = AppMode::About;
-                        ui.close_menu();
-                    }
-                });
-            });
+    /// Opens the vault at `path` in place of the locked one until the app
+    /// exits; a path with no vault yet leads to creating one there.
+    pub fn switch_vault(&mut self, path: PathBuf) {
+        if self.is_unlocked() {
+            return;
+        }
+        self.cancel_unlock();
+        // A retained key belongs to the vault left behind.
+        self.retained_key.zeroize();
+        self.repository = EncryptedFileRepository::new(path);
+        self.repository.backups = self.settings.vault_backups;
+        self.repository_events = self.repository.subscribe();
+        self.key_status = if self.repository.exists() { KeyStatus::Locked } else { KeyStatus::NotSet };
+        self.unlock_form.clear();
+        self.unlock_from_key_cache();
+    }
+
+    /// Starts deriving the key from the entered master key, or the key of a
+    /// new vault on first run. Argon2 takes a noticeable moment, so it runs
+    /// on its own thread and `poll_unlock` opens the vault once it is done.
+    pub fn unlock(&mut self) {
+        if self.pending_unlock.is_some() {
+            return;
+        }
+        let creating = matches!(self.key_status, KeyStatus::NotSet);
+        if creating {
+            if let Err(e) = self.unlock_form.validate_new(self.min_master_key_bits()) {
+                self.error_message = Some(e);
+                return;
+            }
+        }
+        let password = Zeroizing::new(self.unlock_form.master_key.clone());
+        let path = self.repository.path().to_path_buf();
+        let (sender, receiver) = channel();
+        thread::spawn(move || {
+            let derived = if creating {
+                let salt = generate_salt();
+                Ok(Derived::New(Zeroizing::new(derive_key(&password, &salt)), salt))
+            } else {
+                DerivedKeys::derive(&path, &password).map(Derived::Existing)
+            };
+            // Fails when the unlock was cancelled; the keys are wiped on drop.
+            let _ = sender.send(derived);
         });
+        self.pending_unlock = Some(PendingUnlock::new(receiver, Some(estimate_bits(&self.unlock_form.master_key))));
+    }
 
-        if let Some(error_message) = self.error_message.clone() {
-            egui::Window::new("Error")
-                .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
-                .collapsible(false)
-                .resizable(false)
-                .show(ctx, |ui| {
-                    ui.label(error_message);
-                    if ui.button("OK").clicked() {
-                        self.error_message = None;
-                    }
-                });
+    /// Opens or creates the vault once the key started by `unlock` has been
+    /// derived.
+    pub fn poll_unlock(&mut self) {
+        let Some(pending) = &self.pending_unlock else {
+            return;
+        };
+        let derived = match pending.receiver.try_recv() {
+            Ok(derived) => derived,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => Err("The key could not be derived.".to_string()),
+        };
+        let key_bits = pending.key_bits;
+        self.pending_unlock = None;
+        let opened = derived.and_then(|derived| match derived {
+            Derived::New(key, salt) => {
+                self.repository.create(*key, salt);
+                Ok(true)
+            }
+            Derived::Existing(keys) => self.repository.unlock_derived(&keys).map(|()| true),
+            Derived::Stored(key) => self.unlock_with_stored_key(*key).map(|()| false),
+            Derived::Declined => Ok(false),
+        });
+        match opened {
+            Ok(true) => self.finish_unlock(*self.repository.key()),
+            Ok(false) => {}
+            Err(e) => {
+                self.error_message = Some(e);
+                return;
+            }
         }
+        if let Some(key_bits) = key_bits {
+            self.weak_master_key = key_bits < self.repository.policy.min_master_key_bits;
+        }
+    }
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            let clients: Vec<(usize, String)> = self
-                .clients
-                .iter()
-                .enumerate()
-                .map(|(index, client)| (index, client.name.clone()))
-                .collect();
-
-            for (index, client_name) in clients {
-                ui.horizontal(|ui| {
-                    ui.selectable_value(&mut self.selected_client, Some(index), egui::RichText::new(client_name).heading());
-                    if ui.button("Connect").clicked() {
-                        if let Some(client) = self.clients.get(index) {
-                            self.connect_to_client(client);
-                        }
-                    }
+    /// Abandons the running key derivation; its result is wiped unused.
+    pub fn cancel_unlock(&mut self) {
+        self.pending_unlock = None;
+    }
+
+    /// Replaces the master key with the one entered in the Change Master Key
+    /// dialog, keeping the old one if the vault cannot be rewritten.
+    pub fn change_master_key(&mut self) -> Result<(), String> {
+        self.unlock_form.validate_new(self.min_master_key_bits())?;
+        // Stored API tokens are encrypted with the vault key.
+        let mut tokens = [
+            open_setting(&self.settings.netbox_token, self.repository.key()),
+            open_setting(&self.settings.monitoring_token, self.repository.key()),
+        ];
+        let changed = self.repository.change_key(&self.unlock_form.current, &self.unlock_form.master_key);
+        if changed.is_ok() && tokens.iter().any(Option::is_some) {
+            let key = *self.repository.key();
+            let stored = [&mut self.settings.netbox_token, &mut self.settings.monitoring_token];
+            let mut sealed = Ok(());
+            for (stored, token) in stored.into_iter().zip(&tokens) {
+                if let Some(token) = token {
+                    sealed = sealed.and_then(|()| seal_setting(token, &key).map(|token| *stored = token));
+                }
+            }
+            if let Err(e) = sealed.and_then(|()| self.settings.save()) {
+                self.error_message = Some(format!("Could not keep the API tokens: {}", e));
+            }
+        }
+        tokens.iter_mut().flatten().for_each(Zeroize::zeroize);
+        changed?;
+        self.weak_master_key = estimate_bits(&self.unlock_form.master_key) < self.repository.policy.min_master_key_bits;
+        self.unlock_form.clear();
+        if self.repository.is_decoy() {
+            return Ok(());
+        }
+        if self.settings.key_caching == KeyCaching::RememberForSession {
+            if let Err(e) = keycache::store(self.repository.path(), self.repository.key()) {
+                self.error_message = Some(format!("Could not remember the new master key: {}", e));
+            }
+        }
+        if let Some(how) = self.quick_unlock() {
+            if let Err(e) = hello::store(&self.wrapped_keys_path, self.repository.path(), self.repository.key(), how) {
+                self.error_message = Some(format!("Could not store the new key for unlocking with Windows: {}", e));
+            }
+        }
+        Ok(())
+    }
+
+    /// How the open vault unlocks without its master key, if it does.
+    pub fn quick_unlock(&self) -> Option<QuickUnlock> {
+        hello::enrolled(&self.wrapped_keys_path, self.repository.path())
+    }
+
+    /// Stores the open vault's key for unlocking `how`, or forgets it.
+    /// Windows Hello is asked once before it is relied on, on its own
+    /// thread; `poll_hello_enrollment` stores the key once it confirmed.
+    pub fn set_quick_unlock(&mut self, how: Option<QuickUnlock>) -> Result<(), String> {
+        match how {
+            // The real vault's stored key is neither replaced nor forgotten.
+            _ if self.repository.is_decoy() => Ok(()),
+            None => hello::forget(&self.wrapped_keys_path, self.repository.path()),
+            Some(QuickUnlock::WindowsHello) => {
+                let (sender, receiver) = channel();
+                thread::spawn(move || {
+                    let _ = sender.send(hello::verify("Confirm it is you to unlock Remote Desktop Manager with Windows Hello"));
                 });
+                self.pending_hello_enrollment = Some(receiver);
+                Ok(())
             }
+            Some(how) => hello::store(&self.wrapped_keys_path, self.repository.path(), self.repository.key(), how),
+        }
+    }
 
-            ui.separator();
+    /// Stores the key for Windows Hello once the prompt started by
+    /// `set_quick_unlock` confirmed the user.
+    pub fn poll_hello_enrollment(&mut self) {
+        let Some(receiver) = &self.pending_hello_enrollment else {
+            return;
+        };
+        let verified = match receiver.try_recv() {
+            Ok(verified) => verified,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => Err("Windows Hello did not answer.".to_string()),
+        };
+        self.pending_hello_enrollment = None;
+        let stored = verified.and_then(|verified| match verified && self.is_unlocked() {
+            true => hello::store(&self.wrapped_keys_path, self.repository.path(), self.repository.key(), QuickUnlock::WindowsHello),
+            false => Ok(()),
+        });
+        if let Err(e) = stored {
+            self.error_message = Some(format!("Could not change how the vault unlocks: {}", e));
+        }
+    }
 
-            match self.mode {
-                AppMode::Adding => {
-                    ui.label("Add New Client:");
-
-                    ui.horizontal(|ui| {
-                        ui.label("Name:");
-                        ui.text_edit_singleline(&mut self.new_client_name);
-                    });
-                    ui.horizontal(|ui| {
-                        ui.label("IP:");
-                        ui.text_edit_singleline(&mut self.new_client_ip);
-                    });
-                    ui.horizontal(|ui| {
-                        ui.label("Password:");
-                        if self.show_password {
-                            ui.text_edit_singleline(&mut self.new_client_password);
-                        } else {
-                            let masked_password: String = "*".repeat(self.new_client_password.len());
-                            ui.label(masked_password);
-                        }
-                        if ui.button("👁").clicked() {
-                            self.show_password = !self.show_password;
-                        }
-                    });
-
-                    if ui.button("Save").clicked() {
-                        self.clients.push(Client {
-                            name: self.new_client_name.clone(),
-                            ip: self.new_client_ip.clone(),
-                            password: self.new_client_password.clone(),
-                        });
-                        self.clear_new_client_fields();
-                        self.save_clients();
-                        self.mode = AppMode::Normal;
-                    }
+    /// Starts opening the vault with the key stored for it, the master
+    /// key's alternative. The Windows Hello prompt waits for the user, so
+    /// the key is unwrapped on its own thread and `poll_unlock` opens the
+    /// vault with it.
+    pub fn unlock_quickly(&mut self) {
+        if self.pending_unlock.is_some() {
+            return;
+        }
+        let (file, vault) = (self.wrapped_keys_path.clone(), self.repository.path().to_path_buf());
+        let (sender, receiver) = channel();
+        thread::spawn(move || {
+            let loaded = match hello::load(&file, &vault) {
+                Ok(Some(key)) => Ok(Derived::Stored(Zeroizing::new(key))),
+                Ok(None) => Ok(Derived::Declined),
+                Err(e) => Err(format!("Could not unlock with Windows: {}", e)),
+            };
+            let _ = sender.send(loaded);
+        });
+        self.pending_unlock = Some(PendingUnlock::new(receiver, None));
+    }
+
+    /// Opens the vault with the key stored for unlocking with Windows. A
+    /// key that no longer opens the vault, e.g. after the master key was
+    /// changed on another computer, is forgotten.
+    fn unlock_with_stored_key(&mut self, key: [u8; KEY_SIZE]) -> Result<(), String> {
+        match self.unlock_with_key(key) {
+            Err(e) if e == INCORRECT_KEY => {
+                let _ = hello::forget(&self.wrapped_keys_path, self.repository.path());
+                Err("The stored key no longer opens this vault, so it was forgotten. Unlock with the master key, then turn unlocking with Windows on again in Settings > Security.".to_string())
+            }
+            result => result,
+        }
+    }
+
+    /// Decrypts the vault with an already derived key.
+    pub fn unlock_with_key(&mut self, key: [u8; KEY_SIZE]) -> Result<(), String> {
+        self.repository.open(key)?;
+        self.finish_unlock(key);
+        Ok(())
+    }
+
+    fn finish_unlock(&mut self, key: [u8; KEY_SIZE]) {
+        self.retained_key = None;
+        self.unlock_form.clear();
+        self.key_status = KeyStatus::Unlocked;
+        self.mode = AppMode::Normal;
+        // The duress key leaves no trace outside the vault: it is not
+        // cached, and the real vault's layout is neither read nor replaced.
+        if self.repository.is_decoy() {
+            self.pending_layout = None;
+        } else {
+            if self.settings.key_caching == KeyCaching::RememberForSession {
+                if let Err(e) = keycache::store(self.repository.path(), &key) {
+                    self.error_message = Some(format!("Could not remember the master key: {}", e));
+                }
+            }
+            self.pending_layout = layout::load(&self.layouts_path, self.repository.path(), &key);
+        }
+        for (client_id, started, ended) in std::mem::take(&mut self.pending_session_ends) {
+            history::close(&mut self.repository.history, &client_id, started, ended);
+        }
+        // Re-encrypts legacy vaults with the salted key and persists ids
+        // assigned on load.
+        self.save_clients();
+    }
+
+    /// Idle minutes before auto-lock: the user's choice, capped by the vault policy.
+    pub fn effective_auto_lock_minutes(&self) -> Option<u32> {
+        match (self.settings.auto_lock_minutes, self.repository.policy.max_auto_lock_minutes) {
+            (Some(user), Some(max)) => Some(user.min(max)),
+            (user, max) => user.or(max),
+        }
+    }
+
+    /// Warns about sessions that ran past their client's time limit and
+    /// closes those whose limit says so. Runs while locked too.
+    pub fn check_session_limits(&mut self, ctx: &egui::Context) {
+        for session in self.sessions.take_over_limit() {
+            let Some(limit) = session.limit else {
+                continue;
+            };
+            let mut message = format!("The session to {} reached its {}-minute limit", session.client_name, limit.minutes);
+            if limit.disconnect {
+                let closed = if session.simulated {
+                    self.launcher.log(&format!("would close the session to {}", session.client_name));
+                    Ok(())
+                } else {
+                    session_window::close(session.pid)
+                };
+                match closed {
+                    Ok(()) => message.push_str(" and was disconnected"),
+                    Err(e) => message.push_str(&format!(" but could not be disconnected: {}", e)),
+                }
+            }
+            self.notification = Some(message);
+        }
+        if let Some(next) = self.sessions.next_limit() {
+            ctx.request_repaint_after(next);
+        }
+    }
 
-                    if ui.button("Cancel").clicked() {
-                        self.clear_new_client_fields();
-                        self.mode = AppMode::Normal;
+    pub fn check_idle_lock(&mut self, ctx: &egui::Context) {
+        if ctx.input(|input| !input.events.is_empty() || input.pointer.is_moving()) {
+            self.last_activity = Instant::now();
+        }
+        let Some(minutes) = self.effective_auto_lock_minutes() else {
+            return;
+        };
+        let limit = Duration::from_secs(minutes as u64 * 60);
+        if self.last_activity.elapsed() >= limit {
+            self.lock();
+        } else {
+            ctx.request_repaint_after(limit - self.last_activity.elapsed());
+        }
+    }
+
+    pub fn set_key_caching(&mut self, key_caching: KeyCaching) {
+        self.settings.key_caching = key_caching;
+        if self.repository.is_decoy() {
+            // Only the real vault's key is ever cached.
+        } else if key_caching == KeyCaching::RememberForSession {
+            if let Err(e) = keycache::store(self.repository.path(), self.repository.key()) {
+                self.error_message = Some(format!("Could not remember the master key: {}", e));
+                self.settings.key_caching = KeyCaching::ForgetOnLock;
+            }
+        } else {
+            keycache::clear(self.repository.path());
+        }
+        if let Err(e) = self.settings.save() {
+            self.error_message = Some(format!("Could not save settings: {}", e));
+        }
+    }
+
+    /// Zeroizes the key and every decrypted secret, returning to the unlock
+    /// screen. Background work checks `is_unlocked` and pauses until the
+    /// vault is opened again.
+    pub fn lock(&mut self) {
+        if !self.is_unlocked() {
+            return;
+        }
+        if self.rotation.running() {
+            self.rotation.lock_when_done = true;
+            self.notification = Some("The vault locks once the password changes finish.".to_string());
+            return;
+        }
+        self.save_layout();
+        if self.settings.key_caching != KeyCaching::ForgetOnLock {
+            self.retained_key = Some(*self.repository.key());
+        } else {
+            self.retained_key.zeroize();
+        }
+        self.repository.close();
+        self.form.clear();
+        self.unlock_form.clear();
+        self.pending_unlock = None;
+        self.transfer.clear_passphrase();
+        self.netbox_token_input.zeroize();
+        self.monitoring_token_input.zeroize();
+        self.monitoring_status.clear();
+        self.last_monitoring_poll = None;
+        self.reachability.clear();
+        self.last_seen.clear();
+        self.last_reachability_check = None;
+        self.fingerprinted.clear();
+        self.search_index = SearchIndex::default();
+        self.search_query.clear();
+        self.thumbnails.clear();
+        self.viewing_attachment = None;
+        self.client_list.selected = None;
+        self.client_list.filter.clear();
+        self.client_list.tag = None;
+        self.client_list.collapsed.clear();
+        self.connect_failure = None;
+        if let Some(mut client) = self.confirm_connect.take() {
+            client.wipe_secrets();
+        }
+        self.group_connect = GroupConnect::default();
+        self.note_prompt = NotePrompt::default();
+        if let Some(mut client) = self.pending_connect.take() {
+            client.wipe_secrets();
+        }
+        if let Some(mut undo) = self.import_undo.take() {
+            undo.wipe_secrets();
+        }
+        for mut conflict in self.import_conflicts.drain(..) {
+            conflict.incoming.wipe_secrets();
+        }
+        self.duplicate_groups.clear();
+        self.discard_credential_form();
+        self.notification = None;
+        self.mode = AppMode::Normal;
+        self.key_status = KeyStatus::Locked;
+    }
+
+    /// The workspace as it is now, to be restored with the open vault.
+    pub fn current_layout(&self) -> Layout {
+        Layout {
+            window_size: self.window_size.map(|size| [size.x, size.y]),
+            window_position: self.window_position.map(|position| [position.x, position.y]),
+            view: View::of(self.mode),
+            filter: self.client_list.filter.clone(),
+            tag: self.client_list.tag.clone(),
+            selected: self.client_list.selected.clone(),
+            collapsed_groups: self.client_list.collapsed.clone(),
+            search_query: self.search_query.clone(),
+        }
+    }
+
+    /// Saves the open vault's layout, before locking and on exit.
+    pub fn save_layout(&mut self) {
+        if !self.is_unlocked() || self.repository.is_decoy() {
+            return;
+        }
+        if let Err(e) = layout::save(&self.layouts_path, self.repository.path(), self.repository.key(), &self.current_layout()) {
+            self.error_message = Some(format!("Could not save the window layout: {}", e));
+        }
+    }
+
+    /// Puts the window and the list back the way `layout` left them. A
+    /// client removed since is not selected.
+    fn restore_layout(&mut self, ctx: &egui::Context, layout: Layout) {
+        if let Some([width, height]) = layout.window_size {
+            ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(egui::vec2(width, height)));
+        }
+        if let Some([x, y]) = layout.window_position {
+            ctx.send_viewport_cmd(egui::ViewportCommand::OuterPosition(egui::pos2(x, y)));
+        }
+        self.client_list.filter = layout.filter;
+        self.client_list.tag = layout.tag;
+        self.client_list.collapsed = layout.collapsed_groups;
+        self.client_list.selected = layout.selected;
+        self.client_list.retain(self.repository.clients());
+        self.client_list.scroll_to_selection = true;
+        self.search_query = layout.search_query;
+        match layout.view {
+            View::Clients => {}
+            View::Search => self.apply_menu_action(MenuAction::Search),
+            View::Trash => self.apply_menu_action(MenuAction::Trash),
+            View::Sessions => self.apply_menu_action(MenuAction::Sessions),
+            View::History => self.apply_menu_action(MenuAction::History),
+        }
+    }
+
+    pub fn clear_clipboard(&self) {
+        if let Ok(mut clipboard) = Clipboard::new() {
+            let _ = clipboard.clear();
+        }
+    }
+
+    /// Locks the vault and clears the clipboard when the OS session is
+    /// locked or the machine comes back from sleep.
+    pub fn handle_os_events(&mut self) {
+        let Some(receiver) = &self.os_events else {
+            return;
+        };
+        let mut triggered = false;
+        while let Ok(event) = receiver.try_recv() {
+            match event {
+                OsEvent::SessionLocked | OsEvent::Suspending | OsEvent::Resumed => triggered = true,
+            }
+        }
+        if triggered {
+            self.lock();
+            if self.settings.forget_key_on_os_lock && !self.is_unlocked() {
+                self.forget_retained_key();
+            }
+            self.clear_clipboard();
+        }
+    }
+
+    /// Removes the client with `id` and saves.
+    pub fn remove_client(&mut self, id: &str) {
+        self.repository.remove(id);
+        self.client_list.selected = None;
+        self.form.clear();
+        self.save_clients();
+        self.mode = AppMode::Normal;
+    }
+
+    pub fn save_clients(&mut self) {
+        match self.repository.save() {
+            Ok(()) => self.events.publish(AppEvent::VaultSaved),
+            Err(e) => self.error_message = Some(e),
+        }
+    }
+
+    pub fn handle_app_events(&mut self, ctx: &egui::Context) {
+        while let Ok(event) = self.event_receiver.try_recv() {
+            match event {
+                AppEvent::ConnectionStarted(session) => {
+                    self.record_session_start(&session);
+                    self.update_title(ctx);
+                    self.show_session_notification(format!("Connected to {}", session.client_name));
+                }
+                AppEvent::ConnectionEnded(session) => {
+                    self.record_session_end(&session);
+                    if self.settings.ask_for_pinned_note && !session.simulated && self.is_unlocked() {
+                        self.ask_for_pinned_note(&session.client_id);
+                    }
+                    self.update_title(ctx);
+                    let minutes = session.started.elapsed().as_secs() / 60;
+                    self.show_session_notification(format!("Session with {} ended after {} min", session.client_name, minutes));
+                }
+                AppEvent::VaultSaved => {
+                    self.last_saved = Some(chrono::Local::now());
+                    // Saves also follow history and pinned note changes,
+                    // which send no repository event.
+                    self.search_index = SearchIndex::build(self.repository.clients(), &self.repository.history);
+                }
+                AppEvent::NetboxPulled(result) => {
+                    self.netbox_busy = false;
+                    match result {
+                        Ok(objects) => self.apply_netbox_pull(objects),
+                        Err(e) => self.error_message = Some(format!("NetBox pull failed: {}", e)),
+                    }
+                }
+                AppEvent::NetboxPushed(result) => {
+                    self.netbox_busy = false;
+                    match result {
+                        Ok(count) => self.info_message = Some(format!("Pushed the tags of {} clients to NetBox", count)),
+                        Err(e) => self.error_message = Some(format!("NetBox push failed: {}", e)),
                     }
                 }
-                AppMode::Editing => {
-                    if let Some(index) = self.selected_client {
-                        if index < self.clients.len() {
-                            ui.label("Edit Client:");
-
-                            ui.horizontal(|ui| {
-                                ui.label("Name:");
-                                ui.text_edit_singleline(&mut self.new_client_name);
-                            });
-                            ui.horizontal(|ui| {
-                                ui.label("IP:");
-                                ui.text_edit_singleline(&mut self.new_client_ip);
-                            });
-                            ui.horizontal(|ui| {
-                                ui.label("Password:");
-                                if self.show_password {
-                                    ui.text_edit_singleline(&mut self.new_client_password);
-                                } else {
-                                    let masked_password: String = "*".repeat(self.new_client_password.len());
-                                    ui.label(masked_password);
-                                }
-                                if ui.button("👁").clicked() {
-                                    self.show_password = !self.show_password;
-                                }
-                            });
-
-                            if ui.button("Save").clicked() {
-                                self.clients[index] = Client {
-                                    name: self.new_client_name.clone(),
-                                    ip: self.new_client_ip.clone(),
-                                    password: self.new_client_password.clone(),
-                                };
-                                self.clear_new_client_fields();
-                                self.save_clients();
-                                self.mode = AppMode::Normal;
-                            }
-
-                            if ui.button("Cancel").clicked() {
-                                self.clear_new_client_fields();
-                                self.mode = AppMode::Normal;
-                            }
+                AppEvent::PeersChanged => {}
+                AppEvent::PasswordChanged(client_id, result) => self.finish_password_change(&client_id, result),
+                AppEvent::PrerequisitesUp(client_id) => self.finish_waiting(&client_id),
+                AppEvent::DiagnosticsRan(client_id, checks) => {
+                    if let Some(failure) = self.connect_failure.as_mut().filter(|failure| failure.client_id == client_id) {
+                        failure.checking = false;
+                        failure.checks = Some(checks);
+                    }
+                }
+                AppEvent::ReachabilityChecked(results) => {
+                    self.reachability_busy = false;
+                    if self.is_unlocked() {
+                        let now = chrono::Utc::now();
+                        for (client_id, _) in results.iter().filter(|(_, answer)| answer.is_some()) {
+                            self.last_seen.insert(client_id.clone(), now);
                         }
+                        self.reachability = results.into_iter().collect();
+                    }
+                }
+                AppEvent::HostsFingerprinted(guesses) => {
+                    self.fingerprint_busy = false;
+                    if self.is_unlocked() {
+                        self.tag_fingerprinted(guesses);
                     }
                 }
-                AppMode::Removing => {
-                    if let Some(index) = self.selected_client {
-                        if index < self.clients.len() {
-                            ui.label(format!("Remove Client: {}", self.clients[index].name));
-
-                            if ui.button("Confirm").clicked() {
-                                self.clients.remove(index);
-                                self.selected_client = None;
-                                self.clear_new_client_fields();
-                                self.save_clients();
-                                self.mode = AppMode::Normal;
-                            }
-
-                            if ui.button("Cancel").clicked() {
-                                self.clear_new_client_fields();
-                                self.mode = AppMode::Normal;
-                            }
+                AppEvent::MonitoringPolled(result) => {
+                    self.monitoring_busy = false;
+                    // Status from a poll that outlived the vault is dropped
+                    // along with everything else the lock cleared.
+                    if !self.is_unlocked() {
+                        continue;
+                    }
+                    match result {
+                        Ok(hosts) => {
+                            self.monitoring_status = status_index(&hosts);
+                            self.monitoring_error = None;
+                        }
+                        Err(e) => {
+                            self.monitoring_status.clear();
+                            self.monitoring_error = Some(e);
                         }
                     }
                 }
-                AppMode::About => {
-                    ui.label("Powered By Jerry Yu");
-                    if ui.button("Back").clicked() {
-                        self.mode = AppMode::Normal;
+            }
+        }
+    }
+
+    /// Adds a started session to the vault's history.
+    fn record_session_start(&mut self, session: &ActiveSession) {
+        if !self.is_unlocked() || session.simulated {
+            return;
+        }
+        let record = ConnectionRecord {
+            client_id: session.client_id.clone(),
+            client_name: session.client_name.clone(),
+            address: session.address.clone(),
+            started: session.started_at,
+            ended: None,
+            error: None,
+            password_changed: false,
+        };
+        history::append(&mut self.repository.history, record);
+        self.save_clients();
+    }
+
+    /// Closes the session's history record, or remembers the end until the
+    /// vault is unlocked again.
+    fn record_session_end(&mut self, session: &ActiveSession) {
+        if session.simulated {
+            return;
+        }
+        let ended = chrono::Utc::now();
+        if !self.is_unlocked() {
+            self.pending_session_ends.push((session.client_id.clone(), session.started_at, ended));
+            return;
+        }
+        if history::close(&mut self.repository.history, &session.client_id, session.started_at, ended) {
+            self.save_clients();
+        }
+    }
+
+    /// Session notices never replace a notification that offers an undo.
+    fn show_session_notification(&mut self, message: String) {
+        if self.import_undo.is_none() {
+            self.notification = Some(message);
+        }
+    }
+
+    /// Keeps derived UI state in step with the repository.
+    pub fn handle_repository_events(&mut self) {
+        let mut changed = false;
+        while let Ok(event) = self.repository_events.try_recv() {
+            changed = true;
+            match event {
+                RepositoryEvent::Added(id) => self.client_list.selected = Some(id),
+                RepositoryEvent::Updated(id) | RepositoryEvent::Removed(id) => {
+                    // A connect waiting on the clipboard warning holds a stale copy.
+                    if self.pending_connect.as_ref().is_some_and(|client| client.id == id) {
+                        if let Some(mut client) = self.pending_connect.take() {
+                            client.wipe_secrets();
+                        }
                     }
                 }
-                AppMode::Normal => {
-                    // Do nothing
+                RepositoryEvent::Reloaded => {}
+            }
+        }
+        if changed {
+            self.search_index = SearchIndex::build(self.repository.clients(), &self.repository.history);
+            self.client_list.retain(self.repository.clients());
+        }
+    }
+
+    /// Connects, first warning if the password would end up in a clipboard
+    /// history tool or be passed on by the Remote Desktop session the app
+    /// runs in. Inside such a session RDP passwords are never copied: with
+    /// an account they go through the Credential Manager, without one
+    /// mstsc asks for them.
+    pub fn request_connect(&mut self, client: &Client) {
+        if self.settings.confirmations.connect_tag(client).is_some() || client.pinned_note.is_some() {
+            self.confirm_connect = Some(client.clone());
+            return;
+        }
+        self.request_confirmed_connect(client);
+    }
+
+    /// `request_connect` once the connect was confirmed.
+    pub fn request_confirmed_connect(&mut self, client: &Client) {
+        let client = &client.with_credential(&self.repository.credentials).into_owned();
+        let account_handed_over = client.account().is_some() && RdpViewer::detect().takes_password();
+        let copy_password = self.copy_password_allowed() && (client.protocol != Protocol::Rdp || (!account_handed_over && !self.remote_session));
+        let history = copy_password && self.settings.warn_clipboard_history && !self.clipboard_history_tools().is_empty();
+        if copy_password && (history || self.remote_session) {
+            self.pending_connect = Some(client.clone());
+            return;
+        }
+        self.connect(client, copy_password);
+    }
+
+    /// Starts the viewer. A launch that fails is recorded in the history
+    /// and explained in the failure window; one that starts is recorded
+    /// once its session is tracked.
+    pub fn connect(&mut self, client: &Client, copy_password: bool) {
+        let client = &client.with_credential(&self.repository.credentials).into_owned();
+        // A farm broker keeps the port in its own address.
+        let target = client.with_default_port(&self.settings.default_ports).connection_target().into_owned();
+        let Err(e) = self.precheck(&target).and_then(|()| self.connect_to_client(&target, copy_password)) else {
+            self.connect_failure = None;
+            return;
+        };
+        if !client.info_only && !self.launcher.is_dry_run() {
+            let record = ConnectionRecord {
+                client_id: client.id.clone(),
+                client_name: client.name.clone(),
+                address: client.endpoint().0.to_string(),
+                started: chrono::Utc::now(),
+                ended: None,
+                error: Some(e.message.clone()),
+                password_changed: false,
+            };
+            history::append(&mut self.repository.history, record);
+            self.save_clients();
+        }
+        self.connect_failure = Some(ConnectFailure::new(client, e));
+    }
+
+    /// Checks a client the background check found unreachable again before
+    /// launching, so the failure says why instead of the viewer timing out,
+    /// that a client bound to an interface would leave through it, and
+    /// that an RDP server with Network Level Authentication takes the
+    /// stored account, unless that check is turned off or a gateway stands
+    /// in between. The dry run skips all of them along with everything else
+    /// that touches the network.
+    fn precheck(&mut self, client: &Client) -> Result<(), LaunchError> {
+        if client.info_only || self.launcher.is_dry_run() {
+            return Ok(());
+        }
+        let Some(target) = Target::of(client, &self.settings.default_ports) else {
+            return Ok(());
+        };
+        if !client.interface.trim().is_empty() {
+            diagnosis::check_route(&client.interface, &target.host, target.port)?;
+        }
+        if self.reachability.get(&client.id) == Some(&None) {
+            diagnosis::precheck(&target.host, target.port)?;
+            self.reachability.remove(&client.id);
+        }
+        let stored_account = client.account().is_some() && !client.password.is_empty();
+        if client.protocol == Protocol::Rdp && stored_account && client.gateway.is_none() && self.settings.check_rdp_sign_in {
+            diagnosis::check_sign_in(&target.host, target.port, client, &self.server_certificates_path)?;
+        }
+        Ok(())
+    }
+
+    /// The program a connect to `client` starts.
+    pub fn viewer_program(&self, client: &Client) -> String {
+        let command = match client.protocol {
+            Protocol::Rdp => return RdpViewer::detect().program().to_string(),
+            Protocol::Ssh => ssh::command(&self.settings.ssh_client, client),
+            Protocol::Vnc => vnc::command(&self.settings.vnc_viewer, client),
+        };
+        match command {
+            Ok(command) => command.get_program().to_string_lossy().into_owned(),
+            Err(_) => String::new(),
+        }
+    }
+
+    /// Runs the diagnostics for the failed connect in the background.
+    pub fn run_diagnostics(&mut self) {
+        let Some(failure) = self.connect_failure.as_mut() else {
+            return;
+        };
+        let Some(client) = self.repository.get(&failure.client_id).cloned() else {
+            return;
+        };
+        let Some(target) = Target::of(&client, &self.settings.default_ports) else {
+            return;
+        };
+        failure.checking = true;
+        let program = self.viewer_program(&client);
+        let interface = client.interface.clone();
+        let events = self.events.clone();
+        thread::spawn(move || {
+            let checks = diagnosis::run_checks(&target.host, target.port, &interface, &program);
+            events.publish(AppEvent::DiagnosticsRan(target.client_id, checks));
+        });
+    }
+
+    /// Empties the connection history.
+    pub fn clear_history(&mut self) {
+        self.repository.history.clear();
+        self.save_clients();
+    }
+
+    pub fn copy_password_allowed(&self) -> bool {
+        self.repository.policy.clipboard_allowed && self.settings.copy_password_on_connect
+    }
+
+    fn clipboard_history_tools(&mut self) -> Vec<String> {
+        match &self.clipboard_history {
+            Some((checked, tools)) if checked.elapsed().as_secs() < 60 => tools.clone(),
+            _ => {
+                let tools = detect_clipboard_history();
+                self.clipboard_history = Some((Instant::now(), tools.clone()));
+                tools
+            }
+        }
+    }
+
+    pub fn connect_to_client(&self, client: &Client, copy_password: bool) -> Result<(), LaunchError> {
+        if client.info_only {
+            return Err(format!("{} is an info-only entry and cannot be connected to.", client.name).into());
+        }
+        match client.protocol {
+            Protocol::Rdp => self.launch_rdp(client, copy_password),
+            Protocol::Ssh => self.launch_ssh(client, copy_password),
+            Protocol::Vnc => self.launch_vnc(client, copy_password),
+        }
+    }
+
+    fn launch_rdp(&self, client: &Client, copy_password: bool) -> Result<(), LaunchError> {
+        let viewer = RdpViewer::detect();
+        // With a stored account the credential goes through the Credential
+        // Manager and is dropped when mstsc exits, or to FreeRDP on its
+        // standard input. Clients without an account, and the macOS app,
+        // still fall back to the clipboard.
+        let stored_credential = viewer.takes_password() && client.account().is_some() && !client.password.is_empty();
+        let rdp_file = rdp::write_temp(client, !stored_credential, &self.settings.rdp_options)?;
+        let address = client.ip.clone();
+        let rdp_file_to_remove = rdp_file.clone();
+        let remove_credential = stored_credential && viewer == RdpViewer::Mstsc && !self.launcher.is_dry_run();
+        // Only mstsc reads gateway credentials from the Credential Manager;
+        // the other clients sign in to the gateway with the client's account.
+        let gateway = self.gateway_credential(client).filter(|_| viewer == RdpViewer::Mstsc);
+        let gateway_to_remove = gateway.as_ref().filter(|_| !self.launcher.is_dry_run()).map(|(host, _)| host.clone());
+        let cleanup = move || {
+            let _ = fs::remove_file(&rdp_file_to_remove);
+            if remove_credential {
+                cmdkey::remove(&address);
+            }
+            if let Some(host) = &gateway_to_remove {
+                cmdkey::remove_gateway(host);
+            }
+        };
+        let prepared = self.prepare_credentials(client, viewer, stored_credential, copy_password).and_then(|()| match &gateway {
+            Some((host, _)) if self.launcher.is_dry_run() => {
+                self.launcher.log(&format!("would store a credential for the gateway {}", host));
+                Ok(())
+            }
+            Some((host, credential)) => cmdkey::store_gateway(host, &credential.account(), &credential.password),
+            None => Ok(()),
+        });
+        if let Err(e) = prepared {
+            cleanup();
+            return Err(LaunchError::new(Failure::CredentialInjection, e));
+        }
+
+        // The dry run logs the command line, which must not show the password.
+        let password = (stored_credential && !self.launcher.is_dry_run()).then_some(client.password.as_str());
+        let mut process = match self.launcher.spawn(&mut viewer.command(&rdp_file, client, password.is_some())) {
+            Ok(process) => process,
+            Err(e) => {
+                cleanup();
+                return Err(LaunchError::spawn(viewer.program(), "Failed to launch Remote Desktop", &e));
+            }
+        };
+        if let Some(password) = password {
+            if let Err(e) = viewer.send_password(&mut process, client, password) {
+                cleanup();
+                return Err(LaunchError::new(Failure::CredentialInjection, format!("Could not hand the password to {}: {}", viewer.program(), e)));
+            }
+        }
+        self.start_session(client, process, cleanup);
+        Ok(())
+    }
+
+    /// SSH and VNC clients cannot safely take a password on the command
+    /// line, so the clipboard is the only way to hand it over.
+    fn copy_password_for_viewer(&self, client: &Client, copy_password: bool) -> Result<(), String> {
+        let clear_after = self.settings.clear_clipboard_after_seconds.map(|seconds| Duration::from_secs(seconds as u64));
+        if self.launcher.is_dry_run() {
+            let clearing = clear_after.map(|delay| format!(", cleared after {} seconds", delay.as_secs())).unwrap_or_default();
+            self.launcher.log(&format!("would copy the password of {} to the clipboard: {}{}", client.name, copy_password, clearing));
+            return Ok(());
+        }
+        if copy_password && !client.password.is_empty() {
+            let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
+            clipboard.set_text(&client.password).map_err(|e| e.to_string())?;
+            if let Some(delay) = clear_after {
+                clipboard_guard::clear_after(&client.password, delay);
+            }
+        }
+        Ok(())
+    }
+
+    fn launch_ssh(&self, client: &Client, copy_password: bool) -> Result<(), LaunchError> {
+        self.copy_password_for_viewer(client, copy_password).map_err(|e| LaunchError::new(Failure::CredentialInjection, e))?;
+        let mut command = ssh::command(&self.settings.ssh_client, client)?;
+        let program = command.get_program().to_string_lossy().into_owned();
+        let process = self
+            .launcher
+            .spawn(&mut command)
+            .map_err(|e| LaunchError::spawn(&program, &format!("Failed to launch the SSH client {}", self.settings.ssh_client), &e))?;
+        self.start_session(client, process, || {});
+        Ok(())
+    }
+
+    fn launch_vnc(&self, client: &Client, copy_password: bool) -> Result<(), LaunchError> {
+        self.copy_password_for_viewer(client, copy_password).map_err(|e| LaunchError::new(Failure::CredentialInjection, e))?;
+        let mut command = vnc::command(&self.settings.vnc_viewer, client)?;
+        let program = command.get_program().to_string_lossy().into_owned();
+        let process = self
+            .launcher
+            .spawn(&mut command)
+            .map_err(|e| LaunchError::spawn(&program, &format!("Failed to launch the VNC viewer {}", self.settings.vnc_viewer), &e))?;
+        self.start_session(client, process, || {});
+        Ok(())
+    }
+
+    fn start_session(&self, client: &Client, process: Process, on_exit: impl FnOnce() + Send + 'static) {
+        if let (Some(placement), false) = (client.placement, process.is_simulated()) {
+            session_window::place_when_shown(process.id(), placement);
+        }
+        self.sessions.track(client, process, on_exit);
+    }
+
+    /// The gateway host and the shared account to sign in to it with, when
+    /// the client's gateway has one with a password. Otherwise mstsc uses
+    /// the client's account or prompts.
+    fn gateway_credential(&self, client: &Client) -> Option<(String, Credential)> {
+        let gateway = client.gateway.as_ref().filter(|gateway| !gateway.host.trim().is_empty())?;
+        let credential = credential::find(&self.repository.credentials, gateway.credential.as_deref()?)?;
+        (!credential.password.is_empty()).then(|| (gateway.host.trim().to_string(), credential.clone()))
+    }
+
+    fn prepare_credentials(&self, client: &Client, viewer: RdpViewer, stored_credential: bool, copy_password: bool) -> Result<(), String> {
+        if stored_credential && viewer != RdpViewer::Mstsc {
+            Ok(())
+        } else if stored_credential && self.launcher.is_dry_run() {
+            self.launcher.log(&format!("would store a credential for {}", client.name));
+            Ok(())
+        } else if stored_credential {
+            let account = client.account().unwrap_or_default();
+            cmdkey::store(&client.ip, &account, &client.password)
+        } else {
+            self.copy_password_for_viewer(client, copy_password)
+        }
+    }
+
+    /// Shows the number of running sessions in the window title, which is
+    /// also what the taskbar button displays, and on Windows as a badge on
+    /// the taskbar button.
+    pub fn update_title(&self, ctx: &egui::Context) {
+        let count = self.sessions.count();
+        let base = match self.vault_name() {
+            Some(name) => format!("{} - {}", APP_TITLE, name),
+            None => APP_TITLE.to_string(),
+        };
+        let title = match count {
+            0 => base,
+            1 => format!("{} (1 active session)", base),
+            _ => format!("{} ({} active sessions)", base, count),
+        };
+        ctx.send_viewport_cmd(egui::ViewportCommand::Title(title));
+        if let Err(e) = taskbar::show_session_count(count) {
+            eprintln!("{}", e);
+        }
+    }
+
+    /// The clients with their shared credentials filled in, for exports
+    /// read by tools that know nothing of credentials.
+    pub fn resolved_clients(&self) -> Vec<Client> {
+        self.repository.clients().iter().map(|client| client.with_credential(&self.repository.credentials).into_owned()).collect()
+    }
+
+    pub fn export_clients_csv(&mut self) {
+        match export_csv(&self.resolved_clients()).and_then(|data| fs::write(&self.transfer.path, data).map_err(|e| e.to_string())) {
+            Ok(()) => {
+                self.info_message = Some(format!("Exported {} clients to {}", self.repository.clients().len(), self.transfer.path));
+                self.mode = AppMode::Normal;
+            }
+            Err(e) => self.error_message = Some(format!("Export failed: {}", e)),
+        }
+    }
+
+    /// Merges imported clients into the vault. New clients that look like a
+    /// stored one are held back for the Duplicates dialog, which the import
+    /// then leaves open; the summary covers the rest.
+    pub(crate) fn merge_import(&mut self, imported: Vec<Client>) -> ImportSummary {
+        let mut clients = self.repository.clients().to_vec();
+        let (clean, conflicts) = split_conflicts(&clients, imported);
+        let summary = merge_clients(&mut clients, clean);
+        self.repository.replace_all(clients);
+        self.save_clients();
+        self.import_conflicts = conflicts;
+        self.mode = if self.import_conflicts.is_empty() { AppMode::Normal } else { AppMode::ImportConflicts };
+        summary
+    }
+
+    /// Writes the resolutions chosen in the Duplicates dialog.
+    pub fn resolve_import_conflicts(&mut self) {
+        let mut clients = self.repository.clients().to_vec();
+        let conflicts = std::mem::take(&mut self.import_conflicts);
+        let count = conflicts.len();
+        let changed = resolve(&mut clients, conflicts);
+        self.repository.replace_all(clients);
+        self.save_clients();
+        self.info_message = Some(format!("Resolved {} duplicates, {} stored clients changed", count, changed));
+        self.mode = AppMode::Normal;
+    }
+
+    pub fn import_clients_csv(&mut self) {
+        match fs::read_to_string(&self.transfer.path).map_err(|e| e.to_string()).and_then(|data| parse_csv(&data)) {
+            Ok(rows) => {
+                let summary = self.merge_import(rows_to_clients(rows));
+                self.info_message = Some(format!("Imported {} new and updated {} existing clients", summary.added, summary.updated));
+            }
+            Err(e) => self.error_message = Some(format!("Import failed: {}", e)),
+        }
+    }
+
+    pub fn import_clients_rdg(&mut self) {
+        let result = fs::read(&self.transfer.path).map_err(|e| e.to_string()).and_then(|data| parse_rdg(&decode_text(&data)));
+        match result {
+            Ok(import) => {
+                let summary = self.merge_import(import.clients);
+                let mut message = format!("Imported {} servers in {} groups", summary.added, import.groups);
+                if import.undecrypted_passwords > 0 {
+                    message.push_str(&format!(
+                        ". {} passwords could not be decrypted; RDCMan encrypts them for the Windows user who saved the file.",
+                        import.undecrypted_passwords
+                    ));
+                }
+                self.info_message = Some(message);
+            }
+            Err(e) => self.error_message = Some(format!("Import failed: {}", e)),
+        }
+    }
+
+    /// Offers the hosts from mstsc's history that are not stored yet.
+    pub fn offer_mstsc_history(&mut self) {
+        match read_history() {
+            Ok(entries) => {
+                let clients = self.repository.clients();
+                self.history_import = entries
+                    .into_iter()
+                    .filter(|entry| {
+                        !clients.iter().any(|c| c.trashed_at.is_none() && c.protocol == Protocol::Rdp && matches_address(c, &entry.address, &self.settings.default_ports))
+                    })
+                    .map(|entry| (entry, true))
+                    .collect();
+                if self.history_import.is_empty() {
+                    self.info_message = Some("The Remote Desktop history has no hosts that are not stored yet.".to_string());
+                } else {
+                    self.mode = AppMode::ImportingHistory;
+                }
+            }
+            Err(e) => self.error_message = Some(e),
+        }
+    }
+
+    pub fn import_mstsc_history(&mut self) {
+        let imported: Vec<Client> = self.history_import.iter().filter(|(_, selected)| *selected).map(|(entry, _)| entry.to_client()).collect();
+        let summary = self.merge_import(imported);
+        self.info_message = Some(format!("Imported {} hosts from the Remote Desktop history", summary.added));
+        self.history_import.clear();
+    }
+
+    pub fn export_clients(&mut self) {
+        match self.transfer.format {
+            ExportFormat::Csv => self.export_clients_csv(),
+            ExportFormat::Json => self.export_clients_json(),
+        }
+    }
+
+    /// Writes the JSON export. Encrypted exports carry every password, so
+    /// their passphrase has to meet the same minimum as the master key.
+    pub fn export_clients_json(&mut self) {
+        let result = if self.transfer.encrypt {
+            if self.transfer.passphrase != self.transfer.confirm {
+                self.error_message = Some("The export passphrases do not match.".to_string());
+                return;
+            }
+            let bits = estimate_bits(&self.transfer.passphrase);
+            if bits < self.min_master_key_bits() {
+                self.error_message = Some(format!(
+                    "The export passphrase is too weak ({} of {} bits). Use a longer passphrase.",
+                    bits,
+                    self.min_master_key_bits()
+                ));
+                return;
+            }
+            export_encrypted_bundle(&self.resolved_clients(), &self.transfer.passphrase)
+        } else {
+            export_bundle(&self.resolved_clients())
+        };
+        match result.and_then(|data| fs::write(&self.transfer.path, data).map_err(|e| e.to_string())) {
+            Ok(()) => {
+                self.info_message = Some(format!("Exported {} clients to {}", self.repository.clients().len(), self.transfer.path));
+                self.transfer.clear_passphrase();
+                self.mode = AppMode::Normal;
+            }
+            Err(e) => self.error_message = Some(format!("Export failed: {}", e)),
+        }
+    }
+
+    /// A NetBox client for the address and token in the NetBox dialog. A
+    /// newly entered token replaces the stored one.
+    fn netbox_client(&mut self) -> Result<NetboxClient, String> {
+        let url = self.netbox_url_input.trim().to_string();
+        if url.is_empty() {
+            return Err("Please enter the NetBox address.".to_string());
+        }
+        let token = if self.netbox_token_input.trim().is_empty() {
+            if self.settings.netbox_token.is_empty() {
+                return Err("Please enter a NetBox API token.".to_string());
+            }
+            open_setting(&self.settings.netbox_token, self.repository.key()).ok_or("The stored NetBox token was sealed with another key; please enter it again.")?
+        } else {
+            let token = self.netbox_token_input.trim().to_string();
+            self.settings.netbox_token = seal_setting(&token, self.repository.key())?;
+            self.netbox_token_input.zeroize();
+            token
+        };
+        self.settings.netbox_url = url.clone();
+        self.settings.save().map_err(|e| format!("Could not save settings: {}", e))?;
+        Ok(NetboxClient::new(&url, token))
+    }
+
+    /// Fetches devices and virtual machines in the background; the result
+    /// arrives as an `AppEvent`.
+    pub fn pull_from_netbox(&mut self) {
+        match self.netbox_client() {
+            Ok(netbox) => {
+                self.netbox_busy = true;
+                let events = self.events.clone();
+                thread::spawn(move || events.publish(AppEvent::NetboxPulled(netbox.pull())));
+            }
+            Err(e) => self.error_message = Some(e),
+        }
+    }
+
+    pub fn push_to_netbox(&mut self) {
+        let updates: Vec<_> = self
+            .repository
+            .clients()
+            .iter()
+            .filter(|client| client.trashed_at.is_none())
+            .filter_map(|client| client.netbox.map(|link| (link, pushed_tags(client))))
+            .collect();
+        if updates.is_empty() {
+            self.error_message = Some("No clients have been pulled from NetBox yet.".to_string());
+            return;
+        }
+        match self.netbox_client() {
+            Ok(netbox) => {
+                self.netbox_busy = true;
+                let events = self.events.clone();
+                thread::spawn(move || events.publish(AppEvent::NetboxPushed(netbox.push_tags(&updates))));
+            }
+            Err(e) => self.error_message = Some(e),
+        }
+    }
+
+    /// Saves the Monitoring dialog. A newly entered token replaces the
+    /// stored one, and the next frame polls with the new settings.
+    pub fn save_monitoring_settings(&mut self) -> Result<(), String> {
+        let url = self.monitoring_url_input.trim().to_string();
+        if self.monitoring_system_input != MonitoringSystem::None {
+            if url.is_empty() {
+                return Err(format!("Please enter the {} address.", self.monitoring_system_input.name()));
+            }
+            if self.monitoring_token_input.trim().is_empty() && self.settings.monitoring_token.is_empty() {
+                return Err(format!("Please enter a {} API token.", self.monitoring_system_input.name()));
+            }
+        }
+        if !self.monitoring_token_input.trim().is_empty() {
+            self.settings.monitoring_token = seal_setting(self.monitoring_token_input.trim(), self.repository.key())?;
+            self.monitoring_token_input.zeroize();
+        }
+        self.settings.monitoring_system = self.monitoring_system_input;
+        self.settings.monitoring_url = url;
+        self.settings.monitoring_refresh_minutes = self.monitoring_refresh_input.max(1);
+        self.settings.save().map_err(|e| format!("Could not save settings: {}", e))?;
+        self.monitoring_status.clear();
+        self.monitoring_error = None;
+        self.last_monitoring_poll = None;
+        Ok(())
+    }
+
+    fn monitoring_interval(&self) -> Duration {
+        Duration::from_secs(60 * self.settings.monitoring_refresh_minutes.max(1) as u64)
+    }
+
+    /// Asks the monitoring system for host status in the background every
+    /// few minutes; the result arrives as an `AppEvent`.
+    pub fn poll_monitoring(&mut self) {
+        let system = self.settings.monitoring_system;
+        if system == MonitoringSystem::None || self.monitoring_busy || !self.is_unlocked() {
+            return;
+        }
+        if self.last_monitoring_poll.is_some_and(|last| last.elapsed() < self.monitoring_interval()) {
+            return;
+        }
+        self.last_monitoring_poll = Some(Instant::now());
+        let Some(token) = open_setting(&self.settings.monitoring_token, self.repository.key()) else {
+            self.monitoring_error = Some(format!("The stored {} token cannot be opened; please enter it again.", system.name()));
+            return;
+        };
+        self.monitoring_busy = true;
+        let url = self.settings.monitoring_url.clone();
+        let events = self.events.clone();
+        thread::spawn(move || {
+            let mut token = token;
+            let result = monitoring::fetch(system, &url, &token);
+            token.zeroize();
+            events.publish(AppEvent::MonitoringPolled(result));
+        });
+    }
+
+    /// What the monitoring system reports for the client's host, if it
+    /// monitors it.
+    pub fn monitored_status(&self, client: &Client) -> Option<HostStatus> {
+        self.monitoring_status.get(&client.endpoint().0.to_lowercase()).copied()
+    }
+
+    /// Probes every client in the background once the check interval has
+    /// passed; the result arrives as an `AppEvent`. With fingerprinting on,
+    /// the hosts that answered and have no `os:` tag are fingerprinted
+    /// after, once per unlock.
+    pub fn check_reachability(&mut self) {
+        let Some(seconds) = self.settings.reachability_check_seconds else {
+            self.reachability.clear();
+            return;
+        };
+        if self.reachability_busy || !self.is_unlocked() {
+            return;
+        }
+        if self.last_reachability_check.is_some_and(|last| last.elapsed() < Duration::from_secs(seconds.max(5) as u64)) {
+            return;
+        }
+        self.last_reachability_check = Some(Instant::now());
+        let targets: Vec<Target> = self.repository.clients().iter().filter_map(|client| Target::of(client, &self.settings.default_ports)).collect();
+        let fingerprint = self.settings.fingerprint_hosts && !self.fingerprint_busy;
+        let candidates: Vec<Candidate> = self
+            .repository
+            .clients()
+            .iter()
+            .filter(|client| fingerprint && !has_os_tag(client) && !self.fingerprinted.contains(&client.id))
+            .filter_map(|client| {
+                let target = Target::of(client, &self.settings.default_ports)?;
+                let ssh_port = if client.protocol == Protocol::Ssh { target.port } else { Protocol::Ssh.default_port() };
+                Some(Candidate { client_id: target.client_id, host: target.host, ssh_port })
+            })
+            .collect();
+        self.reachability_busy = true;
+        self.fingerprint_busy = !candidates.is_empty();
+        let fingerprint = self.fingerprint_busy;
+        let events = self.events.clone();
+        thread::spawn(move || {
+            let results = probe_all(targets);
+            let answered = |id: &String| results.iter().any(|(probed, answer)| probed == id && answer.is_some());
+            let candidates: Vec<Candidate> = candidates.into_iter().filter(|candidate| answered(&candidate.client_id)).collect();
+            events.publish(AppEvent::ReachabilityChecked(results));
+            if fingerprint {
+                events.publish(AppEvent::HostsFingerprinted(fingerprint_all(candidates)));
+            }
+        });
+    }
+
+    /// Tags each fingerprinted client with the system its host seems to
+    /// run, unless it got an `os:` tag in the meantime.
+    pub fn tag_fingerprinted(&mut self, guesses: Vec<(String, Option<OsGuess>)>) {
+        let mut tagged = 0;
+        for (client_id, guess) in guesses {
+            self.fingerprinted.insert(client_id.clone());
+            let Some(guess) = guess else {
+                continue;
+            };
+            let Some(mut client) = self.repository.get(&client_id).filter(|client| !has_os_tag(client)).cloned() else {
+                continue;
+            };
+            client.tags.push(guess.tag().to_string());
+            self.repository.update(client);
+            tagged += 1;
+        }
+        if tagged > 0 {
+            self.save_clients();
+            let clients = if tagged == 1 { "client" } else { "clients" };
+            self.notification = Some(format!("Tagged {} {} with the system they seem to run.", tagged, clients));
+        }
+    }
+
+    /// Starts or stops LAN presence to match the settings and announces the
+    /// sessions that are open. Dry-run sessions are not announced.
+    pub fn sync_presence(&mut self) {
+        if !self.settings.lan_presence {
+            self.presence = None;
+            return;
+        }
+        if self.presence.is_none() {
+            let name = match self.settings.presence_name.trim() {
+                "" => presence::os_user(),
+                name => name.to_string(),
+            };
+            match LanPresence::start(&name, self.events.clone()) {
+                Ok(started) => self.presence = Some(started),
+                Err(e) => {
+                    // Off until turned on again rather than retried every frame.
+                    self.settings.lan_presence = false;
+                    self.error_message = Some(e);
+                    return;
+                }
+            }
+        }
+        let sessions = self
+            .sessions
+            .active()
+            .into_iter()
+            .filter(|session| !session.simulated)
+            .map(|session| PeerSession { client_name: session.client_name, address: session.address })
+            .collect();
+        if let Some(Err(e)) = self.presence.as_mut().map(|presence| presence.announce(sessions)) {
+            self.notification = Some(e);
+        }
+    }
+
+    /// Merges pulled objects into the vault. A pull that finishes after the
+    /// vault was locked is dropped rather than merged into the empty list.
+    pub fn apply_netbox_pull(&mut self, objects: Vec<NetboxObject>) {
+        if !self.is_unlocked() {
+            return;
+        }
+        let mut clients = self.repository.clients().to_vec();
+        let summary = merge_objects(&mut clients, objects);
+        self.repository.replace_all(clients);
+        self.save_clients();
+        self.info_message = Some(format!("NetBox: {} new and {} updated clients", summary.added, summary.updated));
+        self.mode = AppMode::Normal;
+    }
+
+    /// Remembers the format and field names chosen in the inventory dialog,
+    /// then writes the inventory.
+    pub fn export_inventory(&mut self) {
+        let field_names: BTreeMap<String, String> = self
+            .inventory_field_names_input
+            .iter()
+            .filter(|(field, name)| name.trim() != *field)
+            .map(|(field, name)| (field.to_string(), name.trim().to_string()))
+            .collect();
+        let duplicates = duplicate_names(&field_names);
+        if !duplicates.is_empty() {
+            self.error_message = Some(format!("Each field needs a name of its own; used more than once: {}", duplicates.join(", ")));
+            return;
+        }
+        self.settings.inventory_format = self.inventory_format_input;
+        self.settings.inventory_field_names = field_names;
+        if let Err(e) = self.settings.save() {
+            self.error_message = Some(format!("Could not save settings: {}", e));
+            return;
+        }
+        let result = export_inventory(self.repository.clients(), self.settings.inventory_format, &self.settings.inventory_field_names);
+        match result.and_then(|data| fs::write(&self.transfer.path, data).map_err(|e| e.to_string())) {
+            Ok(()) => {
+                self.info_message = Some(format!("Exported the inventory of {} clients to {}", self.repository.clients().len(), self.transfer.path));
+                self.mode = AppMode::Normal;
+            }
+            Err(e) => self.error_message = Some(format!("Export failed: {}", e)),
+        }
+    }
+
+    /// Opens the contact of the folder at `group` for editing.
+    pub fn open_group_contact(&mut self, group: &str) {
+        let contact = self.repository.group_contacts.get(group).cloned().unwrap_or_default();
+        self.group_contact_input = (group.to_string(), contact);
+        self.mode = AppMode::GroupContact;
+    }
+
+    /// Stores the contact being edited for its group, or removes the
+    /// group's contact when every field was cleared.
+    pub fn save_group_contact(&mut self) {
+        let (group, contact) = std::mem::take(&mut self.group_contact_input);
+        if contact.is_empty() {
+            self.repository.group_contacts.remove(&group);
+        } else {
+            self.repository.group_contacts.insert(group, contact);
+        }
+        self.save_clients();
+        self.mode = AppMode::Normal;
+    }
+
+    /// Opens the .rdp export for the folder at `group`, suggesting a
+    /// directory named after it.
+    pub fn open_rdp_export(&mut self, group: &str) {
+        let name = group.rsplit('/').next().unwrap_or(group);
+        self.transfer.open(name);
+        self.transfer.group = group.to_string();
+        self.transfer.rdp_passwords = false;
+        self.mode = AppMode::ExportingRdp;
+    }
+
+    /// Writes the RDP clients in the export's folder and its subfolders,
+    /// outside the Trash, as .rdp files.
+    pub fn export_rdp_files(&mut self) {
+        let base: Vec<&str> = self.transfer.group.split('/').map(str::trim).filter(|name| !name.is_empty()).collect();
+        let clients: Vec<Client> = self
+            .resolved_clients()
+            .into_iter()
+            .filter(|c| c.trashed_at.is_none() && !c.info_only && c.protocol == Protocol::Rdp)
+            .filter(|c| c.group_path().take(base.len()).eq(base.iter().copied()))
+            .map(|c| c.with_default_port(&self.settings.default_ports).into_owned())
+            .collect();
+        if clients.is_empty() {
+            self.error_message = Some(format!("{} holds no Remote Desktop clients to export.", self.transfer.group));
+            return;
+        }
+        let folder = PathBuf::from(self.transfer.path.trim());
+        match rdp::export(&clients, &self.transfer.group, &folder, self.transfer.rdp_passwords, &self.settings.rdp_options) {
+            Ok(count) => {
+                self.info_message = Some(format!("Exported {} .rdp files to {}", count, folder.display()));
+                self.mode = AppMode::Normal;
+            }
+            Err(e) => self.error_message = Some(format!("Export failed: {}", e)),
+        }
+    }
+
+    /// Clients the reachability check or the monitoring system currently
+    /// reports as down.
+    pub fn down_hosts(&self) -> Vec<DownHost> {
+        let mut down = Vec::new();
+        for client in self.repository.query(&|client| client.trashed_at.is_none() && !client.info_only) {
+            let mut reasons = Vec::new();
+            if let Some(None) = self.reachability.get(&client.id) {
+                reasons.push(format!("no answer on port {}", client.effective_port(&self.settings.default_ports)));
+            }
+            if self.monitored_status(client) == Some(HostStatus::Down) {
+                reasons.push(format!("down in {}", self.settings.monitoring_system.name()));
+            }
+            if !reasons.is_empty() {
+                down.push(DownHost {
+                    name: client.name.clone(),
+                    address: client.address(),
+                    reason: reasons.join(", "),
+                    last_seen: self.last_seen.get(&client.id).copied(),
+                });
+            }
+        }
+        down
+    }
+
+    /// The handover report for the range and format in the dialog.
+    pub fn handover_report(&self) -> Result<String, String> {
+        let parse = |text: &str, field: &str| {
+            NaiveDateTime::parse_from_str(text.trim(), HANDOVER_TIME)
+                .ok()
+                .and_then(|time| Local.from_local_datetime(&time).earliest())
+                .ok_or_else(|| format!("Please enter the {} time as YYYY-MM-DD HH:MM.", field))
+        };
+        let (from, to) = (parse(&self.report_from_input, "start")?, parse(&self.report_to_input, "end")?);
+        if from > to {
+            return Err("The report has to start before it ends.".to_string());
+        }
+        Ok(handover::render(&self.repository.history, &self.down_hosts(), from, to, self.report_format_input))
+    }
+
+    pub fn save_handover_report(&mut self) {
+        match self.handover_report().and_then(|report| fs::write(&self.transfer.path, report).map_err(|e| e.to_string())) {
+            Ok(()) => {
+                self.info_message = Some(format!("Saved the handover report to {}", self.transfer.path));
+                self.mode = AppMode::Normal;
+            }
+            Err(e) => self.error_message = Some(format!("Could not save the report: {}", e)),
+        }
+    }
+
+    pub fn copy_handover_report(&mut self) {
+        let copied = self.handover_report().and_then(|report| {
+            let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
+            clipboard.set_text(report).map_err(|e| e.to_string())
+        });
+        match copied {
+            Ok(()) => self.notification = Some("Copied the handover report".to_string()),
+            Err(e) => self.error_message = Some(format!("Could not copy the report: {}", e)),
+        }
+    }
+
+    /// Copies the hosts currently down as a Markdown table.
+    pub fn copy_down_hosts(&mut self) {
+        let table = handover::down_hosts_table(&self.down_hosts());
+        let copied = Clipboard::new().and_then(|mut clipboard| clipboard.set_text(table));
+        match copied {
+            Ok(()) => self.notification = Some("Copied the list of down hosts".to_string()),
+            Err(e) => self.error_message = Some(format!("Could not copy the list: {}", e)),
+        }
+    }
+
+    pub fn export_down_hosts(&mut self) {
+        let down = self.down_hosts();
+        let path = self.down_hosts_path_input.trim().to_string();
+        match handover::down_hosts_csv(&down).and_then(|data| fs::write(&path, data).map_err(|e| e.to_string())) {
+            Ok(()) => self.info_message = Some(format!("Exported {} down hosts to {}", down.len(), path)),
+            Err(e) => self.error_message = Some(format!("Export failed: {}", e)),
+        }
+    }
+
+    pub fn import_clients_json(&mut self) {
+        match fs::read_to_string(&self.transfer.path).map_err(|e| e.to_string()).and_then(|data| import_bundle(&data, &self.transfer.passphrase)) {
+            Ok(imported) => {
+                let summary = self.merge_import(imported);
+                self.info_message = Some(format!("Imported {} new and updated {} existing clients", summary.added, summary.updated));
+                self.transfer.passphrase.clear();
+            }
+            Err(e) => self.error_message = Some(format!("Import failed:\n{}", e)),
+        }
+    }
+
+    /// Imports any files dropped into the watch folder once they are fully
+    /// written, then moves them into an `imported` (or `failed`) subfolder.
+    pub fn poll_watch_folder(&mut self) {
+        if !self.is_unlocked() || self.settings.watch_folder.is_empty() || self.last_watch_scan.elapsed() < WATCH_INTERVAL {
+            return;
+        }
+        self.last_watch_scan = Instant::now();
+        let files = self.folder_watch.ready_files(&PathBuf::from(&self.settings.watch_folder));
+        if files.is_empty() {
+            return;
+        }
+
+        let before = self.repository.clients().to_vec();
+        let mut clients = before.clone();
+        let mut added = 0;
+        let mut updated = 0;
+        let mut skipped = 0;
+        let mut problems = Vec::new();
+        let mut unmoved = Vec::new();
+        for (path, digest) in files {
+            self.folder_watch.mark_imported(digest);
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            let archived = match read_import_file(&path) {
+                Ok(imported) => {
+                    // Nobody is there to ask, so duplicates are left out.
+                    let (clean, conflicts) = split_conflicts(&clients, imported);
+                    let summary = merge_clients(&mut clients, clean);
+                    added += summary.added;
+                    updated += summary.updated;
+                    skipped += conflicts.len();
+                    archive(&path, "imported")
+                }
+                Err(e) => {
+                    problems.push(format!("{}: {}", file_name, e));
+                    archive(&path, "failed")
                 }
+            };
+            if let Err(e) = archived {
+                unmoved.push(format!("{}: {}", file_name, e));
+            }
+        }
+        if !unmoved.is_empty() {
+            self.error_message = Some(format!(
+                "Could not move imported files out of the watch folder. They stay there, but are not imported again until the app restarts.\n{}",
+                unmoved.join("\n")
+            ));
+        }
+
+        if added + updated > 0 {
+            self.import_undo = Some(ImportUndo::capture(&before, &clients));
+            self.repository.replace_all(clients);
+            self.save_clients();
+        }
+        let mut message = format!("Auto-import: {} new, {} updated", added, updated);
+        if skipped > 0 {
+            message.push_str(&format!(", {} duplicates skipped", skipped));
+        }
+        if !problems.is_empty() {
+            message.push_str(&format!(", {} failed ({})", problems.len(), problems.join("; ")));
+        }
+        self.notification = Some(message);
+    }
+
+    pub fn undo_last_import(&mut self) {
+        if let Some(undo) = self.import_undo.take() {
+            let mut clients = self.repository.clients().to_vec();
+            undo.apply(&mut clients);
+            self.repository.replace_all(clients);
+            self.save_clients();
+        }
+        self.notification = None;
+    }
+
+    /// Connects to the client matching a link opened from outside the app,
+    /// as the link's user when it names one, or asks whether to create one
+    /// when no stored client has that address.
+    pub fn handle_pending_link(&mut self) {
+        let Some(link) = self.pending_link.as_ref() else {
+            return;
+        };
+        if !self.is_unlocked() || !matches!(self.mode, AppMode::Normal) {
+            return;
+        }
+        let Some(index) = self.repository.clients().iter().position(|c| {
+            !c.info_only && c.trashed_at.is_none() && c.protocol.scheme() == link.scheme && matches_address(c, &link.address, &self.settings.default_ports)
+        }) else {
+            self.mode = AppMode::CreateFromLink;
+            return;
+        };
+        let client = link.account_for(&self.repository.clients()[index].with_credential(&self.repository.credentials));
+        self.pending_link = None;
+        self.client_list.select(&client);
+        self.request_connect(&client);
+    }
+
+    /// Runs a command-line action once the vault is unlocked. Commands sent
+    /// by later launches replace one that has not run yet.
+    pub fn handle_pending_command(&mut self) {
+        if let Some(receiver) = &self.remote_commands {
+            while let Ok(command) = receiver.try_recv() {
+                self.pending_command = Some(command);
+            }
+        }
+        if self.pending_command.is_none() || !self.is_unlocked() || !matches!(self.mode, AppMode::Normal) {
+            return;
+        }
+        match self.pending_command.take().unwrap() {
+            RemoteCommand::Connect(name) => {
+                let Some(index) = self
+                    .repository
+                    .clients()
+                    .iter()
+                    .position(|c| c.trashed_at.is_none() && c.name.eq_ignore_ascii_case(name.trim()))
+                else {
+                    self.error_message = Some(format!("No client named {}.", name));
+                    return;
+                };
+                let client = self.repository.clients()[index].clone();
+                self.client_list.select(&client);
+                self.request_connect(&client);
+            }
+            RemoteCommand::Search(query) => {
+                self.search_query = query;
+                self.mode = AppMode::Search;
+            }
+        }
+    }
+
+    pub fn add_client_from_form(&mut self) {
+        let mut client = Client::default();
+        if let Err(e) = self.form.apply_to(&mut client) {
+            self.error_message = Some(e);
+            return;
+        }
+        self.repository.add(client);
+        self.form.clear();
+        self.save_clients();
+        self.mode = AppMode::Normal;
+    }
+
+    pub fn update_client_from_form(&mut self, index: usize) {
+        let mut client = self.repository.clients()[index].clone();
+        if let Err(e) = self.form.apply_to(&mut client) {
+            self.error_message = Some(e);
+            return;
+        }
+        self.repository.update(client);
+        self.form.clear();
+        self.save_clients();
+        self.mode = AppMode::Normal;
+    }
+
+    /// Moves long-expired clients to the Trash, at most once a minute.
+    pub fn check_expired_clients(&mut self) {
+        let Some(grace_days) = self.settings.trash_expired_after_days else {
+            return;
+        };
+        if self.last_expiry_check.map(|checked| checked.elapsed().as_secs() < 60).unwrap_or(false) {
+            return;
+        }
+        self.last_expiry_check = Some(Instant::now());
+        let mut clients = self.repository.clients().to_vec();
+        let moved = trash_expired(&mut clients, grace_days, today());
+        if moved > 0 {
+            self.repository.replace_all(clients);
+            self.save_clients();
+            self.notification = Some(format!("Moved {} expired clients to the Trash", moved));
+        }
+    }
+}
+
+impl Default for AppState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl eframe::App for AppState {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        self.system_theme = frame.info().system_theme;
+        self.ui(ctx);
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.save_layout();
+    }
+}
+
+impl AppState {
+    /// The command whose shortcut was pressed this frame. Shortcuts that
+    /// act on the client list only count while it is shown, and those
+    /// without Ctrl or Alt are left to a text field that has the keyboard.
+    fn pressed_shortcut(&self, ctx: &egui::Context) -> Option<Shortcut> {
+        let typing = ctx.wants_keyboard_input();
+        shortcuts::bindings(&self.settings.shortcuts)
+            .into_iter()
+            .filter(|(command, shortcut)| {
+                (self.mode == AppMode::Normal || !command.acts_on_list()) && (!typing || Shortcut::works_while_typing(shortcut))
+            })
+            .find(|(_, shortcut)| ctx.input_mut(|input| input.consume_shortcut(shortcut)))
+            .map(|(command, _)| command)
+    }
+
+    /// Every keyboard shortcut but Lock ends up here.
+    fn run_shortcut(&mut self, ctx: &egui::Context, command: Shortcut) {
+        match command {
+            Shortcut::NewClient => self.apply_menu_action(MenuAction::New),
+            Shortcut::Edit => self.apply_menu_action(MenuAction::Edit),
+            Shortcut::Remove => self.apply_menu_action(MenuAction::Remove),
+            Shortcut::Find => ctx.memory_mut(|memory| memory.request_focus(filter_id())),
+            Shortcut::Connect => {
+                if let Some(client) = self.client_list.selected_client(self.repository.clients()).filter(|client| !client.info_only).cloned() {
+                    self.request_connect(&client);
+                }
+            }
+            Shortcut::Previous => self.move_selection(-1),
+            Shortcut::Next => self.move_selection(1),
+            Shortcut::Lock => self.lock(),
+            Shortcut::Help => self.apply_menu_action(MenuAction::Help),
+        }
+    }
+
+    /// One frame of the whole app; separate from `update` so tests can run
+    /// it on a headless context.
+    pub fn ui(&mut self, ctx: &egui::Context) {
+        self.apply_theme(ctx);
+        self.handle_os_events();
+        self.handle_repository_events();
+        self.handle_app_events(ctx);
+        self.poll_unlock();
+        self.poll_hello_enrollment();
+        self.sync_presence();
+        self.check_session_limits(ctx);
+        if !self.is_unlocked() {
+            self.message_windows(ctx);
+            self.unlock_view(ctx);
+            return;
+        }
+        (self.window_size, self.window_position) = ctx.input(|input| {
+            let viewport = input.viewport();
+            (viewport.inner_rect.map(|rect| rect.size()), viewport.outer_rect.map(|rect| rect.min))
+        });
+        self.check_idle_lock(ctx);
+        if !self.is_unlocked() {
+            self.unlock_view(ctx);
+            return;
+        }
+        if let Some(layout) = self.pending_layout.take() {
+            self.restore_layout(ctx, layout);
+        }
+        match self.pressed_shortcut(ctx) {
+            Some(Shortcut::Lock) => {
+                self.lock();
+                self.unlock_view(ctx);
+                return;
+            }
+            Some(command) => self.run_shortcut(ctx, command),
+            None => {}
+        }
+        self.poll_watch_folder();
+        self.poll_monitoring();
+        self.check_reachability();
+        self.check_expired_clients();
+        self.handle_pending_link();
+        self.handle_pending_command();
+        if !self.settings.watch_folder.is_empty() {
+            ctx.request_repaint_after(WATCH_INTERVAL);
+        }
+        if self.settings.monitoring_system != MonitoringSystem::None {
+            ctx.request_repaint_after(self.monitoring_interval());
+        }
+        if let Some(seconds) = self.settings.reachability_check_seconds {
+            ctx.request_repaint_after(Duration::from_secs(seconds.max(5) as u64));
+        }
+
+        self.menu_bar(ctx);
+        self.message_windows(ctx);
+        self.connect_confirmation_window(ctx);
+        self.clipboard_warning_window(ctx);
+        self.pinned_note_window(ctx);
+        self.help_window(ctx);
+        self.connect_failure_window(ctx);
+        self.screenshot_window(ctx);
+        self.status_bar(ctx);
+        self.notification_bar(ctx);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            self.client_list(ui);
+            ui.separator();
+            match self.mode {
+                AppMode::Adding | AppMode::Editing => self.editor_panel(ui),
+                AppMode::Normal => self.selection_panel(ui),
+                _ => self.mode_dialog(ui),
             }
         });
     }
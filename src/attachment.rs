@@ -0,0 +1,64 @@
+use arboard::Clipboard;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use uuid::Uuid;
+
+/// A screenshot kept with a client. The PNG bytes live inside the encrypted
+/// vault like every other client field.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct Attachment {
+    pub id: String,
+    pub width: u32,
+    pub height: u32,
+    #[serde(serialize_with = "to_base64", deserialize_with = "from_base64")]
+    pub png: Vec<u8>,
+}
+
+impl Attachment {
+    pub fn from_rgba(width: u32, height: u32, rgba: &[u8]) -> Result<Self, String> {
+        let mut png_data = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut png_data, width, height);
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+            writer.write_image_data(rgba).map_err(|e| e.to_string())?;
+        }
+        Ok(Self {
+            id: Uuid::new_v4().to_string(),
+            width,
+            height,
+            png: png_data,
+        })
+    }
+
+    /// Takes the image currently on the clipboard, e.g. a fresh screenshot.
+    pub fn from_clipboard() -> Result<Self, String> {
+        let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
+        let image = clipboard.get_image().map_err(|_| "The clipboard does not contain an image".to_string())?;
+        Self::from_rgba(image.width as u32, image.height as u32, &image.bytes)
+    }
+
+    pub fn to_rgba(&self) -> Result<Vec<u8>, String> {
+        let mut decoder = png::Decoder::new(self.png.as_slice());
+        decoder.set_transformations(png::Transformations::EXPAND);
+        let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer).map_err(|e| e.to_string())?;
+        if info.color_type != png::ColorType::Rgba || info.bit_depth != png::BitDepth::Eight {
+            return Err("Unsupported image format".to_string());
+        }
+        buffer.truncate(info.buffer_size());
+        Ok(buffer)
+    }
+}
+
+fn to_base64<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&STANDARD.encode(data))
+}
+
+fn from_base64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    STANDARD.decode(text).map_err(serde::de::Error::custom)
+}
@@ -0,0 +1,298 @@
+//! JSON export of the whole vault, either in plaintext without passwords for
+//! configuration-management repositories or encrypted as a whole with an
+//! export passphrase for moving clients between installs. The formats are
+//! described in `docs/json-export.md`.
+
+use crate::client::{Client, Contact, Protocol};
+use crate::encryption::{decrypt, derive_key, encrypt, generate_key_from_password, generate_salt, SALT_SIZE};
+use crate::template::{is_valid_name, BUILT_IN};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+
+pub const BUNDLE_FORMAT: &str = "remote_desktop_manager";
+pub const BUNDLE_VERSION: u64 = 1;
+pub const ENCRYPTED_FORMAT: &str = "remote_desktop_manager_encrypted";
+pub const ENCRYPTED_KDF: &str = "argon2id";
+
+#[derive(Serialize, Deserialize)]
+pub struct Bundle {
+    pub format: String,
+    pub version: u64,
+    /// `"omitted"`, `"included"` (only inside an encrypted bundle) or
+    /// `"encrypted"` (separately encrypted secrets of older exports).
+    pub secrets: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encrypted_secrets: Option<String>,
+    pub clients: Vec<BundleClient>,
+}
+
+/// A bundle encrypted as a whole, passwords included.
+#[derive(Serialize, Deserialize)]
+pub struct EncryptedBundle {
+    pub format: String,
+    pub version: u64,
+    pub kdf: String,
+    /// Base64 of the Argon2id salt for the export passphrase.
+    pub salt: String,
+    /// Base64 of the AES-256-GCM encrypted bundle.
+    pub data: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BundleClient {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    pub ip: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub reference: String,
+    #[serde(default)]
+    pub protocol: Protocol,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub group: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub username: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub domain: String,
+    /// Only written inside an encrypted bundle.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub password: String,
+    #[serde(default)]
+    pub info_only: bool,
+    #[serde(default)]
+    pub owner: Contact,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub variables: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<NaiveDate>,
+}
+
+fn bundle_client(client: &Client, with_password: bool) -> BundleClient {
+    BundleClient {
+        id: client.id.clone(),
+        name: client.name.clone(),
+        ip: client.ip.clone(),
+        port: client.port,
+        reference: client.reference.clone(),
+        protocol: client.protocol,
+        group: client.group.clone(),
+        username: client.username.clone(),
+        domain: client.domain.clone(),
+        password: if with_password { client.password.clone() } else { String::new() },
+        info_only: client.info_only,
+        owner: client.owner.clone(),
+        tags: client.tags.clone(),
+        variables: client.variables.clone(),
+        expires: client.expires,
+    }
+}
+
+fn bundle(clients: &[Client], with_passwords: bool) -> Bundle {
+    Bundle {
+        format: BUNDLE_FORMAT.to_string(),
+        version: BUNDLE_VERSION,
+        secrets: if with_passwords { "included" } else { "omitted" }.to_string(),
+        encrypted_secrets: None,
+        clients: clients
+            .iter()
+            .filter(|client| client.trashed_at.is_none())
+            .map(|client| bundle_client(client, with_passwords))
+            .collect(),
+    }
+}
+
+/// Serializes every client in plaintext, without passwords.
+pub fn export_bundle(clients: &[Client]) -> Result<String, String> {
+    serde_json::to_string_pretty(&bundle(clients, false)).map_err(|e| e.to_string())
+}
+
+/// Serializes every client with its password and encrypts the result with a
+/// key derived from `passphrase`.
+pub fn export_encrypted_bundle(clients: &[Client], passphrase: &str) -> Result<String, String> {
+    let data = serde_json::to_vec(&bundle(clients, true)).map_err(|e| e.to_string())?;
+    let salt = generate_salt();
+    let encrypted = encrypt(&data, &derive_key(passphrase, &salt)).map_err(|_| "Encryption failed")?;
+    let envelope = EncryptedBundle {
+        format: ENCRYPTED_FORMAT.to_string(),
+        version: BUNDLE_VERSION,
+        kdf: ENCRYPTED_KDF.to_string(),
+        salt: STANDARD.encode(salt),
+        data: STANDARD.encode(encrypted),
+    };
+    serde_json::to_string_pretty(&envelope).map_err(|e| e.to_string())
+}
+
+/// Decrypts an encrypted bundle back to the plaintext bundle it carries.
+fn decrypt_bundle(value: Value, passphrase: &str) -> Result<Value, String> {
+    let envelope: EncryptedBundle = serde_json::from_value(value).map_err(|e| format!("$: {}", e))?;
+    if envelope.version != BUNDLE_VERSION {
+        return Err(format!("$.version: unsupported version {}", envelope.version));
+    }
+    if envelope.kdf != ENCRYPTED_KDF {
+        return Err(format!("$.kdf: expected \"{}\", found \"{}\"", ENCRYPTED_KDF, envelope.kdf));
+    }
+    if passphrase.is_empty() {
+        return Err("This export is encrypted; please enter its passphrase.".to_string());
+    }
+    let salt: [u8; SALT_SIZE] = STANDARD
+        .decode(&envelope.salt)
+        .ok()
+        .and_then(|salt| salt.try_into().ok())
+        .ok_or("$.salt: expected a base64 encoded 16-byte salt")?;
+    let encrypted = STANDARD.decode(&envelope.data).map_err(|e| format!("$.data: {}", e))?;
+    let data = decrypt(&encrypted, &derive_key(passphrase, &salt)).map_err(|_| "Incorrect export passphrase")?;
+    serde_json::from_slice(&data).map_err(|e| format!("$.data: {}", e))
+}
+
+/// Checks a parsed bundle against the schema, returning every problem found
+/// with the JSON path it refers to.
+pub fn validate_bundle(value: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    let Some(root) = value.as_object() else {
+        return vec!["$: expected an object".to_string()];
+    };
+    match root.get("format").and_then(Value::as_str) {
+        Some(BUNDLE_FORMAT) => {}
+        Some(other) => errors.push(format!("$.format: expected \"{}\", found \"{}\"", BUNDLE_FORMAT, other)),
+        None => errors.push("$.format: missing or not a string".to_string()),
+    }
+    match root.get("version").and_then(Value::as_u64) {
+        Some(BUNDLE_VERSION) => {}
+        Some(other) => errors.push(format!("$.version: unsupported version {}", other)),
+        None => errors.push("$.version: missing or not a number".to_string()),
+    }
+    match root.get("secrets").and_then(Value::as_str) {
+        Some("omitted" | "included") => {}
+        Some("encrypted") => {
+            if !root.get("encrypted_secrets").map(Value::is_string).unwrap_or(false) {
+                errors.push("$.encrypted_secrets: required when secrets is \"encrypted\"".to_string());
+            }
+        }
+        _ => errors.push("$.secrets: expected \"omitted\", \"included\" or \"encrypted\"".to_string()),
+    }
+    let Some(clients) = root.get("clients").and_then(Value::as_array) else {
+        errors.push("$.clients: missing or not an array".to_string());
+        return errors;
+    };
+    for (index, client) in clients.iter().enumerate() {
+        let Some(client) = client.as_object() else {
+            errors.push(format!("$.clients[{}]: expected an object", index));
+            continue;
+        };
+        for (field, required) in [("id", false), ("name", true), ("ip", true), ("reference", false), ("group", false), ("username", false), ("domain", false), ("password", false)] {
+            match client.get(field) {
+                Some(Value::String(_)) => {}
+                Some(_) => errors.push(format!("$.clients[{}].{}: expected a string", index, field)),
+                None if required => errors.push(format!("$.clients[{}].{}: missing", index, field)),
+                None => {}
+            }
+        }
+        if let Some(port) = client.get("port") {
+            if !port.as_u64().is_some_and(|port| (1..=65535).contains(&port)) {
+                errors.push(format!("$.clients[{}].port: expected a number from 1 to 65535", index));
+            }
+        }
+        if let Some(protocol) = client.get("protocol") {
+            if !matches!(protocol.as_str(), Some("rdp" | "ssh" | "vnc")) {
+                errors.push(format!("$.clients[{}].protocol: expected \"rdp\", \"ssh\" or \"vnc\"", index));
+            }
+        }
+        if client.get("info_only").map(|value| !value.is_boolean()).unwrap_or(false) {
+            errors.push(format!("$.clients[{}].info_only: expected true or false", index));
+        }
+        match client.get("owner") {
+            Some(Value::Object(owner)) => {
+                for field in ["name", "email", "phone", "on_call_url"] {
+                    if !owner.get(field).map(Value::is_string).unwrap_or(false) {
+                        errors.push(format!("$.clients[{}].owner.{}: missing or not a string", index, field));
+                    }
+                }
+            }
+            Some(_) => errors.push(format!("$.clients[{}].owner: expected an object", index)),
+            None => {}
+        }
+        match client.get("tags") {
+            Some(Value::Array(tags)) => {
+                for (tag_index, tag) in tags.iter().enumerate() {
+                    if !tag.as_str().map(|tag| !tag.trim().is_empty() && !tag.contains(',')).unwrap_or(false) {
+                        errors.push(format!("$.clients[{}].tags[{}]: expected a non-empty string without commas", index, tag_index));
+                    }
+                }
+            }
+            Some(_) => errors.push(format!("$.clients[{}].tags: expected an array", index)),
+            None => {}
+        }
+        match client.get("variables") {
+            Some(Value::Object(variables)) => {
+                for (name, value) in variables {
+                    if !is_valid_name(name) || BUILT_IN.contains(&name.as_str()) {
+                        errors.push(format!("$.clients[{}].variables.{}: invalid variable name", index, name));
+                    } else if !value.is_string() {
+                        errors.push(format!("$.clients[{}].variables.{}: expected a string", index, name));
+                    }
+                }
+            }
+            Some(_) => errors.push(format!("$.clients[{}].variables: expected an object", index)),
+            None => {}
+        }
+        if let Some(expires) = client.get("expires") {
+            if !expires.as_str().map(|text| NaiveDate::parse_from_str(text, "%Y-%m-%d").is_ok()).unwrap_or(false) {
+                errors.push(format!("$.clients[{}].expires: expected a YYYY-MM-DD date", index));
+            }
+        }
+    }
+    errors
+}
+
+/// Parses and validates a bundle, decrypting it first if it was encrypted as
+/// a whole. Passwords are restored from encrypted bundles, and from the
+/// separately encrypted secrets of older exports when a passphrase is given.
+pub fn import_bundle(data: &str, passphrase: &str) -> Result<Vec<Client>, String> {
+    let mut value: Value = serde_json::from_str(data).map_err(|e| format!("Invalid JSON: {}", e))?;
+    if value.get("format").and_then(Value::as_str) == Some(ENCRYPTED_FORMAT) {
+        value = decrypt_bundle(value, passphrase)?;
+    }
+    let errors = validate_bundle(&value);
+    if !errors.is_empty() {
+        return Err(errors.join("\n"));
+    }
+    let bundle: Bundle = serde_json::from_value(value).map_err(|e| e.to_string())?;
+
+    let mut secrets: HashMap<String, String> = HashMap::new();
+    if let (Some(encoded), false) = (&bundle.encrypted_secrets, passphrase.is_empty()) {
+        let encrypted = STANDARD.decode(encoded).map_err(|e| format!("$.encrypted_secrets: {}", e))?;
+        let data = decrypt(&encrypted, &generate_key_from_password(passphrase)).map_err(|_| "Incorrect export passphrase")?;
+        secrets = serde_json::from_slice(&data).map_err(|e| format!("$.encrypted_secrets: {}", e))?;
+    }
+
+    Ok(bundle
+        .clients
+        .into_iter()
+        .map(|client| Client {
+            password: secrets.remove(&client.id).unwrap_or(client.password),
+            id: client.id,
+            name: client.name,
+            ip: client.ip,
+            port: client.port,
+            reference: client.reference,
+            protocol: client.protocol,
+            group: client.group,
+            username: client.username,
+            domain: client.domain,
+            info_only: client.info_only,
+            owner: client.owner,
+            tags: client.tags,
+            variables: client.variables,
+            expires: client.expires,
+            ..Default::default()
+        })
+        .collect())
+}
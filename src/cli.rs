@@ -0,0 +1,298 @@
+//! `rdm list`, `rdm connect`, `rdm add` and `rdm export` for scripts and
+//! PowerShell. They open the same vault as the window, asking for the master
+//! key on stdin unless it is remembered for this OS session.
+
+use crate::app::AppState;
+use crate::bundle::export_bundle;
+use crate::client::{AppMode, KeyStatus, Protocol};
+use crate::collation;
+use crate::launcher::Launcher;
+use crate::repository::ClientRepository;
+use crate::secure_prompt;
+use crate::transfer::export_csv;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use eframe::egui;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+const SUBCOMMANDS: [&str; 4] = ["list", "connect", "add", "export"];
+
+fn command() -> Command {
+    Command::new("rdm")
+        .about("Remote Desktop Manager from the command line. Without a command the window opens.")
+        .arg(Arg::new("vault").long("vault").value_name("FILE").global(true).help("Vault to open instead of the default one"))
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("list")
+                .about("Prints name, address, protocol and folder of every client, tab-separated")
+                .arg(Arg::new("filter").long("filter").value_name("TEXT").help("Only clients whose name or address contains TEXT")),
+        )
+        .subcommand(
+            Command::new("connect")
+                .about("Connects to a client and waits until the session ends")
+                .arg(Arg::new("name").required(true))
+                .arg(Arg::new("yes").long("yes").action(ArgAction::SetTrue).help("Connect even when the client's tag asks for confirmation"))
+                .arg(Arg::new("dry-run").long("dry-run").action(ArgAction::SetTrue).help("Only log what would be started")),
+        )
+        .subcommand(
+            Command::new("add")
+                .about("Adds a client")
+                .arg(Arg::new("name").long("name").required(true))
+                .arg(Arg::new("ip").long("ip").required(true).help("Host name or address"))
+                .arg(Arg::new("protocol").long("protocol").value_parser(["rdp", "ssh", "vnc"]).default_value("rdp"))
+                .arg(Arg::new("group").long("group").help("Folder path such as Customers/Acme"))
+                .arg(Arg::new("port").long("port").help("Port when not the protocol's default"))
+                .arg(Arg::new("username").long("username")),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Writes every client without passwords, as the File > Export dialog does unencrypted")
+                .arg(Arg::new("format").long("format").value_parser(["csv", "json"]).default_value("csv"))
+                .arg(Arg::new("output").long("output").value_name("FILE").help("File to write; standard output when left out")),
+        )
+}
+
+/// Runs a command-line action when the first argument names one, returning
+/// the exit code; `None` leaves `args` to the window.
+pub fn run(args: &[String]) -> Option<i32> {
+    let first = args.get(1)?;
+    if !SUBCOMMANDS.contains(&first.as_str()) && first != "help" {
+        return None;
+    }
+    attach_console();
+    let matches = match command().try_get_matches_from(args) {
+        Ok(matches) => matches,
+        Err(e) => {
+            let _ = e.print();
+            return Some(if e.use_stderr() { 2 } else { 0 });
+        }
+    };
+    Some(match execute(&matches) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    })
+}
+
+fn execute(matches: &ArgMatches) -> Result<(), String> {
+    let mut state = match matches.get_one::<String>("vault") {
+        Some(path) => AppState::with_vault_file(PathBuf::from(path)),
+        None => AppState::new(),
+    };
+    open_vault(&mut state)?;
+    match matches.subcommand() {
+        Some(("list", args)) => {
+            let filter = args.get_one::<String>("filter").map(String::as_str).unwrap_or_default();
+            for client in state.repository.clients().iter().filter(|c| c.trashed_at.is_none()) {
+                if collation::contains(&client.name, filter) || collation::contains(&client.address(), filter) {
+                    println!("{}\t{}\t{}\t{}", client.name, client.address(), client.protocol.label(), client.group);
+                }
+            }
+            Ok(())
+        }
+        Some(("connect", args)) => connect(&mut state, args),
+        Some(("add", args)) => {
+            let get = |name: &str| args.get_one::<String>(name).cloned().unwrap_or_default();
+            let name = get("name").trim().to_string();
+            if name.is_empty() || get("ip").trim().is_empty() {
+                return Err("A client needs a name and an address.".to_string());
+            }
+            // The editor's form checks and normalizes the values as it does for the Add dialog.
+            state.form.clear();
+            state.form.name.clone_from(&name);
+            state.form.ip = get("ip").trim().to_string();
+            state.form.port = get("port");
+            state.form.protocol = Protocol::ALL.into_iter().find(|protocol| protocol.scheme() == get("protocol")).unwrap_or(Protocol::Rdp);
+            state.form.group = get("group");
+            state.form.username = get("username");
+            state.add_client_from_form();
+            match state.error_message.take() {
+                Some(e) => Err(e),
+                None => {
+                    println!("Added {}", name);
+                    Ok(())
+                }
+            }
+        }
+        Some(("export", args)) => {
+            let clients = state.resolved_clients();
+            let data = match args.get_one::<String>("format").map(String::as_str) {
+                Some("json") => export_bundle(&clients)?,
+                _ => export_csv(&clients)?,
+            };
+            match args.get_one::<String>("output") {
+                Some(path) => fs::write(path, data).map_err(|e| format!("Could not write {}: {}", path, e)),
+                None => io::stdout().write_all(data.as_bytes()).map_err(|e| e.to_string()),
+            }
+        }
+        _ => unreachable!("clap requires a subcommand"),
+    }
+}
+
+/// Unlocks the vault with the master key read from stdin, unless the key
+/// cache already opened it.
+fn open_vault(state: &mut AppState) -> Result<(), String> {
+    match state.key_status {
+        KeyStatus::Unlocked => return Ok(()),
+        KeyStatus::NotSet => return Err(format!("There is no vault at {}; create it in the app first.", state.repository.path().display())),
+        _ => {}
+    }
+    let key = read_master_key().map_err(|e| format!("Could not read the master key: {}", e))?;
+    unlock_with(state, &key)
+}
+
+fn unlock_with(state: &mut AppState, key: &str) -> Result<(), String> {
+    state.unlock_form.master_key = key.to_string();
+    state.unlock();
+    while state.pending_unlock.is_some() {
+        thread::sleep(Duration::from_millis(20));
+        state.poll_unlock();
+    }
+    match state.error_message.take() {
+        Some(e) => Err(e),
+        None if state.is_unlocked() => Ok(()),
+        None => Err("The vault could not be opened.".to_string()),
+    }
+}
+
+fn connect(state: &mut AppState, args: &ArgMatches) -> Result<(), String> {
+    let name = args.get_one::<String>("name").expect("name is required");
+    let client = state
+        .repository
+        .clients()
+        .iter()
+        .find(|c| c.trashed_at.is_none() && c.name.eq_ignore_ascii_case(name.trim()))
+        .cloned()
+        .ok_or_else(|| format!("No client named {}.", name))?;
+    if let Some(tag) = state.settings.confirmations.connect_tag(&client).filter(|_| !args.get_flag("yes")) {
+        return Err(format!("{} is tagged {}; pass --yes to connect anyway.", client.name, tag));
+    }
+    if let Some(note) = &client.pinned_note {
+        eprintln!("Pinned note: {}", note.text);
+    }
+    if args.get_flag("dry-run") {
+        state.launcher = Launcher::DryRun;
+    }
+    // There is nobody to warn about the clipboard, so passwords stay off it.
+    state.connect(&client, false);
+    if let Some(failure) = state.connect_failure.take() {
+        return Err(failure.error.message);
+    }
+    eprintln!("Connected to {}; waiting for the session to end.", client.name);
+    wait_for_sessions(state);
+    state.error_message.take().map_or(Ok(()), Err)
+}
+
+/// Keeps the process until the sessions end, so their starts and ends are
+/// recorded by the usual event handling.
+fn wait_for_sessions(state: &mut AppState) {
+    let ctx = egui::Context::default();
+    loop {
+        state.handle_app_events(&ctx);
+        if state.sessions.count() == 0 {
+            break;
+        }
+        thread::sleep(Duration::from_millis(250));
+    }
+    state.handle_app_events(&ctx);
+    state.mode = AppMode::Normal;
+}
+
+/// Connects for an `rdm://connect/<name>` link without opening the window:
+/// with the remembered master key, or one entered on the secure desktop.
+/// Returns false, having done nothing, when the window is needed instead:
+/// to enter the key, to confirm the connect or show its pinned note, or to
+/// show what went wrong.
+pub fn connect_link(name: &str) -> bool {
+    let mut state = AppState::new();
+    if !state.is_unlocked() {
+        if !secure_prompt::AVAILABLE || !matches!(state.key_status, KeyStatus::Locked) {
+            return false;
+        }
+        let message = format!("Enter the master key as the password to connect to {}.", name);
+        let Ok(Some(key)) = secure_prompt::ask("Remote Desktop Manager", &message) else {
+            return false;
+        };
+        if unlock_with(&mut state, &key).is_err() {
+            return false;
+        }
+    }
+    let Some(client) = state.repository.clients().iter().find(|c| c.trashed_at.is_none() && c.name.eq_ignore_ascii_case(name.trim())).cloned() else {
+        return false;
+    };
+    if state.settings.confirmations.connect_tag(&client).is_some() || client.pinned_note.is_some() {
+        return false;
+    }
+    state.connect(&client, false);
+    if state.connect_failure.is_some() {
+        return false;
+    }
+    wait_for_sessions(&mut state);
+    true
+}
+
+/// Reads one line without echoing it when stdin is a terminal, so the key
+/// can also be piped in by a script.
+fn read_master_key() -> io::Result<Zeroizing<String>> {
+    let terminal = io::stdin().is_terminal();
+    if terminal {
+        eprint!("Master key: ");
+        io::stderr().flush()?;
+    }
+    let echo = terminal.then(hide_input);
+    let mut line = Zeroizing::new(String::new());
+    let read = io::stdin().lock().read_line(&mut line);
+    if let Some(Some(restore)) = echo {
+        restore();
+        eprintln!();
+    }
+    read?;
+    let trimmed = Zeroizing::new(line.trim_end_matches(['\r', '\n']).to_string());
+    Ok(trimmed)
+}
+
+/// Turns off echo on the terminal, returning how to turn it back on.
+#[cfg(windows)]
+fn hide_input() -> Option<Box<dyn FnOnce()>> {
+    use winapi::um::consoleapi::{GetConsoleMode, SetConsoleMode};
+    use winapi::um::processenv::GetStdHandle;
+    use winapi::um::winbase::STD_INPUT_HANDLE;
+    use winapi::um::wincon::ENABLE_ECHO_INPUT;
+    unsafe {
+        let handle = GetStdHandle(STD_INPUT_HANDLE);
+        let mut mode = 0;
+        if GetConsoleMode(handle, &mut mode) == 0 || SetConsoleMode(handle, mode & !ENABLE_ECHO_INPUT) == 0 {
+            return None;
+        }
+        Some(Box::new(move || {
+            SetConsoleMode(handle, mode);
+        }))
+    }
+}
+
+#[cfg(not(windows))]
+fn hide_input() -> Option<Box<dyn FnOnce()>> {
+    let stty = |setting: &str| std::process::Command::new("stty").arg(setting).stdin(std::process::Stdio::inherit()).status().is_ok_and(|status| status.success());
+    stty("-echo").then(|| Box::new(move || {
+        stty("echo");
+    }) as Box<dyn FnOnce()>)
+}
+
+/// The window build has no console of its own; output goes to the one it
+/// was started from.
+#[cfg(windows)]
+fn attach_console() {
+    use winapi::um::wincon::{AttachConsole, ATTACH_PARENT_PROCESS};
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+fn attach_console() {}
@@ -1,16 +1,535 @@
+use crate::attachment::Attachment;
+use crate::collation;
+use crate::credential::{self, Credential};
+use crate::netbox::NetboxLink;
+use crate::settings::{DefaultPorts, Redirection};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use uuid::Uuid;
+use zeroize::Zeroize;
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
 pub struct Client {
+    #[serde(default = "new_client_id")]
+    pub id: String,
     pub name: String,
     pub ip: String,
+    /// Port to connect to instead of the protocol's default or one written
+    /// into the address.
+    #[serde(default)]
+    pub port: Option<u16>,
+    /// Local interface name or address the connection has to leave
+    /// through; empty lets the system choose.
+    #[serde(default)]
+    pub interface: String,
+    #[serde(default)]
+    pub protocol: Protocol,
+    /// Folder path such as `Customers/Acme/Prod`; empty for the top level.
+    #[serde(default)]
+    pub group: String,
     pub password: String,
+    /// Account used to log on; empty means mstsc asks for it.
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub domain: String,
+    /// Asset or ticket identifier substituted into the configured URL templates.
+    #[serde(default)]
+    pub reference: String,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    /// Documentation-only entry (license server, contact sheet, ...) that is
+    /// never connected to.
+    #[serde(default)]
+    pub info_only: bool,
+    /// Whom to call when the machine misbehaves.
+    #[serde(default)]
+    pub owner: Contact,
+    /// Free text such as the rack location or maintenance windows.
+    #[serde(default)]
+    pub notes: String,
+    /// Day after which a temporary client is flagged as expired.
+    #[serde(default)]
+    pub expires: Option<NaiveDate>,
+    /// Set while the client sits in the Trash.
+    #[serde(default)]
+    pub trashed_at: Option<DateTime<Utc>>,
+    /// Extra placeholders for reference links and launcher templates, used
+    /// as `{name}`.
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+    /// Where the session window is moved after launch; `None` leaves it to
+    /// the viewer.
+    #[serde(default)]
+    pub placement: Option<WindowPlacement>,
+    /// Longest a session may stay open, e.g. a bastion's enforced time limit.
+    #[serde(default)]
+    pub session_limit: Option<SessionLimit>,
+    /// Free-form labels such as `prod` or `customer-a`, used to filter and
+    /// color the client list.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// The NetBox device or virtual machine this client was pulled from.
+    #[serde(default)]
+    pub netbox: Option<NetboxLink>,
+    /// Remote Desktop Services farm the host is a member of.
+    #[serde(default)]
+    pub farm: Option<RdsFarm>,
+    /// Id of the shared credential the client logs on with. Its own
+    /// username, domain and password are left empty then.
+    #[serde(default)]
+    pub credential: Option<String>,
+    /// Remote Desktop Gateway the host is reached through.
+    #[serde(default)]
+    pub gateway: Option<RdGateway>,
+    /// Display options used instead of the ones in the settings.
+    #[serde(default)]
+    pub display: Option<DisplaySettings>,
+    /// Redirection used instead of the defaults in the settings.
+    #[serde(default)]
+    pub redirection: Option<Redirection>,
+    /// Ids of the clients a group connect connects before this one, such
+    /// as its jump host or VPN.
+    #[serde(default)]
+    pub prerequisites: Vec<String>,
+    /// The state the last session left the machine in, shown before the
+    /// next connect.
+    #[serde(default)]
+    pub pinned_note: Option<PinnedNote>,
 }
 
+/// "Reboot pending, don't patch yet": a handoff note left when a session
+/// ends. Unlike `notes`, which describe the machine, it says what the next
+/// person connecting has to know now, and is replaced or unpinned later.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PinnedNote {
+    pub text: String,
+    pub pinned_at: DateTime<Utc>,
+}
+
+/// A Remote Desktop Services farm or collection behind a connection broker.
+/// Connecting to a member goes to the broker, which picks the session host.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct RdsFarm {
+    /// Collection or farm name, sent to the broker as the load balancing info.
+    pub name: String,
+    /// Broker address, with a port when it is not 3389.
+    pub broker: String,
+}
+
+/// A Remote Desktop Gateway that tunnels the connection over HTTPS, for
+/// hosts behind a corporate gateway.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct RdGateway {
+    /// Gateway address, with a port when it is not 443.
+    pub host: String,
+    /// Id of the shared credential to sign in to the gateway with; `None`
+    /// uses the client's own account for both.
+    #[serde(default)]
+    pub credential: Option<String>,
+    /// Connect directly when the host is on the local network.
+    #[serde(default)]
+    pub bypass_local: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct SessionLimit {
+    pub minutes: u32,
+    /// Close the viewer when the time is up instead of only warning.
+    pub disconnect: bool,
+}
+
+impl Default for SessionLimit {
+    fn default() -> Self {
+        Self { minutes: 60, disconnect: false }
+    }
+}
+
+/// A window rectangle on one monitor. Monitors are numbered from 1, left to
+/// right; `x` and `y` are relative to the monitor's top-left corner.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct WindowPlacement {
+    pub monitor: u32,
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Default for WindowPlacement {
+    fn default() -> Self {
+        Self {
+            monitor: 1,
+            x: 0,
+            y: 0,
+            width: 1280,
+            height: 800,
+        }
+    }
+}
+
+/// How an RDP session is displayed, for a client that needs something other
+/// than the defaults in the settings.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct DisplaySettings {
+    /// Full screen, unless the client's window is placed after launch.
+    pub full_screen: bool,
+    pub all_monitors: bool,
+    /// Remote desktop size in a window; full screen uses the monitor's.
+    pub width: u32,
+    pub height: u32,
+    /// Scale of the remote desktop in percent.
+    pub scale: u32,
+}
+
+impl DisplaySettings {
+    /// Scale factors mstsc accepts.
+    pub const SCALES: [u32; 7] = [100, 125, 150, 175, 200, 250, 300];
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        Self {
+            full_screen: true,
+            all_monitors: false,
+            width: 1920,
+            height: 1080,
+            scale: 100,
+        }
+    }
+}
+
+/// A folder in the client tree, built from the clients' group paths.
+/// Clients are referenced by their index in the repository.
+#[derive(Debug, Default, PartialEq)]
+pub struct ClientGroup {
+    pub name: String,
+    /// Full path from the root, e.g. `Customers/Acme`.
+    pub path: String,
+    pub groups: Vec<ClientGroup>,
+    pub clients: Vec<usize>,
+}
+
+impl ClientGroup {
+    /// The tree of all clients outside the Trash, in list order.
+    pub fn build(clients: &[Client]) -> Self {
+        Self::build_filtered(clients, |_| true)
+    }
+
+    /// Like `build`, keeping only the clients `keep` accepts. Groups left
+    /// without clients are dropped.
+    pub fn build_filtered(clients: &[Client], keep: impl Fn(&Client) -> bool) -> Self {
+        let mut root = ClientGroup::default();
+        for (index, client) in clients.iter().enumerate().filter(|(_, c)| c.trashed_at.is_none() && keep(c)) {
+            let mut group = &mut root;
+            for name in client.group_path() {
+                let path = if group.path.is_empty() { name.to_string() } else { format!("{}/{}", group.path, name) };
+                let position = match group.groups.iter().position(|child| child.name == name) {
+                    Some(position) => position,
+                    None => {
+                        group.groups.push(ClientGroup { name: name.to_string(), path, ..Default::default() });
+                        group.groups.len() - 1
+                    }
+                };
+                group = &mut group.groups[position];
+            }
+            group.clients.push(index);
+        }
+        root
+    }
+
+    /// Clients in this group and all groups below it.
+    pub fn count(&self) -> usize {
+        self.clients.len() + self.groups.iter().map(ClientGroup::count).sum::<usize>()
+    }
+}
+
+/// How a client is connected to.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    #[default]
+    Rdp,
+    Ssh,
+    Vnc,
+}
+
+impl Protocol {
+    pub const ALL: [Protocol; 3] = [Protocol::Rdp, Protocol::Ssh, Protocol::Vnc];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Protocol::Rdp => "RDP",
+            Protocol::Ssh => "SSH",
+            Protocol::Vnc => "VNC",
+        }
+    }
+
+    /// The link scheme for this protocol, e.g. `ssh` in `ssh://host`.
+    pub fn scheme(self) -> &'static str {
+        match self {
+            Protocol::Rdp => "rdp",
+            Protocol::Ssh => "ssh",
+            Protocol::Vnc => "vnc",
+        }
+    }
+
+    pub fn default_port(self) -> u16 {
+        match self {
+            Protocol::Rdp => 3389,
+            Protocol::Ssh => 22,
+            Protocol::Vnc => 5900,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct Contact {
+    pub name: String,
+    pub email: String,
+    pub phone: String,
+    pub on_call_url: String,
+}
+
+impl Contact {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_empty() && self.email.is_empty() && self.phone.is_empty() && self.on_call_url.is_empty()
+    }
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self {
+            id: new_client_id(),
+            name: String::new(),
+            ip: String::new(),
+            port: None,
+            interface: String::new(),
+            protocol: Protocol::default(),
+            group: String::new(),
+            password: String::new(),
+            username: String::new(),
+            domain: String::new(),
+            reference: String::new(),
+            attachments: Vec::new(),
+            info_only: false,
+            owner: Contact::default(),
+            notes: String::new(),
+            expires: None,
+            trashed_at: None,
+            variables: BTreeMap::new(),
+            placement: None,
+            session_limit: None,
+            tags: Vec::new(),
+            netbox: None,
+            farm: None,
+            credential: None,
+            gateway: None,
+            display: None,
+            redirection: None,
+            prerequisites: Vec::new(),
+            pinned_note: None,
+        }
+    }
+}
+
+impl Client {
+    /// The logon account as `DOMAIN\user`, or just the user name without a
+    /// domain. `None` when no user name is stored.
+    pub fn account(&self) -> Option<String> {
+        match (self.username.is_empty(), self.domain.is_empty()) {
+            (true, _) => None,
+            (false, true) => Some(self.username.clone()),
+            (false, false) => Some(format!("{}\\{}", self.domain, self.username)),
+        }
+    }
+
+    /// What Connect reaches: the broker for a farm member, else the client
+    /// itself.
+    pub fn connection_target(&self) -> Cow<'_, Client> {
+        match &self.farm {
+            Some(farm) if self.protocol == Protocol::Rdp && !farm.broker.trim().is_empty() => {
+                Cow::Owned(Client { ip: farm.broker.trim().to_string(), port: None, ..self.clone() })
+            }
+            _ => Cow::Borrowed(self),
+        }
+    }
+
+    /// The client with the account of its shared credential filled in. A
+    /// credential that was removed leaves the client without an account.
+    pub fn with_credential(&self, credentials: &[Credential]) -> Cow<'_, Client> {
+        match self.credential.as_deref().and_then(|id| credential::find(credentials, id)) {
+            Some(shared) => Cow::Owned(Client {
+                username: shared.username.clone(),
+                domain: shared.domain.clone(),
+                password: shared.password.clone(),
+                ..self.clone()
+            }),
+            None => Cow::Borrowed(self),
+        }
+    }
+
+    /// The group path split into folder names, ignoring empty segments.
+    pub fn group_path(&self) -> impl Iterator<Item = &str> {
+        self.group.split('/').map(str::trim).filter(|name| !name.is_empty())
+    }
+
+    /// Whom to call about the client: its own owner, else the contact of
+    /// its nearest group that has one, together with that group's path.
+    pub fn contact<'a>(&'a self, group_contacts: &'a BTreeMap<String, Contact>) -> Option<(&'a Contact, Option<&'a str>)> {
+        if !self.owner.is_empty() {
+            return Some((&self.owner, None));
+        }
+        let folders: Vec<&str> = self.group_path().collect();
+        (1..=folders.len())
+            .rev()
+            .filter_map(|depth| group_contacts.get_key_value(&folders[..depth].join("/")))
+            .find(|(_, contact)| !contact.is_empty())
+            .map(|(path, contact)| (contact, Some(path.as_str())))
+    }
+
+    /// Host and port to connect to; the port override wins over a port in
+    /// the address.
+    pub fn endpoint(&self) -> (&str, Option<u16>) {
+        let (host, port) = split_host_port(self.ip.trim());
+        (host, self.port.or(port))
+    }
+
+    /// The port connected to: the client's own, else the default of its
+    /// protocol.
+    pub fn effective_port(&self, defaults: &DefaultPorts) -> u16 {
+        self.endpoint().1.unwrap_or(defaults.get(self.protocol))
+    }
+
+    /// The client with the default port of its protocol filled in when it
+    /// sets none and the default is not the standard port viewers assume.
+    pub fn with_default_port(&self, defaults: &DefaultPorts) -> Cow<'_, Client> {
+        let port = defaults.get(self.protocol);
+        if self.endpoint().1.is_some() || port == self.protocol.default_port() {
+            return Cow::Borrowed(self);
+        }
+        Cow::Owned(Client { port: Some(port), ..self.clone() })
+    }
+
+    /// `host:port` to connect to, or just the host without a port. IPv6
+    /// addresses are bracketed when a port follows.
+    pub fn address(&self) -> String {
+        match self.endpoint() {
+            (host, Some(port)) if host.contains(':') => format!("[{}]:{}", host, port),
+            (host, Some(port)) => format!("{}:{}", host, port),
+            (host, None) => host.to_string(),
+        }
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|own| own.eq_ignore_ascii_case(tag))
+    }
+
+    /// Overwrites secrets in memory before the client is dropped.
+    pub fn wipe_secrets(&mut self) {
+        self.password.zeroize();
+    }
+}
+
+/// Tags from a comma-separated list, trimmed, without empty entries or
+/// case-insensitive duplicates.
+pub fn parse_tags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in text.split(',').map(str::trim).filter(|tag| !tag.is_empty()) {
+        if !tags.iter().any(|known| known.eq_ignore_ascii_case(tag)) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
+/// Every tag used by a client outside the Trash, sorted ignoring case and accents.
+pub fn all_tags(clients: &[Client]) -> Vec<String> {
+    let mut tags = parse_tags(
+        &clients.iter().filter(|c| c.trashed_at.is_none()).flat_map(|c| c.tags.iter().cloned()).collect::<Vec<_>>().join(","),
+    );
+    tags.sort_by(|a, b| collation::compare(a, b));
+    tags
+}
+
+/// The farms clients outside the Trash belong to, one per name, sorted by name.
+pub fn known_farms(clients: &[Client]) -> Vec<RdsFarm> {
+    let mut farms: Vec<RdsFarm> = Vec::new();
+    for farm in clients.iter().filter(|c| c.trashed_at.is_none()).filter_map(|c| c.farm.as_ref()) {
+        if !farms.iter().any(|known| known.name.eq_ignore_ascii_case(&farm.name)) {
+            farms.push(farm.clone());
+        }
+    }
+    farms.sort_by(|a, b| collation::compare(&a.name, &b.name));
+    farms
+}
+
+/// Splits `host:port` or `[v6]:port` into its parts, without the brackets.
+/// Addresses without a numeric port, including bare IPv6 addresses, are
+/// returned unchanged.
+pub fn split_host_port(address: &str) -> (&str, Option<u16>) {
+    if let Some((host, rest)) = address.strip_prefix('[').and_then(|rest| rest.split_once(']')) {
+        return match rest.strip_prefix(':').map(str::parse) {
+            None if rest.is_empty() => (host, None),
+            Some(Ok(port)) => (host, Some(port)),
+            _ => (address, None),
+        };
+    }
+    match address.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') => match port.parse() {
+            Ok(port) => (host, Some(port)),
+            Err(_) => (address, None),
+        },
+        _ => (address, None),
+    }
+}
+
+/// Stable identifier for a client, kept across edits and exports.
+pub fn new_client_id() -> String {
+    Uuid::new_v4().to_string()
+}
+
+pub enum KeyStatus {
+    /// No vault exists yet; the next key entered creates it.
+    NotSet,
+    Locked,
+    Unlocked,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum AppMode {
     Normal,
     Adding,
+    History,
     Editing,
     Removing,
-    About,
+    Exporting,
+    ExportingInventory,
+    ExportingRdp,
+    HandoverReport,
+    Importing,
+    ImportingHistory,
+    ImportingRdg,
+    Netbox,
+    Monitoring,
+    ImportingJson,
+    WatchFolder,
+    CreateFromLink,
+    UrlTemplates,
+    GroupContact,
+    Search,
+    Trash,
+    ImportConflicts,
+    Duplicates,
+    HostsFile,
+    Credentials,
+    RotatingPasswords,
+    ConnectingGroup,
+    Composition,
+    Settings,
+    ChangingMasterKey,
+    RestoringBackup,
+    Sessions,
 }
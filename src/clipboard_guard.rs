@@ -0,0 +1,92 @@
+use arboard::Clipboard;
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+/// Clipboard managers that keep a history of everything copied.
+const CLIPBOARD_MANAGERS: [&str; 10] = [
+    "ditto", "clipclip", "clipboardfusion", "copyq", "clipit", "parcellite", "gpaste-daemon", "klipper", "diodon", "clipman",
+];
+
+/// Clears the clipboard after `delay`, but only if it still holds `secret`;
+/// anything the user copied in the meantime is left alone.
+pub fn clear_after(secret: &str, delay: Duration) {
+    let secret = Zeroizing::new(secret.to_string());
+    thread::spawn(move || {
+        thread::sleep(delay);
+        let Ok(mut clipboard) = Clipboard::new() else {
+            return;
+        };
+        let current = Zeroizing::new(clipboard.get_text().unwrap_or_default());
+        if *current == *secret {
+            let _ = clipboard.clear();
+        }
+    });
+}
+
+/// Whether the app runs inside a Remote Desktop session. Its clipboard is
+/// then redirected to the computer the session is viewed from, and from
+/// there up any further chain of sessions.
+#[cfg(windows)]
+pub fn in_remote_session() -> bool {
+    use winapi::um::winuser::{GetSystemMetrics, SM_REMOTESESSION};
+    unsafe { GetSystemMetrics(SM_REMOTESESSION) != 0 }
+}
+
+#[cfg(not(windows))]
+pub fn in_remote_session() -> bool {
+    false
+}
+
+/// Names of clipboard history tools that would retain a copied password.
+pub fn detect_clipboard_history() -> Vec<String> {
+    let mut found = Vec::new();
+    if windows_history_enabled() {
+        found.push("Windows clipboard history".to_string());
+    }
+    for process in running_processes() {
+        let process = process.to_ascii_lowercase();
+        let name = process.trim_end_matches(".exe");
+        if let Some(manager) = CLIPBOARD_MANAGERS.iter().find(|m| **m == name) {
+            if !found.iter().any(|f| f == manager) {
+                found.push(manager.to_string());
+            }
+        }
+    }
+    found
+}
+
+#[cfg(windows)]
+fn windows_history_enabled() -> bool {
+    Command::new("reg")
+        .args(["query", "HKCU\\Software\\Microsoft\\Clipboard", "/v", "EnableClipboardHistory"])
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).contains("0x1"))
+        .unwrap_or(false)
+}
+
+#[cfg(not(windows))]
+fn windows_history_enabled() -> bool {
+    false
+}
+
+#[cfg(windows)]
+fn running_processes() -> Vec<String> {
+    let Ok(output) = Command::new("tasklist").args(["/FO", "CSV", "/NH"]).output() else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split(',').next())
+        .map(|name| name.trim_matches('"').to_string())
+        .collect()
+}
+
+#[cfg(not(windows))]
+fn running_processes() -> Vec<String> {
+    let Ok(output) = Command::new("ps").args(["-e", "-o", "comm="]).output() else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&output.stdout).lines().map(|line| line.trim().to_string()).collect()
+}
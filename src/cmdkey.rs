@@ -0,0 +1,46 @@
+use crate::client::split_host_port;
+use std::process::Command;
+
+/// Credential Manager target that mstsc looks up for `address`. The port,
+/// if any, is not part of the target.
+pub fn termsrv_target(address: &str) -> String {
+    format!("TERMSRV/{}", split_host_port(address).0)
+}
+
+/// Runs cmdkey with `target_arg` and the account, reporting `what` failed.
+fn add(target_arg: String, account: &str, password: &str, what: &str) -> Result<(), String> {
+    let status = Command::new("cmdkey")
+        .arg(target_arg)
+        .arg(format!("/user:{}", account))
+        .arg(format!("/pass:{}", password))
+        .output()
+        .map_err(|e| format!("Failed to run cmdkey: {}", e))?
+        .status;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("cmdkey could not store the credential for {}.", what))
+    }
+}
+
+/// Stores the account for `address` in the Windows Credential Manager so
+/// mstsc logs on without prompting. The password is briefly visible on the
+/// cmdkey command line; it is removed again with [`remove`].
+pub fn store(address: &str, account: &str, password: &str) -> Result<(), String> {
+    add(format!("/generic:{}", termsrv_target(address)), account, password, address)
+}
+
+pub fn remove(address: &str) {
+    let _ = Command::new("cmdkey").arg(format!("/delete:{}", termsrv_target(address))).output();
+}
+
+/// Stores the account mstsc signs in to the Remote Desktop Gateway `host`
+/// with. Gateways are looked up as domain credentials under their bare host
+/// name rather than a TERMSRV target.
+pub fn store_gateway(host: &str, account: &str, password: &str) -> Result<(), String> {
+    add(format!("/add:{}", split_host_port(host).0), account, password, &format!("the gateway {}", host))
+}
+
+pub fn remove_gateway(host: &str) {
+    let _ = Command::new("cmdkey").arg(format!("/delete:{}", split_host_port(host).0)).output();
+}
@@ -0,0 +1,62 @@
+//! Comparing names the way people read them rather than by their bytes:
+//! case and accents are ignored, so `München` is found by `munchen` and
+//! sorts next to `Munich` instead of after `Zurich`.
+
+use std::cmp::Ordering;
+use unicode_normalization::char::{decompose_compatible, is_combining_mark};
+
+/// Letters that do not decompose into a base letter and a mark, spelled
+/// the way they are usually written without them.
+fn expansion(c: char) -> Option<&'static str> {
+    Some(match c {
+        'ß' | 'ẞ' => "ss",
+        'æ' | 'Æ' => "ae",
+        'œ' | 'Œ' => "oe",
+        'ø' | 'Ø' => "o",
+        'đ' | 'Đ' | 'ð' | 'Ð' => "d",
+        'ł' | 'Ł' => "l",
+        'þ' | 'Þ' => "th",
+        'ı' => "i",
+        _ => return None,
+    })
+}
+
+/// `text` lowercased and without accents, each folded char paired with the
+/// index of the char of `text` it came from.
+pub fn fold_indexed(text: &str) -> Vec<(char, usize)> {
+    let mut folded = Vec::with_capacity(text.len());
+    for (index, c) in text.chars().enumerate() {
+        if let Some(spelled) = expansion(c) {
+            folded.extend(spelled.chars().map(|c| (c, index)));
+            continue;
+        }
+        decompose_compatible(c, |part| {
+            if !is_combining_mark(part) {
+                folded.extend(part.to_lowercase().map(|c| (c, index)));
+            }
+        });
+    }
+    folded
+}
+
+/// `text` lowercased and without accents.
+pub fn fold(text: &str) -> String {
+    fold_indexed(text).into_iter().map(|(c, _)| c).collect()
+}
+
+/// Whether `text` contains `query`, ignoring case and accents.
+pub fn contains(text: &str, query: &str) -> bool {
+    fold(text).contains(&fold(query))
+}
+
+/// Orders names ignoring case and accents, then by them so that names
+/// differing only there still sort the same way every time.
+pub fn compare(a: &str, b: &str) -> Ordering {
+    fold(a).cmp(&fold(b)).then_with(|| a.cmp(b))
+}
+
+/// Orders folder paths such as `Customers/Acme` folder by folder.
+pub fn compare_paths<'a>(a: impl Iterator<Item = &'a str>, b: impl Iterator<Item = &'a str>) -> Ordering {
+    let (a, b): (Vec<&str>, Vec<&str>) = (a.collect(), b.collect());
+    a.iter().zip(&b).map(|(a, b)| compare(a, b)).find(|order| order.is_ne()).unwrap_or_else(|| a.len().cmp(&b.len()))
+}
@@ -0,0 +1,162 @@
+//! How the vault grows, for capacity planning: the clients each folder,
+//! site, customer and environment held, counted once a month. The counts
+//! are kept in the vault itself, so everyone who opens it sees the same
+//! trend.
+//!
+//! Sites, customers and environments come from `site:`, `customer:` and
+//! `env:` tags; NetBox Sync already tags the clients it creates with their
+//! site. Folders are the top-level folder of each client.
+
+use crate::client::Client;
+use chrono::{Datelike, Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// What clients without a folder or a tag for a dimension are counted as.
+pub const UNSET: &str = "(none)";
+
+/// What the clients are counted by.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Dimension {
+    #[default]
+    Folder,
+    Site,
+    Customer,
+    Environment,
+}
+
+impl Dimension {
+    pub const ALL: [Dimension; 4] = [Dimension::Folder, Dimension::Site, Dimension::Customer, Dimension::Environment];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Dimension::Folder => "Folder",
+            Dimension::Site => "Site",
+            Dimension::Customer => "Customer",
+            Dimension::Environment => "Environment",
+        }
+    }
+
+    fn tag_prefix(self) -> Option<&'static str> {
+        match self {
+            Dimension::Folder => None,
+            Dimension::Site => Some("site:"),
+            Dimension::Customer => Some("customer:"),
+            Dimension::Environment => Some("env:"),
+        }
+    }
+
+    /// What `client` is counted as.
+    pub fn of(self, client: &Client) -> String {
+        let value = match self.tag_prefix() {
+            None => client.group_path().next(),
+            Some(prefix) => client.tags.iter().find_map(|tag| tag.strip_prefix(prefix)).map(str::trim).filter(|value| !value.is_empty()),
+        };
+        value.unwrap_or(UNSET).to_string()
+    }
+
+    pub fn counts(self, month: &MonthlyCounts) -> &BTreeMap<String, u32> {
+        match self {
+            Dimension::Folder => &month.folders,
+            Dimension::Site => &month.sites,
+            Dimension::Customer => &month.customers,
+            Dimension::Environment => &month.environments,
+        }
+    }
+
+    fn counts_mut(self, month: &mut MonthlyCounts) -> &mut BTreeMap<String, u32> {
+        match self {
+            Dimension::Folder => &mut month.folders,
+            Dimension::Site => &mut month.sites,
+            Dimension::Customer => &mut month.customers,
+            Dimension::Environment => &mut month.environments,
+        }
+    }
+}
+
+/// The clients of the vault as a month ended, or as it stands for the
+/// current month.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MonthlyCounts {
+    /// First day of the month counted.
+    pub month: NaiveDate,
+    /// Clients outside the Trash.
+    pub clients: u32,
+    #[serde(default)]
+    pub folders: BTreeMap<String, u32>,
+    #[serde(default)]
+    pub sites: BTreeMap<String, u32>,
+    #[serde(default)]
+    pub customers: BTreeMap<String, u32>,
+    #[serde(default)]
+    pub environments: BTreeMap<String, u32>,
+}
+
+impl MonthlyCounts {
+    pub fn count(clients: &[Client], day: NaiveDate) -> Self {
+        let mut counts = Self {
+            month: first_of_month(day),
+            clients: 0,
+            folders: BTreeMap::new(),
+            sites: BTreeMap::new(),
+            customers: BTreeMap::new(),
+            environments: BTreeMap::new(),
+        };
+        for client in clients.iter().filter(|client| client.trashed_at.is_none()) {
+            counts.clients += 1;
+            for dimension in Dimension::ALL {
+                *dimension.counts_mut(&mut counts).entry(dimension.of(client)).or_default() += 1;
+            }
+        }
+        counts
+    }
+}
+
+pub fn first_of_month(day: NaiveDate) -> NaiveDate {
+    day.with_day(1).unwrap_or(day)
+}
+
+/// Counts `clients` for the month of `today`. Every save counts again, so
+/// a month keeps how it ended.
+pub fn record(history: &mut Vec<MonthlyCounts>, clients: &[Client], today: NaiveDate) {
+    let counts = MonthlyCounts::count(clients, today);
+    match history.last_mut() {
+        Some(last) if last.month == counts.month => *last = counts,
+        // A clock set back leaves the months already counted alone.
+        Some(last) if last.month > counts.month => {}
+        _ => history.push(counts),
+    }
+}
+
+/// Every month from the first counted to the last. A month nothing was
+/// saved in ended as the one before it did.
+pub fn by_month(history: &[MonthlyCounts]) -> Vec<MonthlyCounts> {
+    let mut months: Vec<MonthlyCounts> = Vec::with_capacity(history.len());
+    for counts in history {
+        while let Some(next) = months.last().and_then(|last| last.month.checked_add_months(Months::new(1))).filter(|next| *next < counts.month) {
+            let carried = MonthlyCounts { month: next, ..months[months.len() - 1].clone() };
+            months.push(carried);
+        }
+        months.push(counts.clone());
+    }
+    months
+}
+
+/// `month,dimension,name,clients` rows for a spreadsheet, one month after
+/// the other, with the vault's total as dimension `total`.
+pub fn composition_csv(history: &[MonthlyCounts]) -> Result<String, String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(["month", "dimension", "name", "clients"]).map_err(|e| e.to_string())?;
+    for counts in by_month(history) {
+        let month = counts.month.format("%Y-%m").to_string();
+        writer.write_record([month.as_str(), "total", "", &counts.clients.to_string()]).map_err(|e| e.to_string())?;
+        for dimension in Dimension::ALL {
+            let label = dimension.label().to_lowercase();
+            for (name, count) in dimension.counts(&counts) {
+                writer.write_record([month.as_str(), &label, name, &count.to_string()]).map_err(|e| e.to_string())?;
+            }
+        }
+    }
+    let data = writer.into_inner().map_err(|e| e.to_string())?;
+    String::from_utf8(data).map_err(|e| e.to_string())
+}
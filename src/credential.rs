@@ -0,0 +1,54 @@
+//! Accounts shared by several clients, stored in the vault next to them.
+//! A client refers to one by id instead of keeping its own copy, so a
+//! changed password is entered once.
+
+use crate::client::new_client_id;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Credential {
+    #[serde(default = "new_client_id")]
+    pub id: String,
+    /// What the account is called in lists, e.g. `Lab admin`.
+    pub name: String,
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub domain: String,
+    #[serde(default)]
+    pub password: String,
+}
+
+impl Default for Credential {
+    fn default() -> Self {
+        Self {
+            id: new_client_id(),
+            name: String::new(),
+            username: String::new(),
+            domain: String::new(),
+            password: String::new(),
+        }
+    }
+}
+
+impl Credential {
+    /// The account as `DOMAIN\user`, or just the user name.
+    pub fn account(&self) -> String {
+        if self.domain.is_empty() {
+            self.username.clone()
+        } else {
+            format!("{}\\{}", self.domain, self.username)
+        }
+    }
+
+    /// Overwrites the password in memory before the credential is dropped.
+    pub fn wipe_secrets(&mut self) {
+        self.password.zeroize();
+    }
+}
+
+/// The credential with `id`.
+pub fn find<'a>(credentials: &'a [Credential], id: &str) -> Option<&'a Credential> {
+    credentials.iter().find(|credential| credential.id == id)
+}
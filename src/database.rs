@@ -0,0 +1,305 @@
+//! Vaults kept in a SQLite database instead of one encrypted file, for
+//! inventories of thousands of clients. Every client is a row of its own,
+//! encrypted on its own with the vault key, so a save writes the clients
+//! that changed rather than the whole vault. The database keeps the same
+//! versioned header an `RDMF` file starts with, and the file's second slot,
+//! holding the decoy vault or random bytes, as a row of its own.
+
+use crate::client::{Client, Contact};
+use crate::composition::MonthlyCounts;
+use crate::credential::Credential;
+use crate::encryption::{decrypt_siv, encrypt_siv, nonce_counter, NonceSequence, KEY_SIZE, NONCE_SIZE};
+use crate::history::ConnectionRecord;
+use crate::repository::{RepositoryEvent, DAMAGED, TAMPERED};
+use crate::vault::{VaultContents, VaultPolicy};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use rusqlite::{params, Connection, ErrorCode, OpenFlags, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+/// First bytes of every SQLite database file.
+const SQLITE_MAGIC: &[u8; 16] = b"SQLite format 3\0";
+/// The AES-GCM-SIV tag at the end of every encrypted row.
+const TAG_SIZE: usize = 16;
+const MANIFEST: &str = "manifest";
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS header (id INTEGER PRIMARY KEY CHECK (id = 1), data BLOB NOT NULL);
+    CREATE TABLE IF NOT EXISTS clients (id TEXT PRIMARY KEY, data BLOB NOT NULL) WITHOUT ROWID;
+    CREATE TABLE IF NOT EXISTS sections (name TEXT PRIMARY KEY, data BLOB NOT NULL) WITHOUT ROWID;
+    CREATE TABLE IF NOT EXISTS spare (id INTEGER PRIMARY KEY CHECK (id = 1), data BLOB NOT NULL);
+";
+
+/// The encrypted row tying the others together: the clients in list order
+/// and the tag every row was last written with. A row that is removed,
+/// added or swapped for an older copy of itself no longer matches it.
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    clients: Vec<(String, String)>,
+    sections: Vec<(String, String)>,
+}
+
+/// The open vault as a save writes it.
+pub struct Snapshot<'a> {
+    pub header: Vec<u8>,
+    pub policy: &'a VaultPolicy,
+    pub clients: &'a [Client],
+    pub history: &'a [ConnectionRecord],
+    pub credentials: &'a [Credential],
+    pub composition: &'a [MonthlyCounts],
+    pub group_contacts: &'a BTreeMap<String, Contact>,
+    /// The second slot, written when the database is and kept otherwise.
+    pub spare: &'a [u8],
+}
+
+/// Whether the vault at `path` is a database rather than an encrypted file.
+pub fn is_database(path: &Path) -> bool {
+    let mut magic = [0u8; SQLITE_MAGIC.len()];
+    File::open(path).and_then(|mut file| file.read_exact(&mut magic)).is_ok() && magic == *SQLITE_MAGIC
+}
+
+fn read_error(e: rusqlite::Error) -> String {
+    match e.sqlite_error_code() {
+        Some(ErrorCode::DatabaseCorrupt | ErrorCode::NotADatabase) => DAMAGED.to_string(),
+        _ => format!("Could not read the vault: {}", e),
+    }
+}
+
+fn write_error(e: rusqlite::Error) -> String {
+    format!("Could not write the vault: {}", e)
+}
+
+fn open_read_only(path: &Path) -> Result<Connection, String> {
+    Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX).map_err(read_error)
+}
+
+/// The versioned header, which is read before the key is derived.
+pub fn read_header(path: &Path) -> Result<Vec<u8>, String> {
+    open_read_only(path)?
+        .query_row("SELECT data FROM header", [], |row| row.get(0))
+        .optional()
+        .map_err(read_error)?
+        .ok_or_else(|| DAMAGED.to_string())
+}
+
+/// The second slot; `None` for databases written before there was one.
+pub fn read_spare(path: &Path) -> Option<Vec<u8>> {
+    open_read_only(path).ok()?.query_row("SELECT data FROM spare", [], |row| row.get(0)).ok()
+}
+
+/// Replaces the second slot, leaving every other row alone.
+pub fn write_spare(path: &Path, slot: &[u8]) -> Result<(), String> {
+    let connection = Connection::open(path).map_err(write_error)?;
+    connection.execute_batch(SCHEMA).map_err(write_error)?;
+    connection.execute("INSERT OR REPLACE INTO spare (id, data) VALUES (1, ?1)", [slot]).map_err(write_error)?;
+    Ok(())
+}
+
+fn tag(row: &[u8]) -> [u8; TAG_SIZE] {
+    row[row.len().saturating_sub(TAG_SIZE)..].try_into().unwrap_or_default()
+}
+
+fn seal(data: &[u8], key: &[u8; KEY_SIZE], nonces: &mut NonceSequence) -> Result<Vec<u8>, String> {
+    encrypt_siv(data, key, &nonces.next_nonce()?).map_err(|_| "Could not encrypt the vault.".to_string())
+}
+
+/// What is on disk, so a save writes only the rows that changed.
+pub struct Database {
+    /// The tag of each client's row, by client id.
+    client_tags: HashMap<String, [u8; TAG_SIZE]>,
+    /// The tag of each section's row and the SHA-256 of what it holds, so
+    /// an unchanged policy or history is not written again.
+    sections: HashMap<String, ([u8; TAG_SIZE], [u8; 32])>,
+    /// Clients added, changed or removed since the last save.
+    changed: HashSet<String>,
+    /// Whether the next save writes every row: for a new database, after
+    /// the whole list was replaced and after a key change.
+    rewrite: bool,
+}
+
+/// A database the open vault is written to in full on the next save.
+impl Default for Database {
+    fn default() -> Self {
+        Self { client_tags: HashMap::new(), sections: HashMap::new(), changed: HashSet::new(), rewrite: true }
+    }
+}
+
+impl Database {
+    /// Notes a change to the client list for the next save.
+    pub fn note(&mut self, event: RepositoryEvent) {
+        match event {
+            RepositoryEvent::Added(id) | RepositoryEvent::Updated(id) | RepositoryEvent::Removed(id) => {
+                self.changed.insert(id);
+            }
+            RepositoryEvent::Reloaded => self.rewrite = true,
+        }
+    }
+
+    /// Has the next save write every row, e.g. under a new key.
+    pub fn rewrite_all(&mut self) {
+        self.rewrite = true;
+    }
+
+    /// Decrypts every row with `key`, which the header already accepted,
+    /// returning the contents and the nonces to continue with. Rows that
+    /// do not match the manifest mean the database was altered.
+    pub fn load(path: &Path, key: &[u8; KEY_SIZE]) -> Result<(Self, VaultContents, NonceSequence), String> {
+        let connection = open_read_only(path)?;
+        let rows = |sql: &str| -> Result<HashMap<String, Vec<u8>>, String> {
+            let mut statement = connection.prepare(sql).map_err(read_error)?;
+            let rows = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?))).map_err(read_error)?;
+            rows.collect::<Result<_, _>>().map_err(read_error)
+        };
+        let mut client_rows = rows("SELECT id, data FROM clients")?;
+        let mut section_rows = rows("SELECT name, data FROM sections")?;
+        let mut last_nonce = [0u8; NONCE_SIZE];
+        let mut open = |row: &[u8], listed: Option<&str>| -> Result<Vec<u8>, String> {
+            if listed.is_some_and(|listed| listed != STANDARD.encode(tag(row))) {
+                return Err(TAMPERED.to_string());
+            }
+            let data = decrypt_siv(row, key).map_err(|_| TAMPERED.to_string())?;
+            if nonce_counter(row) >= nonce_counter(&last_nonce) {
+                last_nonce.copy_from_slice(&row[..NONCE_SIZE]);
+            }
+            Ok(data)
+        };
+
+        let manifest = section_rows.remove(MANIFEST).ok_or_else(|| TAMPERED.to_string())?;
+        let manifest: Manifest = serde_json::from_slice(&open(&manifest, None)?).map_err(|_| TAMPERED.to_string())?;
+        let mut database = Self { rewrite: false, ..Self::default() };
+        let mut sections = HashMap::new();
+        for (name, listed) in &manifest.sections {
+            let row = section_rows.remove(name).ok_or_else(|| TAMPERED.to_string())?;
+            let data = open(&row, Some(listed))?;
+            database.sections.insert(name.clone(), (tag(&row), Sha256::digest(&data).into()));
+            sections.insert(name.as_str(), data);
+        }
+        let mut clients = Vec::with_capacity(manifest.clients.len());
+        for (id, listed) in &manifest.clients {
+            let row = client_rows.remove(id).ok_or_else(|| TAMPERED.to_string())?;
+            let client: Client = serde_json::from_slice(&open(&row, Some(listed))?).map_err(|e| format!("The vault is corrupt: {}", e))?;
+            if client.id != *id {
+                return Err(TAMPERED.to_string());
+            }
+            database.client_tags.insert(id.clone(), tag(&row));
+            clients.push(client);
+        }
+        if !client_rows.is_empty() || !section_rows.is_empty() {
+            return Err(TAMPERED.to_string());
+        }
+
+        fn section<T: Default + for<'de> Deserialize<'de>>(sections: &HashMap<&str, Vec<u8>>, name: &str) -> Result<T, String> {
+            sections.get(name).map_or(Ok(T::default()), |data| serde_json::from_slice(data).map_err(|e| format!("The vault is corrupt: {}", e)))
+        }
+        let contents = (section(&sections, "policy")?, clients, section(&sections, "history")?, section(&sections, "credentials")?, section(&sections, "composition")?, section(&sections, "group_contacts")?);
+        Ok((database, contents, NonceSequence::after(&last_nonce)))
+    }
+
+    /// Writes the header, the rows that changed since the last save and
+    /// the manifest in one transaction. A vault kept as a file until now is
+    /// written whole to a new database, which then takes the file's place.
+    pub fn save(&mut self, path: &Path, vault: &Snapshot, key: &[u8; KEY_SIZE], nonces: &mut NonceSequence) -> Result<(), String> {
+        let fresh = !is_database(path);
+        let target = if fresh { path.with_file_name(format!("{}.tmp", path.file_name().unwrap_or_default().to_string_lossy())) } else { path.to_path_buf() };
+        if fresh {
+            let _ = fs::remove_file(&target);
+        }
+        let written = self.write(&target, fresh || self.rewrite, vault, key, nonces).and_then(|written| {
+            if fresh {
+                fs::rename(&target, path).map_err(|e| format!("Could not write the vault: {}", e))?;
+            }
+            Ok(written)
+        });
+        match written {
+            Ok((client_tags, sections)) => {
+                self.client_tags = client_tags;
+                self.sections = sections;
+                self.changed.clear();
+                self.rewrite = false;
+                Ok(())
+            }
+            Err(e) => {
+                if fresh {
+                    let _ = fs::remove_file(&target);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// The rows written, as `client_tags` and `sections` become once the
+    /// transaction is committed.
+    #[allow(clippy::type_complexity)]
+    fn write(
+        &self,
+        target: &Path,
+        rewrite: bool,
+        vault: &Snapshot,
+        key: &[u8; KEY_SIZE],
+        nonces: &mut NonceSequence,
+    ) -> Result<(HashMap<String, [u8; TAG_SIZE]>, HashMap<String, ([u8; TAG_SIZE], [u8; 32])>), String> {
+        let mut connection = Connection::open(target).map_err(write_error)?;
+        connection.execute_batch(SCHEMA).map_err(write_error)?;
+        let transaction = connection.transaction().map_err(write_error)?;
+        transaction.execute("INSERT OR REPLACE INTO header (id, data) VALUES (1, ?1)", [&vault.header]).map_err(write_error)?;
+        let spare = if rewrite { "INSERT OR REPLACE INTO spare (id, data) VALUES (1, ?1)" } else { "INSERT OR IGNORE INTO spare (id, data) VALUES (1, ?1)" };
+        transaction.execute(spare, [vault.spare]).map_err(write_error)?;
+        let (mut client_tags, mut sections) = if rewrite {
+            transaction.execute_batch("DELETE FROM clients; DELETE FROM sections;").map_err(write_error)?;
+            (HashMap::new(), HashMap::new())
+        } else {
+            (self.client_tags.clone(), self.sections.clone())
+        };
+
+        {
+            let mut upsert = transaction.prepare("INSERT OR REPLACE INTO clients (id, data) VALUES (?1, ?2)").map_err(write_error)?;
+            // A client that somehow has no row yet is written too rather than left out of the manifest.
+            for client in vault.clients {
+                if !rewrite && !self.changed.contains(&client.id) && client_tags.contains_key(&client.id) {
+                    continue;
+                }
+                let row = seal(&serde_json::to_vec(client).map_err(|e| e.to_string())?, key, nonces)?;
+                upsert.execute(params![client.id, row]).map_err(write_error)?;
+                client_tags.insert(client.id.clone(), tag(&row));
+            }
+        }
+        let present: HashSet<&str> = vault.clients.iter().map(|client| client.id.as_str()).collect();
+        let removed: Vec<String> = client_tags.keys().filter(|id| !present.contains(id.as_str())).cloned().collect();
+        for id in removed {
+            transaction.execute("DELETE FROM clients WHERE id = ?1", [&id]).map_err(write_error)?;
+            client_tags.remove(&id);
+        }
+
+        let serialized = [
+            ("policy", serde_json::to_vec(vault.policy)),
+            ("history", serde_json::to_vec(vault.history)),
+            ("credentials", serde_json::to_vec(vault.credentials)),
+            ("composition", serde_json::to_vec(vault.composition)),
+            ("group_contacts", serde_json::to_vec(vault.group_contacts)),
+        ];
+        for (name, data) in serialized {
+            let data = data.map_err(|e| e.to_string())?;
+            let digest: [u8; 32] = Sha256::digest(&data).into();
+            if sections.get(name).is_some_and(|(_, written)| *written == digest) {
+                continue;
+            }
+            let row = seal(&data, key, nonces)?;
+            transaction.execute("INSERT OR REPLACE INTO sections (name, data) VALUES (?1, ?2)", params![name, row]).map_err(write_error)?;
+            sections.insert(name.to_string(), (tag(&row), digest));
+        }
+
+        let manifest = Manifest {
+            clients: vault.clients.iter().map(|client| (client.id.clone(), STANDARD.encode(client_tags[&client.id]))).collect(),
+            sections: sections.iter().map(|(name, (tag, _))| (name.clone(), STANDARD.encode(tag))).collect(),
+        };
+        let row = seal(&serde_json::to_vec(&manifest).map_err(|e| e.to_string())?, key, nonces)?;
+        transaction.execute("INSERT OR REPLACE INTO sections (name, data) VALUES (?1, ?2)", params![MANIFEST, row]).map_err(write_error)?;
+        transaction.commit().map_err(write_error)?;
+        Ok((client_tags, sections))
+    }
+}
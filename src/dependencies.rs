@@ -0,0 +1,66 @@
+//! "Connect to JUMP-01 before DB-01": clients that have to be connected
+//! before another one, such as a jump host or a VPN endpoint, and the order
+//! a group connect launches clients in.
+
+use crate::client::Client;
+use std::collections::HashSet;
+
+/// The clients `client` waits for that can be connected to; removed,
+/// trashed and information-only ones are left out.
+pub fn prerequisites<'a>(clients: &'a [Client], client: &Client) -> Vec<&'a Client> {
+    client
+        .prerequisites
+        .iter()
+        .filter_map(|id| clients.iter().find(|candidate| candidate.id == *id))
+        .filter(|prerequisite| prerequisite.trashed_at.is_none() && !prerequisite.info_only)
+        .collect()
+}
+
+/// Whether the client with id `from` waits for `target`, directly or
+/// through other prerequisites.
+pub fn depends_on(clients: &[Client], from: &str, target: &str) -> bool {
+    let mut seen = HashSet::new();
+    let mut pending = vec![from.to_string()];
+    while let Some(id) = pending.pop() {
+        let Some(client) = clients.iter().find(|client| client.id == id) else {
+            continue;
+        };
+        for prerequisite in &client.prerequisites {
+            if prerequisite == target {
+                return true;
+            }
+            if seen.insert(prerequisite.clone()) {
+                pending.push(prerequisite.clone());
+            }
+        }
+    }
+    false
+}
+
+/// `ids` and every prerequisite they wait for, each after the clients it
+/// waits for and otherwise in the order given. Fails when prerequisites
+/// wait for each other, which the editor does not allow but an import can
+/// bring in.
+pub fn connect_order(clients: &[Client], ids: &[String]) -> Result<Vec<String>, String> {
+    fn visit(clients: &[Client], client: &Client, visiting: &mut Vec<String>, order: &mut Vec<String>) -> Result<(), String> {
+        if order.contains(&client.id) {
+            return Ok(());
+        }
+        if visiting.contains(&client.id) {
+            return Err(format!("The clients connected before {} lead back to it.", client.name));
+        }
+        visiting.push(client.id.clone());
+        for prerequisite in prerequisites(clients, client) {
+            visit(clients, prerequisite, visiting, order)?;
+        }
+        visiting.pop();
+        order.push(client.id.clone());
+        Ok(())
+    }
+
+    let mut order = Vec::new();
+    for client in ids.iter().filter_map(|id| clients.iter().find(|client| client.id == *id)) {
+        visit(clients, client, &mut Vec::new(), &mut order)?;
+    }
+    Ok(order)
+}
@@ -0,0 +1,210 @@
+//! Why a connect failed, in terms the user can act on, and the checks run
+//! by the Diagnostics button: name resolution, the port, the route and the
+//! viewer.
+
+use crate::client::Client;
+use crate::nla::{self, Outcome};
+use crate::reachability;
+use crate::route;
+use std::io;
+use std::net::ToSocketAddrs;
+use std::path::{Path, PathBuf};
+
+/// What went wrong, as far as it can be told.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Failure {
+    /// The viewer program is not installed or not on the PATH.
+    ViewerMissing(String),
+    HostUnresolvable(String),
+    PortClosed(String, u16),
+    /// Traffic would not leave through the interface the client names.
+    WrongInterface(String),
+    /// The password could not be handed over, through the Credential
+    /// Manager or the clipboard.
+    CredentialInjection,
+    /// The server's Network Level Authentication refused the stored
+    /// account.
+    CredentialsRejected(String),
+    Other,
+}
+
+/// A failed connect: what kind of failure, and the underlying message kept
+/// for the history and the details.
+#[derive(Clone, Debug)]
+pub struct LaunchError {
+    pub failure: Failure,
+    pub message: String,
+}
+
+impl LaunchError {
+    pub fn new(failure: Failure, message: impl Into<String>) -> Self {
+        Self { failure, message: message.into() }
+    }
+
+    /// `message` prefixed with `context`, classified by the I/O error of
+    /// starting `program`.
+    pub fn spawn(program: &str, context: &str, error: &io::Error) -> Self {
+        let failure = match error.kind() {
+            io::ErrorKind::NotFound => Failure::ViewerMissing(program.to_string()),
+            _ => Failure::Other,
+        };
+        Self::new(failure, format!("{}: {}", context, error))
+    }
+}
+
+impl From<String> for LaunchError {
+    fn from(message: String) -> Self {
+        Self::new(Failure::Other, message)
+    }
+}
+
+impl Failure {
+    pub fn title(&self) -> String {
+        match self {
+            Failure::ViewerMissing(program) => format!("{} could not be found", program),
+            Failure::HostUnresolvable(host) => format!("{} could not be resolved", host),
+            Failure::PortClosed(host, port) => format!("{} does not answer on port {}", host, port),
+            Failure::WrongInterface(interface) => format!("The connection would not go through {}", interface),
+            Failure::CredentialInjection => "The password could not be handed to the viewer".to_string(),
+            Failure::CredentialsRejected(host) => format!("{} rejected the stored account", host),
+            Failure::Other => "The connection could not be started".to_string(),
+        }
+    }
+
+    /// What to try next, most likely fix first.
+    pub fn hints(&self) -> Vec<&'static str> {
+        match self {
+            Failure::ViewerMissing(_) => vec![
+                "Install the viewer, or set its full path under File → Settings → Programs.",
+                "If it is installed, check that its folder is on the PATH.",
+            ],
+            Failure::HostUnresolvable(_) => vec![
+                "Check the spelling of the address.",
+                "Connect the VPN if the host is on an internal network.",
+                "Try the IP address instead of the name.",
+            ],
+            Failure::PortClosed(..) => vec![
+                "Check that the host is running and that remote access is enabled on it.",
+                "Check the port: the client may listen on a non-default one.",
+                "A firewall between here and the host may block the port.",
+            ],
+            Failure::WrongInterface(_) => vec![
+                "Connect the VPN or network the interface belongs to.",
+                "Check the routes with `route print` on Windows or `ip route get` elsewhere.",
+                "Clear the client's Interface field to let the system choose.",
+            ],
+            Failure::CredentialInjection => vec![
+                "Another program may hold the clipboard; close clipboard tools and retry.",
+                "cmdkey may be blocked by policy; connect without a stored account and type the password.",
+            ],
+            Failure::CredentialsRejected(_) => vec![
+                "Update the password in the client, or in its shared credential if it uses one.",
+                "A locked, disabled or expired account has to be fixed on the server or the domain controller first.",
+                "If the account is right, turn the check off under File → Settings → Remote Desktop.",
+            ],
+            Failure::Other => vec!["Open the diagnostics for the checks that are run on the client."],
+        }
+    }
+}
+
+/// One line of the diagnostics.
+#[derive(Clone, Debug)]
+pub struct Check {
+    pub name: String,
+    pub passed: bool,
+    pub detail: String,
+}
+
+/// The executable `program` runs, looked up on the PATH unless it is a path
+/// already.
+pub fn find_program(program: &str) -> Option<PathBuf> {
+    let candidates = |path: PathBuf| {
+        let mut names = vec![path.clone()];
+        if cfg!(windows) && path.extension().is_none() {
+            names.push(path.with_extension("exe"));
+        }
+        names
+    };
+    if Path::new(program).components().count() > 1 {
+        return candidates(PathBuf::from(program)).into_iter().find(|path| path.is_file());
+    }
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths).flat_map(|dir| candidates(dir.join(program))).find(|path| path.is_file())
+}
+
+/// Live check of a host the background check flagged as unreachable, made
+/// right before connecting so a stale result does not block the connect.
+pub fn precheck(host: &str, port: u16) -> Result<(), LaunchError> {
+    if (host, port).to_socket_addrs().map_or(true, |mut addresses| addresses.next().is_none()) {
+        return Err(LaunchError::new(Failure::HostUnresolvable(host.to_string()), format!("{} could not be resolved.", host)));
+    }
+    match reachability::probe(host, port) {
+        Some(_) => Ok(()),
+        None => Err(LaunchError::new(Failure::PortClosed(host.to_string(), port), format!("{} did not answer on port {}.", host, port))),
+    }
+}
+
+/// Signs in to the RDP server at `host` with the client's stored account
+/// and refuses the connect when the server's Network Level Authentication
+/// rejects it. Servers without NLA, servers met for the first time, and
+/// checks that cannot tell, let the connect go ahead. Blocks for a few
+/// seconds at most.
+pub fn check_sign_in(host: &str, port: u16, client: &Client, pins: &Path) -> Result<(), LaunchError> {
+    let Ok(Outcome::Rejected { required, reason }) = nla::check(host, port, &client.username, &client.domain, &client.password, pins) else {
+        return Ok(());
+    };
+    let account = client.account().unwrap_or_default();
+    let message = if required {
+        format!("{} requires Network Level Authentication and rejected the stored credentials for {}: {}.", host, account, reason)
+    } else {
+        format!("{} rejected the stored credentials for {}: {}.", host, account, reason)
+    };
+    Err(LaunchError::new(Failure::CredentialsRejected(host.to_string()), message))
+}
+
+/// Refuses a connect to `host` that would leave through another interface
+/// than `interface`.
+pub fn check_route(interface: &str, host: &str, port: u16) -> Result<(), LaunchError> {
+    route::check(interface, host, port).map_err(|e| LaunchError::new(Failure::WrongInterface(interface.trim().to_string()), format!("{}.", e)))
+}
+
+/// Resolves `host`, probes `port`, checks the route if the client names an
+/// interface and looks for `program`. Blocks for up to the probe timeout.
+pub fn run_checks(host: &str, port: u16, interface: &str, program: &str) -> Vec<Check> {
+    let mut checks = Vec::new();
+    let addresses: Vec<String> = (host, port).to_socket_addrs().map(|addresses| addresses.map(|address| address.ip().to_string()).collect()).unwrap_or_default();
+    checks.push(Check {
+        name: "Name resolution".to_string(),
+        passed: !addresses.is_empty(),
+        detail: if addresses.is_empty() { format!("{} does not resolve", host) } else { addresses.join(", ") },
+    });
+    if !addresses.is_empty() {
+        let answer = reachability::probe(host, port);
+        checks.push(Check {
+            name: format!("Port {}", port),
+            passed: answer.is_some(),
+            detail: match answer {
+                Some(time) => format!("open, answered in {} ms", time.as_millis()),
+                None => "no answer".to_string(),
+            },
+        });
+    }
+    if !interface.trim().is_empty() {
+        let route = route::check(interface, host, port);
+        checks.push(Check {
+            name: format!("Route through {}", interface.trim()),
+            passed: route.is_ok(),
+            detail: route.err().unwrap_or_else(|| "ok".to_string()),
+        });
+    }
+    let found = find_program(program);
+    checks.push(Check {
+        name: "Viewer".to_string(),
+        passed: found.is_some(),
+        detail: match found {
+            Some(path) => path.display().to_string(),
+            None => format!("{} not found", program),
+        },
+    });
+    checks
+}
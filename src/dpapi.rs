@@ -0,0 +1,64 @@
+/// Encrypts `data` with the Windows Data Protection API, bound to the
+/// current Windows user.
+#[cfg(windows)]
+pub fn protect(data: &[u8]) -> Result<Vec<u8>, String> {
+    use std::ptr::null_mut;
+    use winapi::um::dpapi::CryptProtectData;
+    use winapi::um::wincrypt::DATA_BLOB;
+
+    let mut input = DATA_BLOB {
+        cbData: data.len() as u32,
+        pbData: data.as_ptr() as *mut u8,
+    };
+    let mut output = DATA_BLOB {
+        cbData: 0,
+        pbData: null_mut(),
+    };
+    let ok = unsafe { CryptProtectData(&mut input, std::ptr::null(), null_mut(), null_mut(), null_mut(), 0, &mut output) };
+    if ok == 0 {
+        return Err("CryptProtectData failed".to_string());
+    }
+    Ok(take_blob(output))
+}
+
+#[cfg(windows)]
+pub fn unprotect(data: &[u8]) -> Result<Vec<u8>, String> {
+    use std::ptr::null_mut;
+    use winapi::um::dpapi::CryptUnprotectData;
+    use winapi::um::wincrypt::DATA_BLOB;
+
+    let mut input = DATA_BLOB {
+        cbData: data.len() as u32,
+        pbData: data.as_ptr() as *mut u8,
+    };
+    let mut output = DATA_BLOB {
+        cbData: 0,
+        pbData: null_mut(),
+    };
+    let ok = unsafe { CryptUnprotectData(&mut input, null_mut(), null_mut(), null_mut(), null_mut(), 0, &mut output) };
+    if ok == 0 {
+        return Err("CryptUnprotectData failed".to_string());
+    }
+    Ok(take_blob(output))
+}
+
+/// Copies a blob allocated by DPAPI and frees the original.
+#[cfg(windows)]
+fn take_blob(blob: winapi::um::wincrypt::DATA_BLOB) -> Vec<u8> {
+    use winapi::um::winbase::LocalFree;
+    unsafe {
+        let data = std::slice::from_raw_parts(blob.pbData, blob.cbData as usize).to_vec();
+        LocalFree(blob.pbData as _);
+        data
+    }
+}
+
+#[cfg(not(windows))]
+pub fn protect(_data: &[u8]) -> Result<Vec<u8>, String> {
+    Err("DPAPI is only available on Windows".to_string())
+}
+
+#[cfg(not(windows))]
+pub fn unprotect(_data: &[u8]) -> Result<Vec<u8>, String> {
+    Err("DPAPI is only available on Windows".to_string())
+}
@@ -0,0 +1,169 @@
+//! Clients that are probably the same machine entered twice: the same name,
+//! or the same host, protocol and user name. Imports ask what to do with
+//! such clients, and Tools → Find Duplicates lists those already stored.
+
+use crate::client::Client;
+use zeroize::Zeroize;
+
+/// What an import does with a client that duplicates a stored one.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Resolution {
+    /// Fills in what the stored client lacks and keeps the rest.
+    #[default]
+    Merge,
+    /// Leaves the stored client as it is.
+    Skip,
+    /// Replaces the stored client's details with the imported ones.
+    Overwrite,
+}
+
+impl Resolution {
+    pub const ALL: [Resolution; 3] = [Resolution::Merge, Resolution::Skip, Resolution::Overwrite];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Resolution::Merge => "Merge",
+            Resolution::Skip => "Skip",
+            Resolution::Overwrite => "Overwrite",
+        }
+    }
+}
+
+/// An imported client that looks like a stored one.
+pub struct ImportConflict {
+    pub incoming: Client,
+    pub existing_id: String,
+    pub resolution: Resolution,
+}
+
+/// Why `a` and `b` look like the same client, if they do.
+pub fn duplicate_reason(a: &Client, b: &Client) -> Option<&'static str> {
+    let name = |client: &Client| client.name.trim().to_lowercase();
+    if !name(a).is_empty() && name(a) == name(b) {
+        return Some("same name");
+    }
+    let (host_a, host_b) = (a.endpoint().0, b.endpoint().0);
+    let same_host = !host_a.is_empty() && host_a.eq_ignore_ascii_case(host_b) && a.endpoint().1 == b.endpoint().1;
+    (same_host && a.protocol == b.protocol && a.username.eq_ignore_ascii_case(&b.username)).then_some("same address and user")
+}
+
+/// Splits `imported` into clients to merge as usual and those that are new
+/// to the vault but duplicate a stored client. Clients with the id of a
+/// stored one are updates, not duplicates.
+pub fn split_conflicts(clients: &[Client], imported: Vec<Client>) -> (Vec<Client>, Vec<ImportConflict>) {
+    let mut clean = Vec::new();
+    let mut conflicts = Vec::new();
+    for incoming in imported {
+        if clients.iter().any(|client| client.id == incoming.id) {
+            clean.push(incoming);
+            continue;
+        }
+        let existing = clients.iter().find(|client| client.trashed_at.is_none() && duplicate_reason(client, &incoming).is_some());
+        match existing {
+            Some(existing) => conflicts.push(ImportConflict { existing_id: existing.id.clone(), incoming, resolution: Resolution::default() }),
+            None => clean.push(incoming),
+        }
+    }
+    (clean, conflicts)
+}
+
+/// Applies the chosen resolutions. Returns how many stored clients changed.
+pub fn resolve(clients: &mut [Client], conflicts: Vec<ImportConflict>) -> usize {
+    let mut changed = 0;
+    for conflict in conflicts {
+        let Some(existing) = clients.iter_mut().find(|client| client.id == conflict.existing_id) else {
+            continue;
+        };
+        let before = existing.clone();
+        match conflict.resolution {
+            Resolution::Merge => merge_into(existing, conflict.incoming),
+            Resolution::Skip => {}
+            Resolution::Overwrite => overwrite(existing, conflict.incoming),
+        }
+        if *existing != before {
+            changed += 1;
+        }
+    }
+    changed
+}
+
+/// Fills the fields `target` leaves empty from `other`, and adds the tags,
+/// variables and attachments it does not have yet.
+pub fn merge_into(target: &mut Client, other: Client) {
+    let fill = |field: &mut String, value: String| {
+        if field.trim().is_empty() {
+            *field = value;
+        }
+    };
+    fill(&mut target.ip, other.ip);
+    fill(&mut target.group, other.group);
+    // A client keeps its own account or a shared one, not parts of both.
+    if target.credential.is_none() && target.username.trim().is_empty() {
+        target.credential = other.credential;
+    }
+    if target.credential.is_none() {
+        fill(&mut target.username, other.username);
+        fill(&mut target.domain, other.domain);
+        fill(&mut target.password, other.password);
+    }
+    fill(&mut target.reference, other.reference);
+    fill(&mut target.interface, other.interface);
+    if target.notes.trim().is_empty() {
+        target.notes = other.notes;
+    } else if !other.notes.trim().is_empty() && !target.notes.contains(other.notes.trim()) {
+        target.notes = format!("{}\n{}", target.notes, other.notes);
+    }
+    target.port = target.port.or(other.port);
+    target.expires = target.expires.or(other.expires);
+    target.placement = target.placement.or(other.placement);
+    target.display = target.display.or(other.display);
+    target.redirection = target.redirection.or(other.redirection);
+    target.session_limit = target.session_limit.or(other.session_limit);
+    target.farm = target.farm.take().or(other.farm);
+    target.gateway = target.gateway.take().or(other.gateway);
+    if target.owner.is_empty() {
+        target.owner = other.owner;
+    }
+    for tag in other.tags {
+        if !target.has_tag(&tag) {
+            target.tags.push(tag);
+        }
+    }
+    for (name, value) in other.variables {
+        target.variables.entry(name).or_insert(value);
+    }
+    for attachment in other.attachments {
+        if !target.attachments.iter().any(|own| own.id == attachment.id) {
+            target.attachments.push(attachment);
+        }
+    }
+}
+
+/// Takes every detail of `other` into `target`, keeping `target`'s id and
+/// its attachments, and its password if `other` has none.
+fn overwrite(target: &mut Client, other: Client) {
+    let id = std::mem::take(&mut target.id);
+    let attachments = std::mem::take(&mut target.attachments);
+    let mut password = std::mem::take(&mut target.password);
+    *target = other;
+    target.id = id;
+    target.attachments = attachments;
+    if target.password.is_empty() {
+        std::mem::swap(&mut target.password, &mut password);
+    }
+    password.zeroize();
+}
+
+/// Stored clients that look alike, as groups of indices into `clients`.
+/// Trashed clients are left out.
+pub fn find_duplicates(clients: &[Client]) -> Vec<Vec<usize>> {
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for (index, client) in clients.iter().enumerate().filter(|(_, client)| client.trashed_at.is_none()) {
+        match groups.iter_mut().find(|group| group.iter().any(|&other| duplicate_reason(&clients[other], client).is_some())) {
+            Some(group) => group.push(index),
+            None => groups.push(vec![index]),
+        }
+    }
+    groups.retain(|group| group.len() > 1);
+    groups
+}
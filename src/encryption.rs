@@ -1,16 +1,198 @@
 use aes_gcm::aead::{Aead, KeyInit, OsRng, generic_array::GenericArray};
 use aes_gcm::{Aes256Gcm, Nonce};
+use aes_gcm_siv::Aes256GcmSiv;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use argon2::{Algorithm, Argon2, Params, Version};
+use hmac::{Hmac, Mac};
 use rand::RngCore;
+use sha2::{Digest, Sha256};
 
 pub const KEY_SIZE: usize = 32; // 256 bits for AES-256
 pub const NONCE_SIZE: usize = 12; // Recommended size for AES-GCM
+pub const SALT_SIZE: usize = 16;
+pub const MAC_SIZE: usize = 32; // HMAC-SHA256
+/// Leading nonce bytes holding the save counter; the rest are random.
+const COUNTER_SIZE: usize = 8;
 
+/// Argon2id cost: 19 MiB of memory, two passes, one lane (the OWASP
+/// baseline), which takes a fraction of a second per unlock.
+const ARGON2_MEMORY_KIB: u32 = 19 * 1024;
+const ARGON2_PASSES: u32 = 2;
+const ARGON2_LANES: u32 = 1;
+/// Bytes of the key check value kept in the vault header.
+pub const KEY_CHECK_SIZE: usize = 16;
+
+/// The Argon2id cost a vault key was derived with. New keys use the
+/// defaults above; vaults record theirs, so the defaults can be raised
+/// without locking out vaults created before.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub passes: u32,
+    pub lanes: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self { memory_kib: ARGON2_MEMORY_KIB, passes: ARGON2_PASSES, lanes: ARGON2_LANES }
+    }
+}
+
+impl KdfParams {
+    /// Whether Argon2 accepts the values and an unlock with them stays
+    /// within 4 GiB of memory; a file asking for more is not trusted.
+    pub fn is_supported(&self) -> bool {
+        (1..=16).contains(&self.lanes) && (1..=64).contains(&self.passes) && (8 * self.lanes..=4 * 1024 * 1024).contains(&self.memory_kib)
+    }
+}
+
+/// Key the vault was encrypted with before master keys were introduced.
+pub const LEGACY_KEY: [u8; KEY_SIZE] = [0; KEY_SIZE];
+
+#[allow(dead_code)]
 pub fn generate_key() -> [u8; KEY_SIZE] {
     let mut key = [0u8; KEY_SIZE];
     OsRng.fill_bytes(&mut key);
     key
 }
 
+pub fn generate_salt() -> [u8; SALT_SIZE] {
+    let mut salt = [0u8; SALT_SIZE];
+    OsRng.fill_bytes(&mut salt);
+    salt
+}
+
+/// `size` bytes from the OS random source, for padding that must not be
+/// told apart from ciphertext.
+pub fn random_bytes(size: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; size];
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+}
+
+/// Derives the vault key from the master key with Argon2id.
+pub fn derive_key(password: &str, salt: &[u8; SALT_SIZE]) -> [u8; KEY_SIZE] {
+    derive_key_with(password, salt, &KdfParams::default())
+}
+
+/// Like `derive_key`, with the cost recorded in a vault header; `params`
+/// must be `is_supported`.
+pub fn derive_key_with(password: &str, salt: &[u8; SALT_SIZE], params: &KdfParams) -> [u8; KEY_SIZE] {
+    let params = Params::new(params.memory_kib, params.passes, params.lanes, Some(KEY_SIZE)).expect("valid Argon2 parameters");
+    let mut key = [0u8; KEY_SIZE];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .expect("Argon2 accepts any password with a 16-byte salt");
+    key
+}
+
+/// Unsalted SHA-256 of the password. Only used to open vaults written before
+/// the salted header, and for the separately encrypted secrets of older
+/// JSON exports.
+pub fn generate_key_from_password(password: &str) -> [u8; KEY_SIZE] {
+    let mut key = [0u8; KEY_SIZE];
+    key.copy_from_slice(&Sha256::digest(password.as_bytes()));
+    key
+}
+
+/// HMAC-SHA256 keyed with a subkey of `key`, so the MAC and the cipher never
+/// share a key.
+fn keyed_mac(key: &[u8; KEY_SIZE]) -> Hmac<Sha256> {
+    let mut subkey = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC takes any key size");
+    subkey.update(b"remote_desktop_manager vault mac");
+    <Hmac<Sha256> as Mac>::new_from_slice(&subkey.finalize().into_bytes()).expect("HMAC takes any key size")
+}
+
+pub fn mac(data: &[u8], key: &[u8; KEY_SIZE]) -> [u8; MAC_SIZE] {
+    let mut mac = keyed_mac(key);
+    mac.update(data);
+    mac.finalize().into_bytes().into()
+}
+
+/// A value derived from `key` alone, stored in the vault header to tell a
+/// wrong key from a damaged file. Its own label keeps it unrelated to the
+/// MAC and cipher keys.
+pub fn key_check(key: &[u8; KEY_SIZE]) -> [u8; KEY_CHECK_SIZE] {
+    let mut check = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC takes any key size");
+    check.update(b"remote_desktop_manager key check");
+    let mut value = [0u8; KEY_CHECK_SIZE];
+    value.copy_from_slice(&check.finalize().into_bytes()[..KEY_CHECK_SIZE]);
+    value
+}
+
+/// Compares in constant time.
+pub fn verify_mac(data: &[u8], tag: &[u8], key: &[u8; KEY_SIZE]) -> bool {
+    let mut mac = keyed_mac(key);
+    mac.update(data);
+    mac.verify_slice(tag).is_ok()
+}
+
+/// Nonces for the saves under one key: a big-endian save counter followed by
+/// random bytes. The counter resumes after the nonce last written with the
+/// key, so no two saves share one; the random part covers a vault rolled
+/// back to an older copy, or saved by two instances at once.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct NonceSequence {
+    next: u64,
+}
+
+impl NonceSequence {
+    /// Continues after `nonce`, the one the file was last written with.
+    pub fn after(nonce: &[u8]) -> Self {
+        Self { next: nonce_counter(nonce).saturating_add(1) }
+    }
+
+    /// The next nonce, or an error once the counter has run out and the key
+    /// has to be changed.
+    pub fn next_nonce(&mut self) -> Result<[u8; NONCE_SIZE], String> {
+        if self.next == u64::MAX {
+            return Err("The vault has been saved too often with this key; please change the master key.".to_string());
+        }
+        let mut nonce = [0u8; NONCE_SIZE];
+        nonce[..COUNTER_SIZE].copy_from_slice(&self.next.to_be_bytes());
+        OsRng.fill_bytes(&mut nonce[COUNTER_SIZE..]);
+        self.next += 1;
+        Ok(nonce)
+    }
+}
+
+/// The save counter in a nonce from a `NonceSequence`.
+pub fn nonce_counter(nonce: &[u8]) -> u64 {
+    nonce.get(..COUNTER_SIZE).and_then(|counter| counter.try_into().ok()).map(u64::from_be_bytes).unwrap_or(0)
+}
+
+/// AES-256-GCM-SIV with the given nonce, returned in front of the
+/// ciphertext. Even a repeated nonce only reveals that two saves were
+/// identical, where AES-GCM would give away the authentication key.
+pub fn encrypt_siv(data: &[u8], key: &[u8; KEY_SIZE], nonce: &[u8; NONCE_SIZE]) -> Result<Vec<u8>, aes_gcm::Error> {
+    let cipher = Aes256GcmSiv::new(GenericArray::from_slice(key));
+    let ciphertext = cipher.encrypt(GenericArray::from_slice(nonce), data)?;
+    Ok([nonce.as_slice(), &ciphertext].concat())
+}
+
+pub fn decrypt_siv(data: &[u8], key: &[u8; KEY_SIZE]) -> Result<Vec<u8>, aes_gcm::Error> {
+    if data.len() < NONCE_SIZE {
+        return Err(aes_gcm::Error);
+    }
+    let cipher = Aes256GcmSiv::new(GenericArray::from_slice(key));
+    let (nonce, ciphertext) = data.split_at(NONCE_SIZE);
+    cipher.decrypt(GenericArray::from_slice(nonce), ciphertext)
+}
+
+/// Encrypts a secret kept in the settings file, such as an API token, with
+/// the vault key and base64 encodes it.
+pub fn seal_setting(secret: &str, key: &[u8; KEY_SIZE]) -> Result<String, String> {
+    encrypt(secret.as_bytes(), key).map(|sealed| STANDARD.encode(sealed)).map_err(|_| "Could not encrypt the setting.".to_string())
+}
+
+/// Decrypts a secret sealed with `seal_setting`; `None` if it was sealed
+/// with another key or is damaged.
+pub fn open_setting(sealed: &str, key: &[u8; KEY_SIZE]) -> Option<String> {
+    let data = decrypt(&STANDARD.decode(sealed).ok()?, key).ok()?;
+    String::from_utf8(data).ok()
+}
+
 pub fn encrypt(data: &[u8], key: &[u8; KEY_SIZE]) -> Result<Vec<u8>, aes_gcm::Error> {
     let cipher = Aes256Gcm::new(GenericArray::from_slice(key));
     let mut nonce = [0u8; NONCE_SIZE];
@@ -22,6 +204,9 @@ pub fn encrypt(data: &[u8], key: &[u8; KEY_SIZE]) -> Result<Vec<u8>, aes_gcm::Er
 }
 
 pub fn decrypt(data: &[u8], key: &[u8; KEY_SIZE]) -> Result<Vec<u8>, aes_gcm::Error> {
+    if data.len() < NONCE_SIZE {
+        return Err(aes_gcm::Error);
+    }
     let cipher = Aes256Gcm::new(GenericArray::from_slice(key));
     let (nonce, ciphertext) = data.split_at(NONCE_SIZE);
     cipher.decrypt(Nonce::from_slice(nonce), ciphertext)
@@ -0,0 +1,64 @@
+use crate::diagnosis::Check;
+use crate::fingerprint::OsGuess;
+use crate::monitoring::MonitoredHost;
+use crate::netbox::NetboxObject;
+use crate::sessions::ActiveSession;
+use eframe::egui;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Notifications shared between modules that should not know about each
+/// other. Per-client changes are published by the repository instead.
+#[derive(Clone)]
+pub enum AppEvent {
+    ConnectionStarted(ActiveSession),
+    ConnectionEnded(ActiveSession),
+    VaultSaved,
+    NetboxPulled(Result<Vec<NetboxObject>, String>),
+    /// Number of clients whose tags were pushed.
+    NetboxPushed(Result<usize, String>),
+    MonitoringPolled(Result<Vec<MonitoredHost>, String>),
+    /// Handshake time per client id; `None` for hosts that did not answer.
+    ReachabilityChecked(Vec<(String, Option<Duration>)>),
+    /// What each client id's host seems to run, after it answered the
+    /// reachability check; `None` when the hints did not tell.
+    HostsFingerprinted(Vec<(String, Option<OsGuess>)>),
+    /// Diagnostics run for the client with this id after a failed connect.
+    DiagnosticsRan(String, Vec<Check>),
+    /// A teammate on the LAN came, left or changed sessions.
+    PeersChanged,
+    /// Tools → Rotate Passwords finished changing the password of the
+    /// client with this id.
+    PasswordChanged(String, Result<(), String>),
+    /// The client with this id, waiting in a group connect, can be
+    /// launched: its address answers or waiting for it timed out.
+    PrerequisitesUp(String),
+}
+
+/// Fans events out to every subscriber. Events may be published from any
+/// thread; publishing wakes the UI so it can react without polling.
+#[derive(Clone, Default)]
+pub struct EventBus {
+    subscribers: Arc<Mutex<Vec<Sender<AppEvent>>>>,
+    repaint: Arc<Mutex<Option<egui::Context>>>,
+}
+
+impl EventBus {
+    pub fn subscribe(&self) -> Receiver<AppEvent> {
+        let (sender, receiver) = channel();
+        self.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    pub fn publish(&self, event: AppEvent) {
+        self.subscribers.lock().unwrap().retain(|subscriber| subscriber.send(event.clone()).is_ok());
+        if let Some(ctx) = self.repaint.lock().unwrap().as_ref() {
+            ctx.request_repaint();
+        }
+    }
+
+    pub fn set_repaint_context(&self, ctx: egui::Context) {
+        *self.repaint.lock().unwrap() = Some(ctx);
+    }
+}
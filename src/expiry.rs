@@ -0,0 +1,50 @@
+use crate::client::Client;
+use chrono::{Duration, Local, NaiveDate, Utc};
+
+/// Clients expiring within this many days are flagged in the list.
+pub const EXPIRY_WARNING_DAYS: i64 = 7;
+
+pub enum ExpiryState {
+    Valid,
+    ExpiringSoon(i64),
+    Expired,
+}
+
+pub fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+pub fn expiry_state(client: &Client, today: NaiveDate) -> ExpiryState {
+    match client.expires {
+        Some(expires) if expires < today => ExpiryState::Expired,
+        Some(expires) if (expires - today).num_days() <= EXPIRY_WARNING_DAYS => {
+            ExpiryState::ExpiringSoon((expires - today).num_days())
+        }
+        _ => ExpiryState::Valid,
+    }
+}
+
+/// Parses the `YYYY-MM-DD` expiry entered in the client form; empty means none.
+pub fn parse_expiry(text: &str) -> Result<Option<NaiveDate>, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(None);
+    }
+    NaiveDate::parse_from_str(text, "%Y-%m-%d")
+        .map(Some)
+        .map_err(|_| format!("\"{}\" is not a date, use YYYY-MM-DD.", text))
+}
+
+/// Moves clients whose expiry lies more than `grace_days` in the past to the
+/// Trash. Returns how many were moved.
+pub fn trash_expired(clients: &mut [Client], grace_days: u32, today: NaiveDate) -> usize {
+    let cutoff = today - Duration::days(grace_days as i64);
+    let mut moved = 0;
+    for client in clients.iter_mut().filter(|c| c.trashed_at.is_none()) {
+        if client.expires.map(|expires| expires < cutoff).unwrap_or(false) {
+            client.trashed_at = Some(Utc::now());
+            moved += 1;
+        }
+    }
+    moved
+}
@@ -0,0 +1,193 @@
+//! Guessing what runs on a host that answered the reachability check, to tag
+//! its client `os:windows`, `os:linux` or `os:network` and show an icon for
+//! it. Three hints are weighed, none of them sure on its own: the TTL of a
+//! ping answer, which of a few telling ports answer, and the banner of an
+//! SSH server.
+//!
+//! Only clients without an `os:` tag are guessed for, so a tag set or
+//! changed by hand, even to something like `os:other`, is left alone.
+
+use crate::client::Client;
+use crate::reachability::probe;
+use crate::rotation::without_window;
+use std::io::Read;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+pub const TAG_PREFIX: &str = "os:";
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+/// Hosts fingerprinted at the same time.
+const PARALLEL: usize = 8;
+
+/// The RPC endpoint mapper, which only Windows answers on.
+const RPC_PORT: u16 = 135;
+/// SMB and Remote Desktop. Windows answers on them, but so do Samba and
+/// xrdp, so they only count towards Windows next to an open RPC port.
+const SHARED_PORTS: [u16; 2] = [445, 3389];
+const TELNET_PORT: u16 = 23;
+
+/// Words in an SSH banner, lowercased, naming what sent it.
+const WINDOWS_BANNERS: [&str; 1] = ["windows"];
+const LINUX_BANNERS: [&str; 4] = ["ubuntu", "debian", "raspbian", "fedora"];
+const NETWORK_BANNERS: [&str; 6] = ["cisco", "rosssh", "huawei", "comware", "netscreen", "dropbear"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OsGuess {
+    Windows,
+    Linux,
+    /// A switch, router or firewall.
+    NetworkDevice,
+}
+
+impl OsGuess {
+    pub const ALL: [OsGuess; 3] = [OsGuess::Windows, OsGuess::Linux, OsGuess::NetworkDevice];
+
+    pub fn tag(self) -> &'static str {
+        match self {
+            OsGuess::Windows => "os:windows",
+            OsGuess::Linux => "os:linux",
+            OsGuess::NetworkDevice => "os:network",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            OsGuess::Windows => "Windows",
+            OsGuess::Linux => "Linux",
+            OsGuess::NetworkDevice => "Network device",
+        }
+    }
+
+    pub fn icon(self) -> &'static str {
+        match self {
+            OsGuess::Windows => "⊞",
+            OsGuess::Linux => "🐧",
+            OsGuess::NetworkDevice => "🔀",
+        }
+    }
+
+    /// What the `os:` tag of `client` names, when it names one of these.
+    pub fn of(client: &Client) -> Option<Self> {
+        Self::ALL.into_iter().find(|guess| client.has_tag(guess.tag()))
+    }
+}
+
+/// Whether `client` carries an `os:` tag, guessed or not.
+pub fn has_os_tag(client: &Client) -> bool {
+    client.tags.iter().any(|tag| tag.get(..TAG_PREFIX.len()).is_some_and(|prefix| prefix.eq_ignore_ascii_case(TAG_PREFIX)))
+}
+
+/// A host to fingerprint, with the port its SSH server would answer on.
+#[derive(Clone, Debug)]
+pub struct Candidate {
+    pub client_id: String,
+    pub host: String,
+    pub ssh_port: u16,
+}
+
+/// What the probes of one host found.
+#[derive(Debug, Default)]
+pub struct Evidence {
+    pub ttl: Option<u32>,
+    pub open_ports: Vec<u16>,
+    pub ssh_banner: Option<String>,
+}
+
+impl Evidence {
+    pub fn collect(host: &str, ssh_port: u16) -> Self {
+        let ports = [RPC_PORT].into_iter().chain(SHARED_PORTS).chain([TELNET_PORT, ssh_port]);
+        let open_ports = ports.filter(|port| probe(host, *port).is_some()).collect::<Vec<_>>();
+        let ssh_banner = open_ports.contains(&ssh_port).then(|| ssh_banner(host, ssh_port)).flatten();
+        Self { ttl: ping_ttl(host), open_ports, ssh_banner }
+    }
+
+    /// The system the hints point to most, `None` when they are missing or
+    /// point to two equally.
+    pub fn guess(&self, ssh_port: u16) -> Option<OsGuess> {
+        let mut scores = [0u32; 3];
+        let mut add = |guess: OsGuess, points: u32| scores[guess as usize] += points;
+        // Hosts start at a TTL of 64, 128 or 255, less a hop or two.
+        match self.ttl {
+            Some(0..=64) => add(OsGuess::Linux, 1),
+            Some(65..=128) => add(OsGuess::Windows, 1),
+            Some(_) => add(OsGuess::NetworkDevice, 1),
+            None => {}
+        }
+        if self.open_ports.contains(&RPC_PORT) {
+            add(OsGuess::Windows, 2);
+            if self.open_ports.iter().any(|port| SHARED_PORTS.contains(port)) {
+                add(OsGuess::Windows, 1);
+            }
+        } else if self.open_ports.contains(&ssh_port) {
+            add(OsGuess::Linux, 1);
+        }
+        if self.open_ports.contains(&TELNET_PORT) {
+            add(OsGuess::NetworkDevice, 1);
+        }
+        if let Some(banner) = self.ssh_banner.as_deref().map(str::to_lowercase) {
+            for (words, guess) in [(&WINDOWS_BANNERS[..], OsGuess::Windows), (&LINUX_BANNERS[..], OsGuess::Linux), (&NETWORK_BANNERS[..], OsGuess::NetworkDevice)] {
+                if words.iter().any(|word| banner.contains(word)) {
+                    add(guess, 3);
+                }
+            }
+        }
+        let best = OsGuess::ALL.into_iter().max_by_key(|guess| scores[*guess as usize])?;
+        let points = scores[best as usize];
+        (points > 0 && scores.iter().filter(|score| **score == points).count() == 1).then_some(best)
+    }
+}
+
+/// The identification line an SSH server sends first, such as
+/// `SSH-2.0-OpenSSH_9.6p1 Ubuntu-3ubuntu13`.
+fn ssh_banner(host: &str, port: u16) -> Option<String> {
+    let address = (host, port).to_socket_addrs().ok()?.next()?;
+    let mut stream = TcpStream::connect_timeout(&address, TIMEOUT).ok()?;
+    stream.set_read_timeout(Some(TIMEOUT)).ok()?;
+    let mut buffer = [0; 256];
+    let read = stream.read(&mut buffer).ok()?;
+    let text = String::from_utf8_lossy(&buffer[..read]);
+    text.lines().find(|line| line.starts_with("SSH-")).map(|line| line.trim().to_string())
+}
+
+/// The TTL of the answer to one ping, as the system's ping prints it.
+fn ping_ttl(host: &str) -> Option<u32> {
+    let mut command = Command::new("ping");
+    if cfg!(windows) {
+        command.args(["-n", "1", "-w", "1000"]);
+    } else if cfg!(target_os = "macos") {
+        command.args(["-c", "1", "-t", "1"]);
+    } else {
+        command.args(["-c", "1", "-W", "1"]);
+    }
+    without_window(&mut command);
+    let output = command.arg(host).output().ok()?;
+    let text = String::from_utf8_lossy(&output.stdout).to_lowercase();
+    let (_, rest) = text.split_once("ttl=")?;
+    rest.chars().take_while(char::is_ascii_digit).collect::<String>().parse().ok()
+}
+
+/// Fingerprints every candidate, a few at a time. Returns each client id
+/// with its guess.
+pub fn fingerprint_all(candidates: Vec<Candidate>) -> Vec<(String, Option<OsGuess>)> {
+    if candidates.is_empty() {
+        return Vec::new();
+    }
+    let chunk = candidates.len().div_ceil(PARALLEL);
+    thread::scope(|scope| {
+        let workers: Vec<_> = candidates
+            .chunks(chunk)
+            .map(|candidates| {
+                scope.spawn(move || {
+                    candidates
+                        .iter()
+                        .map(|candidate| (candidate.client_id.clone(), Evidence::collect(&candidate.host, candidate.ssh_port).guess(candidate.ssh_port)))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        workers.into_iter().flat_map(|worker| worker.join().unwrap_or_default()).collect()
+    })
+}
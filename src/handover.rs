@@ -0,0 +1,172 @@
+//! Shift handover report: the sessions opened or closed in a time range and
+//! the hosts currently flagged down, as Markdown or HTML for pasting into a
+//! wiki or ticket.
+
+use crate::history::ConnectionRecord;
+use chrono::{DateTime, Local, Utc};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ReportFormat {
+    #[default]
+    Markdown,
+    Html,
+}
+
+impl ReportFormat {
+    pub fn file_name(self) -> &'static str {
+        match self {
+            ReportFormat::Markdown => "handover.md",
+            ReportFormat::Html => "handover.html",
+        }
+    }
+}
+
+/// A client that the reachability check or the monitoring system reports as
+/// down, with why.
+pub struct DownHost {
+    pub name: String,
+    pub address: String,
+    pub reason: String,
+    /// When its port last answered; `None` if it has not since the vault
+    /// was unlocked.
+    pub last_seen: Option<DateTime<Utc>>,
+}
+
+impl DownHost {
+    fn last_seen_text(&self) -> String {
+        match self.last_seen {
+            Some(time) => local(time).format(TIME).to_string(),
+            None => "not since unlock".to_string(),
+        }
+    }
+}
+
+const TIME: &str = "%Y-%m-%d %H:%M";
+
+fn local(time: DateTime<Utc>) -> DateTime<Local> {
+    time.with_timezone(&Local)
+}
+
+/// When the session ended, without the date if it is the day it started.
+fn closed(record: &ConnectionRecord) -> String {
+    match record.ended.map(local) {
+        Some(ended) if ended.date_naive() == local(record.started).date_naive() => ended.format("%H:%M").to_string(),
+        Some(ended) => ended.format(TIME).to_string(),
+        None => "still open".to_string(),
+    }
+}
+
+fn duration(record: &ConnectionRecord) -> String {
+    match record.ended {
+        Some(ended) => {
+            let minutes = (ended - record.started).num_minutes().max(0);
+            format!("{}:{:02}", minutes / 60, minutes % 60)
+        }
+        None => String::new(),
+    }
+}
+
+fn markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Renders the report for sessions opened or closed between `from` and `to`.
+pub fn render(history: &[ConnectionRecord], down: &[DownHost], from: DateTime<Local>, to: DateTime<Local>, format: ReportFormat) -> String {
+    let (from_utc, to_utc) = (from.with_timezone(&Utc), to.with_timezone(&Utc));
+    let sessions: Vec<&ConnectionRecord> = history.iter().filter(|record| record.touches(from_utc, to_utc)).collect();
+    let title = format!("Shift handover: {} to {}", from.format(TIME), to.format(TIME));
+    let mut out = String::new();
+    match format {
+        ReportFormat::Markdown => {
+            out.push_str(&format!("# {}\n\n## Sessions ({})\n\n", title, sessions.len()));
+            if sessions.is_empty() {
+                out.push_str("No sessions in this period.\n");
+            } else {
+                out.push_str("| Client | Address | Opened | Closed | Duration |\n|---|---|---|---|---|\n");
+                for record in &sessions {
+                    out.push_str(&format!(
+                        "| {} | {} | {} | {} | {} |\n",
+                        markdown_cell(&record.client_name),
+                        markdown_cell(&record.address),
+                        local(record.started).format(TIME),
+                        closed(record),
+                        duration(record)
+                    ));
+                }
+            }
+            out.push_str(&format!("\n## Hosts down ({})\n\n", down.len()));
+            if down.is_empty() {
+                out.push_str("No hosts are flagged down.\n");
+            }
+            for host in down {
+                out.push_str(&format!("- **{}** ({}): {}\n", markdown_cell(&host.name), markdown_cell(&host.address), host.reason));
+            }
+        }
+        ReportFormat::Html => {
+            out.push_str(&format!("<h1>{}</h1>\n<h2>Sessions ({})</h2>\n", html_escape(&title), sessions.len()));
+            if sessions.is_empty() {
+                out.push_str("<p>No sessions in this period.</p>\n");
+            } else {
+                out.push_str("<table>\n<tr><th>Client</th><th>Address</th><th>Opened</th><th>Closed</th><th>Duration</th></tr>\n");
+                for record in &sessions {
+                    out.push_str(&format!(
+                        "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                        html_escape(&record.client_name),
+                        html_escape(&record.address),
+                        local(record.started).format(TIME),
+                        closed(record),
+                        duration(record)
+                    ));
+                }
+                out.push_str("</table>\n");
+            }
+            out.push_str(&format!("<h2>Hosts down ({})</h2>\n", down.len()));
+            if down.is_empty() {
+                out.push_str("<p>No hosts are flagged down.</p>\n");
+            } else {
+                out.push_str("<ul>\n");
+                for host in down {
+                    out.push_str(&format!(
+                        "<li><strong>{}</strong> ({}): {}</li>\n",
+                        html_escape(&host.name),
+                        html_escape(&host.address),
+                        html_escape(&host.reason)
+                    ));
+                }
+                out.push_str("</ul>\n");
+            }
+        }
+    }
+    out
+}
+
+/// The down hosts as a Markdown table, for pasting into an incident ticket.
+pub fn down_hosts_table(down: &[DownHost]) -> String {
+    let mut out = "| Host | Address | Reason | Last seen |\n|---|---|---|---|\n".to_string();
+    for host in down {
+        out.push_str(&format!(
+            "| {} | {} | {} | {} |\n",
+            markdown_cell(&host.name),
+            markdown_cell(&host.address),
+            markdown_cell(&host.reason),
+            host.last_seen_text()
+        ));
+    }
+    out
+}
+
+/// The down hosts as CSV, with the last-seen time in RFC 3339.
+pub fn down_hosts_csv(down: &[DownHost]) -> Result<String, String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(["name", "address", "reason", "last_seen"]).map_err(|e| e.to_string())?;
+    for host in down {
+        let last_seen = host.last_seen.map(|time| time.to_rfc3339()).unwrap_or_default();
+        writer.write_record([&host.name, &host.address, &host.reason, &last_seen]).map_err(|e| e.to_string())?;
+    }
+    let data = writer.into_inner().map_err(|e| e.to_string())?;
+    String::from_utf8(data).map_err(|e| e.to_string())
+}
@@ -0,0 +1,162 @@
+//! Unlocking without typing the master key: the vault key wrapped with
+//! DPAPI for the signed-in Windows user, kept per vault in the config
+//! directory. Optionally it is only unwrapped once Windows Hello has
+//! confirmed the user with a fingerprint, face or PIN. Unlike the session
+//! key cache it survives reboots, until it is turned off or no longer opens
+//! the vault.
+//!
+//! DPAPI binds the wrapped key to the Windows account, not to this app:
+//! other programs running as the same user can unwrap it too, and Windows
+//! Hello only guards this app's own use of it. The master key keeps
+//! working and is what to fall back to.
+
+use crate::dpapi::{protect, unprotect};
+use crate::encryption::KEY_SIZE;
+use crate::layout::vault_key;
+use crate::storage;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use zeroize::Zeroize;
+
+/// Whether this system has DPAPI to wrap the key with.
+pub const AVAILABLE: bool = cfg!(windows);
+
+/// How the key of a vault is unlocked without the master key.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum QuickUnlock {
+    /// By being signed in to the Windows account that stored it.
+    Account,
+    /// By Windows Hello as well.
+    WindowsHello,
+}
+
+impl QuickUnlock {
+    pub fn button(self) -> &'static str {
+        match self {
+            QuickUnlock::Account => "Unlock with Windows Account",
+            QuickUnlock::WindowsHello => "Unlock with Windows Hello",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct WrappedKey {
+    /// The vault key, DPAPI-protected and base64-encoded.
+    key: String,
+    #[serde(default)]
+    hello: bool,
+}
+
+/// Wrapped keys by vault path.
+type WrappedKeys = BTreeMap<String, WrappedKey>;
+
+fn read_all(file: &Path) -> WrappedKeys {
+    fs::read(file).ok().and_then(|data| serde_json::from_slice(&data).ok()).unwrap_or_default()
+}
+
+fn write_all(file: &Path, keys: &WrappedKeys) -> Result<(), String> {
+    if let Some(dir) = file.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let data = serde_json::to_vec_pretty(keys).map_err(|e| e.to_string())?;
+    storage::write_atomically(file, &data).map_err(|e| e.to_string())
+}
+
+/// How `vault` unlocks without its master key; `None` when it does not.
+pub fn enrolled(file: &Path, vault: &Path) -> Option<QuickUnlock> {
+    let keys = read_all(file);
+    let wrapped = keys.get(&vault_key(vault))?;
+    Some(if wrapped.hello { QuickUnlock::WindowsHello } else { QuickUnlock::Account })
+}
+
+/// Wraps `key` for `vault`, replacing the key stored before. Vaults that
+/// no longer exist are dropped on the way.
+pub fn store(file: &Path, vault: &Path, key: &[u8; KEY_SIZE], how: QuickUnlock) -> Result<(), String> {
+    let wrapped = WrappedKey { key: STANDARD.encode(protect(key)?), hello: how == QuickUnlock::WindowsHello };
+    let mut keys = read_all(file);
+    keys.retain(|path, _| Path::new(path).exists());
+    keys.insert(vault_key(vault), wrapped);
+    write_all(file, &keys)
+}
+
+pub fn forget(file: &Path, vault: &Path) -> Result<(), String> {
+    let mut keys = read_all(file);
+    if keys.remove(&vault_key(vault)).is_none() {
+        return Ok(());
+    }
+    write_all(file, &keys)
+}
+
+/// The key stored for `vault`, once Windows Hello confirmed the user if
+/// the key asks for it. `None` when the prompt was cancelled.
+pub fn load(file: &Path, vault: &Path) -> Result<Option<[u8; KEY_SIZE]>, String> {
+    let keys = read_all(file);
+    let wrapped = keys.get(&vault_key(vault)).ok_or("No key is stored for this vault.")?;
+    if wrapped.hello && !verify("Unlock Remote Desktop Manager")? {
+        return Ok(None);
+    }
+    let mut data = unprotect(&STANDARD.decode(&wrapped.key).map_err(|e| e.to_string())?)?;
+    let key = <[u8; KEY_SIZE]>::try_from(data.as_slice()).map_err(|_| "The stored key is damaged.".to_string());
+    data.zeroize();
+    key.map(Some)
+}
+
+/// Whether Windows Hello is set up for the signed-in user. Asked once per
+/// run, as the answer takes a moment.
+#[cfg(windows)]
+pub fn hello_available() -> bool {
+    use std::sync::OnceLock;
+    use windows::Security::Credentials::UI::{UserConsentVerifier, UserConsentVerifierAvailability};
+    static AVAILABLE: OnceLock<bool> = OnceLock::new();
+    *AVAILABLE.get_or_init(|| {
+        UserConsentVerifier::CheckAvailabilityAsync()
+            .and_then(|availability| availability.get())
+            .is_ok_and(|availability| availability == UserConsentVerifierAvailability::Available)
+    })
+}
+
+#[cfg(not(windows))]
+pub fn hello_available() -> bool {
+    false
+}
+
+/// Asks Windows Hello to confirm the signed-in user, showing `message`
+/// over the app's window. Blocks until the prompt is closed, so it is run
+/// off the UI thread; `false` when it was cancelled.
+#[cfg(windows)]
+pub fn verify(message: &str) -> Result<bool, String> {
+    use crate::session_window::main_window;
+    use windows::core::{factory, HSTRING};
+    use windows::Foundation::IAsyncOperation;
+    use windows::Security::Credentials::UI::{UserConsentVerificationResult, UserConsentVerifier};
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::System::WinRT::IUserConsentVerifierInterop;
+    let message = HSTRING::from(message);
+    // Without its window the prompt may open behind the app.
+    let verification = match main_window() {
+        Some(window) => factory::<UserConsentVerifier, IUserConsentVerifierInterop>().and_then(|interop| unsafe {
+            interop.RequestVerificationForWindowAsync::<_, IAsyncOperation<UserConsentVerificationResult>>(HWND(window as isize), &message)
+        }),
+        None => UserConsentVerifier::RequestVerificationAsync(&message),
+    };
+    let result = verification.and_then(|verification| verification.get()).map_err(|e| format!("Windows Hello failed: {}", e))?;
+    match result {
+        UserConsentVerificationResult::Verified => Ok(true),
+        UserConsentVerificationResult::Canceled => Ok(false),
+        UserConsentVerificationResult::DeviceNotPresent | UserConsentVerificationResult::NotConfiguredForUser => {
+            Err("Windows Hello is not set up on this computer.".to_string())
+        }
+        UserConsentVerificationResult::DisabledByPolicy => Err("Windows Hello is turned off by policy.".to_string()),
+        UserConsentVerificationResult::RetriesExhausted => Err("Windows Hello did not recognize you too many times.".to_string()),
+        _ => Err("Windows Hello is busy; try again.".to_string()),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn verify(_message: &str) -> Result<bool, String> {
+    Err("Windows Hello is only available on Windows".to_string())
+}
@@ -0,0 +1,154 @@
+//! The reference the Help window searches: keyboard shortcuts, launcher
+//! placeholders, import formats and how the vault is protected. The
+//! entries are built from the same constants the features use, so a new
+//! shortcut, placeholder or file type shows up here without a second edit.
+
+use crate::bundle::{BUNDLE_FORMAT, BUNDLE_VERSION, ENCRYPTED_KDF};
+use crate::client::Client;
+use crate::encryption::{KdfParams, KEY_SIZE};
+use crate::shortcuts::{self, Shortcut};
+use crate::template::{self, BUILT_IN};
+use crate::transfer::export_csv;
+use crate::watch::WATCH_EXTENSIONS;
+use std::collections::BTreeMap;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Section {
+    Shortcuts,
+    Placeholders,
+    ImportFormats,
+    Security,
+}
+
+impl Section {
+    pub const ALL: [Section; 4] = [Section::Shortcuts, Section::Placeholders, Section::ImportFormats, Section::Security];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Section::Shortcuts => "Keyboard Shortcuts",
+            Section::Placeholders => "Launcher Placeholders",
+            Section::ImportFormats => "Import Formats",
+            Section::Security => "Security Model",
+        }
+    }
+}
+
+/// One entry of the reference.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Topic {
+    pub section: Section,
+    pub title: String,
+    pub text: String,
+}
+
+impl Topic {
+    fn new(section: Section, title: impl Into<String>, text: impl Into<String>) -> Self {
+        Self { section, title: title.into(), text: text.into() }
+    }
+
+    /// Whether every word of `query` appears in the title, the text or the
+    /// section name, ignoring case.
+    pub fn matches(&self, query: &str) -> bool {
+        let haystack = format!("{} {} {}", self.section.label(), self.title, self.text).to_lowercase();
+        query.to_lowercase().split_whitespace().all(|word| haystack.contains(word))
+    }
+}
+
+/// The whole reference, with the shortcuts as `custom` binds them.
+pub fn topics(custom: &BTreeMap<Shortcut, String>) -> Vec<Topic> {
+    let mut topics = Vec::new();
+    let bound = shortcuts::bindings(custom);
+    for command in Shortcut::ALL {
+        let binding = bound.iter().find(|(bound, _)| *bound == command).map(|(_, shortcut)| shortcuts::format(shortcut));
+        let text = match binding {
+            Some(binding) if binding == command.default_binding() => binding,
+            Some(binding) => format!("{} (default {})", binding, command.default_binding()),
+            None => format!("Turned off (default {})", command.default_binding()),
+        };
+        topics.push(Topic::new(Section::Shortcuts, command.label(), text));
+    }
+    topics.push(Topic::new(Section::Shortcuts, "Changing shortcuts", "Settings > Keyboard rebinds each command, e.g. Ctrl+Shift+N. Ctrl stands for Cmd on macOS; an empty binding turns a shortcut off. Shortcuts without Ctrl or Alt do nothing while typing in a field, except on function keys."));
+
+    for name in BUILT_IN {
+        topics.push(Topic::new(Section::Placeholders, format!("{{{}}}", name), template::description(name).unwrap_or_default()));
+    }
+    topics.push(Topic::new(
+        Section::Placeholders,
+        "Client variables",
+        "Each client can define its own placeholders, such as {jump_user} or {region}, under Variables in the editor. Names are letters, digits and underscores.",
+    ));
+    topics.push(Topic::new(
+        Section::Placeholders,
+        "Where placeholders work",
+        "Reference links (File > Reference Links) and SSH and VNC command lines in Settings > Programs. A command line with placeholders replaces the built-in arguments; connecting fails when a client has no value for one.",
+    ));
+
+    let columns = export_csv(&[Client::default()]).ok().and_then(|csv| csv.lines().next().map(str::to_string)).unwrap_or_default();
+    topics.push(Topic::new(
+        Section::ImportFormats,
+        "CSV (.csv)",
+        format!("File > Import CSV. Columns: {}. Tags are comma-separated; rows are matched to stored clients by id. Passwords are never exported or imported.", columns),
+    ));
+    topics.push(Topic::new(Section::ImportFormats, "Remote Desktop file (.rdp)", "One client per file, named after the file, with the full address saved in it."));
+    topics.push(Topic::new(Section::ImportFormats, "RDCMan (.rdg)", "File > Import RDCMan. Groups become folders; user names and passwords, including those of credential profiles, come along where Windows can decrypt them."));
+    topics.push(Topic::new(
+        Section::ImportFormats,
+        "JSON bundle",
+        format!(
+            "File > Export and Import JSON: a \"{}\" bundle, version {}. Plaintext bundles leave passwords out; bundles encrypted with a passphrase ({}) carry them.",
+            BUNDLE_FORMAT, BUNDLE_VERSION, ENCRYPTED_KDF
+        ),
+    ));
+    topics.push(Topic::new(Section::ImportFormats, "mstsc history", "File > Import mstsc History offers the hosts the built-in Remote Desktop client remembers, with their user names."));
+    let extensions: Vec<String> = WATCH_EXTENSIONS.iter().map(|extension| format!(".{}", extension)).collect();
+    topics.push(Topic::new(
+        Section::ImportFormats,
+        "Watch folder",
+        format!("File > Watch Folder imports every {} file dropped into the folder, then moves it to a subfolder.", extensions.join(", ")),
+    ));
+    topics.push(Topic::new(Section::ImportFormats, "NetBox", "File > NetBox Sync pulls devices and virtual machines with a primary IP, tagged with their site and role."));
+
+    let kdf = KdfParams::default();
+    topics.push(Topic::new(
+        Section::Security,
+        "Master key",
+        format!(
+            "The vault key is derived from the master key with Argon2id ({} MiB of memory, {} passes, parallelism {}) and a random salt. The master key itself is never stored.",
+            kdf.memory_kib / 1024,
+            kdf.passes,
+            kdf.lanes
+        ),
+    ));
+    topics.push(Topic::new(
+        Section::Security,
+        "Vault file",
+        format!(
+            "Clients, history and credentials are encrypted together with AES-{}-GCM-SIV in one file. A versioned header, an HMAC-SHA256 and a checksum tell a wrong key from a damaged or altered file. Large vaults can be kept in a SQLite database instead, with every client encrypted on its own.",
+            KEY_SIZE * 8
+        ),
+    ));
+    topics.push(Topic::new(
+        Section::Security,
+        "Clipboard",
+        "Passwords copied when connecting are cleared after a delay (Settings > Security), only if the clipboard still holds them. Clipboard managers keeping a history are warned about; a vault policy can forbid copying altogether.",
+    ));
+    topics.push(Topic::new(Section::Security, "Locking", "Lock vault (or its shortcut) and the idle auto-lock wipe every decrypted secret from memory. Locking the workstation or sleeping locks the vault too and forgets the key, unless Settings > Security says otherwise. A vault policy can cap the auto-lock time."));
+    topics.push(Topic::new(
+        Section::Security,
+        "Key caching",
+        "On Windows the vault key can be remembered until the next reboot, wrapped with DPAPI for the signed-in user, so unlocking again needs no master key.",
+    ));
+    topics.push(Topic::new(
+        Section::Security,
+        "Windows Hello",
+        "Settings > Security can keep a vault's key wrapped with DPAPI, across reboots, so the lock screen unlocks it with the Windows account or with Windows Hello (fingerprint, face or PIN). Other programs running as the same user could unwrap it; the master key keeps working as the fallback.",
+    ));
+    topics.push(Topic::new(Section::Security, "Duress key", "A second master key opens a separate decoy vault. Nothing in the app tells the decoy from the real vault."));
+    topics.push(Topic::new(Section::Security, "Backups", "Every save first copies the vault aside, keeping as many copies as Settings asks for. Restore Backup on the lock screen brings back an earlier copy."));
+    topics
+}
+
+/// The topics matching `query`; all of them for an empty query.
+pub fn search<'a>(topics: &'a [Topic], query: &str) -> Vec<&'a Topic> {
+    topics.iter().filter(|topic| topic.matches(query)).collect()
+}
@@ -0,0 +1,80 @@
+//! Every connect from the vault, successful or not, kept inside it so the
+//! record is as private as the clients themselves.
+
+use crate::collation;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Oldest records are dropped beyond this many.
+pub const MAX_RECORDS: usize = 5000;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ConnectionRecord {
+    pub client_id: String,
+    /// Name and host at the time, so renamed or removed clients still read
+    /// right.
+    pub client_name: String,
+    pub address: String,
+    pub started: DateTime<Utc>,
+    /// `None` while the session is open, or when the app exited first.
+    #[serde(default)]
+    pub ended: Option<DateTime<Utc>>,
+    /// Why the viewer could not be started; such records have no session.
+    #[serde(default)]
+    pub error: Option<String>,
+    /// The account's password was changed on the host rather than a
+    /// session opened, see Tools → Rotate Passwords.
+    #[serde(default)]
+    pub password_changed: bool,
+}
+
+impl ConnectionRecord {
+    /// Whether the session was opened or closed between `from` and `to`.
+    pub fn touches(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> bool {
+        if self.error.is_some() || self.password_changed {
+            return false;
+        }
+        let within = |time: DateTime<Utc>| from <= time && time <= to;
+        within(self.started) || self.ended.is_some_and(within)
+    }
+
+    /// Whether the client's name or address contains `filter`, ignoring case and
+    /// accents.
+    pub fn matches(&self, filter: &str) -> bool {
+        collation::contains(&self.client_name, filter) || collation::contains(&self.address, filter)
+    }
+}
+
+/// The last `count` clients connected to, newest first, each once.
+pub fn recent(history: &[ConnectionRecord], count: usize) -> Vec<&ConnectionRecord> {
+    let mut recent: Vec<&ConnectionRecord> = Vec::new();
+    for record in history.iter().rev() {
+        if recent.len() == count {
+            break;
+        }
+        if !record.password_changed && !recent.iter().any(|seen| seen.client_id == record.client_id) {
+            recent.push(record);
+        }
+    }
+    recent
+}
+
+/// Appends `record`, dropping the oldest ones past `MAX_RECORDS`.
+pub fn append(history: &mut Vec<ConnectionRecord>, record: ConnectionRecord) {
+    history.push(record);
+    if history.len() > MAX_RECORDS {
+        history.drain(..history.len() - MAX_RECORDS);
+    }
+}
+
+/// Marks the session of `client_id` started at `started` as ended. Returns
+/// false if there is no such record.
+pub fn close(history: &mut [ConnectionRecord], client_id: &str, started: DateTime<Utc>, ended: DateTime<Utc>) -> bool {
+    match history.iter_mut().rev().find(|record| record.client_id == client_id && record.started == started) {
+        Some(record) => {
+            record.ended = Some(ended);
+            true
+        }
+        None => false,
+    }
+}
@@ -0,0 +1,139 @@
+//! Entries in the OS hosts file for lab hosts whose names are not published
+//! in DNS. The entries this app adds sit between two marker comments, so
+//! they can be told from the user's own and removed again; nothing outside
+//! the markers is touched.
+
+use std::fs;
+use std::io;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const BEGIN: &str = "# BEGIN remote_desktop_manager";
+const END: &str = "# END remote_desktop_manager";
+
+/// One `address name` line of the hosts file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HostsEntry {
+    pub address: IpAddr,
+    pub name: String,
+}
+
+impl HostsEntry {
+    /// Checks the inputs of the Hosts File dialog.
+    pub fn parse(name: &str, address: &str) -> Result<Self, String> {
+        let name = name.trim();
+        let valid = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '.' || c == '_';
+        if name.is_empty() || !name.chars().all(valid) || name.parse::<IpAddr>().is_ok() {
+            return Err(format!("{:?} is not a host name", name));
+        }
+        let address = address.trim().parse().map_err(|_| format!("{:?} is not an IP address", address.trim()))?;
+        Ok(Self { address, name: name.to_lowercase() })
+    }
+}
+
+/// Where the OS keeps its hosts file.
+#[cfg(windows)]
+pub fn system_path() -> PathBuf {
+    let root = std::env::var_os("SystemRoot").unwrap_or_else(|| "C:\\Windows".into());
+    Path::new(&root).join("System32\\drivers\\etc\\hosts")
+}
+
+#[cfg(not(windows))]
+pub fn system_path() -> PathBuf {
+    PathBuf::from("/etc/hosts")
+}
+
+/// The entries between the markers in `text`.
+pub fn owned_entries(text: &str) -> Vec<HostsEntry> {
+    let mut owned = false;
+    let mut entries = Vec::new();
+    for line in text.lines().map(str::trim) {
+        match line {
+            BEGIN => owned = true,
+            END => owned = false,
+            _ if owned => {
+                let mut fields = line.split_whitespace();
+                if let (Some(Ok(address)), Some(name)) = (fields.next().map(str::parse), fields.next()) {
+                    entries.push(HostsEntry { address, name: name.to_string() });
+                }
+            }
+            _ => {}
+        }
+    }
+    entries
+}
+
+/// `text` with the marked block replaced by `entries`, or dropped when
+/// there are none. Keeps the file's line endings.
+pub fn with_owned_entries(text: &str, entries: &[HostsEntry]) -> String {
+    let newline = if text.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut owned = false;
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match line.trim() {
+            BEGIN => owned = true,
+            END => owned = false,
+            _ if !owned => lines.push(line.to_string()),
+            _ => {}
+        }
+    }
+    while lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+    if !entries.is_empty() {
+        lines.push(String::new());
+        lines.push(BEGIN.to_string());
+        lines.extend(entries.iter().map(|entry| format!("{}\t{}", entry.address, entry.name)));
+        lines.push(END.to_string());
+    }
+    let mut result = lines.join(newline);
+    result.push_str(newline);
+    result
+}
+
+/// The entries this app owns in the hosts file at `path`.
+pub fn read(path: &Path) -> Result<Vec<HostsEntry>, String> {
+    fs::read_to_string(path).map(|text| owned_entries(&text)).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Rewrites the marked block of the hosts file at `path`. The file is
+/// usually writable only by administrators, so a denied write is retried
+/// through the OS elevation prompt.
+pub fn write(path: &Path, entries: &[HostsEntry]) -> Result<(), String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let updated = with_owned_entries(&text, entries);
+    match fs::write(path, &updated) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => write_elevated(path, &updated),
+        Err(e) => Err(format!("{}: {}", path.display(), e)),
+    }
+}
+
+/// Copies `text` over `path` as administrator, then reads the file back:
+/// a declined prompt leaves it unchanged.
+fn write_elevated(path: &Path, text: &str) -> Result<(), String> {
+    let staged = std::env::temp_dir().join(format!("rdm-hosts-{}", uuid::Uuid::new_v4().simple()));
+    fs::write(&staged, text).map_err(|e| e.to_string())?;
+    let result = copy_as_administrator(&staged, path);
+    let _ = fs::remove_file(&staged);
+    result?;
+    match fs::read_to_string(path) {
+        Ok(written) if written == text => Ok(()),
+        Ok(_) => Err("The hosts file was not changed; administrator rights were not granted.".to_string()),
+        Err(e) => Err(format!("{}: {}", path.display(), e)),
+    }
+}
+
+#[cfg(windows)]
+fn copy_as_administrator(from: &Path, to: &Path) -> Result<(), String> {
+    let copy = format!("/c copy /y \"{}\" \"{}\"", from.display(), to.display());
+    let script = format!("Start-Process -FilePath cmd.exe -ArgumentList '{}' -Verb RunAs -Wait -WindowStyle Hidden", copy.replace('\'', "''"));
+    Command::new("powershell").args(["-NoProfile", "-NonInteractive", "-Command", &script]).status().map(|_| ()).map_err(|e| e.to_string())
+}
+
+#[cfg(not(windows))]
+fn copy_as_administrator(from: &Path, to: &Path) -> Result<(), String> {
+    // `cp` onto the existing file keeps its owner and mode.
+    Command::new("pkexec").arg("cp").arg(from).arg(to).status().map(|_| ()).map_err(|e| format!("pkexec could not be started: {}", e))
+}
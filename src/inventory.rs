@@ -0,0 +1,120 @@
+//! Secrets-free inventory of the vault for NetBox and documentation
+//! generators: one entry per client with its name, address, tags, group and
+//! owner. The field names can be renamed or left out to match what the
+//! consuming tool expects.
+
+use crate::client::Client;
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Every field an inventory entry can carry, in output order.
+pub const FIELDS: [&str; 12] = [
+    "id",
+    "name",
+    "address",
+    "protocol",
+    "group",
+    "tags",
+    "reference",
+    "info_only",
+    "owner_name",
+    "owner_email",
+    "owner_phone",
+    "owner_on_call_url",
+];
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, Debug, PartialEq)]
+pub enum InventoryFormat {
+    #[default]
+    Yaml,
+    Json,
+}
+
+impl InventoryFormat {
+    pub fn file_name(self) -> &'static str {
+        match self {
+            InventoryFormat::Yaml => "inventory.yaml",
+            InventoryFormat::Json => "inventory.json",
+        }
+    }
+}
+
+/// Fields in output order under their exported names.
+struct Entry(Vec<(String, Value)>);
+
+impl Serialize for Entry {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (name, value) in &self.0 {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
+
+/// The name `field` is exported as: its entry in `field_names`, or the field
+/// itself. An empty name leaves the field out.
+pub fn exported_name<'a>(field: &'a str, field_names: &'a BTreeMap<String, String>) -> &'a str {
+    field_names.get(field).map(|name| name.trim()).unwrap_or(field)
+}
+
+fn field_value(client: &Client, field: &str) -> Value {
+    let text = match field {
+        "id" => &client.id,
+        "name" => &client.name,
+        "address" => &client.ip,
+        "protocol" => return serde_json::to_value(client.protocol).unwrap_or(Value::Null),
+        "group" => &client.group,
+        "tags" => return Value::from(client.tags.clone()),
+        "reference" => &client.reference,
+        "info_only" => return Value::Bool(client.info_only),
+        "owner_name" => &client.owner.name,
+        "owner_email" => &client.owner.email,
+        "owner_phone" => &client.owner.phone,
+        "owner_on_call_url" => &client.owner.on_call_url,
+        _ => return Value::Null,
+    };
+    Value::String(text.clone())
+}
+
+/// Serializes every client outside the Trash as a list of entries. Nothing
+/// secret is read: no passwords, user names, notes or attachments.
+pub fn export_inventory(clients: &[Client], format: InventoryFormat, field_names: &BTreeMap<String, String>) -> Result<String, String> {
+    let entries: Vec<Entry> = clients
+        .iter()
+        .filter(|client| client.trashed_at.is_none())
+        .map(|client| {
+            Entry(
+                FIELDS
+                    .iter()
+                    .map(|field| (exported_name(field, field_names), field))
+                    .filter(|(name, _)| !name.is_empty())
+                    .map(|(name, field)| (name.to_string(), field_value(client, field)))
+                    .collect(),
+            )
+        })
+        .collect();
+    match format {
+        InventoryFormat::Yaml => serde_yaml::to_string(&entries).map_err(|e| e.to_string()),
+        InventoryFormat::Json => serde_json::to_string_pretty(&entries).map_err(|e| e.to_string()),
+    }
+}
+
+/// Field names exported under the same name twice.
+pub fn duplicate_names(field_names: &BTreeMap<String, String>) -> Vec<String> {
+    let mut seen = Vec::new();
+    let mut duplicates = Vec::new();
+    for field in FIELDS {
+        let name = exported_name(field, field_names);
+        if name.is_empty() {
+            continue;
+        }
+        if seen.contains(&name) && !duplicates.iter().any(|duplicate| duplicate == name) {
+            duplicates.push(name.to_string());
+        }
+        seen.push(name);
+    }
+    duplicates
+}
@@ -0,0 +1,138 @@
+//! Hands `--connect`, `rdm://connect/` links and `--search` over to an already running instance.
+//!
+//! The running instance listens on an ephemeral loopback port and writes the
+//! port together with a random token to a file in the per-user temp
+//! directory. A second launch reads that file and sends the token and the
+//! command as one line; anything without the right token is ignored.
+
+use crate::link::parse_connect_link;
+use eframe::egui;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+use std::time::Duration;
+use uuid::Uuid;
+
+const IO_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// An action requested on the command line.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RemoteCommand {
+    /// Connect to the client with this name.
+    Connect(String),
+    /// Open the search window with this query.
+    Search(String),
+}
+
+impl RemoteCommand {
+    /// Finds `--connect <name>` or `--search <query>` among the arguments,
+    /// or an `rdm://connect/<name>` link as the first one.
+    pub fn from_args(args: &[String]) -> Option<Self> {
+        if let Some(name) = args.get(1).and_then(|arg| parse_connect_link(arg)) {
+            return Some(Self::Connect(name));
+        }
+        args.windows(2).find_map(|pair| match pair[0].as_str() {
+            "--connect" => Some(Self::Connect(pair[1].clone())),
+            "--search" => Some(Self::Search(pair[1].clone())),
+            _ => None,
+        })
+    }
+
+    fn encode(&self) -> String {
+        match self {
+            Self::Connect(name) => format!("connect\t{}", name),
+            Self::Search(query) => format!("search\t{}", query),
+        }
+    }
+
+    fn decode(text: &str) -> Option<Self> {
+        match text.split_once('\t')? {
+            ("connect", name) => Some(Self::Connect(name.to_string())),
+            ("search", query) => Some(Self::Search(query.to_string())),
+            _ => None,
+        }
+    }
+}
+
+fn endpoint_path() -> PathBuf {
+    std::env::temp_dir().join("remote_desktop_manager.ipc")
+}
+
+/// Sends `command` to a running instance. Returns false when there is none,
+/// in which case the caller starts the app itself.
+pub fn send(command: &RemoteCommand) -> bool {
+    let Ok(endpoint) = fs::read_to_string(endpoint_path()) else {
+        return false;
+    };
+    let Some((port, token)) = endpoint.trim().split_once(' ') else {
+        return false;
+    };
+    let Ok(port) = port.parse::<u16>() else {
+        return false;
+    };
+    let Ok(mut stream) = TcpStream::connect_timeout(&(Ipv4Addr::LOCALHOST, port).into(), IO_TIMEOUT) else {
+        return false;
+    };
+    let _ = stream.set_read_timeout(Some(IO_TIMEOUT));
+    if writeln!(stream, "{}\t{}", token, command.encode()).is_err() {
+        return false;
+    }
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply).is_ok() && reply.trim() == "ok"
+}
+
+/// Accepts commands from later launches, waking the UI for each one.
+pub fn listen(ctx: egui::Context) -> Result<Receiver<RemoteCommand>, String> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).map_err(|e| e.to_string())?;
+    let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+    let token = Uuid::new_v4().simple().to_string();
+    write_endpoint(&format!("{} {}", port, token))?;
+
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let _ = stream.set_read_timeout(Some(IO_TIMEOUT));
+            let mut line = String::new();
+            let mut reader = BufReader::new(&stream);
+            if reader.read_line(&mut line).is_err() {
+                continue;
+            }
+            let command = line
+                .trim_end_matches(['\r', '\n'])
+                .split_once('\t')
+                .filter(|(received, _)| *received == token)
+                .and_then(|(_, command)| RemoteCommand::decode(command));
+            let Some(command) = command else {
+                continue;
+            };
+            if sender.send(command).is_err() {
+                return;
+            }
+            let _ = (&stream).write_all(b"ok\n");
+            ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+            ctx.request_repaint();
+        }
+    });
+    Ok(receiver)
+}
+
+#[cfg(unix)]
+fn write_endpoint(endpoint: &str) -> Result<(), String> {
+    use std::os::unix::fs::OpenOptionsExt;
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(endpoint_path())
+        .map_err(|e| e.to_string())?;
+    file.write_all(endpoint.as_bytes()).map_err(|e| e.to_string())
+}
+
+#[cfg(not(unix))]
+fn write_endpoint(endpoint: &str) -> Result<(), String> {
+    fs::write(endpoint_path(), endpoint).map_err(|e| e.to_string())
+}
@@ -0,0 +1,77 @@
+use crate::dpapi::{protect, unprotect};
+use crate::encryption::KEY_SIZE;
+use crate::layout::vault_key;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use zeroize::Zeroize;
+
+/// A cache written before the last boot belongs to an earlier OS session.
+const BOOT_TIME_TOLERANCE_SECS: u64 = 120;
+
+/// The vault key wrapped with DPAPI, remembered until the next reboot.
+#[derive(Serialize, Deserialize)]
+struct CachedKey {
+    boot_time: u64,
+    key: String,
+}
+
+/// One cache per vault, named by a hash of its path so the temp directory
+/// does not tell which vaults there are.
+fn cache_path(vault: &Path) -> PathBuf {
+    let digest = Sha256::digest(vault_key(vault).as_bytes());
+    let name: String = digest[..8].iter().map(|byte| format!("{:02x}", byte)).collect();
+    std::env::temp_dir().join(format!("remote_desktop_manager-{}.key", name))
+}
+
+pub fn store(vault: &Path, key: &[u8; KEY_SIZE]) -> Result<(), String> {
+    let cached = CachedKey {
+        boot_time: boot_time()?,
+        key: STANDARD.encode(protect(key)?),
+    };
+    let data = serde_json::to_vec(&cached).map_err(|e| e.to_string())?;
+    fs::write(cache_path(vault), data).map_err(|e| e.to_string())
+}
+
+/// Returns the key cached for `vault` if it was stored during the current
+/// OS session.
+pub fn load(vault: &Path) -> Option<[u8; KEY_SIZE]> {
+    let cached: CachedKey = serde_json::from_slice(&fs::read(cache_path(vault)).ok()?).ok()?;
+    if boot_time().ok()?.abs_diff(cached.boot_time) > BOOT_TIME_TOLERANCE_SECS {
+        clear(vault);
+        return None;
+    }
+    let mut data = unprotect(&STANDARD.decode(cached.key).ok()?).ok()?;
+    let key = <[u8; KEY_SIZE]>::try_from(data.as_slice()).ok();
+    data.zeroize();
+    key
+}
+
+pub fn clear(vault: &Path) {
+    let _ = fs::remove_file(cache_path(vault));
+}
+
+fn boot_time() -> Result<u64, String> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map_err(|e| e.to_string())?.as_secs();
+    Ok(now.saturating_sub(uptime_secs()?))
+}
+
+#[cfg(windows)]
+fn uptime_secs() -> Result<u64, String> {
+    Ok(unsafe { winapi::um::sysinfoapi::GetTickCount64() } / 1000)
+}
+
+#[cfg(not(windows))]
+fn uptime_secs() -> Result<u64, String> {
+    let uptime = fs::read_to_string("/proc/uptime").map_err(|e| e.to_string())?;
+    uptime
+        .split_whitespace()
+        .next()
+        .and_then(|secs| secs.parse::<f64>().ok())
+        .map(|secs| secs as u64)
+        .ok_or_else(|| "Could not read the system uptime".to_string())
+}
@@ -0,0 +1,206 @@
+//! Starts the viewer processes behind connections. The dry run only logs
+//! what would be started and simulates the session, for demos, screenshots
+//! and tests on machines without mstsc or the other clients.
+//!
+//! Remote Desktop connections go to the client of the system the app runs
+//! on: mstsc on Windows, FreeRDP on Linux and Microsoft's Remote Desktop
+//! app on macOS. All of them open the same .rdp file.
+
+use crate::client::Client;
+use crate::diagnosis::find_program;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+/// How long a simulated session stays open.
+const SIMULATED_SESSION: Duration = Duration::from_secs(30);
+
+/// Simulated sessions get pids counting down from the top of the range, far
+/// above any real process id.
+static NEXT_SIMULATED_PID: AtomicU32 = AtomicU32::new(u32::MAX);
+
+#[cfg(test)]
+thread_local! {
+    /// What the dry run logged on this thread, for tests to read.
+    pub static DRY_RUN_LOG: std::cell::RefCell<Vec<String>> = const { std::cell::RefCell::new(Vec::new()) };
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Launcher {
+    #[default]
+    System,
+    DryRun,
+}
+
+/// FreeRDP programs by preference, FreeRDP 3's names before FreeRDP 2's.
+/// The native Wayland client comes first in a Wayland session.
+const FREERDP_WAYLAND: [&str; 4] = ["wlfreerdp3", "wlfreerdp", "xfreerdp3", "xfreerdp"];
+const FREERDP_X11: [&str; 2] = ["xfreerdp3", "xfreerdp"];
+
+/// Bundle id of Microsoft Remote Desktop, kept by its successor Windows App.
+const MAC_REMOTE_DESKTOP: &str = "com.microsoft.rdc.macos";
+
+/// The Remote Desktop client RDP connections are handed to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RdpViewer {
+    Mstsc,
+    /// FreeRDP, by the name of its program.
+    FreeRdp(&'static str),
+    /// Microsoft's Remote Desktop app on macOS, started through `open`.
+    MacRemoteDesktop,
+}
+
+impl RdpViewer {
+    /// The client for this system. On Linux the FreeRDP programs on the
+    /// PATH are looked for on every connect, so one installed while the
+    /// app runs is picked up; without any, `xfreerdp` is reported missing.
+    pub fn detect() -> Self {
+        if cfg!(windows) {
+            return RdpViewer::Mstsc;
+        }
+        if cfg!(target_os = "macos") {
+            return RdpViewer::MacRemoteDesktop;
+        }
+        let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some_and(|display| !display.is_empty());
+        let candidates: &[&'static str] = if wayland { &FREERDP_WAYLAND } else { &FREERDP_X11 };
+        RdpViewer::FreeRdp(candidates.iter().copied().find(|program| find_program(program).is_some()).unwrap_or("xfreerdp"))
+    }
+
+    pub fn program(self) -> &'static str {
+        match self {
+            RdpViewer::Mstsc => "mstsc",
+            RdpViewer::FreeRdp(program) => program,
+            RdpViewer::MacRemoteDesktop => "open",
+        }
+    }
+
+    /// Whether a stored password can be handed to the client. The macOS
+    /// app can only be given it through the clipboard.
+    pub fn takes_password(self) -> bool {
+        self != RdpViewer::MacRemoteDesktop
+    }
+
+    /// The command opening `rdp_file` for `client`. With a password, FreeRDP
+    /// is told to read the credentials it lacks from its standard input,
+    /// which `send_password` then answers; the password never shows in its
+    /// command line. mstsc finds it in the Credential Manager instead.
+    pub fn command(self, rdp_file: &Path, client: &Client, with_password: bool) -> Command {
+        match self {
+            RdpViewer::Mstsc => {
+                let mut command = Command::new("mstsc");
+                command.arg(rdp_file).arg(format!("/v:{}", client.address()));
+                command
+            }
+            RdpViewer::FreeRdp(program) => {
+                let mut command = Command::new(program);
+                command.arg(rdp_file);
+                // Nobody answers FreeRDP's certificate question on a terminal;
+                // the first certificate seen is trusted and a changed one is not.
+                command.arg(if freerdp_version(program) >= Some(3) { "/cert:tofu" } else { "/cert-tofu" });
+                if with_password {
+                    command.arg("/from-stdin:force").stdin(Stdio::piped());
+                }
+                command
+            }
+            // -W keeps `open` running as long as the app does, so the
+            // connection file is not removed before it was read.
+            RdpViewer::MacRemoteDesktop => {
+                let mut command = Command::new("open");
+                command.args(["-W", "-b", MAC_REMOTE_DESKTOP]).arg(rdp_file);
+                command
+            }
+        }
+    }
+
+    /// Answers the prompts of a FreeRDP started by `command` with a password.
+    /// The account comes from the .rdp file; FreeRDP still asks for a domain
+    /// when it has none, before the password. A FreeRDP that could not be
+    /// answered is stopped rather than left waiting.
+    pub fn send_password(self, process: &mut Process, client: &Client, password: &str) -> io::Result<()> {
+        let Process::Child(child) = process else {
+            return Ok(());
+        };
+        let Some(mut stdin) = child.stdin.take() else {
+            return Ok(());
+        };
+        let domain = if client.domain.is_empty() { "\n" } else { "" };
+        let written = stdin.write_all(format!("{}{}\n", domain, password).as_bytes());
+        if written.is_err() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        written
+    }
+}
+
+/// The major version of the FreeRDP `program`, from its version banner.
+/// Asked once per program while the app runs.
+fn freerdp_version(program: &'static str) -> Option<u32> {
+    static VERSIONS: OnceLock<Mutex<HashMap<&'static str, Option<u32>>>> = OnceLock::new();
+    let mut versions = VERSIONS.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner());
+    *versions.entry(program).or_insert_with(|| {
+        let output = Command::new(program).arg("/version").output().ok()?;
+        let banner = String::from_utf8_lossy(&output.stdout);
+        let version = banner.split_whitespace().skip_while(|word| *word != "version").nth(1)?;
+        version.split('.').next()?.parse().ok()
+    })
+}
+
+/// A running viewer, real or simulated.
+pub enum Process {
+    Child(Child),
+    Simulated(u32),
+}
+
+impl Process {
+    pub fn id(&self) -> u32 {
+        match self {
+            Process::Child(child) => child.id(),
+            Process::Simulated(pid) => *pid,
+        }
+    }
+
+    pub fn is_simulated(&self) -> bool {
+        matches!(self, Process::Simulated(_))
+    }
+
+    /// Blocks until the viewer exits.
+    pub fn wait(&mut self) {
+        match self {
+            Process::Child(child) => {
+                let _ = child.wait();
+            }
+            Process::Simulated(_) => thread::sleep(SIMULATED_SESSION),
+        }
+    }
+}
+
+impl Launcher {
+    pub fn is_dry_run(self) -> bool {
+        self == Launcher::DryRun
+    }
+
+    pub fn spawn(self, command: &mut Command) -> io::Result<Process> {
+        match self {
+            Launcher::System => command.spawn().map(Process::Child),
+            Launcher::DryRun => {
+                self.log(&format!("would run {:?}", command));
+                Ok(Process::Simulated(NEXT_SIMULATED_PID.fetch_sub(1, Ordering::Relaxed)))
+            }
+        }
+    }
+
+    /// Records a step the dry run skips, such as storing a credential.
+    pub fn log(self, message: &str) {
+        if self.is_dry_run() {
+            eprintln!("[dry run] {}", message);
+            #[cfg(test)]
+            DRY_RUN_LOG.with(|log| log.borrow_mut().push(message.to_string()));
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! The workspace left open in a vault, restored when that vault is unlocked
+//! again: the window, the client list's filter, tag, selection and closed
+//! folders, and the view on screen. Each vault has its own, so the work
+//! vault and a personal one keep separate workspaces.
+//!
+//! Layouts live outside the vaults, so a shared vault does not carry one
+//! user's workspace to the next, but each is sealed with its vault's key:
+//! folder and tag names are as telling as the clients themselves.
+
+use crate::client::AppMode;
+use crate::encryption::{open_setting, seal_setting, KEY_SIZE};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+/// The views a layout restores. Dialogs are left closed.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum View {
+    #[default]
+    Clients,
+    Search,
+    Trash,
+    Sessions,
+    History,
+}
+
+impl View {
+    pub fn of(mode: AppMode) -> Self {
+        match mode {
+            AppMode::Search => View::Search,
+            AppMode::Trash => View::Trash,
+            AppMode::Sessions => View::Sessions,
+            AppMode::History => View::History,
+            _ => View::Clients,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Layout {
+    /// Inner size of the window, in points.
+    #[serde(default)]
+    pub window_size: Option<[f32; 2]>,
+    /// Top-left corner of the window on the desktop.
+    #[serde(default)]
+    pub window_position: Option<[f32; 2]>,
+    #[serde(default)]
+    pub view: View,
+    #[serde(default)]
+    pub filter: String,
+    #[serde(default)]
+    pub tag: Option<String>,
+    /// Id of the selected client.
+    #[serde(default)]
+    pub selected: Option<String>,
+    /// Paths of the folders closed in the client list.
+    #[serde(default)]
+    pub collapsed_groups: BTreeSet<String>,
+    #[serde(default)]
+    pub search_query: String,
+}
+
+/// Sealed layouts by vault path.
+type Layouts = BTreeMap<String, String>;
+
+fn read_all(file: &Path) -> Layouts {
+    fs::read(file).ok().and_then(|data| serde_json::from_slice(&data).ok()).unwrap_or_default()
+}
+
+/// How a vault is named in files keyed by vault: its canonical path.
+pub(crate) fn vault_key(vault: &Path) -> String {
+    fs::canonicalize(vault).unwrap_or_else(|_| vault.to_path_buf()).display().to_string()
+}
+
+/// The layout saved for `vault` in `file`; `None` when there is none or it
+/// was sealed with another key.
+pub fn load(file: &Path, vault: &Path, key: &[u8; KEY_SIZE]) -> Option<Layout> {
+    let sealed = read_all(file).remove(&vault_key(vault))?;
+    serde_json::from_str(&open_setting(&sealed, key)?).ok()
+}
+
+/// Saves `layout` as the one of `vault`, dropping the layouts of vaults
+/// that no longer exist.
+pub fn save(file: &Path, vault: &Path, key: &[u8; KEY_SIZE], layout: &Layout) -> Result<(), String> {
+    let mut layouts = read_all(file);
+    layouts.retain(|path, _| Path::new(path).exists());
+    let json = serde_json::to_string(layout).map_err(|e| e.to_string())?;
+    layouts.insert(vault_key(vault), seal_setting(&json, key)?);
+    if let Some(dir) = file.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    fs::write(file, serde_json::to_vec_pretty(&layouts).map_err(|e| e.to_string())?).map_err(|e| e.to_string())
+}
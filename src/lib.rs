@@ -0,0 +1,66 @@
+//! Library half of the app, shared by the binary, benchmarks and fuzz targets.
+
+pub mod app;
+pub mod attachment;
+pub mod bundle;
+pub mod client;
+pub mod collation;
+pub mod composition;
+pub mod credential;
+pub mod cli;
+pub mod clipboard_guard;
+pub mod cmdkey;
+pub mod database;
+pub mod dependencies;
+pub mod diagnosis;
+pub mod dpapi;
+pub mod duplicates;
+pub mod encryption;
+pub mod events;
+pub mod expiry;
+pub mod fingerprint;
+pub mod handover;
+pub mod hello;
+pub mod help;
+pub mod history;
+pub mod hosts_file;
+pub mod inventory;
+pub mod ipc;
+pub mod keycache;
+pub mod layout;
+pub mod launcher;
+pub mod link;
+pub mod monitoring;
+pub mod mstsc_history;
+pub mod netbox;
+pub mod nla;
+pub mod ntlm;
+pub mod os_events;
+pub mod presence;
+pub mod rdg;
+pub mod reachability;
+pub mod rdp;
+pub mod reference;
+pub mod repository;
+pub mod rotation;
+pub mod route;
+pub mod search;
+pub mod secure_prompt;
+pub mod session_window;
+pub mod sessions;
+pub mod settings;
+pub mod shortcuts;
+pub mod ssh;
+#[cfg(windows)]
+pub mod sspi;
+pub mod storage;
+pub mod strength;
+pub mod taskbar;
+pub mod template;
+pub mod transfer;
+#[cfg(test)]
+mod ui_tests;
+pub mod vault;
+pub mod vnc;
+pub mod views;
+pub mod watch;
@@ -0,0 +1,188 @@
+use crate::client::{split_host_port, Client};
+use crate::settings::DefaultPorts;
+use crate::vault::VAULT_EXTENSION;
+use std::process::Command;
+
+pub const LINK_SCHEMES: [&str; 2] = ["rdp", "ssh"];
+/// Links to the app itself, such as `rdm://connect/<name>`.
+pub const COMMAND_SCHEME: &str = "rdm";
+#[cfg(windows)]
+const VAULT_PROG_ID: &str = "RemoteDesktopManager.Vault";
+#[cfg(not(windows))]
+const VAULT_MIME_TYPE: &str = "application/x-remote-desktop-manager-vault";
+
+/// A connection request received as an `rdp://` or `ssh://` URL.
+pub struct ConnectionLink {
+    pub scheme: String,
+    /// `host`, `host:port` or `[v6]:port`.
+    pub address: String,
+    /// The account to sign in as, such as `CORP\admin`, when the link names
+    /// one.
+    pub user: Option<String>,
+}
+
+impl ConnectionLink {
+    /// `client` as the link asks to connect to it: as the link's user when
+    /// it names one. The stored password belongs to the stored account, so
+    /// it is left out for any other user, who is then asked for theirs.
+    pub fn account_for(&self, client: &Client) -> Client {
+        let Some(user) = self.user.as_deref() else {
+            return client.clone();
+        };
+        let (domain, username) = match user.split_once('\\') {
+            Some((domain, username)) => (domain, username),
+            None => (client.domain.as_str(), user),
+        };
+        if username == client.username && domain == client.domain {
+            return client.clone();
+        }
+        Client { username: username.to_string(), domain: domain.to_string(), password: String::new(), credential: None, ..client.clone() }
+    }
+}
+
+/// Parses `rdp://[user@]host[:port]`, `ssh://[user@]host[:port]` and the
+/// `rdp://full%20address=s:host&username=s:user` form used by Microsoft's
+/// client. IPv6 hosts are bracketed, as in `ssh://[fe80::1]:2222`; a port
+/// that is not a number makes the link invalid.
+pub fn parse_link(url: &str) -> Option<ConnectionLink> {
+    let (scheme, rest) = url.trim().split_once("://")?;
+    let scheme = scheme.to_ascii_lowercase();
+    if !LINK_SCHEMES.contains(&scheme.as_str()) {
+        return None;
+    }
+    let setting = |name: &str| rest.split('&').find_map(|part| percent_decode(part)?.strip_prefix(name).map(str::to_string));
+    let (user, authority) = match setting("full address=s:") {
+        Some(address) => (setting("username=s:"), address),
+        None => {
+            let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+            match authority.rsplit_once('@') {
+                Some((user, host)) => (Some(percent_decode(user)?), host.to_string()),
+                None => (None, authority.to_string()),
+            }
+        }
+    };
+    let (host, port) = split_host_port(authority.trim());
+    // A port that is not a number leaves its colon, or its brackets, in the
+    // host; a bare IPv6 address has more than one colon.
+    if host.is_empty() || host.contains(['[', ']', '@', ' ']) || host.matches(':').count() == 1 {
+        return None;
+    }
+    let address = match port {
+        Some(port) if host.contains(':') => format!("[{}]:{}", host, port),
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    };
+    Some(ConnectionLink { scheme, address, user: user.filter(|user| !user.is_empty()) })
+}
+
+/// The client name in an `rdm://connect/<name>` link, percent-decoded.
+pub fn parse_connect_link(url: &str) -> Option<String> {
+    let (scheme, rest) = url.trim().split_once("://")?;
+    if !scheme.eq_ignore_ascii_case(COMMAND_SCHEME) {
+        return None;
+    }
+    let (action, name) = rest.split_once('/')?;
+    if !action.eq_ignore_ascii_case("connect") {
+        return None;
+    }
+    // Browsers may add a trailing slash.
+    let name = percent_decode(name.trim_end_matches('/'))?;
+    (!name.trim().is_empty()).then_some(name)
+}
+
+/// Decodes `%XX` escapes as UTF-8; `None` for malformed ones.
+fn percent_decode(text: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// True if `client` is reached at `address`: the same host, ignoring case,
+/// on the same port. A side without a port uses the default port of the
+/// client's protocol.
+pub fn matches_address(client: &Client, address: &str, defaults: &DefaultPorts) -> bool {
+    let (host, port) = split_host_port(address.trim());
+    client.endpoint().0.eq_ignore_ascii_case(host) && client.effective_port(defaults) == port.unwrap_or(defaults.get(client.protocol))
+}
+
+/// Registers this executable as the handler for `rdp://`, `ssh://` and
+/// `rdm://` links and `.rdm` vault files for the current user.
+#[cfg(windows)]
+pub fn register_handlers() -> Result<(), String> {
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    let command = format!("\"{}\" \"%1\"", exe.display());
+    for scheme in LINK_SCHEMES.into_iter().chain([COMMAND_SCHEME]) {
+        let key = format!("HKCU\\Software\\Classes\\{}", scheme);
+        let description = format!("URL:{} Protocol", scheme);
+        run_reg(&["add", &key, "/ve", "/d", &description, "/f"])?;
+        run_reg(&["add", &key, "/v", "URL Protocol", "/d", "", "/f"])?;
+        run_reg(&["add", &format!("{}\\shell\\open\\command", key), "/ve", "/d", &command, "/f"])?;
+    }
+    let prog_id = format!("HKCU\\Software\\Classes\\{}", VAULT_PROG_ID);
+    run_reg(&["add", &format!("HKCU\\Software\\Classes\\.{}", VAULT_EXTENSION), "/ve", "/d", VAULT_PROG_ID, "/f"])?;
+    run_reg(&["add", &prog_id, "/ve", "/d", "Remote Desktop Manager vault", "/f"])?;
+    run_reg(&["add", &format!("{}\\shell\\open\\command", prog_id), "/ve", "/d", &command, "/f"])?;
+    Ok(())
+}
+
+#[cfg(windows)]
+fn run_reg(args: &[&str]) -> Result<(), String> {
+    let status = Command::new("reg").args(args).status().map_err(|e| e.to_string())?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("reg {} failed", args.join(" ")))
+    }
+}
+
+#[cfg(not(windows))]
+pub fn register_handlers() -> Result<(), String> {
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    let home = std::env::var("HOME").map_err(|e| e.to_string())?;
+    let applications = std::path::Path::new(&home).join(".local/share/applications");
+    std::fs::create_dir_all(&applications).map_err(|e| e.to_string())?;
+    let mime_types: Vec<String> = LINK_SCHEMES.iter().chain([&COMMAND_SCHEME]).map(|s| format!("x-scheme-handler/{};", s)).collect();
+    let desktop_entry = format!(
+        "[Desktop Entry]\nType=Application\nName=Remote Desktop Manager\nExec=\"{}\" %u\nNoDisplay=true\nMimeType={}\n",
+        exe.display(),
+        mime_types.concat()
+    );
+    std::fs::write(applications.join("remote_desktop_manager.desktop"), desktop_entry).map_err(|e| e.to_string())?;
+    for scheme in LINK_SCHEMES.into_iter().chain([COMMAND_SCHEME]) {
+        Command::new("xdg-mime")
+            .args(["default", "remote_desktop_manager.desktop", &format!("x-scheme-handler/{}", scheme)])
+            .status()
+            .map_err(|e| e.to_string())?;
+    }
+
+    // Vault files get their own MIME type and an entry that takes a path.
+    let mime = std::path::Path::new(&home).join(".local/share/mime");
+    std::fs::create_dir_all(mime.join("packages")).map_err(|e| e.to_string())?;
+    let mime_package = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<mime-info xmlns=\"http://www.freedesktop.org/standards/shared-mime-info\">\n  <mime-type type=\"{}\">\n    <comment>Remote Desktop Manager vault</comment>\n    <glob pattern=\"*.{}\"/>\n  </mime-type>\n</mime-info>\n",
+        VAULT_MIME_TYPE, VAULT_EXTENSION
+    );
+    std::fs::write(mime.join("packages/remote_desktop_manager.xml"), mime_package).map_err(|e| e.to_string())?;
+    Command::new("update-mime-database").arg(&mime).status().map_err(|e| e.to_string())?;
+    let vault_entry = format!(
+        "[Desktop Entry]\nType=Application\nName=Remote Desktop Manager\nExec=\"{}\" %f\nNoDisplay=true\nMimeType={};\n",
+        exe.display(),
+        VAULT_MIME_TYPE
+    );
+    std::fs::write(applications.join("remote_desktop_manager-vault.desktop"), vault_entry).map_err(|e| e.to_string())?;
+    Command::new("xdg-mime")
+        .args(["default", "remote_desktop_manager-vault.desktop", VAULT_MIME_TYPE])
+        .status()
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
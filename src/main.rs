@@ -1,26 +1,69 @@
 #![windows_subsystem = "windows"]
 
-mod app;
-mod client;
-mod encryption;
-
-use app::AppState;
-use eframe::egui;
+use remote_desktop_manager::app::AppState;
+use remote_desktop_manager::cli;
+use remote_desktop_manager::ipc::{self, RemoteCommand};
+use remote_desktop_manager::launcher::Launcher;
+use remote_desktop_manager::link::{parse_connect_link, parse_link};
+use remote_desktop_manager::os_events::spawn_watcher;
 use eframe::NativeOptions;
+use std::path::PathBuf;
 
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(code) = cli::run(&args) {
+        std::process::exit(code);
+    }
+    let command = RemoteCommand::from_args(&args);
+    if command.as_ref().is_some_and(ipc::send) {
+        return;
+    }
+    // A clicked rdm://connect/ link only opens the window when the connect
+    // needs it.
+    if let Some(name) = args.get(1).and_then(|arg| parse_connect_link(arg)) {
+        if cli::connect_link(&name) {
+            return;
+        }
+    }
     println!("Remote Desktop Manager is running.");
+    let dry_run = args.iter().any(|arg| arg == "--dry-run");
+    // Otherwise the first argument is either a connection link or a vault
+    // file to open.
+    let argument = args.get(1).filter(|arg| command.is_none() && !arg.starts_with("--")).cloned();
+    let link = argument.as_deref().and_then(parse_link);
+    let vault = argument.filter(|_| link.is_none()).map(PathBuf::from);
     let native_options = NativeOptions {
         window_builder: Some(Box::new(|builder| {
             builder
                 .with_inner_size(eframe::epaint::Vec2::new(600.0, 400.0))
                 .with_title("Remote Desktop Manager")
         })),
+        // Reports the system theme on every platform; the app's own theme
+        // setting decides whether it is used.
+        follow_system_theme: true,
         ..Default::default()
     };
     let _ = eframe::run_native(
         "Remote Desktop Manager",
         native_options,
-        Box::new(|_cc| Box::new(AppState::new())),
+        Box::new(move |cc| {
+            let mut state = match vault {
+                Some(path) => AppState::with_vault_file(path),
+                None => AppState::new(),
+            };
+            state.pending_link = link;
+            state.pending_command = command;
+            if dry_run {
+                state.launcher = Launcher::DryRun;
+            }
+            match ipc::listen(cc.egui_ctx.clone()) {
+                Ok(receiver) => state.remote_commands = Some(receiver),
+                Err(e) => eprintln!("Command-line actions from other launches are disabled: {}", e),
+            }
+            state.events.set_repaint_context(cc.egui_ctx.clone());
+            state.os_events = Some(spawn_watcher(cc.egui_ctx.clone()));
+            state.update_title(&cc.egui_ctx);
+            Box::new(state)
+        }),
     );
 }
@@ -0,0 +1,197 @@
+//! Host status from a monitoring system, shown next to the clients whose
+//! address it monitors. Zabbix reports the availability of each host's
+//! interfaces and whether it is in maintenance; PRTG reports a status per
+//! device.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, Debug, PartialEq)]
+pub enum MonitoringSystem {
+    #[default]
+    None,
+    Zabbix,
+    Prtg,
+}
+
+impl MonitoringSystem {
+    pub fn name(self) -> &'static str {
+        match self {
+            MonitoringSystem::None => "None",
+            MonitoringSystem::Zabbix => "Zabbix",
+            MonitoringSystem::Prtg => "PRTG",
+        }
+    }
+}
+
+/// Ordered by severity, so the worst status wins when several monitored
+/// hosts share an address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HostStatus {
+    Unknown,
+    Up,
+    Maintenance,
+    Down,
+}
+
+impl HostStatus {
+    pub fn label(self) -> &'static str {
+        match self {
+            HostStatus::Unknown => "unknown",
+            HostStatus::Up => "up",
+            HostStatus::Maintenance => "maintenance",
+            HostStatus::Down => "down",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MonitoredHost {
+    pub name: String,
+    /// IPs and DNS names the host is monitored under.
+    pub addresses: Vec<String>,
+    pub status: HostStatus,
+}
+
+/// Zabbix sends numbers as strings.
+fn number(value: Option<&Value>) -> Option<u64> {
+    match value? {
+        Value::String(text) => text.parse().ok(),
+        value => value.as_u64(),
+    }
+}
+
+/// Reads the result of a Zabbix `host.get`. Disabled hosts are left out;
+/// a host is down when any of its interfaces is unavailable.
+pub fn parse_zabbix(result: &Value) -> Vec<MonitoredHost> {
+    let mut hosts = Vec::new();
+    for host in result.as_array().into_iter().flatten() {
+        if number(host.get("status")) == Some(1) {
+            continue;
+        }
+        let interfaces = host.get("interfaces").and_then(Value::as_array).cloned().unwrap_or_default();
+        let availability: Vec<u64> = interfaces.iter().filter_map(|interface| number(interface.get("available"))).collect();
+        let status = if number(host.get("maintenance_status")) == Some(1) {
+            HostStatus::Maintenance
+        } else if availability.contains(&2) {
+            HostStatus::Down
+        } else if availability.contains(&1) {
+            HostStatus::Up
+        } else {
+            HostStatus::Unknown
+        };
+        let mut addresses: Vec<String> = interfaces
+            .iter()
+            .flat_map(|interface| [interface.get("ip"), interface.get("dns")])
+            .filter_map(|address| address.and_then(Value::as_str))
+            .filter(|address| !address.is_empty())
+            .map(str::to_string)
+            .collect();
+        if let Some(name) = host.get("host").and_then(Value::as_str) {
+            addresses.push(name.to_string());
+        }
+        let name = host.get("name").or_else(|| host.get("host")).and_then(Value::as_str).unwrap_or_default();
+        hosts.push(MonitoredHost { name: name.to_string(), addresses, status });
+    }
+    hosts
+}
+
+/// PRTG's `status_raw` codes.
+fn prtg_status(code: u64) -> HostStatus {
+    match code {
+        // Up, Warning, Unusual
+        3 | 4 | 10 => HostStatus::Up,
+        // Down, Down (Acknowledged), Down (Partial)
+        5 | 13 | 14 => HostStatus::Down,
+        // Paused by user, dependency or schedule, Paused until
+        7 | 8 | 9 | 12 => HostStatus::Maintenance,
+        _ => HostStatus::Unknown,
+    }
+}
+
+/// Reads a PRTG `table.json?content=devices` response.
+pub fn parse_prtg(response: &Value) -> Vec<MonitoredHost> {
+    response
+        .get("devices")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|device| {
+            let host = device.get("host").and_then(Value::as_str).filter(|host| !host.is_empty())?;
+            Some(MonitoredHost {
+                name: device.get("device").and_then(Value::as_str).unwrap_or(host).to_string(),
+                addresses: vec![host.to_string()],
+                status: number(device.get("status_raw")).map(prtg_status).unwrap_or(HostStatus::Unknown),
+            })
+        })
+        .collect()
+}
+
+fn error(system: MonitoringSystem, e: ureq::Error) -> String {
+    match e {
+        ureq::Error::Status(401 | 403, _) => format!("{} rejected the API token.", system.name()),
+        ureq::Error::Status(code, response) => format!("{} answered {}: {}", system.name(), code, response.into_string().unwrap_or_default()),
+        ureq::Error::Transport(transport) => format!("Could not reach {}: {}", system.name(), transport),
+    }
+}
+
+/// Fetches every monitored host. `url` is the address of the web
+/// interface; Zabbix needs 6.4 or later for token authentication.
+pub fn fetch(system: MonitoringSystem, url: &str, token: &str) -> Result<Vec<MonitoredHost>, String> {
+    let base = url.trim().trim_end_matches('/');
+    let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+    match system {
+        MonitoringSystem::None => Ok(Vec::new()),
+        MonitoringSystem::Zabbix => {
+            let response: Value = agent
+                .post(&format!("{}/api_jsonrpc.php", base.trim_end_matches("/api_jsonrpc.php")))
+                .set("Authorization", &format!("Bearer {}", token))
+                .send_json(json!({
+                    "jsonrpc": "2.0",
+                    "method": "host.get",
+                    "params": {
+                        "output": ["host", "name", "status", "maintenance_status"],
+                        "selectInterfaces": ["ip", "dns", "available"],
+                    },
+                    "id": 1,
+                }))
+                .map_err(|e| error(system, e))?
+                .into_json()
+                .map_err(|e| e.to_string())?;
+            if let Some(e) = response.get("error") {
+                let message = e.get("data").or_else(|| e.get("message")).and_then(Value::as_str).unwrap_or_default();
+                return Err(format!("Zabbix: {}", message));
+            }
+            Ok(parse_zabbix(response.get("result").unwrap_or(&Value::Null)))
+        }
+        MonitoringSystem::Prtg => {
+            let response: Value = agent
+                .get(&format!("{}/api/table.json", base))
+                .query("content", "devices")
+                .query("columns", "objid,device,host,status")
+                .query("count", "*")
+                .query("apitoken", token)
+                .call()
+                .map_err(|e| error(system, e))?
+                .into_json()
+                .map_err(|e| e.to_string())?;
+            Ok(parse_prtg(&response))
+        }
+    }
+}
+
+/// Status by lowercase address, for looking up clients.
+pub fn status_index(hosts: &[MonitoredHost]) -> HashMap<String, HostStatus> {
+    let mut index: HashMap<String, HostStatus> = HashMap::new();
+    for host in hosts {
+        for address in &host.addresses {
+            let status = index.entry(address.to_lowercase()).or_insert(host.status);
+            *status = (*status).max(host.status);
+        }
+    }
+    index
+}
@@ -0,0 +1,90 @@
+//! Hosts from the Remote Desktop Connection history, read from mstsc's MRU
+//! list under `Terminal Server Client\Default` together with the user names
+//! it remembered under `Terminal Server Client\Servers`.
+
+use crate::client::Client;
+use std::process::Command;
+
+const MRU_KEY: &str = "HKCU\\Software\\Microsoft\\Terminal Server Client\\Default";
+const SERVERS_KEY: &str = "HKCU\\Software\\Microsoft\\Terminal Server Client\\Servers";
+
+pub struct HistoryEntry {
+    pub address: String,
+    /// `DOMAIN\user` or `user` as last typed for this host; may be empty.
+    pub username_hint: String,
+}
+
+impl HistoryEntry {
+    pub fn to_client(&self) -> Client {
+        let (domain, username) = match self.username_hint.split_once('\\') {
+            Some((domain, username)) => (domain.to_string(), username.to_string()),
+            None => (String::new(), self.username_hint.clone()),
+        };
+        Client {
+            name: self.address.clone(),
+            ip: self.address.clone(),
+            username,
+            domain,
+            ..Default::default()
+        }
+    }
+}
+
+/// Splits a `reg query` value line into name and data.
+fn value_line(line: &str) -> Option<(&str, &str)> {
+    let mut parts = line.trim_start().splitn(3, "    ");
+    let name = parts.next()?;
+    let _kind = parts.next()?;
+    Some((name, parts.next()?.trim()))
+}
+
+/// `MRU0`, `MRU1`, ... in order, most recent first.
+pub fn parse_mru(output: &str) -> Vec<String> {
+    let mut entries: Vec<(u32, String)> = output
+        .lines()
+        .filter_map(value_line)
+        .filter_map(|(name, data)| Some((name.strip_prefix("MRU")?.parse().ok()?, data.to_string())))
+        .filter(|(_, data)| !data.is_empty())
+        .collect();
+    entries.sort_by_key(|(index, _)| *index);
+    entries.into_iter().map(|(_, data)| data).collect()
+}
+
+/// `(host, UsernameHint)` pairs from a recursive query of the Servers key.
+pub fn parse_username_hints(output: &str) -> Vec<(String, String)> {
+    let mut hints = Vec::new();
+    let mut host = None;
+    for line in output.lines() {
+        if let Some(key) = line.strip_prefix("HKEY_CURRENT_USER") {
+            host = key.rsplit_once('\\').map(|(_, host)| host.to_string());
+        } else if let (Some(host), Some(("UsernameHint", hint))) = (&host, value_line(line)) {
+            hints.push((host.clone(), hint.to_string()));
+        }
+    }
+    hints
+}
+
+fn reg_query(args: &[&str]) -> Option<String> {
+    let output = Command::new("reg").arg("query").args(args).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The hosts mstsc remembers, most recently used first.
+pub fn read_history() -> Result<Vec<HistoryEntry>, String> {
+    if !cfg!(windows) {
+        return Err("The Remote Desktop Connection history is only available on Windows.".to_string());
+    }
+    let hosts = reg_query(&[MRU_KEY]).map(|output| parse_mru(&output)).unwrap_or_default();
+    let hints = reg_query(&[SERVERS_KEY, "/s"]).map(|output| parse_username_hints(&output)).unwrap_or_default();
+    Ok(hosts
+        .into_iter()
+        .map(|address| HistoryEntry {
+            username_hint: hints
+                .iter()
+                .find(|(host, _)| host.eq_ignore_ascii_case(&address))
+                .map(|(_, hint)| hint.clone())
+                .unwrap_or_default(),
+            address,
+        })
+        .collect())
+}
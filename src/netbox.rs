@@ -0,0 +1,220 @@
+//! Sync with a NetBox instance over its REST API. Pulling turns devices and
+//! virtual machines with a primary IP into clients tagged with their site
+//! and role; pushing writes the clients' own tags back to the objects they
+//! came from.
+
+use crate::client::Client;
+use crate::transfer::ImportSummary;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+/// Objects fetched per request.
+const PAGE_SIZE: usize = 200;
+const TIMEOUT: Duration = Duration::from_secs(20);
+/// Tags derived from NetBox on every pull; they are never pushed back.
+const SITE_TAG: &str = "site:";
+const ROLE_TAG: &str = "role:";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum NetboxKind {
+    Device,
+    VirtualMachine,
+}
+
+impl NetboxKind {
+    fn endpoint(self) -> &'static str {
+        match self {
+            NetboxKind::Device => "dcim/devices",
+            NetboxKind::VirtualMachine => "virtualization/virtual-machines",
+        }
+    }
+}
+
+/// The NetBox object a client was pulled from.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct NetboxLink {
+    pub kind: NetboxKind,
+    pub id: u64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NetboxObject {
+    pub link: NetboxLink,
+    pub name: String,
+    /// Primary IP without its prefix length.
+    pub address: String,
+    pub site: String,
+    /// Site and role tags followed by the object's NetBox tags.
+    pub tags: Vec<String>,
+}
+
+fn nested_str<'a>(object: &'a Value, field: &str, key: &str) -> &'a str {
+    object.get(field).and_then(|nested| nested.get(key)).and_then(Value::as_str).unwrap_or_default()
+}
+
+/// Reads a device or virtual machine from the API; `None` for objects
+/// without a primary IP, which cannot be connected to.
+pub fn parse_object(kind: NetboxKind, object: &Value) -> Option<NetboxObject> {
+    let id = object.get("id")?.as_u64()?;
+    let address = object.get("primary_ip")?.get("address")?.as_str()?;
+    let address = address.split('/').next().unwrap_or(address).to_string();
+    let name = match object.get("name").and_then(Value::as_str) {
+        Some(name) if !name.is_empty() => name.to_string(),
+        _ => address.clone(),
+    };
+    let mut tags = Vec::new();
+    let site = nested_str(object, "site", "slug");
+    if !site.is_empty() {
+        tags.push(format!("{}{}", SITE_TAG, site));
+    }
+    // `device_role` before NetBox 4.0.
+    let role = [nested_str(object, "role", "slug"), nested_str(object, "device_role", "slug")].into_iter().find(|role| !role.is_empty());
+    if let Some(role) = role {
+        tags.push(format!("{}{}", ROLE_TAG, role));
+    }
+    for tag in object.get("tags").and_then(Value::as_array).into_iter().flatten() {
+        if let Some(tag) = tag.get("name").and_then(Value::as_str) {
+            if !tags.iter().any(|existing| existing.eq_ignore_ascii_case(tag)) {
+                tags.push(tag.replace(',', " "));
+            }
+        }
+    }
+    Some(NetboxObject {
+        link: NetboxLink { kind, id },
+        name,
+        address,
+        site: nested_str(object, "site", "name").to_string(),
+        tags,
+    })
+}
+
+fn is_derived_tag(tag: &str) -> bool {
+    tag.starts_with(SITE_TAG) || tag.starts_with(ROLE_TAG)
+}
+
+/// The tags a push writes to NetBox: the client's own, without the site
+/// and role tags a pull adds.
+pub fn pushed_tags(client: &Client) -> Vec<String> {
+    client.tags.iter().filter(|tag| !is_derived_tag(tag)).cloned().collect()
+}
+
+/// Applies pulled objects. Clients pulled before are updated in place and
+/// keep their credentials and local tags; new ones go to a `NetBox/<site>`
+/// group.
+pub fn merge_objects(clients: &mut Vec<Client>, objects: Vec<NetboxObject>) -> ImportSummary {
+    let mut summary = ImportSummary { added: 0, updated: 0 };
+    for object in objects {
+        match clients.iter_mut().find(|client| client.netbox == Some(object.link)) {
+            Some(client) => {
+                client.name = object.name;
+                client.ip = object.address;
+                client.tags.retain(|tag| !is_derived_tag(tag));
+                for tag in object.tags {
+                    if !client.has_tag(&tag) {
+                        client.tags.push(tag);
+                    }
+                }
+                summary.updated += 1;
+            }
+            None => {
+                clients.push(Client {
+                    name: object.name,
+                    ip: object.address,
+                    group: match object.site.as_str() {
+                        "" => "NetBox".to_string(),
+                        site => format!("NetBox/{}", site.replace('/', "-")),
+                    },
+                    tags: object.tags,
+                    netbox: Some(object.link),
+                    ..Default::default()
+                });
+                summary.added += 1;
+            }
+        }
+    }
+    summary
+}
+
+pub struct NetboxClient {
+    base_url: String,
+    token: String,
+    agent: ureq::Agent,
+}
+
+impl NetboxClient {
+    /// `url` is the NetBox address, e.g. `https://netbox.example.com`.
+    pub fn new(url: &str, token: String) -> Self {
+        Self {
+            base_url: format!("{}/api", url.trim().trim_end_matches('/').trim_end_matches("/api")),
+            token,
+            agent: ureq::AgentBuilder::new().timeout(TIMEOUT).build(),
+        }
+    }
+
+    fn request(&self, method: &str, url: &str) -> ureq::Request {
+        self.agent
+            .request(method, url)
+            .set("Authorization", &format!("Token {}", self.token))
+            .set("Accept", "application/json")
+    }
+
+    fn error(e: ureq::Error) -> String {
+        match e {
+            ureq::Error::Status(401 | 403, _) => "NetBox rejected the API token.".to_string(),
+            ureq::Error::Status(code, response) => format!("NetBox answered {}: {}", code, response.into_string().unwrap_or_default()),
+            ureq::Error::Transport(transport) => format!("Could not reach NetBox: {}", transport),
+        }
+    }
+
+    /// Every result of a list endpoint, following the pagination links.
+    fn list(&self, endpoint: &str) -> Result<Vec<Value>, String> {
+        let mut results = Vec::new();
+        let mut next = Some(format!("{}/{}/?limit={}", self.base_url, endpoint, PAGE_SIZE));
+        while let Some(url) = next {
+            let page: Value = self.request("GET", &url).call().map_err(Self::error)?.into_json().map_err(|e| e.to_string())?;
+            results.extend(page.get("results").and_then(Value::as_array).cloned().unwrap_or_default());
+            next = page.get("next").and_then(Value::as_str).map(str::to_string);
+        }
+        Ok(results)
+    }
+
+    /// Devices and virtual machines that have a primary IP.
+    pub fn pull(&self) -> Result<Vec<NetboxObject>, String> {
+        let mut objects = Vec::new();
+        for kind in [NetboxKind::Device, NetboxKind::VirtualMachine] {
+            objects.extend(self.list(kind.endpoint())?.iter().filter_map(|object| parse_object(kind, object)));
+        }
+        Ok(objects)
+    }
+
+    /// Replaces the tags of each linked object, creating tags NetBox does
+    /// not know yet. Returns the number of objects updated.
+    pub fn push_tags(&self, updates: &[(NetboxLink, Vec<String>)]) -> Result<usize, String> {
+        let mut known: Vec<String> = self
+            .list("extras/tags")?
+            .iter()
+            .filter_map(|tag| tag.get("name").and_then(Value::as_str).map(str::to_string))
+            .collect();
+        for (link, tags) in updates {
+            for tag in tags {
+                if !known.iter().any(|name| name.eq_ignore_ascii_case(tag)) {
+                    self.request("POST", &format!("{}/extras/tags/", self.base_url))
+                        .send_json(json!({ "name": tag, "slug": slug(tag) }))
+                        .map_err(Self::error)?;
+                    known.push(tag.clone());
+                }
+            }
+            let tags: Vec<Value> = tags.iter().map(|tag| json!({ "name": tag })).collect();
+            self.request("PATCH", &format!("{}/{}/{}/", self.base_url, link.kind.endpoint(), link.id))
+                .send_json(json!({ "tags": tags }))
+                .map_err(Self::error)?;
+        }
+        Ok(updates.len())
+    }
+}
+
+/// NetBox slugs allow lowercase letters, digits, `-` and `_`.
+fn slug(tag: &str) -> String {
+    tag.to_lowercase().chars().map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '-' }).collect()
+}
@@ -0,0 +1,381 @@
+//! Pre-flight check of a stored account against an RDP server, so a
+//! rejected password is reported before mstsc opens. The security layer is
+//! negotiated as mstsc does; when the server picks Network Level
+//! Authentication, the account signs in over CredSSP with NTLM inside TLS
+//! and the check stops as soon as the server accepted or refused it. The
+//! password itself, which CredSSP hands over only after that, is never
+//! sent.
+//!
+//! The account is only sent to a server whose certificate is known. The
+//! first time the check meets a server it pins the certificate, as SSH
+//! does a host key, and signs in from the next connect on; a server whose
+//! certificate changed since is not signed in to at all, and mstsc is left
+//! to warn about it. On Windows the NTLM messages come from SSPI, elsewhere
+//! from `ntlm`.
+
+#[cfg(not(windows))]
+use crate::ntlm::Handshake;
+#[cfg(windows)]
+use crate::sspi::Handshake;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, ClientConnection, DigitallySignedStruct, SignatureScheme, StreamOwned};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+const PROTOCOL_SSL: u32 = 1;
+const PROTOCOL_HYBRID: u32 = 2;
+const PROTOCOL_HYBRID_EX: u32 = 8;
+/// Negotiation failure code of a server that only accepts NLA.
+const HYBRID_REQUIRED_BY_SERVER: u32 = 5;
+
+/// CredSSP version offered; from 5 on the public key is bound with a nonce.
+const CREDSSP_VERSION: u32 = 6;
+const CLIENT_BINDING: &[u8] = b"CredSSP Client-To-Server Binding Hash\0";
+
+/// What the server made of the account.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    /// The server does not use Network Level Authentication, so the
+    /// account is only checked on the remote sign-in screen.
+    NoNla,
+    /// The server was new: its certificate is pinned now, and the account
+    /// is first sent on the next check.
+    Pinned,
+    Accepted,
+    /// `required` when the server refuses connections without NLA.
+    Rejected { required: bool, reason: String },
+}
+
+/// Signs in to the RDP server at `host` with the account, blocking for a
+/// few seconds at most. `user` may also be written `DOMAIN\user` or
+/// `user@domain`. `pins` is the file of the certificates pinned so far. An
+/// error means the check could not tell, not that the account is wrong.
+pub fn check(host: &str, port: u16, user: &str, domain: &str, password: &str, pins: &Path) -> Result<Outcome, String> {
+    let (user, domain) = match user.split_once('\\') {
+        Some((domain, user)) if domain.is_empty() || domain == "." => (user, ""),
+        Some((domain, user)) => (user, domain),
+        None => (user, domain),
+    };
+    let mut stream = connect(host, port)?;
+    match negotiate(&mut stream, PROTOCOL_SSL | PROTOCOL_HYBRID | PROTOCOL_HYBRID_EX)? {
+        Negotiation::Selected(protocol) if protocol & (PROTOCOL_HYBRID | PROTOCOL_HYBRID_EX) != 0 => {}
+        _ => return Ok(Outcome::NoNla),
+    }
+    let server = format!("{}:{}", host.to_lowercase(), port);
+    let pinned = read_pins(pins).remove(&server);
+    match sign_in(stream, host, pinned, user, domain, password)? {
+        SignIn::Unpinned(fingerprint) => {
+            let mut known = read_pins(pins);
+            known.insert(server, fingerprint);
+            let data = serde_json::to_vec_pretty(&known).map_err(|e| e.to_string())?;
+            fs::write(pins, data).map_err(|e| format!("The server's certificate could not be pinned: {}", e))?;
+            Ok(Outcome::Pinned)
+        }
+        SignIn::Accepted => Ok(Outcome::Accepted),
+        SignIn::Rejected(reason) => {
+            let required = connect(host, port)
+                .and_then(|mut stream| negotiate(&mut stream, PROTOCOL_SSL))
+                .is_ok_and(|negotiation| negotiation == Negotiation::Refused(HYBRID_REQUIRED_BY_SERVER));
+            Ok(Outcome::Rejected { required, reason: reason.to_string() })
+        }
+    }
+}
+
+/// The pinned certificates by `host:port`, as hex SHA-256 digests.
+fn read_pins(pins: &Path) -> BTreeMap<String, String> {
+    fs::read(pins).ok().and_then(|data| serde_json::from_slice(&data).ok()).unwrap_or_default()
+}
+
+fn fingerprint(certificate: &[u8]) -> String {
+    Sha256::digest(certificate).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn connect(host: &str, port: u16) -> Result<TcpStream, String> {
+    let addresses = (host, port).to_socket_addrs().map_err(|e| format!("{} could not be resolved: {}", host, e))?;
+    let mut last_error = format!("{} could not be resolved", host);
+    for address in addresses.take(2) {
+        match TcpStream::connect_timeout(&address, TIMEOUT) {
+            Ok(stream) => {
+                stream.set_read_timeout(Some(TIMEOUT)).map_err(|e| e.to_string())?;
+                stream.set_write_timeout(Some(TIMEOUT)).map_err(|e| e.to_string())?;
+                return Ok(stream);
+            }
+            Err(e) => last_error = format!("Could not connect to {}: {}", address, e),
+        }
+    }
+    Err(last_error)
+}
+
+#[derive(Debug, PartialEq)]
+enum Negotiation {
+    /// The server chose one of the requested protocols.
+    Selected(u32),
+    /// The server refused them all, with this failure code.
+    Refused(u32),
+    /// The server predates negotiation and uses standard RDP security.
+    Legacy,
+}
+
+/// Sends an X.224 Connection Request asking for `protocols` and reads the
+/// server's choice from the Connection Confirm.
+fn negotiate(stream: &mut TcpStream, protocols: u32) -> Result<Negotiation, String> {
+    // TPKT header, X.224 Connection Request without a cookie, RDP
+    // Negotiation Request.
+    let mut request = vec![3, 0, 0, 19, 14, 0xE0, 0, 0, 0, 0, 0, 1, 0, 8, 0];
+    request.extend_from_slice(&protocols.to_le_bytes());
+    let not_rdp = || "The server does not speak RDP.".to_string();
+    stream.write_all(&request).map_err(|e| e.to_string())?;
+    let mut header = [0; 4];
+    stream.read_exact(&mut header).map_err(|e| e.to_string())?;
+    let length = u16::from_be_bytes([header[2], header[3]]) as usize;
+    if header[0] != 3 || length < 11 {
+        return Err(not_rdp());
+    }
+    let mut confirm = vec![0; length - 4];
+    stream.read_exact(&mut confirm).map_err(|e| e.to_string())?;
+    if confirm[1] & 0xF0 != 0xD0 {
+        return Err(not_rdp());
+    }
+    Ok(match confirm.get(7..15) {
+        Some(&[0x02, _, _, _, a, b, c, d]) => Negotiation::Selected(u32::from_le_bytes([a, b, c, d])),
+        Some(&[0x03, _, _, _, a, b, c, d]) => Negotiation::Refused(u32::from_le_bytes([a, b, c, d])),
+        _ => Negotiation::Legacy,
+    })
+}
+
+enum SignIn {
+    /// The server has no pinned certificate; this is the fingerprint of
+    /// the one it showed.
+    Unpinned(String),
+    Accepted,
+    Rejected(&'static str),
+}
+
+/// Runs CredSSP up to the server's verdict on the account, provided the
+/// server shows the `pinned` certificate. Without one nothing is sent.
+fn sign_in(stream: TcpStream, host: &str, pinned: Option<String>, user: &str, domain: &str, password: &str) -> Result<SignIn, String> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let config = ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(PinnedCertificate { provider, pinned: pinned.clone() }))
+        .with_no_client_auth();
+    let name = ServerName::try_from(host.to_string()).map_err(|e| e.to_string())?;
+    let connection = ClientConnection::new(Arc::new(config), name).map_err(|e| e.to_string())?;
+    let mut tls = StreamOwned::new(connection, stream);
+    while tls.conn.is_handshaking() {
+        tls.conn.complete_io(&mut tls.sock).map_err(|e| format!("TLS handshake failed: {}", e))?;
+    }
+    let certificate = tls.conn.peer_certificates().and_then(|certificates| certificates.first()).ok_or("The server showed no certificate.")?;
+    if pinned.is_none() {
+        let fingerprint = fingerprint(certificate);
+        tls.conn.send_close_notify();
+        let _ = tls.flush();
+        return Ok(SignIn::Unpinned(fingerprint));
+    }
+    let public_key = subject_public_key(certificate).ok_or("The server's certificate has no readable public key.")?.to_vec();
+
+    let (mut handshake, negotiate) = Handshake::start(host, user, domain, password)?;
+    tls.write_all(&ts_request(&negotiate, None, None)).map_err(|e| e.to_string())?;
+    let challenge = read_ts_request(&mut tls).map_err(|e| format!("No NTLM challenge from the server: {}", e))?;
+    if let Some(code) = challenge.error_code {
+        return status_text(code).map(SignIn::Rejected);
+    }
+    let token = challenge.token.ok_or("No NTLM challenge from the server.")?;
+    let authenticate = handshake.answer(&token)?;
+    let version = challenge.version.min(CREDSSP_VERSION);
+    let nonce: [u8; 32] = rand::random();
+    let bound = if version >= 5 { Sha256::new().chain_update(CLIENT_BINDING).chain_update(nonce).chain_update(&public_key).finalize().to_vec() } else { public_key };
+    let request = ts_request(&authenticate, Some(&handshake.seal(&bound)?), (version >= 5).then_some(&nonce[..]));
+    tls.write_all(&request).map_err(|e| e.to_string())?;
+    // Only an explicit error code counts as a refusal: older servers just
+    // close the connection, which a timeout or a dropped network cannot be
+    // told apart from.
+    let answer = read_ts_request(&mut tls).map_err(|e| format!("No answer to the sign-in: {}", e))?;
+    tls.conn.send_close_notify();
+    let _ = tls.flush();
+    match (answer.pub_key_auth, answer.error_code) {
+        (Some(_), _) => Ok(SignIn::Accepted),
+        (None, Some(code)) => status_text(code).map(SignIn::Rejected),
+        (None, None) => Err("The server's answer to the sign-in is empty.".to_string()),
+    }
+}
+
+/// Why the sign-in failed, for the status codes that mean the account
+/// itself is at fault. Others, such as NTLM being turned off on the
+/// server, leave the check undecided.
+fn status_text(code: u32) -> Result<&'static str, String> {
+    match code {
+        0xC000_006D | 0xC000_006A | 0xC000_0064 => Ok("the user name or password is incorrect"),
+        0xC000_0234 => Ok("the account is locked out"),
+        0xC000_0072 => Ok("the account is disabled"),
+        0xC000_0193 => Ok("the account has expired"),
+        0xC000_0071 => Ok("the password has expired"),
+        0xC000_0224 => Ok("the password must be changed before signing in"),
+        0xC000_006F => Ok("the account may not sign in at this time of day"),
+        0xC000_0070 => Ok("the account may not sign in from this computer"),
+        0xC000_006E => Ok("an account restriction prevents the sign-in"),
+        0xC000_015B => Ok("the account is not allowed to sign in remotely"),
+        code => Err(format!("The server ended the sign-in with status 0x{:08X}.", code)),
+    }
+}
+
+/// Accepts the `pinned` certificate only, or any while none is pinned, and
+/// checks that the server holds its key. RDP servers mostly sign their own
+/// certificates, so there is no chain to check them against.
+#[derive(Debug)]
+struct PinnedCertificate {
+    provider: Arc<CryptoProvider>,
+    pinned: Option<String>,
+}
+
+impl ServerCertVerifier for PinnedCertificate {
+    fn verify_server_cert(&self, certificate: &CertificateDer<'_>, _: &[CertificateDer<'_>], _: &ServerName<'_>, _: &[u8], _: UnixTime) -> Result<ServerCertVerified, rustls::Error> {
+        match &self.pinned {
+            Some(pinned) if *pinned != fingerprint(certificate) => Err(rustls::Error::General("the server's certificate changed since it was pinned".to_string())),
+            _ => Ok(ServerCertVerified::assertion()),
+        }
+    }
+
+    fn verify_tls12_signature(&self, message: &[u8], certificate: &CertificateDer<'_>, dss: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, certificate, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(&self, message: &[u8], certificate: &CertificateDer<'_>, dss: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, certificate, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// The fields of a CredSSP TSRequest the check reads.
+#[derive(Default)]
+struct TsRequest {
+    version: u32,
+    token: Option<Vec<u8>>,
+    pub_key_auth: Option<Vec<u8>>,
+    error_code: Option<u32>,
+}
+
+/// A TSRequest carrying the NTLM `token`, and with it the sealed public key
+/// and the nonce it was bound with.
+fn ts_request(token: &[u8], pub_key_auth: Option<&[u8]>, nonce: Option<&[u8]>) -> Vec<u8> {
+    let mut fields = der(0xA0, &der(0x02, &[CREDSSP_VERSION as u8]));
+    fields.extend(der(0xA1, &der(0x30, &der(0x30, &der(0xA0, &der(0x04, token))))));
+    if let Some(pub_key_auth) = pub_key_auth {
+        fields.extend(der(0xA3, &der(0x04, pub_key_auth)));
+    }
+    if let Some(nonce) = nonce {
+        fields.extend(der(0xA5, &der(0x04, nonce)));
+    }
+    der(0x30, &fields)
+}
+
+fn read_ts_request(stream: &mut impl Read) -> io::Result<TsRequest> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "not a CredSSP message");
+    let mut header = [0; 2];
+    stream.read_exact(&mut header)?;
+    if header[0] != 0x30 {
+        return Err(invalid());
+    }
+    let length = match header[1] {
+        length @ 0..=0x7F => length as usize,
+        0x81..=0x84 => {
+            let mut bytes = [0; 4];
+            let count = (header[1] & 0x7F) as usize;
+            stream.read_exact(&mut bytes[4 - count..])?;
+            u32::from_be_bytes(bytes) as usize
+        }
+        _ => return Err(invalid()),
+    };
+    if length > 0x10000 {
+        return Err(invalid());
+    }
+    let mut body = vec![0; length];
+    stream.read_exact(&mut body)?;
+    let mut request = TsRequest::default();
+    let mut fields = body.as_slice();
+    while let Some((tag, content, rest)) = tlv(fields) {
+        fields = rest;
+        let Some((_, value, _)) = tlv(content) else {
+            continue;
+        };
+        match tag {
+            0xA0 => request.version = integer(value),
+            // NegoData: a sequence of sequences holding one [0] OCTET STRING.
+            0xA1 => request.token = tlv(value).and_then(|(_, item, _)| tlv(item)).and_then(|(_, field, _)| tlv(field)).map(|(_, token, _)| token.to_vec()),
+            0xA3 => request.pub_key_auth = Some(value.to_vec()),
+            0xA4 => request.error_code = Some(integer(value)),
+            _ => {}
+        }
+    }
+    Ok(request)
+}
+
+fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut encoded = vec![tag];
+    if content.len() < 0x80 {
+        encoded.push(content.len() as u8);
+    } else {
+        let length = (content.len() as u32).to_be_bytes();
+        let skipped = length.iter().take_while(|byte| **byte == 0).count();
+        encoded.push(0x80 | (4 - skipped) as u8);
+        encoded.extend_from_slice(&length[skipped..]);
+    }
+    encoded.extend_from_slice(content);
+    encoded
+}
+
+/// Splits DER `data` into its first element's tag and content, and what
+/// follows.
+fn tlv(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = data.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+    let (length, rest) = match first {
+        0..=0x7F => (first as usize, rest),
+        0x81..=0x84 => {
+            let count = (first & 0x7F) as usize;
+            let bytes = rest.get(..count)?;
+            (bytes.iter().fold(0, |length, byte| length << 8 | *byte as usize), &rest[count..])
+        }
+        _ => return None,
+    };
+    Some((tag, rest.get(..length)?, rest.get(length..)?))
+}
+
+/// A DER INTEGER's low 32 bits, which keeps NTSTATUS codes encoded as
+/// negative numbers intact.
+fn integer(content: &[u8]) -> u32 {
+    content.iter().fold(0, |value, byte| value << 8 | *byte as u32)
+}
+
+/// The subjectPublicKey of an X.509 certificate, which CredSSP binds the
+/// sign-in to.
+fn subject_public_key(certificate: &[u8]) -> Option<&[u8]> {
+    let (_, certificate, _) = tlv(certificate)?;
+    let (_, mut fields, _) = tlv(certificate)?;
+    if tlv(fields)?.0 == 0xA0 {
+        fields = tlv(fields)?.2;
+    }
+    // Serial number, signature algorithm, issuer, validity and subject.
+    for _ in 0..5 {
+        fields = tlv(fields)?.2;
+    }
+    let (_, key_info, _) = tlv(fields)?;
+    let (_, bits, _) = tlv(tlv(key_info)?.2)?;
+    // The first byte counts the unused bits of the BIT STRING.
+    bits.get(1..)
+}
@@ -0,0 +1,356 @@
+//! Just enough NTLMv2 for the sign-in check in `nla`: the three messages
+//! of the handshake and the sealing of one message. MD4, MD5 and RC4 are
+//! what the protocol prescribes and serve nothing else, so they are kept
+//! here rather than pulled in as crates.
+//!
+//! On Windows the check signs in through SSPI instead, see `sspi`.
+
+use zeroize::Zeroizing;
+
+const SIGNATURE: &[u8; 8] = b"NTLMSSP\0";
+/// Unicode, request target, sign, seal, NTLM, always sign, extended session
+/// security, version, 128-bit, key exchange and 56-bit.
+const NEGOTIATE_FLAGS: u32 = 0xE208_8235;
+const KEY_EXCH: u32 = 0x4000_0000;
+/// Windows 10 build 19041, NTLM revision 15.
+const VERSION: [u8; 8] = [10, 0, 0x61, 0x4A, 0, 0, 0, 15];
+/// Size of the AUTHENTICATE message before its payload, MIC included.
+const AUTHENTICATE_HEADER: usize = 88;
+const MIC_OFFSET: usize = 72;
+
+const AV_EOL: u16 = 0;
+const AV_FLAGS: u16 = 6;
+const AV_TIMESTAMP: u16 = 7;
+/// MsvAvFlags value telling the server the message carries a MIC.
+const MIC_PRESENT: u32 = 2;
+
+const CLIENT_SIGNING: &[u8] = b"session key to client-to-server signing key magic constant\0";
+const CLIENT_SEALING: &[u8] = b"session key to client-to-server sealing key magic constant\0";
+
+/// The NEGOTIATE message.
+pub fn negotiate() -> Vec<u8> {
+    let mut message = SIGNATURE.to_vec();
+    message.extend_from_slice(&1u32.to_le_bytes());
+    message.extend_from_slice(&NEGOTIATE_FLAGS.to_le_bytes());
+    // Neither a domain nor a workstation name is supplied.
+    message.extend_from_slice(&[0; 16]);
+    message.extend_from_slice(&VERSION);
+    message
+}
+
+/// The AUTHENTICATE message answering the server's challenge, with the
+/// keys to seal the messages that follow it.
+pub struct Authentication {
+    pub message: Vec<u8>,
+    signing_key: Zeroizing<[u8; 16]>,
+    sealing_key: Zeroizing<[u8; 16]>,
+    key_exchange: bool,
+}
+
+impl Authentication {
+    /// `data` sealed as the first message to the server: the signature
+    /// followed by the encrypted data.
+    pub fn seal(&self, data: &[u8]) -> Vec<u8> {
+        let sequence = 0u32.to_le_bytes();
+        let mut rc4 = Rc4::new(&*self.sealing_key);
+        let sealed = rc4.apply(data);
+        let mut checksum = hmac_md5(&*self.signing_key, &[&sequence[..], data].concat())[..8].to_vec();
+        if self.key_exchange {
+            checksum = rc4.apply(&checksum);
+        }
+        [&1u32.to_le_bytes()[..], &checksum, &sequence, &sealed].concat()
+    }
+}
+
+/// Answers `challenge`, the server's reply to `negotiate`, for the account
+/// `user` in `domain`.
+pub fn authenticate(negotiate: &[u8], challenge: &[u8], user: &str, domain: &str, password: &str) -> Result<Authentication, String> {
+    let malformed = || "The server's NTLM challenge is malformed.".to_string();
+    if challenge.len() < 48 || &challenge[..8] != SIGNATURE || le32(challenge, 8) != 2 {
+        return Err(malformed());
+    }
+    let flags = le32(challenge, 20) & NEGOTIATE_FLAGS;
+    let server_challenge = &challenge[24..32];
+    let target_info = field(challenge, 40).ok_or_else(malformed)?;
+
+    // The server's attribute-value pairs are sent back, with the flag
+    // saying a MIC follows.
+    let mut pairs = Vec::new();
+    let mut timestamp: Option<[u8; 8]> = None;
+    let mut rest = target_info;
+    while rest.len() >= 4 {
+        let (id, length) = (le16(rest, 0), le16(rest, 2) as usize);
+        let value = rest.get(4..4 + length).ok_or_else(malformed)?;
+        rest = &rest[4 + length..];
+        if id == AV_EOL {
+            break;
+        }
+        if id == AV_TIMESTAMP {
+            timestamp = value.try_into().ok();
+        }
+        if id != AV_FLAGS {
+            av_pair(&mut pairs, id, value);
+        }
+    }
+    av_pair(&mut pairs, AV_FLAGS, &MIC_PRESENT.to_le_bytes());
+    av_pair(&mut pairs, AV_EOL, &[]);
+
+    let client_challenge: [u8; 8] = rand::random();
+    let key = Zeroizing::new(hmac_md5(&md4(&utf16(password)), &utf16(&(user.to_uppercase() + domain))));
+    let mut blob = vec![1, 1, 0, 0, 0, 0, 0, 0];
+    blob.extend_from_slice(&timestamp.unwrap_or_else(filetime_now));
+    blob.extend_from_slice(&client_challenge);
+    blob.extend_from_slice(&[0; 4]);
+    blob.extend_from_slice(&pairs);
+    blob.extend_from_slice(&[0; 4]);
+    let proof = hmac_md5(&*key, &[server_challenge, &blob].concat());
+    let nt_response = [&proof[..], &blob].concat();
+    // With a server timestamp the LM response must be left empty.
+    let lm_response = match timestamp {
+        Some(_) => vec![0; 24],
+        None => [&hmac_md5(&*key, &[server_challenge, &client_challenge].concat())[..], &client_challenge].concat(),
+    };
+    let session_base_key = Zeroizing::new(hmac_md5(&*key, &proof));
+    let key_exchange = flags & KEY_EXCH != 0;
+    let (session_key, encrypted_key) = if key_exchange {
+        let session_key = Zeroizing::new(rand::random::<[u8; 16]>());
+        let encrypted = Rc4::new(&*session_base_key).apply(&*session_key);
+        (session_key, encrypted)
+    } else {
+        (session_base_key, Vec::new())
+    };
+
+    let (domain, user) = (utf16(domain), utf16(user));
+    let payloads: [&[u8]; 6] = [&lm_response, &nt_response, &domain, &user, &[], &encrypted_key];
+    let mut message = SIGNATURE.to_vec();
+    message.extend_from_slice(&3u32.to_le_bytes());
+    let mut offset = AUTHENTICATE_HEADER;
+    for payload in payloads {
+        message.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+        message.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+        message.extend_from_slice(&(offset as u32).to_le_bytes());
+        offset += payload.len();
+    }
+    message.extend_from_slice(&flags.to_le_bytes());
+    message.extend_from_slice(&VERSION);
+    message.extend_from_slice(&[0; 16]);
+    for payload in payloads {
+        message.extend_from_slice(payload);
+    }
+    let mic = hmac_md5(&*session_key, &[negotiate, challenge, &message].concat());
+    message[MIC_OFFSET..AUTHENTICATE_HEADER].copy_from_slice(&mic);
+    Ok(Authentication {
+        message,
+        signing_key: Zeroizing::new(md5(&[&session_key[..], CLIENT_SIGNING].concat())),
+        sealing_key: Zeroizing::new(md5(&[&session_key[..], CLIENT_SEALING].concat())),
+        key_exchange,
+    })
+}
+
+/// One sign-in as `nla` drives it: the NEGOTIATE message, the answer to
+/// the challenge, then the sealing. `sspi` offers the same on Windows.
+pub struct Handshake {
+    negotiate: Vec<u8>,
+    user: String,
+    domain: String,
+    password: Zeroizing<String>,
+    authentication: Option<Authentication>,
+}
+
+impl Handshake {
+    /// Starts a sign-in as `user` in `domain`, returning the NEGOTIATE
+    /// message to send. The host only matters to SSPI.
+    pub fn start(_host: &str, user: &str, domain: &str, password: &str) -> Result<(Self, Vec<u8>), String> {
+        let negotiate = negotiate();
+        let handshake = Self {
+            negotiate: negotiate.clone(),
+            user: user.to_string(),
+            domain: domain.to_string(),
+            password: Zeroizing::new(password.to_string()),
+            authentication: None,
+        };
+        Ok((handshake, negotiate))
+    }
+
+    /// The AUTHENTICATE message answering the server's `challenge`.
+    pub fn answer(&mut self, challenge: &[u8]) -> Result<Vec<u8>, String> {
+        let authentication = authenticate(&self.negotiate, challenge, &self.user, &self.domain, &self.password)?;
+        let message = authentication.message.clone();
+        self.authentication = Some(authentication);
+        Ok(message)
+    }
+
+    pub fn seal(&mut self, data: &[u8]) -> Result<Vec<u8>, String> {
+        let authentication = self.authentication.as_ref().ok_or("The NTLM challenge has not been answered yet.")?;
+        Ok(authentication.seal(data))
+    }
+}
+
+fn av_pair(pairs: &mut Vec<u8>, id: u16, value: &[u8]) {
+    pairs.extend_from_slice(&id.to_le_bytes());
+    pairs.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    pairs.extend_from_slice(value);
+}
+
+/// The payload a length, length and offset field at `at` points to.
+fn field(message: &[u8], at: usize) -> Option<&[u8]> {
+    let length = le16(message, at) as usize;
+    let offset = le32(message, at + 4) as usize;
+    message.get(offset..offset.checked_add(length)?)
+}
+
+fn le16(data: &[u8], at: usize) -> u16 {
+    data.get(at..at + 2).map_or(0, |bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn le32(data: &[u8], at: usize) -> u32 {
+    data.get(at..at + 4).map_or(0, |bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn utf16(text: &str) -> Vec<u8> {
+    text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+}
+
+/// Now as a Windows FILETIME: 100 ns steps since 1601.
+fn filetime_now() -> [u8; 8] {
+    let since_1601 = chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default() / 100 + 116_444_736_000_000_000;
+    since_1601.to_le_bytes()
+}
+
+/// `data` padded to whole 64-byte blocks with its bit length, as MD4 and
+/// MD5 both do.
+fn padded(data: &[u8]) -> Vec<u8> {
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_le_bytes());
+    message
+}
+
+fn words(block: &[u8]) -> [u32; 16] {
+    let mut words = [0; 16];
+    for (word, bytes) in words.iter_mut().zip(block.chunks_exact(4)) {
+        *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+    words
+}
+
+fn digest(state: [u32; 4]) -> [u8; 16] {
+    let mut digest = [0; 16];
+    for (bytes, word) in digest.chunks_exact_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+    digest
+}
+
+const INITIAL_STATE: [u32; 4] = [0x6745_2301, 0xEFCD_AB89, 0x98BA_DCFE, 0x1032_5476];
+
+fn md4(data: &[u8]) -> [u8; 16] {
+    let mut state = INITIAL_STATE;
+    for block in padded(data).chunks_exact(64) {
+        let x = words(block);
+        let [mut a, mut b, mut c, mut d] = state;
+        let step = |a: u32, f: u32, x: u32, shift: u32| a.wrapping_add(f).wrapping_add(x).rotate_left(shift);
+        for i in [0, 4, 8, 12] {
+            let f = |x: u32, y: u32, z: u32| (x & y) | (!x & z);
+            a = step(a, f(b, c, d), x[i], 3);
+            d = step(d, f(a, b, c), x[i + 1], 7);
+            c = step(c, f(d, a, b), x[i + 2], 11);
+            b = step(b, f(c, d, a), x[i + 3], 19);
+        }
+        for i in 0..4 {
+            let g = |x: u32, y: u32, z: u32| ((x & y) | (x & z) | (y & z)).wrapping_add(0x5A82_7999);
+            a = step(a, g(b, c, d), x[i], 3);
+            d = step(d, g(a, b, c), x[i + 4], 5);
+            c = step(c, g(d, a, b), x[i + 8], 9);
+            b = step(b, g(c, d, a), x[i + 12], 13);
+        }
+        for i in [0, 2, 1, 3] {
+            let h = |x: u32, y: u32, z: u32| (x ^ y ^ z).wrapping_add(0x6ED9_EBA1);
+            a = step(a, h(b, c, d), x[i], 3);
+            d = step(d, h(a, b, c), x[i + 8], 9);
+            c = step(c, h(d, a, b), x[i + 4], 11);
+            b = step(b, h(c, d, a), x[i + 12], 15);
+        }
+        for (word, added) in state.iter_mut().zip([a, b, c, d]) {
+            *word = word.wrapping_add(added);
+        }
+    }
+    digest(state)
+}
+
+fn md5(data: &[u8]) -> [u8; 16] {
+    const SHIFTS: [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+    let constants: Vec<u32> = (1..=64).map(|i| ((i as f64).sin().abs() * 4_294_967_296.0) as u32).collect();
+    let mut state = INITIAL_STATE;
+    for block in padded(data).chunks_exact(64) {
+        let m = words(block);
+        let [mut a, mut b, mut c, mut d] = state;
+        for (i, constant) in constants.iter().enumerate() {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let rotated = a.wrapping_add(f).wrapping_add(*constant).wrapping_add(m[g]).rotate_left(SHIFTS[i / 16 * 4 + i % 4]);
+            (a, b, c, d) = (d, b.wrapping_add(rotated), b, c);
+        }
+        for (word, added) in state.iter_mut().zip([a, b, c, d]) {
+            *word = word.wrapping_add(added);
+        }
+    }
+    digest(state)
+}
+
+fn hmac_md5(key: &[u8], data: &[u8]) -> [u8; 16] {
+    let mut block = Zeroizing::new([0u8; 64]);
+    if key.len() > 64 {
+        block[..16].copy_from_slice(&md5(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let inner: Vec<u8> = block.iter().map(|byte| byte ^ 0x36).chain(data.iter().copied()).collect();
+    let outer: Vec<u8> = block.iter().map(|byte| byte ^ 0x5C).chain(md5(&inner)).collect();
+    md5(&outer)
+}
+
+struct Rc4 {
+    state: [u8; 256],
+    i: u8,
+    j: u8,
+}
+
+impl Rc4 {
+    fn new(key: &[u8]) -> Self {
+        let mut state = [0u8; 256];
+        for (value, byte) in state.iter_mut().enumerate() {
+            *byte = value as u8;
+        }
+        let mut j = 0u8;
+        for (i, key_byte) in (0..256).zip(key.iter().cycle()) {
+            j = j.wrapping_add(state[i]).wrapping_add(*key_byte);
+            state.swap(i, j as usize);
+        }
+        Self { state, i: 0, j: 0 }
+    }
+
+    fn apply(&mut self, data: &[u8]) -> Vec<u8> {
+        data.iter()
+            .map(|byte| {
+                self.i = self.i.wrapping_add(1);
+                self.j = self.j.wrapping_add(self.state[self.i as usize]);
+                self.state.swap(self.i as usize, self.j as usize);
+                byte ^ self.state[self.state[self.i as usize].wrapping_add(self.state[self.j as usize]) as usize]
+            })
+            .collect()
+    }
+}
+
+impl Drop for Rc4 {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.state);
+    }
+}
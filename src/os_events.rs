@@ -0,0 +1,151 @@
+use eframe::egui;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// A poll that arrives this much later than scheduled means the machine slept.
+const SUSPEND_GAP: Duration = Duration::from_secs(15);
+
+pub enum OsEvent {
+    SessionLocked,
+    /// The machine is about to sleep or hibernate.
+    Suspending,
+    /// The machine woke up, noticed only after the fact where suspending
+    /// is not announced.
+    Resumed,
+}
+
+/// Watches for the workstation being locked and for sleep/hibernate, waking
+/// the UI whenever either happens. Windows announces both; elsewhere the
+/// lock state is polled and resuming is told from gaps in the clock.
+pub fn spawn_watcher(ctx: egui::Context) -> Receiver<OsEvent> {
+    let (sender, receiver) = channel();
+    if !listen_for_os_events(sender.clone(), ctx.clone()) {
+        thread::spawn(move || poll_for_os_events(sender, ctx));
+    }
+    receiver
+}
+
+fn poll_for_os_events(sender: Sender<OsEvent>, ctx: egui::Context) {
+    let mut was_locked = false;
+    let mut last_poll = SystemTime::now();
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let now = SystemTime::now();
+        let gap = now.duration_since(last_poll).unwrap_or_default();
+        last_poll = now;
+
+        let mut event = None;
+        if gap > POLL_INTERVAL + SUSPEND_GAP {
+            event = Some(OsEvent::Resumed);
+        }
+        let locked = session_locked();
+        if locked && !was_locked {
+            event = Some(OsEvent::SessionLocked);
+        }
+        was_locked = locked;
+
+        if let Some(event) = event {
+            if sender.send(event).is_err() {
+                return;
+            }
+            ctx.request_repaint();
+        }
+    }
+}
+
+/// Registers a hidden window for session change notifications and power
+/// broadcasts, and sends `SessionLocked` and `Suspending` as Windows reports
+/// them. The window is a hidden top-level one, as message-only windows get
+/// no broadcasts. Returns whether the notifications could be registered for.
+#[cfg(windows)]
+fn listen_for_os_events(sender: Sender<OsEvent>, ctx: egui::Context) -> bool {
+    use std::cell::RefCell;
+    use std::ptr::{null, null_mut};
+    use winapi::shared::minwindef::{LPARAM, LRESULT, UINT, WPARAM};
+    use winapi::shared::windef::HWND;
+    use winapi::um::libloaderapi::GetModuleHandleW;
+    use winapi::um::winuser::{
+        CreateWindowExW, DefWindowProcW, DispatchMessageW, GetMessageW, RegisterClassW, MSG, PBT_APMSUSPEND, WM_POWERBROADCAST, WM_WTSSESSION_CHANGE, WNDCLASSW,
+        WTS_SESSION_LOCK,
+    };
+    use windows::Win32::System::RemoteDesktop::{WTSRegisterSessionNotification, NOTIFY_FOR_THIS_SESSION};
+
+    thread_local! {
+        // Where the window procedure, running on the listening thread, reports to.
+        static LISTENER: RefCell<Option<(Sender<OsEvent>, egui::Context)>> = const { RefCell::new(None) };
+    }
+
+    unsafe extern "system" fn window_proc(window: HWND, message: UINT, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+        let event = match message {
+            WM_WTSSESSION_CHANGE if wparam == WTS_SESSION_LOCK => Some(OsEvent::SessionLocked),
+            WM_POWERBROADCAST if wparam == PBT_APMSUSPEND => Some(OsEvent::Suspending),
+            _ => None,
+        };
+        if let Some(event) = event {
+            LISTENER.with(|listener| {
+                if let Some((sender, ctx)) = &*listener.borrow() {
+                    if sender.send(event).is_ok() {
+                        ctx.request_repaint();
+                    }
+                }
+            });
+        }
+        DefWindowProcW(window, message, wparam, lparam)
+    }
+
+    let (registered, result) = channel();
+    thread::spawn(move || unsafe {
+        let class_name: Vec<u16> = "RemoteDesktopManagerSessionEvents\0".encode_utf16().collect();
+        let instance = GetModuleHandleW(null());
+        let class = WNDCLASSW { lpfnWndProc: Some(window_proc), hInstance: instance, lpszClassName: class_name.as_ptr(), ..std::mem::zeroed() };
+        RegisterClassW(&class);
+        let window = CreateWindowExW(0, class_name.as_ptr(), null(), 0, 0, 0, 0, 0, null_mut(), null_mut(), instance, null_mut());
+        if window.is_null() || !WTSRegisterSessionNotification(windows::Win32::Foundation::HWND(window as isize), NOTIFY_FOR_THIS_SESSION).as_bool() {
+            let _ = registered.send(false);
+            return;
+        }
+        LISTENER.with(|listener| *listener.borrow_mut() = Some((sender, ctx)));
+        let _ = registered.send(true);
+        let mut message: MSG = std::mem::zeroed();
+        while GetMessageW(&mut message, null_mut(), 0, 0) > 0 {
+            DispatchMessageW(&message);
+        }
+    });
+    result.recv().unwrap_or(false)
+}
+
+#[cfg(not(windows))]
+fn listen_for_os_events(_sender: Sender<OsEvent>, _ctx: egui::Context) -> bool {
+    false
+}
+
+/// While the workstation is locked the input desktop belongs to Winlogon and
+/// cannot be opened by the user session. So does the secure desktop of UAC
+/// and credential prompts, which is why this is only a fallback for when
+/// the notifications could not be registered for.
+#[cfg(windows)]
+fn session_locked() -> bool {
+    use winapi::um::winuser::{CloseDesktop, OpenInputDesktop, DESKTOP_SWITCHDESKTOP};
+    unsafe {
+        let desktop = OpenInputDesktop(0, 0, DESKTOP_SWITCHDESKTOP);
+        if desktop.is_null() {
+            return true;
+        }
+        CloseDesktop(desktop);
+        false
+    }
+}
+
+#[cfg(not(windows))]
+fn session_locked() -> bool {
+    let Ok(session) = std::env::var("XDG_SESSION_ID") else {
+        return false;
+    };
+    std::process::Command::new("loginctl")
+        .args(["show-session", &session, "-p", "LockedHint"])
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim() == "LockedHint=yes")
+        .unwrap_or(false)
+}
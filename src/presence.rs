@@ -0,0 +1,174 @@
+//! Optional presence on the local network. Each instance that opts in
+//! announces the sessions it has open over mDNS and lists the ones other
+//! instances announce, so teammates see who is already connected to a host
+//! before opening a second admin session. No server is involved; only
+//! instances on the same subnet see each other.
+
+use crate::collation;
+use crate::events::{AppEvent, EventBus};
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+pub const SERVICE_TYPE: &str = "_rdm-presence._udp.local.";
+/// DNS labels, and so instance names, are limited to 63 bytes.
+const MAX_INSTANCE_NAME: usize = 63;
+/// Each TXT entry is limited to 255 bytes including its key.
+const MAX_VALUE: usize = 240;
+
+/// A session as announced: the client's name in the announcer's vault and
+/// the host it connects to, which is what other vaults are matched on.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PeerSession {
+    pub client_name: String,
+    pub address: String,
+}
+
+/// Another instance on the network and the sessions it has open.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Peer {
+    pub user: String,
+    pub machine: String,
+    pub sessions: Vec<PeerSession>,
+}
+
+impl Peer {
+    /// Whether the peer has a session to `address`, compared without case.
+    pub fn connected_to(&self, address: &str) -> bool {
+        self.sessions.iter().any(|session| session.address.eq_ignore_ascii_case(address))
+    }
+}
+
+/// The OS user name, the default name announced to teammates.
+pub fn os_user() -> String {
+    std::env::var("USERNAME").or_else(|_| std::env::var("USER")).unwrap_or_default()
+}
+
+fn machine_name() -> String {
+    std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+fn truncate(text: &str, max: usize) -> &str {
+    let mut end = text.len().min(max);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// TXT properties for an announcement: `user`, `machine` and one
+/// `s<n>` entry per session holding the address and the client's name.
+pub fn encode(user: &str, machine: &str, sessions: &[PeerSession]) -> Vec<(String, String)> {
+    let mut properties = vec![
+        ("user".to_string(), truncate(user, MAX_VALUE).to_string()),
+        ("machine".to_string(), truncate(machine, MAX_VALUE).to_string()),
+    ];
+    for (i, session) in sessions.iter().enumerate() {
+        let value = format!("{} {}", session.address, session.client_name);
+        properties.push((format!("s{}", i), truncate(&value, MAX_VALUE).to_string()));
+    }
+    properties
+}
+
+/// Reads an announcement through `property`, which looks up a TXT entry.
+pub fn decode<'a>(property: impl Fn(&str) -> Option<&'a str>) -> Peer {
+    let sessions = (0..)
+        .map_while(|i| property(&format!("s{}", i)))
+        .filter_map(|value| {
+            let (address, client_name) = value.split_once(' ').unwrap_or((value, value));
+            (!address.is_empty()).then(|| PeerSession { client_name: client_name.to_string(), address: address.to_string() })
+        })
+        .collect();
+    Peer {
+        user: property("user").unwrap_or_default().to_string(),
+        machine: property("machine").unwrap_or_default().to_string(),
+        sessions,
+    }
+}
+
+/// The running announcement and the peers heard from. Dropping it sends a
+/// goodbye so teammates stop showing this instance right away.
+pub struct LanPresence {
+    daemon: ServiceDaemon,
+    instance: String,
+    host_name: String,
+    user: String,
+    machine: String,
+    peers: Arc<Mutex<HashMap<String, Peer>>>,
+    /// What was announced last; `None` before the first announcement.
+    announced: Option<Vec<PeerSession>>,
+}
+
+impl LanPresence {
+    /// Starts listening for peers under `user`; every change publishes
+    /// `AppEvent::PeersChanged`. Nothing is announced until `announce`.
+    pub fn start(user: &str, events: EventBus) -> Result<Self, String> {
+        let daemon = ServiceDaemon::new().map_err(|e| format!("Could not start mDNS: {}", e))?;
+        let machine = machine_name();
+        let instance = format!("{} on {} {}", user, machine, std::process::id());
+        let instance = truncate(&instance, MAX_INSTANCE_NAME).to_string();
+        let own_fullname = format!("{}.{}", instance, SERVICE_TYPE);
+        let label: String = machine.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '-' }).collect();
+        let receiver = daemon.browse(SERVICE_TYPE).map_err(|e| format!("Could not browse for teammates: {}", e))?;
+        let peers: Arc<Mutex<HashMap<String, Peer>>> = Arc::default();
+        let heard = Arc::clone(&peers);
+        thread::spawn(move || {
+            // Ends when the daemon shuts down and closes the channel.
+            while let Ok(event) = receiver.recv() {
+                match event {
+                    ServiceEvent::ServiceResolved(info) if info.get_fullname() != own_fullname => {
+                        let peer = decode(|key| info.get_property_val_str(key));
+                        heard.lock().unwrap().insert(info.get_fullname().to_string(), peer);
+                    }
+                    ServiceEvent::ServiceRemoved(_, fullname) => {
+                        heard.lock().unwrap().remove(&fullname);
+                    }
+                    _ => continue,
+                }
+                events.publish(AppEvent::PeersChanged);
+            }
+        });
+        Ok(Self {
+            daemon,
+            instance,
+            host_name: format!("{}-{}.local.", truncate(&label, 40), std::process::id()),
+            user: user.to_string(),
+            machine,
+            peers,
+            announced: None,
+        })
+    }
+
+    /// Announces the open sessions, unless they are what was announced last.
+    pub fn announce(&mut self, sessions: Vec<PeerSession>) -> Result<(), String> {
+        if self.announced.as_ref() == Some(&sessions) {
+            return Ok(());
+        }
+        let properties = encode(&self.user, &self.machine, &sessions);
+        let info = ServiceInfo::new(SERVICE_TYPE, &self.instance, &self.host_name, "", 0, properties.as_slice())
+            .map_err(|e| e.to_string())?
+            .enable_addr_auto();
+        self.daemon.register(info).map_err(|e| format!("Could not announce sessions: {}", e))?;
+        self.announced = Some(sessions);
+        Ok(())
+    }
+
+    /// Peers heard from, sorted by user.
+    pub fn peers(&self) -> Vec<Peer> {
+        let mut peers: Vec<Peer> = self.peers.lock().unwrap().values().cloned().collect();
+        peers.sort_by(|a, b| collation::compare(&a.user, &b.user).then_with(|| a.machine.cmp(&b.machine)));
+        peers
+    }
+}
+
+impl Drop for LanPresence {
+    fn drop(&mut self) {
+        if self.announced.is_some() {
+            let _ = self.daemon.unregister(&format!("{}.{}", self.instance, SERVICE_TYPE));
+        }
+        let _ = self.daemon.shutdown();
+    }
+}
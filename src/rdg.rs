@@ -0,0 +1,202 @@
+//! Import from Remote Desktop Connection Manager (.rdg) files. Groups become
+//! client groups, servers keep their display names, and logon credentials
+//! are resolved the way RDCMan does: set on the server, inherited from the
+//! enclosing groups, or taken from a named credentials profile.
+//!
+//! RDCMan stores passwords DPAPI-encrypted for the Windows user who saved
+//! the file, so they can only be recovered on Windows by that same user.
+
+use crate::client::Client;
+use crate::dpapi;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+use std::collections::HashMap;
+
+/// An XML element with its text; enough of a DOM to walk an .rdg file.
+#[derive(Default)]
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    text: String,
+    children: Vec<Element>,
+}
+
+impl Element {
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    fn child_text(&self, name: &str) -> &str {
+        self.child(name).map(|child| child.text.trim()).unwrap_or_default()
+    }
+
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    /// Where group and server settings live: in `<properties>` since
+    /// RDCMan 2.7, directly in the element before that.
+    fn properties(&self) -> &Element {
+        self.child("properties").unwrap_or(self)
+    }
+}
+
+fn element(start: &BytesStart) -> Result<Element, String> {
+    let mut attributes = Vec::new();
+    for attribute in start.attributes() {
+        let attribute = attribute.map_err(|e| e.to_string())?;
+        let value = attribute.unescape_value().map_err(|e| e.to_string())?.into_owned();
+        attributes.push((String::from_utf8_lossy(attribute.key.as_ref()).into_owned(), value));
+    }
+    Ok(Element {
+        name: String::from_utf8_lossy(start.name().as_ref()).into_owned(),
+        attributes,
+        ..Default::default()
+    })
+}
+
+fn parse_tree(data: &str) -> Result<Element, String> {
+    let mut reader = Reader::from_str(data);
+    reader.config_mut().trim_text(true);
+    let mut stack = vec![Element::default()];
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(start)) => stack.push(element(&start)?),
+            Ok(Event::Empty(start)) => {
+                let empty = element(&start)?;
+                stack.last_mut().expect("the root is never popped").children.push(empty);
+            }
+            Ok(Event::End(_)) => {
+                let closed = stack.pop().expect("the root is never popped");
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(closed),
+                    None => return Err("Unbalanced XML".to_string()),
+                }
+            }
+            Ok(Event::Text(text)) => {
+                let text = text.unescape().map_err(|e| e.to_string())?;
+                stack.last_mut().expect("the root is never popped").text.push_str(&text);
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(format!("Invalid XML at position {}: {}", reader.error_position(), e)),
+            _ => {}
+        }
+    }
+    match (stack.pop(), stack.is_empty()) {
+        (Some(root), true) => Ok(root),
+        _ => Err("Unexpected end of the file".to_string()),
+    }
+}
+
+#[derive(Clone, Default)]
+struct Credentials {
+    username: String,
+    domain: String,
+    /// `None` when the file has a password that could not be decrypted.
+    password: Option<String>,
+}
+
+/// Decrypts an RDCMan password: base64 of a DPAPI blob holding UTF-16LE
+/// text, or plain text when the file says so.
+fn decrypt_password(password: &Element) -> Option<String> {
+    let text = password.text.trim();
+    if text.is_empty() || password.attribute("storeAsClearText").is_some_and(|clear| clear.eq_ignore_ascii_case("true")) {
+        return Some(text.to_string());
+    }
+    let data = dpapi::unprotect(&STANDARD.decode(text).ok()?).ok()?;
+    let units: Vec<u16> = data.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect();
+    Some(String::from_utf16_lossy(&units).trim_end_matches('\0').to_string())
+}
+
+fn read_credentials(element: &Element) -> Credentials {
+    Credentials {
+        username: element.child_text("userName").to_string(),
+        domain: element.child_text("domain").to_string(),
+        password: element.child("password").map(decrypt_password).unwrap_or_else(|| Some(String::new())),
+    }
+}
+
+/// The credentials that apply to a file, group or server, given the ones
+/// of its parent.
+fn resolve_credentials(node: &Element, inherited: &Credentials, profiles: &HashMap<String, Credentials>) -> Credentials {
+    let Some(logon) = node.child("logonCredentials").or_else(|| node.properties().child("logonCredentials")) else {
+        return inherited.clone();
+    };
+    if logon.attribute("inherit") == Some("FromParent") {
+        return inherited.clone();
+    }
+    match profiles.get(logon.child_text("profileName")) {
+        Some(profile) if logon.child("userName").is_none() => profile.clone(),
+        _ => read_credentials(logon),
+    }
+}
+
+/// What was read from an .rdg file.
+pub struct RdgImport {
+    pub clients: Vec<Client>,
+    pub groups: usize,
+    /// Servers whose password is encrypted for another user or machine.
+    pub undecrypted_passwords: usize,
+}
+
+fn walk(node: &Element, group: &[String], inherited: &Credentials, profiles: &HashMap<String, Credentials>, import: &mut RdgImport) {
+    let credentials = resolve_credentials(node, inherited, profiles);
+    for child in &node.children {
+        match child.name.as_str() {
+            "group" => {
+                let name = child.properties().child_text("name");
+                let mut path = group.to_vec();
+                path.push(name.replace('/', "-"));
+                import.groups += 1;
+                walk(child, &path, &credentials, profiles, import);
+            }
+            "server" => {
+                let properties = child.properties();
+                let host = properties.child_text("name");
+                if host.is_empty() {
+                    continue;
+                }
+                let display_name = properties.child_text("displayName");
+                let server_credentials = resolve_credentials(child, &credentials, profiles);
+                if server_credentials.password.is_none() {
+                    import.undecrypted_passwords += 1;
+                }
+                import.clients.push(Client {
+                    name: if display_name.is_empty() { host } else { display_name }.to_string(),
+                    ip: host.to_string(),
+                    group: group.join("/"),
+                    username: server_credentials.username,
+                    domain: server_credentials.domain,
+                    password: server_credentials.password.unwrap_or_default(),
+                    ..Default::default()
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Reads the servers out of an .rdg file with their groups and credentials.
+/// Smart groups are skipped; they only list servers found elsewhere.
+pub fn parse_rdg(data: &str) -> Result<RdgImport, String> {
+    let root = parse_tree(data)?;
+    let file = root
+        .child("RDCMan")
+        .and_then(|rdcman| rdcman.child("file"))
+        .ok_or("Not a Remote Desktop Connection Manager file")?;
+    let profiles = file
+        .child("credentialsProfiles")
+        .map(|profiles| {
+            profiles
+                .children
+                .iter()
+                .map(|profile| (profile.child_text("profileName").to_string(), read_credentials(profile)))
+                .collect()
+        })
+        .unwrap_or_default();
+    let mut import = RdgImport { clients: Vec::new(), groups: 0, undecrypted_passwords: 0 };
+    walk(file, &[], &Credentials::default(), &profiles, &mut import);
+    Ok(import)
+}
@@ -0,0 +1,140 @@
+//! Connection files handed to mstsc. Each connect writes its own file to the
+//! temp directory, which is deleted again when the session ends. Whole
+//! folders can also be exported as files to hand to someone without the app.
+
+use crate::client::Client;
+use crate::dpapi;
+use crate::settings::RdpOptions;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// A setting value. Each setting is one `name:type:value` line, so a line
+/// break in a value would inject further settings into the file.
+fn escape(value: &str) -> Result<&str, String> {
+    if value.chars().any(char::is_control) {
+        return Err(format!("{:?} contains control characters and cannot be written to an .rdp file.", value));
+    }
+    Ok(value)
+}
+
+/// The .rdp settings for `client`. Without stored credentials mstsc prompts,
+/// with the user name already filled in when there is one. The client's
+/// own display settings win over those in `options`.
+pub fn render(client: &Client, prompt_for_credentials: bool, options: &RdpOptions) -> Result<String, String> {
+    let (full_screen, all_monitors) = match client.display {
+        Some(display) => (display.full_screen, display.all_monitors),
+        None => (options.full_screen, options.all_monitors),
+    };
+    let mut lines = vec![
+        format!("use multimon:i:{}", u8::from(all_monitors)),
+        format!("dynamic resolution:i:{}", u8::from(options.dynamic_resolution)),
+        format!("smart sizing:i:{}", u8::from(options.smart_sizing)),
+    ];
+    // A window that is placed after launch cannot be full screen.
+    let full_screen = full_screen && client.placement.is_none();
+    lines.push(format!("screen mode id:i:{}", if full_screen { 2 } else { 1 }));
+    if let Some(display) = client.display {
+        if !full_screen {
+            lines.push(format!("desktopwidth:i:{}", display.width));
+            lines.push(format!("desktopheight:i:{}", display.height));
+        }
+        lines.push(format!("desktopscalefactor:i:{}", display.scale));
+    }
+    // A farm member is reached through the broker, which needs the farm's
+    // name to pick a session host.
+    lines.push(format!("full address:s:{}", escape(&client.connection_target().address())?));
+    if let Some(farm) = client.farm.as_ref().filter(|farm| !farm.broker.trim().is_empty()) {
+        lines.push(format!("loadbalanceinfo:s:tsv://MS Terminal Services Plugin.1.{}", escape(farm.name.trim())?));
+    }
+    if let Some(gateway) = client.gateway.as_ref().filter(|gateway| !gateway.host.trim().is_empty()) {
+        lines.push(format!("gatewayhostname:s:{}", escape(gateway.host.trim())?));
+        // 1 always goes through the gateway, 2 skips it for local addresses.
+        lines.push(format!("gatewayusagemethod:i:{}", if gateway.bypass_local { 2 } else { 1 }));
+        lines.push("gatewayprofileusagemethod:i:1".to_string());
+        lines.push("gatewaycredentialssource:i:0".to_string());
+        lines.push(format!("promptcredentialonce:i:{}", u8::from(gateway.credential.is_none())));
+    }
+    let redirection = client.redirection.unwrap_or(options.redirection);
+    lines.push(format!("redirectdrives:i:{}", u8::from(redirection.drives)));
+    lines.push(format!("drivestoredirect:s:{}", if redirection.drives { "*" } else { "" }));
+    lines.push(format!("redirectclipboard:i:{}", u8::from(redirection.clipboard)));
+    lines.push(format!("redirectprinters:i:{}", u8::from(redirection.printers)));
+    lines.push(format!("redirectsmartcards:i:{}", u8::from(redirection.smart_cards)));
+    // 0 plays the sound here, 2 not at all.
+    lines.push(format!("audiomode:i:{}", if redirection.audio { 0 } else { 2 }));
+    if let Some(account) = client.account() {
+        lines.push(format!("username:s:{}", escape(&account)?));
+    }
+    lines.push(format!("prompt for credentials:i:{}", u8::from(prompt_for_credentials)));
+    Ok(lines.join("\r\n") + "\r\n")
+}
+
+/// The file as UTF-16 with a byte order mark, the encoding mstsc itself
+/// saves .rdp files in.
+fn encode(text: &str) -> Vec<u8> {
+    let mut data = vec![0xFF, 0xFE];
+    data.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+    data
+}
+
+/// Writes the connection file for one connect to the temp directory.
+pub fn write_temp(client: &Client, prompt_for_credentials: bool, options: &RdpOptions) -> Result<PathBuf, String> {
+    let text = render(client, prompt_for_credentials, options)?;
+    let path = std::env::temp_dir().join(format!("rdm-{}.rdp", Uuid::new_v4().simple()));
+    fs::write(&path, encode(&text)).map_err(|e| format!("Could not write the connection file: {}", e))?;
+    Ok(path)
+}
+
+/// The `password 51` setting mstsc reads a saved password from: the UTF-16
+/// password encrypted with DPAPI, in hex. Only the Windows user who wrote
+/// the file can decrypt it, on the machine it was written on.
+fn protected_password(password: &str) -> Result<String, String> {
+    let plain: Vec<u8> = password.encode_utf16().flat_map(u16::to_le_bytes).collect();
+    let blob = dpapi::protect(&plain).map_err(|e| format!("Could not protect the password: {}", e))?;
+    Ok(format!("password 51:b:{}", blob.iter().map(|byte| format!("{:02X}", byte)).collect::<String>()))
+}
+
+/// `name` with the characters Windows does not allow in file names replaced.
+fn sanitize(name: &str) -> String {
+    let cleaned: String = name.chars().map(|c| if c.is_control() || r#"<>:"/\|?*"#.contains(c) { '_' } else { c }).collect();
+    let cleaned = cleaned.trim().trim_end_matches('.');
+    if cleaned.is_empty() { "client".to_string() } else { cleaned.to_string() }
+}
+
+/// `name` as an .rdp file name, numbered when `taken` already holds it
+/// (compared without case, as the file system does).
+fn file_name(name: &str, taken: &mut HashSet<String>) -> String {
+    let stem = sanitize(name);
+    let mut candidate = format!("{}.rdp", stem);
+    let mut number = 1;
+    while !taken.insert(candidate.to_lowercase()) {
+        number += 1;
+        candidate = format!("{} ({}).rdp", stem, number);
+    }
+    candidate
+}
+
+/// Writes one .rdp file per client into `folder`, each under the
+/// client's folder path relative to `base`, so `base`'s subfolders become
+/// subdirectories. Passwords are only written when `passwords` is set and
+/// the client has an account; mstsc then stops prompting for it.
+pub fn export(clients: &[Client], base: &str, folder: &Path, passwords: bool, options: &RdpOptions) -> Result<usize, String> {
+    let mut taken: HashMap<PathBuf, HashSet<String>> = HashMap::new();
+    let base: Vec<&str> = base.split('/').map(str::trim).filter(|name| !name.is_empty()).collect();
+    for client in clients {
+        let directory = client.group_path().skip(base.len()).fold(folder.to_path_buf(), |path, name| path.join(sanitize(name)));
+        let name = file_name(&client.name, taken.entry(directory.clone()).or_default());
+        let password = passwords && client.account().is_some() && !client.password.is_empty();
+        let mut text = render(client, !password, options)?;
+        if password {
+            text.push_str(&protected_password(&client.password)?);
+            text.push_str("\r\n");
+        }
+        fs::create_dir_all(&directory).map_err(|e| format!("Could not create {}: {}", directory.display(), e))?;
+        let path = directory.join(name);
+        fs::write(&path, encode(&text)).map_err(|e| format!("Could not write {}: {}", path.display(), e))?;
+    }
+    Ok(clients.len())
+}
@@ -0,0 +1,62 @@
+//! Background reachability checks: a TCP connect to each client's port,
+//! timed so slow links stand out from dead ones. A host counts as reachable
+//! once the handshake completes; nothing is sent over the connection.
+
+use crate::client::Client;
+use crate::settings::DefaultPorts;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+/// Answers slower than this are shown as slow.
+pub const SLOW: Duration = Duration::from_millis(250);
+/// Hosts probed at the same time.
+const PARALLEL: usize = 16;
+
+/// A client to probe, by id.
+#[derive(Clone)]
+pub struct Target {
+    pub client_id: String,
+    pub host: String,
+    pub port: u16,
+}
+
+impl Target {
+    /// The client's host and port, or its protocol's port in `defaults`.
+    /// Info-only and trashed clients are not probed.
+    pub fn of(client: &Client, defaults: &DefaultPorts) -> Option<Self> {
+        if client.info_only || client.trashed_at.is_some() {
+            return None;
+        }
+        let host = client.endpoint().0;
+        (!host.is_empty()).then(|| Target { client_id: client.id.clone(), host: host.to_string(), port: client.effective_port(defaults) })
+    }
+}
+
+/// How long the TCP handshake took, `None` when nothing answered.
+pub fn probe(host: &str, port: u16) -> Option<Duration> {
+    let addresses = (host, port).to_socket_addrs().ok()?;
+    addresses.take(2).find_map(|address| {
+        let started = Instant::now();
+        TcpStream::connect_timeout(&address, TIMEOUT).ok().map(|_| started.elapsed())
+    })
+}
+
+/// Probes every target, a few at a time. Returns each client id with its
+/// handshake time.
+pub fn probe_all(targets: Vec<Target>) -> Vec<(String, Option<Duration>)> {
+    if targets.is_empty() {
+        return Vec::new();
+    }
+    let chunk = targets.len().div_ceil(PARALLEL);
+    thread::scope(|scope| {
+        let workers: Vec<_> = targets
+            .chunks(chunk)
+            .map(|targets| {
+                scope.spawn(move || targets.iter().map(|target| (target.client_id.clone(), probe(&target.host, target.port))).collect::<Vec<_>>())
+            })
+            .collect();
+        workers.into_iter().flat_map(|worker| worker.join().unwrap_or_default()).collect()
+    })
+}
@@ -0,0 +1,105 @@
+use crate::client::Client;
+use crate::settings::UrlTemplate;
+use crate::template::{expand, is_valid_name, lookup};
+use std::process::Command;
+
+/// Fills the placeholders in a URL template with the client's values,
+/// percent-encoding them so they are safe in a URL.
+pub fn expand_template(template: &str, client: &Client) -> Result<String, String> {
+    expand(template, client, percent_encode)
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// A run of a client's notes as its details show them.
+#[derive(Debug, PartialEq)]
+pub enum NotePart {
+    Text(String),
+    /// A web address written in the notes, or `{reference}` opened through
+    /// the first URL template.
+    Link { text: String, url: String },
+}
+
+/// Splits `notes` into text and links, filling placeholders such as
+/// `{host}` or `{region}` with the client's values. `{reference}` links to
+/// the first URL template that expands for the client, and web addresses
+/// become links. Placeholders without a value are left as typed.
+pub fn note_parts(notes: &str, client: &Client, templates: &[UrlTemplate]) -> Vec<NotePart> {
+    let reference_url = templates.iter().find_map(|template| expand_template(&template.url, client).ok());
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut rest = notes;
+    while let Some(start) = rest.find('{') {
+        text.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find('}').map(|end| &after[..end]).filter(|name| is_valid_name(name)) {
+            Some(name) => {
+                match (lookup(name, client), &reference_url) {
+                    (Some(reference), Some(url)) if name == "reference" && !reference.is_empty() => {
+                        push_text(&mut parts, &std::mem::take(&mut text));
+                        parts.push(NotePart::Link { text: reference, url: url.clone() });
+                    }
+                    (Some(value), _) => text.push_str(&value),
+                    (None, _) => text.push_str(&format!("{{{}}}", name)),
+                }
+                rest = &after[name.len() + 1..];
+            }
+            None => {
+                text.push('{');
+                rest = after;
+            }
+        }
+    }
+    text.push_str(rest);
+    push_text(&mut parts, &text);
+    parts
+}
+
+/// Appends `text`, turning the web addresses in it into links.
+fn push_text(parts: &mut Vec<NotePart>, mut text: &str) {
+    while let Some(start) = ["https://", "http://"].iter().filter_map(|scheme| text.find(scheme)).min() {
+        let end = text[start..].find(char::is_whitespace).map_or(text.len(), |end| start + end);
+        // Punctuation after an address ends the sentence, not the address.
+        let url = text[start..end].trim_end_matches(['.', ',', ';', ':', '!', '?', ')', ']', '\'', '"']);
+        push_plain(parts, &text[..start]);
+        parts.push(NotePart::Link { text: url.to_string(), url: url.to_string() });
+        text = &text[start + url.len()..];
+    }
+    push_plain(parts, text);
+}
+
+fn push_plain(parts: &mut Vec<NotePart>, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    match parts.last_mut() {
+        Some(NotePart::Text(last)) => last.push_str(text),
+        _ => parts.push(NotePart::Text(text.to_string())),
+    }
+}
+
+/// Opens a URL in the default browser.
+pub fn open_url(url: &str) -> Result<(), String> {
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("rundll32");
+        command.arg("url.dll,FileProtocolHandler");
+        command
+    } else if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else {
+        Command::new("xdg-open")
+    };
+    let mut child = command.arg(url).spawn().map_err(|e| e.to_string())?;
+    std::thread::spawn(move || {
+        let _ = child.wait();
+    });
+    Ok(())
+}
@@ -0,0 +1,925 @@
+use crate::client::{Client, Contact};
+use crate::composition::{self, MonthlyCounts};
+use crate::credential::Credential;
+use crate::database::{self, Database, Snapshot};
+use crate::encryption::{
+    decrypt, decrypt_siv, derive_key, derive_key_with, encrypt, encrypt_siv, generate_key_from_password, generate_salt, key_check, mac, random_bytes, verify_mac,
+    KdfParams, NonceSequence, KEY_CHECK_SIZE, KEY_SIZE, LEGACY_KEY, MAC_SIZE, NONCE_SIZE, SALT_SIZE,
+};
+use crate::history::ConnectionRecord;
+use crate::storage;
+use crate::vault::{parse_vault, serialize_vault, VaultContents, VaultPolicy};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use zeroize::Zeroize;
+
+/// Change notifications sent to every subscriber of a repository.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RepositoryEvent {
+    Added(String),
+    Updated(String),
+    Removed(String),
+    /// The whole list was replaced, e.g. after an import, unlock or lock.
+    Reloaded,
+}
+
+/// Storage for the client list. The UI only talks to this trait, so other
+/// backends (a synced store, say) can be swapped in without touching views.
+pub trait ClientRepository {
+    fn clients(&self) -> &[Client];
+
+    fn get(&self, id: &str) -> Option<&Client> {
+        self.clients().iter().find(|client| client.id == id)
+    }
+
+    fn position(&self, id: &str) -> Option<usize> {
+        self.clients().iter().position(|client| client.id == id)
+    }
+
+    fn query(&self, filter: &dyn Fn(&Client) -> bool) -> Vec<&Client> {
+        self.clients().iter().filter(|client| filter(client)).collect()
+    }
+
+    fn add(&mut self, client: Client);
+
+    /// Replaces the client with the same id. Returns false if there is none.
+    fn update(&mut self, client: Client) -> bool;
+
+    fn remove(&mut self, id: &str) -> Option<Client>;
+
+    /// Moves the client with `id` to `index` in the list, shifting the
+    /// clients after it. Returns false if there is none.
+    fn move_client(&mut self, id: &str, index: usize) -> bool;
+
+    fn replace_all(&mut self, clients: Vec<Client>);
+
+    fn save(&mut self) -> Result<(), String>;
+
+    fn subscribe(&mut self) -> Receiver<RepositoryEvent>;
+}
+
+#[derive(Default)]
+pub struct InMemoryClientRepository {
+    clients: Vec<Client>,
+    subscribers: Vec<Sender<RepositoryEvent>>,
+}
+
+impl InMemoryClientRepository {
+    fn notify(&mut self, event: RepositoryEvent) {
+        self.subscribers.retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
+
+impl ClientRepository for InMemoryClientRepository {
+    fn clients(&self) -> &[Client] {
+        &self.clients
+    }
+
+    fn add(&mut self, client: Client) {
+        let id = client.id.clone();
+        self.clients.push(client);
+        self.notify(RepositoryEvent::Added(id));
+    }
+
+    fn update(&mut self, client: Client) -> bool {
+        let Some(index) = self.position(&client.id) else {
+            return false;
+        };
+        let id = client.id.clone();
+        self.clients[index] = client;
+        self.notify(RepositoryEvent::Updated(id));
+        true
+    }
+
+    fn remove(&mut self, id: &str) -> Option<Client> {
+        let index = self.position(id)?;
+        let client = self.clients.remove(index);
+        self.notify(RepositoryEvent::Removed(client.id.clone()));
+        Some(client)
+    }
+
+    fn move_client(&mut self, id: &str, index: usize) -> bool {
+        let Some(from) = self.position(id) else {
+            return false;
+        };
+        let client = self.clients.remove(from);
+        self.clients.insert(index.min(self.clients.len()), client);
+        self.notify(RepositoryEvent::Updated(id.to_string()));
+        true
+    }
+
+    fn replace_all(&mut self, clients: Vec<Client>) {
+        self.clients = clients;
+        self.notify(RepositoryEvent::Reloaded);
+    }
+
+    fn save(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn subscribe(&mut self) -> Receiver<RepositoryEvent> {
+        let (sender, receiver) = channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+}
+
+/// Vault written with a salt header but no outer MAC; read, never written.
+const SALTED_MAGIC: &[u8; 4] = b"RDMS";
+/// Vault with an outer MAC, AES-GCM encrypted: magic, salt, payload length
+/// as a little-endian u64, the nonce and ciphertext, then an HMAC over
+/// everything before it. The MAC covers the header too, and the length
+/// catches a cut-off file before any key is tried. Read, never written.
+const AUTHENTICATED_MAGIC: &[u8; 4] = b"RDMA";
+/// Laid out like `RDMA`, but AES-GCM-SIV encrypted with nonces from a
+/// `NonceSequence`, since the vault is rewritten with the same key on every
+/// save. Read, never written.
+const SIV_MAGIC: &[u8; 4] = b"RDMV";
+/// Current vault file, with a versioned header:
+///
+/// | bytes | field |
+/// |---|---|
+/// | 4 | `RDMF` |
+/// | 2 | format version, little-endian |
+/// | 1 | key derivation, 1 = Argon2id |
+/// | 12 | its memory in KiB, passes and lanes as little-endian u32s |
+/// | 16 | salt |
+/// | 1 | cipher, 1 = AES-256-GCM-SIV |
+/// | 16 | key check value |
+/// | 8 | slot size, little-endian |
+/// | n | first slot: the vault |
+/// | n | second slot: the decoy vault, or random bytes |
+/// | 32 | HMAC over the header and the used part of the first slot |
+/// | 32 | SHA-256 over everything before it |
+///
+/// Both slots are laid out alike, see `seal_slot`, and a vault without a
+/// duress key fills the second with random bytes, so the file does not
+/// tell whether there is a decoy vault in it. Version 1 files held one
+/// payload, the nonce and ciphertext, where the slots are; the MAC covered
+/// everything before it.
+///
+/// The checksum needs no key, so a damaged file is told apart from a wrong
+/// key before the key is derived; the key check then tells a wrong key from
+/// a file altered by someone who also fixed the checksum. A later format
+/// bumps the version, and this one stays readable so it can be migrated.
+const VERSIONED_MAGIC: &[u8; 4] = b"RDMF";
+const FORMAT_VERSION: u16 = 2;
+/// The first version with two slots.
+const SLOTS_VERSION: u16 = 2;
+const KDF_ARGON2ID: u8 = 1;
+const CIPHER_AES_GCM_SIV: u8 = 1;
+/// Magic, version, key derivation with its parameters, salt, cipher and key
+/// check.
+const HEADER_SIZE: usize = 4 + 2 + 1 + 12 + SALT_SIZE + 1 + KEY_CHECK_SIZE;
+const LENGTH_SIZE: usize = 8;
+const CHECKSUM_SIZE: usize = 32;
+/// The AES-GCM-SIV tag after every ciphertext.
+const TAG_SIZE: usize = 16;
+/// The nonce, the encrypted length of a slot's contents and its tag.
+const SLOT_LENGTH_SIZE: usize = NONCE_SIZE + LENGTH_SIZE + TAG_SIZE;
+/// Slots grow in steps of this size, so their size says little about how
+/// much either holds.
+const SLOT_STEP: usize = 64 * 1024;
+
+pub(crate) const TAMPERED: &str = "The vault file is damaged or has been tampered with.";
+pub(crate) const DAMAGED: &str = "The vault file is damaged: it was cut off or changed on disk. Restore Backup on the lock screen brings back an earlier copy.";
+pub(crate) const INCORRECT_KEY: &str = "Incorrect master key.";
+
+fn newer_format(what: &str) -> String {
+    format!("The vault was saved by a newer version of Remote Desktop Manager ({}). Update the app to open it.", what)
+}
+
+/// A vault file taken apart. Vaults from before the header have no salt and
+/// are encrypted as a whole.
+struct VaultFile<'a> {
+    /// AES-GCM-SIV rather than AES-GCM.
+    siv: bool,
+    salt: Option<[u8; SALT_SIZE]>,
+    /// The cost the key was derived with; the defaults before the header
+    /// recorded it.
+    kdf: KdfParams,
+    /// Tells a wrong key from a damaged file; versioned files only.
+    key_check: Option<&'a [u8]>,
+    payload: &'a [u8],
+    /// The authenticated part of the file and its tag. With two slots only
+    /// the header is here; the used part of the first slot, `payload`,
+    /// follows it under the MAC.
+    mac: Option<(&'a [u8], &'a [u8])>,
+    /// The second slot of a file with two.
+    spare: Option<&'a [u8]>,
+}
+
+fn parse_file(data: &[u8]) -> Result<VaultFile<'_>, String> {
+    if data.starts_with(VERSIONED_MAGIC) {
+        return parse_versioned(data);
+    }
+    let siv = data.starts_with(SIV_MAGIC);
+    if let Some(rest) = data.strip_prefix(AUTHENTICATED_MAGIC.as_slice()).or_else(|| data.strip_prefix(SIV_MAGIC.as_slice())) {
+        if rest.len() < SALT_SIZE + LENGTH_SIZE + MAC_SIZE {
+            return Err(TAMPERED.to_string());
+        }
+        let (salt, rest) = rest.split_at(SALT_SIZE);
+        let (length, rest) = rest.split_at(LENGTH_SIZE);
+        let length = u64::from_le_bytes(length.try_into().unwrap_or_default());
+        if rest.len() as u64 != length.saturating_add(MAC_SIZE as u64) {
+            return Err(TAMPERED.to_string());
+        }
+        let (payload, tag) = rest.split_at(rest.len() - MAC_SIZE);
+        return Ok(VaultFile {
+            siv,
+            salt: salt.try_into().ok(),
+            kdf: KdfParams::default(),
+            key_check: None,
+            payload,
+            mac: Some((&data[..data.len() - MAC_SIZE], tag)),
+            spare: None,
+        });
+    }
+    Ok(match data.strip_prefix(SALTED_MAGIC.as_slice()) {
+        Some(rest) if rest.len() >= SALT_SIZE => {
+            let (salt, payload) = rest.split_at(SALT_SIZE);
+            VaultFile { siv: false, salt: salt.try_into().ok(), kdf: KdfParams::default(), key_check: None, payload, mac: None, spare: None }
+        }
+        _ => VaultFile { siv: false, salt: None, kdf: KdfParams::default(), key_check: None, payload: data, mac: None, spare: None },
+    })
+}
+
+/// Takes apart an `RDMF` file. The version is checked first, since a later
+/// one may lay out the rest differently.
+fn parse_versioned(data: &[u8]) -> Result<VaultFile<'_>, String> {
+    let version = data.get(4..6).map_or(0, |bytes| u16::from_le_bytes([bytes[0], bytes[1]]));
+    if version > FORMAT_VERSION {
+        return Err(newer_format(&format!("file format {}", version)));
+    }
+    if version == 0 || data.len() < HEADER_SIZE + LENGTH_SIZE + MAC_SIZE + CHECKSUM_SIZE {
+        return Err(DAMAGED.to_string());
+    }
+    let (body, checksum) = data.split_at(data.len() - CHECKSUM_SIZE);
+    if Sha256::digest(body).as_slice() != checksum {
+        return Err(DAMAGED.to_string());
+    }
+    let header = parse_header(body)?;
+    let (length, rest) = body[HEADER_SIZE..].split_at(LENGTH_SIZE);
+    let length = u64::from_le_bytes(length.try_into().unwrap_or_default());
+    let slots = if version >= SLOTS_VERSION { 2 } else { 1 };
+    if rest.len() as u64 != length.saturating_mul(slots).saturating_add(MAC_SIZE as u64) {
+        return Err(DAMAGED.to_string());
+    }
+    let (payload, tag) = rest.split_at(rest.len() - MAC_SIZE);
+    let (payload, spare, signed) = match slots {
+        2 => {
+            let (first, second) = payload.split_at(payload.len() / 2);
+            (first, Some(second), &body[..HEADER_SIZE])
+        }
+        _ => (payload, None, &body[..body.len() - MAC_SIZE]),
+    };
+    Ok(VaultFile {
+        siv: true,
+        salt: Some(header.salt),
+        kdf: header.kdf,
+        key_check: Some(header.key_check),
+        payload,
+        mac: Some((signed, tag)),
+        spare,
+    })
+}
+
+/// A slot holding `data` encrypted with `key`, at least `size` bytes long
+/// and grown in whole steps to fit: the salt the key was derived with, the
+/// contents' length sealed on its own, the sealed contents, then random
+/// bytes. A slot in use cannot be told from random bytes without its key.
+/// Returns the slot and how much of it is not padding.
+fn seal_slot(salt: &[u8; SALT_SIZE], data: &[u8], key: &[u8; KEY_SIZE], nonces: &mut NonceSequence, size: usize) -> Result<(Vec<u8>, usize), String> {
+    let mut seal = |data: &[u8]| -> Result<Vec<u8>, String> { encrypt_siv(data, key, &nonces.next_nonce()?).map_err(|_| "Could not encrypt the vault.".to_string()) };
+    let contents = seal(data)?;
+    let length = seal(&(contents.len() as u64).to_le_bytes())?;
+    let mut slot = [salt.as_slice(), &length, &contents].concat();
+    let used = slot.len();
+    slot.extend_from_slice(&random_bytes(size.max(used.div_ceil(SLOT_STEP) * SLOT_STEP) - used));
+    Ok((slot, used))
+}
+
+/// What a slot opened with its key holds.
+struct OpenedSlot {
+    data: Vec<u8>,
+    /// How much of the slot is not padding.
+    used: usize,
+    /// The nonces to seal the slot with next.
+    nonces: NonceSequence,
+}
+
+/// Decrypts a slot sealed by `seal_slot`; `None` when `key` is not the
+/// slot's or the slot holds random bytes.
+fn open_slot(slot: &[u8], key: &[u8; KEY_SIZE]) -> Option<OpenedSlot> {
+    let length_block = slot.get(SALT_SIZE..SALT_SIZE + SLOT_LENGTH_SIZE)?;
+    let length = decrypt_siv(length_block, key).ok()?;
+    let length = usize::try_from(u64::from_le_bytes(length.as_slice().try_into().ok()?)).ok()?;
+    let used = (SALT_SIZE + SLOT_LENGTH_SIZE).checked_add(length)?;
+    let data = decrypt_siv(slot.get(SALT_SIZE + SLOT_LENGTH_SIZE..used)?, key).ok()?;
+    Some(OpenedSlot { data, used, nonces: NonceSequence::after(length_block) })
+}
+
+/// The salt a slot's key is derived with.
+fn slot_salt(slot: &[u8]) -> Option<[u8; SALT_SIZE]> {
+    slot.get(..SALT_SIZE)?.try_into().ok()
+}
+
+/// A slot of random bytes, as a vault without a duress key has.
+fn random_slot(size: usize) -> Vec<u8> {
+    random_bytes(size.max(SLOT_STEP))
+}
+
+/// Lays out a two-slot vault file, growing the shorter slot with random
+/// bytes so both have the same size.
+fn write_slots(header: &[u8], mut first: Vec<u8>, mut second: Vec<u8>, tag: &[u8]) -> Vec<u8> {
+    let size = first.len().max(second.len());
+    first.extend_from_slice(&random_bytes(size - first.len()));
+    second.extend_from_slice(&random_bytes(size - second.len()));
+    let mut file = header.to_vec();
+    file.extend_from_slice(&(size as u64).to_le_bytes());
+    file.extend_from_slice(&first);
+    file.extend_from_slice(&second);
+    file.extend_from_slice(tag);
+    let checksum = Sha256::digest(&file);
+    file.extend_from_slice(&checksum);
+    file
+}
+
+/// The second slot of the vault at `path`, kept in the file or in the
+/// database; `None` for vaults written before there were two.
+fn spare_slot(path: &Path) -> Option<Vec<u8>> {
+    if database::is_database(path) {
+        return database::read_spare(path);
+    }
+    let data = fs::read(path).ok()?;
+    parse_file(&data).ok()?.spare.map(<[u8]>::to_vec)
+}
+
+/// What the versioned header says about the key a vault is encrypted with.
+pub(crate) struct Header<'a> {
+    pub salt: [u8; SALT_SIZE],
+    pub kdf: KdfParams,
+    pub key_check: &'a [u8],
+}
+
+/// Reads the versioned header at the start of `data`: that of an `RDMF`
+/// file, or the one a vault database keeps.
+pub(crate) fn parse_header(data: &[u8]) -> Result<Header<'_>, String> {
+    let version = data.get(4..6).map_or(0, |bytes| u16::from_le_bytes([bytes[0], bytes[1]]));
+    if version > FORMAT_VERSION {
+        return Err(newer_format(&format!("file format {}", version)));
+    }
+    if !data.starts_with(VERSIONED_MAGIC) || version == 0 || data.len() < HEADER_SIZE {
+        return Err(DAMAGED.to_string());
+    }
+    let number = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap_or_default());
+    let kdf = KdfParams { memory_kib: number(7), passes: number(11), lanes: number(15) };
+    let (salt, rest) = data[19..HEADER_SIZE].split_at(SALT_SIZE);
+    let (&cipher, key_check) = rest.split_first().unwrap_or((&0, &[]));
+    if data[6] != KDF_ARGON2ID || !kdf.is_supported() {
+        return Err(newer_format("an unknown key derivation"));
+    }
+    if cipher != CIPHER_AES_GCM_SIV {
+        return Err(newer_format("an unknown cipher"));
+    }
+    Ok(Header { salt: salt.try_into().unwrap_or_default(), kdf, key_check })
+}
+
+/// The versioned header for a vault encrypted with `key`.
+pub(crate) fn write_header(salt: &[u8; SALT_SIZE], kdf: &KdfParams, key: &[u8; KEY_SIZE]) -> Vec<u8> {
+    let mut header = VERSIONED_MAGIC.to_vec();
+    header.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    header.push(KDF_ARGON2ID);
+    for number in [kdf.memory_kib, kdf.passes, kdf.lanes] {
+        header.extend_from_slice(&number.to_le_bytes());
+    }
+    header.extend_from_slice(salt);
+    header.push(CIPHER_AES_GCM_SIV);
+    header.extend_from_slice(&key_check(key));
+    header
+}
+
+/// The salt and key derivation of the vault at `path`, from a file or a
+/// database.
+fn key_parameters(path: &Path) -> Result<(Option<[u8; SALT_SIZE]>, KdfParams), String> {
+    if database::is_database(path) {
+        let header = database::read_header(path)?;
+        let header = parse_header(&header)?;
+        return Ok((Some(header.salt), header.kdf));
+    }
+    let data = fs::read(path).map_err(|e| format!("Could not read the vault: {}", e))?;
+    let file = parse_file(&data)?;
+    Ok((file.salt, file.kdf))
+}
+
+/// Where the decoy vault opened by the duress key was kept, next to the
+/// vault, before it moved into the vault's second slot. Read until its
+/// next save moves it there.
+pub fn decoy_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".cache");
+    path.with_file_name(name)
+}
+
+/// Every key a master key may open the vault at `path` with. Deriving them
+/// is the slow part of unlocking, so it can run away from the UI thread;
+/// a key for the second slot is derived too, whether or not it holds a
+/// decoy vault, so unlocking with the duress key takes no longer than with
+/// the master key and the time taken does not tell whether there is one.
+/// Wiped when dropped.
+pub struct DerivedKeys {
+    /// The salted key, or the legacy key of a vault without a salt.
+    opening: [u8; KEY_SIZE],
+    /// The key and fresh salt a vault without a salt moves to.
+    migrated: Option<([u8; KEY_SIZE], [u8; SALT_SIZE])>,
+    decoy: Option<[u8; KEY_SIZE]>,
+}
+
+impl DerivedKeys {
+    pub fn derive(path: &Path, password: &str) -> Result<Self, String> {
+        let (salt, kdf) = key_parameters(path)?;
+        let (opening, migrated) = match salt {
+            Some(salt) => (derive_key_with(password, &salt, &kdf), None),
+            None => {
+                let salt = generate_salt();
+                (generate_key_from_password(password), Some((derive_key(password, &salt), salt)))
+            }
+        };
+        let legacy = decoy_path(path);
+        let decoy = match legacy.exists() {
+            true => key_parameters(&legacy).ok().and_then(|(salt, kdf)| Some(derive_key_with(password, &salt?, &kdf))),
+            false => spare_slot(path).and_then(|slot| slot_salt(&slot)).map(|salt| derive_key(password, &salt)),
+        };
+        Ok(Self { opening, migrated, decoy })
+    }
+}
+
+impl Drop for DerivedKeys {
+    fn drop(&mut self) {
+        self.opening.zeroize();
+        if let Some((key, _)) = &mut self.migrated {
+            key.zeroize();
+        }
+        self.decoy.zeroize();
+    }
+}
+
+/// The vault file: clients and policy, AES-GCM-SIV encrypted with a key
+/// derived from the master key and the salt in the file header.
+///
+/// A duress key opens a separate decoy vault, kept in the file's second
+/// slot, instead. While it is open every read and write goes to that slot
+/// and the real vault is not touched. Only `is_decoy` tells the two apart,
+/// for the app to leave no trace of the duress key outside the vault.
+pub struct EncryptedFileRepository {
+    path: PathBuf,
+    decoy: bool,
+    key: [u8; KEY_SIZE],
+    /// `None` only while a pre-header vault is open with a cached key; such
+    /// vaults keep their old format until they are unlocked with the master key.
+    salt: Option<[u8; SALT_SIZE]>,
+    /// The cost `key` was derived with, written to the header on save.
+    kdf: KdfParams,
+    /// Nonces for the next saves under `key`.
+    nonces: NonceSequence,
+    /// Set while the vault is kept in a SQLite database.
+    database: Option<Database>,
+    /// Client list changes not yet saved, for a database to write only those.
+    changes: Receiver<RepositoryEvent>,
+    pub policy: VaultPolicy,
+    /// Sessions opened from this vault, oldest first.
+    pub history: Vec<ConnectionRecord>,
+    /// Accounts shared by several clients.
+    pub credentials: Vec<Credential>,
+    /// Client counts of each month, oldest first.
+    pub composition: Vec<MonthlyCounts>,
+    /// Whom to call about the clients of a group, by group path, for
+    /// clients without an owner of their own.
+    pub group_contacts: BTreeMap<String, Contact>,
+    /// Automatic backups kept of the vault file.
+    pub backups: u32,
+    memory: InMemoryClientRepository,
+}
+
+impl EncryptedFileRepository {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let mut memory = InMemoryClientRepository::default();
+        let changes = memory.subscribe();
+        Self {
+            path: path.into(),
+            decoy: false,
+            key: [0; KEY_SIZE],
+            salt: None,
+            kdf: KdfParams::default(),
+            nonces: NonceSequence::default(),
+            database: None,
+            changes,
+            policy: VaultPolicy::default(),
+            history: Vec::new(),
+            credentials: Vec::new(),
+            composition: Vec::new(),
+            group_contacts: BTreeMap::new(),
+            backups: 0,
+            memory,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    pub fn key(&self) -> &[u8; KEY_SIZE] {
+        &self.key
+    }
+
+    /// Whether the duress key opened the decoy vault.
+    pub fn is_decoy(&self) -> bool {
+        self.decoy
+    }
+
+    /// Derives the key from the master key and decrypts the vault, or the
+    /// decoy vault if it is the duress key.
+    pub fn unlock(&mut self, password: &str) -> Result<(), String> {
+        let keys = DerivedKeys::derive(&self.path, password)?;
+        self.unlock_derived(&keys)
+    }
+
+    /// Decrypts the vault, or the decoy vault, with keys derived for it by
+    /// `DerivedKeys::derive`. Vaults from before the salted header move to
+    /// the fresh salt and Argon2id key and are rewritten in the new format
+    /// on the next save.
+    pub fn unlock_derived(&mut self, keys: &DerivedKeys) -> Result<(), String> {
+        let result = self.load_path(&self.path.clone(), keys.opening).map(|()| {
+            if let Some((key, salt)) = keys.migrated {
+                self.key = key;
+                self.salt = Some(salt);
+            }
+        });
+        match result {
+            Ok(()) => {
+                self.decoy = false;
+                Ok(())
+            }
+            Err(e) => match keys.decoy {
+                Some(key) => self.load_decoy(key).map_err(|_| e),
+                None => Err(e),
+            },
+        }
+    }
+
+    /// Decrypts the vault file with an already derived key, e.g. one kept
+    /// across a lock.
+    pub fn open(&mut self, key: [u8; KEY_SIZE]) -> Result<(), String> {
+        match self.load_path(&self.path.clone(), key) {
+            Ok(()) => {
+                self.decoy = false;
+                Ok(())
+            }
+            Err(e) => self.load_decoy(key).map_err(|_| e),
+        }
+    }
+
+    /// Opens the second slot with `key`, or the decoy file it replaced.
+    fn load_decoy(&mut self, key: [u8; KEY_SIZE]) -> Result<(), String> {
+        let legacy = decoy_path(&self.path);
+        if legacy.exists() {
+            if key_parameters(&legacy)?.0.is_none() {
+                return Err("The decoy vault is corrupt.".to_string());
+            }
+            self.load_path(&legacy, key)?;
+        } else {
+            let slot = spare_slot(&self.path).ok_or_else(|| INCORRECT_KEY.to_string())?;
+            let opened = open_slot(&slot, &key).ok_or_else(|| INCORRECT_KEY.to_string())?;
+            let contents = parse_vault(&opened.data).map_err(|e| format!("The vault is corrupt: {}", e))?;
+            self.take_contents(key, slot_salt(&slot), KdfParams::default(), opened.nonces, contents);
+            self.database = None;
+        }
+        self.decoy = true;
+        Ok(())
+    }
+
+    /// Decrypts the vault at `path`, kept as a file or as a database.
+    fn load_path(&mut self, path: &Path, key: [u8; KEY_SIZE]) -> Result<(), String> {
+        if !database::is_database(path) {
+            let data = fs::read(path).map_err(|e| format!("Could not read the vault: {}", e))?;
+            return self.load(&parse_file(&data)?, key);
+        }
+        let header = database::read_header(path)?;
+        let header = parse_header(&header)?;
+        if header.key_check != key_check(&key) {
+            return Err(INCORRECT_KEY.to_string());
+        }
+        let (database, contents, nonces) = Database::load(path, &key)?;
+        self.take_contents(key, Some(header.salt), header.kdf, nonces, contents);
+        self.database = Some(database);
+        Ok(())
+    }
+
+    /// Moves the vault, and its decoy if there is one, to `path`. The open
+    /// vault is saved there and read from there from now on.
+    pub fn relocate(&mut self, path: PathBuf) -> Result<(), String> {
+        if path.exists() {
+            return Err(format!("{} already exists.", path.display()));
+        }
+        for (from, to) in storage::vault_files(&self.path).into_iter().zip(storage::vault_files(&path)) {
+            if from.exists() {
+                storage::move_file(&from, &to).map_err(|e| format!("Could not move {}: {}", from.display(), e))?;
+            }
+        }
+        self.path = path;
+        Ok(())
+    }
+
+    /// Sets up `password` as the duress key with an empty decoy vault in
+    /// the second slot, replacing any earlier one. It can then be filled
+    /// with innocuous entries by unlocking with it. Inside a decoy vault
+    /// this does nothing, so it cannot overwrite the real one.
+    pub fn create_decoy(&mut self, password: &str) -> Result<(), String> {
+        if self.decoy {
+            return Ok(());
+        }
+        let matches_master_key = match self.salt {
+            Some(salt) => derive_key_with(password, &salt, &self.kdf) == self.key,
+            None => generate_key_from_password(password) == self.key,
+        };
+        if matches_master_key {
+            return Err("The duress key must differ from the master key.".to_string());
+        }
+        let salt = generate_salt();
+        let mut key = derive_key(password, &salt);
+        let empty = serialize_vault(&VaultPolicy::default(), &[], &[], &[], &[], &BTreeMap::new());
+        let slot = seal_slot(&salt, &empty, &key, &mut NonceSequence::default(), 0);
+        key.zeroize();
+        self.write_file(Some(slot?.0))?;
+        self.remove_legacy_decoy()
+    }
+
+    /// Fills the second slot with random bytes; the duress key stops
+    /// working. Inside a decoy vault this does nothing.
+    pub fn remove_decoy(&mut self) -> Result<(), String> {
+        if self.decoy {
+            return Ok(());
+        }
+        self.write_file(Some(random_slot(0)))?;
+        self.remove_legacy_decoy()
+    }
+
+    fn remove_legacy_decoy(&self) -> Result<(), String> {
+        match fs::remove_file(decoy_path(&self.path)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(format!("Could not remove the old decoy vault: {}", e)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Vaults written before master keys existed still use the legacy key;
+    /// `key` becomes their key on the next save. A MAC that fails while the
+    /// payload decrypts, or the reverse, means the file was altered; both
+    /// failing is the wrong key, unless the key check in the header says the
+    /// key is right.
+    fn load(&mut self, file: &VaultFile, key: [u8; KEY_SIZE]) -> Result<(), String> {
+        if file.key_check.is_some_and(|check| check != key_check(&key)) {
+            return Err(INCORRECT_KEY.to_string());
+        }
+        let (decrypted, authentic) = match file.spare {
+            Some(_) => {
+                let opened = open_slot(file.payload, &key);
+                let used = opened.as_ref().map_or(file.payload.len(), |opened| opened.used);
+                let authentic = file.mac.map(|(header, tag)| verify_mac(&[header, &file.payload[..used]].concat(), tag, &key));
+                (opened.map(|opened| (opened.data, opened.nonces)).ok_or(()), authentic)
+            }
+            None => {
+                let decrypted = if file.siv {
+                    decrypt_siv(file.payload, &key)
+                } else {
+                    decrypt(file.payload, &key).or_else(|e| if file.salt.is_none() { decrypt(file.payload, &LEGACY_KEY) } else { Err(e) })
+                };
+                let nonces = if file.siv { NonceSequence::after(&file.payload[..NONCE_SIZE]) } else { NonceSequence::default() };
+                (decrypted.map(|data| (data, nonces)).map_err(drop), file.mac.map(|(signed, tag)| verify_mac(signed, tag, &key)))
+            }
+        };
+        let (decrypted_data, nonces) = match (decrypted, authentic) {
+            (Ok(opened), None | Some(true)) => opened,
+            (Err(_), None | Some(false)) if file.key_check.is_none() => return Err(INCORRECT_KEY.to_string()),
+            _ => return Err(TAMPERED.to_string()),
+        };
+        let contents = parse_vault(&decrypted_data).map_err(|e| format!("The vault is corrupt: {}", e))?;
+        self.take_contents(key, file.salt, file.kdf, nonces, contents);
+        self.database = None;
+        Ok(())
+    }
+
+    fn take_contents(&mut self, key: [u8; KEY_SIZE], salt: Option<[u8; SALT_SIZE]>, kdf: KdfParams, nonces: NonceSequence, contents: VaultContents) {
+        let (policy, clients, history, credentials, composition, group_contacts) = contents;
+        self.key = key;
+        self.salt = salt;
+        self.kdf = kdf;
+        self.nonces = nonces;
+        self.policy = policy;
+        self.history = history;
+        self.credentials.iter_mut().for_each(Credential::wipe_secrets);
+        self.credentials = credentials;
+        self.composition = composition;
+        self.group_contacts = group_contacts;
+        self.memory.replace_all(clients);
+        // Loading is not a change a database has to write back.
+        self.changes.try_iter().for_each(drop);
+    }
+
+    /// Whether the vault is kept in a SQLite database rather than one file.
+    pub fn is_database(&self) -> bool {
+        self.database.is_some()
+    }
+
+    /// Keeps the vault in a SQLite database, which saves only the clients
+    /// that changed, or back in one encrypted file. The next save converts
+    /// it in place; the vault keeps its path.
+    pub fn use_database(&mut self, on: bool) -> Result<(), String> {
+        if on == self.database.is_some() {
+            return Ok(());
+        }
+        if on && self.salt.is_none() {
+            return Err("Unlock the vault with the master key before converting it.".to_string());
+        }
+        self.database = on.then(Database::default);
+        Ok(())
+    }
+
+    /// Whether `password` derives the key the open vault uses.
+    pub fn is_master_key(&self, password: &str) -> bool {
+        let mut key = match self.salt {
+            Some(salt) => derive_key_with(password, &salt, &self.kdf),
+            None => generate_key_from_password(password),
+        };
+        let matches = key == self.key;
+        key.zeroize();
+        matches
+    }
+
+    /// Re-encrypts the vault under `new_password` with a fresh salt once
+    /// `current_password` is confirmed to be the master key. The old key
+    /// stays in use if the file cannot be written.
+    pub fn change_key(&mut self, current_password: &str, new_password: &str) -> Result<(), String> {
+        if !self.is_master_key(current_password) {
+            return Err("The current master key is incorrect.".to_string());
+        }
+        let (old_key, old_salt, old_kdf, old_nonces) = (self.key, self.salt, self.kdf, self.nonces);
+        let salt = generate_salt();
+        self.key = derive_key(new_password, &salt);
+        self.salt = Some(salt);
+        self.kdf = KdfParams::default();
+        self.nonces = NonceSequence::default();
+        if let Some(database) = &mut self.database {
+            database.rewrite_all();
+        }
+        let result = self.write_file(None);
+        if result.is_err() {
+            self.key = old_key;
+            self.salt = old_salt;
+            self.kdf = old_kdf;
+            self.nonces = old_nonces;
+        }
+        result
+    }
+
+    /// Writes the open vault to its slot, keeping the other as it is. A
+    /// `spare` given replaces the second slot of the real vault.
+    fn write_file(&mut self, spare: Option<Vec<u8>>) -> Result<(), String> {
+        if self.decoy {
+            return self.write_decoy();
+        }
+        let path = self.path.clone();
+        let replace_spare = spare.is_some();
+        let spare = spare.or_else(|| spare_slot(&path)).unwrap_or_else(|| random_slot(0));
+        let changes: Vec<RepositoryEvent> = self.changes.try_iter().collect();
+        if let (Some(database), Some(salt)) = (&mut self.database, self.salt) {
+            changes.into_iter().for_each(|change| database.note(change));
+            let vault = Snapshot {
+                header: write_header(&salt, &self.kdf, &self.key),
+                policy: &self.policy,
+                clients: self.memory.clients(),
+                history: &self.history,
+                credentials: &self.credentials,
+                composition: &self.composition,
+                group_contacts: &self.group_contacts,
+                spare: &spare,
+            };
+            database.save(&path, &vault, &self.key, &mut self.nonces)?;
+            return if replace_spare { database::write_spare(&path, &spare) } else { Ok(()) };
+        }
+        let data = serialize_vault(&self.policy, self.memory.clients(), &self.history, &self.credentials, &self.composition, &self.group_contacts);
+        let file = match self.salt {
+            Some(salt) => {
+                // The first slot's salt field is random; its key uses the header's.
+                let (first, used) = seal_slot(&generate_salt(), &data, &self.key, &mut self.nonces, spare.len())?;
+                let header = write_header(&salt, &self.kdf, &self.key);
+                let tag = mac(&[header.as_slice(), &first[..used]].concat(), &self.key);
+                write_slots(&header, first, spare, &tag)
+            }
+            None => encrypt(&data, &self.key).map_err(|_| "Could not encrypt the vault.".to_string())?,
+        };
+        storage::write_atomically(&path, &file).map_err(|e| format!("Could not write the vault: {}", e))
+    }
+
+    /// Writes the decoy vault to the second slot, leaving the header and the
+    /// first slot as they are. A decoy still kept in its own file stays
+    /// there until the real vault has been saved with two slots.
+    fn write_decoy(&mut self) -> Result<(), String> {
+        let legacy = decoy_path(&self.path);
+        let salt = self.salt.ok_or_else(|| "The decoy vault is corrupt.".to_string())?;
+        let data = serialize_vault(&self.policy, self.memory.clients(), &self.history, &self.credentials, &self.composition, &self.group_contacts);
+        if database::is_database(&self.path) && database::read_spare(&self.path).is_some() {
+            let (slot, _) = seal_slot(&salt, &data, &self.key, &mut self.nonces, 0)?;
+            database::write_spare(&self.path, &slot)?;
+        } else {
+            let current = fs::read(&self.path).map_err(|e| format!("Could not read the vault: {}", e))?;
+            let file = parse_file(&current)?;
+            let (Some((header, tag)), Some(spare)) = (file.mac, file.spare) else {
+                let (slot, used) = seal_slot(&salt, &data, &self.key, &mut self.nonces, 0)?;
+                let header = write_header(&salt, &self.kdf, &self.key);
+                let tag = mac(&[header.as_slice(), &slot[..used]].concat(), &self.key);
+                let written = write_slots(&header, slot, random_slot(0), &tag);
+                return storage::write_atomically(&legacy, &written).map_err(|e| format!("Could not write the vault: {}", e));
+            };
+            let (slot, _) = seal_slot(&salt, &data, &self.key, &mut self.nonces, spare.len())?;
+            let written = write_slots(header, file.payload.to_vec(), slot, tag);
+            storage::write_atomically(&self.path, &written).map_err(|e| format!("Could not write the vault: {}", e))?;
+        }
+        self.remove_legacy_decoy()
+    }
+
+    /// Starts an empty vault protected by `key`, derived with `salt`.
+    pub fn create(&mut self, key: [u8; KEY_SIZE], salt: [u8; SALT_SIZE]) {
+        self.decoy = false;
+        self.key = key;
+        self.salt = Some(salt);
+        self.kdf = KdfParams::default();
+        self.nonces = NonceSequence::default();
+        self.database = None;
+        self.policy = VaultPolicy::default();
+        self.history.clear();
+        self.credentials.clear();
+        self.composition.clear();
+        self.group_contacts.clear();
+        self.memory.replace_all(Vec::new());
+    }
+
+    /// Zeroizes the key and every decrypted secret.
+    pub fn close(&mut self) {
+        self.key.zeroize();
+        let mut clients = std::mem::take(&mut self.memory.clients);
+        clients.iter_mut().for_each(Client::wipe_secrets);
+        self.credentials.iter_mut().for_each(Credential::wipe_secrets);
+        self.credentials.clear();
+        self.history.clear();
+        self.composition.clear();
+        self.group_contacts.clear();
+        self.memory.replace_all(Vec::new());
+    }
+}
+
+impl ClientRepository for EncryptedFileRepository {
+    fn clients(&self) -> &[Client] {
+        self.memory.clients()
+    }
+
+    fn add(&mut self, client: Client) {
+        self.memory.add(client);
+    }
+
+    fn update(&mut self, client: Client) -> bool {
+        self.memory.update(client)
+    }
+
+    fn remove(&mut self, id: &str) -> Option<Client> {
+        self.memory.remove(id)
+    }
+
+    fn move_client(&mut self, id: &str, index: usize) -> bool {
+        self.memory.move_client(id, index)
+    }
+
+    fn replace_all(&mut self, clients: Vec<Client>) {
+        self.memory.replace_all(clients);
+    }
+
+    /// Backs the file up first, see `storage::backup_vault`. A failed backup
+    /// does not keep the vault from being saved. Counts the clients for
+    /// this month's entry of `composition`.
+    fn save(&mut self) -> Result<(), String> {
+        composition::record(&mut self.composition, self.memory.clients(), chrono::Local::now().date_naive());
+        let backup = storage::backup_vault(&self.path, self.backups);
+        self.write_file(None)?;
+        backup.map_err(|e| format!("The vault was saved, but could not be backed up: {}", e))
+    }
+
+    fn subscribe(&mut self) -> Receiver<RepositoryEvent> {
+        self.memory.subscribe()
+    }
+}
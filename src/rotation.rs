@@ -0,0 +1,168 @@
+//! Changes a client's account password on the host itself: over WinRM with
+//! PowerShell remoting for RDP clients, with `passwd` over SSH for SSH
+//! clients. Both change the account's own password with the current one,
+//! so no administrator rights are needed and the host's password rules
+//! apply. Passwords reach the child process on stdin, never its command
+//! line.
+
+use crate::client::{Client, Protocol};
+use crate::launcher::Launcher;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use zeroize::Zeroizing;
+
+/// WinRM over HTTP; the traffic is still encrypted by Kerberos or NTLM.
+pub const WINRM_PORT: u16 = 5985;
+const TIMEOUT_SECONDS: u32 = 20;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Method {
+    WinRm,
+    Ssh,
+}
+
+impl Method {
+    pub fn label(self) -> &'static str {
+        match self {
+            Method::WinRm => "WinRM",
+            Method::Ssh => "SSH",
+        }
+    }
+
+    /// How the password of `client` is changed, or why it cannot be.
+    pub fn for_client(client: &Client) -> Result<Method, &'static str> {
+        if client.info_only {
+            return Err("information only");
+        }
+        if client.credential.is_some() {
+            return Err("uses a shared credential");
+        }
+        if client.username.is_empty() || client.password.is_empty() {
+            return Err("no stored user name and password");
+        }
+        if !client.domain.is_empty() && client.domain != "." {
+            return Err("domain accounts change on the domain controller");
+        }
+        match client.protocol {
+            Protocol::Rdp => Ok(Method::WinRm),
+            Protocol::Ssh => Ok(Method::Ssh),
+            Protocol::Vnc => Err("VNC has no account to change"),
+        }
+    }
+}
+
+/// One password change, prepared on the UI thread and run on a worker.
+#[derive(Clone)]
+pub struct Job {
+    pub client_id: String,
+    pub method: Method,
+    pub host: String,
+    pub port: Option<u16>,
+    pub username: String,
+    pub current: Zeroizing<String>,
+    pub new: Zeroizing<String>,
+}
+
+impl Job {
+    pub fn new(client: &Client, new: &str) -> Result<Self, String> {
+        let method = Method::for_client(client).map_err(|reason| format!("{}: {}", client.name, reason))?;
+        let (host, port) = client.endpoint();
+        Ok(Self {
+            client_id: client.id.clone(),
+            method,
+            host: host.to_string(),
+            port: if method == Method::Ssh { port } else { None },
+            username: client.username.clone(),
+            current: Zeroizing::new(client.password.clone()),
+            new: Zeroizing::new(new.to_string()),
+        })
+    }
+
+    /// Changes the password and waits for the host's answer.
+    pub fn run(&self, launcher: Launcher) -> Result<(), String> {
+        if launcher.is_dry_run() {
+            launcher.log(&format!("would change the password of {} on {} over {}", self.username, self.host, self.method.label()));
+            return Ok(());
+        }
+        let (mut command, input) = match self.method {
+            Method::WinRm => {
+                let mut command = Command::new(if cfg!(windows) { "powershell" } else { "pwsh" });
+                command.args(["-NoProfile", "-NonInteractive", "-Command", "-"]);
+                (command, self.winrm_script())
+            }
+            Method::Ssh => {
+                let mut command = Command::new("ssh");
+                // Keys only: a password prompt would wait forever on a
+                // terminal nobody sees.
+                command.args(["-o", "BatchMode=yes", "-o"]).arg(format!("ConnectTimeout={}", TIMEOUT_SECONDS));
+                if let Some(port) = self.port {
+                    command.arg("-p").arg(port.to_string());
+                }
+                command.arg("-l").arg(&self.username).arg(&self.host).arg("passwd");
+                (command, Zeroizing::new(format!("{}\n{}\n{}\n", *self.current, *self.new, *self.new)))
+            }
+        };
+        without_window(&mut command);
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Could not start {:?}: {}", command.get_program(), e))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(input.as_bytes()).map_err(|e| format!("Could not pass the passwords on: {}", e))?;
+        }
+        let output = child.wait_with_output().map_err(|e| e.to_string())?;
+        if output.status.success() {
+            return Ok(());
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(match stderr.lines().map(str::trim).rfind(|line| !line.is_empty()) {
+            Some(line) => line.to_string(),
+            None => format!("{} exited with {}", self.method.label(), output.status),
+        })
+    }
+
+    /// A single line, since PowerShell reading a script from stdin runs it
+    /// line by line.
+    fn winrm_script(&self) -> Zeroizing<String> {
+        Zeroizing::new(format!(
+            "try {{ $credential = New-Object System.Management.Automation.PSCredential({user}, (ConvertTo-SecureString {current} -AsPlainText -Force)); \
+             $options = New-PSSessionOption -OpenTimeout {timeout}; \
+             Invoke-Command -ComputerName {host} -Port {port} -Credential $credential -SessionOption $options -ErrorAction Stop \
+             -ScriptBlock {{ param($name, $current, $new) ([adsi]\"WinNT://$env:COMPUTERNAME/$name,user\").ChangePassword($current, $new) }} \
+             -ArgumentList {user}, {current}, {new}; exit 0 }} \
+             catch {{ [Console]::Error.WriteLine($_.Exception.Message); exit 1 }}\n\n",
+            user = quote(&self.username),
+            current = quote(&self.current),
+            new = quote(&self.new),
+            timeout = TIMEOUT_SECONDS * 1000,
+            host = quote(&self.host),
+            port = WINRM_PORT,
+        ))
+    }
+}
+
+/// `text` as a single-quoted PowerShell string. PowerShell also ends such
+/// strings at typographic single quotes, so those are doubled as well.
+fn quote(text: &str) -> String {
+    let mut quoted = String::from("'");
+    for c in text.chars() {
+        if matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}') {
+            quoted.push(c);
+        }
+        quoted.push(c);
+    }
+    quoted.push('\'');
+    quoted
+}
+
+#[cfg(windows)]
+pub(crate) fn without_window(command: &mut Command) {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    command.creation_flags(CREATE_NO_WINDOW);
+}
+
+#[cfg(not(windows))]
+pub(crate) fn without_window(_command: &mut Command) {}
@@ -0,0 +1,68 @@
+//! Which local interface a connection leaves through. On a workstation with
+//! several networks, e.g. the office LAN and a customer VPN, a client can
+//! name the interface its traffic must use; the route the OS would pick is
+//! checked before connecting so nothing goes out over the wrong network.
+
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+
+/// A local network interface with one of its addresses.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LocalInterface {
+    pub name: String,
+    pub address: IpAddr,
+}
+
+/// The interfaces of this machine, loopback left out.
+pub fn interfaces() -> io::Result<Vec<LocalInterface>> {
+    Ok(if_addrs::get_if_addrs()?
+        .into_iter()
+        .filter(|interface| !interface.is_loopback())
+        .map(|interface| LocalInterface { address: interface.ip(), name: interface.name })
+        .collect())
+}
+
+/// The addresses `interface` stands for: itself if it is an address, else
+/// those of the interface with that name.
+pub fn addresses_of(interface: &str, local: &[LocalInterface]) -> Vec<IpAddr> {
+    let interface = interface.trim();
+    if let Ok(address) = interface.parse::<IpAddr>() {
+        return vec![address];
+    }
+    local.iter().filter(|local| local.name.eq_ignore_ascii_case(interface)).map(|local| local.address).collect()
+}
+
+/// The local address the OS would send from to reach `host`. Connecting a
+/// UDP socket only consults the routing table; nothing is sent.
+pub fn source_address(host: &str, port: u16) -> io::Result<IpAddr> {
+    let target = (host, port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{} does not resolve", host)))?;
+    let unspecified: SocketAddr = match target {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = UdpSocket::bind(unspecified)?;
+    socket.connect(target)?;
+    Ok(socket.local_addr()?.ip())
+}
+
+/// Checks that traffic to `host` would leave through `interface`. Returns
+/// the local address it would leave from otherwise.
+pub fn check(interface: &str, host: &str, port: u16) -> Result<(), String> {
+    let local = interfaces().map_err(|e| format!("The network interfaces could not be listed: {}", e))?;
+    let expected = addresses_of(interface, &local);
+    if expected.is_empty() {
+        return Err(format!("there is no interface {}", interface.trim()));
+    }
+    let source = source_address(host, port).map_err(|e| e.to_string())?;
+    if expected.contains(&source) {
+        return Ok(());
+    }
+    let name = local.iter().find(|local| local.address == source).map(|local| local.name.as_str());
+    Err(match name {
+        Some(name) => format!("{} is reached through {} ({})", host, name, source),
+        None => format!("{} is reached from {}", host, source),
+    })
+}
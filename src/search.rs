@@ -0,0 +1,192 @@
+use crate::client::Client;
+use crate::collation::fold_indexed;
+use crate::history::ConnectionRecord;
+use crate::template::is_secret_name;
+use eframe::egui;
+use eframe::egui::text::LayoutJob;
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Range;
+
+const SNIPPET_CONTEXT: usize = 40;
+
+/// One searchable field of one client, pre-folded to lowercase without
+/// accents.
+struct IndexEntry {
+    client_id: String,
+    field: &'static str,
+    text: String,
+    folded: Vec<(char, usize)>,
+}
+
+/// In-memory search index over every text field of the vault, the
+/// client's variables and its connection history, rebuilt whenever the
+/// client list is loaded or saved.
+#[derive(Default)]
+pub struct SearchIndex {
+    entries: Vec<IndexEntry>,
+}
+
+pub struct SearchHit {
+    pub field: &'static str,
+    pub text: String,
+}
+
+pub struct SearchResult {
+    pub client_id: String,
+    pub hits: Vec<SearchHit>,
+}
+
+impl SearchIndex {
+    /// Variables whose name marks them as secret, such as `{api_token}`,
+    /// are left out.
+    pub fn build(clients: &[Client], history: &[ConnectionRecord]) -> Self {
+        let mut records: HashMap<&str, Vec<&ConnectionRecord>> = HashMap::new();
+        for record in history {
+            records.entry(record.client_id.as_str()).or_default().push(record);
+        }
+        let mut entries = Vec::new();
+        for client in clients.iter().filter(|c| c.trashed_at.is_none()) {
+            let tags = client.tags.join(", ");
+            let farm = client.farm.as_ref().map(|farm| farm.name.clone()).unwrap_or_default();
+            let pinned_note = client.pinned_note.as_ref().map(|note| note.text.clone()).unwrap_or_default();
+            let fields = [
+                ("Name", &client.name),
+                ("IP", &client.ip),
+                ("Reference", &client.reference),
+                ("Group", &client.group),
+                ("Username", &client.username),
+                ("Tags", &tags),
+                ("Owner", &client.owner.name),
+                ("Owner email", &client.owner.email),
+                ("Owner phone", &client.owner.phone),
+                ("Notes", &client.notes),
+                ("Pinned note", &pinned_note),
+                ("Farm", &farm),
+            ];
+            let variables = client.variables.iter().filter(|(name, _)| !is_secret_name(name)).map(|(name, value)| ("Variable", format!("{} = {}", name, value)));
+            // Earlier names and addresses, and why launches failed, once each.
+            let mut seen = BTreeSet::new();
+            for record in records.get(client.id.as_str()).into_iter().flatten() {
+                if record.client_name != client.name || record.address != client.endpoint().0 {
+                    seen.insert(("History", format!("{} ({})", record.client_name, record.address)));
+                }
+                if let Some(error) = &record.error {
+                    seen.insert(("History", format!("Failed: {}", error)));
+                }
+            }
+            let fields = fields.into_iter().map(|(field, text)| (field, text.clone())).chain(variables).chain(seen);
+            for (field, text) in fields {
+                if !text.is_empty() {
+                    entries.push(IndexEntry {
+                        client_id: client.id.clone(),
+                        field,
+                        folded: fold_indexed(&text),
+                        text,
+                    });
+                }
+            }
+        }
+        Self { entries }
+    }
+
+    /// Clients with at least one field containing `query`, ignoring case and
+    /// accents, in vault order.
+    pub fn search(&self, query: &str) -> Vec<SearchResult> {
+        let query: Vec<char> = fold_indexed(query.trim()).into_iter().map(|(c, _)| c).collect();
+        let mut results: Vec<SearchResult> = Vec::new();
+        if query.is_empty() {
+            return results;
+        }
+        for entry in &self.entries {
+            if find_folded(&entry.folded, &query).is_empty() {
+                continue;
+            }
+            let hit = SearchHit {
+                field: entry.field,
+                text: entry.text.clone(),
+            };
+            match results.last_mut() {
+                Some(result) if result.client_id == entry.client_id => result.hits.push(hit),
+                _ => results.push(SearchResult {
+                    client_id: entry.client_id.clone(),
+                    hits: vec![hit],
+                }),
+            }
+        }
+        results
+    }
+}
+
+/// Matches of `needle` in the folded `haystack`, as ranges of the chars
+/// the folded text came from.
+fn find_folded(haystack: &[(char, usize)], needle: &[char]) -> Vec<Range<usize>> {
+    let mut matches = Vec::new();
+    if needle.is_empty() || needle.len() > haystack.len() {
+        return matches;
+    }
+    let mut start = 0;
+    while start + needle.len() <= haystack.len() {
+        let window = &haystack[start..start + needle.len()];
+        if window.iter().map(|(c, _)| c).eq(needle) {
+            matches.push(window[0].1..window[needle.len() - 1].1 + 1);
+            start += needle.len();
+        } else {
+            start += 1;
+        }
+    }
+    matches
+}
+
+/// Matches of `query` in `text` ignoring case and accents, as char ranges
+/// of `text`.
+pub fn find_matches(text: &str, query: &str) -> Vec<Range<usize>> {
+    let query: Vec<char> = fold_indexed(query.trim()).into_iter().map(|(c, _)| c).collect();
+    find_folded(&fold_indexed(text), &query)
+}
+
+/// Lays out `text` with every occurrence of `query` highlighted. Long text is
+/// cut down to a snippet around the first match.
+pub fn highlight(text: &str, query: &str, style: &egui::Style) -> LayoutJob {
+    highlight_in(text, query, style, egui::TextStyle::Body)
+}
+
+/// Like `highlight`, in the given text style.
+pub fn highlight_in(text: &str, query: &str, style: &egui::Style, text_style: egui::TextStyle) -> LayoutJob {
+    let chars: Vec<char> = text.chars().collect();
+    let matches = find_matches(text, query);
+    let (from, to) = match matches.first() {
+        Some(first) if chars.len() > SNIPPET_CONTEXT * 3 => (
+            first.start.saturating_sub(SNIPPET_CONTEXT),
+            (first.end + SNIPPET_CONTEXT).min(chars.len()),
+        ),
+        _ => (0, chars.len()),
+    };
+
+    let normal = egui::TextFormat {
+        font_id: text_style.resolve(style),
+        color: style.visuals.text_color(),
+        ..Default::default()
+    };
+    let highlighted = egui::TextFormat {
+        background: style.visuals.selection.bg_fill,
+        color: style.visuals.strong_text_color(),
+        ..normal.clone()
+    };
+
+    let mut job = LayoutJob::default();
+    let slice = |range: Range<usize>| chars[range].iter().collect::<String>();
+    if from > 0 {
+        job.append("…", 0.0, normal.clone());
+    }
+    let mut position = from;
+    for range in matches.into_iter().filter(|m| m.start >= from && m.end <= to) {
+        job.append(&slice(position..range.start), 0.0, normal.clone());
+        job.append(&slice(range.clone()), 0.0, highlighted.clone());
+        position = range.end;
+    }
+    job.append(&slice(position..to), 0.0, normal.clone());
+    if to < chars.len() {
+        job.append("…", 0.0, normal);
+    }
+    job
+}
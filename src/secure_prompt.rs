@@ -0,0 +1,72 @@
+use zeroize::Zeroizing;
+
+/// Whether this system has the Windows credential prompt.
+pub const AVAILABLE: bool = cfg!(windows);
+
+/// Asks for a secret in the Windows credential prompt on the secure
+/// desktop, where other programs can neither read the keystrokes nor draw
+/// over the dialog. Blocks until the prompt is closed; `None` when it was
+/// cancelled. The prompt's user name field is ignored.
+#[cfg(windows)]
+pub fn ask(caption: &str, message: &str) -> Result<Option<Zeroizing<String>>, String> {
+    use std::ptr::{null, null_mut};
+    use winapi::shared::winerror::{ERROR_CANCELLED, ERROR_SUCCESS};
+    use winapi::um::combaseapi::CoTaskMemFree;
+    use winapi::um::wincred::{
+        CredUIPromptForWindowsCredentialsW, CredUnPackAuthenticationBufferW, CREDUIWIN_GENERIC, CREDUIWIN_SECURE_PROMPT, CREDUI_INFOW,
+        CREDUI_MAX_PASSWORD_LENGTH, CREDUI_MAX_USERNAME_LENGTH,
+    };
+
+    let wide = |text: &str| text.encode_utf16().chain([0]).collect::<Vec<u16>>();
+    let (caption, message) = (wide(caption), wide(message));
+    let mut info = CREDUI_INFOW {
+        cbSize: std::mem::size_of::<CREDUI_INFOW>() as u32,
+        hwndParent: null_mut(),
+        pszMessageText: message.as_ptr(),
+        pszCaptionText: caption.as_ptr(),
+        hbmBanner: null_mut(),
+    };
+    let mut package = 0;
+    let mut buffer = null_mut();
+    let mut size = 0;
+    let mut save = 0;
+    let result = unsafe {
+        CredUIPromptForWindowsCredentialsW(&mut info, 0, &mut package, null(), 0, &mut buffer, &mut size, &mut save, CREDUIWIN_GENERIC | CREDUIWIN_SECURE_PROMPT)
+    };
+    match result {
+        ERROR_SUCCESS => {}
+        ERROR_CANCELLED => return Ok(None),
+        code => return Err(format!("The secure prompt could not be shown (error {}).", code)),
+    }
+    let mut user = [0u16; CREDUI_MAX_USERNAME_LENGTH as usize + 1];
+    let mut password = Zeroizing::new([0u16; CREDUI_MAX_PASSWORD_LENGTH as usize + 1]);
+    let (mut user_length, mut password_length) = (user.len() as u32, password.len() as u32);
+    let mut domain_length = 0;
+    let unpacked = unsafe {
+        let unpacked = CredUnPackAuthenticationBufferW(
+            0,
+            buffer,
+            size,
+            user.as_mut_ptr(),
+            &mut user_length,
+            null_mut(),
+            &mut domain_length,
+            password.as_mut_ptr(),
+            &mut password_length,
+        );
+        std::ptr::write_bytes(buffer as *mut u8, 0, size as usize);
+        CoTaskMemFree(buffer);
+        unpacked
+    };
+    if unpacked == 0 {
+        return Err("The secure prompt returned an unreadable answer.".to_string());
+    }
+    // The length includes the terminating zero.
+    let length = (password_length as usize).saturating_sub(1).min(password.len());
+    Ok(Some(Zeroizing::new(String::from_utf16_lossy(&password[..length]))))
+}
+
+#[cfg(not(windows))]
+pub fn ask(_caption: &str, _message: &str) -> Result<Option<Zeroizing<String>>, String> {
+    Err("The secure desktop prompt is only available on Windows".to_string())
+}
@@ -0,0 +1,161 @@
+//! Finds the windows of a running viewer process by its pid, so the sessions
+//! board can raise or close a particular session and launches can move it
+//! to its configured place.
+
+use crate::client::WindowPlacement;
+use std::thread;
+use std::time::{Duration, Instant};
+#[cfg(windows)]
+use winapi::shared::{minwindef::LPARAM, windef::HWND};
+
+/// How long a launched viewer gets to show its window.
+const PLACEMENT_TIMEOUT: Duration = Duration::from_secs(30);
+const PLACEMENT_POLL: Duration = Duration::from_millis(250);
+
+#[cfg(windows)]
+unsafe extern "system" fn collect_window(hwnd: HWND, lparam: LPARAM) -> winapi::shared::minwindef::BOOL {
+    use winapi::um::winuser::{GetWindow, GetWindowThreadProcessId, IsWindowVisible, GW_OWNER};
+    let (pid, found) = &mut *(lparam as *mut (u32, Vec<HWND>));
+    let mut owner_pid = 0;
+    GetWindowThreadProcessId(hwnd, &mut owner_pid);
+    if owner_pid == *pid && IsWindowVisible(hwnd) != 0 && GetWindow(hwnd, GW_OWNER).is_null() {
+        found.push(hwnd);
+    }
+    1
+}
+
+/// Visible top-level windows owned by `pid`.
+#[cfg(windows)]
+fn windows_of(pid: u32) -> Vec<HWND> {
+    let mut found: (u32, Vec<HWND>) = (pid, Vec::new());
+    unsafe {
+        winapi::um::winuser::EnumWindows(Some(collect_window), &mut found as *mut (u32, Vec<HWND>) as LPARAM);
+    }
+    found.1
+}
+
+/// The app's own window, for the taskbar badge and Windows Hello prompts.
+#[cfg(windows)]
+pub fn main_window() -> Option<HWND> {
+    windows_of(std::process::id()).first().copied()
+}
+
+#[cfg(windows)]
+pub fn focus(pid: u32) -> Result<(), String> {
+    use winapi::um::winuser::{IsIconic, SetForegroundWindow, ShowWindow, SW_RESTORE};
+    let hwnd = *windows_of(pid).first().ok_or("The session window was not found.")?;
+    unsafe {
+        if IsIconic(hwnd) != 0 {
+            ShowWindow(hwnd, SW_RESTORE);
+        }
+        SetForegroundWindow(hwnd);
+    }
+    Ok(())
+}
+
+#[cfg(not(windows))]
+pub fn focus(_pid: u32) -> Result<(), String> {
+    Err("Bringing session windows to the front is only supported on Windows.".to_string())
+}
+
+/// Asks the viewer to close, which disconnects the session without logging off.
+#[cfg(windows)]
+pub fn close(pid: u32) -> Result<(), String> {
+    use winapi::um::winuser::{PostMessageW, WM_CLOSE};
+    let windows = windows_of(pid);
+    if windows.is_empty() {
+        return Err("The session window was not found.".to_string());
+    }
+    for hwnd in windows {
+        unsafe {
+            PostMessageW(hwnd, WM_CLOSE, 0, 0);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(windows))]
+pub fn close(pid: u32) -> Result<(), String> {
+    let status = std::process::Command::new("kill").arg(pid.to_string()).status().map_err(|e| e.to_string())?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err("The session could not be closed.".to_string())
+    }
+}
+
+/// Moves the window of `pid` into `placement` as soon as it appears.
+pub fn place_when_shown(pid: u32, placement: WindowPlacement) {
+    if cfg!(not(windows)) {
+        return;
+    }
+    thread::spawn(move || {
+        let started = Instant::now();
+        while started.elapsed() < PLACEMENT_TIMEOUT {
+            if place(pid, &placement).is_ok() {
+                return;
+            }
+            thread::sleep(PLACEMENT_POLL);
+        }
+    });
+}
+
+/// Monitor rectangles as `(left, top)` origins, numbered left to right.
+#[cfg(windows)]
+fn monitor_origins() -> Vec<(i32, i32)> {
+    use winapi::shared::minwindef::BOOL;
+    use winapi::shared::windef::{HDC, HMONITOR, LPRECT};
+    use winapi::um::winuser::EnumDisplayMonitors;
+
+    unsafe extern "system" fn collect_monitor(_: HMONITOR, _: HDC, rect: LPRECT, lparam: LPARAM) -> BOOL {
+        let origins = &mut *(lparam as *mut Vec<(i32, i32)>);
+        origins.push(((*rect).left, (*rect).top));
+        1
+    }
+
+    let mut origins: Vec<(i32, i32)> = Vec::new();
+    unsafe {
+        EnumDisplayMonitors(
+            std::ptr::null_mut(),
+            std::ptr::null(),
+            Some(collect_monitor),
+            &mut origins as *mut Vec<(i32, i32)> as LPARAM,
+        );
+    }
+    origins.sort();
+    origins
+}
+
+/// Only resizable windows are placed, which skips the connection progress
+/// and credential dialogs shown before the session window.
+#[cfg(windows)]
+pub fn place(pid: u32, placement: &WindowPlacement) -> Result<(), String> {
+    use winapi::um::winuser::{GetWindowLongW, SetWindowPos, GWL_STYLE, SWP_NOACTIVATE, SWP_NOZORDER, WS_THICKFRAME};
+    let hwnd = windows_of(pid)
+        .into_iter()
+        .find(|&hwnd| unsafe { GetWindowLongW(hwnd, GWL_STYLE) } as u32 & WS_THICKFRAME != 0)
+        .ok_or("The session window was not found.")?;
+    let origins = monitor_origins();
+    let index = placement.monitor.saturating_sub(1) as usize;
+    let (left, top) = origins.get(index).or(origins.first()).copied().unwrap_or_default();
+    let moved = unsafe {
+        SetWindowPos(
+            hwnd,
+            std::ptr::null_mut(),
+            left + placement.x,
+            top + placement.y,
+            placement.width,
+            placement.height,
+            SWP_NOZORDER | SWP_NOACTIVATE,
+        )
+    };
+    if moved == 0 {
+        return Err("The session window could not be moved.".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(not(windows))]
+pub fn place(_pid: u32, _placement: &WindowPlacement) -> Result<(), String> {
+    Err("Placing session windows is only supported on Windows.".to_string())
+}
@@ -0,0 +1,107 @@
+use crate::client::{Client, SessionLimit};
+use crate::events::{AppEvent, EventBus};
+use crate::launcher::Process;
+use chrono::{DateTime, Utc};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Clone)]
+pub struct ActiveSession {
+    pub pid: u32,
+    pub client_id: String,
+    pub client_name: String,
+    /// Host the session connects to, without the port.
+    pub address: String,
+    pub started: Instant,
+    /// Wall-clock start, which identifies the session in the history.
+    pub started_at: DateTime<Utc>,
+    /// Started by the dry-run launcher; there is no window or process.
+    pub simulated: bool,
+    pub limit: Option<SessionLimit>,
+    /// Set once the time limit has been reported.
+    pub limit_reached: bool,
+}
+
+impl ActiveSession {
+    /// Time left before the session's limit, zero once it has passed.
+    pub fn remaining(&self) -> Option<Duration> {
+        let limit = Duration::from_secs(self.limit?.minutes as u64 * 60);
+        Some(limit.saturating_sub(self.started.elapsed()))
+    }
+}
+
+/// Remote sessions launched by the app that are still running. Each session
+/// is dropped by its watcher thread once the viewer process exits.
+#[derive(Clone)]
+pub struct SessionTracker {
+    sessions: Arc<Mutex<Vec<ActiveSession>>>,
+    events: EventBus,
+}
+
+impl SessionTracker {
+    pub fn new(events: EventBus) -> Self {
+        Self {
+            sessions: Arc::default(),
+            events,
+        }
+    }
+
+    /// Watches `process` until it exits, then runs `on_exit` on the watcher thread.
+    pub fn track(&self, client: &Client, mut process: Process, on_exit: impl FnOnce() + Send + 'static) {
+        let pid = process.id();
+        let session = ActiveSession {
+            pid,
+            client_id: client.id.clone(),
+            client_name: client.name.clone(),
+            address: client.endpoint().0.to_string(),
+            started: Instant::now(),
+            started_at: Utc::now(),
+            simulated: process.is_simulated(),
+            limit: client.session_limit,
+            limit_reached: false,
+        };
+        self.sessions.lock().unwrap().push(session.clone());
+        self.events.publish(AppEvent::ConnectionStarted(session.clone()));
+        let sessions = Arc::clone(&self.sessions);
+        let events = self.events.clone();
+        thread::spawn(move || {
+            process.wait();
+            on_exit();
+            sessions.lock().unwrap().retain(|session| session.pid != pid);
+            events.publish(AppEvent::ConnectionEnded(session));
+        });
+    }
+
+    pub fn count(&self) -> usize {
+        self.sessions.lock().unwrap().len()
+    }
+
+    pub fn active(&self) -> Vec<ActiveSession> {
+        self.sessions.lock().unwrap().clone()
+    }
+
+    /// Sessions whose time limit has just run out. Each is returned once.
+    pub fn take_over_limit(&self) -> Vec<ActiveSession> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let mut over = Vec::new();
+        for session in sessions.iter_mut() {
+            if !session.limit_reached && session.remaining() == Some(Duration::ZERO) {
+                session.limit_reached = true;
+                over.push(session.clone());
+            }
+        }
+        over
+    }
+
+    /// The shortest time until a running session reaches its limit.
+    pub fn next_limit(&self) -> Option<Duration> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|session| !session.limit_reached)
+            .filter_map(ActiveSession::remaining)
+            .min()
+    }
+}
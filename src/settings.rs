@@ -0,0 +1,382 @@
+use crate::client::{Client, Protocol};
+use crate::inventory::InventoryFormat;
+use crate::monitoring::MonitoringSystem;
+use crate::shortcuts::Shortcut;
+use crate::storage;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+
+/// A named link such as `https://cmdb/ci/{reference}` shown next to clients
+/// that have a reference set.
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct UrlTemplate {
+    pub name: String,
+    pub url: String,
+}
+
+/// How long the derived vault key is kept once the vault has been unlocked.
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+pub enum KeyCaching {
+    /// Locking wipes the key; every unlock and launch needs the master key.
+    #[default]
+    ForgetOnLock,
+    /// Locking hides the vault but keeps the key in memory until the app
+    /// exits; it is never stored, so every launch needs the master key.
+    RequireEveryLaunch,
+    /// Locking hides the vault but keeps the key, which is also stored
+    /// DPAPI-wrapped per vault until the next reboot, so new launches in the
+    /// same OS session open without the master key.
+    RememberForSession,
+}
+
+/// Light or dark look of the window.
+#[derive(Serialize, Deserialize, Default, Clone, Copy, Debug, PartialEq)]
+pub enum Theme {
+    /// Follows the operating system, dark where it cannot be told.
+    #[default]
+    System,
+    Dark,
+    Light,
+}
+
+/// Display and redirection options written into the .rdp file of every RDP
+/// connection.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct RdpOptions {
+    /// Full screen, unless the client's window is placed after launch.
+    pub full_screen: bool,
+    pub all_monitors: bool,
+    /// Resize the remote desktop along with the window.
+    pub dynamic_resolution: bool,
+    /// Scale the remote desktop to the window instead of showing scroll bars.
+    pub smart_sizing: bool,
+    /// Used by clients without redirection settings of their own.
+    pub redirection: Redirection,
+}
+
+impl Default for RdpOptions {
+    fn default() -> Self {
+        Self {
+            full_screen: true,
+            all_monitors: false,
+            dynamic_resolution: true,
+            smart_sizing: true,
+            redirection: Redirection::default(),
+        }
+    }
+}
+
+/// Port each protocol connects to when a client sets none, for networks
+/// that moved a service off its standard port everywhere.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct DefaultPorts {
+    pub rdp: u16,
+    pub ssh: u16,
+    pub vnc: u16,
+}
+
+impl Default for DefaultPorts {
+    fn default() -> Self {
+        Self { rdp: Protocol::Rdp.default_port(), ssh: Protocol::Ssh.default_port(), vnc: Protocol::Vnc.default_port() }
+    }
+}
+
+impl DefaultPorts {
+    pub fn get(&self, protocol: Protocol) -> u16 {
+        match protocol {
+            Protocol::Rdp => self.rdp,
+            Protocol::Ssh => self.ssh,
+            Protocol::Vnc => self.vnc,
+        }
+    }
+
+    pub fn get_mut(&mut self, protocol: Protocol) -> &mut u16 {
+        match protocol {
+            Protocol::Rdp => &mut self.rdp,
+            Protocol::Ssh => &mut self.ssh,
+            Protocol::Vnc => &mut self.vnc,
+        }
+    }
+}
+
+/// Local devices and data a remote session can reach.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct Redirection {
+    /// Every local drive, including ones plugged in later.
+    pub drives: bool,
+    pub clipboard: bool,
+    pub printers: bool,
+    pub smart_cards: bool,
+    /// Play the remote computer's sound here.
+    pub audio: bool,
+}
+
+impl Redirection {
+    /// Nothing reaches the remote computer.
+    pub const NONE: Redirection = Redirection { drives: false, clipboard: false, printers: false, smart_cards: false, audio: false };
+}
+
+impl Default for Redirection {
+    fn default() -> Self {
+        Self {
+            drives: false,
+            clipboard: true,
+            printers: true,
+            smart_cards: true,
+            audio: true,
+        }
+    }
+}
+
+/// Actions that ask before they go ahead, so a team can trade speed for
+/// safety one action at a time.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Confirmations {
+    /// Ask before a client is removed.
+    pub remove_client: bool,
+    /// Ask before an export is written without encryption.
+    pub plaintext_export: bool,
+    /// Ask before a stored password is shown.
+    pub reveal_password: bool,
+    /// Ask before connecting to a client with one of these tags.
+    pub connect_tags: Vec<String>,
+}
+
+impl Default for Confirmations {
+    fn default() -> Self {
+        Self {
+            remove_client: true,
+            plaintext_export: false,
+            reveal_password: false,
+            connect_tags: Vec::new(),
+        }
+    }
+}
+
+impl Confirmations {
+    /// The tag of `client` that makes connecting to it ask first.
+    pub fn connect_tag<'a>(&self, client: &'a Client) -> Option<&'a str> {
+        client.tags.iter().map(String::as_str).find(|tag| self.connect_tags.iter().any(|asked| asked.eq_ignore_ascii_case(tag)))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Settings {
+    /// Vault opened at startup; empty uses the one in the config directory.
+    #[serde(default)]
+    pub vault_path: String,
+    /// Automatic backups kept of the vault, at most one an hour; 0 keeps none.
+    #[serde(default = "default_vault_backups")]
+    pub vault_backups: u32,
+    /// Folder polled for .rdp/.csv/.rdg files to import. Empty disables it.
+    #[serde(default)]
+    pub watch_folder: String,
+    #[serde(default)]
+    pub url_templates: Vec<UrlTemplate>,
+    /// NetBox address, e.g. `https://netbox.example.com`.
+    #[serde(default)]
+    pub netbox_url: String,
+    /// NetBox API token, encrypted with the vault key and base64 encoded.
+    #[serde(default)]
+    pub netbox_token: String,
+    /// Monitoring system whose host status is shown next to clients.
+    #[serde(default)]
+    pub monitoring_system: MonitoringSystem,
+    #[serde(default)]
+    pub monitoring_url: String,
+    /// Monitoring API token, encrypted with the vault key and base64 encoded.
+    #[serde(default)]
+    pub monitoring_token: String,
+    #[serde(default = "default_monitoring_refresh_minutes")]
+    pub monitoring_refresh_minutes: u32,
+    /// Seconds between TCP reachability checks of every client; `None`
+    /// turns them off.
+    #[serde(default = "default_reachability_check_seconds")]
+    pub reachability_check_seconds: Option<u32>,
+    /// Guess the operating system of hosts that answer the reachability
+    /// check and tag clients without an `os:` tag with it.
+    #[serde(default)]
+    pub fingerprint_hosts: bool,
+    /// Announce open sessions to other instances on the LAN and show theirs.
+    #[serde(default)]
+    pub lan_presence: bool,
+    /// Name teammates see; empty uses the OS user name.
+    #[serde(default)]
+    pub presence_name: String,
+    #[serde(default)]
+    pub inventory_format: InventoryFormat,
+    /// Inventory fields exported under another name, or left out when the
+    /// name is empty.
+    #[serde(default)]
+    pub inventory_field_names: BTreeMap<String, String>,
+    /// Days after expiry before a client is moved to the Trash; `None` keeps
+    /// expired clients in the list.
+    #[serde(default)]
+    pub trash_expired_after_days: Option<u32>,
+    #[serde(default)]
+    pub key_caching: KeyCaching,
+    /// Forget the key as well when the workstation locks or resumes from
+    /// sleep, whatever `key_caching` says.
+    #[serde(default = "default_true")]
+    pub forget_key_on_os_lock: bool,
+    /// Lock the vault after this many idle minutes.
+    #[serde(default)]
+    pub auto_lock_minutes: Option<u32>,
+    /// Copy the password to the clipboard when connecting. When off, the
+    /// remote desktop client asks for it instead.
+    #[serde(default = "default_true")]
+    pub copy_password_on_connect: bool,
+    #[serde(default = "default_true")]
+    pub warn_clipboard_history: bool,
+    /// Ask for a handoff note pinned to the client when a session ends.
+    #[serde(default = "default_true")]
+    pub ask_for_pinned_note: bool,
+    /// Seconds before a copied password is cleared from the clipboard;
+    /// `None` leaves it there.
+    #[serde(default = "default_clipboard_clear_seconds")]
+    pub clear_clipboard_after_seconds: Option<u32>,
+    /// Show passwords in the editor only while the eye button is held down
+    /// instead of toggling them.
+    #[serde(default)]
+    pub hold_to_reveal: bool,
+    /// Enter the master key, and confirm it before a password is shown, in
+    /// the Windows credential prompt on the secure desktop.
+    #[serde(default)]
+    pub secure_desktop_prompt: bool,
+    #[serde(default)]
+    pub confirmations: Confirmations,
+    /// Weakest master key accepted for a new vault or a key change, in
+    /// estimated bits. A vault's own policy can raise it further.
+    #[serde(default = "default_min_master_key_bits")]
+    pub min_master_key_bits: u32,
+    /// Show the on-screen keypad on the unlock screen.
+    #[serde(default)]
+    pub onscreen_keypad: bool,
+    /// OpenSSH `ssh` or PuTTY, as a command name or full path.
+    #[serde(default = "default_ssh_client")]
+    pub ssh_client: String,
+    /// VNC viewer program, or a command line with placeholders.
+    #[serde(default = "default_vnc_viewer")]
+    pub vnc_viewer: String,
+    /// Log and simulate connections instead of starting any client.
+    #[serde(default)]
+    pub dry_run: bool,
+    #[serde(default)]
+    pub default_ports: DefaultPorts,
+    #[serde(default)]
+    pub rdp_options: RdpOptions,
+    /// Sign in to servers with Network Level Authentication before mstsc
+    /// starts, so a rejected stored account is reported up front. Off
+    /// unless turned on, as it sends the account to the server.
+    #[serde(default)]
+    pub check_rdp_sign_in: bool,
+    #[serde(default)]
+    pub theme: Theme,
+    /// Color of selections and links; `None` keeps the theme's own.
+    #[serde(default)]
+    pub accent_color: Option<[u8; 3]>,
+    /// Shortcuts bound to another key than their default; an empty binding
+    /// turns a shortcut off.
+    #[serde(default)]
+    pub shortcuts: BTreeMap<Shortcut, String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_clipboard_clear_seconds() -> Option<u32> {
+    Some(30)
+}
+
+fn default_vault_backups() -> u32 {
+    10
+}
+
+fn default_min_master_key_bits() -> u32 {
+    50
+}
+
+fn default_monitoring_refresh_minutes() -> u32 {
+    2
+}
+
+fn default_reachability_check_seconds() -> Option<u32> {
+    Some(60)
+}
+
+fn default_ssh_client() -> String {
+    "ssh".to_string()
+}
+
+fn default_vnc_viewer() -> String {
+    "vncviewer".to_string()
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            vault_path: String::new(),
+            vault_backups: default_vault_backups(),
+            watch_folder: String::new(),
+            url_templates: Vec::new(),
+            netbox_url: String::new(),
+            netbox_token: String::new(),
+            monitoring_system: MonitoringSystem::default(),
+            monitoring_url: String::new(),
+            monitoring_token: String::new(),
+            monitoring_refresh_minutes: default_monitoring_refresh_minutes(),
+            reachability_check_seconds: default_reachability_check_seconds(),
+            fingerprint_hosts: false,
+            lan_presence: false,
+            presence_name: String::new(),
+            inventory_format: InventoryFormat::default(),
+            inventory_field_names: BTreeMap::new(),
+            trash_expired_after_days: None,
+            key_caching: KeyCaching::default(),
+            forget_key_on_os_lock: true,
+            auto_lock_minutes: None,
+            copy_password_on_connect: true,
+            warn_clipboard_history: true,
+            ask_for_pinned_note: true,
+            clear_clipboard_after_seconds: default_clipboard_clear_seconds(),
+            hold_to_reveal: false,
+            secure_desktop_prompt: false,
+            confirmations: Confirmations::default(),
+            min_master_key_bits: default_min_master_key_bits(),
+            onscreen_keypad: false,
+            ssh_client: default_ssh_client(),
+            vnc_viewer: default_vnc_viewer(),
+            dry_run: false,
+            default_ports: DefaultPorts::default(),
+            rdp_options: RdpOptions::default(),
+            check_rdp_sign_in: false,
+            theme: Theme::System,
+            accent_color: None,
+            shortcuts: BTreeMap::new(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn load() -> Self {
+        fs::read(storage::settings_path())
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let data = serde_json::to_vec_pretty(self).map_err(|e| e.to_string())?;
+        let path = storage::settings_path();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        fs::write(path, data).map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,142 @@
+//! Keyboard shortcuts of the main window and how they are written in the
+//! settings, e.g. `Ctrl+Shift+N`. `Ctrl` stands for Cmd on macOS.
+
+use eframe::egui::{Key, KeyboardShortcut, ModifierNames, Modifiers};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// What a shortcut does.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Shortcut {
+    NewClient,
+    Find,
+    Connect,
+    Edit,
+    Remove,
+    Previous,
+    Next,
+    Lock,
+    Help,
+}
+
+impl Shortcut {
+    pub const ALL: [Shortcut; 9] = [
+        Shortcut::NewClient,
+        Shortcut::Find,
+        Shortcut::Connect,
+        Shortcut::Edit,
+        Shortcut::Remove,
+        Shortcut::Previous,
+        Shortcut::Next,
+        Shortcut::Lock,
+        Shortcut::Help,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Shortcut::NewClient => "New client",
+            Shortcut::Find => "Focus the filter",
+            Shortcut::Connect => "Connect to the selection",
+            Shortcut::Edit => "Edit the selection",
+            Shortcut::Remove => "Remove the selection",
+            Shortcut::Previous => "Select the previous client",
+            Shortcut::Next => "Select the next client",
+            Shortcut::Lock => "Lock",
+            Shortcut::Help => "Open the help",
+        }
+    }
+
+    pub fn default_binding(self) -> &'static str {
+        match self {
+            Shortcut::NewClient => "Ctrl+N",
+            Shortcut::Find => "Ctrl+F",
+            Shortcut::Connect => "Enter",
+            Shortcut::Edit => "Ctrl+E",
+            Shortcut::Remove => "Delete",
+            Shortcut::Previous => "Up",
+            Shortcut::Next => "Down",
+            Shortcut::Lock => "Ctrl+L",
+            Shortcut::Help => "F1",
+        }
+    }
+
+    /// Whether it needs the client list rather than a dialog on screen.
+    pub fn acts_on_list(self) -> bool {
+        matches!(self, Shortcut::Connect | Shortcut::Remove | Shortcut::Previous | Shortcut::Next)
+    }
+
+    /// Whether it still works while a text field has the keyboard: only
+    /// those with Ctrl or Alt or on a function key, which typing does not
+    /// produce.
+    pub fn works_while_typing(shortcut: &KeyboardShortcut) -> bool {
+        let name = shortcut.logical_key.name();
+        let function_key = name.len() > 1 && name.starts_with('F') && name[1..].chars().all(|c| c.is_ascii_digit());
+        shortcut.modifiers.command || shortcut.modifiers.ctrl || shortcut.modifiers.alt || shortcut.modifiers.mac_cmd || function_key
+    }
+}
+
+/// Reads `Ctrl+Shift+N`, `Alt+Up` or `Delete`. Keys are named as egui names
+/// them; spaces around the `+` and the case of modifiers do not matter.
+pub fn parse(text: &str) -> Result<KeyboardShortcut, String> {
+    let parts: Vec<&str> = text.split('+').map(str::trim).collect();
+    let (key, modifiers) = parts.split_last().filter(|(key, _)| !key.is_empty()).ok_or_else(|| format!("{:?} names no key", text))?;
+    let mut shortcut = KeyboardShortcut::new(Modifiers::NONE, Key::from_name(key).ok_or_else(|| format!("{:?} is not a key", key))?);
+    for modifier in modifiers {
+        shortcut.modifiers = shortcut.modifiers
+            | match modifier.to_lowercase().as_str() {
+                "ctrl" | "cmd" | "command" => Modifiers::COMMAND,
+                "alt" | "option" => Modifiers::ALT,
+                "shift" => Modifiers::SHIFT,
+                _ => return Err(format!("{:?} is not Ctrl, Alt or Shift", modifier)),
+            };
+    }
+    Ok(shortcut)
+}
+
+/// `shortcut` the way `parse` reads it.
+pub fn format(shortcut: &KeyboardShortcut) -> String {
+    shortcut.format(&ModifierNames::NAMES, false)
+}
+
+/// The shortcut of each command: its binding in `custom`, else its
+/// default. An empty binding turns the command's shortcut off.
+pub fn bindings(custom: &BTreeMap<Shortcut, String>) -> Vec<(Shortcut, KeyboardShortcut)> {
+    let mut bindings: Vec<(Shortcut, KeyboardShortcut)> = Shortcut::ALL
+        .into_iter()
+        .filter_map(|command| {
+            let text = custom.get(&command).map(String::as_str).unwrap_or(command.default_binding());
+            parse(text).ok().map(|shortcut| (command, shortcut))
+        })
+        .collect();
+    // egui ignores extra Shift and Alt when matching, so Ctrl+Shift+N has
+    // to be tried before Ctrl+N.
+    bindings.sort_by_key(|(_, shortcut)| std::cmp::Reverse(modifier_count(&shortcut.modifiers)));
+    bindings
+}
+
+fn modifier_count(modifiers: &Modifiers) -> usize {
+    [modifiers.command || modifiers.ctrl, modifiers.alt, modifiers.shift].into_iter().filter(|&on| on).count()
+}
+
+/// Checks bindings typed into the settings: each must parse, and no two
+/// commands may share one. Returns them in their canonical form, leaving
+/// out those equal to the default.
+pub fn validate(inputs: &[(Shortcut, String)]) -> Result<BTreeMap<Shortcut, String>, String> {
+    let mut custom = BTreeMap::new();
+    let mut taken: Vec<(Shortcut, KeyboardShortcut)> = Vec::new();
+    for (command, text) in inputs {
+        if text.trim().is_empty() {
+            custom.insert(*command, String::new());
+            continue;
+        }
+        let shortcut = parse(text).map_err(|e| format!("{}: {}", command.label(), e))?;
+        if let Some((other, _)) = taken.iter().find(|(_, other)| *other == shortcut) {
+            return Err(format!("{} is used for both \"{}\" and \"{}\"", format(&shortcut), other.label(), command.label()));
+        }
+        taken.push((*command, shortcut));
+        if shortcut != parse(command.default_binding()).expect("default bindings parse") {
+            custom.insert(*command, format(&shortcut));
+        }
+    }
+    Ok(custom)
+}
@@ -0,0 +1,63 @@
+//! Launches the configured SSH client. PuTTY and OpenSSH spell the port
+//! option differently, so the client is recognised by its file name. A
+//! setting with placeholders is a full command line instead. OpenSSH is
+//! bound to the client's interface: `-b` for an address, `-B` for a name.
+
+use crate::client::Client;
+use crate::template::templated_command;
+use std::net::IpAddr;
+use std::path::Path;
+use std::process::Command;
+
+fn is_putty(client_path: &str) -> bool {
+    Path::new(client_path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_ascii_lowercase().contains("putty"))
+        .unwrap_or(false)
+}
+
+/// The command connecting to `client` with its stored host, port and user.
+pub fn command(setting: &str, client: &Client) -> Result<Command, String> {
+    if setting.contains('{') {
+        let mut command = templated_command(setting, client)?;
+        let putty = is_putty(&command.get_program().to_string_lossy());
+        with_console(&mut command, putty);
+        return Ok(command);
+    }
+    let (host, port) = client.endpoint();
+    let mut command = Command::new(setting);
+    let putty = is_putty(setting);
+    if putty {
+        command.arg("-ssh");
+    }
+    if let Some(port) = port {
+        command.arg(if putty { "-P" } else { "-p" }).arg(port.to_string());
+    }
+    if !client.username.is_empty() {
+        command.arg("-l").arg(&client.username);
+    }
+    // PuTTY cannot be bound from the command line; the route check before
+    // connecting still applies.
+    let interface = client.interface.trim();
+    if !putty && !interface.is_empty() {
+        let option = if interface.parse::<IpAddr>().is_ok() { "-b" } else { "-B" };
+        command.arg(option).arg(interface);
+    }
+    command.arg(host);
+    with_console(&mut command, putty);
+    Ok(command)
+}
+
+/// OpenSSH is a console program; without its own console it would have
+/// nowhere to run, since the app itself has none.
+#[cfg(windows)]
+fn with_console(command: &mut Command, putty: bool) {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NEW_CONSOLE: u32 = 0x0000_0010;
+    if !putty {
+        command.creation_flags(CREATE_NEW_CONSOLE);
+    }
+}
+
+#[cfg(not(windows))]
+fn with_console(_command: &mut Command, _putty: bool) {}
@@ -0,0 +1,152 @@
+//! The sign-in of the check in `nla` done by Windows itself, through SSPI
+//! and its NTLM package, so the account is handled by the same code mstsc
+//! relies on rather than by `ntlm`. Offers the same `Handshake` as `ntlm`.
+
+use std::mem::zeroed;
+use std::ptr::null_mut;
+use std::slice;
+use winapi::shared::rpcdce::SEC_WINNT_AUTH_IDENTITY_W;
+use winapi::shared::sspi::{
+    AcquireCredentialsHandleW, DeleteSecurityContext, EncryptMessage, FreeContextBuffer, FreeCredentialsHandle, InitializeSecurityContextW, QueryContextAttributesW, CredHandle,
+    CtxtHandle, SecBuffer, SecBufferDesc, SecPkgContext_Sizes, ISC_REQ_ALLOCATE_MEMORY, ISC_REQ_CONFIDENTIALITY, ISC_REQ_INTEGRITY, SECBUFFER_DATA, SECBUFFER_TOKEN,
+    SECBUFFER_VERSION, SECPKG_ATTR_SIZES, SECPKG_CRED_OUTBOUND, SECURITY_NATIVE_DREP,
+};
+use winapi::shared::winerror::{SEC_E_OK, SEC_I_CONTINUE_NEEDED};
+use zeroize::Zeroizing;
+
+/// SEC_WINNT_AUTH_IDENTITY_UNICODE: the identity's strings are UTF-16.
+const IDENTITY_UNICODE: u32 = 2;
+/// Sealing, which CredSSP binds the public key with, in tokens SSPI
+/// allocates.
+const REQUIREMENTS: u32 = ISC_REQ_ALLOCATE_MEMORY | ISC_REQ_CONFIDENTIALITY | ISC_REQ_INTEGRITY;
+
+/// One sign-in: the credentials handle of the account and the security
+/// context once the first token is out.
+pub struct Handshake {
+    credentials: CredHandle,
+    context: Option<CtxtHandle>,
+    /// The service principal, `TERMSRV/host`, as mstsc names it.
+    target: Vec<u16>,
+}
+
+impl Handshake {
+    /// Starts a sign-in to `host` as `user` in `domain`, returning the
+    /// NEGOTIATE message to send.
+    pub fn start(host: &str, user: &str, domain: &str, password: &str) -> Result<(Self, Vec<u8>), String> {
+        let wide = |text: &str| Zeroizing::new(text.encode_utf16().collect::<Vec<u16>>());
+        let (mut user, mut domain, mut password) = (wide(user), wide(domain), wide(password));
+        let pointer = |text: &mut Vec<u16>| if text.is_empty() { null_mut() } else { text.as_mut_ptr() };
+        let mut identity = SEC_WINNT_AUTH_IDENTITY_W {
+            User: pointer(&mut user),
+            UserLength: user.len() as u32,
+            Domain: pointer(&mut domain),
+            DomainLength: domain.len() as u32,
+            Password: pointer(&mut password),
+            PasswordLength: password.len() as u32,
+            Flags: IDENTITY_UNICODE,
+        };
+        let mut package = "NTLM\0".encode_utf16().collect::<Vec<u16>>();
+        // SSPI copies the identity, so the strings may go once this returns.
+        let credentials = unsafe {
+            let mut credentials: CredHandle = zeroed();
+            let status = AcquireCredentialsHandleW(null_mut(), package.as_mut_ptr(), SECPKG_CRED_OUTBOUND, null_mut(), &mut identity as *mut _ as *mut _, None, null_mut(), &mut credentials, &mut zeroed());
+            if status != SEC_E_OK {
+                return Err(format!("Windows could not take up the account for the sign-in (error {:#x}).", status));
+            }
+            credentials
+        };
+        let mut handshake = Self { credentials, context: None, target: format!("TERMSRV/{}\0", host).encode_utf16().collect() };
+        let negotiate = handshake.step(None)?;
+        Ok((handshake, negotiate))
+    }
+
+    /// The AUTHENTICATE message answering the server's `challenge`.
+    pub fn answer(&mut self, challenge: &[u8]) -> Result<Vec<u8>, String> {
+        self.step(Some(challenge))
+    }
+
+    /// `data` sealed as the first message to the server: the signature
+    /// followed by the encrypted data.
+    pub fn seal(&mut self, data: &[u8]) -> Result<Vec<u8>, String> {
+        let context = self.context.as_mut().ok_or("The NTLM challenge has not been answered yet.")?;
+        unsafe {
+            let mut sizes: SecPkgContext_Sizes = zeroed();
+            let status = QueryContextAttributesW(context, SECPKG_ATTR_SIZES, &mut sizes as *mut _ as *mut _);
+            if status != SEC_E_OK {
+                return Err(format!("Windows could not seal the sign-in (error {:#x}).", status));
+            }
+            let mut signature = vec![0u8; sizes.cbSecurityTrailer as usize];
+            let mut sealed = data.to_vec();
+            let mut buffers = [
+                SecBuffer { cbBuffer: signature.len() as u32, BufferType: SECBUFFER_TOKEN, pvBuffer: signature.as_mut_ptr() as *mut _ },
+                SecBuffer { cbBuffer: sealed.len() as u32, BufferType: SECBUFFER_DATA, pvBuffer: sealed.as_mut_ptr() as *mut _ },
+            ];
+            let mut message = SecBufferDesc { ulVersion: SECBUFFER_VERSION, cBuffers: buffers.len() as u32, pBuffers: buffers.as_mut_ptr() };
+            let status = EncryptMessage(context, 0, &mut message, 0);
+            if status != SEC_E_OK {
+                return Err(format!("Windows could not seal the sign-in (error {:#x}).", status));
+            }
+            signature.truncate(buffers[0].cbBuffer as usize);
+            signature.extend(sealed);
+            Ok(signature)
+        }
+    }
+
+    /// Feeds the server's `input`, none at first, to
+    /// InitializeSecurityContext and returns the token it makes.
+    fn step(&mut self, input: Option<&[u8]>) -> Result<Vec<u8>, String> {
+        let mut input = input.map(<[u8]>::to_vec);
+        unsafe {
+            let mut in_buffer = SecBuffer { cbBuffer: 0, BufferType: SECBUFFER_TOKEN, pvBuffer: null_mut() };
+            if let Some(input) = input.as_mut() {
+                in_buffer.cbBuffer = input.len() as u32;
+                in_buffer.pvBuffer = input.as_mut_ptr() as *mut _;
+            }
+            let mut in_description = SecBufferDesc { ulVersion: SECBUFFER_VERSION, cBuffers: 1, pBuffers: &mut in_buffer };
+            let mut out_buffer = SecBuffer { cbBuffer: 0, BufferType: SECBUFFER_TOKEN, pvBuffer: null_mut() };
+            let mut out_description = SecBufferDesc { ulVersion: SECBUFFER_VERSION, cBuffers: 1, pBuffers: &mut out_buffer };
+            let mut context = self.context.unwrap_or_else(|| zeroed());
+            let previous = if self.context.is_some() { &mut context as *mut _ } else { null_mut() };
+            let status = InitializeSecurityContextW(
+                &mut self.credentials,
+                previous,
+                self.target.as_mut_ptr(),
+                REQUIREMENTS,
+                0,
+                SECURITY_NATIVE_DREP,
+                if input.is_some() { &mut in_description } else { null_mut() },
+                0,
+                &mut context,
+                &mut out_description,
+                &mut 0,
+                &mut zeroed(),
+            );
+            if status == SEC_E_OK || status == SEC_I_CONTINUE_NEEDED {
+                self.context = Some(context);
+            }
+            let token = if out_buffer.pvBuffer.is_null() {
+                Vec::new()
+            } else {
+                let token = slice::from_raw_parts(out_buffer.pvBuffer as *const u8, out_buffer.cbBuffer as usize).to_vec();
+                FreeContextBuffer(out_buffer.pvBuffer);
+                token
+            };
+            match status {
+                SEC_E_OK | SEC_I_CONTINUE_NEEDED => Ok(token),
+                status if input.is_some() => Err(format!("The server's NTLM challenge was not accepted by Windows (error {:#x}).", status)),
+                status => Err(format!("Windows could not start the NTLM sign-in (error {:#x}).", status)),
+            }
+        }
+    }
+}
+
+impl Drop for Handshake {
+    fn drop(&mut self) {
+        unsafe {
+            if let Some(context) = self.context.as_mut() {
+                DeleteSecurityContext(context);
+            }
+            FreeCredentialsHandle(&mut self.credentials);
+        }
+    }
+}
@@ -0,0 +1,197 @@
+//! Where the app keeps its files: a per-user config directory,
+//! `%APPDATA%\RemoteDesktopManager` on Windows and
+//! `$XDG_CONFIG_HOME/remote_desktop_manager` (usually `~/.config/...`)
+//! elsewhere. Older versions wrote everything to the working directory;
+//! those files are moved over on the first start.
+
+use crate::repository::decoy_path;
+use chrono::{Local, NaiveDateTime, TimeZone};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+pub const VAULT_FILE_NAME: &str = "clients.json";
+pub const SETTINGS_FILE_NAME: &str = "settings.json";
+pub const LAYOUTS_FILE_NAME: &str = "layouts.json";
+pub const WRAPPED_KEYS_FILE_NAME: &str = "wrapped_keys.json";
+pub const SERVER_CERTIFICATES_FILE_NAME: &str = "server_certificates.json";
+/// Saves closer together than this share one backup.
+const BACKUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const BACKUP_STAMP: &str = "%Y%m%d-%H%M%S";
+
+#[cfg(windows)]
+pub fn config_dir() -> PathBuf {
+    match std::env::var_os("APPDATA") {
+        Some(app_data) => PathBuf::from(app_data).join("RemoteDesktopManager"),
+        None => PathBuf::from("."),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn config_dir() -> PathBuf {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+    match base {
+        Some(base) => base.join("remote_desktop_manager"),
+        None => PathBuf::from("."),
+    }
+}
+
+pub fn settings_path() -> PathBuf {
+    config_dir().join(SETTINGS_FILE_NAME)
+}
+
+pub fn layouts_path() -> PathBuf {
+    config_dir().join(LAYOUTS_FILE_NAME)
+}
+
+pub fn wrapped_keys_path() -> PathBuf {
+    config_dir().join(WRAPPED_KEYS_FILE_NAME)
+}
+
+pub fn server_certificates_path() -> PathBuf {
+    config_dir().join(SERVER_CERTIFICATES_FILE_NAME)
+}
+
+/// The vault opened at startup: `configured` if set, else the one in the
+/// config directory.
+pub fn vault_path(configured: &str) -> PathBuf {
+    if configured.trim().is_empty() {
+        config_dir().join(VAULT_FILE_NAME)
+    } else {
+        PathBuf::from(configured.trim())
+    }
+}
+
+/// Files that belong to a vault: the vault itself and its decoy.
+pub fn vault_files(vault: &Path) -> [PathBuf; 2] {
+    [vault.to_path_buf(), decoy_path(vault)]
+}
+
+/// Renames `from` to `to`, copying across drives where a rename cannot.
+pub fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if let Some(parent) = to.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to)?;
+    fs::remove_file(from)
+}
+
+/// Replaces `path` with `data` in one rename, so a crash leaves either the
+/// old file or the new one but never a torn one.
+pub fn write_atomically(path: &Path, data: &[u8]) -> io::Result<()> {
+    let temp = path.with_file_name(format!("{}.tmp", path.file_name().unwrap_or_default().to_string_lossy()));
+    let written = fs::File::create(&temp).and_then(|mut file| {
+        file.write_all(data)?;
+        file.sync_all()
+    });
+    match written.and_then(|()| fs::rename(&temp, path)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = fs::remove_file(&temp);
+            Err(e)
+        }
+    }
+}
+
+/// Folder with the automatic backups of `vault`, next to it.
+pub fn backups_dir(vault: &Path) -> PathBuf {
+    vault.with_file_name(format!("{}.backups", vault.file_name().unwrap_or_default().to_string_lossy()))
+}
+
+/// Automatic backups of `vault` with the time each was taken, newest first.
+pub fn list_backups(vault: &Path) -> Vec<(PathBuf, chrono::DateTime<Local>)> {
+    let prefix = format!("{}.", vault.file_name().unwrap_or_default().to_string_lossy());
+    let Ok(entries) = fs::read_dir(backups_dir(vault)) else {
+        return Vec::new();
+    };
+    let mut backups: Vec<_> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let stamp = path.file_name()?.to_str()?.strip_prefix(&prefix)?.to_string();
+            let taken = NaiveDateTime::parse_from_str(&stamp, BACKUP_STAMP).ok()?;
+            Some((path, Local.from_local_datetime(&taken).earliest()?))
+        })
+        .collect();
+    backups.sort_by_key(|(_, taken)| std::cmp::Reverse(*taken));
+    backups
+}
+
+/// Copies `vault` into its backups folder before it is overwritten, unless
+/// the newest backup is recent, and drops the oldest beyond `keep`. With
+/// `keep` at 0 nothing is backed up.
+pub fn backup_vault(vault: &Path, keep: u32) -> io::Result<()> {
+    if keep == 0 || !vault.is_file() {
+        return Ok(());
+    }
+    let backups = list_backups(vault);
+    let recent = backups.first().and_then(|(path, _)| fs::metadata(path).and_then(|metadata| metadata.modified()).ok());
+    if recent.is_some_and(|modified| SystemTime::now().duration_since(modified).unwrap_or_default() < BACKUP_INTERVAL) {
+        return Ok(());
+    }
+    let dir = backups_dir(vault);
+    fs::create_dir_all(&dir)?;
+    let name = format!("{}.{}", vault.file_name().unwrap_or_default().to_string_lossy(), Local::now().format(BACKUP_STAMP));
+    fs::copy(vault, dir.join(name))?;
+    for (old, _) in backups.iter().skip(keep as usize - 1) {
+        fs::remove_file(old)?;
+    }
+    Ok(())
+}
+
+/// Puts the vault file `backup` in place of `vault`. The vault it replaces
+/// is renamed aside rather than overwritten; returns where it went.
+pub fn restore_vault(backup: &Path, vault: &Path) -> Result<Option<PathBuf>, String> {
+    if fs::canonicalize(backup).ok() == fs::canonicalize(vault).ok() {
+        return Err("That is the vault itself, not a backup of it.".to_string());
+    }
+    match fs::metadata(backup) {
+        Ok(metadata) if metadata.is_file() && metadata.len() > 0 => {}
+        Ok(_) => return Err(format!("{} is not a vault file.", backup.display())),
+        Err(e) => return Err(format!("{}: {}", backup.display(), e)),
+    }
+    let aside = if vault.exists() {
+        let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+        let aside = vault.with_file_name(format!("{}.before-restore-{}", vault.file_name().unwrap_or_default().to_string_lossy(), stamp));
+        move_file(vault, &aside).map_err(|e| format!("Could not move {} aside: {}", vault.display(), e))?;
+        Some(aside)
+    } else {
+        None
+    };
+    if let Err(e) = fs::copy(backup, vault) {
+        if let Some(aside) = &aside {
+            let _ = move_file(aside, vault);
+        }
+        return Err(format!("Could not copy {} to {}: {}", backup.display(), vault.display(), e));
+    }
+    Ok(aside)
+}
+
+/// Creates the config directory and moves settings and vault files left in
+/// the working directory by older versions into it, unless it already has
+/// its own. Returns the files moved.
+pub fn migrate_local_files() -> Result<Vec<PathBuf>, String> {
+    let dir = config_dir();
+    fs::create_dir_all(&dir).map_err(|e| format!("Could not create {}: {}", dir.display(), e))?;
+    let local = PathBuf::from(".");
+    if fs::canonicalize(&dir).ok() == fs::canonicalize(&local).ok() {
+        return Ok(Vec::new());
+    }
+    let mut moved = Vec::new();
+    let mut names = vec![PathBuf::from(SETTINGS_FILE_NAME)];
+    names.extend(vault_files(Path::new(VAULT_FILE_NAME)));
+    for name in names {
+        let (from, to) = (local.join(&name), dir.join(&name));
+        if from.exists() && !to.exists() {
+            move_file(&from, &to).map_err(|e| format!("Could not move {} to {}: {}", from.display(), to.display(), e))?;
+            moved.push(to);
+        }
+    }
+    Ok(moved)
+}
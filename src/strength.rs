@@ -0,0 +1,124 @@
+//! Master key strength, estimated zxcvbn-style: the key is split into the
+//! guessable patterns an attacker would try first (common passwords,
+//! keyboard runs, sequences, repeats and years), each costing only a few
+//! bits, while the remaining characters are charged by their alphabet.
+
+const COMMON_PASSWORDS: [&str; 24] = [
+    "password", "passwort", "admin", "administrator", "welcome", "letmein", "qwerty", "dragon", "monkey", "master",
+    "login", "secret", "iloveyou", "sunshine", "princess", "football", "baseball", "shadow", "superman", "trustno1",
+    "changeme", "default", "root", "summer",
+];
+const KEYBOARD_ROWS: [&str; 4] = ["1234567890", "qwertyuiop", "asdfghjkl", "zxcvbnm"];
+/// Shortest run of repeats, sequence or keyboard keys counted as a pattern.
+const MIN_RUN: usize = 3;
+
+/// Estimated strength with hints on how to improve it.
+pub struct Strength {
+    pub bits: u32,
+    pub feedback: Vec<&'static str>,
+}
+
+/// Rough entropy estimate of a master key in bits.
+pub fn estimate_bits(password: &str) -> u32 {
+    assess(password).bits
+}
+
+pub fn assess(password: &str) -> Strength {
+    let chars: Vec<char> = password.to_lowercase().chars().collect();
+    let char_bits = alphabet_bits(password);
+    let mut feedback = Vec::new();
+    let mut bits = 0.0;
+    let mut i = 0;
+    while i < chars.len() {
+        let rest = &chars[i..];
+        let (length, cost, hint) = if let Some(length) = common_password_at(rest) {
+            (length, 10.0, "Avoid common passwords and words like \"password\" or \"admin\".")
+        } else if let Some(length) = year_at(rest) {
+            (length, 7.0, "Avoid years, they are easy to guess.")
+        } else if let Some(length) = repeat_at(rest) {
+            (length, char_bits + (length as f64).log2(), "Avoid repeated characters like \"aaa\".")
+        } else if let Some(length) = sequence_at(rest) {
+            (length, 6.0 + (length as f64).log2(), "Avoid sequences like \"abc\" or \"123\".")
+        } else if let Some(length) = keyboard_run_at(rest) {
+            (length, 6.0 + (length as f64).log2(), "Avoid keyboard patterns like \"qwerty\" or \"asdf\".")
+        } else {
+            (1, char_bits, "")
+        };
+        if !hint.is_empty() && !feedback.contains(&hint) {
+            feedback.push(hint);
+        }
+        bits += cost;
+        i += length;
+    }
+    if chars.len() < 12 {
+        feedback.push("Use at least 12 characters; a few unrelated words work well.");
+    }
+    Strength { bits: bits as u32, feedback }
+}
+
+/// Bits per character for the character classes the password uses.
+fn alphabet_bits(password: &str) -> f64 {
+    let mut pool = 0;
+    if password.chars().any(|c| c.is_ascii_lowercase()) {
+        pool += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_uppercase()) {
+        pool += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_digit()) {
+        pool += 10;
+    }
+    if password.chars().any(|c| c.is_ascii_punctuation() || c == ' ') {
+        pool += 33;
+    }
+    if !password.is_ascii() {
+        pool += 100;
+    }
+    if pool == 0 {
+        0.0
+    } else {
+        (pool as f64).log2()
+    }
+}
+
+fn common_password_at(rest: &[char]) -> Option<usize> {
+    COMMON_PASSWORDS
+        .iter()
+        .filter(|word| rest.iter().zip(word.chars()).filter(|(a, b)| **a == *b).count() == word.len())
+        .map(|word| word.len())
+        .max()
+}
+
+fn year_at(rest: &[char]) -> Option<usize> {
+    let year: String = rest.iter().take(4).collect();
+    (year.len() == 4 && (year.starts_with("19") || year.starts_with("20")) && year.chars().all(|c| c.is_ascii_digit())).then_some(4)
+}
+
+fn run_length(rest: &[char], follows: impl Fn(char, char) -> bool) -> Option<usize> {
+    let length = 1 + rest.windows(2).take_while(|pair| follows(pair[0], pair[1])).count();
+    (length >= MIN_RUN).then_some(length)
+}
+
+fn repeat_at(rest: &[char]) -> Option<usize> {
+    run_length(rest, |a, b| a == b)
+}
+
+/// Letters or digits counting up or down by one, like `abc` or `987`.
+fn sequence_at(rest: &[char]) -> Option<usize> {
+    let step = |a: char, b: char| b as i32 - a as i32;
+    let same_class = |a: char, b: char| (a.is_ascii_lowercase() && b.is_ascii_lowercase()) || (a.is_ascii_digit() && b.is_ascii_digit());
+    let direction = match rest {
+        [a, b, ..] if same_class(*a, *b) && step(*a, *b).abs() == 1 => step(*a, *b),
+        _ => return None,
+    };
+    run_length(rest, |a, b| same_class(a, b) && step(a, b) == direction)
+}
+
+/// Neighbouring keys along one keyboard row, in either direction.
+fn keyboard_run_at(rest: &[char]) -> Option<usize> {
+    KEYBOARD_ROWS
+        .iter()
+        .flat_map(|row| [row.chars().collect::<String>(), row.chars().rev().collect::<String>()])
+        .filter_map(|row| run_length(rest, |a, b| row.find(a).is_some_and(|index| row[index + 1..].starts_with(b))))
+        .max()
+}
@@ -0,0 +1,95 @@
+//! The badge on the app's taskbar button counting its open sessions, drawn
+//! over the icon with `ITaskbarList3::SetOverlayIcon`. Only Windows has
+//! one; elsewhere the window title carries the count alone.
+
+/// Shows `count` on the taskbar button, or removes the badge at zero.
+/// Counts above nine show as `9+`.
+#[cfg(windows)]
+pub fn show_session_count(count: usize) -> Result<(), String> {
+    use crate::session_window::main_window;
+    use std::ptr::{null, null_mut};
+    use winapi::shared::winerror::SUCCEEDED;
+    use winapi::shared::wtypesbase::CLSCTX_INPROC_SERVER;
+    use winapi::um::combaseapi::{CoCreateInstance, CoInitializeEx};
+    use winapi::um::objbase::COINIT_APARTMENTTHREADED;
+    use winapi::um::shobjidl_core::{CLSID_TaskbarList, ITaskbarList3};
+    use winapi::um::winuser::DestroyIcon;
+    use winapi::Interface;
+
+    let Some(window) = main_window() else {
+        return Ok(());
+    };
+    unsafe {
+        // winit has usually set the UI thread up for COM already.
+        CoInitializeEx(null_mut(), COINIT_APARTMENTTHREADED);
+        let mut list: *mut ITaskbarList3 = null_mut();
+        let created = CoCreateInstance(&CLSID_TaskbarList, null_mut(), CLSCTX_INPROC_SERVER, &ITaskbarList3::uuidof(), &mut list as *mut _ as *mut _);
+        if !SUCCEEDED(created) || list.is_null() {
+            return Err(format!("The taskbar could not be reached (error {:#x}).", created));
+        }
+        let result = if !SUCCEEDED((*list).HrInit()) {
+            Err("The taskbar could not be reached.".to_string())
+        } else if count == 0 {
+            Ok((*list).SetOverlayIcon(window, null_mut(), null()))
+        } else {
+            let text = if count > 9 { "9+".to_string() } else { count.to_string() };
+            let description = if count == 1 { "1 active session".to_string() } else { format!("{} active sessions", count) };
+            let description = description.encode_utf16().chain([0]).collect::<Vec<u16>>();
+            let icon = badge_icon(&text);
+            // The taskbar keeps its own copy of the icon.
+            let shown = (*list).SetOverlayIcon(window, icon, description.as_ptr());
+            DestroyIcon(icon);
+            Ok(shown)
+        };
+        (*list).Release();
+        match result? {
+            shown if SUCCEEDED(shown) => Ok(()),
+            shown => Err(format!("The taskbar badge could not be set (error {:#x}).", shown)),
+        }
+    }
+}
+
+#[cfg(not(windows))]
+pub fn show_session_count(_count: usize) -> Result<(), String> {
+    Ok(())
+}
+
+/// A 16 pixel red square with `text` in white, the size the taskbar draws
+/// overlays at.
+#[cfg(windows)]
+unsafe fn badge_icon(text: &str) -> winapi::shared::windef::HICON {
+    use std::ptr::null_mut;
+    use winapi::shared::windef::RECT;
+    use winapi::um::wingdi::{
+        CreateBitmap, CreateCompatibleBitmap, CreateCompatibleDC, CreateSolidBrush, DeleteDC, DeleteObject, GetStockObject, SelectObject, SetBkMode, SetTextColor,
+        DEFAULT_GUI_FONT, RGB, TRANSPARENT,
+    };
+    use winapi::um::winuser::{CreateIconIndirect, DrawTextW, FillRect, GetDC, ReleaseDC, DT_CENTER, DT_SINGLELINE, DT_VCENTER, ICONINFO};
+
+    const SIZE: i32 = 16;
+    let screen = GetDC(null_mut());
+    let dc = CreateCompatibleDC(screen);
+    let color = CreateCompatibleBitmap(screen, SIZE, SIZE);
+    // An all-zero mask draws every pixel of the color bitmap.
+    let mask_bits = [0u8; (SIZE * SIZE / 8) as usize];
+    let mask = CreateBitmap(SIZE, SIZE, 1, 1, mask_bits.as_ptr() as *const _);
+    let previous_bitmap = SelectObject(dc, color as _);
+    let previous_font = SelectObject(dc, GetStockObject(DEFAULT_GUI_FONT as i32));
+    let mut rect = RECT { left: 0, top: 0, right: SIZE, bottom: SIZE };
+    let brush = CreateSolidBrush(RGB(196, 43, 28));
+    FillRect(dc, &rect, brush);
+    DeleteObject(brush as _);
+    SetBkMode(dc, TRANSPARENT as i32);
+    SetTextColor(dc, RGB(255, 255, 255));
+    let mut wide = text.encode_utf16().collect::<Vec<u16>>();
+    DrawTextW(dc, wide.as_mut_ptr(), wide.len() as i32, &mut rect, DT_CENTER | DT_VCENTER | DT_SINGLELINE);
+    SelectObject(dc, previous_font);
+    SelectObject(dc, previous_bitmap);
+    let mut info = ICONINFO { fIcon: 1, xHotspot: 0, yHotspot: 0, hbmMask: mask, hbmColor: color };
+    let icon = CreateIconIndirect(&mut info);
+    DeleteObject(color as _);
+    DeleteObject(mask as _);
+    DeleteDC(dc);
+    ReleaseDC(null_mut(), screen);
+    icon
+}
@@ -0,0 +1,133 @@
+//! `{placeholder}` expansion shared by reference links and launcher command
+//! templates. Besides the built-in client fields, each client can define its
+//! own variables such as `{jump_user}` or `{region}`.
+
+use crate::client::Client;
+use std::process::Command;
+
+/// Placeholders filled from the client's own fields. Client variables may
+/// not reuse these names.
+pub const BUILT_IN: [&str; 7] = ["name", "ip", "host", "port", "username", "domain", "reference"];
+
+/// Words in a variable name that mark its value as secret, kept out of the
+/// search index.
+const SECRET_WORDS: [&str; 5] = ["password", "passwd", "secret", "token", "key"];
+
+/// True for variables such as `{api_token}` or `{vpn_password}` whose value
+/// should not show up in search results.
+pub fn is_secret_name(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    SECRET_WORDS.iter().any(|word| name.contains(word))
+}
+
+/// True for names made of ASCII letters, digits and underscores.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// What a built-in placeholder stands for, for the help window.
+pub fn description(name: &str) -> Option<&'static str> {
+    match name {
+        "name" => Some("The client's display name."),
+        "ip" => Some("The address as typed, including a :port."),
+        "host" => Some("The address without its port."),
+        "port" => Some("The port set on the client or in its address. Clients with neither have no value for it."),
+        "username" => Some("The user name, without the domain."),
+        "domain" => Some("The Windows domain of the account."),
+        "reference" => Some("The ticket or asset reference."),
+        _ => None,
+    }
+}
+
+/// The value `{name}` stands for with `client`, if it has one.
+pub fn lookup(name: &str, client: &Client) -> Option<String> {
+    let (host, port) = client.endpoint();
+    match name {
+        "name" => Some(client.name.clone()),
+        "ip" => Some(client.ip.clone()),
+        "host" => Some(host.to_string()),
+        "port" => port.map(|port| port.to_string()),
+        "username" => Some(client.username.clone()),
+        "domain" => Some(client.domain.clone()),
+        "reference" => Some(client.reference.clone()),
+        _ => client.variables.get(name).cloned(),
+    }
+}
+
+/// Replaces every `{name}` in `template` with `encode` applied to its value.
+/// Fails with the list of placeholders the client has no value for. Braces
+/// around anything that is not a valid name are left alone.
+pub fn expand(template: &str, client: &Client, encode: impl Fn(&str) -> String) -> Result<String, String> {
+    let mut output = String::new();
+    let mut missing: Vec<&str> = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find('}').map(|end| &after[..end]).filter(|name| is_valid_name(name)) {
+            Some(name) => {
+                match lookup(name, client) {
+                    Some(value) => output.push_str(&encode(&value)),
+                    None if !missing.contains(&name) => missing.push(name),
+                    None => {}
+                }
+                rest = &after[name.len() + 1..];
+            }
+            None => {
+                output.push('{');
+                rest = after;
+            }
+        }
+    }
+    output.push_str(rest);
+    if missing.is_empty() {
+        Ok(output)
+    } else {
+        let names: Vec<String> = missing.iter().map(|name| format!("{{{}}}", name)).collect();
+        Err(format!("{} has no value for {}.", client.name, names.join(", ")))
+    }
+}
+
+/// Splits a command line into arguments at whitespace, keeping double-quoted
+/// parts together.
+pub fn split_command_line(line: &str) -> Vec<String> {
+    let mut arguments = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut started = false;
+    for c in line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                started = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if started {
+                    arguments.push(std::mem::take(&mut current));
+                    started = false;
+                }
+            }
+            c => {
+                current.push(c);
+                started = true;
+            }
+        }
+    }
+    if started {
+        arguments.push(current);
+    }
+    arguments
+}
+
+/// Builds a command from a command-line template. Every argument is expanded
+/// on its own, so values with spaces stay one argument.
+pub fn templated_command(template: &str, client: &Client) -> Result<Command, String> {
+    let arguments = split_command_line(template)
+        .iter()
+        .map(|argument| expand(argument, client, str::to_string))
+        .collect::<Result<Vec<String>, String>>()?;
+    let (program, arguments) = arguments.split_first().ok_or("The command line is empty.")?;
+    let mut command = Command::new(program);
+    command.args(arguments);
+    Ok(command)
+}
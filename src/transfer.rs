@@ -0,0 +1,217 @@
+use crate::client::{new_client_id, parse_tags, Client, Protocol};
+use crate::rdg::parse_rdg;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// One line of the round-trip CSV. Passwords are never exported; rows are
+/// matched back to existing clients by `id` on import.
+#[derive(Serialize, Deserialize)]
+pub struct CsvRow {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    pub ip: String,
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub reference: String,
+    #[serde(default)]
+    pub protocol: Protocol,
+    #[serde(default)]
+    pub group: String,
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub domain: String,
+    /// Comma-separated.
+    #[serde(default)]
+    pub tags: String,
+}
+
+pub struct ImportSummary {
+    pub added: usize,
+    pub updated: usize,
+}
+
+pub fn export_csv(clients: &[Client]) -> Result<String, String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for client in clients.iter().filter(|c| c.trashed_at.is_none()) {
+        writer
+            .serialize(CsvRow {
+                id: client.id.clone(),
+                name: client.name.clone(),
+                ip: client.ip.clone(),
+                port: client.port,
+                reference: client.reference.clone(),
+                protocol: client.protocol,
+                group: client.group.clone(),
+                username: client.username.clone(),
+                domain: client.domain.clone(),
+                tags: client.tags.join(", "),
+            })
+            .map_err(|e| e.to_string())?;
+    }
+    let data = writer.into_inner().map_err(|e| e.to_string())?;
+    String::from_utf8(data).map_err(|e| e.to_string())
+}
+
+pub fn parse_csv(data: &str) -> Result<Vec<CsvRow>, String> {
+    let mut reader = csv::Reader::from_reader(data.as_bytes());
+    reader
+        .deserialize()
+        .enumerate()
+        .map(|(line, row)| row.map_err(|e| format!("Row {}: {}", line + 1, e)))
+        .collect()
+}
+
+/// The clients of imported rows, with their ids so that `merge_clients`
+/// updates known clients in place; new ones come without a password.
+pub fn rows_to_clients(rows: Vec<CsvRow>) -> Vec<Client> {
+    rows.into_iter()
+        .map(|row| Client {
+            id: row.id.trim().to_string(),
+            name: row.name,
+            ip: row.ip,
+            port: row.port,
+            reference: row.reference,
+            protocol: row.protocol,
+            group: row.group,
+            username: row.username,
+            domain: row.domain,
+            tags: parse_tags(&row.tags),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Merges imported clients by id. Existing clients keep their password and
+/// attachments unless the import carries a password of its own.
+pub fn merge_clients(clients: &mut Vec<Client>, imported: Vec<Client>) -> ImportSummary {
+    let mut summary = ImportSummary { added: 0, updated: 0 };
+    for mut incoming in imported {
+        if let Some(client) = clients.iter_mut().find(|c| !incoming.id.is_empty() && c.id == incoming.id) {
+            client.name = incoming.name;
+            client.ip = incoming.ip;
+            if incoming.port.is_some() {
+                client.port = incoming.port;
+            }
+            client.reference = incoming.reference;
+            client.protocol = incoming.protocol;
+            if !incoming.group.is_empty() {
+                client.group = incoming.group;
+            }
+            if !incoming.username.is_empty() {
+                client.username = incoming.username;
+                client.domain = incoming.domain;
+            }
+            if !incoming.tags.is_empty() {
+                client.tags = incoming.tags;
+            }
+            if !incoming.variables.is_empty() {
+                client.variables = incoming.variables;
+            }
+            if !incoming.owner.is_empty() {
+                client.owner = incoming.owner;
+            }
+            if incoming.expires.is_some() {
+                client.expires = incoming.expires;
+            }
+            if !incoming.password.is_empty() {
+                client.password = incoming.password;
+            }
+            summary.updated += 1;
+        } else {
+            if incoming.id.is_empty() {
+                incoming.id = new_client_id();
+            }
+            clients.push(incoming);
+            summary.added += 1;
+        }
+    }
+    summary
+}
+
+/// Decodes a text file that may be saved as UTF-16LE with a BOM, which is
+/// how mstsc writes .rdp files.
+pub fn decode_text(data: &[u8]) -> String {
+    if data.starts_with(&[0xFF, 0xFE]) {
+        let units: Vec<u16> = data[2..]
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    } else {
+        String::from_utf8_lossy(data.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(data)).into_owned()
+    }
+}
+
+/// Reads the target address out of an .rdp file; `name` is used as the client name.
+pub fn parse_rdp(data: &str, name: &str) -> Result<Client, String> {
+    let address = data
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("full address:s:"))
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .ok_or("No \"full address\" entry found")?;
+    Ok(Client {
+        name: name.to_string(),
+        ip: address,
+        ..Default::default()
+    })
+}
+
+/// Reads the clients in a .csv, .rdp or .rdg file, picking the parser by
+/// extension.
+pub fn read_import_file(path: &Path) -> Result<Vec<Client>, String> {
+    let data = decode_text(&fs::read(path).map_err(|e| e.to_string())?);
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match extension.as_str() {
+        "csv" => Ok(rows_to_clients(parse_csv(&data)?)),
+        "rdp" => {
+            let name = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default();
+            Ok(vec![parse_rdp(&data, name)?])
+        }
+        "rdg" => Ok(parse_rdg(&data)?.clients),
+        _ => Err(format!("Unsupported file type: {}", path.display())),
+    }
+}
+
+/// What an import changed, so it can be reverted without touching edits
+/// made to other clients since.
+pub struct ImportUndo {
+    pub added_ids: Vec<String>,
+    pub replaced: Vec<Client>,
+}
+
+impl ImportUndo {
+    pub fn capture(before: &[Client], after: &[Client]) -> Self {
+        let mut added_ids = Vec::new();
+        let mut replaced = Vec::new();
+        for client in after {
+            match before.iter().find(|old| old.id == client.id) {
+                Some(old) if old != client => replaced.push(old.clone()),
+                Some(_) => {}
+                None => added_ids.push(client.id.clone()),
+            }
+        }
+        Self { added_ids, replaced }
+    }
+
+    pub fn wipe_secrets(&mut self) {
+        self.replaced.iter_mut().for_each(Client::wipe_secrets);
+    }
+
+    pub fn apply(self, clients: &mut Vec<Client>) {
+        clients.retain(|client| !self.added_ids.contains(&client.id));
+        for old in self.replaced {
+            if let Some(client) = clients.iter_mut().find(|c| c.id == old.id) {
+                *client = old;
+            }
+        }
+    }
+}
@@ -0,0 +1,1374 @@
+//! Drives whole frames of the app on a headless egui context and checks the
+//! resulting repository state. Widgets are found by the text they paint.
+
+use crate::app::AppState;
+use crate::client::{AppMode, Client, KeyStatus, Protocol};
+use crate::ipc::RemoteCommand;
+use crate::launcher::Launcher;
+use crate::repository::ClientRepository;
+use eframe::egui::{self, Event, Key, Modifiers, PointerButton, Pos2, RawInput, Rect, Shape, Vec2};
+
+struct Harness {
+    ctx: egui::Context,
+    state: AppState,
+    events: Vec<Event>,
+    texts: Vec<(String, Rect)>,
+}
+
+impl Harness {
+    fn new(state: AppState) -> Self {
+        let mut harness = Self {
+            ctx: egui::Context::default(),
+            state,
+            events: Vec::new(),
+            texts: Vec::new(),
+        };
+        harness.settle();
+        harness
+    }
+
+    fn step(&mut self) {
+        let input = RawInput {
+            screen_rect: Some(Rect::from_min_size(Pos2::ZERO, Vec2::new(1024.0, 768.0))),
+            events: std::mem::take(&mut self.events),
+            ..Default::default()
+        };
+        let output = self.ctx.run(input, |ctx| self.state.ui(ctx));
+        self.texts.clear();
+        for clipped in output.shapes {
+            collect_texts(&clipped.shape, &mut self.texts);
+        }
+    }
+
+    fn find(&self, text: &str) -> Option<Rect> {
+        self.texts.iter().find(|(painted, _)| painted == text).map(|(_, rect)| *rect)
+    }
+
+    fn has_text(&self, fragment: &str) -> bool {
+        self.texts.iter().any(|(painted, _)| painted.contains(fragment))
+    }
+
+    fn click_at(&mut self, pos: Pos2) {
+        self.events.push(Event::PointerMoved(pos));
+        self.events.push(pointer_button(pos, true));
+        self.step();
+        self.events.push(pointer_button(pos, false));
+        self.settle();
+    }
+
+    fn right_click(&mut self, text: &str) {
+        let pos = self.find(text).unwrap_or_else(|| panic!("no widget labelled {:?}", text)).center();
+        self.events.push(Event::PointerMoved(pos));
+        for pressed in [true, false] {
+            self.events.push(Event::PointerButton { pos, button: PointerButton::Secondary, pressed, modifiers: Modifiers::NONE });
+            self.step();
+        }
+        self.settle();
+    }
+
+    /// Presses on `from`, moves the pointer onto `to` and lets go there.
+    fn drag(&mut self, from: &str, to: &str) {
+        let start = self.find(from).unwrap_or_else(|| panic!("no widget labelled {:?}", from)).center();
+        let end = self.find(to).unwrap_or_else(|| panic!("no widget labelled {:?}", to)).center();
+        self.events.push(Event::PointerMoved(start));
+        self.events.push(pointer_button(start, true));
+        self.step();
+        for step in 1..=10 {
+            self.events.push(Event::PointerMoved(start.lerp(end, step as f32 / 10.0)));
+            self.step();
+        }
+        self.events.push(pointer_button(end, false));
+        self.settle();
+    }
+
+    /// Runs enough frames for windows and menus to finish fading in.
+    fn settle(&mut self) {
+        for _ in 0..10 {
+            self.step();
+        }
+    }
+
+    fn click(&mut self, text: &str) {
+        let rect = self.find(text).unwrap_or_else(|| panic!("no widget labelled {:?}", text));
+        self.click_at(rect.center());
+    }
+
+    /// Replaces the contents of the text field to the right of `label`.
+    fn fill(&mut self, label: &str, text: &str) {
+        let rect = self.find(label).unwrap_or_else(|| panic!("no field labelled {:?}", label));
+        self.click_at(Pos2::new(rect.max.x + 40.0, rect.center().y));
+        self.key(Key::A, Modifiers::COMMAND);
+        self.type_text(text);
+    }
+
+    fn type_text(&mut self, text: &str) {
+        self.events.push(Event::Text(text.to_string()));
+        self.step();
+    }
+
+    fn key(&mut self, key: Key, modifiers: Modifiers) {
+        for pressed in [true, false] {
+            self.events.push(Event::Key {
+                key,
+                physical_key: None,
+                pressed,
+                repeat: false,
+                modifiers,
+            });
+        }
+        self.step();
+    }
+
+    /// Runs frames until the key derivation started by Unlock or Create
+    /// has finished.
+    fn wait_for_unlock(&mut self) {
+        while self.state.pending_unlock.is_some() {
+            std::thread::sleep(std::time::Duration::from_millis(5));
+            self.step();
+        }
+        self.settle();
+    }
+
+    fn menu(&mut self, menu: &str, item: &str) {
+        self.click(menu);
+        self.click(item);
+    }
+}
+
+fn pointer_button(pos: Pos2, pressed: bool) -> Event {
+    Event::PointerButton {
+        pos,
+        button: PointerButton::Primary,
+        pressed,
+        modifiers: Modifiers::NONE,
+    }
+}
+
+fn collect_texts(shape: &Shape, texts: &mut Vec<(String, Rect)>) {
+    match shape {
+        Shape::Text(text) => texts.push((text.galley.text().to_string(), text.visual_bounding_rect())),
+        Shape::Vec(shapes) => shapes.iter().for_each(|shape| collect_texts(shape, texts)),
+        _ => {}
+    }
+}
+
+fn unlocked_with(names: &[&str]) -> Harness {
+    let mut state = AppState::unlocked_for_test();
+    for name in names {
+        state.repository.add(Client {
+            name: name.to_string(),
+            ip: format!("{}.example", name),
+            ..Default::default()
+        });
+    }
+    state.save_clients();
+    Harness::new(state)
+}
+
+#[test]
+fn creating_a_master_key_unlocks_the_vault() {
+    let mut harness = Harness::new(AppState::for_test());
+    assert!(harness.has_text("Create Master Key"));
+    harness.key(Key::Tab, Modifiers::NONE);
+    harness.type_text("correct horse battery");
+    harness.key(Key::Tab, Modifiers::NONE);
+    harness.type_text("correct horse battery");
+    harness.click("Create");
+    harness.wait_for_unlock();
+    assert!(harness.state.is_unlocked());
+    assert!(harness.state.repository.exists());
+}
+
+#[test]
+fn mismatched_master_keys_show_an_error() {
+    let mut harness = Harness::new(AppState::for_test());
+    harness.key(Key::Tab, Modifiers::NONE);
+    harness.type_text("correct horse battery");
+    harness.key(Key::Tab, Modifiers::NONE);
+    harness.type_text("something else");
+    harness.click("Create");
+    assert!(matches!(harness.state.key_status, KeyStatus::NotSet));
+    assert!(harness.has_text("The master keys do not match."));
+}
+
+#[test]
+fn key_derivation_shows_progress_and_can_be_cancelled() {
+    let mut harness = Harness::new(AppState::for_test());
+    // Holding the sender keeps the derivation running for as long as needed.
+    let (_sender, receiver) = std::sync::mpsc::channel();
+    harness.state.pending_unlock = Some(crate::views::unlock_view::PendingUnlock::new(receiver, Some(0)));
+    harness.settle();
+    assert!(harness.has_text("Deriving the key"));
+    assert!(harness.find("Create").is_none());
+    harness.click("Cancel");
+    assert!(harness.state.pending_unlock.is_none());
+    assert!(harness.find("Create").is_some());
+    assert!(matches!(harness.state.key_status, KeyStatus::NotSet));
+}
+
+#[test]
+fn adding_a_client() {
+    let mut harness = unlocked_with(&[]);
+    harness.menu("File", "New");
+    assert_eq!(harness.state.mode, AppMode::Adding);
+    harness.fill("Name:", "db01");
+    harness.fill("IP:", "10.0.0.5");
+    harness.click("Save");
+    assert_eq!(harness.state.mode, AppMode::Normal);
+    let clients = harness.state.repository.clients();
+    assert_eq!(clients.len(), 1);
+    assert_eq!(clients[0].name, "db01");
+    assert_eq!(clients[0].ip, "10.0.0.5");
+}
+
+#[test]
+fn notes_are_edited_in_the_form_and_shown_for_the_selection() {
+    let mut harness = unlocked_with(&["db01"]);
+    harness.click("db01");
+    harness.menu("File", "Edit");
+    let label = harness.find("Notes:").expect("notes label");
+    harness.click_at(Pos2::new(label.min.x + 20.0, label.max.y + 20.0));
+    harness.type_text("Rack B4");
+    harness.key(Key::Enter, Modifiers::NONE);
+    harness.type_text("Patch window: Sun 02:00");
+    harness.click("Save");
+    assert_eq!(harness.state.repository.clients()[0].notes, "Rack B4\nPatch window: Sun 02:00");
+    assert!(harness.has_text("Patch window: Sun 02:00"));
+}
+
+#[test]
+fn editing_a_client() {
+    let mut harness = unlocked_with(&["db01", "web01"]);
+    harness.click("web01");
+    harness.menu("File", "Edit");
+    assert_eq!(harness.state.mode, AppMode::Editing);
+    harness.fill("Name:", "web02");
+    harness.click("Save");
+    let names: Vec<&str> = harness.state.repository.clients().iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, ["db01", "web02"]);
+}
+
+#[test]
+fn edits_go_to_the_loaded_client_and_cancel_asks_first() {
+    let mut harness = unlocked_with(&["db01", "web01"]);
+    harness.click("db01");
+    harness.menu("File", "Edit");
+    harness.fill("Name:", "db02");
+    harness.click("web01");
+    harness.click("Cancel");
+    assert!(harness.has_text("Discard the unsaved changes?"));
+    harness.click("Keep Editing");
+    harness.click("Save");
+    let names: Vec<&str> = harness.state.repository.clients().iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, ["db02", "web01"]);
+
+    harness.menu("File", "Edit");
+    harness.fill("Name:", "web02");
+    harness.click("Cancel");
+    harness.click("Discard");
+    assert_eq!(harness.state.mode, AppMode::Normal);
+    assert_eq!(harness.state.repository.clients()[1].name, "web01");
+}
+
+#[test]
+fn removing_a_client() {
+    let mut harness = unlocked_with(&["db01", "web01"]);
+    harness.click("db01");
+    harness.menu("File", "Remove");
+    assert!(harness.has_text("Remove Client: db01"));
+    harness.click("Confirm");
+    let names: Vec<&str> = harness.state.repository.clients().iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, ["web01"]);
+    assert_eq!(harness.state.client_list.selected, None);
+}
+
+#[test]
+fn selection_stays_on_the_client_when_one_above_it_goes() {
+    let mut harness = unlocked_with(&["db01", "web01", "web02"]);
+    harness.click("web02");
+    let db01 = harness.state.repository.clients()[0].id.clone();
+    harness.state.repository.remove(&db01);
+    harness.settle();
+    let selected = harness.state.client_list.selected_client(harness.state.repository.clients());
+    assert_eq!(selected.map(|client| client.name.as_str()), Some("web02"));
+}
+
+#[test]
+fn importing_a_csv_file() {
+    let mut harness = unlocked_with(&["db01"]);
+    let existing = harness.state.repository.clients()[0].id.clone();
+    let path = std::env::temp_dir().join(format!("rdm-import-{}.csv", crate::client::new_client_id()));
+    let csv = format!("id,name,ip,reference\n{},db01-renamed,10.0.0.5,\n,new01,10.0.0.6,T-1\n", existing);
+    std::fs::write(&path, csv).unwrap();
+
+    harness.menu("File", "Import CSV");
+    assert_eq!(harness.state.mode, AppMode::Importing);
+    harness.fill("File:", &path.to_string_lossy());
+    harness.click("Import");
+    let _ = std::fs::remove_file(&path);
+
+    assert!(harness.has_text("Imported 1 new and updated 1 existing clients"));
+    let clients = harness.state.repository.clients();
+    assert_eq!(clients.len(), 2);
+    assert_eq!(clients[0].name, "db01-renamed");
+    assert_eq!(clients[1].reference, "T-1");
+}
+
+#[test]
+fn command_line_search_runs_after_unlock() {
+    let mut state = AppState::for_test();
+    state.pending_command = Some(RemoteCommand::Search("prod".to_string()));
+    let mut harness = Harness::new(state);
+    harness.key(Key::Tab, Modifiers::NONE);
+    harness.type_text("correct horse battery");
+    harness.key(Key::Tab, Modifiers::NONE);
+    harness.type_text("correct horse battery");
+    harness.click("Create");
+    harness.wait_for_unlock();
+    harness.settle();
+    assert_eq!(harness.state.mode, AppMode::Search);
+    assert_eq!(harness.state.search_query, "prod");
+}
+
+#[test]
+fn command_line_connect_reports_unknown_clients() {
+    let mut harness = unlocked_with(&["db01"]);
+    harness.state.pending_command = Some(RemoteCommand::Connect("web01".to_string()));
+    harness.settle();
+    assert!(harness.state.pending_command.is_none());
+    assert!(harness.has_text("No client named web01."));
+}
+
+#[test]
+fn filtering_the_client_list() {
+    let mut harness = unlocked_with(&["db01", "web01", "web02"]);
+    harness.fill("Filter:", "WEB");
+    assert!(harness.find("db01").is_none());
+    assert!(harness.find("web01").is_some() && harness.find("web02").is_some());
+    harness.fill("Filter:", "db01.example");
+    assert!(harness.find("db01").is_some() && harness.find("web01").is_none());
+    assert!(harness.has_text("IP:"));
+}
+
+#[test]
+fn filtering_and_sorting_ignore_accents() {
+    let mut harness = unlocked_with(&["Zürich", "Munich", "München"]);
+    harness.fill("Filter:", "munchen");
+    assert!(harness.find("München").is_some());
+    assert!(harness.find("Munich").is_none() && harness.find("Zürich").is_none());
+    harness.fill("Filter:", "");
+    harness.menu("Tools", "Sort Clients by Name");
+    let names: Vec<&str> = harness.state.repository.clients().iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, ["München", "Munich", "Zürich"]);
+}
+
+#[test]
+fn dry_run_connections_start_simulated_sessions() {
+    let mut harness = unlocked_with(&["db01"]);
+    harness.state.launcher = Launcher::DryRun;
+    harness.state.pending_command = Some(RemoteCommand::Connect("db01".to_string()));
+    harness.settle();
+    let sessions = harness.state.sessions.active();
+    assert_eq!(sessions.len(), 1);
+    assert!(sessions[0].simulated);
+    assert!(harness.has_text("1 active sessions"));
+    assert!(harness.has_text("Dry run"));
+}
+
+#[test]
+fn filtering_the_client_list_by_tag() {
+    let mut harness = unlocked_with(&["db01", "web01", "web02"]);
+    let mut clients = harness.state.repository.clients().to_vec();
+    clients[0].tags = vec!["prod".to_string()];
+    clients[1].tags = vec!["prod".to_string(), "dmz".to_string()];
+    harness.state.repository.replace_all(clients);
+    harness.state.save_clients();
+    harness.state.client_list.selected = None;
+    harness.settle();
+    harness.click("dmz");
+    assert_eq!(harness.state.client_list.tag.as_deref(), Some("dmz"));
+    assert!(harness.find("web01").is_some());
+    assert!(harness.find("db01").is_none() && harness.find("web02").is_none());
+}
+
+#[test]
+fn hold_to_reveal_hides_the_password_on_release() {
+    let mut harness = unlocked_with(&["db01"]);
+    harness.state.settings.hold_to_reveal = true;
+    let mut clients = harness.state.repository.clients().to_vec();
+    clients[0].password = "hunter2".to_string();
+    harness.state.repository.replace_all(clients);
+    harness.click("db01");
+    harness.menu("File", "Edit");
+    assert!(!harness.has_text("hunter2"));
+
+    let eye = harness.find("👁").expect("eye button").center();
+    harness.events.push(Event::PointerMoved(eye));
+    harness.events.push(pointer_button(eye, true));
+    harness.settle();
+    assert!(harness.has_text("hunter2"));
+    harness.events.push(pointer_button(eye, false));
+    harness.step();
+    assert!(!harness.has_text("hunter2"));
+}
+
+#[test]
+fn revealed_passwords_are_hidden_again_when_the_form_reopens() {
+    let mut harness = unlocked_with(&["db01"]);
+    let mut clients = harness.state.repository.clients().to_vec();
+    clients[0].password = "hunter2".to_string();
+    harness.state.repository.replace_all(clients);
+    harness.click("db01");
+    harness.menu("File", "Edit");
+    assert!(!harness.has_text("hunter2"));
+    harness.click("👁");
+    assert!(harness.has_text("hunter2"));
+
+    harness.click("Cancel");
+    harness.menu("File", "Edit");
+    assert!(!harness.has_text("hunter2"));
+    harness.click("🎲");
+    assert_eq!(harness.state.form.password.len(), 20);
+    assert_ne!(harness.state.form.password, "hunter2");
+}
+
+#[test]
+fn unlocking_migrates_an_unsalted_vault() {
+    let path = std::env::temp_dir().join(format!("rdm-legacy-{}.json", crate::client::new_client_id()));
+    let clients = vec![Client { name: "db01".to_string(), ..Default::default() }];
+    let data = crate::vault::serialize_vault(&Default::default(), &clients, &[], &[], &[], &Default::default());
+    let key = crate::encryption::generate_key_from_password("correct horse battery");
+    std::fs::write(&path, crate::encryption::encrypt(&data, &key).unwrap()).unwrap();
+
+    let repository = crate::repository::EncryptedFileRepository::new(&path);
+    let mut harness = Harness::new(AppState::for_test_with_repository(repository));
+    harness.key(Key::Tab, Modifiers::NONE);
+    harness.type_text("correct horse battery");
+    harness.click("Unlock");
+    harness.wait_for_unlock();
+    assert!(harness.state.is_unlocked());
+    assert!(std::fs::read(&path).unwrap().starts_with(b"RDMF"));
+
+    harness.state.lock();
+    harness.state.repository.unlock("correct horse battery").unwrap();
+    assert_eq!(harness.state.repository.clients()[0].name, "db01");
+    assert!(harness.state.repository.unlock("wrong").is_err());
+    assert!(harness.state.layouts_path.starts_with(std::env::temp_dir()));
+    let _ = std::fs::remove_file(&harness.state.layouts_path);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn changing_the_master_key() {
+    let mut harness = Harness::new(AppState::for_test());
+    harness.key(Key::Tab, Modifiers::NONE);
+    harness.type_text("correct horse battery");
+    harness.key(Key::Tab, Modifiers::NONE);
+    harness.type_text("correct horse battery");
+    harness.click("Create");
+    harness.wait_for_unlock();
+
+    harness.menu("File", "Change Master Key");
+    assert_eq!(harness.state.mode, AppMode::ChangingMasterKey);
+    harness.fill("Current master key:", "wrong");
+    harness.fill("New master key:", "staple battery");
+    harness.fill("Confirm:", "staple battery");
+    harness.click("Change");
+    assert!(harness.has_text("The current master key is incorrect."));
+    harness.state.error_message = None;
+
+    harness.fill("Current master key:", "correct horse battery");
+    harness.click("Change");
+    assert_eq!(harness.state.mode, AppMode::Normal);
+    harness.state.lock();
+    assert!(harness.state.repository.unlock("correct horse battery").is_err());
+    assert!(harness.state.repository.unlock("staple battery").is_ok());
+}
+
+#[test]
+fn the_duress_key_opens_the_decoy_vault() {
+    let mut harness = Harness::new(AppState::for_test());
+    harness.key(Key::Tab, Modifiers::NONE);
+    harness.type_text("correct horse battery");
+    harness.key(Key::Tab, Modifiers::NONE);
+    harness.type_text("correct horse battery");
+    harness.click("Create");
+    harness.wait_for_unlock();
+    harness.state.repository.add(Client { name: "secret-dc".to_string(), ..Default::default() });
+    harness.state.save_clients();
+    let path = harness.state.repository.path().to_path_buf();
+    let without_decoy = std::fs::read(&path).unwrap().len();
+
+    harness.menu("File", "Settings");
+    harness.click("Security");
+    harness.fill("Duress key:", "plum tractor lantern");
+    harness.fill("Confirm:", "plum tractor lantern");
+    harness.click("Set Up Duress Key");
+    // The decoy lives in the vault's second slot, which was there already.
+    assert_eq!(std::fs::read(&path).unwrap().len(), without_decoy);
+    assert!(!crate::repository::decoy_path(&path).exists());
+
+    harness.state.lock();
+    let layouts = std::fs::read(&harness.state.layouts_path).ok();
+    harness.state.settings.key_caching = crate::settings::KeyCaching::RememberForSession;
+    crate::keycache::clear(&path);
+    harness.state.unlock_form.master_key = "plum tractor lantern".to_string();
+    harness.state.unlock_and_wait();
+    assert!(harness.state.is_unlocked());
+    assert!(harness.state.repository.is_decoy());
+    assert!(harness.state.repository.clients().is_empty());
+    assert!(crate::keycache::load(&path).is_none());
+    harness.state.repository.add(Client { name: "printer".to_string(), ..Default::default() });
+    harness.state.save_clients();
+    assert_eq!(std::fs::read(&path).unwrap().len(), without_decoy);
+    harness.state.lock();
+    assert_eq!(std::fs::read(&harness.state.layouts_path).ok(), layouts);
+    harness.state.retained_key = None;
+    harness.state.settings.key_caching = crate::settings::KeyCaching::ForgetOnLock;
+
+    harness.state.unlock_form.master_key = "correct horse battery".to_string();
+    harness.state.unlock_and_wait();
+    assert!(!harness.state.repository.is_decoy());
+    let names: Vec<&str> = harness.state.repository.clients().iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, ["secret-dc"]);
+    harness.state.save_clients();
+    harness.state.lock();
+    harness.state.unlock_form.master_key = "plum tractor lantern".to_string();
+    harness.state.unlock_and_wait();
+    let names: Vec<&str> = harness.state.repository.clients().iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, ["printer"]);
+
+    harness.state.lock();
+    harness.state.unlock_form.master_key = "correct horse battery".to_string();
+    harness.state.unlock_and_wait();
+    harness.state.repository.remove_decoy().unwrap();
+    assert_eq!(std::fs::read(&path).unwrap().len(), without_decoy);
+    harness.state.lock();
+    assert!(harness.state.repository.unlock("plum tractor lantern").is_err());
+}
+
+#[test]
+fn cut_off_or_extended_vaults_are_reported_as_damaged() {
+    let mut state = AppState::unlocked_for_test();
+    state.repository.add(Client { name: "db01".to_string(), ..Default::default() });
+    state.save_clients();
+    let path = state.repository.path().to_path_buf();
+    let key = *state.repository.key();
+    let data = std::fs::read(&path).unwrap();
+
+    for altered in [data[..data.len() - 40].to_vec(), [data.as_slice(), b"stale"].concat()] {
+        std::fs::write(&path, altered).unwrap();
+        let error = state.repository.open(key).unwrap_err();
+        assert!(error.contains("damaged"), "{}", error);
+    }
+    std::fs::write(&path, &data).unwrap();
+    assert!(state.repository.open(key).is_ok());
+}
+
+#[test]
+fn unlocking_tells_a_wrong_key_from_a_damaged_or_newer_file() {
+    use sha2::{Digest, Sha256};
+    let mut state = AppState::unlocked_for_test();
+    state.repository.add(Client { name: "db01".to_string(), ..Default::default() });
+    state.save_clients();
+    let path = state.repository.path().to_path_buf();
+    let (key, wrong_key) = (*state.repository.key(), [8; crate::encryption::KEY_SIZE]);
+    let data = std::fs::read(&path).unwrap();
+    let open = |state: &mut AppState, file: &[u8], key| {
+        std::fs::write(&path, file).unwrap();
+        state.repository.open(key).unwrap_err()
+    };
+
+    assert_eq!(open(&mut state, &data, wrong_key), "Incorrect master key.");
+    let mut flipped = data.clone();
+    // Inside the first slot, where the vault's contents are.
+    flipped[100] ^= 1;
+    for key in [wrong_key, key] {
+        assert!(open(&mut state, &flipped, key).starts_with("The vault file is damaged:"));
+    }
+    // Altered with the checksum fixed up: the key checks out, the MAC does not.
+    flipped.truncate(data.len() - 32);
+    let checksum = Sha256::digest(&flipped);
+    flipped.extend_from_slice(&checksum);
+    assert!(open(&mut state, &flipped, key).contains("tampered"));
+    let mut newer = data.clone();
+    newer[4] = 3;
+    assert!(open(&mut state, &newer, key).contains("newer version"));
+
+    std::fs::write(&path, &data).unwrap();
+    assert!(state.repository.open(key).is_ok());
+}
+
+#[test]
+fn vaults_kept_in_a_database_save_only_the_clients_that_changed() {
+    let mut state = AppState::unlocked_for_test();
+    for name in ["db01", "web01", "mail01"] {
+        state.repository.add(Client { name: name.to_string(), password: format!("{}-pass", name), ..Default::default() });
+    }
+    state.save_clients();
+    let path = state.repository.path().to_path_buf();
+    let key = *state.repository.key();
+    state.repository.use_database(true).unwrap();
+    state.save_clients();
+    assert!(crate::database::is_database(&path));
+    let rows = || -> std::collections::HashMap<String, Vec<u8>> {
+        let connection = rusqlite::Connection::open(&path).unwrap();
+        let mut statement = connection.prepare("SELECT id, data FROM clients").unwrap();
+        let rows = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?))).unwrap().collect::<Result<_, _>>().unwrap();
+        rows
+    };
+    let set_row = |id: &str, data: &[u8]| {
+        let connection = rusqlite::Connection::open(&path).unwrap();
+        connection.execute("UPDATE clients SET data = ?1 WHERE id = ?2", rusqlite::params![data, id]).unwrap();
+    };
+    let before = rows();
+
+    let mut web = state.repository.clients()[1].clone();
+    web.ip = "10.0.0.2".to_string();
+    state.repository.update(web.clone());
+    state.repository.move_client(&web.id, 0);
+    let mail = state.repository.clients()[2].id.clone();
+    state.repository.remove(&mail);
+    state.save_clients();
+    let after = rows();
+    let db = &state.repository.clients()[1].id;
+    assert_eq!(after[db], before[db]);
+    assert_ne!(after[&web.id], before[&web.id]);
+    assert!(!after.contains_key(&mail));
+
+    state.repository.close();
+    assert_eq!(state.repository.open([8; crate::encryption::KEY_SIZE]).unwrap_err(), "Incorrect master key.");
+    state.repository.open(key).unwrap();
+    assert!(state.repository.is_database());
+    let names: Vec<(&str, &str)> = state.repository.clients().iter().map(|c| (c.name.as_str(), c.password.as_str())).collect();
+    assert_eq!(names, [("web01", "web01-pass"), ("db01", "db01-pass")]);
+    assert_eq!(state.repository.get(&web.id).unwrap().ip, "10.0.0.2");
+
+    // A row put back from an older copy no longer matches the manifest.
+    set_row(&web.id, &before[&web.id]);
+    assert!(state.repository.open(key).unwrap_err().contains("tampered"));
+    set_row(&web.id, &after[&web.id]);
+    state.repository.open(key).unwrap();
+
+    state.repository.use_database(false).unwrap();
+    state.save_clients();
+    assert!(std::fs::read(&path).unwrap().starts_with(b"RDMF"));
+    state.repository.open(key).unwrap();
+    assert!(!state.repository.is_database());
+    assert_eq!(state.repository.clients().len(), 2);
+}
+
+#[test]
+fn encrypted_json_exports_carry_passwords() {
+    let mut harness = unlocked_with(&[]);
+    harness.state.repository.add(Client { name: "db01".to_string(), password: "hunter2".to_string(), ..Default::default() });
+    harness.state.handle_repository_events();
+    let path = std::env::temp_dir().join(format!("rdm-export-{}.json", crate::client::new_client_id()));
+
+    harness.menu("File", "Export");
+    assert!(harness.has_text("CSV files are plaintext"));
+    harness.click("JSON");
+    harness.fill("File:", &path.to_string_lossy());
+    harness.fill("Passphrase:", "velvet otter harbour crane");
+    harness.fill("Confirm:", "velvet otter harbour crane");
+    harness.click("Export");
+    assert_eq!(harness.state.mode, AppMode::Normal);
+    let exported = std::fs::read_to_string(&path).unwrap();
+    assert!(!exported.contains("db01") && !exported.contains("hunter2"));
+
+    harness.state.repository.replace_all(Vec::new());
+    harness.menu("File", "Import JSON");
+    harness.fill("File:", &path.to_string_lossy());
+    harness.fill("Passphrase (if the export is encrypted):", "wrong passphrase");
+    harness.click("Import");
+    assert!(harness.has_text("Incorrect export passphrase"));
+    harness.state.error_message = None;
+    harness.fill("Passphrase (if the export is encrypted):", "velvet otter harbour crane");
+    harness.click("Import");
+    let clients = harness.state.repository.clients();
+    assert_eq!((clients[0].name.as_str(), clients[0].password.as_str()), ("db01", "hunter2"));
+    let _ = std::fs::remove_file(path);
+}
+
+/// The nonce the vault at `path` was last written with: that of the
+/// length in its first slot, which is sealed after the contents.
+fn saved_nonce(path: &std::path::Path) -> [u8; crate::encryption::NONCE_SIZE] {
+    let data = std::fs::read(path).unwrap();
+    assert!(data.starts_with(b"RDMF"));
+    let start = 4 + 2 + 1 + 12 + crate::encryption::SALT_SIZE + 1 + crate::encryption::KEY_CHECK_SIZE + 8 + crate::encryption::SALT_SIZE;
+    data[start..start + crate::encryption::NONCE_SIZE].try_into().unwrap()
+}
+
+#[test]
+fn saves_under_one_key_never_reuse_a_nonce() {
+    use crate::encryption::nonce_counter;
+    let mut state = AppState::unlocked_for_test();
+    state.repository.add(Client { name: "db01".to_string(), ..Default::default() });
+    let path = state.repository.path().to_path_buf();
+    let mut nonces = std::collections::HashSet::new();
+    let mut last_counter = None;
+    for _ in 0..200 {
+        state.repository.save().unwrap();
+        let nonce = saved_nonce(&path);
+        assert!(nonces.insert(nonce), "nonce reused");
+        assert!(last_counter < Some(nonce_counter(&nonce)));
+        last_counter = Some(nonce_counter(&nonce));
+    }
+
+    // Reopening the vault carries on after the last counter in the file.
+    let key = *state.repository.key();
+    state.repository.close();
+    state.repository.open(key).unwrap();
+    state.repository.save().unwrap();
+    // One nonce for the contents, one for their length.
+    assert_eq!(Some(nonce_counter(&saved_nonce(&path))), last_counter.map(|counter| counter + 2));
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn aes_gcm_vaults_are_rewritten_with_aes_gcm_siv() {
+    use crate::encryption::{derive_key, encrypt, generate_salt, mac};
+    let path = std::env::temp_dir().join(format!("rdm-gcm-{}.json", crate::client::new_client_id()));
+    let clients = vec![Client { name: "db01".to_string(), ..Default::default() }];
+    let salt = generate_salt();
+    let key = derive_key("correct horse battery", &salt);
+    let payload = encrypt(&crate::vault::serialize_vault(&Default::default(), &clients, &[], &[], &[], &Default::default()), &key).unwrap();
+    let mut file = [b"RDMA".as_slice(), &salt, &(payload.len() as u64).to_le_bytes(), &payload].concat();
+    let tag = mac(&file, &key);
+    file.extend_from_slice(&tag);
+    std::fs::write(&path, file).unwrap();
+
+    let mut repository = crate::repository::EncryptedFileRepository::new(&path);
+    repository.unlock("correct horse battery").unwrap();
+    repository.save().unwrap();
+    assert_eq!(crate::encryption::nonce_counter(&saved_nonce(&path)), 1);
+    repository.unlock("correct horse battery").unwrap();
+    assert_eq!(repository.clients()[0].name, "db01");
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn importing_an_rdcman_file_keeps_groups_and_credentials() {
+    let rdg = r#"<?xml version="1.0" encoding="utf-8"?>
+<RDCMan programVersion="2.7" schemaVersion="3">
+  <file>
+    <credentialsProfiles>
+      <credentialsProfile inherit="None">
+        <profileName scope="Local">Ops</profileName>
+        <userName>ops</userName>
+        <password storeAsClearText="True">ops-pass</password>
+        <domain>CORP</domain>
+      </credentialsProfile>
+    </credentialsProfiles>
+    <properties><expanded>True</expanded><name>Datacenter</name></properties>
+    <group>
+      <properties><expanded>True</expanded><name>Web</name></properties>
+      <logonCredentials inherit="None"><profileName scope="File">Ops</profileName></logonCredentials>
+      <server>
+        <properties><displayName>Web 1</displayName><name>web1.corp</name></properties>
+      </server>
+      <group>
+        <properties><name>Legacy</name></properties>
+        <server>
+          <properties><name>web0.corp</name></properties>
+          <logonCredentials inherit="None">
+            <profileName scope="Local">Custom</profileName>
+            <userName>admin</userName>
+            <password>AQAAANCMnd8BFdERjHoAwE</password>
+            <domain>WEB0</domain>
+          </logonCredentials>
+        </server>
+      </group>
+    </group>
+    <smartGroup><properties><name>All web</name></properties></smartGroup>
+  </file>
+</RDCMan>"#;
+    let path = std::env::temp_dir().join(format!("rdm-import-{}.rdg", crate::client::new_client_id()));
+    std::fs::write(&path, rdg).unwrap();
+    let mut harness = unlocked_with(&[]);
+    harness.menu("File", "Import RDCMan");
+    assert_eq!(harness.state.mode, AppMode::ImportingRdg);
+    harness.fill("File:", &path.to_string_lossy());
+    harness.click("Import");
+    let _ = std::fs::remove_file(&path);
+
+    assert!(harness.has_text("Imported 2 servers in 2 groups"));
+    let clients = harness.state.repository.clients();
+    let web1 = clients.iter().find(|c| c.ip == "web1.corp").unwrap();
+    assert_eq!((web1.name.as_str(), web1.group.as_str()), ("Web 1", "Web"));
+    assert_eq!((web1.domain.as_str(), web1.username.as_str(), web1.password.as_str()), ("CORP", "ops", "ops-pass"));
+    let web0 = clients.iter().find(|c| c.ip == "web0.corp").unwrap();
+    assert_eq!((web0.name.as_str(), web0.group.as_str()), ("web0.corp", "Web/Legacy"));
+    assert_eq!((web0.domain.as_str(), web0.username.as_str()), ("WEB0", "admin"));
+    assert!(web0.password.is_empty());
+    assert!(harness.state.info_message.as_deref().unwrap().contains("1 passwords could not be decrypted"));
+}
+
+/// Answers each request with the JSON body `respond` returns for the server
+/// address and the request (head, blank line, body), closing the connection after every
+/// response. Returns the server address.
+fn serve_json(respond: impl Fn(&str, &str) -> String + Send + 'static) -> String {
+    use std::io::{BufRead, BufReader, Read, Write};
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = format!("http://{}", listener.local_addr().unwrap());
+    let base = address.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request = String::new();
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                    break;
+                }
+                request.push_str(&line);
+            }
+            let length = request
+                .lines()
+                .find_map(|line| line.to_ascii_lowercase().strip_prefix("content-length:").map(|length| length.trim().parse().unwrap()))
+                .unwrap_or(0);
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            request.push_str("\r\n");
+            request.push_str(&String::from_utf8(body).unwrap());
+            let body = respond(&base, &request);
+            let response = format!("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body);
+            stream.write_all(response.as_bytes()).unwrap();
+        }
+    });
+    address
+}
+
+#[test]
+fn pulling_from_netbox_tags_clients_by_site_and_role() {
+    use crate::events::AppEvent;
+    use crate::netbox::{pushed_tags, NetboxClient};
+    let address = serve_json(|base, request| {
+        assert!(request.contains("Authorization: Token secret-token"), "{}", request);
+        let path = request.split_whitespace().nth(1).unwrap();
+        if path.starts_with("/api/dcim/devices/?limit") {
+            format!(
+                r#"{{"next": "{}/api/dcim/devices/?page=2", "results": [
+                    {{"id": 1, "name": "web01", "primary_ip": {{"address": "10.0.0.5/24"}}, "site": {{"name": "Berlin", "slug": "ber"}},
+                      "device_role": {{"slug": "web"}}, "tags": [{{"name": "prod"}}]}},
+                    {{"id": 2, "name": "switch01", "primary_ip": null, "site": {{"name": "Berlin", "slug": "ber"}}}}
+                ]}}"#,
+                base
+            )
+        } else if path.starts_with("/api/dcim/devices/?page=2") {
+            r#"{"next": null, "results": [{"id": 3, "name": "db01", "primary_ip": {"address": "10.0.0.6/24"}, "site": null, "role": {"slug": "db"}}]}"#.to_string()
+        } else {
+            r#"{"next": null, "results": [{"id": 1, "name": "vm01", "primary_ip": {"address": "fd00::1/64"}, "site": {"name": "Paris", "slug": "par"}}]}"#.to_string()
+        }
+    });
+    let objects = NetboxClient::new(&format!("{}/", address), "secret-token".to_string()).pull().unwrap();
+    assert_eq!(objects.len(), 3);
+
+    let mut harness = unlocked_with(&[]);
+    harness.state.events.publish(AppEvent::NetboxPulled(Ok(objects.clone())));
+    harness.settle();
+    assert!(harness.has_text("NetBox: 3 new and 0 updated clients"));
+    let web01 = harness.state.repository.clients().iter().find(|c| c.name == "web01").unwrap().clone();
+    assert_eq!((web01.ip.as_str(), web01.group.as_str()), ("10.0.0.5", "NetBox/Berlin"));
+    assert_eq!(web01.tags, ["site:ber", "role:web", "prod"]);
+    let vm01 = harness.state.repository.clients().iter().find(|c| c.name == "vm01").unwrap();
+    assert_eq!((vm01.ip.as_str(), vm01.tags.as_slice()), ("fd00::1", ["site:par".to_string()].as_slice()));
+
+    // A second pull updates in place and keeps local tags.
+    let mut edited = web01.clone();
+    edited.tags.push("customer-a".to_string());
+    edited.password = "hunter2".to_string();
+    harness.state.repository.update(edited);
+    harness.state.apply_netbox_pull(objects);
+    let clients = harness.state.repository.clients();
+    assert_eq!(clients.len(), 3);
+    let web01 = clients.iter().find(|c| c.name == "web01").unwrap();
+    assert_eq!(web01.tags, ["prod", "customer-a", "site:ber", "role:web"]);
+    assert_eq!(web01.password, "hunter2");
+    assert_eq!(pushed_tags(web01), ["prod", "customer-a"]);
+}
+
+#[test]
+fn sealed_settings_only_open_with_the_vault_key() {
+    use crate::encryption::{open_setting, seal_setting};
+    let sealed = seal_setting("secret-token", &[7; crate::encryption::KEY_SIZE]).unwrap();
+    assert!(!sealed.contains("secret-token"));
+    assert_eq!(open_setting(&sealed, &[7; crate::encryption::KEY_SIZE]).unwrap(), "secret-token");
+    assert!(open_setting(&sealed, &[8; crate::encryption::KEY_SIZE]).is_none());
+}
+
+#[test]
+fn monitoring_status_shows_next_to_clients_by_address() {
+    use crate::events::AppEvent;
+    use crate::monitoring::{fetch, HostStatus, MonitoringSystem};
+    let zabbix = serve_json(|_, request| {
+        assert!(request.starts_with("POST /zabbix/api_jsonrpc.php"), "{}", request);
+        assert!(request.contains("Authorization: Bearer zabbix-token"), "{}", request);
+        assert!(request.contains(r#""method":"host.get""#), "{}", request);
+        r#"{"jsonrpc": "2.0", "id": 1, "result": [
+            {"host": "web01", "name": "Web 01", "status": "0", "maintenance_status": "0",
+             "interfaces": [{"ip": "10.0.0.5", "dns": "web01.example.com", "available": "1"}]},
+            {"host": "db01", "name": "db01", "status": "0", "maintenance_status": "1", "interfaces": [{"ip": "10.0.0.6", "dns": "", "available": "2"}]},
+            {"host": "old", "name": "old", "status": "1", "maintenance_status": "0", "interfaces": [{"ip": "10.0.0.9", "dns": "", "available": "2"}]}
+        ]}"#
+        .to_string()
+    });
+    let hosts = fetch(MonitoringSystem::Zabbix, &format!("{}/zabbix/", zabbix), "zabbix-token").unwrap();
+    let statuses: Vec<_> = hosts.iter().map(|host| (host.name.as_str(), host.status)).collect();
+    assert_eq!(statuses, [("Web 01", HostStatus::Up), ("db01", HostStatus::Maintenance)]);
+
+    let prtg = serve_json(|_, request| {
+        let path = request.split_whitespace().nth(1).unwrap();
+        assert!(path.starts_with("/api/table.json?content=devices") && path.contains("apitoken=prtg-token"), "{}", path);
+        r#"{"devices": [{"objid": 40, "device": "web01", "host": "WEB01.example.com", "status": "Down", "status_raw": 5},
+                        {"objid": 41, "device": "probe", "host": "", "status": "Up", "status_raw": 3}]}"#
+            .to_string()
+    });
+    let hosts = fetch(MonitoringSystem::Prtg, &prtg, "prtg-token").unwrap();
+    assert_eq!(hosts.len(), 1);
+    assert_eq!((hosts[0].addresses[0].as_str(), hosts[0].status), ("WEB01.example.com", HostStatus::Down));
+
+    let web = Client { name: "Web".to_string(), ip: "web01.example.com:3390".to_string(), ..Default::default() };
+    let other = Client { name: "Other".to_string(), ip: "10.1.1.1".to_string(), ..Default::default() };
+    let mut harness = unlocked_with(&[]);
+    harness.state.repository.replace_all(vec![web.clone(), other.clone()]);
+    harness.state.settings.monitoring_system = MonitoringSystem::Prtg;
+    harness.state.monitoring_busy = true;
+    harness.state.last_monitoring_poll = Some(std::time::Instant::now());
+    harness.state.events.publish(AppEvent::MonitoringPolled(Ok(hosts)));
+    harness.settle();
+    assert_eq!(harness.state.monitored_status(&web), Some(HostStatus::Down));
+    assert_eq!(harness.state.monitored_status(&other), None);
+    assert!(harness.has_text("down"));
+    assert!(harness.has_text("PRTG status"));
+
+    harness.state.events.publish(AppEvent::MonitoringPolled(Err("Could not reach PRTG".to_string())));
+    harness.settle();
+    assert!(harness.state.monitoring_status.is_empty());
+    assert!(harness.has_text("PRTG unavailable"));
+    harness.state.lock();
+    assert!(harness.state.last_monitoring_poll.is_none());
+}
+
+#[test]
+fn presence_announcements_round_trip_through_txt_properties() {
+    use crate::presence::{decode, encode, PeerSession};
+    let sessions = vec![
+        PeerSession { client_name: "SQL-02".to_string(), address: "10.0.0.12".to_string() },
+        PeerSession { client_name: "Jump host with a very long name ".repeat(10), address: "jump.example.com".to_string() },
+    ];
+    let properties = encode("alice", "WS-17", &sessions);
+    assert!(properties.iter().all(|(key, value)| key.len() + value.len() < 255));
+    let peer = decode(|key| properties.iter().find(|(name, _)| name == key).map(|(_, value)| value.as_str()));
+    assert_eq!((peer.user.as_str(), peer.machine.as_str()), ("alice", "WS-17"));
+    assert_eq!(peer.sessions.len(), 2);
+    assert_eq!(peer.sessions[0], sessions[0]);
+    assert_eq!(peer.sessions[1].address, "jump.example.com");
+    assert!(peer.connected_to("10.0.0.12"));
+    assert!(!peer.connected_to("10.0.0.13"));
+}
+
+#[test]
+fn reachability_checks_tell_open_ports_from_closed_ones() {
+    use crate::events::AppEvent;
+    use crate::reachability::{probe_all, Target};
+    let open = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let up = Client { name: "Up".to_string(), ip: open.local_addr().unwrap().to_string(), ..Default::default() };
+    let down = Client { name: "Down".to_string(), ip: "127.0.0.1".to_string(), port: Some(closed), ..Default::default() };
+    let info = Client { name: "Info".to_string(), ip: "127.0.0.1".to_string(), info_only: true, ..Default::default() };
+    assert!(Target::of(&info, &Default::default()).is_none());
+    let results = probe_all([&up, &down, &info].into_iter().filter_map(|client| Target::of(client, &Default::default())).collect());
+    assert_eq!(results.len(), 2);
+    assert!(results.iter().any(|(id, latency)| *id == up.id && latency.is_some()));
+    assert!(results.iter().any(|(id, latency)| *id == down.id && latency.is_none()));
+
+    let mut harness = unlocked_with(&[]);
+    harness.state.repository.replace_all(vec![up.clone(), down.clone()]);
+    // Let the check of the empty vault started by the first frame finish.
+    while harness.state.reachability_busy {
+        harness.settle();
+    }
+    harness.state.events.publish(AppEvent::ReachabilityChecked(results));
+    harness.settle();
+    assert_eq!(harness.state.reachability.len(), 2);
+    assert!(harness.has_text("⏺"));
+
+    harness.state.settings.reachability_check_seconds = None;
+    harness.settle();
+    assert!(harness.state.reachability.is_empty());
+    assert!(!harness.has_text("⏺"));
+}
+
+#[test]
+fn failed_connects_are_recorded_and_offered_as_recent() {
+    let mut harness = unlocked_with(&["Bastion"]);
+    harness.state.settings.ssh_client = "/nonexistent/ssh-client".to_string();
+    harness.state.settings.reachability_check_seconds = None;
+    harness.state.reachability.clear();
+    let mut bastion = harness.state.repository.clients()[0].clone();
+    bastion.protocol = crate::client::Protocol::Ssh;
+    harness.state.repository.update(bastion.clone());
+    assert!(!harness.has_text("Recent:"));
+    harness.state.connect(&bastion, false);
+    let failure = &harness.state.connect_failure.as_ref().expect("failure window").error.failure;
+    assert_eq!(*failure, crate::diagnosis::Failure::ViewerMissing("/nonexistent/ssh-client".to_string()));
+    let record = harness.state.repository.history.last().unwrap();
+    assert_eq!(record.client_id, bastion.id);
+    assert!(record.error.as_ref().unwrap().contains("/nonexistent/ssh-client"));
+    harness.settle();
+    assert!(harness.has_text("/nonexistent/ssh-client could not be found"));
+    harness.click("Close");
+    assert!(harness.state.connect_failure.is_none());
+    assert!(harness.has_text("Recent:"));
+
+    harness.click("History");
+    assert!(harness.has_text("failed: Failed to launch the SSH client"));
+    harness.click("Clear History…");
+    harness.click("Clear History");
+    assert!(harness.state.repository.history.is_empty());
+    assert!(harness.has_text("No connections recorded."));
+}
+
+#[test]
+fn unreachable_hosts_are_checked_again_before_connecting() {
+    let mut harness = unlocked_with(&["db01"]);
+    harness.state.settings.reachability_check_seconds = None;
+    let mut client = harness.state.repository.clients()[0].clone();
+    client.ip = "db01.invalid".to_string();
+    harness.state.repository.update(client.clone());
+    harness.state.reachability.insert(client.id.clone(), None);
+    harness.state.connect(&client, false);
+    let failure = &harness.state.connect_failure.as_ref().expect("failure window").error.failure;
+    assert_eq!(*failure, crate::diagnosis::Failure::HostUnresolvable("db01.invalid".to_string()));
+    assert!(harness.state.sessions.count() == 0);
+
+    harness.settle();
+    assert!(harness.has_text("Connect the VPN if the host is on an internal network."));
+    harness.click("Open Diagnostics");
+    for _ in 0..100 {
+        if harness.state.connect_failure.as_ref().is_some_and(|failure| failure.checks.is_some()) {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        harness.step();
+    }
+    harness.settle();
+    assert!(harness.has_text("db01.invalid does not resolve"));
+    let viewer = crate::launcher::RdpViewer::detect().program();
+    assert!(harness.has_text(&format!("{} not found", viewer)) || crate::diagnosis::find_program(viewer).is_some());
+}
+
+#[test]
+fn clients_bound_to_an_interface_only_connect_through_it() {
+    let mut harness = unlocked_with(&["db01"]);
+    harness.state.settings.reachability_check_seconds = None;
+    let mut client = harness.state.repository.clients()[0].clone();
+    client.ip = "127.0.0.1".to_string();
+    client.interface = "no-such-interface".to_string();
+    harness.state.repository.update(client.clone());
+    harness.state.connect(&client, false);
+    let failure = &harness.state.connect_failure.as_ref().expect("failure window").error;
+    assert_eq!(failure.failure, crate::diagnosis::Failure::WrongInterface("no-such-interface".to_string()));
+    assert!(failure.message.contains("there is no interface no-such-interface"), "{}", failure.message);
+
+    // Loopback traffic leaves from the loopback address.
+    assert!(crate::diagnosis::check_route("127.0.0.1", "127.0.0.1", 3389).is_ok());
+    assert!(crate::diagnosis::check_route("127.0.0.2", "127.0.0.1", 3389).is_err());
+}
+
+#[test]
+fn find_duplicates_merges_the_kept_client_and_trashes_the_rest() {
+    let mut harness = unlocked_with(&["db01", "DB01", "web01"]);
+    harness.menu("Tools", "Find Duplicates");
+    harness.settle();
+    assert!(harness.has_text("db01 (db01.example)"));
+    assert!(!harness.has_text("web01 (web01.example)"));
+    harness.click("Keep");
+    harness.settle();
+    assert!(harness.has_text("No clients look alike."));
+    let trashed: Vec<&str> = harness.state.repository.clients().iter().filter(|c| c.trashed_at.is_some()).map(|c| c.name.as_str()).collect();
+    assert_eq!(trashed, ["DB01"]);
+}
+
+#[test]
+fn the_client_list_is_driven_from_the_keyboard() {
+    let mut harness = unlocked_with(&["db01", "web01", "web02"]);
+    harness.state.launcher = Launcher::DryRun;
+    let ids: Vec<String> = harness.state.repository.clients().iter().map(|c| c.id.clone()).collect();
+    harness.state.client_list.selected = None;
+    harness.key(Key::ArrowUp, Modifiers::NONE);
+    assert_eq!(harness.state.client_list.selected.as_ref(), Some(&ids[2]));
+    harness.key(Key::ArrowDown, Modifiers::NONE);
+    harness.key(Key::ArrowUp, Modifiers::NONE);
+    assert_eq!(harness.state.client_list.selected.as_ref(), Some(&ids[1]));
+
+    harness.key(Key::Enter, Modifiers::NONE);
+    assert_eq!(harness.state.sessions.active()[0].client_name, "web01");
+
+    harness.key(Key::F, Modifiers::COMMAND);
+    harness.type_text("web");
+    harness.key(Key::ArrowDown, Modifiers::NONE);
+    assert_eq!(harness.state.client_list.filter, "web");
+    assert_eq!(harness.state.client_list.selected.as_ref(), Some(&ids[1]));
+
+    harness.key(Key::E, Modifiers::COMMAND);
+    assert_eq!(harness.state.mode, AppMode::Editing);
+    harness.click("Cancel");
+    harness.key(Key::Delete, Modifiers::NONE);
+    assert_eq!(harness.state.mode, AppMode::Removing);
+    harness.click("Confirm");
+    assert_eq!(harness.state.repository.clients().len(), 2);
+    harness.key(Key::N, Modifiers::COMMAND);
+    assert_eq!(harness.state.mode, AppMode::Adding);
+}
+
+#[test]
+fn each_vault_reopens_with_the_workspace_it_was_left_in() {
+    let mut harness = Harness::new(AppState::for_test());
+    harness.key(Key::Tab, Modifiers::NONE);
+    harness.type_text("correct horse battery");
+    harness.key(Key::Tab, Modifiers::NONE);
+    harness.type_text("correct horse battery");
+    harness.click("Create");
+    harness.wait_for_unlock();
+    for (name, group) in [("db01", "Databases"), ("web01", "Web"), ("web02", "Web")] {
+        harness.state.repository.add(Client { name: name.to_string(), ip: format!("{}.example", name), group: group.to_string(), ..Default::default() });
+    }
+    harness.state.save_clients();
+    let web01 = harness.state.repository.clients().iter().find(|c| c.name == "web01").unwrap().id.clone();
+    harness.settle();
+    harness.state.client_list.selected = Some(web01.clone());
+    harness.click("Databases (1)");
+    harness.key(Key::F, Modifiers::COMMAND);
+    harness.type_text("b0");
+    harness.settle();
+    harness.state.lock();
+    assert!(harness.state.client_list.filter.is_empty());
+    assert!(harness.state.client_list.collapsed.is_empty());
+
+    let vault = harness.state.repository.path().to_path_buf();
+    assert!(crate::layout::load(&harness.state.layouts_path, &vault, &[7; crate::encryption::KEY_SIZE]).is_none());
+    harness.state.unlock_form.master_key = "correct horse battery".to_string();
+    harness.state.unlock_and_wait();
+    harness.settle();
+    assert_eq!(harness.state.client_list.filter, "b0");
+    assert_eq!(harness.state.client_list.selected, Some(web01));
+    assert!(harness.state.client_list.collapsed.contains("Databases"));
+    let _ = std::fs::remove_file(&harness.state.layouts_path);
+}
+
+#[test]
+fn clients_are_reordered_and_moved_between_folders_by_dragging() {
+    let mut harness = unlocked_with(&["db01", "web01", "web02"]);
+    let web02 = harness.state.repository.clients()[2].id.clone();
+    harness.state.client_list.selected = Some(web02.clone());
+    let mut client = harness.state.repository.clients()[2].clone();
+    client.group = "Web".to_string();
+    harness.state.repository.update(client);
+    harness.settle();
+
+    harness.drag("web01", "db01");
+    let names: Vec<&str> = harness.state.repository.clients().iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, ["web01", "db01", "web02"]);
+    harness.drag("db01", "Web (1)");
+    let db01 = harness.state.repository.clients().iter().find(|c| c.name == "db01").unwrap();
+    assert_eq!(db01.group, "Web");
+    assert!(harness.has_text("Web (2)"));
+    assert_eq!(harness.state.client_list.selected, Some(web02));
+}
+
+#[test]
+fn a_folder_is_exported_as_one_rdp_file_per_client() {
+    let mut harness = unlocked_with(&["db01", "web01", "web02"]);
+    for (index, group) in [(1, "Web"), (2, "Web/Old")] {
+        let mut client = harness.state.repository.clients()[index].clone();
+        client.group = group.to_string();
+        harness.state.repository.update(client);
+    }
+    harness.settle();
+    let folder = std::env::temp_dir().join(format!("rdm-rdp-{}", crate::client::new_client_id()));
+
+    harness.right_click("Web (2)");
+    harness.click("Export .rdp files…");
+    harness.fill("Folder:", folder.to_str().unwrap());
+    harness.click("Export");
+    assert!(harness.has_text("Exported 2 .rdp files"));
+    let read = |path: std::path::PathBuf| {
+        let data = std::fs::read(path).unwrap();
+        String::from_utf16(&data[2..].chunks(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect::<Vec<_>>()).unwrap()
+    };
+    assert!(read(folder.join("web01.rdp")).contains("full address:s:web01.example"));
+    assert!(read(folder.join("Old").join("web02.rdp")).contains("prompt for credentials:i:1"));
+    assert!(!folder.join("db01.rdp").exists());
+    let _ = std::fs::remove_dir_all(folder);
+}
+
+#[test]
+fn inside_a_remote_session_passwords_stay_off_the_clipboard() {
+    let mut harness = unlocked_with(&["db01", "jump01"]);
+    harness.state.launcher = Launcher::DryRun;
+    harness.state.remote_session = true;
+    let mut clients = harness.state.repository.clients().to_vec();
+    clients[0].password = "rdp secret".to_string();
+    clients[1].protocol = Protocol::Ssh;
+    clients[1].password = "ssh secret".to_string();
+    harness.state.repository.replace_all(clients.clone());
+    harness.settle();
+    assert!(harness.has_text("Remote session"));
+
+    harness.state.request_connect(&clients[0]);
+    assert!(harness.state.pending_connect.is_none());
+    assert_eq!(harness.state.sessions.count(), 1);
+
+    harness.state.request_connect(&clients[1]);
+    harness.settle();
+    assert!(harness.has_text("runs inside a Remote Desktop session"));
+    harness.click("Connect Without Copying");
+    assert!(harness.state.pending_connect.is_none());
+    assert_eq!(harness.state.sessions.count(), 2);
+}
+
+#[test]
+fn a_password_copied_for_an_rdp_connect_is_cleared_from_the_clipboard() {
+    let mut harness = unlocked_with(&["db01"]);
+    harness.state.launcher = Launcher::DryRun;
+    let mut client = harness.state.repository.clients()[0].clone();
+    client.password = "rdp secret".to_string();
+    assert!(client.account().is_none());
+
+    harness.state.connect(&client, true);
+    harness.state.settings.clear_clipboard_after_seconds = None;
+    harness.state.connect(&client, true);
+    assert_eq!(harness.state.sessions.count(), 2);
+    let log = crate::launcher::DRY_RUN_LOG.with(|log| log.take());
+    let copies = log.iter().filter(|line| line.starts_with("would copy the password of db01")).collect::<Vec<_>>();
+    assert_eq!(copies, ["would copy the password of db01 to the clipboard: true, cleared after 30 seconds", "would copy the password of db01 to the clipboard: true"]);
+}
+
+#[test]
+fn confirmations_are_asked_for_as_configured() {
+    let mut harness = unlocked_with(&["db01", "web01", "web02"]);
+    let mut clients = harness.state.repository.clients().to_vec();
+    clients[0].password = "hunter2".to_string();
+    clients[1].tags = vec!["Prod".to_string()];
+    harness.state.repository.replace_all(clients.clone());
+    harness.state.launcher = Launcher::DryRun;
+    let confirmations = &mut harness.state.settings.confirmations;
+    confirmations.remove_client = false;
+    confirmations.plaintext_export = true;
+    confirmations.reveal_password = true;
+    confirmations.connect_tags = vec!["prod".to_string()];
+    harness.settle();
+
+    harness.state.client_list.selected = Some(clients[2].id.clone());
+    harness.key(Key::Delete, Modifiers::NONE);
+    assert_eq!(harness.state.repository.clients().len(), 2);
+
+    harness.state.request_connect(&clients[1]);
+    harness.settle();
+    assert!(harness.has_text("web01 is tagged Prod. Connect to it?"));
+    assert_eq!(harness.state.sessions.count(), 0);
+    harness.click("Connect");
+    assert_eq!(harness.state.sessions.count(), 1);
+
+    let path = std::env::temp_dir().join(format!("rdm-export-{}.csv", crate::client::new_client_id()));
+    harness.menu("File", "Export");
+    harness.fill("File:", &path.to_string_lossy());
+    harness.click("Export");
+    assert!(harness.has_text("This file will not be encrypted"));
+    assert!(!path.exists());
+    harness.click("Export Unencrypted");
+    assert!(path.exists());
+    let _ = std::fs::remove_file(path);
+
+    harness.state.client_list.selected = Some(clients[0].id.clone());
+    harness.menu("File", "Edit");
+    harness.click("👁");
+    assert!(!harness.has_text("hunter2"));
+    harness.click("Show");
+    assert!(harness.has_text("hunter2"));
+}
+
+/// A self-signed certificate for `rdp.test` and its PKCS#8 key, for the fake
+/// RDP server.
+const TEST_CERTIFICATE: &str = "MIIBfDCCASOgAwIBAgIUSNywis0IO4+9kR7vL0kgIrxQTp8wCgYIKoZIzj0EAwIwEzERMA8GA1UEAwwIcmRwLnRlc3QwIBcNMjYxMDE2MTQyMDQ0WhgPMjEyNjA5MjIxNDIwNDRaMBMxETAPBgNVBAMMCHJkcC50ZXN0MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAECMrgPhrnaU5TzhbdMfJ6UBZ/lbd2PIzvb5CjyyOzAeE3LjTbEzkrjDMwzYNluzkOD82l1t8xvZ42eJ5rSCxnDqNTMFEwHQYDVR0OBBYEFGArNUPijyBw5v98nA+9bhH/w9qWMB8GA1UdIwQYMBaAFGArNUPijyBw5v98nA+9bhH/w9qWMA8GA1UdEwEB/wQFMAMBAf8wCgYIKoZIzj0EAwIDRwAwRAIgIltsJgrhO3FF/gQEbdtF/HuI5Enuzbfj35GfA3UPRlwCIGdbdABy+X1uSTOkQGZ9q8PoEsKaKf/E744p/GhbX/8z";
+const TEST_KEY: &str = "MIGHAgEAMBMGByqGSM49AgEGCCqGSM49AwEHBG0wawIBAQQgN+zjgXijG5meHOIBPcj7ziMAE7n55rtkwEpcdSmS0UihRANCAAQIyuA+GudpTlPOFt0x8npQFn+Vt3Y8jO9vkKPLI7MB4TcuNNsTOSuMMzDNg2W7OQ4PzaXW3zG9njZ4nmtILGcO";
+
+/// An RDP server that picks Network Level Authentication for `connections`
+/// connections and answers the first CredSSP message with the NTLM token
+/// `challenge`. Returns its port, and a handle to what each connection sent
+/// once TLS was up.
+fn serve_rdp(connections: usize, challenge: &'static [u8]) -> (u16, std::thread::JoinHandle<Vec<Vec<u8>>>) {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+    use std::io::{Read, Write};
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let certificate = CertificateDer::from(STANDARD.decode(TEST_CERTIFICATE).unwrap());
+    let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(STANDARD.decode(TEST_KEY).unwrap()));
+    let config = rustls::ServerConfig::builder_with_provider(std::sync::Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(vec![certificate], key)
+        .unwrap();
+    let config = std::sync::Arc::new(config);
+    let der = |tag: u8, content: &[u8]| [&[tag, content.len() as u8][..], content].concat();
+    let answer = der(0x30, &[der(0xA0, &der(0x02, &[6])), der(0xA1, &der(0x30, &der(0x30, &der(0xA0, &der(0x04, challenge)))))].concat());
+    let server = std::thread::spawn(move || {
+        let mut received = Vec::new();
+        for stream in listener.incoming().take(connections) {
+            let mut stream = stream.unwrap();
+            let mut request = [0; 19];
+            stream.read_exact(&mut request).unwrap();
+            stream.write_all(&[3, 0, 0, 19, 14, 0xD0, 0, 0, 0, 0, 0, 2, 0, 8, 0, 2, 0, 0, 0]).unwrap();
+            let mut tls = rustls::StreamOwned::new(rustls::ServerConnection::new(config.clone()).unwrap(), stream);
+            let mut handshaken = true;
+            while handshaken && tls.conn.is_handshaking() {
+                handshaken = tls.conn.complete_io(&mut tls.sock).is_ok();
+            }
+            let mut buffer = [0; 4096];
+            let read = if handshaken { tls.read(&mut buffer).unwrap_or(0) } else { 0 };
+            if read > 0 {
+                let _ = tls.write_all(&answer);
+            }
+            received.push(buffer[..read].to_vec());
+        }
+        received
+    });
+    (port, server)
+}
+
+#[test]
+fn the_sign_in_check_sends_nothing_to_a_server_whose_certificate_changed() {
+    use crate::nla;
+    let pins = std::env::temp_dir().join(format!("rdm-test-{}.pins.json", crate::client::new_client_id()));
+    let (port, server) = serve_rdp(1, b"NTLMSSP\0");
+    let pinned = format!("{{\"127.0.0.1:{}\": \"{}\"}}", port, "00".repeat(32));
+    std::fs::write(&pins, &pinned).unwrap();
+
+    let checked = nla::check("127.0.0.1", port, "admin", "CORP", "hunter2", &pins);
+    assert!(checked.unwrap_err().contains("certificate changed"));
+    assert!(server.join().unwrap()[0].is_empty());
+    assert_eq!(std::fs::read_to_string(&pins).unwrap(), pinned);
+    let _ = std::fs::remove_file(pins);
+}
+
+#[test]
+fn the_sign_in_check_pins_a_new_server_and_gives_up_on_a_malformed_challenge() {
+    use crate::nla::{self, Outcome};
+    let pins = std::env::temp_dir().join(format!("rdm-test-{}.pins.json", crate::client::new_client_id()));
+    let (port, server) = serve_rdp(2, b"NTLMSSP\0\x02\0\0\0");
+
+    assert_eq!(nla::check("127.0.0.1", port, "admin", "CORP", "hunter2", &pins), Ok(Outcome::Pinned));
+    assert!(std::fs::read_to_string(&pins).unwrap().contains(&format!("127.0.0.1:{}", port)));
+    let checked = nla::check("127.0.0.1", port, "admin", "CORP", "hunter2", &pins);
+    assert!(checked.unwrap_err().contains("NTLM challenge"));
+    let received = server.join().unwrap();
+    assert!(received[0].is_empty());
+    assert!(received[1].windows(7).any(|window| window == b"NTLMSSP"));
+    let _ = std::fs::remove_file(pins);
+}
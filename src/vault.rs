@@ -0,0 +1,90 @@
+use crate::client::{Client, Contact};
+use crate::composition::MonthlyCounts;
+use crate::credential::Credential;
+use crate::history::ConnectionRecord;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Extension for vault files opened by double-clicking them.
+pub const VAULT_EXTENSION: &str = "rdm";
+
+/// Rules stored inside the encrypted vault so every install that opens it
+/// enforces the same baseline.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct VaultPolicy {
+    /// Minimum estimated master key strength, in bits.
+    #[serde(default)]
+    pub min_master_key_bits: u32,
+    /// Upper bound for the idle auto-lock; users may only pick shorter times.
+    #[serde(default)]
+    pub max_auto_lock_minutes: Option<u32>,
+    /// Whether passwords may be placed on the clipboard when connecting.
+    #[serde(default = "default_true")]
+    pub clipboard_allowed: bool,
+}
+
+impl Default for VaultPolicy {
+    fn default() -> Self {
+        Self {
+            min_master_key_bits: 0,
+            max_auto_lock_minutes: None,
+            clipboard_allowed: true,
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Decrypted vault contents.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredVault {
+    Current {
+        #[serde(default)]
+        policy: VaultPolicy,
+        clients: Vec<Client>,
+        #[serde(default)]
+        history: Vec<ConnectionRecord>,
+        #[serde(default)]
+        credentials: Vec<Credential>,
+        #[serde(default)]
+        composition: Vec<MonthlyCounts>,
+        #[serde(default)]
+        group_contacts: BTreeMap<String, Contact>,
+    },
+    /// Vaults saved before policies existed held only the client list.
+    Legacy(Vec<Client>),
+}
+
+#[derive(Serialize)]
+struct VaultRef<'a> {
+    policy: &'a VaultPolicy,
+    clients: &'a [Client],
+    history: &'a [ConnectionRecord],
+    credentials: &'a [Credential],
+    composition: &'a [MonthlyCounts],
+    group_contacts: &'a BTreeMap<String, Contact>,
+}
+
+/// Everything a vault holds besides its policy.
+pub type VaultContents = (VaultPolicy, Vec<Client>, Vec<ConnectionRecord>, Vec<Credential>, Vec<MonthlyCounts>, BTreeMap<String, Contact>);
+
+pub fn parse_vault(data: &[u8]) -> Result<VaultContents, String> {
+    match serde_json::from_slice(data).map_err(|e| e.to_string())? {
+        StoredVault::Current { policy, clients, history, credentials, composition, group_contacts } => Ok((policy, clients, history, credentials, composition, group_contacts)),
+        StoredVault::Legacy(clients) => Ok((VaultPolicy::default(), clients, Vec::new(), Vec::new(), Vec::new(), BTreeMap::new())),
+    }
+}
+
+pub fn serialize_vault(
+    policy: &VaultPolicy,
+    clients: &[Client],
+    history: &[ConnectionRecord],
+    credentials: &[Credential],
+    composition: &[MonthlyCounts],
+    group_contacts: &BTreeMap<String, Contact>,
+) -> Vec<u8> {
+    serde_json::to_vec(&VaultRef { policy, clients, history, credentials, composition, group_contacts }).unwrap()
+}
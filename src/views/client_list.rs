@@ -0,0 +1,826 @@
+use crate::app::AppState;
+use crate::attachment::Attachment;
+use crate::client::{all_tags, Client, ClientGroup, RdsFarm};
+use crate::collation;
+use crate::credential;
+use crate::expiry::{expiry_state, today, ExpiryState};
+use crate::fingerprint::OsGuess;
+use crate::monitoring::HostStatus;
+use crate::presence::Peer;
+use crate::reachability::{Target, SLOW};
+use crate::reference::{expand_template, note_parts, open_url, NotePart};
+use crate::repository::ClientRepository;
+use crate::search::{find_matches, highlight, highlight_in, SearchResult};
+use crate::sessions::ActiveSession;
+use crate::views::history_view::when;
+use chrono::NaiveDate;
+use std::collections::{BTreeSet, HashMap};
+use eframe::egui;
+
+/// Selection and filter of the client list.
+#[derive(Default)]
+pub struct ClientList {
+    /// Id of the selected client, so the selection stays on it when the
+    /// clients are removed, reordered or replaced around it.
+    pub selected: Option<String>,
+    /// Text typed into the filter box; empty shows every client.
+    pub filter: String,
+    /// Only clients with this tag are listed.
+    pub tag: Option<String>,
+    /// Scroll the selected row into view on the next frame, after the
+    /// selection was moved with the keyboard.
+    pub scroll_to_selection: bool,
+    /// Paths of the folders the user closed; all others are open.
+    pub collapsed: BTreeSet<String>,
+}
+
+impl ClientList {
+    pub fn selected_client<'a>(&self, clients: &'a [Client]) -> Option<&'a Client> {
+        let id = self.selected.as_deref()?;
+        clients.iter().find(|client| client.id == id)
+    }
+
+    pub fn is_selected(&self, client: &Client) -> bool {
+        self.selected.as_deref() == Some(client.id.as_str())
+    }
+
+    pub fn select(&mut self, client: &Client) {
+        self.selected = Some(client.id.clone());
+    }
+
+    /// Drops the selection if its client was removed or moved to the Trash.
+    pub fn retain(&mut self, clients: &[Client]) {
+        if self.selected_client(clients).is_none_or(|client| client.trashed_at.is_some()) {
+            self.selected = None;
+        }
+    }
+}
+
+/// Where a client dragged in the list was dropped.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DropTarget {
+    /// Onto another client's row: it goes above that client, into its folder.
+    Before(String),
+    /// Onto a folder header, or the top-level strip for `""`: it goes to
+    /// the end of that folder.
+    Folder(String),
+}
+
+/// Drag payload of a client row.
+struct DraggedClient(String);
+
+/// The filter box, focused by the Find shortcut.
+pub fn filter_id() -> egui::Id {
+    egui::Id::new("client_filter")
+}
+
+/// A color per tag, derived from its name so it stays the same everywhere
+/// without being stored.
+pub fn tag_color(tag: &str) -> egui::Color32 {
+    let hash = tag.to_lowercase().bytes().fold(5381u32, |hash, byte| hash.wrapping_mul(33) ^ byte as u32);
+    let hue = (hash % 360) as f32 / 360.0;
+    egui::ecolor::Hsva::new(hue, 0.55, 0.75, 1.0).into()
+}
+
+/// A folder's own clients in list order: those outside a farm, then the
+/// members of each farm together.
+fn farm_sections<'a>(indices: &[usize], clients: &'a [Client]) -> (Vec<usize>, Vec<(&'a RdsFarm, Vec<usize>)>) {
+    let mut plain = Vec::new();
+    let mut farms: Vec<(&RdsFarm, Vec<usize>)> = Vec::new();
+    for &index in indices {
+        match &clients[index].farm {
+            Some(farm) => match farms.iter_mut().find(|(known, _)| known.name.eq_ignore_ascii_case(&farm.name)) {
+                Some((_, members)) => members.push(index),
+                None => farms.push((farm, vec![index])),
+            },
+            None => plain.push(index),
+        }
+    }
+    (plain, farms)
+}
+
+fn tag_chip(ui: &mut egui::Ui, tag: &str, selected: bool) -> egui::Response {
+    let color = tag_color(tag);
+    let text = egui::RichText::new(tag).small().color(if selected { egui::Color32::WHITE } else { color });
+    let fill = if selected { color } else { egui::Color32::TRANSPARENT };
+    ui.add(egui::Button::new(text).small().fill(fill).stroke(egui::Stroke::new(1.0, color)))
+}
+
+/// What every row of one frame's client list draws from.
+struct ClientRows<'a> {
+    clients: &'a [Client],
+    matches: &'a HashMap<String, SearchResult>,
+    active: &'a [ActiveSession],
+    /// Teammates on the LAN with the sessions they have open.
+    peers: &'a [Peer],
+    today: NaiveDate,
+}
+
+impl AppState {
+    pub(crate) fn client_list(&mut self, ui: &mut egui::Ui) {
+        if self.client_list.filter.is_empty() && self.client_list.tag.is_none() {
+            self.recent_connections(ui);
+        }
+        ui.horizontal(|ui| {
+            ui.label("Filter:");
+            ui.add(egui::TextEdit::singleline(&mut self.client_list.filter).id(filter_id()).hint_text("name, IP, group, ..."));
+            if !self.client_list.filter.is_empty() && ui.small_button("✖").on_hover_text("Clear filter").clicked() {
+                self.client_list.filter.clear();
+            }
+        });
+        let clients = self.repository.clients().to_vec();
+        self.tag_bar(ui, &clients);
+        let filter = self.client_list.filter.trim().to_string();
+        let (tree, matches) = self.listed_tree(&clients);
+        if tree.count() == 0 && !filter.is_empty() {
+            ui.label(format!("No clients match \"{}\".", filter));
+        }
+        let active = self.sessions.active();
+        let today = today();
+        let peers = self.presence.as_ref().map(|presence| presence.peers()).unwrap_or_default();
+        let rows = ClientRows { clients: &clients, matches: &matches, active: &active, peers: &peers, today };
+        self.client_group(ui, &tree, &rows);
+        if egui::DragAndDrop::has_payload_of_type::<DraggedClient>(ui.ctx()) {
+            let (_, dropped) = ui.dnd_drop_zone::<DraggedClient, _>(egui::Frame::group(ui.style()), |ui| {
+                ui.weak("Drop here to move it out of its folder");
+            });
+            if let Some(dragged) = dropped {
+                self.drop_client(&dragged.0, &DropTarget::Folder(String::new()));
+            }
+        }
+    }
+
+    /// The clients that pass the filter and the tag, with why each matched.
+    fn listed_tree(&self, clients: &[Client]) -> (ClientGroup, HashMap<String, SearchResult>) {
+        let filter = self.client_list.filter.trim();
+        let tag = self.client_list.tag.as_ref();
+        let matches: HashMap<String, SearchResult> =
+            self.search_index.search(filter).into_iter().map(|result| (result.client_id.clone(), result)).collect();
+        let tree = if filter.is_empty() && tag.is_none() {
+            ClientGroup::build(clients)
+        } else {
+            ClientGroup::build_filtered(clients, |client| {
+                (filter.is_empty() || matches.contains_key(&client.id)) && tag.is_none_or(|tag| client.has_tag(tag))
+            })
+        };
+        (tree, matches)
+    }
+
+    /// Ids of the listed clients from top to bottom, including those in
+    /// collapsed folders.
+    pub(crate) fn listed_clients(&self) -> Vec<String> {
+        fn collect(group: &ClientGroup, clients: &[Client], ids: &mut Vec<String>) {
+            for child in &group.groups {
+                collect(child, clients, ids);
+            }
+            let (plain, farms) = farm_sections(&group.clients, clients);
+            let members = farms.into_iter().flat_map(|(_, members)| members);
+            ids.extend(plain.into_iter().chain(members).map(|index| clients[index].id.clone()));
+        }
+        let clients = self.repository.clients();
+        let mut ids = Vec::new();
+        collect(&self.listed_tree(clients).0, clients, &mut ids);
+        ids
+    }
+
+    /// Selects the client `offset` rows below the selection, or the first
+    /// or last one when nothing is selected.
+    pub(crate) fn move_selection(&mut self, offset: isize) {
+        let ids = self.listed_clients();
+        if ids.is_empty() {
+            return;
+        }
+        let current = self.client_list.selected.as_ref().and_then(|selected| ids.iter().position(|id| id == selected));
+        let index = match current {
+            Some(index) => index.saturating_add_signed(offset).min(ids.len() - 1),
+            None if offset < 0 => ids.len() - 1,
+            None => 0,
+        };
+        self.client_list.selected = Some(ids[index].clone());
+        self.client_list.scroll_to_selection = true;
+    }
+
+    /// Moves client `id` to where it was dropped, changing its folder when
+    /// dropped into another one, and saves the new order.
+    pub fn drop_client(&mut self, id: &str, target: &DropTarget) {
+        let folder_of = |client: &Client| client.group_path().collect::<Vec<_>>().join("/");
+        let Some(mut client) = self.repository.get(id).cloned() else {
+            return;
+        };
+        let folder = match target {
+            DropTarget::Before(other) if other == id => return,
+            DropTarget::Before(other) => match self.repository.get(other) {
+                Some(other) => folder_of(other),
+                None => return,
+            },
+            DropTarget::Folder(path) => path.clone(),
+        };
+        if folder_of(&client) != folder {
+            client.group = folder.clone();
+            self.repository.update(client);
+        }
+        let from = self.repository.position(id).expect("the client was just found");
+        let to = match target {
+            DropTarget::Before(other) => self.repository.position(other).unwrap_or(from),
+            DropTarget::Folder(_) => {
+                let clients = self.repository.clients();
+                clients.iter().rposition(|c| c.id != id && folder_of(c) == folder).map_or(clients.len(), |last| last + 1)
+            }
+        };
+        // Taking the client out first shifts the clients after it up.
+        self.repository.move_client(id, if from < to { to - 1 } else { to });
+        self.save_clients();
+    }
+
+    /// Puts folders and the clients in each of them in alphabetical order,
+    /// ignoring case and accents, and saves the new order.
+    pub fn sort_clients_by_name(&mut self) {
+        let mut clients = self.repository.clients().to_vec();
+        clients.sort_by(|a, b| collation::compare_paths(a.group_path(), b.group_path()).then_with(|| collation::compare(&a.name, &b.name)));
+        self.repository.replace_all(clients);
+        self.save_clients();
+    }
+
+    /// One chip per tag in use; clicking a chip lists only its clients,
+    /// clicking it again shows everyone.
+    fn tag_bar(&mut self, ui: &mut egui::Ui, clients: &[Client]) {
+        let tags = all_tags(clients);
+        if self.client_list.tag.as_ref().is_some_and(|tag| !tags.iter().any(|known| known.eq_ignore_ascii_case(tag))) {
+            self.client_list.tag = None;
+        }
+        if tags.is_empty() {
+            return;
+        }
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Tags:");
+            for tag in tags {
+                let selected = self.client_list.tag.as_ref().is_some_and(|current| current.eq_ignore_ascii_case(&tag));
+                if tag_chip(ui, &tag, selected).clicked() {
+                    self.client_list.tag = if selected { None } else { Some(tag) };
+                }
+            }
+        });
+    }
+
+    /// Subgroups as collapsible folders, followed by the group's own clients.
+    /// While filtering every folder is forced open so no match stays hidden.
+    fn client_group(&mut self, ui: &mut egui::Ui, group: &ClientGroup, rows: &ClientRows) {
+        let filtering = !self.client_list.filter.trim().is_empty() || self.client_list.tag.is_some();
+        for child in &group.groups {
+            let open = filtering || !self.client_list.collapsed.contains(&child.path);
+            let response = egui::CollapsingHeader::new(format!("{} ({})", child.name, child.count()))
+                .id_source(("client_group", &child.path))
+                .open(Some(open))
+                .show(ui, |ui| self.client_group(ui, child, rows));
+            if let Some(dragged) = response.header_response.dnd_release_payload::<DraggedClient>() {
+                self.drop_client(&dragged.0, &DropTarget::Folder(child.path.clone()));
+            } else if response.header_response.dnd_hover_payload::<DraggedClient>().is_some() {
+                ui.painter().rect_stroke(response.header_response.rect, 2.0, ui.visuals().selection.stroke);
+            }
+            response.header_response.context_menu(|ui| {
+                if ui.button("Connect All…").clicked() {
+                    self.open_group_connect(&child.path);
+                    ui.close_menu();
+                }
+                if ui.button("Export .rdp files…").clicked() {
+                    self.open_rdp_export(&child.path);
+                    ui.close_menu();
+                }
+                if ui.button("Contact…").on_hover_text("Whom to call about clients in this folder that have no owner of their own").clicked() {
+                    self.open_group_contact(&child.path);
+                    ui.close_menu();
+                }
+            });
+            if response.header_response.clicked() && !filtering {
+                if open {
+                    self.client_list.collapsed.insert(child.path.clone());
+                } else {
+                    self.client_list.collapsed.remove(&child.path);
+                }
+            }
+        }
+        let (plain, farms) = farm_sections(&group.clients, rows.clients);
+        for index in plain {
+            self.client_row(ui, &rows.clients[index], rows);
+        }
+        for (farm, members) in farms {
+            ui.group(|ui| {
+                ui.horizontal(|ui| {
+                    ui.strong(format!("🖧 {}", farm.name));
+                    ui.weak(format!("farm via {}", farm.broker));
+                });
+                for index in members {
+                    self.client_row(ui, &rows.clients[index], rows);
+                }
+            });
+        }
+    }
+
+    fn client_row(&mut self, ui: &mut egui::Ui, client: &Client, rows: &ClientRows) {
+        let (active, today) = (rows.active, rows.today);
+        // Rows need ids of their own to tell where a client is dropped.
+        let row = ui.push_id(&client.id, |ui| ui.horizontal(|ui| {
+            self.reachability_dot(ui, client);
+            if let Some(os) = OsGuess::of(client) {
+                ui.label(os.icon()).on_hover_text(os.label());
+            }
+            let filter = self.client_list.filter.trim().to_string();
+            let filter = filter.as_str();
+            let name = highlight_in(&client.name, filter, ui.style(), egui::TextStyle::Heading);
+            let response = ui
+                .selectable_label(self.client_list.is_selected(client), name)
+                .interact(egui::Sense::drag())
+                .on_hover_text("Drag onto another client or a folder to move it");
+            if response.clicked() {
+                self.client_list.select(client);
+            }
+            response.dnd_set_drag_payload(DraggedClient(client.id.clone()));
+            if self.client_list.scroll_to_selection && self.client_list.is_selected(client) {
+                response.scroll_to_me(None);
+                self.client_list.scroll_to_selection = false;
+            }
+            for tag in &client.tags {
+                let selected = self.client_list.tag.as_ref().is_some_and(|current| current.eq_ignore_ascii_case(tag));
+                if tag_chip(ui, tag, selected).on_hover_text("Show only clients with this tag").clicked() {
+                    self.client_list.tag = if selected { None } else { Some(tag.clone()) };
+                }
+            }
+            // Show why a client matched when it was not by name.
+            if find_matches(&client.name, filter).is_empty() {
+                if let Some(hit) = rows.matches.get(&client.id).and_then(|result| result.hits.first()) {
+                    ui.label(format!("{}:", hit.field));
+                    ui.label(highlight(&hit.text, filter, ui.style()));
+                }
+            }
+            match expiry_state(client, today) {
+                ExpiryState::Expired => {
+                    ui.colored_label(egui::Color32::RED, "expired");
+                }
+                ExpiryState::ExpiringSoon(days) => {
+                    ui.colored_label(egui::Color32::YELLOW, format!("expires in {} days", days));
+                }
+                ExpiryState::Valid => {}
+            }
+            if let Some(note) = &client.pinned_note {
+                ui.colored_label(ui.visuals().warn_fg_color, "📌").on_hover_text(&note.text);
+            }
+            if let Some(status) = self.monitored_status(client) {
+                let color = match status {
+                    HostStatus::Up => egui::Color32::GREEN,
+                    HostStatus::Down => egui::Color32::RED,
+                    HostStatus::Maintenance => egui::Color32::from_rgb(80, 140, 230),
+                    HostStatus::Unknown => egui::Color32::GRAY,
+                };
+                ui.colored_label(color, status.label())
+                    .on_hover_text(format!("Reported by {}", self.settings.monitoring_system.name()));
+            }
+            if active.iter().any(|session| session.client_id == client.id) {
+                ui.colored_label(egui::Color32::GREEN, "●").on_hover_text("Session open");
+            }
+            let address = client.endpoint().0;
+            for peer in rows.peers.iter().filter(|peer| peer.connected_to(address)) {
+                ui.colored_label(egui::Color32::from_rgb(220, 150, 0), format!("👥 {}", peer.user))
+                    .on_hover_text(format!("{} is connected from {}", peer.user, peer.machine));
+            }
+            if client.info_only {
+                ui.label("info");
+            } else if ui.button("Connect").clicked() {
+                self.request_connect(client);
+            }
+            if !client.reference.is_empty() {
+                for template in self.settings.url_templates.clone() {
+                    match expand_template(&template.url, client) {
+                        Ok(url) => {
+                            if ui.button(template.name).on_hover_text(&url).clicked() {
+                                if let Err(e) = open_url(&url) {
+                                    self.error_message = Some(format!("Could not open {}: {}", url, e));
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            ui.add_enabled(false, egui::Button::new(template.name)).on_disabled_hover_text(e);
+                        }
+                    }
+                }
+            }
+        }));
+        if let Some(dragged) = row.response.dnd_release_payload::<DraggedClient>() {
+            self.drop_client(&dragged.0, &DropTarget::Before(client.id.clone()));
+        } else if row.response.dnd_hover_payload::<DraggedClient>().is_some_and(|dragged| dragged.0 != client.id) {
+            let rect = row.response.rect;
+            ui.painter().hline(rect.x_range(), rect.top(), ui.visuals().selection.stroke);
+        }
+    }
+
+    /// Green when the client's port answered, yellow when it answered
+    /// slowly and red when it did not. Nothing while checks are off.
+    fn reachability_dot(&self, ui: &mut egui::Ui, client: &Client) {
+        let Some(target) = self.settings.reachability_check_seconds.and(Target::of(client, &self.settings.default_ports)) else {
+            return;
+        };
+        let (color, text) = match self.reachability.get(&client.id) {
+            None => (egui::Color32::GRAY, "Not checked yet".to_string()),
+            Some(None) => (egui::Color32::RED, format!("No answer on port {}", target.port)),
+            Some(Some(latency)) if *latency > SLOW => {
+                (egui::Color32::YELLOW, format!("Port {} answered slowly, in {} ms", target.port, latency.as_millis()))
+            }
+            Some(Some(latency)) => (egui::Color32::GREEN, format!("Port {} answered in {} ms", target.port, latency.as_millis())),
+        };
+        ui.colored_label(color, "⏺").on_hover_text(text);
+    }
+
+    /// Details of the selected client, shown below the list in normal mode.
+    pub(crate) fn selection_panel(&mut self, ui: &mut egui::Ui) {
+        if self.weak_master_key {
+            ui.colored_label(
+                egui::Color32::YELLOW,
+                format!("Your master key is weaker than this vault's policy requires ({} bits).", self.repository.policy.min_master_key_bits),
+            );
+        }
+        if let Some(client) = self.client_list.selected_client(self.repository.clients()).cloned() {
+            self.client_details(ui, &client);
+        } else if cfg!(windows) && self.repository.clients().is_empty() {
+            ui.label("No clients yet.");
+            if ui.button("Import hosts from the Remote Desktop history").clicked() {
+                self.offer_mstsc_history();
+            }
+        }
+    }
+
+    pub(crate) fn attachment_texture(&mut self, ctx: &egui::Context, attachment: &Attachment) -> Option<egui::TextureHandle> {
+        if let Some(texture) = self.thumbnails.get(&attachment.id) {
+            return Some(texture.clone());
+        }
+        let rgba = attachment.to_rgba().ok()?;
+        let image = egui::ColorImage::from_rgba_unmultiplied([attachment.width as usize, attachment.height as usize], &rgba);
+        let texture = ctx.load_texture(&attachment.id, image, egui::TextureOptions::LINEAR);
+        self.thumbnails.insert(attachment.id.clone(), texture.clone());
+        Some(texture)
+    }
+
+    /// Draws clickable thumbnails; returns the index of one the user asked to remove.
+    pub(crate) fn attachment_thumbnails(&mut self, ui: &mut egui::Ui, attachments: &[Attachment], removable: bool) -> Option<usize> {
+        let mut removed = None;
+        ui.horizontal_wrapped(|ui| {
+            for (index, attachment) in attachments.iter().enumerate() {
+                ui.vertical(|ui| {
+                    if let Some(texture) = self.attachment_texture(ui.ctx(), attachment) {
+                        let size = texture.size_vec2() * (120.0 / texture.size_vec2().max_elem()).min(1.0);
+                        if ui.add(egui::ImageButton::new((texture.id(), size))).clicked() {
+                            self.viewing_attachment = Some(attachment.clone());
+                        }
+                    }
+                    if removable && ui.small_button("Remove").clicked() {
+                        removed = Some(index);
+                    }
+                });
+            }
+        });
+        removed
+    }
+
+    /// Read-only summary of the selected client.
+    fn client_details(&mut self, ui: &mut egui::Ui, client: &Client) {
+        ui.heading(&client.name);
+        if let Some(note) = &client.pinned_note {
+            egui::Frame::group(ui.style()).show(ui, |ui| {
+                ui.colored_label(ui.visuals().warn_fg_color, egui::RichText::new(format!("📌 {}", note.text)).strong());
+                ui.horizontal(|ui| {
+                    ui.weak(format!("Pinned {}", when(note.pinned_at, chrono::Local::now())));
+                    if ui.small_button("Edit").clicked() {
+                        self.ask_for_pinned_note(&client.id);
+                    }
+                    if ui.small_button("Unpin").clicked() {
+                        self.pin_note(&client.id, "");
+                    }
+                });
+            });
+        } else if !client.info_only && ui.small_button("📌 Pin a Note…").on_hover_text("Shown before the next connect").clicked() {
+            self.ask_for_pinned_note(&client.id);
+        }
+        if !client.ip.is_empty() {
+            if client.info_only {
+                ui.label(format!("Address: {}", client.address()));
+            } else {
+                ui.label(format!("Address: {} ({})", client.address(), client.protocol.label()));
+                let port = client.effective_port(&self.settings.default_ports);
+                match client.endpoint().1 {
+                    Some(_) => ui.label(format!("Port: {}", port)),
+                    None => ui.label(format!("Port: {} (default for {})", port, client.protocol.label())),
+                };
+            }
+        }
+        if let Some(farm) = &client.farm {
+            ui.label(format!("Farm: {}, connected through the broker {}", farm.name, farm.broker));
+        }
+        if let Some(gateway) = &client.gateway {
+            ui.label(format!("Gateway: {}{}", gateway.host, if gateway.bypass_local { ", bypassed for local addresses" } else { "" }));
+        }
+        if !client.group.is_empty() {
+            ui.label(format!("Group: {}", client.group));
+        }
+        if !client.tags.is_empty() {
+            ui.label(format!("Tags: {}", client.tags.join(", ")));
+        }
+        match client.credential.as_deref().map(|id| credential::find(&self.repository.credentials, id)) {
+            Some(Some(shared)) => {
+                ui.label(format!("Account: {} (shared: {})", shared.account(), shared.name));
+            }
+            Some(None) => {
+                ui.colored_label(ui.visuals().warn_fg_color, "Account: its shared credential was removed");
+            }
+            None => {
+                if let Some(account) = client.account() {
+                    ui.label(format!("Account: {}", account));
+                }
+            }
+        }
+        if !client.reference.is_empty() {
+            ui.label(format!("Reference: {}", client.reference));
+        }
+        if let Some(expires) = client.expires {
+            ui.label(format!("Expires: {}", expires));
+        }
+        if let Some((owner, group)) = client.contact(&self.repository.group_contacts) {
+            ui.horizontal(|ui| {
+                ui.label(egui::RichText::new("Owner").strong());
+                match group {
+                    Some(group) => ui.weak(format!("contact of the group {}", group)),
+                    None => ui.weak("set on this client"),
+                };
+            });
+            if !owner.name.is_empty() {
+                ui.label(&owner.name);
+            }
+            if !owner.email.is_empty() {
+                ui.hyperlink_to(&owner.email, format!("mailto:{}", owner.email));
+            }
+            if !owner.phone.is_empty() {
+                ui.hyperlink_to(&owner.phone, format!("tel:{}", owner.phone.replace(' ', "")));
+            }
+            if !owner.on_call_url.is_empty() {
+                ui.hyperlink_to("On-call schedule", &owner.on_call_url);
+            }
+        }
+        if !client.notes.is_empty() {
+            ui.label(egui::RichText::new("Notes").strong());
+            let parts = note_parts(&client.notes, client, &self.settings.url_templates);
+            egui::ScrollArea::vertical().id_source("client_notes").max_height(120.0).show(ui, |ui| {
+                ui.horizontal_wrapped(|ui| {
+                    ui.spacing_mut().item_spacing.x = 0.0;
+                    for part in parts {
+                        match part {
+                            NotePart::Text(text) => {
+                                ui.label(text);
+                            }
+                            NotePart::Link { text, url } => {
+                                if ui.link(text).on_hover_text(&url).clicked() {
+                                    if let Err(e) = open_url(&url) {
+                                        self.error_message = Some(format!("Could not open {}: {}", url, e));
+                                    }
+                                }
+                            }
+                        }
+                    }
+                });
+            });
+        }
+        if !client.attachments.is_empty() {
+            self.attachment_thumbnails(ui, &client.attachments, false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn named(name: &str) -> Client {
+        Client { name: name.to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn fingerprinted_hosts_are_tagged_unless_tagged_by_hand() {
+        use crate::fingerprint::Evidence;
+        let windows = Evidence { ttl: Some(127), open_ports: vec![135, 3389, 445], ssh_banner: None };
+        assert_eq!(windows.guess(22), Some(OsGuess::Windows));
+        let samba_and_xrdp = Evidence { ttl: Some(64), open_ports: vec![22, 445, 3389], ssh_banner: None };
+        assert_eq!(samba_and_xrdp.guess(22), Some(OsGuess::Linux));
+        let openssh = Evidence { ttl: Some(128), open_ports: vec![22], ssh_banner: Some("SSH-2.0-OpenSSH_for_Windows_8.1".to_string()) };
+        assert_eq!(openssh.guess(22), Some(OsGuess::Windows));
+        let linux = Evidence { ttl: Some(63), open_ports: vec![2222], ssh_banner: Some("SSH-2.0-OpenSSH_9.6p1 Ubuntu-3ubuntu13".to_string()) };
+        assert_eq!(linux.guess(2222), Some(OsGuess::Linux));
+        let switch = Evidence { ttl: Some(254), open_ports: vec![22, 23], ssh_banner: Some("SSH-2.0-Cisco-1.25".to_string()) };
+        assert_eq!(switch.guess(22), Some(OsGuess::NetworkDevice));
+        assert_eq!(Evidence { ttl: Some(64), open_ports: vec![135], ssh_banner: None }.guess(22), Some(OsGuess::Windows));
+        assert_eq!(Evidence { ttl: Some(128), open_ports: vec![3389], ssh_banner: None }.guess(22), Some(OsGuess::Windows));
+        assert_eq!(Evidence { ttl: Some(250), open_ports: vec![22], ssh_banner: None }.guess(22), None);
+        assert_eq!(Evidence::default().guess(22), None);
+
+        let mut state = AppState::unlocked_for_test();
+        let (guessed, by_hand, undecided) = (named("web01"), Client { tags: vec!["OS:Other".to_string()], ..named("web02") }, named("web03"));
+        for client in [&guessed, &by_hand, &undecided] {
+            state.repository.add(client.clone());
+        }
+        state.tag_fingerprinted(vec![
+            (guessed.id.clone(), Some(OsGuess::Linux)),
+            (by_hand.id.clone(), Some(OsGuess::Windows)),
+            (undecided.id.clone(), None),
+        ]);
+        let tags = |id: &str| state.repository.get(id).unwrap().tags.clone();
+        assert_eq!(tags(&guessed.id), ["os:linux"]);
+        assert_eq!(tags(&by_hand.id), ["OS:Other"]);
+        assert!(tags(&undecided.id).is_empty());
+        assert_eq!(OsGuess::of(state.repository.get(&guessed.id).unwrap()), Some(OsGuess::Linux));
+        assert_eq!(state.fingerprinted.len(), 3);
+        assert!(state.notification.as_deref().unwrap().starts_with("Tagged 1 client "));
+    }
+
+    #[test]
+    fn dropped_clients_move_above_the_target_or_to_the_end_of_the_folder() {
+        let mut state = AppState::unlocked_for_test();
+        for (name, group) in [("a", ""), ("b", "Web"), ("c", ""), ("d", "Web")] {
+            state.repository.add(Client { group: group.to_string(), ..named(name) });
+        }
+        let id = |state: &AppState, name: &str| state.repository.clients().iter().find(|c| c.name == name).unwrap().id.clone();
+        let order = |state: &AppState| state.repository.clients().iter().map(|c| format!("{}{}", c.group, c.name)).collect::<Vec<_>>().join(" ");
+
+        state.drop_client(&id(&state, "c"), &DropTarget::Before(id(&state, "a")));
+        assert_eq!(order(&state), "c a Webb Webd");
+        state.drop_client(&id(&state, "a"), &DropTarget::Before(id(&state, "d")));
+        assert_eq!(order(&state), "c Webb Weba Webd");
+        state.drop_client(&id(&state, "c"), &DropTarget::Folder("Web".to_string()));
+        assert_eq!(order(&state), "Webb Weba Webd Webc");
+        state.drop_client(&id(&state, "b"), &DropTarget::Folder(String::new()));
+        assert_eq!(order(&state), "Weba Webd Webc b");
+        state.drop_client(&id(&state, "d"), &DropTarget::Before(id(&state, "d")));
+        assert_eq!(order(&state), "Weba Webd Webc b");
+    }
+
+    #[test]
+    fn sorting_ignores_case_and_accents_folder_by_folder() {
+        let mut state = AppState::unlocked_for_test();
+        for (name, group) in [("Zürich", ""), ("ölberg", "Web"), ("München", ""), ("Munich", ""), ("Oslo", "Web"), ("apple", "Ärzte")] {
+            state.repository.add(Client { group: group.to_string(), ..named(name) });
+        }
+        state.sort_clients_by_name();
+        let order = state.repository.clients().iter().map(|c| format!("{}/{}", c.group, c.name)).collect::<Vec<_>>().join(" ");
+        assert_eq!(order, "/München /Munich /Zürich Ärzte/apple Web/ölberg Web/Oslo");
+    }
+
+    #[test]
+    fn selection_resolves_to_the_client() {
+        let clients = vec![named("a"), named("b")];
+        let mut list = ClientList::default();
+        list.select(&clients[1]);
+        assert_eq!(list.selected_client(&clients).map(|c| c.name.as_str()), Some("b"));
+    }
+
+    #[test]
+    fn selection_follows_the_client_not_its_position() {
+        let mut clients = vec![named("a"), named("b"), named("c"), named("d")];
+        let mut list = ClientList::default();
+        list.select(&clients[3]);
+        clients.remove(2);
+        clients.reverse();
+        list.retain(&clients);
+        assert_eq!(list.selected_client(&clients).map(|c| c.name.as_str()), Some("d"));
+        clients[0].trashed_at = Some(chrono::Utc::now());
+        list.retain(&clients);
+        assert_eq!(list.selected, None);
+    }
+
+    #[test]
+    fn search_finds_variables_pinned_notes_and_history_but_not_secrets() {
+        use crate::client::PinnedNote;
+        use crate::history::ConnectionRecord;
+        let mut state = AppState::unlocked_for_test();
+        let mut client = Client { ip: "10.0.0.5".to_string(), ..named("web01") };
+        client.variables.insert("site".to_string(), "Frankfurt".to_string());
+        client.variables.insert("api_token".to_string(), "hunter2".to_string());
+        client.pinned_note = Some(PinnedNote { text: "Reboots on Sundays".to_string(), pinned_at: chrono::Utc::now() });
+        let record = |name: &str, address: &str, error: Option<&str>| ConnectionRecord {
+            client_id: client.id.clone(),
+            client_name: name.to_string(),
+            address: address.to_string(),
+            started: chrono::Utc::now(),
+            ended: None,
+            error: error.map(str::to_string),
+            password_changed: false,
+        };
+        state.repository.history = vec![record("web01", "10.0.0.5", None), record("legacy-web", "192.168.7.20", None), record("web01", "10.0.0.5", Some("mstsc was not found"))];
+        state.repository.add(client);
+        state.handle_repository_events();
+
+        let fields = |query: &str| state.search_index.search(query).iter().flat_map(|result| result.hits.iter().map(|hit| (hit.field, hit.text.clone()))).collect::<Vec<_>>();
+        assert_eq!(fields("frankfurt"), [("Variable", "site = Frankfurt".to_string())]);
+        assert_eq!(fields("sundays"), [("Pinned note", "Reboots on Sundays".to_string())]);
+        assert_eq!(fields("192.168.7"), [("History", "legacy-web (192.168.7.20)".to_string())]);
+        assert_eq!(fields("not found"), [("History", "Failed: mstsc was not found".to_string())]);
+        assert!(fields("hunter2").is_empty());
+    }
+
+    #[test]
+    fn notes_fill_in_placeholders_and_link_the_reference_and_web_addresses() {
+        use crate::settings::UrlTemplate;
+        let mut client = Client { ip: "db01.example.com:1433".to_string(), reference: "CI 42".to_string(), ..named("db01") };
+        client.variables.insert("region".to_string(), "eu-west".to_string());
+        let templates = [UrlTemplate { name: "CMDB".to_string(), url: "https://cmdb/ci/{reference}".to_string() }];
+        let notes = "Asset {reference} on {host} in {region}, runbook at https://wiki/db01. Ask {owner}.";
+        let link = |text: &str, url: &str| NotePart::Link { text: text.to_string(), url: url.to_string() };
+        let text = |text: &str| NotePart::Text(text.to_string());
+        assert_eq!(
+            note_parts(notes, &client, &templates),
+            [text("Asset "), link("CI 42", "https://cmdb/ci/CI%2042"), text(" on db01.example.com in eu-west, runbook at "), link("https://wiki/db01", "https://wiki/db01"), text(". Ask {owner}.")]
+        );
+        assert_eq!(note_parts("Asset {reference}", &client, &[]), [text("Asset CI 42")]);
+    }
+
+    #[test]
+    fn clients_without_an_owner_inherit_the_nearest_group_contact() {
+        use crate::client::Contact;
+        let mut state = AppState::unlocked_for_test();
+        let owned = Client { group: "Customers/Acme".to_string(), owner: Contact { name: "Dana".to_string(), ..Default::default() }, ..named("a") };
+        let nested = Client { group: " Customers / Acme / Web ".to_string(), ..named("b") };
+        let sibling = Client { group: "Customers/Globex".to_string(), ..named("c") };
+        let ungrouped = named("d");
+        for client in [&owned, &nested, &sibling, &ungrouped] {
+            state.repository.add(client.clone());
+        }
+        for (group, name) in [("Customers", "Service desk"), ("Customers/Acme", "Acme on-call")] {
+            state.open_group_contact(group);
+            state.group_contact_input.1.name = name.to_string();
+            state.save_group_contact();
+        }
+        state.lock();
+        state.unlock_with_key([7; 32]).unwrap();
+
+        let contacts = &state.repository.group_contacts;
+        let contact = |client: &Client| client.contact(contacts).map(|(contact, group)| (contact.name.clone(), group.map(str::to_string)));
+        assert_eq!(contact(&owned), Some(("Dana".to_string(), None)));
+        assert_eq!(contact(&nested), Some(("Acme on-call".to_string(), Some("Customers/Acme".to_string()))));
+        assert_eq!(contact(&sibling), Some(("Service desk".to_string(), Some("Customers".to_string()))));
+        assert_eq!(contact(&ungrouped), None);
+
+        state.open_group_contact("Customers/Acme");
+        assert_eq!(state.group_contact_input.1.name, "Acme on-call");
+        state.group_contact_input.1.name.clear();
+        state.save_group_contact();
+        assert_eq!(nested.contact(&state.repository.group_contacts).map(|(contact, _)| contact.name.as_str()), Some("Service desk"));
+    }
+
+    #[test]
+    fn adding_a_client_selects_it() {
+        let mut state = AppState::unlocked_for_test();
+        state.repository.add(named("a"));
+        let b = named("b");
+        state.repository.add(b.clone());
+        state.handle_repository_events();
+        assert_eq!(state.client_list.selected, Some(b.id));
+    }
+
+    #[test]
+    fn removing_the_last_client_clears_the_selection() {
+        let mut state = AppState::unlocked_for_test();
+        let client = named("a");
+        let id = client.id.clone();
+        state.repository.add(client);
+        state.handle_repository_events();
+        state.repository.remove(&id);
+        state.handle_repository_events();
+        assert_eq!(state.client_list.selected, None);
+    }
+
+    #[test]
+    fn groups_nest_by_path() {
+        let mut clients = vec![named("a"), named("b"), named("c"), named("d")];
+        clients[0].group = "Customers/Acme".to_string();
+        clients[1].group = "Customers".to_string();
+        clients[2].group = "Customers/ Acme /".to_string();
+        let tree = ClientGroup::build(&clients);
+        assert_eq!(tree.clients, [3]);
+        assert_eq!(tree.count(), 4);
+        let customers = &tree.groups[0];
+        assert_eq!((customers.name.as_str(), customers.clients.as_slice()), ("Customers", &[1][..]));
+        let acme = &customers.groups[0];
+        assert_eq!((acme.path.as_str(), acme.clients.as_slice()), ("Customers/Acme", &[0, 2][..]));
+    }
+
+    #[test]
+    fn farm_members_are_listed_together_after_the_other_clients() {
+        let mut state = AppState::unlocked_for_test();
+        let farm = |name: &str| Some(RdsFarm { name: name.to_string(), broker: "broker.corp".to_string() });
+        let clients = [
+            Client { farm: farm("Office"), ..named("rdsh01") },
+            named("db01"),
+            Client { farm: farm("Apps"), ..named("app01") },
+            Client { farm: farm("office"), ..named("rdsh02") },
+        ];
+        for client in &clients {
+            state.repository.add(client.clone());
+        }
+        let listed: Vec<String> = state.listed_clients();
+        let names: Vec<&str> = listed.iter().map(|id| state.repository.get(id).unwrap().name.as_str()).collect();
+        assert_eq!(names, ["db01", "rdsh01", "rdsh02", "app01"]);
+    }
+}
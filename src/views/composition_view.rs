@@ -0,0 +1,188 @@
+use crate::app::AppState;
+use crate::client::AppMode;
+use crate::composition::{by_month, composition_csv, Dimension, MonthlyCounts};
+use chrono::Datelike;
+use eframe::egui;
+use egui_plot::{Legend, Line, Plot, PlotPoints};
+use std::fs;
+
+/// Lines drawn besides the total; smaller ones are added up as "Others".
+const PLOTTED: usize = 8;
+
+/// Tools → Vault Composition: what the plot counts by and where the counts
+/// are exported to.
+pub struct CompositionReport {
+    pub dimension: Dimension,
+    pub path: String,
+}
+
+impl Default for CompositionReport {
+    fn default() -> Self {
+        Self { dimension: Dimension::default(), path: "composition.csv".to_string() }
+    }
+}
+
+/// Months since year 0, the plot's x axis.
+fn month_index(month: &MonthlyCounts) -> f64 {
+    (month.month.year() * 12 + month.month.month0() as i32) as f64
+}
+
+fn month_label(index: f64) -> String {
+    let index = index.round() as i32;
+    format!("{}-{:02}", index.div_euclid(12), index.rem_euclid(12) + 1)
+}
+
+/// The names of `dimension` with the most clients in the last month, the
+/// largest first.
+fn largest(months: &[MonthlyCounts], dimension: Dimension) -> Vec<String> {
+    let Some(last) = months.last() else {
+        return Vec::new();
+    };
+    let mut names: Vec<(&String, &u32)> = dimension.counts(last).iter().collect();
+    names.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    names.into_iter().take(PLOTTED).map(|(name, _)| name.clone()).collect()
+}
+
+impl AppState {
+    pub fn open_composition(&mut self) {
+        self.mode = AppMode::Composition;
+    }
+
+    pub fn export_composition(&mut self) {
+        let path = self.composition.path.trim().to_string();
+        match composition_csv(&self.repository.composition).and_then(|data| fs::write(&path, data).map_err(|e| e.to_string())) {
+            Ok(()) => self.info_message = Some(format!("Exported {} months of vault composition to {}", by_month(&self.repository.composition).len(), path)),
+            Err(e) => self.error_message = Some(format!("Export failed: {}", e)),
+        }
+    }
+
+    pub(crate) fn composition_view(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Vault Composition");
+        ui.label("Clients counted once a month, each month as it ended. Sites, customers and environments are read from site:, customer: and env: tags.");
+        ui.horizontal(|ui| {
+            ui.label("Count by:");
+            for dimension in Dimension::ALL {
+                ui.radio_value(&mut self.composition.dimension, dimension, dimension.label());
+            }
+        });
+        let dimension = self.composition.dimension;
+        let months = by_month(&self.repository.composition);
+        let plotted = largest(&months, dimension);
+        Plot::new("composition")
+            .height(260.0)
+            .legend(Legend::default())
+            .include_y(0.0)
+            .allow_scroll(false)
+            .x_axis_formatter(|mark, _, _| if mark.value.fract() == 0.0 { month_label(mark.value) } else { String::new() })
+            .label_formatter(|name, point| format!("{}\n{}: {:.0}", name, month_label(point.x), point.y))
+            .show(ui, |plot| {
+                let line = |count: &dyn Fn(&MonthlyCounts) -> u32| PlotPoints::from_iter(months.iter().map(|month| [month_index(month), count(month) as f64]));
+                plot.line(Line::new(line(&|month| month.clients)).name("All clients").width(2.0));
+                for name in &plotted {
+                    plot.line(Line::new(line(&|month| dimension.counts(month).get(name).copied().unwrap_or(0))).name(name));
+                }
+                let others = |month: &MonthlyCounts| dimension.counts(month).iter().filter(|(name, _)| !plotted.contains(name)).map(|(_, count)| count).sum();
+                if months.iter().any(|month| others(month) > 0) {
+                    plot.line(Line::new(line(&others)).name("Others"));
+                }
+            });
+        match (months.first(), months.last()) {
+            (Some(first), Some(last)) => {
+                egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                    egui::Grid::new("composition_counts").striped(true).num_columns(3).show(ui, |ui| {
+                        ui.strong(dimension.label());
+                        ui.strong(last.month.format("%Y-%m").to_string());
+                        ui.strong(format!("since {}", first.month.format("%Y-%m")));
+                        ui.end_row();
+                        let mut names: Vec<(&String, &u32)> = dimension.counts(last).iter().collect();
+                        names.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+                        let rows = std::iter::once(("All clients", last.clients, first.clients))
+                            .chain(names.into_iter().map(|(name, count)| (name.as_str(), *count, dimension.counts(first).get(name).copied().unwrap_or(0))));
+                        for (name, now, then) in rows {
+                            ui.label(name);
+                            ui.label(now.to_string());
+                            ui.label(format!("{:+}", now as i64 - then as i64));
+                            ui.end_row();
+                        }
+                    });
+                });
+            }
+            _ => {
+                ui.label("Nothing counted yet. The clients are counted the next time the vault is saved.");
+            }
+        }
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label("File:");
+            ui.text_edit_singleline(&mut self.composition.path);
+            if ui.add_enabled(!months.is_empty(), egui::Button::new("Export CSV")).clicked() {
+                self.export_composition();
+            }
+            if ui.button("Close").clicked() {
+                self.mode = AppMode::Normal;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Client;
+    use crate::composition::{record, UNSET};
+    use crate::repository::{ClientRepository, EncryptedFileRepository};
+    use chrono::NaiveDate;
+
+    fn client(group: &str, tags: &[&str]) -> Client {
+        Client { name: "db01".to_string(), group: group.to_string(), tags: tags.iter().map(|tag| tag.to_string()).collect(), ..Default::default() }
+    }
+
+    fn day(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn each_month_keeps_how_it_ended() {
+        let mut clients = vec![client("Customers/Acme", &["site:fra1", "env:prod"]), client("", &["customer:Acme"])];
+        let mut history = Vec::new();
+        record(&mut history, &clients, day(2026, 1, 5));
+        clients.push(client("Customers/Other", &["site:fra1"]));
+        clients[1].trashed_at = Some(chrono::Utc::now());
+        record(&mut history, &clients, day(2026, 1, 30));
+        assert_eq!(history.len(), 1);
+        let january = &history[0];
+        assert_eq!((january.month, january.clients), (day(2026, 1, 1), 2));
+        assert_eq!(january.folders.get("Customers"), Some(&2));
+        assert_eq!(january.sites.get("fra1"), Some(&2));
+        assert_eq!(january.environments.get(UNSET), Some(&1));
+        assert!(!january.customers.contains_key("Acme"));
+
+        clients.push(client("Lab", &[]));
+        record(&mut history, &clients, day(2026, 4, 2));
+        record(&mut history, &clients, day(2026, 3, 2));
+        let months = by_month(&history);
+        assert_eq!(months.iter().map(|month| (month.month.month(), month.clients)).collect::<Vec<_>>(), [(1, 2), (2, 2), (3, 2), (4, 3)]);
+        assert_eq!(largest(&months, Dimension::Folder), ["Customers", "Lab"]);
+    }
+
+    #[test]
+    fn counts_are_saved_in_the_vault_and_exported() {
+        let mut state = AppState::unlocked_for_test();
+        state.repository.add(client("Customers/Acme", &["site:fra1"]));
+        state.save_clients();
+        assert!(state.error_message.is_none());
+
+        let mut reopened = EncryptedFileRepository::new(state.repository.path());
+        reopened.open([7; crate::encryption::KEY_SIZE]).unwrap();
+        assert_eq!(reopened.composition.len(), 1);
+        assert_eq!(reopened.composition[0].sites.get("fra1"), Some(&1));
+
+        let path = std::env::temp_dir().join(format!("rdm-composition-{}.csv", crate::client::new_client_id()));
+        state.composition.path = path.to_string_lossy().into_owned();
+        state.export_composition();
+        let exported = fs::read_to_string(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert!(exported.starts_with("month,dimension,name,clients\n"));
+        assert!(exported.contains(",site,fra1,1\n"));
+    }
+}
@@ -0,0 +1,85 @@
+use crate::app::AppState;
+use crate::client::Client;
+use crate::diagnosis::{Check, LaunchError};
+use crate::repository::ClientRepository;
+use eframe::egui;
+
+/// A failed connect waiting to be retried or dismissed, with the
+/// diagnostics once they have run.
+pub struct ConnectFailure {
+    pub client_id: String,
+    pub client_name: String,
+    pub error: LaunchError,
+    pub checks: Option<Vec<Check>>,
+    pub checking: bool,
+}
+
+impl ConnectFailure {
+    pub fn new(client: &Client, error: LaunchError) -> Self {
+        Self {
+            client_id: client.id.clone(),
+            client_name: client.name.clone(),
+            error,
+            checks: None,
+            checking: false,
+        }
+    }
+}
+
+fn check_row(ui: &mut egui::Ui, check: &Check) {
+    let (mark, color) = if check.passed { ("✔", egui::Color32::GREEN) } else { ("✖", egui::Color32::RED) };
+    ui.colored_label(color, mark);
+    ui.label(&check.name);
+    ui.label(&check.detail);
+    ui.end_row();
+}
+
+impl AppState {
+    pub(crate) fn connect_failure_window(&mut self, ctx: &egui::Context) {
+        let Some(failure) = self.connect_failure.as_ref() else {
+            return;
+        };
+        let mut retry = false;
+        let mut diagnose = false;
+        let mut close = false;
+        egui::Window::new(format!("Could Not Connect to {}", failure.client_name))
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(egui::RichText::new(failure.error.failure.title()).strong());
+                for hint in failure.error.failure.hints() {
+                    ui.label(format!("• {}", hint));
+                }
+                ui.collapsing("Details", |ui| ui.weak(&failure.error.message));
+                if failure.checking {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Running diagnostics…");
+                    });
+                }
+                if let Some(checks) = &failure.checks {
+                    egui::Grid::new("connect_diagnostics").num_columns(3).show(ui, |ui| {
+                        for check in checks {
+                            check_row(ui, check);
+                        }
+                    });
+                }
+                ui.horizontal(|ui| {
+                    retry = ui.button("Retry").clicked();
+                    diagnose = ui.add_enabled(!failure.checking, egui::Button::new("Open Diagnostics")).clicked();
+                    close = ui.button("Close").clicked();
+                });
+            });
+        if diagnose {
+            self.run_diagnostics();
+        } else if retry {
+            let client = self.connect_failure.take().and_then(|failure| self.repository.get(&failure.client_id).cloned());
+            if let Some(client) = client {
+                self.request_connect(&client);
+            }
+        } else if close {
+            self.connect_failure = None;
+        }
+    }
+}
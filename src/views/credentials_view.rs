@@ -0,0 +1,221 @@
+use crate::app::AppState;
+use crate::client::{AppMode, Client};
+use crate::credential::Credential;
+use crate::repository::ClientRepository;
+use crate::views::secret_field::{confirm_master_key, SecretField};
+use eframe::egui;
+
+impl AppState {
+    /// Opens Tools → Credentials.
+    pub fn open_credentials(&mut self) {
+        self.discard_credential_form();
+        self.mode = AppMode::Credentials;
+    }
+
+    /// How many clients log on with the credential `id`, to the host or to
+    /// its gateway.
+    pub fn clients_using(&self, id: &str) -> usize {
+        let uses = |client: &&Client| client.credential.as_deref() == Some(id) || client.gateway.as_ref().is_some_and(|gateway| gateway.credential.as_deref() == Some(id));
+        self.repository.clients().iter().filter(uses).count()
+    }
+
+    /// Adds the credential in the form, or replaces the one it was loaded from.
+    pub fn save_credential(&mut self) {
+        let Some(form) = &self.credential_form else {
+            return;
+        };
+        let name = form.name.trim();
+        if name.is_empty() {
+            self.error_message = Some("Please give the credential a name.".to_string());
+            return;
+        }
+        if self.repository.credentials.iter().any(|other| other.id != form.id && other.name.eq_ignore_ascii_case(name)) {
+            self.error_message = Some(format!("There already is a credential named {}.", name));
+            return;
+        }
+        let Some(mut credential) = self.credential_form.take() else {
+            return;
+        };
+        credential.name = credential.name.trim().to_string();
+        credential.username = credential.username.trim().to_string();
+        credential.domain = credential.domain.trim().to_string();
+        match self.repository.credentials.iter_mut().find(|other| other.id == credential.id) {
+            Some(stored) => {
+                stored.wipe_secrets();
+                *stored = credential;
+            }
+            None => self.repository.credentials.push(credential),
+        }
+        self.save_clients();
+    }
+
+    /// Removes the credential `id`. Clients that used it keep its account
+    /// as their own, so they still log on.
+    pub fn remove_credential(&mut self, id: &str) {
+        let Some(index) = self.repository.credentials.iter().position(|credential| credential.id == id) else {
+            return;
+        };
+        let mut credential = self.repository.credentials.remove(index);
+        let users: Vec<_> = self.repository.clients().iter().filter(|client| client.credential.as_deref() == Some(id)).cloned().collect();
+        for mut client in users.iter().cloned() {
+            client.credential = None;
+            client.username = credential.username.clone();
+            client.domain = credential.domain.clone();
+            client.password = credential.password.clone();
+            self.repository.update(client);
+        }
+        self.save_clients();
+        self.info_message = Some(match users.len() {
+            0 => format!("Removed the credential {}.", credential.name),
+            count => format!("Removed the credential {}; its {} clients keep the account as their own.", credential.name, count),
+        });
+        credential.wipe_secrets();
+    }
+
+    pub(crate) fn discard_credential_form(&mut self) {
+        if let Some(mut form) = self.credential_form.take() {
+            form.wipe_secrets();
+        }
+    }
+
+    pub(crate) fn credentials_view(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Credentials");
+        ui.label("Accounts shared by several clients. Pick one as a client's account to log on with it; a new password is then entered only here.");
+        ui.separator();
+        if self.repository.credentials.is_empty() {
+            ui.label("No credentials yet.");
+        }
+        let (mut edit, mut remove) = (None, None);
+        egui::Grid::new("credentials").striped(true).num_columns(4).show(ui, |ui| {
+            for credential in &self.repository.credentials {
+                ui.strong(&credential.name);
+                ui.label(credential.account());
+                ui.label(match self.clients_using(&credential.id) {
+                    1 => "used by 1 client".to_string(),
+                    count => format!("used by {} clients", count),
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Edit").clicked() {
+                        edit = Some(credential.clone());
+                    }
+                    if ui.button("Remove").clicked() {
+                        remove = Some(credential.id.clone());
+                    }
+                });
+                ui.end_row();
+            }
+        });
+        if let Some(credential) = edit {
+            self.discard_credential_form();
+            self.credential_form = Some(credential);
+        }
+        if let Some(id) = remove {
+            self.remove_credential(&id);
+        }
+        ui.separator();
+        let hold = self.settings.hold_to_reveal;
+        let clear_after = self.settings.clear_clipboard_after_seconds;
+        let confirm = self.settings.confirmations.reveal_password;
+        let secure = self.secure_prompt();
+        let repository = &self.repository;
+        let check = || confirm_master_key(repository);
+        if let Some(form) = &mut self.credential_form {
+            egui::Grid::new("credential_form").num_columns(2).show(ui, |ui| {
+                ui.label("Name:");
+                ui.add(egui::TextEdit::singleline(&mut form.name).hint_text("Lab admin"));
+                ui.end_row();
+                ui.label("Username:");
+                ui.text_edit_singleline(&mut form.username);
+                ui.end_row();
+                ui.label("Domain:");
+                ui.text_edit_singleline(&mut form.domain);
+                ui.end_row();
+            });
+            ui.add(SecretField::new("Password:", &mut form.password).hold_to_reveal(hold).confirm_reveal(confirm).verify_reveal(secure.then_some(&check as &dyn Fn() -> bool)).copyable(clear_after).generate().strength(0));
+            ui.horizontal(|ui| {
+                if ui.button("Save Credential").clicked() {
+                    self.save_credential();
+                }
+                if ui.button("Cancel").clicked() {
+                    self.discard_credential_form();
+                }
+            });
+        } else {
+            ui.horizontal(|ui| {
+                if ui.button("New Credential").clicked() {
+                    self.credential_form = Some(Credential::default());
+                }
+                if ui.button("Back").clicked() {
+                    self.mode = AppMode::Normal;
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Client;
+
+    fn shared_admin(state: &mut AppState) -> Credential {
+        let credential = Credential {
+            name: "Lab admin".to_string(),
+            username: "administrator".to_string(),
+            domain: "LAB".to_string(),
+            password: "hunter2".to_string(),
+            ..Default::default()
+        };
+        state.credential_form = Some(credential.clone());
+        state.save_credential();
+        credential
+    }
+
+    #[test]
+    fn clients_log_on_with_the_shared_credential() {
+        let mut state = AppState::unlocked_for_test();
+        let credential = shared_admin(&mut state);
+        let client = Client { name: "dc01".to_string(), credential: Some(credential.id.clone()), ..Default::default() };
+        state.repository.add(client.clone());
+
+        let resolved = client.with_credential(&state.repository.credentials);
+        assert_eq!((resolved.account().as_deref(), resolved.password.as_str()), (Some("LAB\\administrator"), "hunter2"));
+        assert_eq!(state.resolved_clients()[0].password, "hunter2");
+        assert_eq!(state.clients_using(&credential.id), 1);
+
+        state.credential_form = Some(Credential { password: "correct horse".to_string(), ..credential.clone() });
+        state.save_credential();
+        assert_eq!(state.resolved_clients()[0].password, "correct horse");
+        state.credential_form = Some(Credential { name: "lab ADMIN ".to_string(), ..Default::default() });
+        state.save_credential();
+        assert!(state.error_message.take().is_some());
+        assert_eq!(state.repository.credentials.len(), 1);
+    }
+
+    #[test]
+    fn removing_a_credential_hands_its_account_to_the_clients() {
+        let mut state = AppState::unlocked_for_test();
+        let credential = shared_admin(&mut state);
+        let client = Client { name: "dc01".to_string(), credential: Some(credential.id.clone()), ..Default::default() };
+        state.repository.add(client.clone());
+        state.remove_credential(&credential.id);
+        let kept = state.repository.get(&client.id).unwrap();
+        assert_eq!((kept.credential.as_ref(), kept.account().as_deref(), kept.password.as_str()), (None, Some("LAB\\administrator"), "hunter2"));
+        assert!(state.repository.credentials.is_empty());
+    }
+
+    #[test]
+    fn credentials_are_kept_in_the_vault() {
+        let mut state = AppState::for_test();
+        state.unlock_form.master_key = "correct horse".to_string();
+        state.unlock_form.confirm = "correct horse".to_string();
+        state.unlock_and_wait();
+        let credential = shared_admin(&mut state);
+        state.lock();
+        assert!(state.repository.credentials.is_empty());
+        state.unlock_form.master_key = "correct horse".to_string();
+        state.unlock_and_wait();
+        assert_eq!(state.repository.credentials, [credential]);
+        let _ = std::fs::remove_file(state.repository.path());
+    }
+}
@@ -0,0 +1,909 @@
+use crate::app::AppState;
+use crate::client::{AppMode, Client, Protocol};
+use crate::handover::ReportFormat;
+use crate::inventory::InventoryFormat;
+use crate::monitoring::MonitoringSystem;
+use crate::repository::ClientRepository;
+use crate::search::highlight;
+use crate::settings::UrlTemplate;
+use crate::views::editor_panel::contact_grid;
+use crate::views::history_view::when;
+use crate::views::secret_field::SecretField;
+use crate::views::settings_view::SettingsTab;
+use eframe::egui;
+use zeroize::Zeroize;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn file_name(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "clients.csv",
+            ExportFormat::Json => "clients.export.json",
+        }
+    }
+}
+
+/// File and passphrase shared by the import and export dialogs.
+#[derive(Default)]
+pub struct TransferDialog {
+    pub path: String,
+    pub passphrase: String,
+    pub confirm: String,
+    pub format: ExportFormat,
+    /// Encrypt a JSON export, passwords included, with the passphrase.
+    pub encrypt: bool,
+    /// Folder whose clients are exported as .rdp files.
+    pub group: String,
+    /// Save DPAPI-protected passwords in exported .rdp files.
+    pub rdp_passwords: bool,
+    /// Export was clicked for a plaintext file and waits for confirmation.
+    pub confirming_plaintext: bool,
+}
+
+impl TransferDialog {
+    /// Resets the dialog for a new transfer, suggesting `path`.
+    pub fn open(&mut self, path: &str) {
+        self.path = path.to_string();
+        self.confirming_plaintext = false;
+        self.clear_passphrase();
+    }
+
+    pub fn clear_passphrase(&mut self) {
+        self.passphrase.zeroize();
+        self.confirm.zeroize();
+    }
+
+    /// Switches the export format, suggesting a matching file name.
+    pub fn set_format(&mut self, format: ExportFormat) {
+        if self.path.is_empty() || self.path == self.format.file_name() {
+            self.path = format.file_name().to_string();
+        }
+        self.format = format;
+    }
+}
+
+impl AppState {
+    pub(crate) fn message_windows(&mut self, ctx: &egui::Context) {
+        if let Some(error_message) = self.error_message.clone() {
+            egui::Window::new("Error")
+                .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.label(error_message);
+                    if ui.button("OK").clicked() {
+                        self.error_message = None;
+                    }
+                });
+        }
+
+        if let Some(info_message) = self.info_message.clone() {
+            egui::Window::new("Info")
+                .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.label(info_message);
+                    if ui.button("OK").clicked() {
+                        self.info_message = None;
+                    }
+                });
+        }
+    }
+
+    /// Export button that, for a `plaintext` file and when the settings ask
+    /// for it, only runs `export` once it is clicked a second time.
+    fn export_button(&mut self, ui: &mut egui::Ui, plaintext: bool, export: fn(&mut Self)) {
+        let confirm = plaintext && self.settings.confirmations.plaintext_export;
+        if !confirm {
+            self.transfer.confirming_plaintext = false;
+        }
+        if self.transfer.confirming_plaintext {
+            ui.colored_label(ui.visuals().warn_fg_color, "This file will not be encrypted. Export it anyway?");
+            ui.horizontal(|ui| {
+                if ui.button("Export Unencrypted").clicked() {
+                    self.transfer.confirming_plaintext = false;
+                    export(self);
+                }
+                if ui.button("Back").clicked() {
+                    self.transfer.confirming_plaintext = false;
+                }
+            });
+        } else if ui.button("Export").clicked() {
+            if confirm {
+                self.transfer.confirming_plaintext = true;
+            } else {
+                export(self);
+            }
+        }
+    }
+
+    pub(crate) fn connect_confirmation_window(&mut self, ctx: &egui::Context) {
+        let Some(client) = self.confirm_connect.clone().filter(|client| self.repository.get(&client.id).is_some()) else {
+            self.confirm_connect = None;
+            return;
+        };
+        let tag = self.settings.confirmations.connect_tag(&client).map(str::to_string);
+        egui::Window::new(if client.pinned_note.is_some() { "Pinned Note" } else { "Confirm Connection" })
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                if let Some(note) = &client.pinned_note {
+                    ui.label(egui::RichText::new(format!("📌 {}", note.text)).strong().color(ui.visuals().warn_fg_color));
+                    ui.weak(format!("Pinned {}", when(note.pinned_at, chrono::Local::now())));
+                    ui.separator();
+                }
+                match &tag {
+                    Some(tag) => ui.label(format!("{} is tagged {}. Connect to it?", client.name, tag)),
+                    None => ui.label(format!("Connect to {}?", client.name)),
+                };
+                ui.horizontal(|ui| {
+                    if ui.button("Connect").clicked() {
+                        self.confirm_connect = None;
+                        self.request_confirmed_connect(&client);
+                    }
+                    if client.pinned_note.is_some() && ui.button("Unpin and Connect").clicked() {
+                        self.confirm_connect = None;
+                        self.pin_note(&client.id, "");
+                        self.request_confirmed_connect(&client);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.confirm_connect = None;
+                    }
+                });
+            });
+    }
+
+    pub(crate) fn clipboard_warning_window(&mut self, ctx: &egui::Context) {
+        let Some(client) = self.pending_connect.clone() else {
+            return;
+        };
+        let tools = match &self.clipboard_history {
+            Some((_, tools)) if self.settings.warn_clipboard_history => tools.join(", "),
+            _ => String::new(),
+        };
+        let title = if tools.is_empty() { "Clipboard Shared With Another Computer" } else { "Clipboard History Detected" };
+        egui::Window::new(title)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                if !tools.is_empty() {
+                    ui.label(format!("{} keeps a history of copied text, so the password for {} would stay there.", tools, client.name));
+                }
+                if self.remote_session {
+                    ui.label(format!(
+                        "This app runs inside a Remote Desktop session. The password for {} would be passed on to the clipboard of the computer the session is viewed from, and of every session above it.",
+                        client.name
+                    ));
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Copy Anyway").clicked() {
+                        self.pending_connect = None;
+                        self.connect(&client, true);
+                    }
+                    if ui.button("Connect Without Copying").clicked() {
+                        self.pending_connect = None;
+                        self.connect(&client, false);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.pending_connect = None;
+                    }
+                });
+                if ui.link("Stop copying passwords (Settings → Security)").clicked() {
+                    self.pending_connect = None;
+                    self.open_settings(SettingsTab::Security);
+                }
+            });
+    }
+
+    pub(crate) fn screenshot_window(&mut self, ctx: &egui::Context) {
+        let Some(attachment) = self.viewing_attachment.clone() else {
+            return;
+        };
+        let mut open = true;
+        egui::Window::new("Screenshot").open(&mut open).show(ctx, |ui| {
+            if let Some(texture) = self.attachment_texture(ctx, &attachment) {
+                let size = texture.size_vec2() * (800.0 / texture.size_vec2().x).min(1.0);
+                ui.image((texture.id(), size));
+            }
+        });
+        if !open {
+            self.viewing_attachment = None;
+        }
+    }
+
+    /// Panels for every mode other than browsing and editing clients.
+    pub(crate) fn mode_dialog(&mut self, ui: &mut egui::Ui) {
+        match self.mode {
+            AppMode::Removing => {
+                if let Some(client) = self.client_list.selected_client(self.repository.clients()).cloned() {
+                    ui.label(format!("Remove Client: {}", client.name));
+
+                    if ui.button("Confirm").clicked() {
+                        self.remove_client(&client.id);
+                    }
+
+                    if ui.button("Cancel").clicked() {
+                        self.form.clear();
+                        self.mode = AppMode::Normal;
+                    }
+                }
+            }
+            AppMode::Exporting => {
+                ui.label("Export all clients to move them to another install or into a spreadsheet:");
+                ui.horizontal(|ui| {
+                    ui.label("Format:");
+                    for (format, label) in [(ExportFormat::Csv, "CSV"), (ExportFormat::Json, "JSON")] {
+                        if ui.radio(self.transfer.format == format, label).clicked() {
+                            self.transfer.set_format(format);
+                        }
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("File:");
+                    ui.text_edit_singleline(&mut self.transfer.path);
+                });
+                match self.transfer.format {
+                    ExportFormat::Csv => {
+                        ui.colored_label(
+                            ui.visuals().warn_fg_color,
+                            "⚠ CSV files are plaintext: anyone who gets the file can read every host, user name and tag. Passwords are not exported.",
+                        );
+                    }
+                    ExportFormat::Json => {
+                        ui.checkbox(&mut self.transfer.encrypt, "Encrypt with an export passphrase, passwords included");
+                        if self.transfer.encrypt {
+                            let (hold, min_bits) = (self.settings.hold_to_reveal, self.min_master_key_bits());
+                            ui.add(SecretField::new("Passphrase:", &mut self.transfer.passphrase).hold_to_reveal(hold).strength(min_bits));
+                            ui.add(SecretField::new("Confirm:", &mut self.transfer.confirm).hold_to_reveal(hold));
+                            ui.label("The passphrase is only needed to import this file; it is not stored anywhere.");
+                        } else {
+                            ui.colored_label(
+                                ui.visuals().warn_fg_color,
+                                "⚠ Without encryption the file is plaintext (see docs/json-export.md). Passwords are not exported.",
+                            );
+                        }
+                    }
+                }
+                let plaintext = self.transfer.format == ExportFormat::Csv || !self.transfer.encrypt;
+                self.export_button(ui, plaintext, Self::export_clients);
+                if ui.button("Cancel").clicked() {
+                    self.transfer.clear_passphrase();
+                    self.mode = AppMode::Normal;
+                }
+            }
+            AppMode::ExportingRdp => {
+                ui.label(format!("Write one .rdp file per client in {} for someone who connects with Remote Desktop directly:", self.transfer.group));
+                ui.horizontal(|ui| {
+                    ui.label("Folder:");
+                    ui.text_edit_singleline(&mut self.transfer.path);
+                });
+                ui.label("Subfolders become subdirectories. Files of the same name are overwritten.");
+                ui.checkbox(&mut self.transfer.rdp_passwords, "Include passwords (DPAPI-protected)");
+                if self.transfer.rdp_passwords {
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        "⚠ Saved passwords only work for your Windows account on this computer. Anyone else is asked for the password.",
+                    );
+                }
+                if ui.button("Export").clicked() {
+                    self.export_rdp_files();
+                }
+                if ui.button("Cancel").clicked() {
+                    self.mode = AppMode::Normal;
+                }
+            }
+            AppMode::ExportingInventory => {
+                ui.label("Export names, addresses, tags, groups and owners for NetBox or documentation tools. No passwords or user names are included.");
+                ui.horizontal(|ui| {
+                    ui.label("Format:");
+                    for (format, label) in [(InventoryFormat::Yaml, "YAML"), (InventoryFormat::Json, "JSON")] {
+                        if ui.radio(self.inventory_format_input == format, label).clicked() {
+                            if self.transfer.path == self.inventory_format_input.file_name() {
+                                self.transfer.path = format.file_name().to_string();
+                            }
+                            self.inventory_format_input = format;
+                        }
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("File:");
+                    ui.text_edit_singleline(&mut self.transfer.path);
+                });
+                ui.label("Field names (leave a name empty to leave the field out):");
+                egui::Grid::new("inventory_field_names").show(ui, |ui| {
+                    for (field, name) in &mut self.inventory_field_names_input {
+                        ui.label(*field);
+                        ui.add(egui::TextEdit::singleline(name).desired_width(160.0));
+                        ui.end_row();
+                    }
+                });
+                self.export_button(ui, true, Self::export_inventory);
+                if ui.button("Cancel").clicked() {
+                    self.mode = AppMode::Normal;
+                }
+            }
+            AppMode::HandoverReport => {
+                ui.label("Sessions opened or closed in a time range and the hosts currently flagged down, for the next shift:");
+                ui.horizontal(|ui| {
+                    ui.label("From:");
+                    ui.add(egui::TextEdit::singleline(&mut self.report_from_input).desired_width(130.0));
+                    ui.label("to:");
+                    ui.add(egui::TextEdit::singleline(&mut self.report_to_input).desired_width(130.0));
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Format:");
+                    for (format, label) in [(ReportFormat::Markdown, "Markdown"), (ReportFormat::Html, "HTML")] {
+                        if ui.radio(self.report_format_input == format, label).clicked() {
+                            if self.transfer.path == self.report_format_input.file_name() {
+                                self.transfer.path = format.file_name().to_string();
+                            }
+                            self.report_format_input = format;
+                        }
+                    }
+                });
+                match self.handover_report() {
+                    Ok(report) => {
+                        egui::ScrollArea::vertical().max_height(240.0).show(ui, |ui| {
+                            ui.add(egui::TextEdit::multiline(&mut report.as_str()).code_editor().desired_width(f32::INFINITY));
+                        });
+                    }
+                    Err(e) => {
+                        ui.colored_label(egui::Color32::RED, e);
+                    }
+                }
+                if ui.button("Copy").clicked() {
+                    self.copy_handover_report();
+                }
+                ui.horizontal(|ui| {
+                    ui.label("File:");
+                    ui.text_edit_singleline(&mut self.transfer.path);
+                    if ui.button("Save").clicked() {
+                        self.save_handover_report();
+                    }
+                });
+                if ui.button("Close").clicked() {
+                    self.mode = AppMode::Normal;
+                }
+            }
+            AppMode::Importing => {
+                ui.label("Import clients from CSV (rows with a known id update that client):");
+                ui.horizontal(|ui| {
+                    ui.label("File:");
+                    ui.text_edit_singleline(&mut self.transfer.path);
+                });
+                if ui.button("Import").clicked() {
+                    self.import_clients_csv();
+                }
+                if ui.button("Cancel").clicked() {
+                    self.mode = AppMode::Normal;
+                }
+            }
+            AppMode::ImportingRdg => {
+                ui.label("Import servers, groups and logon credentials from a Remote Desktop Connection Manager (.rdg) file:");
+                ui.horizontal(|ui| {
+                    ui.label("File:");
+                    ui.text_edit_singleline(&mut self.transfer.path);
+                });
+                if ui.button("Import").clicked() {
+                    self.import_clients_rdg();
+                }
+                if ui.button("Cancel").clicked() {
+                    self.mode = AppMode::Normal;
+                }
+            }
+            AppMode::ImportingHistory => {
+                ui.label("Hosts from the Remote Desktop Connection history that are not stored yet:");
+                for (entry, selected) in &mut self.history_import {
+                    let label = match entry.username_hint.as_str() {
+                        "" => entry.address.clone(),
+                        hint => format!("{} ({})", entry.address, hint),
+                    };
+                    ui.checkbox(selected, label);
+                }
+                if ui.button("Import").clicked() {
+                    self.import_mstsc_history();
+                }
+                if ui.button("Cancel").clicked() {
+                    self.history_import.clear();
+                    self.mode = AppMode::Normal;
+                }
+            }
+            AppMode::ImportingJson => {
+                ui.label("Import clients from a JSON export (known ids update that client):");
+                ui.horizontal(|ui| {
+                    ui.label("File:");
+                    ui.text_edit_singleline(&mut self.transfer.path);
+                });
+                let hold = self.settings.hold_to_reveal;
+                ui.add(SecretField::new("Passphrase (if the export is encrypted):", &mut self.transfer.passphrase).hold_to_reveal(hold));
+                if ui.button("Import").clicked() {
+                    self.import_clients_json();
+                }
+                if ui.button("Cancel").clicked() {
+                    self.transfer.passphrase.clear();
+                    self.mode = AppMode::Normal;
+                }
+            }
+            AppMode::Netbox => {
+                ui.label("Pull devices and virtual machines with a primary IP from NetBox, tagged with their site and role, and push your own tags back to them:");
+                ui.horizontal(|ui| {
+                    ui.label("NetBox address:");
+                    ui.text_edit_singleline(&mut self.netbox_url_input);
+                });
+                let hint = if self.settings.netbox_token.is_empty() { "" } else { "stored; type to replace" };
+                ui.add(SecretField::new("API token:", &mut self.netbox_token_input).hint_text(hint).hold_to_reveal(self.settings.hold_to_reveal));
+                ui.label("The token is stored encrypted with the vault key.");
+                if self.netbox_busy {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Talking to NetBox…");
+                    });
+                }
+                ui.add_enabled_ui(!self.netbox_busy, |ui| {
+                    if ui.button("Pull").clicked() {
+                        self.pull_from_netbox();
+                    }
+                    if ui.button("Push Tags").clicked() {
+                        self.push_to_netbox();
+                    }
+                });
+                if ui.button("Close").clicked() {
+                    self.netbox_token_input.zeroize();
+                    self.mode = AppMode::Normal;
+                }
+            }
+            AppMode::Monitoring => {
+                ui.horizontal(|ui| {
+                    let mut enabled = self.settings.reachability_check_seconds.is_some();
+                    let mut seconds = self.settings.reachability_check_seconds.unwrap_or(60);
+                    let mut changed = ui.checkbox(&mut enabled, "Check that each client's port answers every").changed();
+                    changed |= ui.add(egui::DragValue::new(&mut seconds).clamp_range(5..=3600)).changed();
+                    ui.label("seconds");
+                    if changed {
+                        self.settings.reachability_check_seconds = enabled.then_some(seconds);
+                        self.last_reachability_check = None;
+                        if let Err(e) = self.settings.save() {
+                            self.error_message = Some(format!("Could not save settings: {}", e));
+                        }
+                    }
+                });
+                let response = ui
+                    .add_enabled(self.settings.reachability_check_seconds.is_some(), egui::Checkbox::new(&mut self.settings.fingerprint_hosts, "Guess the operating system of hosts that answer"))
+                    .on_hover_text("Pings the host, tries a few telling ports and reads its SSH banner, then tags the client os:windows, os:linux or os:network. Clients with an os: tag of their own are left alone.");
+                if response.changed() {
+                    self.last_reachability_check = None;
+                    if let Err(e) = self.settings.save() {
+                        self.error_message = Some(format!("Could not save settings: {}", e));
+                    }
+                }
+                ui.separator();
+                ui.label("Show the host status reported by a monitoring system next to clients with a matching address:");
+                ui.horizontal(|ui| {
+                    for system in [MonitoringSystem::None, MonitoringSystem::Zabbix, MonitoringSystem::Prtg] {
+                        ui.radio_value(&mut self.monitoring_system_input, system, system.name());
+                    }
+                });
+                ui.add_enabled_ui(self.monitoring_system_input != MonitoringSystem::None, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Address:");
+                        let hint = match self.monitoring_system_input {
+                            MonitoringSystem::Prtg => "https://prtg.example.com",
+                            _ => "https://zabbix.example.com",
+                        };
+                        ui.add(egui::TextEdit::singleline(&mut self.monitoring_url_input).hint_text(hint));
+                    });
+                    let hint = if self.settings.monitoring_token.is_empty() { "" } else { "stored; type to replace" };
+                    ui.add(SecretField::new("API token:", &mut self.monitoring_token_input).hint_text(hint).hold_to_reveal(self.settings.hold_to_reveal));
+                    ui.horizontal(|ui| {
+                        ui.label("Refresh every");
+                        ui.add(egui::DragValue::new(&mut self.monitoring_refresh_input).clamp_range(1..=60));
+                        ui.label("minutes");
+                    });
+                    ui.label("The token is stored encrypted with the vault key. Zabbix needs version 6.4 or later.");
+                });
+                if ui.button("Save").clicked() {
+                    match self.save_monitoring_settings() {
+                        Ok(()) => self.mode = AppMode::Normal,
+                        Err(e) => self.error_message = Some(e),
+                    }
+                }
+                if ui.button("Cancel").clicked() {
+                    self.monitoring_token_input.zeroize();
+                    self.mode = AppMode::Normal;
+                }
+            }
+            AppMode::WatchFolder => {
+                ui.label("Automatically import .rdp, .csv and .rdg files dropped into this folder:");
+                ui.horizontal(|ui| {
+                    ui.label("Folder:");
+                    ui.text_edit_singleline(&mut self.watch_folder_input);
+                });
+                if ui.button("Save").clicked() {
+                    self.settings.watch_folder = self.watch_folder_input.trim().to_string();
+                    match self.settings.save() {
+                        Ok(()) => self.mode = AppMode::Normal,
+                        Err(e) => self.error_message = Some(format!("Could not save settings: {}", e)),
+                    }
+                }
+                if ui.button("Cancel").clicked() {
+                    self.mode = AppMode::Normal;
+                }
+            }
+            AppMode::CreateFromLink => {
+                let address = self.pending_link.as_ref().map(|link| link.address.clone()).unwrap_or_default();
+                let account = self.pending_link.as_ref().map(|link| link.account_for(&Client::default())).unwrap_or_default();
+                let protocol = self
+                    .pending_link
+                    .as_ref()
+                    .and_then(|link| Protocol::ALL.into_iter().find(|protocol| protocol.scheme() == link.scheme))
+                    .unwrap_or_default();
+                ui.label(format!("No client found for {}. Create one?", address));
+                if ui.button("Create").clicked() {
+                    self.form.clear();
+                    self.form.name = address.clone();
+                    self.form.ip = address;
+                    self.form.protocol = protocol;
+                    (self.form.username, self.form.domain) = (account.username, account.domain);
+                    self.pending_link = None;
+                    self.mode = AppMode::Adding;
+                }
+                if ui.button("Cancel").clicked() {
+                    self.pending_link = None;
+                    self.mode = AppMode::Normal;
+                }
+            }
+            AppMode::GroupContact => {
+                ui.label(format!("Whom to call about the clients in {} and its subfolders that have no owner of their own:", self.group_contact_input.0));
+                contact_grid(ui, "group_contact_grid", &mut self.group_contact_input.1);
+                ui.weak("Clear every field to remove the folder's contact.");
+                if ui.button("Save").clicked() {
+                    self.save_group_contact();
+                }
+                if ui.button("Cancel").clicked() {
+                    self.mode = AppMode::Normal;
+                }
+            }
+            AppMode::UrlTemplates => {
+                ui.label("Reference links (placeholders: {reference}, {name}, {ip}, {host}, {port}, {username}, {domain} and client variables):");
+                let mut removed = None;
+                for (index, template) in self.url_templates_input.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.label("Name:");
+                        ui.add(egui::TextEdit::singleline(&mut template.name).desired_width(100.0));
+                        ui.label("URL:");
+                        ui.text_edit_singleline(&mut template.url);
+                        if ui.button("Remove").clicked() {
+                            removed = Some(index);
+                        }
+                    });
+                }
+                if let Some(index) = removed {
+                    self.url_templates_input.remove(index);
+                }
+                if ui.button("Add Link").clicked() {
+                    self.url_templates_input.push(UrlTemplate::default());
+                }
+                if ui.button("Save").clicked() {
+                    self.settings.url_templates = self.url_templates_input.clone();
+                    match self.settings.save() {
+                        Ok(()) => self.mode = AppMode::Normal,
+                        Err(e) => self.error_message = Some(format!("Could not save settings: {}", e)),
+                    }
+                }
+                if ui.button("Cancel").clicked() {
+                    self.mode = AppMode::Normal;
+                }
+            }
+            AppMode::Search => {
+                ui.horizontal(|ui| {
+                    ui.label("Search everything:");
+                    let response = ui.text_edit_singleline(&mut self.search_query);
+                    if ui.memory(|memory| memory.focused().is_none()) {
+                        response.request_focus();
+                    }
+                    if ui.button("Close").clicked() {
+                        self.mode = AppMode::Normal;
+                    }
+                });
+                let results = self.search_index.search(&self.search_query);
+                if !self.search_query.trim().is_empty() {
+                    ui.label(format!("{} matching clients", results.len()));
+                }
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for result in results {
+                        let Some(client) = self.repository.get(&result.client_id) else {
+                            continue;
+                        };
+                        if ui.selectable_label(self.client_list.is_selected(client), egui::RichText::new(&client.name).strong()).clicked() {
+                            self.client_list.select(client);
+                            self.mode = AppMode::Normal;
+                        }
+                        for hit in result.hits {
+                            ui.horizontal(|ui| {
+                                ui.label(format!("{}:", hit.field));
+                                ui.label(highlight(&hit.text, &self.search_query, ui.style()));
+                            });
+                        }
+                    }
+                });
+            }
+            AppMode::Trash => {
+                ui.horizontal(|ui| {
+                    let mut enabled = self.settings.trash_expired_after_days.is_some();
+                    let mut changed = ui.checkbox(&mut enabled, "Move expired clients here after").changed();
+                    changed |= ui.add(egui::DragValue::new(&mut self.trash_after_days_input).clamp_range(0..=365)).changed();
+                    ui.label("days");
+                    if changed {
+                        self.settings.trash_expired_after_days = enabled.then_some(self.trash_after_days_input);
+                        self.last_expiry_check = None;
+                        if let Err(e) = self.settings.save() {
+                            self.error_message = Some(format!("Could not save settings: {}", e));
+                        }
+                    }
+                });
+                ui.separator();
+                let trashed: Vec<Client> = self.repository.query(&|c| c.trashed_at.is_some()).into_iter().cloned().collect();
+                if trashed.is_empty() {
+                    ui.label("The Trash is empty.");
+                }
+                let mut restore = None;
+                let mut delete = None;
+                for client in trashed {
+                    ui.horizontal(|ui| {
+                        ui.label(egui::RichText::new(&client.name).strong());
+                        if let Some(expires) = client.expires {
+                            ui.label(format!("expired {}", expires));
+                        }
+                        if ui.button("Restore").clicked() {
+                            restore = Some(client.clone());
+                        }
+                        if ui.button("Delete Permanently").clicked() {
+                            delete = Some(client.id.clone());
+                        }
+                    });
+                }
+                if let Some(mut client) = restore {
+                    client.trashed_at = None;
+                    client.expires = None;
+                    self.repository.update(client);
+                    self.save_clients();
+                }
+                if let Some(id) = delete {
+                    self.repository.remove(&id);
+                    self.save_clients();
+                }
+                if ui.button("Back").clicked() {
+                    self.mode = AppMode::Normal;
+                }
+            }
+            AppMode::ChangingMasterKey => {
+                ui.heading("Change Master Key");
+                ui.label("The vault is re-encrypted with the new key. Keep the old one until it has been saved.");
+                let (hold, min_bits) = (self.settings.hold_to_reveal, self.min_master_key_bits());
+                ui.add(SecretField::new("Current master key:", &mut self.unlock_form.current).hold_to_reveal(hold));
+                ui.add(SecretField::new("New master key:", &mut self.unlock_form.master_key).hold_to_reveal(hold).strength(min_bits));
+                ui.add(SecretField::new("Confirm:", &mut self.unlock_form.confirm).hold_to_reveal(hold));
+                if ui.button("Change").clicked() {
+                    match self.change_master_key() {
+                        Ok(()) => {
+                            self.info_message = Some("The master key was changed.".to_string());
+                            self.mode = AppMode::Normal;
+                        }
+                        Err(e) => self.error_message = Some(e),
+                    }
+                }
+                if ui.button("Cancel").clicked() {
+                    self.unlock_form.clear();
+                    self.mode = AppMode::Normal;
+                }
+            }
+            AppMode::Settings => self.settings_view(ui),
+            AppMode::RestoringBackup => self.restore_backup_view(ui),
+            AppMode::Sessions => self.sessions_board(ui),
+            AppMode::History => self.history_view(ui),
+            AppMode::ImportConflicts => self.import_conflicts_view(ui),
+            AppMode::Duplicates => self.duplicates_view(ui),
+            AppMode::HostsFile => self.hosts_file_view(ui),
+            AppMode::Credentials => self.credentials_view(ui),
+            AppMode::RotatingPasswords => self.rotation_view(ui),
+            AppMode::ConnectingGroup => self.group_connect_view(ui),
+            AppMode::Composition => self.composition_view(ui),
+            AppMode::Normal | AppMode::Adding | AppMode::Editing => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opening_a_transfer_forgets_the_last_passphrase() {
+        let mut dialog = TransferDialog { passphrase: "secret".to_string(), ..Default::default() };
+        dialog.open("clients.csv");
+        assert_eq!(dialog.path, "clients.csv");
+        assert!(dialog.passphrase.is_empty());
+    }
+
+    #[test]
+    fn switching_the_export_format_keeps_a_custom_path() {
+        let mut dialog = TransferDialog::default();
+        dialog.set_format(ExportFormat::Json);
+        assert_eq!(dialog.path, "clients.export.json");
+        dialog.set_format(ExportFormat::Csv);
+        assert_eq!(dialog.path, "clients.csv");
+        dialog.path = "backup/hosts.csv".to_string();
+        dialog.set_format(ExportFormat::Json);
+        assert_eq!(dialog.path, "backup/hosts.csv");
+    }
+
+    #[test]
+    fn inventory_exports_leave_out_secrets_and_rename_fields() {
+        use crate::inventory::{export_inventory, InventoryFormat};
+        use std::collections::BTreeMap;
+        let client = Client {
+            name: "db01".to_string(),
+            ip: "10.0.0.5".to_string(),
+            username: "admin".to_string(),
+            password: "hunter2".to_string(),
+            tags: vec!["prod".to_string()],
+            ..Default::default()
+        };
+        let field_names = BTreeMap::from([("address".to_string(), "primary_ip".to_string()), ("id".to_string(), String::new())]);
+        let yaml = export_inventory(&[client], InventoryFormat::Yaml, &field_names).unwrap();
+        assert!(yaml.starts_with("- name: db01\n  primary_ip: 10.0.0.5\n  protocol: rdp\n"), "{}", yaml);
+        assert!(yaml.contains("  tags:\n  - prod\n"));
+        assert!(!yaml.contains("id:") && !yaml.contains("admin") && !yaml.contains("hunter2"));
+    }
+
+    #[test]
+    fn handover_reports_list_sessions_in_range_and_down_hosts() {
+        use crate::history::ConnectionRecord;
+        use chrono::{Local, TimeZone, Utc};
+        let mut state = AppState::unlocked_for_test();
+        let web = Client { name: "web <01>".to_string(), ip: "10.0.0.5".to_string(), ..Default::default() };
+        state.repository.add(web.clone());
+        state.reachability.insert(web.id.clone(), None);
+        let at = |hour| Local.with_ymd_and_hms(2026, 10, 16, hour, 0, 0).unwrap().with_timezone(&Utc);
+        let record = |name: &str, started, ended| ConnectionRecord {
+            client_id: String::new(),
+            client_name: name.to_string(),
+            address: "10.0.0.12".to_string(),
+            started,
+            ended,
+            error: None,
+            password_changed: false,
+        };
+        state.repository.history = vec![
+            record("before", at(1), Some(at(2))),
+            record("SQL|02", at(5), Some(at(9))),
+            record("jump", at(10), None),
+        ];
+        state.report_from_input = "2026-10-16 06:00".to_string();
+        state.report_to_input = "2026-10-16 18:00".to_string();
+        let report = state.handover_report().unwrap();
+        assert!(report.contains("## Sessions (2)"), "{}", report);
+        assert!(report.contains("| SQL\\|02 | 10.0.0.12 | 2026-10-16 05:00 | 09:00 | 4:00 |"), "{}", report);
+        assert!(report.contains("| jump | 10.0.0.12 | 2026-10-16 10:00 | still open |  |"), "{}", report);
+        assert!(!report.contains("before"));
+        assert!(report.contains("- **web <01>** (10.0.0.5): no answer on port 3389"), "{}", report);
+
+        state.report_format_input = ReportFormat::Html;
+        let report = state.handover_report().unwrap();
+        assert!(report.contains("<li><strong>web &lt;01&gt;</strong> (10.0.0.5): no answer on port 3389</li>"), "{}", report);
+
+        state.report_to_input = "yesterday".to_string();
+        assert!(state.handover_report().is_err());
+    }
+
+    #[test]
+    fn export_returns_to_normal_mode() {
+        let mut state = AppState::unlocked_for_test();
+        state.repository.add(Client { name: "db01".to_string(), ..Default::default() });
+        let path = std::env::temp_dir().join(format!("rdm-export-{}.csv", crate::client::new_client_id()));
+        state.transfer.open(&path.to_string_lossy());
+        state.mode = AppMode::Exporting;
+        state.export_clients_csv();
+        assert_eq!(state.mode, AppMode::Normal);
+        assert!(state.info_message.is_some());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn watched_files_are_imported_once_settled_and_only_once() {
+        let mut state = AppState::unlocked_for_test();
+        let folder = std::env::temp_dir().join(format!("rdm-watch-{}", crate::client::new_client_id()));
+        std::fs::create_dir_all(&folder).unwrap();
+        state.settings.watch_folder = folder.to_string_lossy().into_owned();
+        let scan = |state: &mut AppState| {
+            state.last_watch_scan = std::time::Instant::now() - crate::watch::WATCH_INTERVAL;
+            state.poll_watch_folder();
+            state.repository.clients().len()
+        };
+        std::fs::write(folder.join("web01.rdp"), "full address:s:10.0.0.9\r\n").unwrap();
+        assert_eq!(scan(&mut state), 0, "a file seen for the first time may still be written");
+        assert_eq!(scan(&mut state), 1);
+        assert!(folder.join("imported").join("web01.rdp").is_file());
+
+        // A file where the archive folder should be keeps the next one in place.
+        std::fs::write(folder.join("failed"), "").unwrap();
+        std::fs::write(folder.join("db01.rdp"), "no address").unwrap();
+        scan(&mut state);
+        scan(&mut state);
+        assert!(state.error_message.take().unwrap().starts_with("Could not move imported files"));
+        assert!(folder.join("db01.rdp").is_file());
+        scan(&mut state);
+        assert!(state.error_message.is_none(), "the unmoved file was imported again");
+        let _ = std::fs::remove_dir_all(folder);
+    }
+
+    #[test]
+    fn links_match_clients_by_host_and_port_and_carry_their_user() {
+        use crate::link::{matches_address, parse_link};
+        use crate::settings::DefaultPorts;
+        let address = |url: &str| parse_link(url).map(|link| (link.address, link.user));
+        assert_eq!(address("ssh://[fe80::1]:2222"), Some(("[fe80::1]:2222".to_string(), None)));
+        assert_eq!(address("rdp://[fe80::1]"), Some(("fe80::1".to_string(), None)));
+        assert_eq!(address("ssh://deploy@web01:2222/"), Some(("web01:2222".to_string(), Some("deploy".to_string()))));
+        assert_eq!(address("rdp://full%20address=s:db01:3390&username=s:CORP%5Cadmin"), Some(("db01:3390".to_string(), Some("CORP\\admin".to_string()))));
+        assert!(parse_link("rdp://web01:http").is_none());
+        assert!(parse_link("rdp://[fe80::1]:x").is_none());
+
+        let defaults = DefaultPorts::default();
+        let web = Client { name: "web01".to_string(), ip: "WEB01".to_string(), protocol: Protocol::Ssh, ..Default::default() };
+        assert!(matches_address(&web, "web01", &defaults));
+        assert!(matches_address(&web, "web01:22", &defaults));
+        assert!(!matches_address(&web, "web01:2222", &defaults));
+        assert!(!matches_address(&web, "web011", &defaults));
+        let v6 = Client { ip: "[fe80::1]:3390".to_string(), ..Default::default() };
+        assert!(matches_address(&v6, "[fe80::1]:3390", &defaults));
+        assert!(!matches_address(&v6, "fe80::1", &defaults));
+
+        let mut state = AppState::unlocked_for_test();
+        let db = Client { name: "db01".to_string(), ip: "db01".to_string(), username: "svc".to_string(), password: "hunter2".to_string(), ..Default::default() };
+        state.repository.add(Client { tags: vec!["prod".to_string()], ..db.clone() });
+        state.settings.confirmations.connect_tags = vec!["prod".to_string()];
+        state.pending_link = parse_link("rdp://CORP%5Cadmin@db01:3389");
+        state.handle_pending_link();
+        let confirmed = state.confirm_connect.take().unwrap();
+        assert_eq!((confirmed.domain.as_str(), confirmed.username.as_str(), confirmed.password.as_str()), ("CORP", "admin", ""));
+        state.pending_link = parse_link("rdp://svc@db01");
+        state.handle_pending_link();
+        assert_eq!(state.confirm_connect.take().unwrap().password, "hunter2");
+    }
+
+    #[test]
+    fn failed_import_keeps_the_dialog_open() {
+        let mut state = AppState::unlocked_for_test();
+        state.transfer.open("does-not-exist.csv");
+        state.mode = AppMode::Importing;
+        state.import_clients_csv();
+        assert_eq!(state.mode, AppMode::Importing);
+        assert!(state.error_message.is_some());
+    }
+
+    #[test]
+    fn history_import_adds_only_ticked_hosts() {
+        use crate::mstsc_history::HistoryEntry;
+        let mut state = AppState::unlocked_for_test();
+        state.history_import = vec![
+            (HistoryEntry { address: "web01".to_string(), username_hint: "CORP\\admin".to_string() }, true),
+            (HistoryEntry { address: "db01".to_string(), username_hint: String::new() }, false),
+        ];
+        state.mode = AppMode::ImportingHistory;
+        state.import_mstsc_history();
+        assert_eq!(state.mode, AppMode::Normal);
+        let clients = state.repository.clients();
+        assert_eq!(clients.len(), 1);
+        assert_eq!((clients[0].ip.as_str(), clients[0].domain.as_str(), clients[0].username.as_str()), ("web01", "CORP", "admin"));
+    }
+}
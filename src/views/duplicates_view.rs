@@ -0,0 +1,199 @@
+use crate::app::AppState;
+use crate::client::{AppMode, Client};
+use crate::duplicates::{duplicate_reason, find_duplicates, merge_into, Resolution};
+use crate::repository::ClientRepository;
+use eframe::egui;
+
+/// One line describing `client` in the duplicate lists.
+fn summary(client: &Client) -> String {
+    let mut parts = vec![client.address()];
+    if let Some(account) = client.account() {
+        parts.push(account);
+    }
+    if !client.group.is_empty() {
+        parts.push(client.group.clone());
+    }
+    format!("{} ({})", client.name, parts.join(", "))
+}
+
+impl AppState {
+    /// Opens Tools → Find Duplicates on the clients stored now.
+    pub fn open_duplicates(&mut self) {
+        let clients = self.repository.clients();
+        self.duplicate_groups = find_duplicates(clients).into_iter().map(|group| group.into_iter().map(|index| clients[index].id.clone()).collect()).collect();
+        self.mode = AppMode::Duplicates;
+    }
+
+    /// Merges the other clients of a duplicate group into `keep` and moves
+    /// them to the Trash, where they can still be restored.
+    pub fn keep_duplicate(&mut self, keep: &str, others: &[String]) {
+        let Some(mut kept) = self.repository.get(keep).cloned() else {
+            return;
+        };
+        for id in others {
+            let Some(mut other) = self.repository.get(id).cloned() else {
+                continue;
+            };
+            // Attachments stay with the trashed client for a restore.
+            merge_into(&mut kept, Client { attachments: Vec::new(), ..other.clone() });
+            other.trashed_at = Some(chrono::Utc::now());
+            self.repository.update(other);
+        }
+        self.repository.update(kept);
+        self.save_clients();
+        self.duplicate_groups.retain(|group| !group.iter().any(|id| id == keep));
+    }
+
+    pub(crate) fn import_conflicts_view(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Possible Duplicates");
+        ui.label("These imported clients look like clients already stored. Choose what to do with each:");
+        ui.horizontal(|ui| {
+            ui.label("All:");
+            for resolution in Resolution::ALL {
+                if ui.button(resolution.label()).clicked() {
+                    self.import_conflicts.iter_mut().for_each(|conflict| conflict.resolution = resolution);
+                }
+            }
+        });
+        ui.separator();
+        egui::ScrollArea::vertical().max_height(360.0).show(ui, |ui| {
+            egui::Grid::new("import_conflicts").striped(true).num_columns(3).show(ui, |ui| {
+                for (index, conflict) in self.import_conflicts.iter_mut().enumerate() {
+                    let Some(existing) = self.repository.get(&conflict.existing_id) else {
+                        continue;
+                    };
+                    ui.label(summary(&conflict.incoming));
+                    let reason = duplicate_reason(existing, &conflict.incoming).unwrap_or("alike");
+                    ui.label(format!("{}: {}", reason, summary(existing)));
+                    ui.horizontal(|ui| {
+                        for resolution in Resolution::ALL {
+                            ui.push_id(index, |ui| ui.radio_value(&mut conflict.resolution, resolution, resolution.label()));
+                        }
+                    });
+                    ui.end_row();
+                }
+            });
+        });
+        ui.weak("Merge fills in what the stored client lacks; Overwrite replaces its details but keeps its screenshots.");
+        ui.horizontal(|ui| {
+            if ui.button("Apply").clicked() {
+                self.resolve_import_conflicts();
+            }
+            if ui.button("Skip All").clicked() {
+                self.import_conflicts.iter_mut().for_each(|conflict| conflict.resolution = Resolution::Skip);
+                self.resolve_import_conflicts();
+            }
+        });
+    }
+
+    pub(crate) fn duplicates_view(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Duplicates");
+        if self.duplicate_groups.is_empty() {
+            ui.label("No clients look alike.");
+        } else {
+            ui.label("Clients with the same name, or the same address, protocol and user. Keep one to merge the others into it and move them to the Trash.");
+        }
+        let mut keep = None;
+        egui::ScrollArea::vertical().max_height(360.0).show(ui, |ui| {
+            for group in &self.duplicate_groups {
+                ui.separator();
+                for id in group {
+                    let Some(client) = self.repository.get(id) else {
+                        continue;
+                    };
+                    ui.horizontal(|ui| {
+                        if ui.button("Keep").clicked() {
+                            let others: Vec<String> = group.iter().filter(|other| *other != id).cloned().collect();
+                            keep = Some((id.clone(), others));
+                        }
+                        ui.label(summary(client));
+                    });
+                }
+            }
+        });
+        if let Some((id, others)) = keep {
+            self.keep_duplicate(&id, &others);
+        }
+        ui.horizontal(|ui| {
+            if ui.button("Refresh").clicked() {
+                self.open_duplicates();
+            }
+            if ui.button("Back").clicked() {
+                self.mode = AppMode::Normal;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::duplicates::split_conflicts;
+
+    fn client(name: &str, ip: &str, username: &str) -> Client {
+        Client { name: name.to_string(), ip: ip.to_string(), username: username.to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn same_name_or_same_address_and_user_are_duplicates() {
+        let stored = client("db01", "10.0.0.5", "admin");
+        assert_eq!(duplicate_reason(&stored, &client("DB01 ", "10.0.0.9", "")), Some("same name"));
+        assert_eq!(duplicate_reason(&stored, &client("database", "10.0.0.5", "Admin")), Some("same address and user"));
+        assert_eq!(duplicate_reason(&stored, &client("database", "10.0.0.5", "backup")), None);
+        assert_eq!(duplicate_reason(&stored, &client("database", "10.0.0.5:3390", "admin")), None);
+    }
+
+    #[test]
+    fn imports_hold_back_duplicates_but_not_updates() {
+        let stored = vec![client("db01", "10.0.0.5", "admin")];
+        let update = Client { name: "db01".to_string(), ..stored[0].clone() };
+        let (clean, conflicts) = split_conflicts(&stored, vec![update, client("db01", "10.0.0.6", ""), client("web01", "10.0.0.7", "")]);
+        assert_eq!(clean.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(), ["db01", "web01"]);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].existing_id, stored[0].id);
+    }
+
+    #[test]
+    fn resolutions_merge_skip_or_overwrite() {
+        let mut state = AppState::unlocked_for_test();
+        let stored = Client { tags: vec!["prod".to_string()], ..client("db01", "10.0.0.5", "") };
+        state.repository.add(stored.clone());
+        let incoming = Client { tags: vec!["sql".to_string()], password: "hunter2".to_string(), ..client("db01", "db01.corp", "admin") };
+
+        state.merge_import(vec![incoming.clone()]);
+        assert_eq!(state.mode, AppMode::ImportConflicts);
+        state.resolve_import_conflicts();
+        let merged = state.repository.get(&stored.id).unwrap();
+        assert_eq!((merged.ip.as_str(), merged.username.as_str(), merged.password.as_str()), ("10.0.0.5", "admin", "hunter2"));
+        assert_eq!(merged.tags, ["prod", "sql"]);
+
+        state.merge_import(vec![Client { ip: "10.9.9.9".to_string(), ..incoming.clone() }]);
+        state.import_conflicts[0].resolution = Resolution::Skip;
+        state.resolve_import_conflicts();
+        assert_eq!(state.repository.get(&stored.id).unwrap().ip, "10.0.0.5");
+
+        state.merge_import(vec![Client { ip: "10.9.9.9".to_string(), password: String::new(), ..incoming }]);
+        state.import_conflicts[0].resolution = Resolution::Overwrite;
+        state.resolve_import_conflicts();
+        let overwritten = state.repository.get(&stored.id).unwrap();
+        assert_eq!((overwritten.ip.as_str(), overwritten.password.as_str()), ("10.9.9.9", "hunter2"));
+        assert_eq!(state.repository.clients().len(), 1);
+    }
+
+    #[test]
+    fn keeping_one_duplicate_trashes_the_others() {
+        let mut state = AppState::unlocked_for_test();
+        let (a, b, c) = (client("db01", "10.0.0.5", ""), client("DB01", "", "admin"), client("web01", "10.0.0.7", ""));
+        for client in [&a, &b, &c] {
+            state.repository.add(client.clone());
+        }
+        state.open_duplicates();
+        assert_eq!(state.duplicate_groups, [vec![a.id.clone(), b.id.clone()]]);
+        state.keep_duplicate(&a.id, std::slice::from_ref(&b.id));
+        assert_eq!(state.repository.get(&a.id).unwrap().username, "admin");
+        assert!(state.repository.get(&b.id).unwrap().trashed_at.is_some());
+        assert!(state.duplicate_groups.is_empty());
+        state.open_duplicates();
+        assert!(state.duplicate_groups.is_empty());
+    }
+}
@@ -0,0 +1,1005 @@
+use crate::app::AppState;
+use crate::attachment::Attachment;
+use crate::client::{known_farms, parse_tags, split_host_port, AppMode, Client, Contact, DisplaySettings, Protocol, RdGateway, RdsFarm, SessionLimit, WindowPlacement};
+use crate::credential;
+use crate::dependencies;
+use crate::expiry::parse_expiry;
+use crate::repository::ClientRepository;
+use crate::route;
+use crate::template::{is_valid_name, BUILT_IN};
+use crate::settings::Redirection;
+use crate::views::secret_field::{confirm_master_key, SecretField};
+use crate::views::settings_view::redirection_checkboxes;
+use eframe::egui;
+use std::collections::BTreeMap;
+use zeroize::Zeroize;
+
+/// A part of the form, for marking unsaved changes and invalid input.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FormField {
+    Name,
+    Ip,
+    Port,
+    Group,
+    Tags,
+    InfoOnly,
+    Protocol,
+    Interface,
+    Farm,
+    Gateway,
+    Credential,
+    Username,
+    Domain,
+    Password,
+    Reference,
+    Expires,
+    Owner,
+    Notes,
+    Variables,
+    Placement,
+    Display,
+    Redirection,
+    SessionLimit,
+    Prerequisites,
+    Attachments,
+}
+
+/// Fields of the Add and Edit forms, with the client they were loaded from
+/// so unsaved changes can be told apart field by field.
+#[derive(Default)]
+pub struct ClientForm {
+    pub name: String,
+    pub ip: String,
+    /// Port override as typed; empty uses the default.
+    pub port: String,
+    pub interface: String,
+    /// Farm name and broker as typed; an empty name means no farm.
+    pub farm: RdsFarm,
+    /// Gateway as typed; an empty host means none.
+    pub gateway: RdGateway,
+    pub protocol: Protocol,
+    pub group: String,
+    /// Id of the shared credential chosen as the account; `None` for the
+    /// client's own username and password below.
+    pub credential: Option<String>,
+    pub password: String,
+    pub username: String,
+    pub domain: String,
+    pub reference: String,
+    pub attachments: Vec<Attachment>,
+    pub info_only: bool,
+    pub owner: Contact,
+    pub notes: String,
+    pub expires: String,
+    /// Client variables as editable name/value rows.
+    pub variables: Vec<(String, String)>,
+    pub fixed_placement: bool,
+    pub placement: WindowPlacement,
+    pub own_display: bool,
+    pub display: DisplaySettings,
+    pub own_redirection: bool,
+    pub redirection: Redirection,
+    pub limit_session: bool,
+    pub session_limit: SessionLimit,
+    /// Tags as typed, separated by commas.
+    pub tags: String,
+    /// Ids of the clients connected first by Connect All.
+    pub prerequisites: Vec<String>,
+    /// The client being edited, or a blank one when adding.
+    baseline: Client,
+    /// Discarding was requested with unsaved changes and waits for
+    /// confirmation.
+    pub confirm_discard: bool,
+}
+
+impl ClientForm {
+    pub fn clear(&mut self) {
+        self.password.zeroize();
+        self.baseline.password.zeroize();
+        *self = Self::default();
+    }
+
+    pub fn load(&mut self, client: &Client) {
+        self.baseline = client.clone();
+        self.confirm_discard = false;
+        self.name = client.name.clone();
+        self.ip = client.ip.clone();
+        self.port = client.port.map(|port| port.to_string()).unwrap_or_default();
+        self.interface = client.interface.clone();
+        self.farm = client.farm.clone().unwrap_or_default();
+        self.gateway = client.gateway.clone().unwrap_or_default();
+        self.protocol = client.protocol;
+        self.group = client.group.clone();
+        self.credential = client.credential.clone();
+        self.password = client.password.clone();
+        self.username = client.username.clone();
+        self.domain = client.domain.clone();
+        self.reference = client.reference.clone();
+        self.attachments = client.attachments.clone();
+        self.info_only = client.info_only;
+        self.owner = client.owner.clone();
+        self.notes = client.notes.clone();
+        self.expires = client.expires.map(|date| date.to_string()).unwrap_or_default();
+        self.variables = client.variables.clone().into_iter().collect();
+        self.fixed_placement = client.placement.is_some();
+        self.placement = client.placement.unwrap_or_default();
+        self.own_display = client.display.is_some();
+        self.display = client.display.unwrap_or_default();
+        self.own_redirection = client.redirection.is_some();
+        self.redirection = client.redirection.unwrap_or_default();
+        self.limit_session = client.session_limit.is_some();
+        self.session_limit = client.session_limit.unwrap_or_default();
+        self.tags = client.tags.join(", ");
+        self.prerequisites = client.prerequisites.clone();
+    }
+
+    /// Id of the client the form was loaded from; a fresh id when adding.
+    pub fn client_id(&self) -> &str {
+        &self.baseline.id
+    }
+
+    /// Fields that differ from the loaded client, or from a blank one.
+    pub fn dirty_fields(&self) -> Vec<FormField> {
+        let client = &self.baseline;
+        let variables = self.variables.iter().map(|(name, value)| (name.as_str(), value.as_str()));
+        [
+            (FormField::Name, self.name != client.name),
+            (FormField::Ip, self.ip != client.ip),
+            (FormField::Port, self.port != client.port.map(|port| port.to_string()).unwrap_or_default()),
+            (FormField::Group, self.group != client.group),
+            (FormField::Tags, self.tags != client.tags.join(", ")),
+            (FormField::InfoOnly, self.info_only != client.info_only),
+            (FormField::Protocol, self.protocol != client.protocol),
+            (FormField::Interface, self.interface != client.interface),
+            (FormField::Farm, self.parsed_farm() != client.farm),
+            (FormField::Gateway, self.parsed_gateway() != client.gateway),
+            (FormField::Credential, self.credential != client.credential),
+            (FormField::Username, self.username != client.username),
+            (FormField::Domain, self.domain != client.domain),
+            (FormField::Password, self.password != client.password),
+            (FormField::Reference, self.reference != client.reference),
+            (FormField::Expires, self.expires != client.expires.map(|date| date.to_string()).unwrap_or_default()),
+            (FormField::Owner, self.owner != client.owner),
+            (FormField::Notes, self.notes != client.notes),
+            (FormField::Variables, !variables.eq(client.variables.iter().map(|(name, value)| (name.as_str(), value.as_str())))),
+            (FormField::Placement, (self.fixed_placement.then_some(self.placement)) != client.placement),
+            (FormField::Display, self.parsed_display() != client.display),
+            (FormField::Redirection, self.parsed_redirection() != client.redirection),
+            (FormField::SessionLimit, (self.limit_session.then_some(self.session_limit)) != client.session_limit),
+            (FormField::Prerequisites, self.prerequisites != client.prerequisites),
+            (FormField::Attachments, self.attachments != client.attachments),
+        ]
+        .into_iter()
+        .filter_map(|(field, dirty)| dirty.then_some(field))
+        .collect()
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        !self.dirty_fields().is_empty()
+    }
+
+    /// Every field that would stop the form from being saved, with why.
+    pub fn problems(&self) -> Vec<(FormField, String)> {
+        let mut problems = Vec::new();
+        if let Err(e) = self.parsed_port() {
+            problems.push((FormField::Port, e));
+        }
+        if let Err(e) = parse_expiry(&self.expires) {
+            problems.push((FormField::Expires, e));
+        }
+        if let Err(e) = self.parsed_variables() {
+            problems.push((FormField::Variables, e));
+        }
+        if self.protocol == Protocol::Rdp && self.parsed_farm().is_some_and(|farm| farm.broker.is_empty()) {
+            problems.push((FormField::Farm, "A farm needs the address of its connection broker.".to_string()));
+        }
+        problems
+    }
+
+    /// Copies the form into `client`, leaving it untouched if a field is invalid.
+    pub fn apply_to(&self, client: &mut Client) -> Result<(), String> {
+        let expires = parse_expiry(&self.expires)?;
+        let variables = self.parsed_variables()?;
+        let port = self.parsed_port()?;
+        let farm = if self.protocol == Protocol::Rdp { self.parsed_farm() } else { None };
+        if farm.as_ref().is_some_and(|farm| farm.broker.is_empty()) {
+            return Err("A farm needs the address of its connection broker.".to_string());
+        }
+        client.name = self.name.clone();
+        client.ip = self.ip.clone();
+        client.port = port;
+        client.interface = self.interface.trim().to_string();
+        client.farm = farm;
+        client.gateway = if self.protocol == Protocol::Rdp { self.parsed_gateway() } else { None };
+        client.protocol = self.protocol;
+        client.group = self.group.split('/').map(str::trim).filter(|name| !name.is_empty()).collect::<Vec<_>>().join("/");
+        client.credential = self.credential.clone();
+        if client.credential.is_some() {
+            client.password.zeroize();
+            client.password.clear();
+            client.username.clear();
+            client.domain.clear();
+        } else {
+            client.password = self.password.clone();
+            client.username = self.username.trim().to_string();
+            client.domain = self.domain.trim().to_string();
+        }
+        client.reference = self.reference.clone();
+        client.attachments = self.attachments.clone();
+        client.info_only = self.info_only;
+        client.owner = self.owner.clone();
+        client.notes = self.notes.clone();
+        client.expires = expires;
+        client.placement = self.fixed_placement.then_some(self.placement);
+        client.display = self.parsed_display();
+        client.redirection = self.parsed_redirection();
+        client.session_limit = self.limit_session.then_some(self.session_limit);
+        client.tags = parse_tags(&self.tags);
+        client.prerequisites = self.prerequisites.clone();
+        client.variables = variables;
+        Ok(())
+    }
+
+    /// The port override, rejecting one that contradicts a port already
+    /// written into the address.
+    fn parsed_port(&self) -> Result<Option<u16>, String> {
+        let port = self.port.trim();
+        if port.is_empty() {
+            return Ok(None);
+        }
+        let port = match port.parse::<u16>() {
+            Ok(port) if port > 0 => port,
+            _ => return Err("The port must be a number from 1 to 65535.".to_string()),
+        };
+        match split_host_port(self.ip.trim()) {
+            (_, Some(address_port)) if address_port != port => {
+                Err(format!("The address already names port {}; remove it there or clear the port field.", address_port))
+            }
+            _ => Ok(Some(port)),
+        }
+    }
+
+    /// The farm as it would be saved; `None` while its name is empty.
+    fn parsed_farm(&self) -> Option<RdsFarm> {
+        let name = self.farm.name.trim();
+        (!name.is_empty()).then(|| RdsFarm { name: name.to_string(), broker: self.farm.broker.trim().to_string() })
+    }
+
+    /// The display settings as they would be saved; only RDP clients have
+    /// their own.
+    fn parsed_display(&self) -> Option<DisplaySettings> {
+        (self.own_display && self.protocol == Protocol::Rdp).then_some(self.display)
+    }
+
+    /// The redirection as it would be saved; only RDP clients have their own.
+    fn parsed_redirection(&self) -> Option<Redirection> {
+        (self.own_redirection && self.protocol == Protocol::Rdp).then_some(self.redirection)
+    }
+
+    /// The gateway as it would be saved; `None` while its host is empty.
+    fn parsed_gateway(&self) -> Option<RdGateway> {
+        let host = self.gateway.host.trim();
+        (!host.is_empty()).then(|| RdGateway { host: host.to_string(), ..self.gateway.clone() })
+    }
+
+    /// The variable rows as a map, skipping rows left completely empty.
+    fn parsed_variables(&self) -> Result<BTreeMap<String, String>, String> {
+        let mut variables = BTreeMap::new();
+        for (name, value) in &self.variables {
+            let name = name.trim();
+            if name.is_empty() && value.is_empty() {
+                continue;
+            }
+            if !is_valid_name(name) {
+                return Err(format!("Variable name {:?} may only contain letters, digits and underscores.", name));
+            }
+            if BUILT_IN.contains(&name) {
+                return Err(format!("{{{}}} is a built-in placeholder and cannot be a variable.", name));
+            }
+            if variables.insert(name.to_string(), value.clone()).is_some() {
+                return Err(format!("Variable {{{}}} is defined twice.", name));
+            }
+        }
+        Ok(variables)
+    }
+}
+
+/// A field's label, in `changed_color` while it has unsaved changes.
+fn label_text(text: &str, changed_color: Option<egui::Color32>) -> egui::RichText {
+    match changed_color {
+        Some(color) => egui::RichText::new(text).color(color),
+        None => egui::RichText::new(text),
+    }
+}
+
+/// Why `field` cannot be saved, under it, if it cannot.
+fn problem_note(ui: &mut egui::Ui, problems: &[(FormField, String)], field: FormField) {
+    for (_, problem) in problems.iter().filter(|(problem_field, _)| *problem_field == field) {
+        ui.colored_label(ui.visuals().error_fg_color, problem);
+    }
+}
+
+impl AppState {
+    /// The Add or Edit form, depending on the current mode.
+    pub(crate) fn editor_panel(&mut self, ui: &mut egui::Ui) {
+        let editing = match self.mode {
+            AppMode::Editing => match self.repository.position(self.form.client_id()) {
+                Some(index) => Some(index),
+                None => {
+                    self.error_message = Some("The client was removed while it was being edited.".to_string());
+                    self.cancel_form();
+                    return;
+                }
+            },
+            _ => None,
+        };
+        let dirty = self.form.dirty_fields();
+        let problems = self.form.problems();
+        let changed_color = ui.visuals().warn_fg_color;
+        let label = |field: FormField, text: &str| label_text(text, dirty.contains(&field).then_some(changed_color));
+        ui.horizontal(|ui| {
+            ui.label(if editing.is_some() { "Edit Client:" } else { "Add New Client:" });
+            if !dirty.is_empty() {
+                ui.colored_label(ui.visuals().warn_fg_color, "unsaved changes");
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(label(FormField::Name, "Name:"));
+            ui.text_edit_singleline(&mut self.form.name);
+        });
+        ui.horizontal(|ui| {
+            ui.label(label(FormField::Ip, "IP:"));
+            ui.text_edit_singleline(&mut self.form.ip);
+            if !self.form.info_only {
+                ui.label(label(FormField::Port, "Port:"));
+                ui.add(egui::TextEdit::singleline(&mut self.form.port).desired_width(50.0).hint_text(self.settings.default_ports.get(self.form.protocol).to_string()));
+            }
+        });
+        problem_note(ui, &problems, FormField::Port);
+        ui.horizontal(|ui| {
+            ui.label(label(FormField::Group, "Group:"));
+            ui.add(egui::TextEdit::singleline(&mut self.form.group).hint_text("Customers/Acme"));
+        });
+        ui.horizontal(|ui| {
+            ui.label(label(FormField::Tags, "Tags:"));
+            ui.add(egui::TextEdit::singleline(&mut self.form.tags).hint_text("prod, customer-a"));
+        });
+        ui.checkbox(&mut self.form.info_only, label(FormField::InfoOnly, "Info only (no connection)"));
+        if !self.form.info_only {
+            ui.horizontal(|ui| {
+                ui.label(label(FormField::Protocol, "Protocol:"));
+                for protocol in Protocol::ALL {
+                    ui.selectable_value(&mut self.form.protocol, protocol, protocol.label());
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label(label(FormField::Interface, "Interface:"));
+                ui.add(egui::TextEdit::singleline(&mut self.form.interface).hint_text("any"))
+                    .on_hover_text("Local interface name or address the connection must leave through");
+                // Listed only while the menu is open.
+                ui.menu_button("⏷", |ui| match route::interfaces() {
+                    Ok(interfaces) => {
+                        if ui.button("Any (the system chooses)").clicked() {
+                            self.form.interface.clear();
+                            ui.close_menu();
+                        }
+                        for interface in interfaces {
+                            if ui.button(format!("{} ({})", interface.name, interface.address)).clicked() {
+                                self.form.interface = interface.name;
+                                ui.close_menu();
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        ui.label(format!("The interfaces could not be listed: {}", e));
+                    }
+                });
+            });
+            let shared = self.form.credential.as_deref().and_then(|id| credential::find(&self.repository.credentials, id)).cloned();
+            if !self.repository.credentials.is_empty() || self.form.credential.is_some() {
+                ui.horizontal(|ui| {
+                    ui.label(label(FormField::Credential, "Account:"));
+                    let selected = match (&self.form.credential, &shared) {
+                        (None, _) => "Its own".to_string(),
+                        (Some(_), Some(shared)) => shared.name.clone(),
+                        (Some(_), None) => "Removed credential".to_string(),
+                    };
+                    egui::ComboBox::from_id_source("credential").selected_text(selected).show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.form.credential, None, "Its own");
+                        for credential in &self.repository.credentials {
+                            ui.selectable_value(&mut self.form.credential, Some(credential.id.clone()), &credential.name);
+                        }
+                    });
+                    if let Some(shared) = &shared {
+                        ui.weak(format!("logs on as {}", shared.account()));
+                    }
+                });
+            }
+            let own_account = self.form.credential.is_none();
+            if own_account {
+                ui.horizontal(|ui| {
+                    ui.label(label(FormField::Username, "Username:"));
+                    ui.text_edit_singleline(&mut self.form.username);
+                });
+            }
+            if self.form.protocol == Protocol::Rdp {
+                if own_account {
+                    ui.horizontal(|ui| {
+                        ui.label(label(FormField::Domain, "Domain:"));
+                        ui.text_edit_singleline(&mut self.form.domain);
+                    });
+                }
+                ui.horizontal(|ui| {
+                    ui.label(label(FormField::Farm, "RDS farm:"));
+                    ui.add(egui::TextEdit::singleline(&mut self.form.farm.name).hint_text("none").desired_width(120.0))
+                        .on_hover_text("Collection or farm name; Connect then goes through the broker");
+                    ui.label("Broker:");
+                    ui.add(egui::TextEdit::singleline(&mut self.form.farm.broker).hint_text("broker.example.com"));
+                    let farms = known_farms(self.repository.clients());
+                    if !farms.is_empty() {
+                        ui.menu_button("⏷", |ui| {
+                            for farm in farms {
+                                if ui.button(format!("{} via {}", farm.name, farm.broker)).clicked() {
+                                    self.form.farm = farm;
+                                    ui.close_menu();
+                                }
+                            }
+                        });
+                    }
+                });
+                problem_note(ui, &problems, FormField::Farm);
+                ui.horizontal(|ui| {
+                    ui.label(label(FormField::Gateway, "RD Gateway:"));
+                    ui.add(egui::TextEdit::singleline(&mut self.form.gateway.host).hint_text("none").desired_width(160.0))
+                        .on_hover_text("Remote Desktop Gateway to tunnel the connection through");
+                    if !self.form.gateway.host.trim().is_empty() {
+                        ui.checkbox(&mut self.form.gateway.bypass_local, "Bypass for local addresses");
+                    }
+                });
+                if !self.form.gateway.host.trim().is_empty() {
+                    ui.horizontal(|ui| {
+                        ui.label("Gateway account:");
+                        let selected = match &self.form.gateway.credential {
+                            None => "Same as the client".to_string(),
+                            Some(id) => credential::find(&self.repository.credentials, id).map_or("Removed credential".to_string(), |shared| shared.name.clone()),
+                        };
+                        egui::ComboBox::from_id_source("gateway_credential").selected_text(selected).show_ui(ui, |ui| {
+                            ui.selectable_value(&mut self.form.gateway.credential, None, "Same as the client");
+                            for credential in &self.repository.credentials {
+                                ui.selectable_value(&mut self.form.gateway.credential, Some(credential.id.clone()), &credential.name);
+                            }
+                        })
+                        .response
+                        .on_hover_text("Accounts are added under Tools → Credentials");
+                    });
+                }
+            }
+            if own_account {
+                let secure = self.secure_prompt();
+                let repository = &self.repository;
+                let check = || confirm_master_key(repository);
+                ui.add(
+                    SecretField::new(label(FormField::Password, "Password:"), &mut self.form.password)
+                        .hold_to_reveal(self.settings.hold_to_reveal)
+                        .confirm_reveal(self.settings.confirmations.reveal_password)
+                        .verify_reveal(secure.then_some(&check as &dyn Fn() -> bool))
+                        .copyable(self.settings.clear_clipboard_after_seconds)
+                        .generate()
+                        .strength(0),
+                );
+            }
+        }
+        ui.horizontal(|ui| {
+            ui.label(label(FormField::Reference, "Reference:"));
+            ui.text_edit_singleline(&mut self.form.reference);
+        });
+        ui.horizontal(|ui| {
+            ui.label(label(FormField::Expires, "Expires (YYYY-MM-DD):"));
+            ui.text_edit_singleline(&mut self.form.expires);
+        });
+        problem_note(ui, &problems, FormField::Expires);
+        self.owner_editor(ui, label(FormField::Owner, "Owner / Contact"));
+        ui.label(label(FormField::Notes, "Notes:"));
+        ui.add(
+            egui::TextEdit::multiline(&mut self.form.notes)
+                .desired_rows(4)
+                .desired_width(f32::INFINITY)
+                .hint_text("Rack location, maintenance windows, ..."),
+        );
+        self.variables_editor(ui, label(FormField::Variables, "Variables"));
+        problem_note(ui, &problems, FormField::Variables);
+        if !self.form.info_only {
+            self.placement_editor(ui, label(FormField::Placement, "Window Placement"));
+            if self.form.protocol == Protocol::Rdp {
+                self.display_editor(ui, label(FormField::Display, "Display"));
+                self.redirection_editor(ui, label(FormField::Redirection, "Redirection"));
+            }
+            self.session_limit_editor(ui, label(FormField::SessionLimit, "Session Time Limit"));
+            self.prerequisites_editor(ui, label(FormField::Prerequisites, "Connect First"));
+        }
+        self.attachment_editor(ui, label(FormField::Attachments, "Screenshots:"));
+
+        if self.form.confirm_discard {
+            ui.horizontal(|ui| {
+                ui.label("Discard the unsaved changes?");
+                if ui.button("Discard").clicked() {
+                    self.cancel_form();
+                }
+                if ui.button("Keep Editing").clicked() {
+                    self.form.confirm_discard = false;
+                }
+            });
+            return;
+        }
+
+        if ui.button("Save").clicked() {
+            match editing {
+                Some(index) => self.update_client_from_form(index),
+                None => self.add_client_from_form(),
+            }
+        }
+
+        if ui.button("Cancel").clicked() {
+            if dirty.is_empty() {
+                self.cancel_form();
+            } else {
+                self.form.confirm_discard = true;
+            }
+        }
+    }
+
+    pub(crate) fn cancel_form(&mut self) {
+        self.form.clear();
+        self.mode = AppMode::Normal;
+    }
+
+    fn attachment_editor(&mut self, ui: &mut egui::Ui, label: egui::RichText) {
+        ui.horizontal(|ui| {
+            ui.label(label);
+            if ui.button("Paste Image").clicked() {
+                match Attachment::from_clipboard() {
+                    Ok(attachment) => self.form.attachments.push(attachment),
+                    Err(e) => self.error_message = Some(e),
+                }
+            }
+        });
+        let attachments = self.form.attachments.clone();
+        if let Some(index) = self.attachment_thumbnails(ui, &attachments, true) {
+            self.form.attachments.remove(index);
+        }
+    }
+
+    fn variables_editor(&mut self, ui: &mut egui::Ui, label: egui::RichText) {
+        egui::CollapsingHeader::new(label).id_source("variables").show(ui, |ui| {
+            ui.label("Used as {name} in reference links and the SSH command line.");
+            let mut removed = None;
+            for (index, (name, value)) in self.form.variables.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.add(egui::TextEdit::singleline(name).hint_text("name").desired_width(100.0));
+                    ui.add(egui::TextEdit::singleline(value).hint_text("value"));
+                    if ui.button("Remove").clicked() {
+                        removed = Some(index);
+                    }
+                });
+            }
+            if let Some(index) = removed {
+                self.form.variables.remove(index);
+            }
+            if ui.button("Add Variable").clicked() {
+                self.form.variables.push(Default::default());
+            }
+        });
+    }
+
+    fn placement_editor(&mut self, ui: &mut egui::Ui, label: egui::RichText) {
+        egui::CollapsingHeader::new(label).id_source("window_placement").show(ui, |ui| {
+            ui.checkbox(&mut self.form.fixed_placement, "Move the session window after launch");
+            ui.add_enabled_ui(self.form.fixed_placement, |ui| {
+                let placement = &mut self.form.placement;
+                egui::Grid::new("placement_grid").num_columns(2).show(ui, |ui| {
+                    ui.label("Monitor:").on_hover_text("Numbered from 1, left to right");
+                    ui.add(egui::DragValue::new(&mut placement.monitor).clamp_range(1..=16));
+                    ui.end_row();
+                    ui.label("Position:");
+                    ui.horizontal(|ui| {
+                        ui.add(egui::DragValue::new(&mut placement.x).prefix("x "));
+                        ui.add(egui::DragValue::new(&mut placement.y).prefix("y "));
+                    });
+                    ui.end_row();
+                    ui.label("Size:");
+                    ui.horizontal(|ui| {
+                        ui.add(egui::DragValue::new(&mut placement.width).clamp_range(200..=16384));
+                        ui.label("×");
+                        ui.add(egui::DragValue::new(&mut placement.height).clamp_range(200..=16384));
+                    });
+                    ui.end_row();
+                });
+            });
+        });
+    }
+
+    fn display_editor(&mut self, ui: &mut egui::Ui, label: egui::RichText) {
+        egui::CollapsingHeader::new(label).id_source("display").show(ui, |ui| {
+            ui.checkbox(&mut self.form.own_display, "Use these instead of the settings' Remote Desktop options");
+            ui.add_enabled_ui(self.form.own_display, |ui| {
+                let display = &mut self.form.display;
+                ui.checkbox(&mut display.full_screen, "Full screen");
+                ui.checkbox(&mut display.all_monitors, "Use all monitors");
+                egui::Grid::new("display_grid").num_columns(2).show(ui, |ui| {
+                    ui.label("Resolution:").on_hover_text("Size of the remote desktop when it opens in a window");
+                    ui.add_enabled_ui(!display.full_screen, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.add(egui::DragValue::new(&mut display.width).clamp_range(200..=8192));
+                            ui.label("×");
+                            ui.add(egui::DragValue::new(&mut display.height).clamp_range(200..=8192));
+                        });
+                    });
+                    ui.end_row();
+                    ui.label("Scale:");
+                    egui::ComboBox::from_id_source("display_scale").selected_text(format!("{}%", display.scale)).show_ui(ui, |ui| {
+                        for scale in DisplaySettings::SCALES {
+                            ui.selectable_value(&mut display.scale, scale, format!("{}%", scale));
+                        }
+                    });
+                    ui.end_row();
+                });
+            });
+        });
+    }
+
+    fn redirection_editor(&mut self, ui: &mut egui::Ui, label: egui::RichText) {
+        egui::CollapsingHeader::new(label).id_source("redirection").show(ui, |ui| {
+            ui.horizontal(|ui| {
+                if ui.checkbox(&mut self.form.own_redirection, "Use these instead of the settings' defaults").changed() && self.form.own_redirection {
+                    self.form.redirection = self.settings.rdp_options.redirection;
+                }
+                if ui.add_enabled(self.form.own_redirection, egui::Button::new("None")).on_hover_text("Redirect nothing to this host").clicked() {
+                    self.form.redirection = Redirection::NONE;
+                }
+            });
+            ui.add_enabled_ui(self.form.own_redirection, |ui| redirection_checkboxes(ui, &mut self.form.redirection));
+        });
+    }
+
+    fn session_limit_editor(&mut self, ui: &mut egui::Ui, label: egui::RichText) {
+        egui::CollapsingHeader::new(label).id_source("session_limit").show(ui, |ui| {
+            ui.checkbox(&mut self.form.limit_session, "Limit how long a session may stay open");
+            ui.add_enabled_ui(self.form.limit_session, |ui| {
+                let limit = &mut self.form.session_limit;
+                ui.horizontal(|ui| {
+                    ui.label("Maximum:");
+                    ui.add(egui::DragValue::new(&mut limit.minutes).clamp_range(1..=24 * 60).suffix(" min"));
+                });
+                ui.checkbox(&mut limit.disconnect, "Disconnect when the time is up, not just warn");
+            });
+        });
+    }
+
+    /// Clients such as a jump host or VPN that Connect All on a folder
+    /// connects before this one. Clients that already wait for this one are
+    /// not offered, so no two clients wait for each other.
+    fn prerequisites_editor(&mut self, ui: &mut egui::Ui, label: egui::RichText) {
+        egui::CollapsingHeader::new(label).id_source("prerequisites").show(ui, |ui| {
+            ui.weak("Connect All on a folder connects these first and waits until this client's address answers through them.");
+            let mut removed = None;
+            for (index, id) in self.form.prerequisites.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(self.repository.get(id).map_or("Removed client", |client| client.name.as_str()));
+                    if ui.small_button("✖").on_hover_text("Remove").clicked() {
+                        removed = Some(index);
+                    }
+                });
+            }
+            if let Some(index) = removed {
+                self.form.prerequisites.remove(index);
+            }
+            let own_id = self.form.client_id();
+            let clients = self.repository.clients();
+            let candidates: Vec<(String, String)> = clients
+                .iter()
+                .filter(|client| client.trashed_at.is_none() && !client.info_only && client.id != own_id)
+                .filter(|client| !self.form.prerequisites.contains(&client.id) && !dependencies::depends_on(clients, &client.id, own_id))
+                .map(|client| (client.id.clone(), client.name.clone()))
+                .collect();
+            ui.menu_button("Add…", |ui| {
+                egui::ScrollArea::vertical().max_height(240.0).show(ui, |ui| {
+                    for (id, name) in candidates {
+                        if ui.button(name).clicked() {
+                            self.form.prerequisites.push(id);
+                            ui.close_menu();
+                        }
+                    }
+                });
+            });
+        });
+    }
+
+    fn owner_editor(&mut self, ui: &mut egui::Ui, label: egui::RichText) {
+        egui::CollapsingHeader::new(label).id_source("owner").show(ui, |ui| {
+            contact_grid(ui, "owner_grid", &mut self.form.owner);
+            if self.form.owner.is_empty() {
+                let group = Client { group: self.form.group.clone(), ..Default::default() };
+                if let Some((_, Some(path))) = group.contact(&self.repository.group_contacts) {
+                    ui.weak(format!("Left empty, the contact of the group {} applies.", path));
+                }
+            }
+        });
+    }
+}
+
+/// Name, email, phone and on-call URL fields for editing `contact`.
+pub fn contact_grid(ui: &mut egui::Ui, id_source: &str, contact: &mut Contact) {
+    egui::Grid::new(id_source).num_columns(2).show(ui, |ui| {
+        ui.label("Name:");
+        ui.text_edit_singleline(&mut contact.name);
+        ui.end_row();
+        ui.label("Email:");
+        ui.text_edit_singleline(&mut contact.email);
+        ui.end_row();
+        ui.label("Phone:");
+        ui.text_edit_singleline(&mut contact.phone);
+        ui.end_row();
+        ui.label("On-call URL:");
+        ui.text_edit_singleline(&mut contact.on_call_url);
+        ui.end_row();
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_client() -> Client {
+        Client {
+            name: "db01".to_string(),
+            ip: "10.0.0.5".to_string(),
+            password: "hunter2".to_string(),
+            protocol: Protocol::Ssh,
+            group: "Customers/Acme".to_string(),
+            username: "admin".to_string(),
+            domain: "CORP".to_string(),
+            expires: parse_expiry("2030-01-31").unwrap(),
+            placement: Some(WindowPlacement { monitor: 2, ..Default::default() }),
+            session_limit: Some(SessionLimit { minutes: 90, disconnect: true }),
+            tags: vec!["prod".to_string(), "customer-a".to_string()],
+            notes: "Rack B4\nPatch window: Sun 02:00".to_string(),
+            port: Some(2222),
+            interface: "wg0".to_string(),
+            variables: BTreeMap::from([("region".to_string(), "eu-west".to_string())]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn a_shared_account_replaces_the_clients_own() {
+        let mut form = ClientForm::default();
+        form.load(&sample_client());
+        form.credential = Some("shared".to_string());
+        assert!(form.dirty_fields().contains(&FormField::Credential));
+        let mut client = sample_client();
+        form.apply_to(&mut client).unwrap();
+        assert_eq!(client.credential.as_deref(), Some("shared"));
+        assert!(client.username.is_empty() && client.domain.is_empty() && client.password.is_empty());
+    }
+
+    #[test]
+    fn load_then_apply_round_trips() {
+        let original = sample_client();
+        let mut form = ClientForm::default();
+        form.load(&original);
+        let mut copy = Client { id: original.id.clone(), ..Default::default() };
+        form.apply_to(&mut copy).unwrap();
+        assert!(copy == original);
+    }
+
+    #[test]
+    fn invalid_expiry_leaves_client_untouched() {
+        let original = sample_client();
+        let mut form = ClientForm::default();
+        form.load(&original);
+        form.name = "renamed".to_string();
+        form.expires = "next tuesday".to_string();
+        let mut client = original.clone();
+        assert!(form.apply_to(&mut client).is_err());
+        assert!(client == original);
+    }
+
+    #[test]
+    fn variables_must_have_valid_unique_names() {
+        let mut client = sample_client();
+        let mut form = ClientForm::default();
+        for rows in [vec![("jump user", "ops")], vec![("host", "x")], vec![("region", "a"), ("region", "b")]] {
+            form.variables = rows.into_iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+            assert!(form.apply_to(&mut client).is_err());
+        }
+        form.variables = vec![(" jump_user ".to_string(), "ops".to_string()), (String::new(), String::new())];
+        form.apply_to(&mut client).unwrap();
+        assert_eq!(client.variables, BTreeMap::from([("jump_user".to_string(), "ops".to_string())]));
+    }
+
+    #[test]
+    fn ports_must_be_valid_and_agree_with_the_address() {
+        let mut client = sample_client();
+        let mut form = ClientForm::default();
+        form.load(&client);
+        for (ip, port) in [("db01", "0"), ("db01", "70000"), ("db01", "ssh"), ("db01:3390", "3391")] {
+            form.ip = ip.to_string();
+            form.port = port.to_string();
+            assert!(form.apply_to(&mut client).is_err(), "{} {}", ip, port);
+        }
+        form.ip = "db01:3390".to_string();
+        form.port = "3390".to_string();
+        form.apply_to(&mut client).unwrap();
+        form.ip = "fd00::1".to_string();
+        form.port = " 3391 ".to_string();
+        form.apply_to(&mut client).unwrap();
+        assert_eq!((client.endpoint(), client.address().as_str()), (("fd00::1", Some(3391)), "[fd00::1]:3391"));
+        form.port.clear();
+        form.apply_to(&mut client).unwrap();
+        assert_eq!(client.address(), "fd00::1");
+    }
+
+    #[test]
+    fn farm_members_connect_through_the_broker() {
+        let mut form = ClientForm {
+            name: "rdsh01".to_string(),
+            ip: "rdsh01.corp".to_string(),
+            port: "3390".to_string(),
+            farm: RdsFarm { name: " Office ".to_string(), broker: String::new() },
+            ..Default::default()
+        };
+        let mut client = Client::default();
+        assert_eq!(form.problems()[0].0, FormField::Farm);
+        assert!(form.apply_to(&mut client).is_err());
+        form.farm.broker = "broker.corp".to_string();
+        form.apply_to(&mut client).unwrap();
+        assert_eq!(client.farm, Some(RdsFarm { name: "Office".to_string(), broker: "broker.corp".to_string() }));
+
+        let rdp = crate::rdp::render(&client, true, &Default::default()).unwrap();
+        assert!(rdp.contains("full address:s:broker.corp\r\n"));
+        assert!(rdp.contains("loadbalanceinfo:s:tsv://MS Terminal Services Plugin.1.Office\r\n"));
+        assert_eq!(client.address(), "rdsh01.corp:3390");
+
+        form.protocol = Protocol::Ssh;
+        form.apply_to(&mut client).unwrap();
+        assert_eq!(client.farm, None);
+        assert_eq!(client.connection_target().address(), "rdsh01.corp:3390");
+    }
+
+    #[test]
+    fn gateways_are_written_into_the_connection_file() {
+        let mut form = ClientForm {
+            name: "app01".to_string(),
+            ip: "app01.corp".to_string(),
+            gateway: RdGateway { host: " rdg.corp ".to_string(), ..Default::default() },
+            ..Default::default()
+        };
+        let mut client = Client::default();
+        form.apply_to(&mut client).unwrap();
+        assert_eq!(client.gateway, Some(RdGateway { host: "rdg.corp".to_string(), ..Default::default() }));
+        let rdp = crate::rdp::render(&client, true, &Default::default()).unwrap();
+        assert!(rdp.contains("gatewayhostname:s:rdg.corp\r\ngatewayusagemethod:i:1\r\n"));
+        assert!(rdp.contains("promptcredentialonce:i:1\r\n"));
+
+        form.gateway.bypass_local = true;
+        form.gateway.credential = Some("gateway-account".to_string());
+        form.apply_to(&mut client).unwrap();
+        let rdp = crate::rdp::render(&client, true, &Default::default()).unwrap();
+        assert!(rdp.contains("gatewayusagemethod:i:2\r\n"));
+        assert!(rdp.contains("promptcredentialonce:i:0\r\n"));
+
+        form.gateway.host.clear();
+        form.apply_to(&mut client).unwrap();
+        assert_eq!(client.gateway, None);
+        assert!(!crate::rdp::render(&client, true, &Default::default()).unwrap().contains("gateway"));
+    }
+
+    #[test]
+    fn own_display_settings_replace_the_defaults() {
+        let mut form = ClientForm { name: "kiosk".to_string(), ip: "kiosk.corp".to_string(), ..Default::default() };
+        let mut client = Client::default();
+        form.apply_to(&mut client).unwrap();
+        let rdp = crate::rdp::render(&client, true, &Default::default()).unwrap();
+        assert!(rdp.contains("screen mode id:i:2\r\n") && !rdp.contains("desktopwidth"));
+
+        form.load(&client);
+        form.own_display = true;
+        form.display = DisplaySettings { full_screen: false, all_monitors: true, width: 1600, height: 900, scale: 150 };
+        assert_eq!(form.dirty_fields(), [FormField::Display]);
+        form.apply_to(&mut client).unwrap();
+        let rdp = crate::rdp::render(&client, true, &Default::default()).unwrap();
+        assert!(rdp.contains("use multimon:i:1\r\n"));
+        assert!(rdp.contains("screen mode id:i:1\r\ndesktopwidth:i:1600\r\ndesktopheight:i:900\r\ndesktopscalefactor:i:150\r\n"));
+
+        form.protocol = Protocol::Ssh;
+        form.apply_to(&mut client).unwrap();
+        assert_eq!(client.display, None);
+    }
+
+    #[test]
+    fn sensitive_hosts_get_no_redirection() {
+        let mut form = ClientForm { name: "vault01".to_string(), ip: "vault01.corp".to_string(), ..Default::default() };
+        let mut client = Client::default();
+        form.apply_to(&mut client).unwrap();
+        let options = crate::settings::RdpOptions::default();
+        let rdp = crate::rdp::render(&client, true, &options).unwrap();
+        assert!(rdp.contains("redirectclipboard:i:1\r\n") && rdp.contains("redirectdrives:i:0\r\n"));
+
+        form.own_redirection = true;
+        form.redirection = Redirection::NONE;
+        form.apply_to(&mut client).unwrap();
+        let rdp = crate::rdp::render(&client, true, &options).unwrap();
+        for line in ["redirectclipboard:i:0", "redirectprinters:i:0", "redirectsmartcards:i:0", "audiomode:i:2", "drivestoredirect:s:\r\n"] {
+            assert!(rdp.contains(line), "{} missing", line);
+        }
+
+        form.redirection.drives = true;
+        form.apply_to(&mut client).unwrap();
+        assert!(crate::rdp::render(&client, true, &options).unwrap().contains("redirectdrives:i:1\r\ndrivestoredirect:s:*\r\n"));
+    }
+
+    #[test]
+    fn clear_resets_every_field() {
+        let mut form = ClientForm::default();
+        form.load(&sample_client());
+        form.clear();
+        assert!(form.name.is_empty() && form.password.is_empty() && form.expires.is_empty());
+    }
+
+    #[test]
+    fn only_changed_fields_are_dirty() {
+        let mut form = ClientForm::default();
+        assert!(!form.is_dirty());
+        form.load(&sample_client());
+        assert!(!form.is_dirty());
+        form.name = "db02".to_string();
+        form.limit_session = false;
+        form.variables.push(Default::default());
+        assert_eq!(form.dirty_fields(), [FormField::Name, FormField::Variables, FormField::SessionLimit]);
+        form.load(&sample_client());
+        assert!(!form.is_dirty());
+    }
+
+    #[test]
+    fn problems_name_the_field() {
+        let mut form = ClientForm::default();
+        assert!(form.problems().is_empty());
+        form.port = "ssh".to_string();
+        form.expires = "soon".to_string();
+        let fields: Vec<FormField> = form.problems().into_iter().map(|(field, _)| field).collect();
+        assert_eq!(fields, [FormField::Port, FormField::Expires]);
+    }
+
+    #[test]
+    fn saving_the_add_form_adds_a_client_and_returns_to_normal() {
+        let mut state = AppState::unlocked_for_test();
+        state.mode = AppMode::Adding;
+        state.form.name = "web01".to_string();
+        state.add_client_from_form();
+        assert_eq!(state.mode, AppMode::Normal);
+        assert_eq!(state.repository.clients().len(), 1);
+        assert!(state.form.name.is_empty());
+    }
+
+    #[test]
+    fn invalid_add_form_stays_open() {
+        let mut state = AppState::unlocked_for_test();
+        state.mode = AppMode::Adding;
+        state.form.expires = "soon".to_string();
+        state.add_client_from_form();
+        assert_eq!(state.mode, AppMode::Adding);
+        assert!(state.error_message.is_some());
+        assert!(state.repository.clients().is_empty());
+    }
+
+    #[test]
+    fn cancel_discards_the_form() {
+        let mut state = AppState::unlocked_for_test();
+        state.mode = AppMode::Editing;
+        state.form.name = "draft".to_string();
+        state.cancel_form();
+        assert_eq!(state.mode, AppMode::Normal);
+        assert!(state.form.name.is_empty());
+    }
+}
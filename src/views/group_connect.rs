@@ -0,0 +1,305 @@
+use crate::app::AppState;
+use crate::client::{AppMode, Client};
+use crate::dependencies;
+use crate::events::AppEvent;
+use crate::reachability::{self, Target};
+use crate::repository::ClientRepository;
+use eframe::egui;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a client waits for its own address to answer once its
+/// prerequisites are connected, before it is launched regardless.
+const TUNNEL_TIMEOUT: Duration = Duration::from_secs(60);
+const TUNNEL_POLL: Duration = Duration::from_secs(2);
+
+/// Where a client of a group connect stands.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Step {
+    Queued,
+    /// Its prerequisites are connected; waiting for the tunnel or VPN
+    /// they bring up to reach the client.
+    Waiting,
+    Connected,
+    Failed(String),
+}
+
+/// Connect All on a folder: its clients and what they wait for, launched
+/// one after another.
+#[derive(Default)]
+pub struct GroupConnect {
+    pub group: String,
+    /// Client ids in launch order, prerequisites first.
+    pub steps: Vec<(String, Step)>,
+    /// Connect All was clicked.
+    pub started: bool,
+}
+
+impl GroupConnect {
+    pub fn running(&self) -> bool {
+        self.started && self.steps.iter().any(|(_, step)| matches!(step, Step::Queued | Step::Waiting))
+    }
+}
+
+/// Whether `client` lies in the folder `group` or one below it.
+fn in_group(client: &Client, group: &str) -> bool {
+    let base: Vec<&str> = group.split('/').map(str::trim).filter(|name| !name.is_empty()).collect();
+    client.group_path().take(base.len()).eq(base.iter().copied())
+}
+
+impl AppState {
+    /// Lists the clients of `group` in the order Connect All would launch
+    /// them.
+    pub fn open_group_connect(&mut self, group: &str) {
+        if self.group_connect.running() {
+            self.mode = AppMode::ConnectingGroup;
+            return;
+        }
+        let ids: Vec<String> = self
+            .repository
+            .clients()
+            .iter()
+            .filter(|client| client.trashed_at.is_none() && !client.info_only && in_group(client, group))
+            .map(|client| client.id.clone())
+            .collect();
+        match dependencies::connect_order(self.repository.clients(), &ids) {
+            Ok(order) => {
+                self.group_connect = GroupConnect { group: group.to_string(), steps: order.into_iter().map(|id| (id, Step::Queued)).collect(), started: false };
+                self.mode = AppMode::ConnectingGroup;
+            }
+            Err(e) => self.error_message = Some(e),
+        }
+    }
+
+    pub fn start_group_connect(&mut self) {
+        self.group_connect.started = true;
+        self.advance_group_connect();
+    }
+
+    /// Launches the queued clients in order until one has to wait for its
+    /// prerequisites' tunnel; `AppEvent::PrerequisitesUp` resumes from there.
+    pub fn advance_group_connect(&mut self) {
+        if !self.group_connect.started || self.group_connect.steps.iter().any(|(_, step)| *step == Step::Waiting) {
+            return;
+        }
+        while let Some(index) = self.group_connect.steps.iter().position(|(_, step)| *step == Step::Queued) {
+            let id = self.group_connect.steps[index].0.clone();
+            let Some(client) = self.repository.get(&id).cloned() else {
+                self.group_connect.steps[index].1 = Step::Failed("removed".to_string());
+                continue;
+            };
+            let waits = !dependencies::prerequisites(self.repository.clients(), &client).is_empty();
+            if let Some(missing) = self.missing_prerequisite(&client) {
+                self.group_connect.steps[index].1 = Step::Failed(format!("{} is not connected", missing));
+                continue;
+            }
+            if !waits {
+                self.group_connect.steps[index].1 = self.launch_group_member(&client);
+                continue;
+            }
+            self.group_connect.steps[index].1 = Step::Waiting;
+            let target = Target::of(&client.connection_target(), &self.settings.default_ports).filter(|_| !self.launcher.is_dry_run());
+            let events = self.events.clone();
+            thread::spawn(move || {
+                if let Some(target) = target {
+                    let started = Instant::now();
+                    while reachability::probe(&target.host, target.port).is_none() && started.elapsed() < TUNNEL_TIMEOUT {
+                        thread::sleep(TUNNEL_POLL);
+                    }
+                }
+                events.publish(AppEvent::PrerequisitesUp(id));
+            });
+            return;
+        }
+    }
+
+    /// Launches the waiting client once its tunnel answered or waiting for
+    /// it timed out, then carries on with the queue.
+    pub fn finish_waiting(&mut self, client_id: &str) {
+        let Some(index) = self.group_connect.steps.iter().position(|(id, step)| id == client_id && *step == Step::Waiting) else {
+            return;
+        };
+        let Some(client) = self.repository.get(client_id).cloned() else {
+            self.group_connect.steps[index].1 = Step::Failed("removed".to_string());
+            self.advance_group_connect();
+            return;
+        };
+        self.group_connect.steps[index].1 = match self.missing_prerequisite(&client) {
+            Some(missing) => Step::Failed(format!("{} is no longer connected", missing)),
+            None => self.launch_group_member(&client),
+        };
+        self.advance_group_connect();
+    }
+
+    /// The name of a prerequisite of `client` without a running session.
+    fn missing_prerequisite(&self, client: &Client) -> Option<String> {
+        let active = self.sessions.active();
+        dependencies::prerequisites(self.repository.clients(), client)
+            .into_iter()
+            .find(|prerequisite| !active.iter().any(|session| session.client_id == prerequisite.id))
+            .map(|prerequisite| prerequisite.name.clone())
+    }
+
+    /// Connects without handing the password to the clipboard, which
+    /// several viewers opening at once would race for.
+    fn launch_group_member(&mut self, client: &Client) -> Step {
+        self.connect(client, false);
+        match self.connect_failure.as_ref().filter(|failure| failure.client_id == client.id) {
+            Some(failure) => Step::Failed(failure.error.message.clone()),
+            None => Step::Connected,
+        }
+    }
+
+    pub(crate) fn group_connect_view(&mut self, ui: &mut egui::Ui) {
+        let title = if self.group_connect.group.is_empty() { "Connect All".to_string() } else { format!("Connect All in {}", self.group_connect.group) };
+        ui.heading(title);
+        ui.label("Clients are connected in this order. One that waits for others is launched once they are connected and its address answers, or after a minute.");
+        let clients = self.repository.clients();
+        let tagged: Vec<String> = self
+            .group_connect
+            .steps
+            .iter()
+            .filter_map(|(id, _)| self.repository.get(id))
+            .filter_map(|client| self.settings.confirmations.connect_tag(client).map(|tag| format!("{} ({})", client.name, tag)))
+            .collect();
+        ui.separator();
+        egui::ScrollArea::vertical().max_height(320.0).show(ui, |ui| {
+            egui::Grid::new("group_connect").striped(true).num_columns(3).show(ui, |ui| {
+                for (id, step) in &self.group_connect.steps {
+                    let Some(client) = self.repository.get(id) else {
+                        continue;
+                    };
+                    ui.label(&client.name);
+                    let waits_for: Vec<&str> = dependencies::prerequisites(clients, client).iter().map(|prerequisite| prerequisite.name.as_str()).collect();
+                    if waits_for.is_empty() {
+                        ui.label("");
+                    } else {
+                        ui.weak(format!("after {}", waits_for.join(", ")));
+                    }
+                    match step {
+                        Step::Queued => {
+                            ui.label("");
+                        }
+                        Step::Waiting => {
+                            ui.horizontal(|ui| {
+                                ui.spinner();
+                                ui.label("waiting for the tunnel…");
+                            });
+                        }
+                        Step::Connected => {
+                            ui.colored_label(egui::Color32::GREEN, "✔ connected");
+                        }
+                        Step::Failed(e) => {
+                            ui.colored_label(egui::Color32::RED, format!("✖ {}", e));
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+            if self.group_connect.steps.is_empty() {
+                ui.label("The folder has no client to connect to.");
+            }
+        });
+        let notes: Vec<String> = self
+            .group_connect
+            .steps
+            .iter()
+            .filter_map(|(id, _)| self.repository.get(id))
+            .filter_map(|client| client.pinned_note.as_ref().map(|note| format!("📌 {}: {}", client.name, note.text)))
+            .collect();
+        ui.separator();
+        if !self.group_connect.started && !tagged.is_empty() {
+            ui.colored_label(ui.visuals().warn_fg_color, format!("Connect All also connects {}.", tagged.join(", ")));
+        }
+        if !self.group_connect.started {
+            for note in &notes {
+                ui.colored_label(ui.visuals().warn_fg_color, note);
+            }
+        }
+        ui.horizontal(|ui| {
+            let idle = !self.group_connect.started && !self.group_connect.steps.is_empty();
+            if ui.add_enabled(idle, egui::Button::new("Connect All")).clicked() {
+                self.start_group_connect();
+            }
+            if ui.button("Close").clicked() {
+                self.mode = AppMode::Normal;
+            }
+        });
+        if self.group_connect.running() {
+            ui.weak("Closing this dialog does not stop the clients still queued.");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::launcher::Launcher;
+
+    fn added(state: &mut AppState, name: &str, group: &str, prerequisites: &[&Client]) -> Client {
+        let client = Client {
+            name: name.to_string(),
+            ip: format!("{}.example.com", name.to_lowercase()),
+            group: group.to_string(),
+            prerequisites: prerequisites.iter().map(|client| client.id.clone()).collect(),
+            ..Default::default()
+        };
+        state.repository.add(client.clone());
+        client
+    }
+
+    fn names(state: &AppState) -> Vec<String> {
+        state.group_connect.steps.iter().map(|(id, _)| state.repository.get(id).unwrap().name.clone()).collect()
+    }
+
+    #[test]
+    fn prerequisites_come_first_even_from_other_folders() {
+        let mut state = AppState::unlocked_for_test();
+        let jump = added(&mut state, "JUMP-01", "Shared", &[]);
+        added(&mut state, "DB-01", "Customers/Acme", &[&jump]);
+        added(&mut state, "WEB-01", "Customers/Acme/Web", &[]);
+        added(&mut state, "OTHER", "Customers/Other", &[]);
+        state.open_group_connect("Customers/Acme");
+        assert_eq!(state.mode, AppMode::ConnectingGroup);
+        assert_eq!(names(&state), ["JUMP-01", "DB-01", "WEB-01"]);
+    }
+
+    #[test]
+    fn prerequisites_that_wait_for_each_other_are_refused() {
+        let mut state = AppState::unlocked_for_test();
+        let mut first = added(&mut state, "A", "Lab", &[]);
+        let second = added(&mut state, "B", "Lab", &[&first]);
+        first.prerequisites = vec![second.id.clone()];
+        state.repository.update(first);
+        state.open_group_connect("Lab");
+        assert!(state.error_message.is_some());
+        assert_ne!(state.mode, AppMode::ConnectingGroup);
+    }
+
+    #[test]
+    fn dependents_wait_for_their_prerequisites() {
+        let mut state = AppState::unlocked_for_test();
+        state.launcher = Launcher::DryRun;
+        let jump = added(&mut state, "JUMP-01", "Acme", &[]);
+        let db = added(&mut state, "DB-01", "Acme", &[&jump]);
+        state.open_group_connect("Acme");
+        state.start_group_connect();
+        assert_eq!(state.group_connect.steps, [(jump.id.clone(), Step::Connected), (db.id.clone(), Step::Waiting)]);
+        assert!(state.group_connect.running());
+        state.finish_waiting(&db.id);
+        assert_eq!(state.group_connect.steps[1].1, Step::Connected);
+        assert!(!state.group_connect.running());
+    }
+
+    #[test]
+    fn a_failed_prerequisite_skips_its_dependents() {
+        let mut state = AppState::unlocked_for_test();
+        state.launcher = Launcher::DryRun;
+        let jump = added(&mut state, "JUMP-01", "Acme", &[]);
+        let db = added(&mut state, "DB-01", "Acme", &[&jump]);
+        state.open_group_connect("Acme");
+        state.group_connect.steps[0].1 = Step::Failed("refused".to_string());
+        state.start_group_connect();
+        assert_eq!(state.group_connect.steps[1], (db.id, Step::Failed("JUMP-01 is not connected".to_string())));
+    }
+}
@@ -0,0 +1,111 @@
+use crate::app::AppState;
+use crate::help::{search, topics, Section};
+use eframe::egui;
+
+/// Help → Help Topics, kept open beside the rest of the window.
+#[derive(Default)]
+pub struct HelpWindow {
+    pub open: bool,
+    pub query: String,
+    /// Move the keyboard to the search field on the next frame.
+    focus: bool,
+}
+
+impl AppState {
+    pub fn open_help(&mut self) {
+        self.help.open = true;
+        self.help.focus = true;
+    }
+
+    pub(crate) fn help_window(&mut self, ctx: &egui::Context) {
+        if !self.help.open {
+            return;
+        }
+        let topics = topics(&self.settings.shortcuts);
+        let mut open = true;
+        egui::Window::new("Help").open(&mut open).default_size([560.0, 480.0]).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Search:");
+                let response = ui.add(egui::TextEdit::singleline(&mut self.help.query).hint_text("shortcut, {host}, rdg, clipboard…").desired_width(280.0));
+                if std::mem::take(&mut self.help.focus) {
+                    response.request_focus();
+                }
+            });
+            ui.separator();
+            let found = search(&topics, &self.help.query);
+            egui::ScrollArea::vertical().max_height(380.0).show(ui, |ui| {
+                for section in Section::ALL {
+                    let entries: Vec<_> = found.iter().filter(|topic| topic.section == section).collect();
+                    if entries.is_empty() {
+                        continue;
+                    }
+                    ui.strong(section.label());
+                    egui::Grid::new(section.label()).striped(true).num_columns(2).show(ui, |ui| {
+                        for topic in entries {
+                            ui.label(&topic.title);
+                            ui.add(egui::Label::new(&topic.text).wrap(true));
+                            ui.end_row();
+                        }
+                    });
+                    ui.add_space(8.0);
+                }
+                if found.is_empty() {
+                    ui.label(format!("Nothing matches \"{}\".", self.help.query.trim()));
+                }
+            });
+            ui.separator();
+            ui.weak(format!("Remote Desktop Manager {}. Powered By Jerry Yu", env!("CARGO_PKG_VERSION")));
+        });
+        self.help.open = open;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::AppMode;
+    use crate::shortcuts::Shortcut;
+    use crate::template::BUILT_IN;
+    use crate::views::menu_bar::MenuAction;
+
+    #[test]
+    fn the_reference_follows_the_shortcuts_and_placeholders_in_use() {
+        let mut state = AppState::for_test();
+        state.settings.shortcuts.insert(Shortcut::Lock, "Ctrl+Shift+L".to_string());
+        state.settings.shortcuts.insert(Shortcut::Remove, String::new());
+        let topics = topics(&state.settings.shortcuts);
+        assert_eq!(topics.iter().filter(|topic| topic.section == Section::Shortcuts).count(), Shortcut::ALL.len() + 1);
+        let lock = search(&topics, "lock ctrl+shift");
+        assert_eq!(lock.len(), 1);
+        assert_eq!(lock[0].text, "Ctrl+Shift+L (default Ctrl+L)");
+        assert!(search(&topics, "remove").iter().any(|topic| topic.text == "Turned off (default Delete)"));
+        for name in BUILT_IN {
+            let title = format!("{{{}}}", name);
+            assert!(topics.iter().any(|topic| topic.title == title && !topic.text.is_empty()), "{} is not described", title);
+        }
+    }
+
+    #[test]
+    fn searching_matches_every_word_in_any_section() {
+        let topics = topics(&Default::default());
+        let titles = |query: &str| search(&topics, query).iter().map(|topic| topic.title.clone()).collect::<Vec<_>>();
+        assert_eq!(titles("RDG  decrypt"), ["RDCMan (.rdg)"]);
+        assert_eq!(titles("placeholders {port}"), ["{port}"]);
+        assert!(titles("csv")[0].starts_with("CSV"));
+        assert_eq!(search(&topics, "id,name,ip").len(), 1);
+        assert!(titles("argon2id").contains(&"Master key".to_string()));
+        assert!(titles("no such topic").is_empty());
+        assert_eq!(search(&topics, " ").len(), topics.len());
+    }
+
+    #[test]
+    fn help_opens_beside_an_unsaved_form() {
+        let mut state = AppState::unlocked_for_test();
+        state.mode = AppMode::Adding;
+        state.form.name = "db01".to_string();
+        state.apply_menu_action(MenuAction::Help);
+        assert!(state.help.open);
+        assert_eq!(state.mode, AppMode::Adding);
+        assert!(!state.form.confirm_discard);
+    }
+}
@@ -0,0 +1,164 @@
+use crate::app::AppState;
+use crate::client::AppMode;
+use crate::history::{recent, ConnectionRecord};
+use crate::repository::ClientRepository;
+use chrono::{DateTime, Local, Utc};
+use eframe::egui;
+
+/// Clients offered in the Recent section.
+const RECENT_COUNT: usize = 5;
+
+/// Filter of the History window.
+#[derive(Default)]
+pub struct HistoryFilter {
+    pub text: String,
+    pub failures_only: bool,
+    /// Clear was clicked once and waits for confirmation.
+    pub confirm_clear: bool,
+}
+
+/// `14:02` for today, `Mon 14:02` within a week, the date before that.
+pub fn when(time: DateTime<Utc>, now: DateTime<Local>) -> String {
+    let time = time.with_timezone(&Local);
+    let days = (now.date_naive() - time.date_naive()).num_days();
+    match days {
+        0 => time.format("%H:%M").to_string(),
+        1..=6 => time.format("%a %H:%M").to_string(),
+        _ => time.format("%Y-%m-%d %H:%M").to_string(),
+    }
+}
+
+fn outcome(record: &ConnectionRecord) -> String {
+    match (&record.error, record.ended) {
+        (Some(e), _) if record.password_changed => format!("password change failed: {}", e),
+        (None, _) if record.password_changed => "password changed".to_string(),
+        (Some(e), _) => format!("failed: {}", e),
+        (None, Some(ended)) => format!("{} min", (ended - record.started).num_minutes().max(0)),
+        (None, None) => "open".to_string(),
+    }
+}
+
+impl AppState {
+    /// The last few clients connected to, above the client list.
+    pub(crate) fn recent_connections(&mut self, ui: &mut egui::Ui) {
+        let now = Local::now();
+        let recent: Vec<ConnectionRecord> = recent(&self.repository.history, RECENT_COUNT).into_iter().cloned().collect();
+        let recent: Vec<(ConnectionRecord, usize)> = recent
+            .into_iter()
+            .filter_map(|record| {
+                let index = self.repository.position(&record.client_id)?;
+                (self.repository.clients()[index].trashed_at.is_none()).then_some((record, index))
+            })
+            .collect();
+        if recent.is_empty() {
+            return;
+        }
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Recent:");
+            for (record, index) in recent {
+                let client = self.repository.clients()[index].clone();
+                let color = if record.error.is_some() { egui::Color32::RED } else { ui.visuals().text_color() };
+                let text = egui::RichText::new(&client.name).color(color);
+                let response = ui.button(text).on_hover_text(format!("{} ({})\nDouble-click to connect", when(record.started, now), outcome(&record)));
+                if response.double_clicked() {
+                    self.request_connect(&client);
+                } else if response.clicked() {
+                    self.client_list.select(&client);
+                }
+            }
+        });
+        ui.separator();
+    }
+
+    pub(crate) fn history_view(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Connection History");
+        ui.horizontal(|ui| {
+            ui.label("Filter:");
+            ui.add(egui::TextEdit::singleline(&mut self.history_filter.text).hint_text("name or address"));
+            ui.checkbox(&mut self.history_filter.failures_only, "Failures only");
+        });
+        let now = Local::now();
+        let filter = self.history_filter.text.trim().to_string();
+        let records: Vec<&ConnectionRecord> = self
+            .repository
+            .history
+            .iter()
+            .rev()
+            .filter(|record| filter.is_empty() || record.matches(&filter))
+            .filter(|record| !self.history_filter.failures_only || record.error.is_some())
+            .collect();
+        if records.is_empty() {
+            ui.label("No connections recorded.");
+        }
+        egui::ScrollArea::vertical().max_height(360.0).show(ui, |ui| {
+            egui::Grid::new("connection_history").striped(true).show(ui, |ui| {
+                for record in records {
+                    ui.label(when(record.started, now));
+                    ui.label(&record.client_name);
+                    ui.label(&record.address);
+                    if record.error.is_some() {
+                        ui.colored_label(egui::Color32::RED, outcome(record));
+                    } else {
+                        ui.label(outcome(record));
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+        ui.horizontal(|ui| {
+            if self.history_filter.confirm_clear {
+                ui.label("Delete every record?");
+                if ui.button("Clear History").clicked() {
+                    self.clear_history();
+                    self.history_filter.confirm_clear = false;
+                }
+                if ui.button("Keep").clicked() {
+                    self.history_filter.confirm_clear = false;
+                }
+            } else if ui.add_enabled(!self.repository.history.is_empty(), egui::Button::new("Clear History…")).clicked() {
+                self.history_filter.confirm_clear = true;
+            }
+        });
+        if ui.button("Back").clicked() {
+            self.history_filter.confirm_clear = false;
+            self.mode = AppMode::Normal;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Client;
+    use chrono::TimeZone;
+
+    fn record(client: &Client, minute: u32, error: Option<&str>) -> ConnectionRecord {
+        ConnectionRecord {
+            client_id: client.id.clone(),
+            client_name: client.name.clone(),
+            address: client.ip.clone(),
+            started: Utc.with_ymd_and_hms(2026, 10, 16, 8, minute, 0).unwrap(),
+            ended: None,
+            error: error.map(str::to_string),
+            password_changed: false,
+        }
+    }
+
+    #[test]
+    fn recent_lists_each_client_once_newest_first() {
+        let (a, b, c) = (Client::default(), Client::default(), Client::default());
+        let history = vec![record(&a, 1, None), record(&b, 2, None), record(&a, 3, Some("mstsc not found")), record(&c, 4, None)];
+        let recent: Vec<&str> = recent(&history, 2).iter().map(|record| record.client_id.as_str()).collect();
+        assert_eq!(recent, [c.id.as_str(), a.id.as_str()]);
+        assert_eq!(outcome(&history[2]), "failed: mstsc not found");
+    }
+
+    #[test]
+    fn times_drop_the_date_for_today() {
+        let now = Local.with_ymd_and_hms(2026, 10, 16, 18, 0, 0).unwrap();
+        let today = Local.with_ymd_and_hms(2026, 10, 16, 9, 5, 0).unwrap().with_timezone(&Utc);
+        let long_ago = Local.with_ymd_and_hms(2026, 9, 1, 9, 5, 0).unwrap().with_timezone(&Utc);
+        assert_eq!(when(today, now), "09:05");
+        assert_eq!(when(long_ago, now), "2026-09-01 09:05");
+    }
+}
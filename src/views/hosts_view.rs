@@ -0,0 +1,179 @@
+use crate::app::AppState;
+use crate::client::AppMode;
+use crate::hosts_file::{self, HostsEntry};
+use crate::repository::ClientRepository;
+use eframe::egui;
+use std::net::IpAddr;
+
+impl AppState {
+    /// Opens Tools → Hosts File, offering the selected client's host name
+    /// for a new entry.
+    pub fn open_hosts_file(&mut self) {
+        match hosts_file::read(&self.hosts_path) {
+            Ok(entries) => self.hosts_entries = entries,
+            Err(e) => {
+                self.error_message = Some(format!("Could not read the hosts file: {}", e));
+                return;
+            }
+        }
+        self.hosts_name_input.clear();
+        self.hosts_address_input.clear();
+        if let Some(client) = self.client_list.selected_client(self.repository.clients()) {
+            let host = client.endpoint().0;
+            if host.parse::<IpAddr>().is_err() {
+                self.hosts_name_input = host.to_string();
+            }
+        }
+        self.mode = AppMode::HostsFile;
+    }
+
+    /// Entries whose name no stored client connects to any more.
+    pub fn unused_hosts_entries(&self) -> Vec<HostsEntry> {
+        let used = |entry: &HostsEntry| self.repository.clients().iter().any(|c| c.trashed_at.is_none() && c.endpoint().0.eq_ignore_ascii_case(&entry.name));
+        self.hosts_entries.iter().filter(|entry| !used(entry)).cloned().collect()
+    }
+
+    /// Adds the entry of the dialog's inputs, replacing one for the same name.
+    pub fn add_hosts_entry(&mut self) {
+        let entry = match HostsEntry::parse(&self.hosts_name_input, &self.hosts_address_input) {
+            Ok(entry) => entry,
+            Err(e) => {
+                self.error_message = Some(e);
+                return;
+            }
+        };
+        let mut entries: Vec<HostsEntry> = self.hosts_entries.iter().filter(|own| own.name != entry.name).cloned().collect();
+        entries.push(entry.clone());
+        if self.write_hosts_entries(entries) {
+            self.info_message = Some(format!("{} now resolves to {}", entry.name, entry.address));
+            self.hosts_name_input.clear();
+            self.hosts_address_input.clear();
+        }
+    }
+
+    /// Removes `remove` from the hosts file.
+    pub fn remove_hosts_entries(&mut self, remove: &[HostsEntry]) {
+        let entries = self.hosts_entries.iter().filter(|entry| !remove.contains(entry)).cloned().collect();
+        if self.write_hosts_entries(entries) {
+            self.info_message = Some(format!("Removed {} hosts file entries", remove.len()));
+        }
+    }
+
+    fn write_hosts_entries(&mut self, entries: Vec<HostsEntry>) -> bool {
+        match hosts_file::write(&self.hosts_path, &entries) {
+            Ok(()) => {
+                self.hosts_entries = entries;
+                true
+            }
+            Err(e) => {
+                self.error_message = Some(format!("Could not write the hosts file: {}", e));
+                false
+            }
+        }
+    }
+
+    pub(crate) fn hosts_file_view(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Hosts File");
+        ui.label(format!("Names of lab hosts that DNS does not know, added to {}. Only the entries made here are listed and changed.", self.hosts_path.display()));
+        ui.separator();
+        let unused = self.unused_hosts_entries();
+        let mut remove = None;
+        if self.hosts_entries.is_empty() {
+            ui.label("No entries yet.");
+        }
+        egui::Grid::new("hosts_entries").striped(true).num_columns(3).show(ui, |ui| {
+            for entry in &self.hosts_entries {
+                ui.label(&entry.name);
+                ui.label(entry.address.to_string());
+                ui.horizontal(|ui| {
+                    if ui.button("Remove").clicked() {
+                        remove = Some(vec![entry.clone()]);
+                    }
+                    if unused.contains(entry) {
+                        ui.weak("no client uses it");
+                    }
+                });
+                ui.end_row();
+            }
+        });
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label("Host name:");
+            ui.text_edit_singleline(&mut self.hosts_name_input);
+            ui.label("Address:");
+            ui.text_edit_singleline(&mut self.hosts_address_input);
+            if ui.button("Add").clicked() {
+                self.add_hosts_entry();
+            }
+        });
+        ui.weak("Writing the hosts file needs administrator rights; the system asks for them when needed.");
+        ui.horizontal(|ui| {
+            if ui.add_enabled(!unused.is_empty(), egui::Button::new("Remove Unused")).clicked() {
+                remove = Some(unused.clone());
+            }
+            if ui.add_enabled(!self.hosts_entries.is_empty(), egui::Button::new("Remove All")).clicked() {
+                remove = Some(self.hosts_entries.clone());
+            }
+            if ui.button("Back").clicked() {
+                self.mode = AppMode::Normal;
+            }
+        });
+        if let Some(remove) = remove {
+            self.remove_hosts_entries(&remove);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Client;
+    use crate::hosts_file::{owned_entries, with_owned_entries};
+
+    const SYSTEM: &str = "127.0.0.1\tlocalhost\r\n# user's own\r\n10.1.1.1 build\r\n";
+
+    #[test]
+    fn only_the_marked_block_is_rewritten() {
+        let dc = HostsEntry::parse("DC01.lab.local", " 192.168.56.10 ").unwrap();
+        let added = with_owned_entries(SYSTEM, std::slice::from_ref(&dc));
+        assert!(added.starts_with(SYSTEM));
+        assert!(added.ends_with("# END remote_desktop_manager\r\n"));
+        assert_eq!(owned_entries(&added), [dc]);
+        assert_eq!(owned_entries(SYSTEM), []);
+        assert_eq!(with_owned_entries(&added, &[]), SYSTEM);
+        assert!(HostsEntry::parse("dc 01", "192.168.56.10").is_err());
+        assert!(HostsEntry::parse("dc01", "192.168.56").is_err());
+    }
+
+    #[test]
+    fn entries_are_added_replaced_and_cleaned_up() {
+        let mut state = AppState::unlocked_for_test();
+        state.hosts_path = std::env::temp_dir().join(format!("rdm-hosts-{}", crate::client::new_client_id()));
+        std::fs::write(&state.hosts_path, SYSTEM).unwrap();
+        let client = Client { name: "Lab DC".to_string(), ip: "dc01.lab.local:3389".to_string(), ..Default::default() };
+        state.repository.add(client.clone());
+        state.client_list.select(&client);
+
+        state.open_hosts_file();
+        assert_eq!(state.hosts_name_input, "dc01.lab.local");
+        state.hosts_address_input = "192.168.56.10".to_string();
+        state.add_hosts_entry();
+        state.hosts_name_input = "old.lab.local".to_string();
+        state.hosts_address_input = "192.168.56.99".to_string();
+        state.add_hosts_entry();
+        state.hosts_name_input = "dc01.lab.local".to_string();
+        state.hosts_address_input = "192.168.56.11".to_string();
+        state.add_hosts_entry();
+        assert_eq!(state.error_message, None);
+
+        let written = hosts_file::read(&state.hosts_path).unwrap();
+        assert_eq!(written.iter().map(|e| (e.name.as_str(), e.address.to_string())).collect::<Vec<_>>(), [("old.lab.local", "192.168.56.99".to_string()), ("dc01.lab.local", "192.168.56.11".to_string())]);
+        let unused = state.unused_hosts_entries();
+        assert_eq!(unused.len(), 1);
+        state.remove_hosts_entries(&unused);
+        assert_eq!(hosts_file::read(&state.hosts_path).unwrap().len(), 1);
+        state.remove_hosts_entries(&state.hosts_entries.clone());
+        assert_eq!(std::fs::read_to_string(&state.hosts_path).unwrap(), SYSTEM);
+        let _ = std::fs::remove_file(&state.hosts_path);
+    }
+}
@@ -0,0 +1,117 @@
+use eframe::egui;
+use rand::seq::SliceRandom;
+
+const KEYS: &str = "abcdefghijklmnopqrstuvwxyz0123456789!@#$%&*()-_=+[]{};:'\",.<>/?\\|`~^";
+const KEYS_PER_ROW: usize = 10;
+
+/// What a click on the keypad does to the field it types into.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KeypadInput {
+    Char(char),
+    Backspace,
+}
+
+/// On-screen keyboard for the master key, so a hardware keylogger sees no
+/// keystrokes. The keys are shuffled again after every click, so the click
+/// positions alone do not give the key away either.
+pub struct Keypad {
+    keys: Vec<char>,
+    pub shift: bool,
+    /// Type into the confirmation field instead of the master key field.
+    pub confirm: bool,
+}
+
+impl Default for Keypad {
+    fn default() -> Self {
+        let mut keypad = Self {
+            keys: KEYS.chars().collect(),
+            shift: false,
+            confirm: false,
+        };
+        keypad.shuffle();
+        keypad
+    }
+}
+
+impl Keypad {
+    pub fn shuffle(&mut self) {
+        self.keys.shuffle(&mut rand::thread_rng());
+    }
+
+    fn label(&self, key: char) -> char {
+        if self.shift {
+            key.to_ascii_uppercase()
+        } else {
+            key
+        }
+    }
+
+    /// Draws the keypad and returns the key clicked this frame, if any.
+    pub fn show(&mut self, ui: &mut egui::Ui) -> Option<KeypadInput> {
+        let mut input = None;
+        egui::Frame::group(ui.style()).show(ui, |ui| {
+            for row in self.keys.chunks(KEYS_PER_ROW) {
+                ui.horizontal(|ui| {
+                    for &key in row {
+                        let label = self.label(key);
+                        let button = egui::Button::new(egui::RichText::new(label.to_string()).monospace()).min_size(egui::vec2(24.0, 24.0));
+                        if ui.add(button).clicked() {
+                            input = Some(KeypadInput::Char(label));
+                        }
+                    }
+                });
+            }
+            ui.horizontal(|ui| {
+                ui.toggle_value(&mut self.shift, "Shift");
+                if ui.button("Space").clicked() {
+                    input = Some(KeypadInput::Char(' '));
+                }
+                if ui.button("⌫").on_hover_text("Backspace").clicked() {
+                    input = Some(KeypadInput::Backspace);
+                }
+            });
+        });
+        if input.is_some() {
+            self.shuffle();
+        }
+        input
+    }
+}
+
+/// Applies a keypad click to `field`.
+pub fn apply(field: &mut String, input: KeypadInput) {
+    match input {
+        KeypadInput::Char(c) => field.push(c),
+        KeypadInput::Backspace => {
+            field.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shuffling_keeps_every_key() {
+        let mut keypad = Keypad::default();
+        keypad.shuffle();
+        let mut keys = keypad.keys.clone();
+        keys.sort_unstable();
+        let mut expected: Vec<char> = KEYS.chars().collect();
+        expected.sort_unstable();
+        assert_eq!(keys, expected);
+    }
+
+    #[test]
+    fn shift_types_capitals_and_backspace_deletes() {
+        let mut keypad = Keypad { shift: true, ..Default::default() };
+        let mut field = String::from("ab");
+        apply(&mut field, KeypadInput::Char(keypad.label('c')));
+        assert_eq!(field, "abC");
+        keypad.shift = false;
+        apply(&mut field, KeypadInput::Char(keypad.label('1')));
+        apply(&mut field, KeypadInput::Backspace);
+        assert_eq!(field, "abC");
+    }
+}
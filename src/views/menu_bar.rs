@@ -0,0 +1,472 @@
+use crate::app::{AppState, HANDOVER_TIME};
+use crate::client::AppMode;
+use crate::inventory::{exported_name, FIELDS};
+use crate::link::register_handlers;
+use crate::monitoring::MonitoringSystem;
+use crate::repository::ClientRepository;
+use crate::shortcuts::{self, Shortcut};
+use crate::views::history_view::HistoryFilter;
+use crate::views::settings_view::SettingsTab;
+use eframe::egui;
+use zeroize::Zeroize;
+
+/// Commands offered by the menu bar.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MenuAction {
+    New,
+    Edit,
+    Remove,
+    Export,
+    ExportInventory,
+    HandoverReport,
+    ImportCsv,
+    ImportMstscHistory,
+    ImportRdg,
+    ImportJson,
+    WatchFolder,
+    Netbox,
+    Monitoring,
+    ReferenceLinks,
+    Settings,
+    ChangeMasterKey,
+    RegisterLinkHandlers,
+    RestoreBackup,
+    Lock,
+    Exit,
+    Search,
+    Trash,
+    FindDuplicates,
+    SortByName,
+    HostsFile,
+    Credentials,
+    RotatePasswords,
+    VaultComposition,
+    Sessions,
+    History,
+    Help,
+}
+
+const FILE_MENU: [(&str, MenuAction); 18] = [
+    ("New", MenuAction::New),
+    ("Edit", MenuAction::Edit),
+    ("Remove", MenuAction::Remove),
+    ("Export", MenuAction::Export),
+    ("Export Inventory", MenuAction::ExportInventory),
+    ("Handover Report", MenuAction::HandoverReport),
+    ("Import CSV", MenuAction::ImportCsv),
+    ("Import mstsc History", MenuAction::ImportMstscHistory),
+    ("Import RDCMan", MenuAction::ImportRdg),
+    ("Import JSON", MenuAction::ImportJson),
+    ("Watch Folder", MenuAction::WatchFolder),
+    ("NetBox Sync", MenuAction::Netbox),
+    ("Monitoring", MenuAction::Monitoring),
+    ("Reference Links", MenuAction::ReferenceLinks),
+    ("Settings", MenuAction::Settings),
+    ("Change Master Key", MenuAction::ChangeMasterKey),
+    ("Restore from Backup", MenuAction::RestoreBackup),
+    ("Register Link Handlers", MenuAction::RegisterLinkHandlers),
+];
+
+impl AppState {
+    pub(crate) fn menu_bar(&mut self, ctx: &egui::Context) {
+        let mut action = None;
+        egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
+            egui::menu::bar(ui, |ui| {
+                let bindings = shortcuts::bindings(&self.settings.shortcuts);
+                let shortcut_text = |command: Shortcut| {
+                    bindings.iter().find(|(bound, _)| *bound == command).map(|(_, shortcut)| ctx.format_shortcut(shortcut)).unwrap_or_default()
+                };
+                ui.menu_button("File", |ui| {
+                    for (label, item) in FILE_MENU {
+                        let shortcut = match item {
+                            MenuAction::New => shortcut_text(Shortcut::NewClient),
+                            MenuAction::Edit => shortcut_text(Shortcut::Edit),
+                            MenuAction::Remove => shortcut_text(Shortcut::Remove),
+                            _ => String::new(),
+                        };
+                        if ui.add(egui::Button::new(label).shortcut_text(shortcut)).clicked() {
+                            action = Some(item);
+                        }
+                    }
+                    if ui.add(egui::Button::new("Lock").shortcut_text(shortcut_text(Shortcut::Lock))).clicked() {
+                        action = Some(MenuAction::Lock);
+                    }
+                    if ui.button("Exit").clicked() {
+                        action = Some(MenuAction::Exit);
+                    }
+                    if action.is_some() {
+                        ui.close_menu();
+                    }
+                });
+                ui.menu_button("Tools", |ui| {
+                    if ui.button("Find Duplicates").clicked() {
+                        action = Some(MenuAction::FindDuplicates);
+                        ui.close_menu();
+                    }
+                    if ui.button("Sort Clients by Name").clicked() {
+                        action = Some(MenuAction::SortByName);
+                        ui.close_menu();
+                    }
+                    if ui.button("Hosts File").clicked() {
+                        action = Some(MenuAction::HostsFile);
+                        ui.close_menu();
+                    }
+                    if ui.button("Credentials").clicked() {
+                        action = Some(MenuAction::Credentials);
+                        ui.close_menu();
+                    }
+                    if ui.button("Rotate Passwords").clicked() {
+                        action = Some(MenuAction::RotatePasswords);
+                        ui.close_menu();
+                    }
+                    if ui.button("Vault Composition").clicked() {
+                        action = Some(MenuAction::VaultComposition);
+                        ui.close_menu();
+                    }
+                });
+                if ui.button("Search").clicked() {
+                    action = Some(MenuAction::Search);
+                }
+                if ui.button("Trash").clicked() {
+                    action = Some(MenuAction::Trash);
+                }
+                if ui.button("Sessions").clicked() {
+                    action = Some(MenuAction::Sessions);
+                }
+                if ui.button("History").clicked() {
+                    action = Some(MenuAction::History);
+                }
+                ui.menu_button("Help", |ui| {
+                    if ui.add(egui::Button::new("Help Topics").shortcut_text(shortcut_text(Shortcut::Help))).clicked() {
+                        action = Some(MenuAction::Help);
+                        ui.close_menu();
+                    }
+                });
+                let active = self.sessions.active();
+                if !active.is_empty() {
+                    let details: Vec<String> = active
+                        .iter()
+                        .map(|session| format!("{} ({} min)", session.client_name, session.started.elapsed().as_secs() / 60))
+                        .collect();
+                    let response = ui.link(format!("{} active", active.len())).on_hover_text(details.join("\n"));
+                    if response.clicked() {
+                        action = Some(MenuAction::Sessions);
+                    }
+                }
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    let hover = match shortcut_text(Shortcut::Lock) {
+                        shortcut if shortcut.is_empty() => "Wipe the key and return to the lock screen".to_string(),
+                        shortcut => format!("Wipe the key and return to the lock screen ({})", shortcut),
+                    };
+                    if ui.button("🔒 Lock vault").on_hover_text(hover).clicked() {
+                        action = Some(MenuAction::Lock);
+                    }
+                });
+            });
+        });
+        if let Some(action) = action {
+            self.apply_menu_action(action);
+        }
+    }
+
+    pub(crate) fn apply_menu_action(&mut self, action: MenuAction) {
+        // Anything that would replace the Add or Edit form asks first when
+        // it has unsaved changes.
+        let keeps_form = matches!(action, MenuAction::Lock | MenuAction::Exit | MenuAction::RegisterLinkHandlers | MenuAction::Help);
+        if !keeps_form && matches!(self.mode, AppMode::Adding | AppMode::Editing) && self.form.is_dirty() {
+            self.form.confirm_discard = true;
+            return;
+        }
+        match action {
+            MenuAction::New => {
+                self.form.clear();
+                self.mode = AppMode::Adding;
+            }
+            MenuAction::Edit => {
+                if let Some(client) = self.client_list.selected_client(self.repository.clients()).cloned() {
+                    self.form.load(&client);
+                    self.mode = AppMode::Editing;
+                } else {
+                    self.error_message = Some("Please select a target to edit.".to_string());
+                }
+            }
+            MenuAction::Remove => {
+                if let Some(id) = self.client_list.selected.clone().filter(|_| !self.settings.confirmations.remove_client) {
+                    self.remove_client(&id);
+                } else if self.client_list.selected.is_some() {
+                    self.mode = AppMode::Removing;
+                } else {
+                    self.error_message = Some("Please select a target to remove.".to_string());
+                }
+            }
+            MenuAction::Export => {
+                self.transfer.open(self.transfer.format.file_name());
+                self.transfer.encrypt = true;
+                self.mode = AppMode::Exporting;
+            }
+            MenuAction::ExportInventory => {
+                self.inventory_format_input = self.settings.inventory_format;
+                self.inventory_field_names_input =
+                    FIELDS.iter().map(|field| (*field, exported_name(field, &self.settings.inventory_field_names).to_string())).collect();
+                self.transfer.open(self.inventory_format_input.file_name());
+                self.mode = AppMode::ExportingInventory;
+            }
+            MenuAction::HandoverReport => {
+                // The last twelve hours, a typical shift.
+                let now = chrono::Local::now();
+                self.report_from_input = (now - chrono::Duration::hours(12)).format(HANDOVER_TIME).to_string();
+                self.report_to_input = now.format(HANDOVER_TIME).to_string();
+                self.transfer.open(self.report_format_input.file_name());
+                self.mode = AppMode::HandoverReport;
+            }
+            MenuAction::ImportCsv => {
+                self.transfer.open("clients.csv");
+                self.mode = AppMode::Importing;
+            }
+            MenuAction::ImportMstscHistory => self.offer_mstsc_history(),
+            MenuAction::ImportRdg => {
+                self.transfer.open("");
+                self.mode = AppMode::ImportingRdg;
+            }
+            MenuAction::ImportJson => {
+                self.transfer.open("clients.export.json");
+                self.mode = AppMode::ImportingJson;
+            }
+            MenuAction::WatchFolder => {
+                self.watch_folder_input = self.settings.watch_folder.clone();
+                self.mode = AppMode::WatchFolder;
+            }
+            MenuAction::Netbox => {
+                self.netbox_url_input = self.settings.netbox_url.clone();
+                self.netbox_token_input.zeroize();
+                self.mode = AppMode::Netbox;
+            }
+            MenuAction::Monitoring => {
+                self.monitoring_system_input = self.settings.monitoring_system;
+                self.monitoring_url_input = self.settings.monitoring_url.clone();
+                self.monitoring_token_input.zeroize();
+                self.monitoring_refresh_input = self.settings.monitoring_refresh_minutes;
+                self.mode = AppMode::Monitoring;
+            }
+            MenuAction::ReferenceLinks => {
+                self.url_templates_input = self.settings.url_templates.clone();
+                self.mode = AppMode::UrlTemplates;
+            }
+            MenuAction::Settings => self.open_settings(SettingsTab::General),
+            MenuAction::ChangeMasterKey => {
+                self.unlock_form.clear();
+                self.mode = AppMode::ChangingMasterKey;
+            }
+            MenuAction::RegisterLinkHandlers => match register_handlers() {
+                Ok(()) => self.info_message = Some("rdp://, ssh:// and rdm:// links and .rdm vault files now open in this app.".to_string()),
+                Err(e) => self.error_message = Some(format!("Could not register link handlers: {}", e)),
+            },
+            MenuAction::RestoreBackup => {
+                self.unlock_form.vault_file.clear();
+                self.mode = AppMode::RestoringBackup;
+            }
+            MenuAction::Lock => self.lock(),
+            MenuAction::Exit => {
+                self.save_layout();
+                std::process::exit(0)
+            }
+            MenuAction::Search => self.mode = AppMode::Search,
+            MenuAction::Trash => {
+                self.trash_after_days_input = self.settings.trash_expired_after_days.unwrap_or(7);
+                self.mode = AppMode::Trash;
+            }
+            MenuAction::FindDuplicates => self.open_duplicates(),
+            MenuAction::SortByName => self.sort_clients_by_name(),
+            MenuAction::HostsFile => self.open_hosts_file(),
+            MenuAction::Credentials => self.open_credentials(),
+            MenuAction::RotatePasswords => self.open_rotation(),
+            MenuAction::VaultComposition => self.open_composition(),
+            MenuAction::Sessions => self.mode = AppMode::Sessions,
+            MenuAction::History => {
+                self.history_filter = HistoryFilter::default();
+                self.mode = AppMode::History;
+            }
+            MenuAction::Help => self.open_help(),
+        }
+    }
+
+    pub(crate) fn status_bar(&mut self, ctx: &egui::Context) {
+        egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(format!("{} clients", self.repository.query(&|c| c.trashed_at.is_none()).len()));
+                ui.separator();
+                ui.label(format!("{} active sessions", self.sessions.count()));
+                if let Some(saved) = self.last_saved {
+                    ui.separator();
+                    ui.label(format!("Saved {}", saved.format("%H:%M:%S")));
+                }
+                if self.settings.monitoring_system != MonitoringSystem::None {
+                    ui.separator();
+                    let system = self.settings.monitoring_system.name();
+                    match &self.monitoring_error {
+                        Some(e) => {
+                            ui.colored_label(egui::Color32::RED, format!("{} unavailable", system)).on_hover_text(e);
+                        }
+                        None if self.monitoring_busy && self.monitoring_status.is_empty() => {
+                            ui.label(format!("Asking {}…", system));
+                        }
+                        None => {
+                            ui.label(format!("{} status", system))
+                                .on_hover_text(format!("{} addresses monitored", self.monitoring_status.len()));
+                        }
+                    }
+                }
+                let down = self.down_hosts().len();
+                if down > 0 {
+                    ui.separator();
+                    let text = egui::RichText::new(format!("{} down", down)).color(egui::Color32::RED);
+                    ui.menu_button(text, |ui| {
+                        ui.label("Hosts the reachability check or the monitoring system reports as down.");
+                        if ui.button("Copy as Table").clicked() {
+                            self.copy_down_hosts();
+                            ui.close_menu();
+                        }
+                        ui.horizontal(|ui| {
+                            ui.label("File:");
+                            ui.text_edit_singleline(&mut self.down_hosts_path_input);
+                            if ui.button("Export CSV").clicked() {
+                                self.export_down_hosts();
+                                ui.close_menu();
+                            }
+                        });
+                    });
+                }
+                if self.remote_session {
+                    ui.separator();
+                    ui.colored_label(egui::Color32::from_rgb(200, 140, 0), "Remote session")
+                        .on_hover_text("This app runs inside a Remote Desktop session, whose clipboard reaches the computer it is viewed from. RDP passwords are not copied.");
+                }
+                if self.launcher.is_dry_run() {
+                    ui.separator();
+                    ui.colored_label(egui::Color32::from_rgb(200, 140, 0), "Dry run")
+                        .on_hover_text("Connections are logged, nothing is started");
+                }
+            });
+        });
+    }
+
+    pub(crate) fn notification_bar(&mut self, ctx: &egui::Context) {
+        let Some(notification) = self.notification.clone() else {
+            return;
+        };
+        egui::TopBottomPanel::bottom("notification_bar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(notification);
+                if self.import_undo.is_some() && ui.button("Undo").clicked() {
+                    self.undo_last_import();
+                }
+                if ui.button("Dismiss").clicked() {
+                    self.notification = None;
+                    self.import_undo = None;
+                }
+            });
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Client;
+    use crate::views::dialogs::ExportFormat;
+
+    #[test]
+    fn edit_and_remove_need_a_selection() {
+        let mut state = AppState::unlocked_for_test();
+        state.apply_menu_action(MenuAction::Edit);
+        assert_eq!(state.mode, AppMode::Normal);
+        assert!(state.error_message.take().is_some());
+        state.apply_menu_action(MenuAction::Remove);
+        assert_eq!(state.mode, AppMode::Normal);
+        assert!(state.error_message.is_some());
+    }
+
+    #[test]
+    fn edit_loads_the_selected_client() {
+        let mut state = AppState::unlocked_for_test();
+        state.repository.add(Client { name: "db01".to_string(), ..Default::default() });
+        state.handle_repository_events();
+        state.apply_menu_action(MenuAction::Edit);
+        assert_eq!(state.mode, AppMode::Editing);
+        assert_eq!(state.form.name, "db01");
+    }
+
+    #[test]
+    fn new_starts_from_an_empty_form() {
+        let mut state = AppState::unlocked_for_test();
+        state.form.name = "left over".to_string();
+        state.apply_menu_action(MenuAction::New);
+        assert_eq!(state.mode, AppMode::Adding);
+        assert!(state.form.name.is_empty());
+    }
+
+    #[test]
+    fn unsaved_changes_are_not_replaced_without_asking() {
+        let mut state = AppState::unlocked_for_test();
+        state.apply_menu_action(MenuAction::New);
+        state.form.name = "draft".to_string();
+        state.apply_menu_action(MenuAction::Trash);
+        assert_eq!(state.mode, AppMode::Adding);
+        assert!(state.form.confirm_discard);
+        assert_eq!(state.form.name, "draft");
+    }
+
+    #[test]
+    fn inventory_export_starts_from_the_saved_field_names() {
+        let mut state = AppState::unlocked_for_test();
+        state.settings.inventory_field_names.insert("address".to_string(), "primary_ip".to_string());
+        state.settings.inventory_field_names.insert("owner_phone".to_string(), String::new());
+        state.apply_menu_action(MenuAction::ExportInventory);
+        assert_eq!(state.mode, AppMode::ExportingInventory);
+        assert_eq!(state.transfer.path, "inventory.yaml");
+        let names = &state.inventory_field_names_input;
+        assert_eq!(names.len(), FIELDS.len());
+        assert!(names.contains(&("name", "name".to_string())));
+        assert!(names.contains(&("address", "primary_ip".to_string())));
+        assert!(names.contains(&("owner_phone", String::new())));
+    }
+
+    #[test]
+    fn transfer_dialogs_get_a_default_path() {
+        let mut state = AppState::unlocked_for_test();
+        state.transfer.passphrase = "secret".to_string();
+        state.transfer.format = ExportFormat::Json;
+        state.apply_menu_action(MenuAction::Export);
+        assert_eq!(state.mode, AppMode::Exporting);
+        assert_eq!(state.transfer.path, "clients.export.json");
+        assert!(state.transfer.passphrase.is_empty());
+        assert!(state.transfer.encrypt);
+        state.apply_menu_action(MenuAction::ImportCsv);
+        assert_eq!(state.mode, AppMode::Importing);
+        assert_eq!(state.transfer.path, "clients.csv");
+    }
+
+    #[test]
+    fn down_hosts_are_exported_with_when_they_last_answered() {
+        use chrono::{TimeZone, Utc};
+        let mut state = AppState::unlocked_for_test();
+        let web = Client { name: "web01".to_string(), ip: "10.0.0.5".to_string(), ..Default::default() };
+        let db = Client { name: "db01".to_string(), ip: "10.0.0.6".to_string(), ..Default::default() };
+        let up = Client { name: "jump".to_string(), ip: "10.0.0.7".to_string(), ..Default::default() };
+        for client in [&web, &db, &up] {
+            state.repository.add(client.clone());
+            state.reachability.insert(client.id.clone(), None);
+        }
+        state.reachability.insert(up.id.clone(), Some(std::time::Duration::from_millis(3)));
+        state.last_seen.insert(web.id.clone(), Utc.with_ymd_and_hms(2026, 10, 16, 7, 30, 0).unwrap());
+        let path = std::env::temp_dir().join(format!("rdm-down-{}.csv", crate::client::new_client_id()));
+        state.down_hosts_path_input = path.to_string_lossy().into_owned();
+        state.export_down_hosts();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(path);
+        assert_eq!(csv.lines().count(), 3, "{}", csv);
+        assert!(csv.contains("web01,10.0.0.5,no answer on port 3389,2026-10-16T07:30:00+00:00\n"), "{}", csv);
+        assert!(csv.contains("db01,10.0.0.6,no answer on port 3389,\n"), "{}", csv);
+        assert!(crate::handover::down_hosts_table(&state.down_hosts()).contains("| db01 | 10.0.0.6 | no answer on port 3389 | not since unlock |"));
+    }
+}
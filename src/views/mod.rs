@@ -0,0 +1,22 @@
+//! The egui views. Each module adds its drawing code to `AppState` and owns
+//! the state that only its view needs.
+
+pub mod client_list;
+pub mod composition_view;
+pub mod connect_failure;
+pub mod credentials_view;
+pub mod dialogs;
+pub mod duplicates_view;
+pub mod editor_panel;
+pub mod group_connect;
+pub mod help_view;
+pub mod history_view;
+pub mod hosts_view;
+pub mod keypad;
+pub mod menu_bar;
+pub mod pinned_note;
+pub mod rotation_view;
+pub mod secret_field;
+pub mod sessions_board;
+pub mod settings_view;
+pub mod unlock_view;
@@ -0,0 +1,141 @@
+use crate::app::AppState;
+use crate::client::PinnedNote;
+use crate::repository::ClientRepository;
+use eframe::egui;
+
+/// Clients waiting to be asked for a handoff note, one at a time.
+#[derive(Default)]
+pub struct NotePrompt {
+    /// Client ids; the window asks about the first.
+    pub queue: Vec<String>,
+    /// The note as typed for the first client.
+    pub text: String,
+}
+
+impl AppState {
+    /// Asks for the note to pin to `client_id`, starting from the one
+    /// pinned now. Asked after every session and from the details pane.
+    pub fn ask_for_pinned_note(&mut self, client_id: &str) {
+        if self.note_prompt.queue.iter().any(|id| id == client_id) || self.repository.get(client_id).is_none() {
+            return;
+        }
+        self.note_prompt.queue.push(client_id.to_string());
+        if self.note_prompt.queue.len() == 1 {
+            self.fill_note_prompt();
+        }
+    }
+
+    fn fill_note_prompt(&mut self) {
+        let pinned = self.note_prompt.queue.first().and_then(|id| self.repository.get(id)).and_then(|client| client.pinned_note.as_ref());
+        self.note_prompt.text = pinned.map(|note| note.text.clone()).unwrap_or_default();
+    }
+
+    /// Pins `text` to the client, or unpins its note when `text` is blank.
+    /// A note left as it was keeps the time it was pinned.
+    pub fn pin_note(&mut self, client_id: &str, text: &str) {
+        let Some(mut client) = self.repository.get(client_id).cloned() else {
+            return;
+        };
+        let text = text.trim();
+        if client.pinned_note.as_ref().map_or("", |note| note.text.as_str()) == text {
+            return;
+        }
+        client.pinned_note = (!text.is_empty()).then(|| PinnedNote { text: text.to_string(), pinned_at: chrono::Utc::now() });
+        self.repository.update(client);
+        self.save_clients();
+    }
+
+    /// Moves on to the next client, pinning `text` to this one if given.
+    fn finish_note_prompt(&mut self, text: Option<String>) {
+        if self.note_prompt.queue.is_empty() {
+            return;
+        }
+        let id = self.note_prompt.queue.remove(0);
+        if let Some(text) = text {
+            self.pin_note(&id, &text);
+        }
+        self.fill_note_prompt();
+    }
+
+    pub(crate) fn pinned_note_window(&mut self, ctx: &egui::Context) {
+        let Some(id) = self.note_prompt.queue.first() else {
+            return;
+        };
+        let Some(client) = self.repository.get(id).cloned() else {
+            self.finish_note_prompt(None);
+            return;
+        };
+        egui::Window::new("Handoff Note")
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!("Note for whoever connects to {} next, such as \"reboot pending, don't patch yet\":", client.name));
+                ui.add(egui::TextEdit::multiline(&mut self.note_prompt.text).desired_rows(3).desired_width(360.0));
+                ui.horizontal(|ui| {
+                    let typed = !self.note_prompt.text.trim().is_empty();
+                    if ui.add_enabled(typed, egui::Button::new("📌 Pin Note")).clicked() {
+                        self.finish_note_prompt(Some(self.note_prompt.text.clone()));
+                    }
+                    if client.pinned_note.is_some() && ui.button("Unpin").clicked() {
+                        self.finish_note_prompt(Some(String::new()));
+                    }
+                    if ui.button("Skip").clicked() {
+                        self.finish_note_prompt(None);
+                    }
+                });
+                if self.note_prompt.queue.len() > 1 {
+                    ui.weak(format!("{} more sessions ended.", self.note_prompt.queue.len() - 1));
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Client;
+
+    fn added(state: &mut AppState, name: &str) -> String {
+        let client = Client { name: name.to_string(), ip: "10.0.0.5".to_string(), ..Default::default() };
+        state.repository.add(client.clone());
+        client.id
+    }
+
+    #[test]
+    fn a_pinned_note_is_shown_before_the_next_connect() {
+        let mut state = AppState::unlocked_for_test();
+        let id = added(&mut state, "db01");
+        state.ask_for_pinned_note(&id);
+        state.note_prompt.text = "  Reboot pending, don't patch yet ".to_string();
+        state.finish_note_prompt(Some(state.note_prompt.text.clone()));
+        let client = state.repository.get(&id).unwrap().clone();
+        assert_eq!(client.pinned_note.as_ref().unwrap().text, "Reboot pending, don't patch yet");
+        assert!(state.note_prompt.queue.is_empty());
+
+        state.request_connect(&client);
+        assert_eq!(state.confirm_connect.map(|client| client.id), Some(id));
+    }
+
+    #[test]
+    fn prompts_queue_up_and_start_from_the_note_pinned_now() {
+        let mut state = AppState::unlocked_for_test();
+        let (db, web) = (added(&mut state, "db01"), added(&mut state, "web01"));
+        state.pin_note(&web, "Disk 95% full");
+        let pinned_at = state.repository.get(&web).unwrap().pinned_note.as_ref().unwrap().pinned_at;
+        state.ask_for_pinned_note(&db);
+        state.ask_for_pinned_note(&web);
+        state.ask_for_pinned_note(&db);
+        assert_eq!(state.note_prompt.queue, [db.clone(), web.clone()]);
+        assert_eq!(state.note_prompt.text, "");
+
+        state.finish_note_prompt(None);
+        assert_eq!(state.note_prompt.text, "Disk 95% full");
+        state.finish_note_prompt(Some(state.note_prompt.text.clone()));
+        assert_eq!(state.repository.get(&web).unwrap().pinned_note.as_ref().unwrap().pinned_at, pinned_at);
+        assert!(state.repository.get(&db).unwrap().pinned_note.is_none());
+
+        state.pin_note(&web, " ");
+        assert!(state.repository.get(&web).unwrap().pinned_note.is_none());
+    }
+}
@@ -0,0 +1,270 @@
+use crate::app::AppState;
+use crate::client::AppMode;
+use crate::events::AppEvent;
+use crate::history::{self, ConnectionRecord};
+use crate::repository::ClientRepository;
+use crate::rotation::{Job, Method};
+use crate::views::secret_field::generate_password;
+use eframe::egui;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::thread;
+use zeroize::{Zeroize, Zeroizing};
+
+/// Tools → Rotate Passwords.
+pub struct RotationDialog {
+    /// Ids of the clients ticked for a change.
+    pub selected: BTreeSet<String>,
+    /// Length of the generated passwords.
+    pub length: usize,
+    /// Change was clicked once and waits for confirmation.
+    pub confirming: bool,
+    /// New passwords of the changes still running, by client id.
+    pub pending: HashMap<String, Zeroizing<String>>,
+    /// Outcome per client id of the changes that finished.
+    pub results: BTreeMap<String, Result<(), String>>,
+    /// Locking was asked for while changes ran; it happens once they finish
+    /// so no new password is lost.
+    pub lock_when_done: bool,
+}
+
+impl Default for RotationDialog {
+    fn default() -> Self {
+        Self {
+            selected: BTreeSet::new(),
+            length: 24,
+            confirming: false,
+            pending: HashMap::new(),
+            results: BTreeMap::new(),
+            lock_when_done: false,
+        }
+    }
+}
+
+impl RotationDialog {
+    pub fn running(&self) -> bool {
+        !self.pending.is_empty()
+    }
+}
+
+impl AppState {
+    pub fn open_rotation(&mut self) {
+        if !self.rotation.running() {
+            self.rotation = RotationDialog::default();
+        }
+        self.mode = AppMode::RotatingPasswords;
+    }
+
+    /// Generates a new password for every ticked client and changes them
+    /// one host after another on a worker thread.
+    pub fn start_rotation(&mut self) {
+        let mut jobs = Vec::new();
+        for id in std::mem::take(&mut self.rotation.selected) {
+            let Some(client) = self.repository.get(&id) else {
+                continue;
+            };
+            let password = Zeroizing::new(generate_password(self.rotation.length));
+            match Job::new(&client.with_default_port(&self.settings.default_ports), &password) {
+                Ok(job) => {
+                    jobs.push(job);
+                    self.rotation.results.remove(&id);
+                    self.rotation.pending.insert(id, password);
+                }
+                Err(e) => {
+                    self.rotation.results.insert(id, Err(e));
+                }
+            }
+        }
+        self.rotation.confirming = false;
+        let (events, launcher) = (self.events.clone(), self.launcher);
+        thread::spawn(move || {
+            for job in jobs {
+                let result = job.run(launcher);
+                events.publish(AppEvent::PasswordChanged(job.client_id.clone(), result));
+            }
+        });
+    }
+
+    /// Stores the new password once the host took it and records the
+    /// attempt in the history. The dry run stores and records nothing.
+    pub fn finish_password_change(&mut self, client_id: &str, result: Result<(), String>) {
+        let Some(password) = self.rotation.pending.remove(client_id) else {
+            return;
+        };
+        if let Some(mut client) = self.repository.get(client_id).cloned().filter(|_| !self.launcher.is_dry_run()) {
+            let now = chrono::Utc::now();
+            let record = ConnectionRecord {
+                client_id: client.id.clone(),
+                client_name: client.name.clone(),
+                address: client.endpoint().0.to_string(),
+                started: now,
+                ended: result.is_ok().then_some(now),
+                error: result.clone().err(),
+                password_changed: true,
+            };
+            history::append(&mut self.repository.history, record);
+            if result.is_ok() {
+                client.password.zeroize();
+                client.password = password.to_string();
+                self.repository.update(client);
+            } else {
+                client.wipe_secrets();
+            }
+            self.save_clients();
+        }
+        self.rotation.results.insert(client_id.to_string(), result);
+        if !self.rotation.running() && std::mem::take(&mut self.rotation.lock_when_done) {
+            self.lock();
+        }
+    }
+
+    pub(crate) fn rotation_view(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Rotate Passwords");
+        ui.label("Changes the stored account's password on each ticked host to a new random one and stores it once the host accepts it.");
+        ui.weak("RDP clients are changed over WinRM, SSH clients with passwd over SSH, which needs key authentication.");
+        if self.launcher.is_dry_run() {
+            ui.weak("Dry run: the changes are only logged, and no password is stored.");
+        }
+        let running = self.rotation.running();
+        let mut offered = Vec::new();
+        let mut others = Vec::new();
+        for client in self.repository.clients().iter().filter(|client| client.trashed_at.is_none()) {
+            match Method::for_client(client) {
+                Ok(method) => offered.push((client.id.clone(), client.name.clone(), client.endpoint().0.to_string(), method)),
+                Err(reason) => others.push((client.name.clone(), reason)),
+            }
+        }
+        ui.separator();
+        egui::ScrollArea::vertical().max_height(320.0).show(ui, |ui| {
+            egui::Grid::new("rotation_clients").striped(true).num_columns(4).show(ui, |ui| {
+                for (id, name, host, method) in &offered {
+                    let mut ticked = self.rotation.selected.contains(id);
+                    if ui.add_enabled(!running, egui::Checkbox::new(&mut ticked, name.as_str())).changed() {
+                        if ticked {
+                            self.rotation.selected.insert(id.clone());
+                        } else {
+                            self.rotation.selected.remove(id);
+                        }
+                    }
+                    ui.label(host);
+                    ui.label(method.label());
+                    match self.rotation.results.get(id) {
+                        _ if self.rotation.pending.contains_key(id) => {
+                            ui.horizontal(|ui| {
+                                ui.spinner();
+                                ui.label("changing…");
+                            });
+                        }
+                        Some(Ok(())) => {
+                            ui.colored_label(egui::Color32::GREEN, "✔ changed");
+                        }
+                        Some(Err(e)) => {
+                            ui.colored_label(egui::Color32::RED, format!("✖ {}", e));
+                        }
+                        None => {
+                            ui.label("");
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+            if offered.is_empty() {
+                ui.label("No client has its own stored account to change.");
+            }
+            if !others.is_empty() {
+                egui::CollapsingHeader::new(format!("Not offered ({})", others.len())).id_source("rotation_others").show(ui, |ui| {
+                    for (name, reason) in &others {
+                        ui.label(format!("{}: {}", name, reason));
+                    }
+                });
+            }
+        });
+        ui.separator();
+        ui.add_enabled_ui(!running, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Select All").clicked() {
+                    self.rotation.selected = offered.iter().map(|(id, ..)| id.clone()).collect();
+                }
+                if ui.button("Select None").clicked() {
+                    self.rotation.selected.clear();
+                }
+                ui.label("New passwords:");
+                ui.add(egui::DragValue::new(&mut self.rotation.length).clamp_range(12..=64).suffix(" characters"));
+            });
+        });
+        ui.horizontal(|ui| {
+            let count = self.rotation.selected.len();
+            if self.rotation.confirming {
+                ui.label(format!("Change the password on {} hosts?", count));
+                if ui.button("Change Passwords").clicked() {
+                    self.start_rotation();
+                }
+                if ui.button("Cancel").clicked() {
+                    self.rotation.confirming = false;
+                }
+            } else if ui.add_enabled(count > 0 && !running, egui::Button::new("Change Passwords…")).clicked() {
+                self.rotation.confirming = true;
+            }
+        });
+        if running {
+            ui.weak("Closing this dialog does not stop the changes; each new password is still stored.");
+        }
+        if ui.button("Close").clicked() {
+            self.rotation.confirming = false;
+            self.mode = AppMode::Normal;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{Client, KeyStatus, Protocol};
+
+    fn rotated(state: &mut AppState, password: &str) -> Client {
+        let client = Client { name: "dc01".to_string(), ip: "10.0.0.5".to_string(), username: "admin".to_string(), password: "old".to_string(), ..Default::default() };
+        state.repository.add(client.clone());
+        state.rotation.pending.insert(client.id.clone(), Zeroizing::new(password.to_string()));
+        client
+    }
+
+    #[test]
+    fn accepted_changes_store_the_new_password() {
+        let mut state = AppState::unlocked_for_test();
+        let client = rotated(&mut state, "n3w-Password");
+        state.finish_password_change(&client.id, Ok(()));
+        assert_eq!(state.repository.get(&client.id).unwrap().password, "n3w-Password");
+        let record = state.repository.history.last().unwrap();
+        assert!(record.password_changed && record.error.is_none());
+        assert!(history::recent(&state.repository.history, 5).is_empty());
+        assert!(!state.rotation.running());
+    }
+
+    #[test]
+    fn refused_changes_keep_the_old_password() {
+        let mut state = AppState::unlocked_for_test();
+        let client = rotated(&mut state, "n3w-Password");
+        state.finish_password_change(&client.id, Err("Access is denied.".to_string()));
+        assert_eq!(state.repository.get(&client.id).unwrap().password, "old");
+        assert_eq!(state.repository.history.last().unwrap().error.as_deref(), Some("Access is denied."));
+    }
+
+    #[test]
+    fn locking_waits_for_running_changes() {
+        let mut state = AppState::unlocked_for_test();
+        let client = rotated(&mut state, "n3w-Password");
+        state.lock();
+        assert!(state.is_unlocked());
+        state.finish_password_change(&client.id, Ok(()));
+        assert!(matches!(state.key_status, KeyStatus::Locked));
+    }
+
+    #[test]
+    fn only_own_accounts_on_rdp_and_ssh_are_offered() {
+        let own = Client { username: "admin".to_string(), password: "secret".to_string(), ..Default::default() };
+        assert_eq!(Method::for_client(&own), Ok(Method::WinRm));
+        assert_eq!(Method::for_client(&Client { protocol: Protocol::Ssh, ..own.clone() }), Ok(Method::Ssh));
+        assert!(Method::for_client(&Client { protocol: Protocol::Vnc, ..own.clone() }).is_err());
+        assert!(Method::for_client(&Client { domain: "CORP".to_string(), ..own.clone() }).is_err());
+        assert!(Method::for_client(&Client { credential: Some("shared".to_string()), ..own }).is_err());
+    }
+}
@@ -0,0 +1,253 @@
+use crate::clipboard_guard;
+use crate::repository::EncryptedFileRepository;
+use crate::secure_prompt;
+use crate::strength::assess;
+use eframe::egui;
+use rand::seq::SliceRandom;
+use std::time::Duration;
+
+const LOWER: &str = "abcdefghijkmnopqrstuvwxyz";
+const UPPER: &str = "ABCDEFGHJKLMNPQRSTUVWXYZ";
+const DIGITS: &str = "23456789";
+/// Symbols that survive .rdp files, command lines and most password rules.
+const SYMBOLS: &str = "!#%+-=?@_";
+const GENERATED_LENGTH: usize = 20;
+
+/// A random password with at least one character of each class. Look-alikes
+/// such as `l`, `1`, `O` and `0` are left out so it can be read aloud.
+pub fn generate_password(length: usize) -> String {
+    let classes = [LOWER, UPPER, DIGITS, SYMBOLS];
+    let alphabet: Vec<char> = classes.concat().chars().collect();
+    let mut rng = rand::thread_rng();
+    loop {
+        let password: String = (0..length).map(|_| *alphabet.choose(&mut rng).unwrap()).collect();
+        if length < classes.len() || classes.iter().all(|class| password.chars().any(|c| class.contains(c))) {
+            return password;
+        }
+    }
+}
+
+/// Strength bar and hints for `secret`, measured against `min_bits`; with
+/// `min_bits` of 0 nothing is required and only the estimate is shown.
+pub(crate) fn strength_meter(ui: &mut egui::Ui, secret: &str, min_bits: u32) {
+    if secret.is_empty() {
+        return;
+    }
+    let strength = assess(secret);
+    // Without a requirement, rate against a typical one.
+    let base = if min_bits == 0 { 40 } else { min_bits };
+    let (color, verdict) = if strength.bits < min_bits {
+        (egui::Color32::from_rgb(210, 50, 50), "too weak")
+    } else if strength.bits < base + 20 {
+        (egui::Color32::from_rgb(220, 150, 0), "acceptable")
+    } else {
+        (egui::Color32::from_rgb(40, 160, 70), "strong")
+    };
+    let target = (base + 40) as f32;
+    let text = match min_bits {
+        0 => format!("about {} bits, {}", strength.bits, verdict),
+        _ => format!("about {} bits, {} (at least {} required)", strength.bits, verdict, min_bits),
+    };
+    ui.add(egui::ProgressBar::new((strength.bits as f32 / target).min(1.0)).fill(color).text(text));
+    for hint in strength.feedback {
+        ui.weak(hint);
+    }
+}
+
+/// Asks for the master key on the secure desktop before a password is shown,
+/// see `SecretField::verify_reveal`.
+pub(crate) fn confirm_master_key(repository: &EncryptedFileRepository) -> bool {
+    let answer = secure_prompt::ask("Remote Desktop Manager", "Enter the master key as the password to show the stored password.");
+    matches!(answer, Ok(Some(key)) if repository.is_master_key(&key))
+}
+
+/// Whether a secret field shows its secret, kept per label between frames.
+#[derive(Clone, Copy, Default)]
+struct RevealState {
+    revealed: bool,
+    /// Revealing was confirmed, for fields that ask first.
+    confirmed: bool,
+    /// The eye was clicked and the confirmation is showing.
+    asking: bool,
+    /// The check given to `verify_reveal` failed the last time.
+    refused: bool,
+    last_drawn: u64,
+}
+
+/// Labelled masked field for a password, key or token, with an eye button
+/// to reveal it and optional copy, generate and strength meter. Whether it
+/// is revealed is kept per label and forgotten as soon as the field is not
+/// drawn for a frame, so closing a form always hides the secret again.
+pub struct SecretField<'a> {
+    label: egui::WidgetText,
+    secret: &'a mut String,
+    hint: &'a str,
+    hold_to_reveal: bool,
+    confirm_reveal: bool,
+    verify_reveal: Option<&'a dyn Fn() -> bool>,
+    copy: Option<Option<u32>>,
+    generate: bool,
+    min_bits: Option<u32>,
+}
+
+impl<'a> SecretField<'a> {
+    pub fn new(label: impl Into<egui::WidgetText>, secret: &'a mut String) -> Self {
+        Self {
+            label: label.into(),
+            secret,
+            hint: "",
+            hold_to_reveal: false,
+            confirm_reveal: false,
+            verify_reveal: None,
+            copy: None,
+            generate: false,
+            min_bits: None,
+        }
+    }
+
+    pub fn hint_text(mut self, hint: &'a str) -> Self {
+        self.hint = hint;
+        self
+    }
+
+    /// Only reveal while the pointer is held on the eye button.
+    pub fn hold_to_reveal(mut self, hold: bool) -> Self {
+        self.hold_to_reveal = hold;
+        self
+    }
+
+    /// Ask before the secret is shown the first time while the field is open.
+    pub fn confirm_reveal(mut self, confirm: bool) -> Self {
+        self.confirm_reveal = confirm;
+        self
+    }
+
+    /// Runs `check` before the secret is shown the first time while the
+    /// field is open, instead of asking in the form; see
+    /// `confirm_master_key`.
+    pub fn verify_reveal(mut self, check: Option<&'a dyn Fn() -> bool>) -> Self {
+        self.verify_reveal = check;
+        self
+    }
+
+    /// Adds a copy button; the clipboard is cleared after `clear_after_seconds`
+    /// if it still holds the secret.
+    pub fn copyable(mut self, clear_after_seconds: Option<u32>) -> Self {
+        self.copy = Some(clear_after_seconds);
+        self
+    }
+
+    /// Adds a button that replaces the secret with a random password.
+    pub fn generate(mut self) -> Self {
+        self.generate = true;
+        self
+    }
+
+    /// Shows the strength meter below the field, see `strength_meter`.
+    pub fn strength(mut self, min_bits: u32) -> Self {
+        self.min_bits = Some(min_bits);
+        self
+    }
+}
+
+/// Small button that Tab skips, so tabbing goes from field to field.
+fn icon_button(ui: &mut egui::Ui, enabled: bool, icon: &str, hover: &str) -> egui::Response {
+    let sense = egui::Sense { focusable: false, ..egui::Sense::click() };
+    ui.add_enabled(enabled, egui::Button::new(icon).sense(sense)).on_hover_text(hover)
+}
+
+impl egui::Widget for SecretField<'_> {
+    /// Returns the response of the text field.
+    fn ui(self, ui: &mut egui::Ui) -> egui::Response {
+        let id = ui.make_persistent_id(("secret_field", self.label.text()));
+        let frame = ui.ctx().frame_nr();
+        let mut state = ui.data(|data| data.get_temp::<RevealState>(id)).unwrap_or_default();
+        if state.last_drawn + 1 < frame {
+            state = RevealState::default();
+        }
+        let allowed = (!self.confirm_reveal && self.verify_reveal.is_none()) || state.confirmed;
+        // With hold to reveal, last frame's press only counts while the
+        // button is still down, so releasing hides the secret at once.
+        let shown = allowed && state.revealed && (!self.hold_to_reveal || ui.input(|input| input.pointer.primary_down()));
+        let response = ui
+            .horizontal(|ui| {
+                ui.label(self.label);
+                let response = ui.add(egui::TextEdit::singleline(self.secret).password(!shown).hint_text(self.hint));
+                if !allowed {
+                    if icon_button(ui, true, "👁", "Show").clicked() {
+                        match self.verify_reveal {
+                            Some(check) => {
+                                state.confirmed = check();
+                                state.refused = !state.confirmed;
+                                state.revealed = state.confirmed && !self.hold_to_reveal;
+                            }
+                            None => state.asking = true,
+                        }
+                    }
+                } else if self.hold_to_reveal {
+                    let pressed = icon_button(ui, true, "👁", "Hold to show").is_pointer_button_down_on();
+                    if pressed != state.revealed {
+                        state.revealed = pressed;
+                        ui.ctx().request_repaint();
+                    }
+                } else if icon_button(ui, true, "👁", if state.revealed { "Hide" } else { "Show" }).clicked() {
+                    state.revealed = !state.revealed;
+                }
+                if let Some(clear_after) = self.copy {
+                    if icon_button(ui, !self.secret.is_empty(), "📋", "Copy").clicked() {
+                        ui.output_mut(|output| output.copied_text = self.secret.clone());
+                        if let Some(seconds) = clear_after {
+                            clipboard_guard::clear_after(self.secret, Duration::from_secs(seconds as u64));
+                        }
+                    }
+                }
+                if self.generate && icon_button(ui, true, "🎲", "Generate a random password").clicked() {
+                    *self.secret = generate_password(GENERATED_LENGTH);
+                }
+                response
+            })
+            .inner;
+        if state.asking {
+            ui.horizontal(|ui| {
+                ui.label("Show this password?");
+                if ui.button("Show").clicked() {
+                    state.confirmed = true;
+                    state.revealed = !self.hold_to_reveal;
+                    state.asking = false;
+                }
+                if ui.button("Cancel").clicked() {
+                    state.asking = false;
+                }
+            });
+        }
+        if state.refused {
+            ui.weak("Not shown: the master key was not confirmed.");
+        }
+        state.last_drawn = frame;
+        ui.data_mut(|data| data.insert_temp(id, state));
+        if let Some(min_bits) = self.min_bits {
+            strength_meter(ui, self.secret, min_bits);
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_passwords_mix_every_class() {
+        let password = generate_password(GENERATED_LENGTH);
+        assert_eq!(password.chars().count(), GENERATED_LENGTH);
+        for class in [LOWER, UPPER, DIGITS, SYMBOLS] {
+            assert!(password.chars().any(|c| class.contains(c)), "{} lacks one of {}", password, class);
+        }
+        assert_ne!(password, generate_password(GENERATED_LENGTH));
+    }
+
+    #[test]
+    fn short_passwords_are_still_generated() {
+        assert_eq!(generate_password(2).len(), 2);
+    }
+}
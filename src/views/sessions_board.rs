@@ -0,0 +1,143 @@
+use crate::app::AppState;
+use crate::client::AppMode;
+use crate::presence;
+use crate::session_window;
+use crate::sessions::ActiveSession;
+use eframe::egui;
+use std::time::Duration;
+
+const TILE_WIDTH: f32 = 180.0;
+
+/// `h:mm:ss` since the session started.
+pub fn format_duration(elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs();
+    format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+/// Green with more than a quarter of the limit left, amber for the last
+/// quarter and red once it has run out.
+pub fn countdown_color(remaining: Duration, limit: Duration) -> egui::Color32 {
+    if remaining.is_zero() {
+        egui::Color32::from_rgb(210, 50, 50)
+    } else if remaining * 4 <= limit {
+        egui::Color32::from_rgb(220, 150, 0)
+    } else {
+        egui::Color32::from_rgb(40, 160, 70)
+    }
+}
+
+impl AppState {
+    /// One tile per running session with buttons to raise or disconnect it.
+    pub(crate) fn sessions_board(&mut self, ui: &mut egui::Ui) {
+        let sessions = self.sessions.active();
+        ui.heading("Open Sessions");
+        if sessions.is_empty() {
+            ui.label("No sessions are running.");
+        } else {
+            ui.ctx().request_repaint_after(Duration::from_secs(1));
+        }
+        ui.horizontal_wrapped(|ui| {
+            for session in &sessions {
+                self.session_tile(ui, session);
+            }
+        });
+        ui.separator();
+        self.teammates(ui);
+        if ui.button("Back").clicked() {
+            self.mode = AppMode::Normal;
+        }
+    }
+
+    /// Sessions other instances on the LAN announce, and the switch to
+    /// announce our own.
+    fn teammates(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Teammates");
+        let mut changed = ui
+            .checkbox(&mut self.settings.lan_presence, "Share my open sessions with teammates on this network")
+            .on_hover_text("Announces the names and hosts of open sessions over mDNS")
+            .changed();
+        ui.horizontal(|ui| {
+            ui.label("Shown as:");
+            let response = ui.add(egui::TextEdit::singleline(&mut self.settings.presence_name).hint_text(presence::os_user()));
+            changed |= response.lost_focus();
+        });
+        if changed {
+            // Restarted under the new name by the next frame.
+            self.presence = None;
+            if let Err(e) = self.settings.save() {
+                self.error_message = Some(format!("Could not save settings: {}", e));
+            }
+        }
+        let Some(presence) = &self.presence else {
+            return;
+        };
+        let peers = presence.peers();
+        if peers.is_empty() {
+            ui.label("No teammates found on this network.");
+        }
+        for peer in peers {
+            let sessions: Vec<&str> = peer.sessions.iter().map(|session| session.client_name.as_str()).collect();
+            let text = if sessions.is_empty() {
+                format!("{} ({}): no sessions", peer.user, peer.machine)
+            } else {
+                format!("{} ({}): connected to {}", peer.user, peer.machine, sessions.join(", "))
+            };
+            ui.label(text);
+        }
+    }
+
+    fn session_tile(&mut self, ui: &mut egui::Ui, session: &ActiveSession) {
+        egui::Frame::group(ui.style()).show(ui, |ui| {
+            ui.set_width(TILE_WIDTH);
+            ui.vertical(|ui| {
+                ui.label(egui::RichText::new(&session.client_name).strong());
+                ui.label(format_duration(session.started.elapsed()));
+                if let (Some(limit), Some(remaining)) = (session.limit, session.remaining()) {
+                    let color = countdown_color(remaining, Duration::from_secs(limit.minutes as u64 * 60));
+                    let text = if remaining.is_zero() {
+                        "Time limit reached".to_string()
+                    } else {
+                        format!("{} left", format_duration(remaining))
+                    };
+                    ui.colored_label(color, text);
+                }
+                if session.simulated {
+                    ui.weak("Dry run");
+                }
+                ui.add_enabled_ui(!session.simulated, |ui| ui.horizontal(|ui| {
+                    if ui.button("Focus").clicked() {
+                        if let Err(e) = session_window::focus(session.pid) {
+                            self.error_message = Some(e);
+                        }
+                    }
+                    if ui.button("Disconnect").clicked() {
+                        if let Err(e) = session_window::close(session.pid) {
+                            self.error_message = Some(e);
+                        }
+                    }
+                }));
+            });
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_show_hours_minutes_and_seconds() {
+        assert_eq!(format_duration(Duration::from_secs(59)), "0:00:59");
+        assert_eq!(format_duration(Duration::from_secs(3 * 3600 + 5 * 60 + 7)), "3:05:07");
+    }
+
+    #[test]
+    fn countdown_turns_amber_then_red() {
+        let limit = Duration::from_secs(3600);
+        let green = countdown_color(Duration::from_secs(1800), limit);
+        let amber = countdown_color(Duration::from_secs(900), limit);
+        let red = countdown_color(Duration::ZERO, limit);
+        assert!(green != amber && amber != red && green != red);
+        assert_eq!(amber, countdown_color(Duration::from_secs(60), limit));
+    }
+}
@@ -0,0 +1,541 @@
+use crate::app::AppState;
+use crate::client::{parse_tags, AppMode, Protocol};
+use crate::hello::{self, QuickUnlock};
+use crate::launcher::Launcher;
+use crate::repository::ClientRepository;
+use crate::secure_prompt;
+use crate::settings::{DefaultPorts, KeyCaching, RdpOptions, Redirection, Settings, Theme};
+use crate::shortcuts::{self, Shortcut};
+use crate::storage;
+use crate::views::secret_field::SecretField;
+use eframe::egui;
+
+/// Page of the Settings dialog.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SettingsTab {
+    #[default]
+    General,
+    Programs,
+    RemoteDesktop,
+    Keyboard,
+    Security,
+}
+
+impl SettingsTab {
+    pub const ALL: [SettingsTab; 5] =
+        [SettingsTab::General, SettingsTab::Programs, SettingsTab::RemoteDesktop, SettingsTab::Keyboard, SettingsTab::Security];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SettingsTab::General => "General",
+            SettingsTab::Programs => "Programs",
+            SettingsTab::RemoteDesktop => "Remote Desktop",
+            SettingsTab::Keyboard => "Keyboard",
+            SettingsTab::Security => "Security",
+        }
+    }
+}
+
+impl Theme {
+    pub const ALL: [Theme; 3] = [Theme::System, Theme::Dark, Theme::Light];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Theme::System => "Same as system",
+            Theme::Dark => "Dark",
+            Theme::Light => "Light",
+        }
+    }
+}
+
+/// The egui look for `theme`, with `system_dark` deciding `Theme::System`.
+/// The accent replaces the selection and link colors.
+pub fn visuals(theme: Theme, accent: Option<[u8; 3]>, system_dark: bool) -> egui::Visuals {
+    let dark = match theme {
+        Theme::System => system_dark,
+        Theme::Dark => true,
+        Theme::Light => false,
+    };
+    let mut visuals = if dark { egui::Visuals::dark() } else { egui::Visuals::light() };
+    if let Some([r, g, b]) = accent {
+        let accent = egui::Color32::from_rgb(r, g, b);
+        visuals.selection.bg_fill = accent;
+        visuals.hyperlink_color = accent;
+    }
+    visuals
+}
+
+/// One checkbox per kind of redirection.
+pub fn redirection_checkboxes(ui: &mut egui::Ui, redirection: &mut Redirection) {
+    ui.checkbox(&mut redirection.clipboard, "Clipboard");
+    ui.checkbox(&mut redirection.drives, "Local drives");
+    ui.checkbox(&mut redirection.printers, "Printers");
+    ui.checkbox(&mut redirection.smart_cards, "Smart cards");
+    ui.checkbox(&mut redirection.audio, "Play remote audio here");
+}
+
+impl AppState {
+    /// Sets the saved theme, or the one being picked while the Settings
+    /// dialog is open so it can be tried before saving.
+    pub fn apply_theme(&self, ctx: &egui::Context) {
+        let (theme, accent) = match self.mode {
+            AppMode::Settings => (self.theme_input, self.accent_input),
+            _ => (self.settings.theme, self.settings.accent_color),
+        };
+        let system_dark = self.system_theme != Some(eframe::Theme::Light);
+        let visuals = visuals(theme, accent, system_dark);
+        if ctx.style().visuals != visuals {
+            ctx.set_visuals(visuals);
+        }
+    }
+
+    /// Opens the Settings dialog on `tab`, with the saved values filled in.
+    pub fn open_settings(&mut self, tab: SettingsTab) {
+        self.vault_path_input = self.settings.vault_path.clone();
+        self.vault_backups_input = self.settings.vault_backups;
+        self.vault_database_input = self.repository.is_database();
+        self.ssh_client_input = self.settings.ssh_client.clone();
+        self.vnc_viewer_input = self.settings.vnc_viewer.clone();
+        self.default_ports_input = self.settings.default_ports;
+        self.confirm_tags_input = self.settings.confirmations.connect_tags.join(", ");
+        self.dry_run_input = self.settings.dry_run;
+        self.ask_for_pinned_note_input = self.settings.ask_for_pinned_note;
+        self.rdp_options_input = self.settings.rdp_options;
+        self.check_rdp_sign_in_input = self.settings.check_rdp_sign_in;
+        self.theme_input = self.settings.theme;
+        self.accent_input = self.settings.accent_color;
+        self.shortcut_inputs = Shortcut::ALL
+            .into_iter()
+            .map(|command| (command, self.settings.shortcuts.get(&command).cloned().unwrap_or_else(|| command.default_binding().to_string())))
+            .collect();
+        self.unlock_form.clear();
+        self.settings_tab = tab;
+        self.mode = AppMode::Settings;
+    }
+
+    /// Saves every page but Security. The vault location is only touched
+    /// when it was changed.
+    pub fn save_settings(&mut self) -> Result<Option<String>, String> {
+        self.settings.shortcuts = shortcuts::validate(&self.shortcut_inputs)?;
+        let defaults = Settings::default();
+        let or_default = |input: &str, default: String| if input.trim().is_empty() { default } else { input.trim().to_string() };
+        self.settings.ssh_client = or_default(&self.ssh_client_input, defaults.ssh_client);
+        self.settings.vnc_viewer = or_default(&self.vnc_viewer_input, defaults.vnc_viewer);
+        self.settings.default_ports = self.default_ports_input;
+        self.settings.dry_run = self.dry_run_input;
+        self.settings.ask_for_pinned_note = self.ask_for_pinned_note_input;
+        self.settings.vault_backups = self.vault_backups_input;
+        self.repository.backups = self.vault_backups_input;
+        self.settings.rdp_options = self.rdp_options_input;
+        self.settings.check_rdp_sign_in = self.check_rdp_sign_in_input;
+        self.settings.theme = self.theme_input;
+        self.settings.accent_color = self.accent_input;
+        self.launcher = if self.dry_run_input { Launcher::DryRun } else { Launcher::System };
+        if self.is_unlocked() && self.vault_database_input != self.repository.is_database() {
+            self.repository.use_database(self.vault_database_input)?;
+            if let Err(e) = self.repository.save() {
+                let _ = self.repository.use_database(!self.vault_database_input);
+                return Err(e);
+            }
+        }
+        if self.vault_path_input.trim() != self.settings.vault_path {
+            return self.change_vault_location().map(Some);
+        }
+        self.settings.save().map(|()| None).map_err(|e| format!("Could not save settings: {}", e))
+    }
+
+    pub(crate) fn settings_view(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Settings");
+        // Security settings belong to the open vault.
+        let unlocked = self.is_unlocked();
+        ui.horizontal(|ui| {
+            for tab in SettingsTab::ALL.into_iter().filter(|&tab| unlocked || tab != SettingsTab::Security) {
+                ui.selectable_value(&mut self.settings_tab, tab, tab.label());
+            }
+        });
+        ui.separator();
+        match self.settings_tab {
+            SettingsTab::General => self.general_settings(ui),
+            SettingsTab::Programs => self.program_settings(ui),
+            SettingsTab::RemoteDesktop => self.remote_desktop_settings(ui),
+            SettingsTab::Keyboard => self.keyboard_settings(ui),
+            SettingsTab::Security => {
+                self.security_settings(ui);
+                ui.weak("Security settings apply at once.");
+            }
+        }
+        ui.separator();
+        ui.horizontal(|ui| {
+            if self.settings_tab != SettingsTab::Security && ui.button("Save").clicked() {
+                match self.save_settings() {
+                    Ok(message) => {
+                        self.info_message = message;
+                        self.unlock_form.clear();
+                        self.mode = AppMode::Normal;
+                    }
+                    Err(e) => self.error_message = Some(e),
+                }
+            }
+            if ui.button("Close").clicked() {
+                self.unlock_form.clear();
+                self.mode = AppMode::Normal;
+            }
+        });
+    }
+
+    fn general_settings(&mut self, ui: &mut egui::Ui) {
+        ui.label("The vault opened at startup. Leave empty to use the one in the settings folder:");
+        ui.label(storage::vault_path("").display().to_string());
+        ui.horizontal(|ui| {
+            ui.label("Vault file:");
+            ui.text_edit_singleline(&mut self.vault_path_input);
+        });
+        ui.horizontal(|ui| {
+            ui.label("Keep");
+            ui.add(egui::DragValue::new(&mut self.vault_backups_input).clamp_range(0..=100));
+            ui.label("hourly backups of the vault");
+        });
+        ui.add_enabled(self.is_unlocked(), egui::Checkbox::new(&mut self.vault_database_input, "Keep the vault in a SQLite database"))
+            .on_hover_text("Saves only the clients that changed rather than the whole vault, for vaults with thousands of clients. The vault file keeps its name.");
+        ui.checkbox(&mut self.dry_run_input, "Dry run: log connections instead of starting any client");
+        ui.checkbox(&mut self.ask_for_pinned_note_input, "Ask for a handoff note when a session ends");
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label("Theme:");
+            egui::ComboBox::from_id_source("theme").selected_text(self.theme_input.label()).show_ui(ui, |ui| {
+                for theme in Theme::ALL {
+                    ui.selectable_value(&mut self.theme_input, theme, theme.label());
+                }
+            });
+        });
+        ui.horizontal(|ui| {
+            let mut custom = self.accent_input.is_some();
+            if ui.checkbox(&mut custom, "Accent color:").changed() {
+                self.accent_input = custom.then(|| ui.visuals().selection.bg_fill.to_array()).map(|[r, g, b, _]| [r, g, b]);
+            }
+            if let Some(accent) = &mut self.accent_input {
+                ui.color_edit_button_srgb(accent);
+            } else {
+                ui.weak("the theme's own");
+            }
+        });
+    }
+
+    fn program_settings(&mut self, ui: &mut egui::Ui) {
+        ui.label("Programs used for SSH (OpenSSH ssh or PuTTY) and VNC (TightVNC, RealVNC, ...) clients.");
+        ui.label("A full command line with placeholders such as {host} or {jump_user} replaces the built-in arguments.");
+        ui.horizontal(|ui| {
+            ui.label("SSH:");
+            ui.text_edit_singleline(&mut self.ssh_client_input);
+        });
+        ui.horizontal(|ui| {
+            ui.label("VNC:");
+            ui.text_edit_singleline(&mut self.vnc_viewer_input);
+        });
+        ui.separator();
+        ui.label("Default ports, used by clients that set no port of their own:");
+        ui.horizontal(|ui| {
+            for protocol in Protocol::ALL {
+                ui.label(format!("{}:", protocol.label()));
+                ui.add(egui::DragValue::new(self.default_ports_input.get_mut(protocol)).clamp_range(1..=u16::MAX));
+            }
+            if ui.button("Restore Defaults").clicked() {
+                self.default_ports_input = DefaultPorts::default();
+            }
+        });
+    }
+
+    fn remote_desktop_settings(&mut self, ui: &mut egui::Ui) {
+        ui.label("Display options for every Remote Desktop connection, unless a client has its own under Display in its form.");
+        let options = &mut self.rdp_options_input;
+        ui.checkbox(&mut options.full_screen, "Full screen (clients with a window placement always open in a window)");
+        ui.checkbox(&mut options.all_monitors, "Use all monitors");
+        ui.checkbox(&mut options.dynamic_resolution, "Resize the remote desktop with the window");
+        ui.checkbox(&mut options.smart_sizing, "Scale the remote desktop to fit the window");
+        ui.separator();
+        ui.label("Redirected to the remote computer, unless a client has its own under Redirection in its form:");
+        redirection_checkboxes(ui, &mut options.redirection);
+        if ui.button("Restore Defaults").clicked() {
+            *options = RdpOptions::default();
+        }
+        ui.separator();
+        ui.checkbox(&mut self.check_rdp_sign_in_input, "Check the stored account before connecting");
+        ui.weak("Servers with Network Level Authentication are signed in to first, so a wrong or locked account is reported before the remote desktop window opens. The account is only sent to a server showing the certificate it showed the first time.");
+    }
+
+    fn keyboard_settings(&mut self, ui: &mut egui::Ui) {
+        ui.label("Shortcuts such as Ctrl+Shift+N, Alt+Up or Delete. Ctrl is Cmd on macOS; leave a field empty to turn its shortcut off.");
+        ui.label("Shortcuts without Ctrl or Alt do nothing while typing in a field, except on function keys.");
+        egui::Grid::new("shortcuts").num_columns(2).show(ui, |ui| {
+            for (command, binding) in &mut self.shortcut_inputs {
+                ui.label(format!("{}:", command.label()));
+                ui.add(egui::TextEdit::singleline(binding).desired_width(120.0));
+                ui.end_row();
+            }
+        });
+        if ui.button("Restore Defaults").clicked() {
+            for (command, binding) in &mut self.shortcut_inputs {
+                *binding = command.default_binding().to_string();
+            }
+        }
+    }
+
+    fn security_settings(&mut self, ui: &mut egui::Ui) {
+        ui.label(egui::RichText::new("Master key caching").strong());
+        let current = self.settings.key_caching;
+        let mut selected = current;
+        ui.radio_value(&mut selected, KeyCaching::ForgetOnLock, "Forget immediately on lock");
+        ui.radio_value(&mut selected, KeyCaching::RequireEveryLaunch, "Keep until exit, require on every launch");
+        ui.add_enabled_ui(cfg!(windows), |ui| {
+            ui.radio_value(&mut selected, KeyCaching::RememberForSession, "Remember for this OS session (DPAPI)");
+        });
+        if selected != current {
+            self.set_key_caching(selected);
+        }
+        if ui.checkbox(&mut self.settings.forget_key_on_os_lock, "Forget the key when the workstation locks or sleeps").changed() {
+            if let Err(e) = self.settings.save() {
+                self.error_message = Some(format!("Could not save settings: {}", e));
+            }
+        }
+
+        ui.separator();
+        ui.label(egui::RichText::new("Unlock without the master key").strong());
+        let current = self.quick_unlock();
+        let mut selected = current;
+        // Disabled while Windows Hello is asked to confirm the user.
+        ui.add_enabled_ui(hello::AVAILABLE && self.pending_hello_enrollment.is_none(), |ui| {
+            ui.radio_value(&mut selected, None, "Off, always ask for the master key");
+            ui.radio_value(&mut selected, Some(QuickUnlock::Account), "With my Windows account (DPAPI)");
+            ui.add_enabled_ui(hello::hello_available(), |ui| {
+                ui.radio_value(&mut selected, Some(QuickUnlock::WindowsHello), "With Windows Hello: fingerprint, face or PIN");
+            });
+        });
+        ui.weak("The vault key is kept wrapped for your Windows account until this is turned off, across reboots. Other programs running as you could unwrap it; the master key keeps working.");
+        if selected != current {
+            if let Err(e) = self.set_quick_unlock(selected) {
+                self.error_message = Some(format!("Could not change how the vault unlocks: {}", e));
+            }
+        }
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            let mut enabled = self.settings.auto_lock_minutes.is_some();
+            let mut minutes = self.settings.auto_lock_minutes.unwrap_or(15);
+            let mut changed = ui.checkbox(&mut enabled, "Lock after").changed();
+            changed |= ui.add(egui::DragValue::new(&mut minutes).clamp_range(1..=1440)).changed();
+            ui.label("idle minutes");
+            if changed {
+                self.settings.auto_lock_minutes = enabled.then_some(minutes);
+                if let Err(e) = self.settings.save() {
+                    self.error_message = Some(format!("Could not save settings: {}", e));
+                }
+            }
+        });
+        if let Some(minutes) = self.effective_auto_lock_minutes() {
+            ui.label(format!("The vault locks after {} idle minutes.", minutes));
+        }
+        if ui.checkbox(&mut self.settings.hold_to_reveal, "Show passwords only while the eye button is held").changed() {
+            if let Err(e) = self.settings.save() {
+                self.error_message = Some(format!("Could not save settings: {}", e));
+            }
+        }
+        let response = ui
+            .add_enabled(secure_prompt::AVAILABLE, egui::Checkbox::new(&mut self.settings.secure_desktop_prompt, "Enter the master key on the secure desktop"))
+            .on_hover_text("Uses the Windows credential prompt, which other programs cannot read or cover. Showing a stored password asks for the master key there too.")
+            .on_disabled_hover_text("Only available on Windows");
+        if response.changed() {
+            if let Err(e) = self.settings.save() {
+                self.error_message = Some(format!("Could not save settings: {}", e));
+            }
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("New master keys need at least");
+            let response = ui.add(egui::DragValue::new(&mut self.settings.min_master_key_bits).clamp_range(0..=128).suffix(" bits"));
+            if response.changed() {
+                if let Err(e) = self.settings.save() {
+                    self.error_message = Some(format!("Could not save settings: {}", e));
+                }
+            }
+        });
+
+        ui.separator();
+        ui.label(egui::RichText::new("Ask before").strong());
+        let confirmations = &mut self.settings.confirmations;
+        let mut confirmations_changed = ui.checkbox(&mut confirmations.remove_client, "Removing a client").changed();
+        confirmations_changed |= ui.checkbox(&mut confirmations.plaintext_export, "Exporting to a file that is not encrypted").changed();
+        confirmations_changed |= ui.checkbox(&mut confirmations.reveal_password, "Showing a stored password").changed();
+        ui.horizontal(|ui| {
+            ui.label("Connecting to clients tagged:");
+            let response = ui.add(egui::TextEdit::singleline(&mut self.confirm_tags_input).hint_text("prod, customer"));
+            if response.changed() {
+                confirmations.connect_tags = parse_tags(&self.confirm_tags_input);
+                confirmations_changed = true;
+            }
+        });
+        if confirmations_changed {
+            if let Err(e) = self.settings.save() {
+                self.error_message = Some(format!("Could not save settings: {}", e));
+            }
+        }
+
+        ui.separator();
+        self.duress_key_settings(ui);
+
+        ui.separator();
+        ui.label(egui::RichText::new("Vault policy").strong());
+        ui.label("Stored in the vault and enforced on every install that opens it.");
+        let mut policy = self.repository.policy.clone();
+        ui.horizontal(|ui| {
+            ui.label("Minimum master key strength:");
+            ui.add(egui::DragValue::new(&mut policy.min_master_key_bits).clamp_range(0..=128).suffix(" bits"));
+        });
+        ui.horizontal(|ui| {
+            let mut capped = policy.max_auto_lock_minutes.is_some();
+            let mut minutes = policy.max_auto_lock_minutes.unwrap_or(30);
+            ui.checkbox(&mut capped, "Auto-lock at most after");
+            ui.add(egui::DragValue::new(&mut minutes).clamp_range(1..=1440));
+            ui.label("minutes");
+            policy.max_auto_lock_minutes = capped.then_some(minutes);
+        });
+        ui.checkbox(&mut policy.clipboard_allowed, "Allow copying passwords to the clipboard");
+        ui.separator();
+        ui.label(egui::RichText::new("Clipboard").strong());
+        let mut clipboard_changed = ui
+            .add_enabled(
+                self.repository.policy.clipboard_allowed,
+                egui::Checkbox::new(&mut self.settings.copy_password_on_connect, "Copy password to the clipboard for SSH, VNC and RDP clients without a username"),
+            )
+            .changed();
+        clipboard_changed |= ui.checkbox(&mut self.settings.warn_clipboard_history, "Warn when a clipboard history tool is running").changed();
+        ui.horizontal(|ui| {
+            let mut enabled = self.settings.clear_clipboard_after_seconds.is_some();
+            let mut seconds = self.settings.clear_clipboard_after_seconds.unwrap_or(30);
+            let mut changed = ui.checkbox(&mut enabled, "Clear a copied password after").changed();
+            changed |= ui.add(egui::DragValue::new(&mut seconds).clamp_range(5..=600)).changed();
+            ui.label("seconds");
+            if changed {
+                self.settings.clear_clipboard_after_seconds = enabled.then_some(seconds);
+                clipboard_changed = true;
+            }
+        });
+        if clipboard_changed {
+            if let Err(e) = self.settings.save() {
+                self.error_message = Some(format!("Could not save settings: {}", e));
+            }
+        }
+        if policy != self.repository.policy {
+            self.repository.policy = policy;
+            self.save_clients();
+        }
+    }
+
+    fn duress_key_settings(&mut self, ui: &mut egui::Ui) {
+        ui.label(egui::RichText::new("Duress key").strong());
+        ui.label("A second master key that opens a separate decoy vault instead of this one. The vault file does not show whether one is set up, so setting one up replaces any earlier duress key.");
+        let hold = self.settings.hold_to_reveal;
+        ui.add(SecretField::new("Duress key:", &mut self.unlock_form.master_key).hold_to_reveal(hold));
+        ui.add(SecretField::new("Confirm:", &mut self.unlock_form.confirm).hold_to_reveal(hold));
+        if ui.button("Set Up Duress Key").clicked() {
+            let result = self
+                .unlock_form
+                .validate_new(self.min_master_key_bits())
+                .and_then(|()| self.repository.create_decoy(&self.unlock_form.master_key));
+            match result {
+                Ok(()) => {
+                    self.unlock_form.clear();
+                    self.info_message = Some("Unlock with the duress key to fill the decoy vault with innocuous entries.".to_string());
+                }
+                Err(e) => self.error_message = Some(e),
+            }
+        }
+        if ui.button("Remove Duress Key").clicked() {
+            match self.repository.remove_decoy() {
+                Ok(()) => self.info_message = Some("The duress key no longer opens anything.".to_string()),
+                Err(e) => self.error_message = Some(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opening_fills_the_inputs_from_the_saved_settings() {
+        let mut state = AppState::unlocked_for_test();
+        state.settings.ssh_client = "putty".to_string();
+        state.settings.rdp_options.all_monitors = true;
+        state.settings.check_rdp_sign_in = true;
+        state.rdp_options_input = RdpOptions::default();
+        state.open_settings(SettingsTab::RemoteDesktop);
+        assert_eq!(state.mode, AppMode::Settings);
+        assert_eq!(state.settings_tab, SettingsTab::RemoteDesktop);
+        assert_eq!(state.ssh_client_input, "putty");
+        assert!(state.rdp_options_input.all_monitors);
+        assert!(state.check_rdp_sign_in_input);
+    }
+
+    #[test]
+    fn the_theme_being_picked_is_shown_until_the_dialog_closes() {
+        let ctx = egui::Context::default();
+        let mut state = AppState::unlocked_for_test();
+        state.settings.theme = Theme::Dark;
+        state.open_settings(SettingsTab::General);
+        state.theme_input = Theme::Light;
+        state.accent_input = Some([200, 0, 100]);
+        state.apply_theme(&ctx);
+        assert!(!ctx.style().visuals.dark_mode);
+        assert_eq!(ctx.style().visuals.selection.bg_fill, egui::Color32::from_rgb(200, 0, 100));
+        state.mode = AppMode::Normal;
+        state.apply_theme(&ctx);
+        assert!(ctx.style().visuals.dark_mode);
+        assert_eq!(ctx.style().visuals.selection.bg_fill, egui::Visuals::dark().selection.bg_fill);
+    }
+
+    #[test]
+    fn shortcuts_are_saved_in_canonical_form_and_must_differ() {
+        let mut state = AppState::unlocked_for_test();
+        state.open_settings(SettingsTab::Keyboard);
+        fn set(state: &mut AppState, command: Shortcut, binding: &str) {
+            state.shortcut_inputs.iter_mut().find(|(c, _)| *c == command).unwrap().1 = binding.to_string();
+        }
+        set(&mut state, Shortcut::NewClient, "shift + ctrl+n");
+        set(&mut state, Shortcut::Remove, "");
+        set(&mut state, Shortcut::Edit, "Ctrl+E");
+        let custom = shortcuts::validate(&state.shortcut_inputs).unwrap();
+        assert_eq!(custom.into_iter().collect::<Vec<_>>(), [(Shortcut::NewClient, "Ctrl+Shift+N".to_string()), (Shortcut::Remove, String::new())]);
+
+        set(&mut state, Shortcut::Find, "Ctrl+Shift+N");
+        assert_eq!(state.save_settings().unwrap_err(), "Ctrl+Shift+N is used for both \"New client\" and \"Focus the filter\"");
+        set(&mut state, Shortcut::Find, "Ctrl+Hyper");
+        assert!(state.save_settings().is_err());
+        assert!(shortcuts::bindings(&state.settings.shortcuts).iter().any(|(c, _)| *c == Shortcut::Remove));
+    }
+
+    #[test]
+    fn default_ports_apply_to_clients_without_their_own() {
+        use crate::client::{Client, RdsFarm};
+        use crate::reachability::Target;
+        let mut state = AppState::unlocked_for_test();
+        state.settings.default_ports.ssh = 2222;
+        state.open_settings(SettingsTab::Programs);
+        assert_eq!(state.default_ports_input.ssh, 2222);
+
+        let ports = DefaultPorts { rdp: 3390, ..state.default_ports_input };
+        let jump = Client { name: "jump01".to_string(), ip: "jump01.corp".to_string(), protocol: Protocol::Ssh, ..Default::default() };
+        let own = Client { ip: "jump02.corp:22".to_string(), ..jump.clone() };
+        assert_eq!(Target::of(&jump, &ports).unwrap().port, 2222);
+        assert_eq!(own.effective_port(&ports), 22);
+        let command = crate::ssh::command("ssh", &jump.with_default_port(&ports)).unwrap();
+        assert!(command.get_args().any(|arg| arg == "2222"));
+        assert!(matches!(own.with_default_port(&ports), std::borrow::Cow::Borrowed(_)));
+
+        let vnc = Client { protocol: Protocol::Vnc, ..jump.clone() };
+        assert!(matches!(vnc.with_default_port(&ports), std::borrow::Cow::Borrowed(_)));
+        let member = Client { protocol: Protocol::Rdp, farm: Some(RdsFarm { name: "Office".to_string(), broker: "broker01".to_string() }), ..jump };
+        assert_eq!(member.with_default_port(&ports).address(), "jump01.corp:3390");
+        assert_eq!(member.with_default_port(&ports).connection_target().address(), "broker01");
+    }
+}
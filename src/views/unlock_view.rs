@@ -0,0 +1,570 @@
+use crate::app::AppState;
+use crate::client::{AppMode, KeyStatus};
+use crate::encryption::{KEY_SIZE, SALT_SIZE};
+use crate::keycache;
+use crate::secure_prompt;
+use crate::storage;
+use crate::repository::DerivedKeys;
+use crate::strength::estimate_bits;
+use crate::views::keypad::{self, Keypad};
+use crate::views::secret_field::{strength_meter, SecretField};
+use eframe::egui;
+use std::path::PathBuf;
+use std::sync::mpsc::Receiver;
+use std::time::Instant;
+use zeroize::{Zeroize, Zeroizing};
+
+/// Master key entry shown while the vault is locked or not yet created,
+/// and in the Change Master Key dialog.
+#[derive(Default)]
+pub struct UnlockForm {
+    pub master_key: String,
+    pub confirm: String,
+    /// The master key being replaced; only used when changing it.
+    pub current: String,
+    pub keypad: Keypad,
+    /// The lock screen action waiting for a vault file, if one was chosen.
+    pub vault_action: Option<VaultAction>,
+    pub vault_file: String,
+}
+
+/// Lock screen actions that take a vault file and work without the key.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VaultAction {
+    Open,
+    Create,
+    Restore,
+}
+
+impl VaultAction {
+    pub const ALL: [VaultAction; 3] = [VaultAction::Open, VaultAction::Create, VaultAction::Restore];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            VaultAction::Open => "Open Other Vault",
+            VaultAction::Create => "New Vault",
+            VaultAction::Restore => "Restore Backup",
+        }
+    }
+
+    fn prompt(self) -> &'static str {
+        match self {
+            VaultAction::Open => "Vault file to open:",
+            VaultAction::Create => "New vault file:",
+            VaultAction::Restore => "Backup to restore over the current vault:",
+        }
+    }
+}
+
+impl UnlockForm {
+    /// Checks a new master key before the vault is created with it.
+    pub fn validate_new(&self, min_bits: u32) -> Result<(), String> {
+        if self.master_key.is_empty() {
+            return Err("Please enter a master key.".to_string());
+        }
+        let bits = estimate_bits(&self.master_key);
+        if bits < min_bits {
+            return Err(format!("The master key is too weak: about {} bits, at least {} are required.", bits, min_bits));
+        }
+        if self.master_key != self.confirm {
+            return Err("The master keys do not match.".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn clear(&mut self) {
+        self.master_key.zeroize();
+        self.confirm.zeroize();
+        self.current.zeroize();
+    }
+}
+
+/// Result of the key derivation thread.
+pub enum Derived {
+    /// Key and salt for a vault being created.
+    New(Zeroizing<[u8; KEY_SIZE]>, [u8; SALT_SIZE]),
+    Existing(DerivedKeys),
+    /// The key stored for unlocking with Windows, unwrapped.
+    Stored(Zeroizing<[u8; KEY_SIZE]>),
+    /// The Windows Hello prompt was cancelled.
+    Declined,
+}
+
+/// Key derivation started by the Unlock or Create button.
+pub struct PendingUnlock {
+    pub receiver: Receiver<Result<Derived, String>>,
+    pub started: Instant,
+    /// Strength of the entered master key, checked against the vault's
+    /// policy once it is open. `None` when unlocking with Windows.
+    pub key_bits: Option<u32>,
+}
+
+impl PendingUnlock {
+    pub fn new(receiver: Receiver<Result<Derived, String>>, key_bits: Option<u32>) -> Self {
+        Self { receiver, started: Instant::now(), key_bits }
+    }
+}
+
+impl AppState {
+    /// Runs `action` on the file typed on the lock screen.
+    pub fn run_vault_action(&mut self, action: VaultAction) -> Result<(), String> {
+        let path = PathBuf::from(self.unlock_form.vault_file.trim());
+        if path.as_os_str().is_empty() {
+            return Err("Please enter the path of a vault file.".to_string());
+        }
+        match action {
+            VaultAction::Open if !path.is_file() => return Err(format!("There is no vault at {}.", path.display())),
+            VaultAction::Open => self.switch_vault(path),
+            VaultAction::Create if path.exists() => return Err(format!("{} already exists.", path.display())),
+            VaultAction::Create => self.switch_vault(path),
+            VaultAction::Restore => {
+                let vault = self.repository.path().to_path_buf();
+                let aside = storage::restore_vault(&path, &vault)?;
+                self.switch_vault(vault);
+                self.info_message = Some(match aside {
+                    Some(aside) => format!("Restored {}. The vault it replaced was kept as {}.", path.display(), aside.display()),
+                    None => format!("Restored {}.", path.display()),
+                });
+            }
+        }
+        self.unlock_form.vault_action = None;
+        self.unlock_form.vault_file.clear();
+        Ok(())
+    }
+
+    /// Asks for the master key on the secure desktop, twice for a new
+    /// vault, and unlocks with it.
+    pub fn unlock_with_secure_prompt(&mut self) {
+        let creating = matches!(self.key_status, KeyStatus::NotSet);
+        let messages = if creating {
+            ["Enter the master key for the new vault as the password.", "Enter the new master key again to confirm it."].as_slice()
+        } else {
+            ["Enter the master key as the password to unlock the vault."].as_slice()
+        };
+        let mut keys = Vec::new();
+        for message in messages {
+            match secure_prompt::ask("Remote Desktop Manager", message) {
+                Ok(Some(key)) => keys.push(key),
+                Ok(None) => return,
+                Err(e) => {
+                    self.error_message = Some(e);
+                    return;
+                }
+            }
+        }
+        for (key, field) in keys.iter().zip([&mut self.unlock_form.master_key, &mut self.unlock_form.confirm]) {
+            field.clone_from(key);
+        }
+        self.unlock();
+    }
+
+    /// Drops the key kept across a lock, and the one cached for this OS
+    /// session, so the vault opens only with the master key again.
+    pub fn forget_retained_key(&mut self) {
+        self.retained_key.zeroize();
+        keycache::clear(self.repository.path());
+    }
+
+    /// Locks the vault and puts the backup named in the form in its place.
+    /// The vault then opens with the master key the backup was saved with.
+    pub fn restore_backup(&mut self) -> Result<(), String> {
+        self.lock();
+        if self.is_unlocked() {
+            return Err("Wait for the password changes to finish before restoring a backup.".to_string());
+        }
+        self.run_vault_action(VaultAction::Restore)
+    }
+
+    /// The automatic backups of the vault; choosing one fills in the file
+    /// to restore.
+    fn backup_picker(&mut self, ui: &mut egui::Ui) {
+        let backups = storage::list_backups(self.repository.path());
+        if backups.is_empty() {
+            ui.weak("There are no automatic backups yet.");
+            return;
+        }
+        egui::ScrollArea::vertical().id_source("vault_backups").max_height(160.0).show(ui, |ui| {
+            for (path, taken) in backups {
+                let path = path.display().to_string();
+                if ui.selectable_label(self.unlock_form.vault_file == path, taken.format("%Y-%m-%d %H:%M").to_string()).clicked() {
+                    self.unlock_form.vault_file = path;
+                }
+            }
+        });
+    }
+
+    pub(crate) fn restore_backup_view(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Restore from Backup");
+        ui.label("A backup is taken before the vault is saved, at most once an hour:");
+        self.backup_picker(ui);
+        ui.horizontal(|ui| {
+            ui.label("File:");
+            ui.text_edit_singleline(&mut self.unlock_form.vault_file);
+        });
+        ui.weak(format!(
+            "The vault is locked and {} is renamed, not deleted. Unlock with the master key the backup was saved with.",
+            self.repository.path().display()
+        ));
+        ui.horizontal(|ui| {
+            if ui.add_enabled(!self.unlock_form.vault_file.trim().is_empty(), egui::Button::new("Restore")).clicked() {
+                if let Err(e) = self.restore_backup() {
+                    self.error_message = Some(e);
+                }
+            }
+            if ui.button("Cancel").clicked() {
+                self.unlock_form.vault_file.clear();
+                self.mode = AppMode::Normal;
+            }
+        });
+    }
+
+    pub(crate) fn unlock_view(&mut self, ctx: &egui::Context) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if self.mode == AppMode::Settings {
+                self.settings_view(ui);
+                return;
+            }
+            self.unlock_screen(ui);
+            ui.separator();
+            self.vault_actions(ui);
+        });
+    }
+
+    /// Settings and vault files, reachable without the master key so a
+    /// moved or damaged vault can be dealt with.
+    fn vault_actions(&mut self, ui: &mut egui::Ui) {
+        ui.add_enabled_ui(self.pending_unlock.is_none(), |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("⚙ Settings").clicked() {
+                    self.open_settings(Default::default());
+                }
+                for action in VaultAction::ALL {
+                    if ui.selectable_label(self.unlock_form.vault_action == Some(action), action.label()).clicked() {
+                        self.unlock_form.vault_action = (self.unlock_form.vault_action != Some(action)).then_some(action);
+                    }
+                }
+            });
+            let Some(action) = self.unlock_form.vault_action else {
+                return;
+            };
+            ui.horizontal(|ui| {
+                ui.label(action.prompt());
+                ui.text_edit_singleline(&mut self.unlock_form.vault_file);
+                if ui.button("OK").clicked() {
+                    if let Err(e) = self.run_vault_action(action) {
+                        self.error_message = Some(e);
+                    }
+                }
+            });
+            if action == VaultAction::Restore {
+                self.backup_picker(ui);
+            }
+            match action {
+                VaultAction::Open | VaultAction::Create => {
+                    ui.weak("Used until the app exits. Set the vault file in Settings to open it at every start.");
+                }
+                VaultAction::Restore => {
+                    ui.weak(format!("Replaces {}; the file there now is renamed, not deleted.", self.repository.path().display()));
+                }
+            }
+        });
+    }
+
+    fn unlock_screen(&mut self, ui: &mut egui::Ui) {
+        if let Some(key) = self.retained_key {
+            ui.heading("Vault Locked");
+            if ui.button("Unlock").clicked() {
+                if let Err(e) = self.unlock_with_key(key) {
+                    self.error_message = Some(e);
+                }
+            }
+            if ui.button("Forget Key").on_hover_text("Require the master key to unlock").clicked() {
+                self.forget_retained_key();
+            }
+            return;
+        }
+        let creating = matches!(self.key_status, KeyStatus::NotSet);
+        ui.heading(if creating { "Create Master Key" } else { "Enter Master Key" });
+        if let Some(name) = self.vault_name() {
+            ui.label(format!("Vault: {}", name));
+        }
+        let deriving = self.pending_unlock.as_ref().map(|pending| (pending.started.elapsed(), pending.key_bits.is_none()));
+        if let Some(how) = self.quick_unlock().filter(|_| !creating) {
+            if ui.add_enabled(deriving.is_none(), egui::Button::new(how.button())).clicked() {
+                self.unlock_quickly();
+            }
+            ui.weak("or enter the master key:");
+        }
+        let secure = self.secure_prompt();
+        let mut submitted = false;
+        ui.add_enabled_ui(deriving.is_none() && !secure, |ui| {
+            if secure {
+                ui.label("The master key is entered on the secure desktop.");
+                return;
+            }
+            let hold = self.settings.hold_to_reveal;
+            let response = ui.add(SecretField::new("Master key:", &mut self.unlock_form.master_key).hold_to_reveal(hold));
+            submitted |= response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if creating {
+                let response = ui.add(SecretField::new("Confirm:", &mut self.unlock_form.confirm).hold_to_reveal(hold));
+                submitted |= response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                strength_meter(ui, &self.unlock_form.master_key, self.min_master_key_bits());
+            }
+        });
+        match deriving {
+            Some((elapsed, quick)) => {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label(if quick { "Waiting for Windows…".to_string() } else { format!("Deriving the key… {} s", elapsed.as_secs()) });
+                    if ui.button("Cancel").clicked() {
+                        self.cancel_unlock();
+                    }
+                });
+            }
+            None if secure => {
+                if ui.button(if creating { "🔒 Create…" } else { "🔒 Unlock…" }).clicked() {
+                    self.unlock_with_secure_prompt();
+                }
+            }
+            None => {
+                if ui.button(if creating { "Create" } else { "Unlock" }).clicked() || submitted {
+                    self.unlock();
+                }
+            }
+        }
+        if secure {
+            return;
+        }
+        ui.separator();
+        if ui.toggle_value(&mut self.settings.onscreen_keypad, "⌨ On-screen keypad").changed() {
+            if let Err(e) = self.settings.save() {
+                self.error_message = Some(format!("Could not save settings: {}", e));
+            }
+        }
+        if self.settings.onscreen_keypad {
+            if creating {
+                ui.horizontal(|ui| {
+                    ui.label("Type into:");
+                    ui.radio_value(&mut self.unlock_form.keypad.confirm, false, "Master key");
+                    ui.radio_value(&mut self.unlock_form.keypad.confirm, true, "Confirm");
+                });
+            }
+            if let Some(input) = self.unlock_form.keypad.show(ui) {
+                let form = &mut self.unlock_form;
+                keypad::apply(if creating && form.keypad.confirm { &mut form.confirm } else { &mut form.master_key }, input);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hello::QuickUnlock;
+    use crate::os_events::OsEvent;
+    use crate::settings::KeyCaching;
+    use crate::repository::ClientRepository;
+    use crate::strength::assess;
+
+    #[test]
+    fn new_key_must_be_confirmed() {
+        let mut form = UnlockForm::default();
+        assert!(form.validate_new(0).is_err());
+        form.master_key = "correct horse".to_string();
+        form.confirm = "correct hose".to_string();
+        assert!(form.validate_new(0).is_err());
+        form.confirm = "correct horse".to_string();
+        assert!(form.validate_new(0).is_ok());
+    }
+
+    #[test]
+    fn weak_new_keys_are_rejected() {
+        let mut form = UnlockForm::default();
+        for weak in ["password2024", "qwertyuiop", "aaaaaaaaaaaaaaaa", "abcdefgh12345678"] {
+            form.master_key = weak.to_string();
+            form.confirm = weak.to_string();
+            assert!(form.validate_new(50).is_err(), "{} was accepted", weak);
+        }
+        form.master_key = "plum Tractor 7 lantern".to_string();
+        form.confirm = form.master_key.clone();
+        assert!(form.validate_new(50).is_ok());
+    }
+
+    #[test]
+    fn patterns_cost_fewer_bits_than_random_characters() {
+        assert!(assess("password").bits < assess("pqzvmwjx").bits);
+        assert!(assess("123456789").bits < assess("730194826").bits);
+        assert!(!assess("letmein1990").feedback.is_empty());
+    }
+
+    #[test]
+    fn creating_the_vault_unlocks_it_and_clears_the_form() {
+        let mut state = AppState::for_test();
+        assert!(matches!(state.key_status, KeyStatus::NotSet));
+        state.unlock_form.master_key = "correct horse".to_string();
+        state.unlock_form.confirm = "correct horse".to_string();
+        state.unlock_and_wait();
+        assert!(state.is_unlocked());
+        assert!(state.unlock_form.master_key.is_empty() && state.unlock_form.confirm.is_empty());
+    }
+
+    #[test]
+    fn lock_then_unlock_with_the_wrong_key_stays_locked() {
+        let mut state = AppState::for_test();
+        state.unlock_form.master_key = "correct horse".to_string();
+        state.unlock_form.confirm = "correct horse".to_string();
+        state.unlock_and_wait();
+        state.lock();
+        assert!(matches!(state.key_status, KeyStatus::Locked));
+        state.unlock_form.master_key = "wrong".to_string();
+        state.unlock_and_wait();
+        assert!(!state.is_unlocked());
+        assert!(state.error_message.is_some());
+        state.unlock_form.master_key = "correct horse".to_string();
+        state.unlock_and_wait();
+        assert!(state.is_unlocked());
+    }
+
+    #[test]
+    fn locking_the_workstation_forgets_a_key_kept_across_locks() {
+        let mut state = AppState::unlocked_for_test();
+        state.settings.key_caching = KeyCaching::RememberForSession;
+        state.save_clients();
+        state.lock();
+        state.unlock_with_key(state.retained_key.unwrap()).unwrap();
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        state.os_events = Some(receiver);
+        sender.send(OsEvent::SessionLocked).unwrap();
+        state.handle_os_events();
+        assert!(matches!(state.key_status, KeyStatus::Locked));
+        assert!(state.retained_key.is_none());
+
+        state.settings.forget_key_on_os_lock = false;
+        state.unlock_with_key([7; crate::encryption::KEY_SIZE]).unwrap();
+        sender.send(OsEvent::Suspending).unwrap();
+        state.handle_os_events();
+        assert!(!state.is_unlocked());
+        assert!(state.retained_key.is_some());
+
+        state.settings.key_caching = KeyCaching::ForgetOnLock;
+        state.unlock_with_key([7; crate::encryption::KEY_SIZE]).unwrap();
+        state.lock();
+        assert!(state.retained_key.is_none());
+    }
+
+    #[test]
+    fn each_key_caching_policy_keeps_the_key_as_long_as_it_says() {
+        let mut state = AppState::unlocked_for_test();
+        state.save_clients();
+        let path = state.repository.path().to_path_buf();
+        for (caching, kept_across_lock, stored) in [(KeyCaching::ForgetOnLock, false, false), (KeyCaching::RequireEveryLaunch, true, false), (KeyCaching::RememberForSession, true, true)] {
+            state.settings.key_caching = caching;
+            state.lock();
+            assert_eq!(state.retained_key.is_some(), kept_across_lock);
+            state.error_message = None;
+            state.unlock_with_key([7; crate::encryption::KEY_SIZE]).unwrap();
+            // Elsewhere DPAPI is missing, so only a store attempt shows.
+            #[cfg(windows)]
+            assert_eq!(crate::keycache::load(&path).is_some(), stored);
+            #[cfg(not(windows))]
+            assert_eq!(state.error_message.is_some(), stored);
+            crate::keycache::clear(&path);
+        }
+    }
+
+    #[test]
+    fn a_key_stored_for_windows_unlock_falls_back_to_the_master_key() {
+        let mut state = AppState::unlocked_for_test();
+        state.save_clients();
+        assert_eq!(state.quick_unlock(), None);
+        #[cfg(not(windows))]
+        assert!(state.set_quick_unlock(Some(QuickUnlock::Account)).is_err());
+        assert_eq!(state.quick_unlock(), None);
+
+        // A key DPAPI cannot unwrap, such as one stored by another Windows user.
+        let stored = serde_json::json!({ crate::layout::vault_key(state.repository.path()): { "key": "AAAA" } });
+        std::fs::write(&state.wrapped_keys_path, stored.to_string()).unwrap();
+        assert_eq!(state.quick_unlock(), Some(QuickUnlock::Account));
+        state.lock();
+        state.unlock_quickly();
+        state.wait_for_unlock();
+        assert!(!state.is_unlocked());
+        assert!(state.error_message.take().unwrap().starts_with("Could not unlock with Windows"));
+        assert_eq!(state.quick_unlock(), Some(QuickUnlock::Account));
+        state.set_quick_unlock(None).unwrap();
+        assert_eq!(state.quick_unlock(), None);
+    }
+
+    #[test]
+    fn other_vaults_are_opened_created_and_restored_while_locked() {
+        let mut state = AppState::for_test();
+        state.unlock_form.master_key = "correct horse".to_string();
+        state.unlock_form.confirm = "correct horse".to_string();
+        state.unlock_and_wait();
+        let vault = state.repository.path().to_path_buf();
+        let backup = vault.with_extension("backup");
+        std::fs::copy(&vault, &backup).unwrap();
+        state.repository.add(crate::client::Client { name: "db01".to_string(), ..Default::default() });
+        state.save_clients();
+        state.lock();
+
+        let other = vault.with_extension("other");
+        state.unlock_form.vault_file = other.display().to_string();
+        assert!(state.run_vault_action(VaultAction::Open).is_err());
+        state.run_vault_action(VaultAction::Create).unwrap();
+        assert_eq!(state.repository.path(), other);
+        assert!(matches!(state.key_status, KeyStatus::NotSet));
+
+        state.unlock_form.vault_file = vault.display().to_string();
+        state.run_vault_action(VaultAction::Open).unwrap();
+        assert!(matches!(state.key_status, KeyStatus::Locked));
+        state.unlock_form.vault_file = backup.display().to_string();
+        state.run_vault_action(VaultAction::Restore).unwrap();
+        state.unlock_form.master_key = "correct horse".to_string();
+        state.unlock_and_wait();
+        assert!(state.is_unlocked());
+        assert!(state.repository.clients().is_empty());
+
+        let kept: Vec<_> = std::fs::read_dir(vault.parent().unwrap())
+            .unwrap()
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.file_name().unwrap().to_string_lossy().starts_with(&format!("{}.before-restore-", vault.file_name().unwrap().to_string_lossy())))
+            .collect();
+        assert_eq!(kept.len(), 1);
+        for path in kept.iter().chain([&vault, &backup]) {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn saves_are_backed_up_hourly_and_restored_from_the_menu() {
+        let mut state = AppState::for_test();
+        state.repository.backups = 3;
+        state.unlock_form.master_key = "correct horse".to_string();
+        state.unlock_form.confirm = "correct horse".to_string();
+        state.unlock_and_wait();
+        state.save_clients();
+        let vault = state.repository.path().to_path_buf();
+        state.repository.add(crate::client::Client { name: "db01".to_string(), ..Default::default() });
+        state.save_clients();
+        state.repository.add(crate::client::Client { name: "db02".to_string(), ..Default::default() });
+        state.save_clients();
+        let backups = storage::list_backups(&vault);
+        assert_eq!(backups.len(), 1);
+
+        state.unlock_form.vault_file = backups[0].0.display().to_string();
+        state.restore_backup().unwrap();
+        assert!(matches!(state.key_status, KeyStatus::Locked));
+        state.unlock_form.master_key = "correct horse".to_string();
+        state.unlock_and_wait();
+        assert!(state.repository.clients().is_empty());
+
+        let _ = std::fs::remove_dir_all(storage::backups_dir(&vault));
+        for entry in std::fs::read_dir(vault.parent().unwrap()).unwrap().filter_map(Result::ok) {
+            if entry.file_name().to_string_lossy().starts_with(&*vault.file_name().unwrap().to_string_lossy()) {
+                let _ = std::fs::remove_file(entry.path());
+            }
+        }
+    }
+}
@@ -0,0 +1,22 @@
+//! Launches the configured VNC viewer. TightVNC, RealVNC, TigerVNC and
+//! UltraVNC all accept `host::port`, where a single colon would mean a
+//! display number instead.
+
+use crate::client::Client;
+use crate::template::templated_command;
+use std::process::Command;
+
+/// The command connecting to `client`. A setting with placeholders is a full
+/// command line, otherwise it is the viewer program.
+pub fn command(setting: &str, client: &Client) -> Result<Command, String> {
+    if setting.contains('{') {
+        return templated_command(setting, client);
+    }
+    let (host, port) = client.endpoint();
+    let mut command = Command::new(setting);
+    match port {
+        Some(port) => command.arg(format!("{}::{}", host, port)),
+        None => command.arg(host),
+    };
+    Ok(command)
+}
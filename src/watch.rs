@@ -0,0 +1,80 @@
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+pub const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+pub const WATCH_EXTENSIONS: [&str; 3] = ["rdp", "csv", "rdg"];
+
+/// Files in the drop folder that still need importing.
+pub fn pending_files(folder: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(folder) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file() && has_watched_extension(path))
+        .collect();
+    files.sort();
+    files
+}
+
+/// What the drop folder held at the last scan, and what was imported from
+/// it while the app runs.
+#[derive(Default)]
+pub struct FolderWatch {
+    /// Size and modification time of each file at the last scan.
+    last_seen: HashMap<PathBuf, (u64, Option<SystemTime>)>,
+    /// SHA-256 of every file imported, so one that could not be moved away
+    /// afterwards is not imported again.
+    imported: HashSet<[u8; 32]>,
+}
+
+impl FolderWatch {
+    /// Files in `folder` ready to import, with their hash. A file is only
+    /// ready once its size and modification time were the same at two
+    /// scans in a row, so one still being copied in is left alone.
+    pub fn ready_files(&mut self, folder: &Path) -> Vec<(PathBuf, [u8; 32])> {
+        let mut seen = HashMap::new();
+        let mut ready = Vec::new();
+        for path in pending_files(folder) {
+            let Ok(metadata) = fs::metadata(&path) else {
+                continue;
+            };
+            let stamp = (metadata.len(), metadata.modified().ok());
+            if self.last_seen.get(&path) == Some(&stamp) {
+                if let Ok(data) = fs::read(&path) {
+                    let digest: [u8; 32] = Sha256::digest(&data).into();
+                    if !self.imported.contains(&digest) {
+                        ready.push((path.clone(), digest));
+                    }
+                }
+            }
+            seen.insert(path, stamp);
+        }
+        self.last_seen = seen;
+        ready
+    }
+
+    /// Records that the file with `digest` was imported, or tried to be.
+    pub fn mark_imported(&mut self, digest: [u8; 32]) {
+        self.imported.insert(digest);
+    }
+}
+
+fn has_watched_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| WATCH_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Moves a processed file into `subfolder` next to it so it is not picked up again.
+pub fn archive(path: &Path, subfolder: &str) -> Result<(), String> {
+    let parent = path.parent().ok_or("File has no parent folder")?;
+    let target_dir = parent.join(subfolder);
+    fs::create_dir_all(&target_dir).map_err(|e| e.to_string())?;
+    let file_name = path.file_name().ok_or("File has no name")?;
+    fs::rename(path, target_dir.join(file_name)).map_err(|e| e.to_string())
+}